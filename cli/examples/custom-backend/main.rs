@@ -23,13 +23,19 @@ use jj_cli::cli_util::CliRunner;
 use jj_cli::cli_util::CommandHelper;
 use jj_cli::command_error::CommandError;
 use jj_cli::ui::Ui;
+use jj_lib::api::BackendResult;
+use jj_lib::api::ChangeId;
+use jj_lib::api::CommitId;
+use jj_lib::api::RepoPath;
+use jj_lib::api::RepoPathBuf;
+use jj_lib::api::StoreFactories;
+use jj_lib::api::UserSettings;
+use jj_lib::api::Workspace;
+use jj_lib::api::WorkspaceInitError;
 use jj_lib::backend::Backend;
 use jj_lib::backend::BackendInitError;
 use jj_lib::backend::BackendLoadError;
-use jj_lib::backend::BackendResult;
-use jj_lib::backend::ChangeId;
 use jj_lib::backend::Commit;
-use jj_lib::backend::CommitId;
 use jj_lib::backend::Conflict;
 use jj_lib::backend::ConflictId;
 use jj_lib::backend::CopyHistory;
@@ -42,13 +48,7 @@ use jj_lib::backend::Tree;
 use jj_lib::backend::TreeId;
 use jj_lib::git_backend::GitBackend;
 use jj_lib::index::Index;
-use jj_lib::repo::StoreFactories;
-use jj_lib::repo_path::RepoPath;
-use jj_lib::repo_path::RepoPathBuf;
-use jj_lib::settings::UserSettings;
 use jj_lib::signing::Signer;
-use jj_lib::workspace::Workspace;
-use jj_lib::workspace::WorkspaceInitError;
 use tokio::io::AsyncRead;
 
 #[derive(clap::Parser, Clone, Debug)]
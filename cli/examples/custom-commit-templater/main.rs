@@ -22,16 +22,16 @@ use jj_cli::commit_templater::CommitTemplateLanguageExtension;
 use jj_cli::template_parser;
 use jj_cli::template_parser::TemplateParseError;
 use jj_cli::templater::TemplatePropertyExt as _;
-use jj_lib::backend::CommitId;
-use jj_lib::commit::Commit;
+use jj_lib::api::Commit;
+use jj_lib::api::CommitId;
+use jj_lib::api::ObjectId as _;
+use jj_lib::api::Repo;
+use jj_lib::api::RevsetExpression;
 use jj_lib::extensions_map::ExtensionsMap;
-use jj_lib::object_id::ObjectId as _;
-use jj_lib::repo::Repo;
 use jj_lib::revset::FunctionCallNode;
 use jj_lib::revset::LoweringContext;
 use jj_lib::revset::PartialSymbolResolver;
 use jj_lib::revset::RevsetDiagnostics;
-use jj_lib::revset::RevsetExpression;
 use jj_lib::revset::RevsetFilterExtension;
 use jj_lib::revset::RevsetFilterPredicate;
 use jj_lib::revset::RevsetParseError;
@@ -18,10 +18,10 @@ use jj_cli::operation_templater::OperationTemplateLanguageExtension;
 use jj_cli::template_parser;
 use jj_cli::template_parser::TemplateParseError;
 use jj_cli::templater::TemplatePropertyExt as _;
+use jj_lib::api::ObjectId as _;
+use jj_lib::api::Operation;
+use jj_lib::api::OperationId;
 use jj_lib::extensions_map::ExtensionsMap;
-use jj_lib::object_id::ObjectId as _;
-use jj_lib::op_store::OperationId;
-use jj_lib::operation::Operation;
 
 struct HexCounter;
 
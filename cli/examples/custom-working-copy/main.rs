@@ -22,19 +22,24 @@ use jj_cli::cli_util::CliRunner;
 use jj_cli::cli_util::CommandHelper;
 use jj_cli::command_error::CommandError;
 use jj_cli::ui::Ui;
+use jj_lib::api::Commit;
+use jj_lib::api::MergedTree;
+use jj_lib::api::MergedTreeId;
+use jj_lib::api::OperationId;
+use jj_lib::api::ReadonlyRepo;
+use jj_lib::api::RepoPathBuf;
+use jj_lib::api::Store;
+use jj_lib::api::UserSettings;
+use jj_lib::api::Workspace;
+use jj_lib::api::WorkspaceInitError;
+use jj_lib::api::WorkspaceName;
+use jj_lib::api::WorkspaceNameBuf;
 use jj_lib::backend::Backend;
-use jj_lib::backend::MergedTreeId;
-use jj_lib::commit::Commit;
 use jj_lib::git_backend::GitBackend;
 use jj_lib::local_working_copy::LocalWorkingCopy;
-use jj_lib::op_store::OperationId;
-use jj_lib::ref_name::WorkspaceName;
-use jj_lib::ref_name::WorkspaceNameBuf;
-use jj_lib::repo::ReadonlyRepo;
-use jj_lib::repo_path::RepoPathBuf;
-use jj_lib::settings::UserSettings;
+use jj_lib::matchers::Matcher;
 use jj_lib::signing::Signer;
-use jj_lib::store::Store;
+use jj_lib::working_copy::ChangedPathsOptions;
 use jj_lib::working_copy::CheckoutError;
 use jj_lib::working_copy::CheckoutOptions;
 use jj_lib::working_copy::CheckoutStats;
@@ -47,8 +52,6 @@ use jj_lib::working_copy::WorkingCopy;
 use jj_lib::working_copy::WorkingCopyFactory;
 use jj_lib::working_copy::WorkingCopyStateError;
 use jj_lib::workspace::WorkingCopyFactories;
-use jj_lib::workspace::Workspace;
-use jj_lib::workspace::WorkspaceInitError;
 
 #[derive(clap::Parser, Clone, Debug)]
 enum CustomCommand {
@@ -171,6 +174,15 @@ impl WorkingCopy for ConflictsWorkingCopy {
         self.inner.sparse_patterns()
     }
 
+    fn changed_paths_since(
+        &self,
+        base_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        options: &ChangedPathsOptions,
+    ) -> Result<Vec<RepoPathBuf>, SnapshotError> {
+        self.inner.changed_paths_since(base_tree, matcher, options)
+    }
+
     fn start_mutation(&self) -> Result<Box<dyn LockedWorkingCopy>, WorkingCopyStateError> {
         let inner = self.inner.start_mutation()?;
         Ok(Box::new(LockedConflictsWorkingCopy {
@@ -1,7 +1,12 @@
 use std::io;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::Once;
 
+use once_cell::sync::Lazy;
 use slab::Slab;
 use tracing::instrument;
 
@@ -10,6 +15,12 @@ static LIVE_GUARDS: Mutex<GuardTable> = Mutex::new(Slab::new());
 
 type GuardTable = Slab<Box<dyn FnOnce() + Send>>;
 
+/// Set by `SIGINT` while an [`InterruptScope`] is live
+static INTERRUPTED: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool::new(false)));
+
+/// Number of currently-live [`InterruptScope`]s
+static LIVE_INTERRUPT_SCOPES: AtomicUsize = AtomicUsize::new(0);
+
 /// Prepare to run [`CleanupGuard`]s on `SIGINT`/`SIGTERM`
 pub fn init() {
     // Safety: `` ensures at most one call
@@ -45,14 +56,43 @@ impl Drop for CleanupGuard {
     }
 }
 
+/// Returns the flag that is set when `SIGINT` is received while an
+/// [`InterruptScope`] is live.
+pub fn interrupt_flag() -> Arc<AtomicBool> {
+    INTERRUPTED.clone()
+}
+
+/// While live, `SIGINT` sets the [`interrupt_flag()`] instead of terminating
+/// the process.
+///
+/// This should only cover operations that check the flag regularly and can be
+/// stopped without leaving anything in an inconsistent state, such as revset
+/// evaluation.
+pub struct InterruptScope {
+    _private: (),
+}
+
+impl InterruptScope {
+    /// Makes `SIGINT` interrupt instead of terminate until the scope is
+    /// dropped.
+    pub fn enter() -> Self {
+        LIVE_INTERRUPT_SCOPES.fetch_add(1, Ordering::SeqCst);
+        Self { _private: () }
+    }
+}
+
+impl Drop for InterruptScope {
+    fn drop(&mut self) {
+        LIVE_INTERRUPT_SCOPES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
 #[cfg(unix)]
 mod platform {
     use std::os::unix::io::IntoRawFd as _;
     use std::os::unix::io::RawFd;
     use std::os::unix::net::UnixDatagram;
     use std::panic::AssertUnwindSafe;
-    use std::sync::atomic::AtomicBool;
-    use std::sync::atomic::Ordering;
     use std::thread;
 
     use libc::c_int;
@@ -69,9 +109,19 @@ mod platform {
         // into it
         thread::spawn(move || {
             let mut buf = [0];
-            let signal = match recv.recv(&mut buf) {
-                Ok(1) => c_int::from(buf[0]),
-                _ => unreachable!(),
+            let signal = loop {
+                let signal = match recv.recv(&mut buf) {
+                    Ok(1) => c_int::from(buf[0]),
+                    _ => unreachable!(),
+                };
+                if signal == SIGINT && LIVE_INTERRUPT_SCOPES.load(Ordering::SeqCst) > 0 {
+                    // Let the interruptible operation fail cleanly. The next
+                    // signal is handled as usual.
+                    INTERRUPTED.store(true, Ordering::SeqCst);
+                    SIGNALED.store(false, Ordering::Relaxed);
+                    continue;
+                }
+                break signal;
             };
             // We must hold the lock for the remainder of the process's lifetime to avoid a
             // race where a guard is created between `on_signal` and `raise`.
@@ -99,9 +149,11 @@ mod platform {
         }
     }
 
+    /// Whether a signal is being handled
+    static SIGNALED: AtomicBool = AtomicBool::new(false);
+
     unsafe extern "C" fn handler(signal: c_int) {
         // Treat the second signal as instantly fatal.
-        static SIGNALED: AtomicBool = AtomicBool::new(false);
         if SIGNALED.swap(true, Ordering::Relaxed) {
             libc::signal(signal, libc::SIG_DFL);
             libc::raise(signal);
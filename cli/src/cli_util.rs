@@ -24,12 +24,14 @@ use std::fmt::Debug;
 use std::io;
 use std::io::Write as _;
 use std::mem;
+use std::ops::ControlFlow;
 use std::path::Path;
 use std::path::PathBuf;
 use std::rc::Rc;
 use std::str;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use std::time::SystemTime;
 
 use bstr::ByteVec as _;
@@ -46,6 +48,7 @@ use clap::Command;
 use clap::FromArgMatches as _;
 use clap_complete::ArgValueCandidates;
 use clap_complete::ArgValueCompleter;
+use futures::StreamExt as _;
 use indexmap::IndexMap;
 use indexmap::IndexSet;
 use indoc::indoc;
@@ -64,13 +67,18 @@ use jj_lib::config::ConfigMigrationRule;
 use jj_lib::config::ConfigNamePathBuf;
 use jj_lib::config::ConfigSource;
 use jj_lib::config::StackedConfig;
+use jj_lib::conflicts::ConflictLabels;
 use jj_lib::conflicts::ConflictMarkerStyle;
+use jj_lib::copies::CopyRecords;
 use jj_lib::fileset;
 use jj_lib::fileset::FilesetDiagnostics;
 use jj_lib::fileset::FilesetExpression;
 use jj_lib::gitignore::GitIgnoreError;
 use jj_lib::gitignore::GitIgnoreFile;
 use jj_lib::id_prefix::IdPrefixContext;
+use jj_lib::id_prefix::PrefixCache;
+use jj_lib::id_prefix::PrefixCacheNotice;
+use jj_lib::matchers::EverythingMatcher;
 use jj_lib::matchers::Matcher;
 use jj_lib::merge::MergedTreeValue;
 use jj_lib::merged_tree::MergedTree;
@@ -86,6 +94,8 @@ use jj_lib::ref_name::RefName;
 use jj_lib::ref_name::RefNameBuf;
 use jj_lib::ref_name::WorkspaceName;
 use jj_lib::ref_name::WorkspaceNameBuf;
+use jj_lib::refs::RefMergeConflict;
+use jj_lib::refs::RefMergeConflictKind;
 use jj_lib::repo::merge_factories_map;
 use jj_lib::repo::CheckOutCommitError;
 use jj_lib::repo::EditCommitError;
@@ -100,6 +110,8 @@ use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::repo_path::RepoPathUiConverter;
 use jj_lib::repo_path::UiPathParseError;
 use jj_lib::revset;
+use jj_lib::revset::ExpressionNode;
+use jj_lib::revset::LoweringContext;
 use jj_lib::revset::ResolvedRevsetExpression;
 use jj_lib::revset::RevsetAliasesMap;
 use jj_lib::revset::RevsetDiagnostics;
@@ -110,10 +122,14 @@ use jj_lib::revset::RevsetFunction;
 use jj_lib::revset::RevsetIteratorExt as _;
 use jj_lib::revset::RevsetModifier;
 use jj_lib::revset::RevsetParseContext;
+use jj_lib::revset::RevsetParseError;
 use jj_lib::revset::RevsetWorkspaceContext;
 use jj_lib::revset::SymbolResolverExtension;
 use jj_lib::revset::UserRevsetExpression;
-use jj_lib::rewrite::restore_tree;
+use jj_lib::rewrite::restore_tree_with_copies;
+use jj_lib::rewrite_policy::RewriteOverride;
+use jj_lib::rewrite_policy::RewritePermission;
+use jj_lib::rewrite_policy::RewritePolicy;
 use jj_lib::settings::HumanByteSize;
 use jj_lib::settings::UserSettings;
 use jj_lib::str_util::StringPattern;
@@ -122,6 +138,7 @@ use jj_lib::view::View;
 use jj_lib::working_copy;
 use jj_lib::working_copy::CheckoutOptions;
 use jj_lib::working_copy::CheckoutStats;
+use jj_lib::working_copy::SnapshotError;
 use jj_lib::working_copy::SnapshotOptions;
 use jj_lib::working_copy::SnapshotStats;
 use jj_lib::working_copy::UntrackedReason;
@@ -132,11 +149,13 @@ use jj_lib::workspace::default_working_copy_factories;
 use jj_lib::workspace::get_working_copy_factory;
 use jj_lib::workspace::DefaultWorkspaceLoaderFactory;
 use jj_lib::workspace::LockedWorkspace;
+use jj_lib::workspace::UpdateStaleError;
 use jj_lib::workspace::WorkingCopyFactories;
 use jj_lib::workspace::Workspace;
 use jj_lib::workspace::WorkspaceLoadError;
 use jj_lib::workspace::WorkspaceLoader;
 use jj_lib::workspace::WorkspaceLoaderFactory;
+use pollster::FutureExt as _;
 use tracing::instrument;
 use tracing_chrome::ChromeLayerBuilder;
 use tracing_subscriber::prelude::*;
@@ -167,12 +186,15 @@ use crate::formatter::FormatRecorder;
 use crate::formatter::Formatter;
 use crate::formatter::PlainTextFormatter;
 use crate::merge_tools::DiffEditor;
+use crate::merge_tools::DriverTools;
 use crate::merge_tools::MergeEditor;
 use crate::merge_tools::MergeToolConfigError;
 use crate::operation_templater::OperationTemplateLanguage;
 use crate::operation_templater::OperationTemplateLanguageExtension;
 use crate::revset_util;
 use crate::revset_util::RevsetExpressionEvaluator;
+use crate::selection_plan::SelectionPlan;
+use crate::selection_plan::SelectionPlanArgs;
 use crate::template_builder;
 use crate::template_builder::TemplateLanguage;
 use crate::template_parser::TemplateAliasesMap;
@@ -299,10 +321,13 @@ struct CommandHelperData {
     revset_extensions: Arc<RevsetExtensions>,
     commit_template_extensions: Vec<Arc<dyn CommitTemplateLanguageExtension>>,
     operation_template_extensions: Vec<Arc<dyn OperationTemplateLanguageExtension>>,
+    rewrite_policies: Vec<Arc<dyn RewritePolicy>>,
     maybe_workspace_loader: Result<Box<dyn WorkspaceLoader>, CommandError>,
     store_factories: StoreFactories,
     working_copy_factories: WorkingCopyFactories,
     workspace_loader_factory: Box<dyn WorkspaceLoaderFactory>,
+    /// Cache of printed short ID prefixes, loaded with the first workspace.
+    prefix_cache: OnceCell<Option<Rc<PrefixCache>>>,
 }
 
 impl CommandHelper {
@@ -345,8 +370,9 @@ impl CommandHelper {
     /// Settings for the current command and workspace.
     ///
     /// This may be different from the settings for new workspace created by
-    /// e.g. `jj git init`. There may be conditional variables and repo config
-    /// `.jj/repo/config.toml` loaded for the cwd workspace.
+    /// e.g. `jj git init`. There may be conditional variables, repo config
+    /// `.jj/repo/config.toml`, and workspace config
+    /// `.jj/working_copy/config.toml` loaded for the cwd workspace.
     pub fn settings(&self) -> &UserSettings {
         &self.data.settings
     }
@@ -361,12 +387,61 @@ impl CommandHelper {
         let repo_path = workspace_root.join(".jj").join("repo");
         config_env.reset_repo_path(&repo_path);
         config_env.reload_repo_config(&mut raw_config)?;
+        config_env.reset_workspace_path(workspace_root);
+        config_env.reload_workspace_config(&mut raw_config)?;
         let mut config = config_env.resolve_config(&raw_config)?;
         // No migration messages here, which would usually be emitted before.
         jj_lib::config::migrate(&mut config, &self.data.config_migrations)?;
         Ok(self.data.settings.with_new_config(config)?)
     }
 
+    /// Loads the cache of printed short ID prefixes of the workspace if
+    /// `short-prefix-cache.enabled` is set.
+    fn load_prefix_cache(
+        &self,
+        workspace: &Workspace,
+    ) -> Result<Option<Rc<PrefixCache>>, CommandError> {
+        if let Some(prefix_cache) = self.data.prefix_cache.get() {
+            return Ok(prefix_cache.clone());
+        }
+        let settings = workspace.settings();
+        let prefix_cache = if settings.get_bool("short-prefix-cache.enabled")? {
+            let dir = workspace.workspace_root().join(".jj").join("working_copy");
+            let max_entries = settings.get("short-prefix-cache.max-entries")?;
+            let max_age_hours: u64 = settings.get("short-prefix-cache.max-age-hours")?;
+            let max_age = Duration::from_secs(max_age_hours.saturating_mul(3600));
+            Some(Rc::new(PrefixCache::load(&dir, max_entries, max_age)?))
+        } else {
+            None
+        };
+        Ok(self.data.prefix_cache.get_or_init(|| prefix_cache).clone())
+    }
+
+    /// Reports ambiguous prefixes resolved by the prefix cache, and saves the
+    /// recorded prefixes.
+    fn finish_prefix_cache(&self, ui: &Ui) -> Result<(), CommandError> {
+        let Some(Some(prefix_cache)) = self.data.prefix_cache.get() else {
+            return Ok(());
+        };
+        for notice in prefix_cache.take_notices() {
+            let (kind, prefix, id) = match notice {
+                PrefixCacheNotice::Commit { prefix_len, id } => {
+                    ("commit", format!("{id:.prefix_len$}"), id.to_string())
+                }
+                PrefixCacheNotice::Change { prefix_len, id } => {
+                    ("change", format!("{id:.prefix_len$}"), id.to_string())
+                }
+            };
+            writeln!(
+                ui.warning_default(),
+                "Ambiguous {kind} ID prefix `{prefix}` was resolved to previously printed \
+                 {kind} ID {id}"
+            )?;
+        }
+        prefix_cache.save()?;
+        Ok(())
+    }
+
     /// Loads text editor from the settings.
     pub fn text_editor(&self) -> Result<TextEditor, ConfigGetError> {
         TextEditor::from_settings(self.settings())
@@ -468,6 +543,7 @@ impl CommandHelper {
         let workspace = self.load_workspace()?;
         let op_head = self.resolve_operation(ui, workspace.repo_loader())?;
         let repo = workspace.repo_loader().load_at(&op_head)?;
+        migrate_index_incrementally(ui, self.settings(), &repo)?;
         let env = self.workspace_environment(ui, &workspace)?;
         revset_util::warn_unresolvable_trunk(ui, repo.as_ref(), &env.revset_parse_context())?;
         WorkspaceCommandHelper::new(ui, workspace, repo, env, self.is_at_head_operation())
@@ -546,7 +622,9 @@ impl CommandHelper {
                 let stale_wc_commit = repo.store().get_commit(wc_commit_id)?;
 
                 let mut workspace_command = self.workspace_helper_no_snapshot(ui)?;
-                let checkout_options = workspace_command.checkout_options();
+                let mut checkout_options = workspace_command.checkout_options();
+                let progress = crate::progress::checkout_progress(ui);
+                checkout_options.progress = progress.as_ref().map(|x| x as _);
 
                 let repo = workspace_command.repo().clone();
                 let (mut locked_ws, desired_wc_commit) =
@@ -564,7 +642,7 @@ impl CommandHelper {
                     }
                     WorkingCopyFreshness::WorkingCopyStale
                     | WorkingCopyFreshness::SiblingOperation => {
-                        let stats = update_stale_working_copy(
+                        let stats = check_out_fresh_wc_commit(
                             locked_ws,
                             repo.op_id().clone(),
                             &stale_wc_commit,
@@ -662,6 +740,7 @@ impl CommandHelper {
                             )?;
                         }
                     }
+                    print_ref_merge_conflicts(ui, tx.repo().ref_merge_conflicts())?;
                     Ok(tx
                         .write("reconcile divergent operations")?
                         .leave_unpublished()
@@ -786,7 +865,11 @@ pub struct WorkspaceCommandEnvironment {
     workspace_name: WorkspaceNameBuf,
     immutable_heads_expression: Rc<UserRevsetExpression>,
     short_prefixes_expression: Option<Rc<UserRevsetExpression>>,
+    prefix_cache: Option<Rc<PrefixCache>>,
     conflict_marker_style: ConflictMarkerStyle,
+    conflict_marker_labels: bool,
+    lazy_conflicts: bool,
+    prefetch_concurrency: usize,
 }
 
 impl WorkspaceCommandEnvironment {
@@ -808,7 +891,11 @@ impl WorkspaceCommandEnvironment {
             workspace_name: workspace.workspace_name().to_owned(),
             immutable_heads_expression: RevsetExpression::root(),
             short_prefixes_expression: None,
+            prefix_cache: command.load_prefix_cache(workspace)?,
             conflict_marker_style: settings.get("ui.conflict-marker-style")?,
+            conflict_marker_labels: settings.get_bool("ui.conflict-marker-labels")?,
+            lazy_conflicts: settings.get_bool("ui.lazy-conflicts")?,
+            prefetch_concurrency: settings.get("backend.prefetch-concurrency")?,
         };
         env.immutable_heads_expression = env.load_immutable_heads_expression(ui)?;
         env.short_prefixes_expression = env.load_short_prefixes_expression(ui)?;
@@ -848,11 +935,14 @@ impl WorkspaceCommandEnvironment {
     /// Creates fresh new context which manages cache of short commit/change ID
     /// prefixes. New context should be created per repo view (or operation.)
     pub fn new_id_prefix_context(&self) -> IdPrefixContext {
-        let context = IdPrefixContext::new(self.command.revset_extensions().clone());
-        match &self.short_prefixes_expression {
-            None => context,
-            Some(expression) => context.disambiguate_within(expression.clone()),
+        let mut context = IdPrefixContext::new(self.command.revset_extensions().clone());
+        if let Some(expression) = &self.short_prefixes_expression {
+            context = context.disambiguate_within(expression.clone());
+        }
+        if let Some(prefix_cache) = &self.prefix_cache {
+            context = context.with_prefix_cache(prefix_cache.clone());
         }
+        context
     }
 
     /// User-configured expression defining the immutable set.
@@ -872,6 +962,24 @@ impl WorkspaceCommandEnvironment {
         self.conflict_marker_style
     }
 
+    /// Whether to label the sides of conflicts materialized in the working
+    /// copy
+    pub fn conflict_marker_labels(&self) -> bool {
+        self.conflict_marker_labels
+    }
+
+    /// Whether to leave placeholders in conflicted files on checkout, and
+    /// write the conflict markers on demand
+    pub fn lazy_conflicts(&self) -> bool {
+        self.lazy_conflicts
+    }
+
+    /// Maximum number of concurrent requests made to prefetch file contents
+    /// for external diff tools
+    pub fn prefetch_concurrency(&self) -> usize {
+        self.prefetch_concurrency
+    }
+
     fn load_immutable_heads_expression(
         &self,
         ui: &Ui,
@@ -911,6 +1019,24 @@ impl WorkspaceCommandEnvironment {
         }
     }
 
+    /// Returns a policy which requires an override for rewriting the immutable
+    /// commits of the given repo.
+    fn immutable_commits_policy(
+        &self,
+        repo: &dyn Repo,
+    ) -> Result<ImmutableCommitsPolicy, CommandError> {
+        let id_prefix_context = IdPrefixContext::new(self.command.revset_extensions().clone());
+        let heads = RevsetExpressionEvaluator::new(
+            repo,
+            self.command.revset_extensions().clone(),
+            &id_prefix_context,
+            self.immutable_heads_expression().heads(),
+        )
+        .evaluate_to_commit_ids()?
+        .try_collect()?;
+        Ok(ImmutableCommitsPolicy { heads })
+    }
+
     /// Returns first immutable commit + lower and upper bounds on number of
     /// immutable commits.
     fn find_immutable_commit<'a>(
@@ -1011,6 +1137,33 @@ impl WorkspaceCommandEnvironment {
     }
 }
 
+/// Requires an override for rewriting ancestors of the immutable heads.
+#[derive(Debug)]
+struct ImmutableCommitsPolicy {
+    heads: Vec<CommitId>,
+}
+
+impl RewritePolicy for ImmutableCommitsPolicy {
+    fn check(
+        &self,
+        repo: &dyn Repo,
+        commit_id: &CommitId,
+    ) -> Result<RewritePermission, Box<dyn std::error::Error + Send + Sync>> {
+        let index = repo.index();
+        if self
+            .heads
+            .iter()
+            .any(|head_id| index.is_ancestor(commit_id, head_id))
+        {
+            Ok(RewritePermission::RequiresOverride(
+                "immutable commit".to_owned(),
+            ))
+        } else {
+            Ok(RewritePermission::Allowed)
+        }
+    }
+}
+
 /// Provides utilities for writing a command that works on a [`Workspace`]
 /// (which most commands do).
 pub struct WorkspaceCommandHelper {
@@ -1138,6 +1291,26 @@ impl WorkspaceCommandHelper {
         Ok(())
     }
 
+    /// Reloads the repo at the head operation if other processes have
+    /// committed operations since the repo was loaded, and snapshots the
+    /// working copy again. Returns true if the repo was reloaded.
+    ///
+    /// Commands that wait for user input before starting a transaction can
+    /// use this to build the transaction on top of the latest operation.
+    #[instrument(skip_all)]
+    pub fn maybe_reload_at_head(&mut self, ui: &Ui) -> Result<bool, CommandError> {
+        if !self.may_update_working_copy {
+            return Ok(false);
+        }
+        let repo = self.repo().reload_at_head()?;
+        if repo.op_id() == self.repo().op_id() {
+            return Ok(false);
+        }
+        self.user_repo = ReadonlyUserRepo::new(repo);
+        self.maybe_snapshot(ui)?;
+        Ok(true)
+    }
+
     /// Imports new HEAD from the colocated Git repo.
     ///
     /// If the Git HEAD has changed, this function checks out the new Git HEAD.
@@ -1190,7 +1363,7 @@ impl WorkspaceCommandHelper {
             }
         } else {
             // Unlikely, but the HEAD ref got deleted by git?
-            self.finish_transaction(ui, tx, "import git head")?;
+            self.finish_transaction(ui, tx, "import git head", &[])?;
         }
         Ok(())
     }
@@ -1223,7 +1396,7 @@ impl WorkspaceCommandHelper {
                 "Rebased {num_rebased} descendant commits off of commits rewritten from git"
             )?;
         }
-        self.finish_transaction(ui, tx, "import git refs")?;
+        self.finish_transaction(ui, tx, "import git refs", &[])?;
         writeln!(
             ui.status(),
             "Done importing changes from the underlying Git repo."
@@ -1251,10 +1424,37 @@ impl WorkspaceCommandHelper {
         &self.env
     }
 
-    pub fn checkout_options(&self) -> CheckoutOptions {
+    pub fn checkout_options(&self) -> CheckoutOptions<'static> {
         CheckoutOptions {
             conflict_marker_style: self.env.conflict_marker_style(),
+            conflict_labels: ConflictLabels::unlabeled(),
+            lazy_conflicts: self.env.lazy_conflicts(),
+            progress: None,
+        }
+    }
+
+    /// Writes the conflict markers of the conflicted files matching `matcher`
+    /// which were left as placeholders by a checkout with `ui.lazy-conflicts`.
+    pub fn materialize_conflicts(
+        &mut self,
+        matcher: &dyn Matcher,
+    ) -> Result<CheckoutStats, CommandError> {
+        let mut checkout_options = self.checkout_options();
+        if let Some(wc_commit_id) = self.get_wc_commit_id() {
+            if self.env.conflict_marker_labels() {
+                let wc_commit = self.repo().store().get_commit(wc_commit_id)?;
+                checkout_options.conflict_labels =
+                    parent_conflict_labels(self.repo().as_ref(), &wc_commit)?;
+            }
         }
+        let (mut locked_ws, _wc_commit) = self.start_working_copy_mutation()?;
+        let stats = locked_ws
+            .locked_wc()
+            .materialize_conflicts(matcher, &checkout_options)
+            .map_err(|err| internal_error_with_message("Failed to materialize conflicts", err))?;
+        let operation_id = locked_ws.locked_wc().old_operation_id().clone();
+        locked_ws.finish(operation_id)?;
+        Ok(stats)
     }
 
     pub fn unchecked_start_working_copy_mutation(
@@ -1469,6 +1669,7 @@ to the current parents may contain changes from multiple commits.
             self.repo().as_ref(),
             self.path_converter(),
             self.env.conflict_marker_style(),
+            self.env.prefetch_concurrency(),
             formats,
         )
     }
@@ -1479,7 +1680,7 @@ to the current parents may contain changes from multiple commits.
         args: &DiffFormatArgs,
     ) -> Result<DiffRenderer<'_>, CommandError> {
         let formats = diff_util::diff_formats_for(self.settings(), args)?;
-        Ok(self.diff_renderer(formats))
+        self.diff_renderer_with_driver_tools(formats, args)
     }
 
     /// Loads textual diff renderer from the settings and log-like command
@@ -1491,7 +1692,31 @@ to the current parents may contain changes from multiple commits.
         patch: bool,
     ) -> Result<Option<DiffRenderer<'_>>, CommandError> {
         let formats = diff_util::diff_formats_for_log(self.settings(), args, patch)?;
-        Ok((!formats.is_empty()).then(|| self.diff_renderer(formats)))
+        if formats.is_empty() {
+            return Ok(None);
+        }
+        self.diff_renderer_with_driver_tools(formats, args)
+            .map(Some)
+    }
+
+    /// Creates textual diff renderer which selects external diff tools by the
+    /// `diff` attribute of files, unless a tool is specified explicitly.
+    fn diff_renderer_with_driver_tools(
+        &self,
+        formats: Vec<DiffFormat>,
+        args: &DiffFormatArgs,
+    ) -> Result<DiffRenderer<'_>, CommandError> {
+        let use_drivers = args.tool.is_none()
+            && formats
+                .iter()
+                .any(|format| matches!(format, DiffFormat::Tool(_)));
+        let renderer = self.diff_renderer(formats);
+        if use_drivers {
+            let driver_tools = DriverTools::diff_from_settings(self.settings())?;
+            Ok(renderer.with_driver_tools(driver_tools))
+        } else {
+            Ok(renderer)
+        }
     }
 
     /// Loads diff editor from the settings.
@@ -1531,12 +1756,42 @@ to the current parents may contain changes from multiple commits.
         force_interactive: bool,
     ) -> Result<DiffSelector, CommandError> {
         if tool_name.is_some() || force_interactive {
-            Ok(DiffSelector::Interactive(self.diff_editor(ui, tool_name)?))
+            Ok(DiffSelector::Interactive {
+                editor: self.diff_editor(ui, tool_name)?,
+                emit_plan_path: None,
+            })
         } else {
             Ok(DiffSelector::NonInteractive)
         }
     }
 
+    /// Like [`Self::diff_selector()`], but selects changes by the plan file if
+    /// specified.
+    ///
+    /// If the plan is to be emitted, interactive session is implied.
+    pub fn diff_selector_with_plan(
+        &self,
+        ui: &Ui,
+        tool_name: Option<&str>,
+        force_interactive: bool,
+        plan_args: &SelectionPlanArgs,
+    ) -> Result<DiffSelector, CommandError> {
+        if let Some(path) = &plan_args.selection_plan {
+            return Ok(DiffSelector::Plan {
+                plan: SelectionPlan::load(path)?,
+                text_editor: self.text_editor()?,
+            });
+        }
+        let force_interactive = force_interactive || plan_args.emit_selection_plan.is_some();
+        match self.diff_selector(ui, tool_name, force_interactive)? {
+            DiffSelector::Interactive { editor, .. } => Ok(DiffSelector::Interactive {
+                editor,
+                emit_plan_path: plan_args.emit_selection_plan.clone(),
+            }),
+            selector => Ok(selector),
+        }
+    }
+
     /// Loads 3-way merge editor from the settings.
     ///
     /// If the `tool_name` isn't specified, the default editor will be returned.
@@ -1808,6 +2063,15 @@ to the current parents may contain changes from multiple commits.
         self.commit_summary_template().format(commit, formatter)
     }
 
+    /// Returns true if the `commit_id` can't be rewritten because it's
+    /// immutable.
+    pub fn is_immutable(&self, commit_id: &CommitId) -> Result<bool, CommandError> {
+        let found = self
+            .env
+            .find_immutable_commit(self.repo().as_ref(), [commit_id])?;
+        Ok(found.is_some())
+    }
+
     pub fn check_rewritable<'a>(
         &self,
         commits: impl IntoIterator<Item = &'a CommitId>,
@@ -1845,6 +2109,57 @@ to the current parents may contain changes from multiple commits.
         Err(error)
     }
 
+    /// Checks that `commit_ids` are still rewritable after merging the
+    /// operations that were published while `tx` was open.
+    ///
+    /// The commits were checked against the base repo of `tx` when the command
+    /// started, but a concurrent operation may have made them immutable since
+    /// then. Publishing the transaction would then rewrite immutable commits
+    /// once the operations are merged.
+    fn check_rewritable_at_op_heads(
+        &self,
+        tx: &Transaction,
+        commit_ids: &[CommitId],
+    ) -> Result<(), CommandError> {
+        if commit_ids.is_empty() {
+            return Ok(());
+        }
+        let base_repo = tx.base_repo();
+        let repo_loader = base_repo.loader();
+        let concurrent_op_ids = repo_loader
+            .op_heads_store()
+            .get_op_heads()?
+            .into_iter()
+            .filter(|id| id != base_repo.op_id())
+            .collect_vec();
+        if concurrent_op_ids.is_empty() {
+            return Ok(());
+        }
+        let mut merged_tx = base_repo.start_transaction();
+        for op_id in &concurrent_op_ids {
+            let op = repo_loader.load_operation(op_id)?;
+            merged_tx.merge_operation(op)?;
+        }
+        let Some((commit_id, _, _)) = self
+            .env
+            .find_immutable_commit(merged_tx.repo(), commit_ids)?
+        else {
+            return Ok(());
+        };
+        let mut error = user_error(format!(
+            "Commit {commit_id:.12} became immutable in a concurrent operation"
+        ));
+        error.add_hint(format!(
+            "The operation was not published. Concurrent operations: {}",
+            concurrent_op_ids
+                .iter()
+                .map(short_operation_hash)
+                .join(", ")
+        ));
+        error.add_hint("Run the command again to see the current state of the repo.");
+        Err(error)
+    }
+
     #[instrument(skip_all)]
     fn snapshot_working_copy(
         &mut self,
@@ -1870,6 +2185,7 @@ to the current parents may contain changes from multiple commits.
         let options = self
             .snapshot_options_with_start_tracking_matcher(&auto_tracking_matcher)
             .map_err(snapshot_command_error)?;
+        let checkout_options = self.checkout_options();
 
         // Compare working-copy tree and operation with repo's, and reload as needed.
         let mut locked_ws = self
@@ -1933,10 +2249,29 @@ See https://jj-vcs.github.io/jj/latest/working-copy/#stale-working-copy \
             let mut options = options;
             let progress = crate::progress::snapshot_progress(ui);
             options.progress = progress.as_ref().map(|x| x as _);
-            locked_ws
-                .locked_wc()
-                .snapshot(&options)
-                .map_err(snapshot_command_error)?
+            match locked_ws.locked_wc().snapshot(&options) {
+                Err(SnapshotError::InterruptedCheckout) => {
+                    // The files can't be snapshotted until the interrupted
+                    // checkout of the working-copy commit is finished.
+                    writeln!(ui.status(), "Finishing interrupted working copy update")
+                        .map_err(snapshot_command_error)?;
+                    let mut checkout_options = checkout_options;
+                    let progress = crate::progress::checkout_progress(ui);
+                    checkout_options.progress = progress.as_ref().map(|x| x as _);
+                    locked_ws
+                        .locked_wc()
+                        .check_out(&wc_commit, &checkout_options)
+                        .map_err(|err| {
+                            snapshot_command_error(internal_error_with_message(
+                                "Failed to finish interrupted working copy update",
+                                err,
+                            ))
+                        })?;
+                    locked_ws.locked_wc().snapshot(&options)
+                }
+                result => result,
+            }
+            .map_err(snapshot_command_error)?
         };
         if new_tree_id != *wc_commit.tree_id() {
             let mut tx =
@@ -1998,7 +2333,13 @@ See https://jj-vcs.github.io/jj/latest/working-copy/#stale-working-copy \
         new_commit: &Commit,
     ) -> Result<(), CommandError> {
         assert!(self.may_update_working_copy);
-        let checkout_options = self.checkout_options();
+        let mut checkout_options = self.checkout_options();
+        if self.env.conflict_marker_labels() {
+            checkout_options.conflict_labels =
+                parent_conflict_labels(self.repo().as_ref(), new_commit)?;
+        }
+        let progress = crate::progress::checkout_progress(ui);
+        checkout_options.progress = progress.as_ref().map(|x| x as _);
         let stats = update_working_copy(
             &self.user_repo.repo,
             &mut self.workspace,
@@ -2048,7 +2389,18 @@ See https://jj-vcs.github.io/jj/latest/working-copy/#stale-working-copy \
     }
 
     pub fn start_transaction(&mut self) -> WorkspaceCommandTransaction {
-        let tx = start_repo_transaction(self.repo(), self.env.command.string_args());
+        let mut tx = start_repo_transaction(self.repo(), self.env.command.string_args());
+        for policy in &self.env.command.data.rewrite_policies {
+            tx.repo_mut().add_rewrite_policy(policy.clone());
+        }
+        if self.env.command.global_args().ignore_immutable {
+            tx.repo_mut()
+                .set_rewrite_override(Some(RewriteOverride::new()));
+        } else if let Ok(policy) = self.env.immutable_commits_policy(self.repo().as_ref()) {
+            // An invalid immutable_heads() expression is reported by
+            // check_rewritable(), so it's fine not to install the policy here.
+            tx.repo_mut().add_rewrite_policy(Arc::new(policy));
+        }
         let id_prefix_context = mem::take(&mut self.user_repo.id_prefix_context);
         WorkspaceCommandTransaction {
             helper: self,
@@ -2062,11 +2414,13 @@ See https://jj-vcs.github.io/jj/latest/working-copy/#stale-working-copy \
         ui: &Ui,
         mut tx: Transaction,
         description: impl Into<String>,
+        rewritten_ids: &[CommitId],
     ) -> Result<(), CommandError> {
         if !tx.repo().has_changes() {
             writeln!(ui.status(), "Nothing changed.")?;
             return Ok(());
         }
+        self.check_rewritable_at_op_heads(&tx, rewritten_ids)?;
         let num_rebased = tx.repo_mut().rebase_descendants()?;
         if num_rebased > 0 {
             writeln!(ui.status(), "Rebased {num_rebased} descendant commits")?;
@@ -2247,6 +2601,7 @@ See https://jj-vcs.github.io/jj/latest/working-copy/#stale-working-copy \
                 fmt.as_mut(),
                 &self.commit_summary_template(),
                 new_conflicts_by_change_id.values().flatten().copied(),
+                ui.term_width(),
             )?;
         }
 
@@ -2477,7 +2832,24 @@ impl WorkspaceCommandTransaction<'_> {
     }
 
     pub fn finish(self, ui: &Ui, description: impl Into<String>) -> Result<(), CommandError> {
-        self.helper.finish_transaction(ui, self.tx, description)
+        self.helper
+            .finish_transaction(ui, self.tx, description, &[])
+    }
+
+    /// Like [`Self::finish`], but also re-checks that the `rewritten` commits
+    /// are still mutable if other operations were published concurrently.
+    ///
+    /// The transaction is not published if any of the commits became
+    /// immutable in the merged view of the concurrent operations.
+    pub fn finish_rewriting<'a>(
+        self,
+        ui: &Ui,
+        description: impl Into<String>,
+        rewritten: impl IntoIterator<Item = &'a CommitId>,
+    ) -> Result<(), CommandError> {
+        let rewritten_ids = rewritten.into_iter().cloned().collect_vec();
+        self.helper
+            .finish_transaction(ui, self.tx, description, &rewritten_ids)
     }
 
     /// Returns the wrapped [`Transaction`] for circumstances where
@@ -2488,6 +2860,12 @@ impl WorkspaceCommandTransaction<'_> {
         self.tx
     }
 
+    /// Returns the inner `Transaction` for library operations that need it.
+    pub fn inner_mut(&mut self) -> &mut Transaction {
+        self.id_prefix_context.take(); // invalidate
+        &mut self.tx
+    }
+
     /// Moves each bookmark in `bookmarks` from an old commit it's associated
     /// with (configured by `get_advanceable_bookmarks`) to the `move_to`
     /// commit. If the bookmark is conflicted before the update, it will
@@ -2579,44 +2957,44 @@ pub fn start_repo_transaction(repo: &Arc<ReadonlyRepo>, string_args: &[String])
     tx
 }
 
-fn update_stale_working_copy(
-    mut locked_ws: LockedWorkspace,
+/// Checks out `new_commit` over the stale working copy, whose changes must have
+/// been snapshotted already. Unlike
+/// [`jj_lib::workspace::update_stale_working_copy()`], this doesn't merge the
+/// local changes into `new_commit`.
+fn check_out_fresh_wc_commit(
+    locked_ws: LockedWorkspace,
     op_id: OperationId,
     stale_commit: &Commit,
     new_commit: &Commit,
     options: &CheckoutOptions,
 ) -> Result<CheckoutStats, CommandError> {
-    // The same check as start_working_copy_mutation(), but with the stale
-    // working-copy commit.
-    if stale_commit.tree_id() != locked_ws.locked_wc().old_tree_id() {
-        return Err(user_error("Concurrent working copy operation. Try again."));
-    }
-    let stats = locked_ws
-        .locked_wc()
-        .check_out(new_commit, options)
-        .map_err(|err| {
-            internal_error_with_message(
+    locked_ws
+        .update_stale(op_id, stale_commit, new_commit, options)
+        .map_err(|err| match err {
+            UpdateStaleError::Checkout(err) => internal_error_with_message(
                 format!("Failed to check out commit {}", new_commit.id().hex()),
                 err,
-            )
-        })?;
-    locked_ws.finish(op_id)?;
-
-    Ok(stats)
+            ),
+            err => err.into(),
+        })
 }
 
 /// Prints a list of commits by the given summary template. The list may be
 /// elided. Use this to show created, rewritten, or abandoned commits.
+///
+/// Each summary line is elided to `max_width`.
 pub fn print_updated_commits<'a>(
     formatter: &mut dyn Formatter,
     template: &TemplateRenderer<Commit>,
     commits: impl IntoIterator<Item = &'a Commit>,
+    max_width: usize,
 ) -> io::Result<()> {
     let mut commits = commits.into_iter().fuse();
     for commit in commits.by_ref().take(10) {
-        write!(formatter, "  ")?;
-        template.format(commit, formatter)?;
-        writeln!(formatter)?;
+        write_elided_line(formatter, max_width, |formatter| {
+            write!(formatter, "  ")?;
+            template.format(commit, formatter)
+        })?;
     }
     if commits.next().is_some() {
         writeln!(formatter, "  ...")?;
@@ -2624,6 +3002,50 @@ pub fn print_updated_commits<'a>(
     Ok(())
 }
 
+/// Writes a line rendered by `write_content` followed by a newline. The end of
+/// the line is elided if it's wider than `max_width`.
+///
+/// The rendered content should be a single-line text.
+pub fn write_elided_line(
+    formatter: &mut dyn Formatter,
+    max_width: usize,
+    write_content: impl FnOnce(&mut dyn Formatter) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut recorder = FormatRecorder::new();
+    write_content(&mut recorder)?;
+    let mut ellipsis = FormatRecorder::new();
+    write!(ellipsis, "...")?;
+    text_util::write_truncated_end(formatter, &recorder, &ellipsis, max_width)?;
+    writeln!(formatter)
+}
+
+/// Warns about bookmarks and tags which became conflicted while reconciling
+/// divergent operations.
+fn print_ref_merge_conflicts(ui: &Ui, conflicts: &[RefMergeConflict]) -> Result<(), CommandError> {
+    let Some(summary) = RefMergeConflict::summarize(conflicts) else {
+        return Ok(());
+    };
+    let names = conflicts
+        .iter()
+        .map(|conflict| conflict.name.as_symbol().to_string())
+        .join(", ");
+    writeln!(
+        ui.warning_default(),
+        "Reconciliation created {summary}: {names}"
+    )?;
+    if conflicts
+        .iter()
+        .any(|conflict| conflict.kind == RefMergeConflictKind::LocalBookmark)
+    {
+        writeln!(
+            ui.hint_default(),
+            "Use `jj bookmark list --conflicted` to see them, and `jj bookmark set` to resolve \
+             them."
+        )?;
+    }
+    Ok(())
+}
+
 #[instrument(skip_all)]
 pub fn print_conflicted_paths(
     conflicts: Vec<(RepoPathBuf, BackendResult<MergedTreeValue>)>,
@@ -2761,6 +3183,46 @@ pub fn print_untracked_files(
     Ok(())
 }
 
+/// Migrates parts of the index stored in an older format, up to
+/// `index.migration-steps-per-command` parts per command.
+fn migrate_index_incrementally(
+    ui: &Ui,
+    settings: &UserSettings,
+    repo: &ReadonlyRepo,
+) -> Result<(), CommandError> {
+    let max_steps: usize = settings.get("index.migration-steps-per-command")?;
+    let index_store = repo.index_store();
+    if max_steps == 0
+        || !index_store
+            .needs_rebuild(repo.operation(), repo.store())
+            .map_err(internal_error)?
+    {
+        return Ok(());
+    }
+    let mut num_steps = 0;
+    let progress = index_store
+        .rebuild_incrementally(repo.operation(), repo.store(), &mut |_| {
+            num_steps += 1;
+            if num_steps < max_steps {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        })
+        .map_err(internal_error)?;
+    if progress.remaining > 0 {
+        writeln!(
+            ui.status(),
+            "Migrated {} parts of the index to the new format, {} remaining",
+            progress.migrated,
+            progress.remaining
+        )?;
+    } else {
+        writeln!(ui.status(), "Migrated the index to the new format")?;
+    }
+    Ok(())
+}
+
 pub fn print_snapshot_stats(
     ui: &Ui,
     stats: &SnapshotStats,
@@ -2850,6 +3312,36 @@ pub fn print_unmatched_explicit_paths<'a>(
     Ok(())
 }
 
+/// Labels the sides of the conflicts in a merge commit with its parents.
+///
+/// A parent is labeled by its bookmark name, or by the first line of its
+/// description if it has no bookmarks.
+fn parent_conflict_labels(
+    repo: &dyn Repo,
+    commit: &Commit,
+) -> Result<ConflictLabels, CommandError> {
+    if commit.parent_ids().len() < 2 {
+        return Ok(ConflictLabels::unlabeled());
+    }
+    let labels: Vec<String> = commit
+        .parents()
+        .map_ok(|parent| {
+            if let Some((name, _)) = repo.view().local_bookmarks_for_commit(parent.id()).next() {
+                name.as_symbol().to_string()
+            } else if let Some(line) = parent
+                .description()
+                .lines()
+                .find(|line| !line.trim().is_empty())
+            {
+                line.to_owned()
+            } else {
+                short_commit_hash(parent.id())
+            }
+        })
+        .try_collect()?;
+    Ok(ConflictLabels::from_sides(labels))
+}
+
 pub fn update_working_copy(
     repo: &Arc<ReadonlyRepo>,
     workspace: &mut Workspace,
@@ -2987,16 +3479,40 @@ pub fn short_operation_hash(operation_id: &OperationId) -> String {
 #[derive(Clone, Debug)]
 pub enum DiffSelector {
     NonInteractive,
-    Interactive(DiffEditor),
+    Interactive {
+        editor: DiffEditor,
+        /// Path to record the selection as a plan file.
+        emit_plan_path: Option<PathBuf>,
+    },
+    Plan {
+        plan: SelectionPlan,
+        /// Editor to edit the hunks marked in the plan.
+        text_editor: TextEditor,
+    },
 }
 
 impl DiffSelector {
     pub fn is_interactive(&self) -> bool {
-        matches!(self, DiffSelector::Interactive(_))
+        matches!(self, DiffSelector::Interactive { .. })
+    }
+
+    /// Makes the interactive editor select conflicted files as a whole. See
+    /// [`DiffEditor::with_whole_file_conflicts()`].
+    pub fn with_whole_file_conflicts(self) -> Self {
+        match self {
+            DiffSelector::Interactive {
+                editor,
+                emit_plan_path,
+            } => DiffSelector::Interactive {
+                editor: editor.with_whole_file_conflicts(),
+                emit_plan_path,
+            },
+            selector @ (DiffSelector::NonInteractive | DiffSelector::Plan { .. }) => selector,
+        }
     }
 
     /// Restores diffs from the `right_tree` to the `left_tree` by using an
-    /// interactive editor if enabled.
+    /// interactive editor or a selection plan if enabled.
     ///
     /// Only files matching the `matcher` will be copied to the new tree.
     pub fn select(
@@ -3006,15 +3522,107 @@ impl DiffSelector {
         matcher: &dyn Matcher,
         format_instructions: impl FnOnce() -> String,
     ) -> Result<MergedTreeId, CommandError> {
-        let selected_tree_id = restore_tree(right_tree, left_tree, matcher)?;
+        let copy_records = CopyRecords::default();
+        let (_, edited_tree_id) = self.select_tree(
+            left_tree,
+            right_tree,
+            matcher,
+            &copy_records,
+            format_instructions,
+        )?;
+        Ok(edited_tree_id)
+    }
+
+    /// Like [`Self::select()`], but pairs up the renamed files in
+    /// `copy_records` so they're selected as a whole.
+    ///
+    /// The `copy_records` should map paths in the `right_tree` to paths in the
+    /// `left_tree`. Since the selected changes are expected to be a part of
+    /// the diff, a warning is printed if the editor changed other files.
+    pub fn select_with_copies(
+        &self,
+        ui: &Ui,
+        left_tree: &MergedTree,
+        right_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        copy_records: &CopyRecords,
+        format_instructions: impl FnOnce() -> String,
+    ) -> Result<MergedTreeId, CommandError> {
+        let (diff_tree, edited_tree_id) = self.select_tree(
+            left_tree,
+            right_tree,
+            matcher,
+            copy_records,
+            format_instructions,
+        )?;
+        if edited_tree_id == diff_tree.id() {
+            return Ok(edited_tree_id);
+        }
+        // The edited tree isn't necessarily a subset of the diff, which is
+        // fine, but changes to other files are likely unintended.
+        let edited_tree = left_tree.store().get_root_tree(&edited_tree_id)?;
+        let changed_paths: Vec<RepoPathBuf> = left_tree
+            .diff_stream(&edited_tree, &EverythingMatcher)
+            .map(|entry| entry.path)
+            .collect()
+            .block_on();
+        let mut outside_paths = vec![];
+        for path in changed_paths {
+            if left_tree.path_value(&path)? == diff_tree.path_value(&path)? {
+                outside_paths.push(path);
+            }
+        }
+        if !outside_paths.is_empty() {
+            writeln!(
+                ui.warning_default(),
+                "The diff editor changed files outside of the diff:"
+            )?;
+            for path in &outside_paths {
+                writeln!(
+                    ui.warning_no_heading(),
+                    "  {}",
+                    path.as_internal_file_string()
+                )?;
+            }
+        }
+        Ok(edited_tree_id)
+    }
+
+    /// Selects changes, returning the tree of the diff to select from and the
+    /// selected tree.
+    fn select_tree(
+        &self,
+        left_tree: &MergedTree,
+        right_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        copy_records: &CopyRecords,
+        format_instructions: impl FnOnce() -> String,
+    ) -> Result<(MergedTree, MergedTreeId), CommandError> {
+        let selected_tree_id =
+            restore_tree_with_copies(right_tree, left_tree, matcher, copy_records)?;
+        let store = right_tree.store();
+        let selected_tree = store.get_root_tree(&selected_tree_id)?;
         match self {
-            DiffSelector::NonInteractive => Ok(selected_tree_id),
-            DiffSelector::Interactive(editor) => {
+            DiffSelector::NonInteractive => Ok((selected_tree, selected_tree_id)),
+            DiffSelector::Interactive {
+                editor,
+                emit_plan_path,
+            } => {
                 // edit_diff_external() is designed to edit the right tree,
                 // whereas we want to update the left tree. Unmatched paths
                 // shouldn't be based off the right tree.
-                let right_tree = right_tree.store().get_root_tree(&selected_tree_id)?;
-                Ok(editor.edit(left_tree, &right_tree, matcher, format_instructions)?)
+                let edited_tree_id =
+                    editor.edit(left_tree, &selected_tree, matcher, format_instructions)?;
+                if let Some(path) = emit_plan_path {
+                    let edited_tree = store.get_root_tree(&edited_tree_id)?;
+                    SelectionPlan::from_selection(left_tree, &selected_tree, &edited_tree)?
+                        .save(path)?;
+                }
+                Ok((selected_tree, edited_tree_id))
+            }
+            DiffSelector::Plan { plan, text_editor } => {
+                let edited_tree_id = plan.apply(left_tree, &selected_tree, text_editor)?;
+                Ok((selected_tree, edited_tree_id))
             }
         }
     }
@@ -3317,6 +3925,187 @@ impl EarlyArgs {
     }
 }
 
+/// Maximum number of characters in an operation description given by
+/// `--op-description`.
+const MAX_OP_DESCRIPTION_LEN: usize = 1000;
+
+/// Argument to override the description of the operation created by a
+/// command.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct OpDescriptionArgs {
+    /// Describe the operation with this text in the operation log
+    ///
+    /// By default, the operation is described by what the command did. The
+    /// command line is recorded in the operation's `args` tag either way.
+    #[arg(long, value_name = "TEXT", value_parser = parse_op_description)]
+    pub op_description: Option<String>,
+}
+
+impl OpDescriptionArgs {
+    /// Returns the description given by the user, or `default` if there's
+    /// none.
+    pub fn description_or(&self, default: String) -> String {
+        self.op_description.clone().unwrap_or(default)
+    }
+}
+
+/// Arguments to create a bookmark pointing to the revisions a command is about
+/// to rewrite.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct CheckpointArgs {
+    /// Create a bookmark with this name pointing to the original revisions
+    ///
+    /// The bookmark is created in the same operation and keeps pointing to
+    /// the revisions as they were before the command rewrote them, so they
+    /// stay visible and can be restored later. If several revisions are
+    /// rewritten, the bookmark points to the heads of them, and is conflicted
+    /// if there's more than one head.
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_parser = revset_util::parse_bookmark_name,
+    )]
+    pub checkpoint: Option<RefNameBuf>,
+    /// Allow `--checkpoint` to move an existing bookmark
+    #[arg(long, requires = "checkpoint")]
+    pub checkpoint_force: bool,
+}
+
+impl CheckpointArgs {
+    /// Returns an error if the checkpoint bookmark already exists in `view`
+    /// and isn't allowed to be moved.
+    pub fn check(&self, view: &View) -> Result<(), CommandError> {
+        let Some(name) = &self.checkpoint else {
+            return Ok(());
+        };
+        if !self.checkpoint_force && view.get_local_bookmark(name).is_present() {
+            return Err(user_error_with_hint(
+                format!("Bookmark already exists: {name}", name = name.as_symbol()),
+                "Use `--checkpoint-force` to move it.",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Points the checkpoint bookmark to the heads of `commit_ids`.
+    ///
+    /// This should be called after the commits have been rewritten in `tx`.
+    /// Otherwise the bookmark would be moved to the rewritten commits along
+    /// with the other bookmarks.
+    pub fn set_bookmark(
+        &self,
+        ui: &Ui,
+        tx: &mut WorkspaceCommandTransaction,
+        commit_ids: &[CommitId],
+    ) -> Result<(), CommandError> {
+        let Some(name) = &self.checkpoint else {
+            return Ok(());
+        };
+        self.check(tx.base_repo().view())?;
+        let heads: Vec<CommitId> = RevsetExpression::commits(commit_ids.to_vec())
+            .heads()
+            .evaluate(tx.base_repo().as_ref())?
+            .iter()
+            .try_collect()?;
+        tx.repo_mut()
+            .set_local_bookmark_target(name, RefTarget::from_legacy_form([], heads.clone()))?;
+        if let Some(mut formatter) = ui.status_formatter() {
+            if let [head] = &*heads {
+                let commit = tx.base_repo().store().get_commit(head)?;
+                write!(
+                    formatter,
+                    "Created checkpoint bookmark {name} pointing to ",
+                    name = name.as_symbol()
+                )?;
+                tx.base_workspace_helper()
+                    .write_commit_summary(formatter.as_mut(), &commit)?;
+                writeln!(formatter)?;
+            } else {
+                writeln!(
+                    formatter,
+                    "Created conflicted checkpoint bookmark {name} pointing to {n} revisions",
+                    name = name.as_symbol(),
+                    n = heads.len()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How `--restore-descendants` treats reparented merge commits whose content is
+/// no longer the merge of their new parents.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum RestoreDescendantsMode {
+    /// Preserve the content of such merge commits, and warn about them
+    #[default]
+    Warn,
+    /// Fail if the content of a merge commit would no longer be the merge of
+    /// its parents
+    Strict,
+}
+
+impl RestoreDescendantsMode {
+    /// Warns about `diverged_merges`, or returns an error in strict mode.
+    ///
+    /// The merge commits should be computed by
+    /// [`jj_lib::rewrite::find_diverged_merges()`] after reparenting
+    /// descendants in `tx`.
+    pub fn report_diverged_merges(
+        self,
+        ui: &Ui,
+        tx: &WorkspaceCommandTransaction,
+        diverged_merges: &[Commit],
+    ) -> Result<(), CommandError> {
+        if diverged_merges.is_empty() {
+            return Ok(());
+        }
+        match self {
+            Self::Warn => {
+                writeln!(
+                    ui.warning_default(),
+                    "The content of {} merge commits is no longer the merge of their parents:",
+                    diverged_merges.len()
+                )?;
+                print_updated_commits(
+                    ui.stderr_formatter().as_mut(),
+                    &tx.commit_summary_template(),
+                    diverged_merges,
+                    ui.term_width(),
+                )?;
+                writeln!(
+                    ui.hint_default(),
+                    "Their diffs now include the changes that were made in their rewritten parents. \
+                     Use `--restore-descendants=strict` to fail instead."
+                )?;
+                Ok(())
+            }
+            Self::Strict => Err(user_error_with_hint(
+                format!(
+                    "The content of merge commits would no longer be the merge of their parents: {}",
+                    diverged_merges
+                        .iter()
+                        .map(|commit| short_commit_hash(commit.id()))
+                        .join(", ")
+                ),
+                "Run without `--restore-descendants` to rebase them instead.",
+            )),
+        }
+    }
+}
+
+fn parse_op_description(text: &str) -> Result<String, String> {
+    if text.trim().is_empty() {
+        Err("Operation description must not be empty".to_owned())
+    } else if text.chars().count() > MAX_OP_DESCRIPTION_LEN {
+        Err(format!(
+            "Operation description must not be longer than {MAX_OP_DESCRIPTION_LEN} characters"
+        ))
+    } else {
+        Ok(text.to_owned())
+    }
+}
+
 /// Wrapper around revset expression argument.
 ///
 /// An empty string is rejected early by the CLI value parser, but it's still
@@ -3672,6 +4461,7 @@ pub struct CliRunner<'a> {
     revset_extensions: RevsetExtensions,
     commit_template_extensions: Vec<Arc<dyn CommitTemplateLanguageExtension>>,
     operation_template_extensions: Vec<Arc<dyn OperationTemplateLanguageExtension>>,
+    rewrite_policies: Vec<Arc<dyn RewritePolicy>>,
     dispatch_fn: CliDispatchFn<'a>,
     dispatch_hook_fns: Vec<CliDispatchHookFn<'a>>,
     process_global_args_fns: Vec<ProcessGlobalArgsFn<'a>>,
@@ -3703,6 +4493,7 @@ impl<'a> CliRunner<'a> {
             revset_extensions: Default::default(),
             commit_template_extensions: vec![],
             operation_template_extensions: vec![],
+            rewrite_policies: vec![],
             dispatch_fn: Box::new(crate::commands::run_command),
             dispatch_hook_fns: vec![],
             process_global_args_fns: vec![],
@@ -3783,6 +4574,23 @@ impl<'a> CliRunner<'a> {
         self
     }
 
+    /// Registers a revset function that may capture state. See
+    /// [`RevsetExtensions::add_function()`] for details.
+    pub fn add_revset_function(
+        mut self,
+        name: impl Into<String>,
+        arity: usize,
+        resolver: impl Fn(
+                &mut RevsetDiagnostics,
+                &[ExpressionNode],
+                &LoweringContext,
+            ) -> Result<Rc<UserRevsetExpression>, RevsetParseError>
+            + 'static,
+    ) -> Self {
+        self.revset_extensions.add_function(name, arity, resolver);
+        self
+    }
+
     pub fn add_commit_template_extension(
         mut self,
         commit_template_extension: Box<dyn CommitTemplateLanguageExtension>,
@@ -3801,6 +4609,16 @@ impl<'a> CliRunner<'a> {
         self
     }
 
+    /// Adds a policy to be consulted before commits are rewritten in any
+    /// transaction started by a command.
+    ///
+    /// Unlike the immutable commits, commits which the policy forbids to
+    /// rewrite can't be rewritten with `--ignore-immutable`.
+    pub fn add_rewrite_policy(mut self, policy: Arc<dyn RewritePolicy>) -> Self {
+        self.rewrite_policies.push(policy);
+        self
+    }
+
     /// Add a hook that gets called when it's time to run the command. It is
     /// the hook's responsibility to call the given inner dispatch function to
     /// run the command.
@@ -3877,6 +4695,8 @@ impl<'a> CliRunner<'a> {
         if let Ok(loader) = &maybe_cwd_workspace_loader {
             config_env.reset_repo_path(loader.repo_path());
             config_env.reload_repo_config(&mut raw_config)?;
+            config_env.reset_workspace_path(loader.workspace_root());
+            config_env.reload_workspace_config(&mut raw_config)?;
         }
         let mut config = config_env.resolve_config(&raw_config)?;
         migrate_config(&mut config)?;
@@ -3927,6 +4747,8 @@ impl<'a> CliRunner<'a> {
                 .map_err(|err| map_workspace_load_error(err, Some(path)))?;
             config_env.reset_repo_path(loader.repo_path());
             config_env.reload_repo_config(&mut raw_config)?;
+            config_env.reset_workspace_path(loader.workspace_root());
+            config_env.reload_workspace_config(&mut raw_config)?;
             Ok(loader)
         } else {
             maybe_cwd_workspace_loader
@@ -3944,6 +4766,7 @@ impl<'a> CliRunner<'a> {
                 ConfigSource::EnvBase | ConfigSource::EnvOverrides => "environment-provided",
                 ConfigSource::User => "user-level",
                 ConfigSource::Repo => "repo-level",
+                ConfigSource::Workspace => "workspace-level",
                 ConfigSource::CommandArg => "CLI-provided",
             };
             writeln!(
@@ -3970,10 +4793,12 @@ impl<'a> CliRunner<'a> {
             revset_extensions: self.revset_extensions.into(),
             commit_template_extensions: self.commit_template_extensions,
             operation_template_extensions: self.operation_template_extensions,
+            rewrite_policies: self.rewrite_policies,
             maybe_workspace_loader,
             store_factories: self.store_factories,
             working_copy_factories: self.working_copy_factories,
             workspace_loader_factory: self.workspace_loader_factory,
+            prefix_cache: OnceCell::new(),
         };
         let command_helper = CommandHelper {
             data: Rc::new(command_helper_data),
@@ -3986,7 +4811,9 @@ impl<'a> CliRunner<'a> {
                 })
             },
         );
-        (dispatch_fn)(ui, &command_helper)
+        let result = (dispatch_fn)(ui, &command_helper);
+        let prefix_cache_result = command_helper.finish_prefix_cache(ui);
+        result.and(prefix_cache_result)
     }
 
     #[must_use]
@@ -39,10 +39,13 @@ use jj_lib::op_heads_store::OpHeadsStoreError;
 use jj_lib::op_store::OpStoreError;
 use jj_lib::op_walk::OpsetEvaluationError;
 use jj_lib::op_walk::OpsetResolutionError;
+use jj_lib::op_walk::UndoOperationError;
 use jj_lib::repo::CheckOutCommitError;
 use jj_lib::repo::EditCommitError;
+use jj_lib::repo::RefUpdateError;
 use jj_lib::repo::RepoLoaderError;
 use jj_lib::repo::RewriteRootCommit;
+use jj_lib::repo_path::FsPathParseError;
 use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::repo_path::UiPathParseError;
 use jj_lib::revset;
@@ -58,6 +61,7 @@ use jj_lib::working_copy::RecoverWorkspaceError;
 use jj_lib::working_copy::ResetError;
 use jj_lib::working_copy::SnapshotError;
 use jj_lib::working_copy::WorkingCopyStateError;
+use jj_lib::workspace::UpdateStaleError;
 use jj_lib::workspace::WorkspaceInitError;
 use thiserror::Error;
 
@@ -310,6 +314,12 @@ impl From<CheckOutCommitError> for CommandError {
     }
 }
 
+impl From<RefUpdateError> for CommandError {
+    fn from(err: RefUpdateError) -> Self {
+        user_error(err)
+    }
+}
+
 impl From<RenameWorkspaceError> for CommandError {
     fn from(err: RenameWorkspaceError) -> Self {
         user_error_with_message("Failed to rename a workspace", err)
@@ -405,6 +415,17 @@ impl From<RepoLoaderError> for CommandError {
     }
 }
 
+impl From<UndoOperationError> for CommandError {
+    fn from(err: UndoOperationError) -> Self {
+        match err {
+            UndoOperationError::RootOperation | UndoOperationError::MergeOperation => {
+                user_error(err)
+            }
+            UndoOperationError::RepoLoader(err) => err.into(),
+        }
+    }
+}
+
 impl From<ResetError> for CommandError {
     fn from(err: ResetError) -> Self {
         internal_error_with_message("Failed to reset the working copy", err)
@@ -413,7 +434,11 @@ impl From<ResetError> for CommandError {
 
 impl From<TransactionCommitError> for CommandError {
     fn from(err: TransactionCommitError) -> Self {
-        internal_error(err)
+        match err {
+            TransactionCommitError::RewriteDenied(err) => user_error(err),
+            TransactionCommitError::HookVeto(err) => user_error(err),
+            _ => internal_error(err),
+        }
     }
 }
 
@@ -440,6 +465,7 @@ impl From<DiffRenderError> for CommandError {
             DiffRenderError::Backend(err) => err.into(),
             DiffRenderError::AccessDenied { .. } => user_error(err),
             DiffRenderError::InvalidRepoPath(_) => user_error(err),
+            DiffRenderError::GitAttributes(_) => user_error(err),
             DiffRenderError::Io(err) => err.into(),
         }
     }
@@ -640,6 +666,25 @@ impl From<RecoverWorkspaceError> for CommandError {
     }
 }
 
+impl From<UpdateStaleError> for CommandError {
+    fn from(err: UpdateStaleError) -> Self {
+        match err {
+            err @ (UpdateStaleError::ConcurrentWorkingCopyOperation
+            | UpdateStaleError::WorkspaceMissingWorkingCopy(_)) => user_error(err),
+            err @ UpdateStaleError::SiblingOperation { .. } => internal_error(err),
+            UpdateStaleError::Backend(err) => err.into(),
+            UpdateStaleError::OpStore(err) => err.into(),
+            UpdateStaleError::WorkingCopyState(err) => err.into(),
+            UpdateStaleError::Snapshot(err) => err.into(),
+            UpdateStaleError::Checkout(err) => {
+                internal_error_with_message("Failed to check out the working copy", err)
+            }
+            UpdateStaleError::RewriteRootCommit(err) => err.into(),
+            UpdateStaleError::TransactionCommit(err) => err.into(),
+        }
+    }
+}
+
 impl From<RevsetParseError> for CommandError {
     fn from(err: RevsetParseError) -> Self {
         let hint = revset_parse_error_hint(&err);
@@ -680,7 +725,10 @@ impl From<TemplateParseError> for CommandError {
 
 impl From<UiPathParseError> for CommandError {
     fn from(err: UiPathParseError) -> Self {
-        user_error(err)
+        let hint = ui_path_parse_error_hint(&err);
+        let mut cmd_err = user_error(err);
+        cmd_err.extend_hints(hint);
+        cmd_err
     }
 }
 
@@ -716,6 +764,7 @@ impl From<AbsorbError> for CommandError {
         match err {
             AbsorbError::Backend(err) => err.into(),
             AbsorbError::RevsetEvaluation(err) => err.into(),
+            AbsorbError::StalePlan { .. } => internal_error(err),
         }
     }
 }
@@ -788,14 +837,25 @@ fn file_pattern_parse_error_hint(err: &FilePatternParseError) -> Option<String>
             "See https://jj-vcs.github.io/jj/latest/filesets/#file-patterns or `jj help -k \
              filesets` for valid prefixes.",
         )),
+        FilePatternParseError::UiPath(e) => ui_path_parse_error_hint(e),
+        FilePatternParseError::RelativePath(_) => None,
+        FilePatternParseError::GlobPattern(_) => None,
+    }
+}
+
+fn ui_path_parse_error_hint(err: &UiPathParseError) -> Option<String> {
+    match err {
         // Suggest root:"<path>" if input can be parsed as repo-relative path
-        FilePatternParseError::UiPath(UiPathParseError::Fs(e)) => {
-            RepoPathBuf::from_relative_path(&e.input).ok().map(|path| {
+        UiPathParseError::Fs(FsPathParseError { input, .. })
+        | UiPathParseError::EscapesWorkspace { input, .. } => {
+            RepoPathBuf::from_relative_path(input).ok().map(|path| {
                 format!(r#"Consider using root:{path:?} to specify repo-relative path"#)
             })
         }
-        FilePatternParseError::RelativePath(_) => None,
-        FilePatternParseError::GlobPattern(_) => None,
+        UiPathParseError::DriveMismatch { .. } | UiPathParseError::NonUtf8 { .. } => None,
+        UiPathParseError::SymlinkLoop { .. } => {
+            Some("Check the symlinks in the path and in the current directory.".to_owned())
+        }
     }
 }
 
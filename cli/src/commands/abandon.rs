@@ -14,25 +14,36 @@
 
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fs;
 use std::io::Write as _;
+use std::path::PathBuf;
+use std::rc::Rc;
 
 use clap_complete::ArgValueCompleter;
 use itertools::Itertools as _;
 use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
 use jj_lib::commit::CommitIteratorExt as _;
 use jj_lib::object_id::ObjectId as _;
+use jj_lib::ref_name::RefNameBuf;
 use jj_lib::refs::diff_named_ref_targets;
 use jj_lib::repo::Repo as _;
+use jj_lib::revset::UserRevsetExpression;
+use jj_lib::rewrite::find_diverged_merges;
 use jj_lib::rewrite::RewriteRefsOptions;
 use tracing::instrument;
 
-#[cfg(feature = "git")]
 use crate::cli_util::has_tracked_remote_bookmarks;
 use crate::cli_util::print_updated_commits;
+use crate::cli_util::CheckpointArgs;
 use crate::cli_util::CommandHelper;
+use crate::cli_util::OpDescriptionArgs;
+use crate::cli_util::RestoreDescendantsMode;
 use crate::cli_util::RevisionArg;
+use crate::command_error::user_error_with_message;
 use crate::command_error::CommandError;
 use crate::complete;
+use crate::text_util;
 use crate::ui::Ui;
 
 /// Abandon a revision
@@ -67,8 +78,61 @@ pub(crate) struct AbandonArgs {
     #[arg(long)]
     retain_bookmarks: bool,
     /// Do not modify the content of the children of the abandoned commits
+    ///
+    /// If only some parents of a merge descendant were rewritten, its
+    /// preserved content may no longer be the merge of its parents. Such merge
+    /// commits are reported, or the command fails with
+    /// `--restore-descendants=strict`.
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn"
+    )]
+    restore_descendants: Option<RestoreDescendantsMode>,
+    /// Do not modify the content of working-copy commits that are children of
+    /// the abandoned commits
+    ///
+    /// The working-copy commits absorb the changes of their abandoned parents,
+    /// so the files on disk are left as they are. Other descendants are
+    /// rebased as usual.
     #[arg(long)]
-    restore_descendants: bool,
+    keep_working_copy_content: bool,
+    /// Also abandon descendants that are only reachable through the given
+    /// revisions
+    ///
+    /// Descendants that also have an ancestor outside of the abandoned subtree
+    /// (such as a merge with another parent) are rebased instead.
+    #[arg(long)]
+    subtree: bool,
+    /// Write a JSON file describing the abandoned revisions to this path
+    ///
+    /// The file lists the commit id, change id, parent change ids, and
+    /// description of each abandoned revision, along with the id of the
+    /// operation that abandoned them. It can be used to recover the revisions
+    /// later. The file is only written if the revisions were abandoned.
+    #[arg(long, value_name = "PATH", value_hint = clap::ValueHint::FilePath)]
+    write_recovery: Option<PathBuf>,
+    #[command(flatten)]
+    checkpoint: CheckpointArgs,
+    #[command(flatten)]
+    op_description: OpDescriptionArgs,
+}
+
+/// Contents of the file written by `--write-recovery`.
+#[derive(Debug, serde::Serialize)]
+struct RecoveryRecord {
+    operation_id: String,
+    commits: Vec<AbandonedCommitRecord>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct AbandonedCommitRecord {
+    commit_id: String,
+    change_id: String,
+    parent_change_ids: Vec<String>,
+    description: String,
 }
 
 #[instrument(skip_all)]
@@ -81,26 +145,40 @@ pub(crate) fn cmd_abandon(
         writeln!(ui.warning_default(), "--summary is no longer supported.")?;
     }
     let mut workspace_command = command.workspace_helper(ui)?;
-    let to_abandon: Vec<_> = if !args.revisions_pos.is_empty() || !args.revisions_opt.is_empty() {
-        workspace_command
-            .parse_union_revsets(ui, &[&*args.revisions_pos, &*args.revisions_opt].concat())?
-    } else {
-        workspace_command.parse_revset(ui, &RevisionArg::AT)?
+    let mut to_abandon_evaluator =
+        if !args.revisions_pos.is_empty() || !args.revisions_opt.is_empty() {
+            workspace_command
+                .parse_union_revsets(ui, &[&*args.revisions_pos, &*args.revisions_opt].concat())?
+        } else {
+            workspace_command.parse_revset(ui, &RevisionArg::AT)?
+        };
+    if args.subtree {
+        let expression = subtree_expression(to_abandon_evaluator.expression());
+        to_abandon_evaluator = workspace_command.attach_revset_evaluator(expression);
     }
-    .evaluate_to_commits()?
-    .try_collect()?;
+    let to_abandon: Vec<_> = to_abandon_evaluator.evaluate_to_commits()?.try_collect()?;
     if to_abandon.is_empty() {
         writeln!(ui.status(), "No revisions to abandon.")?;
         return Ok(());
     }
     let to_abandon_set: HashSet<&CommitId> = to_abandon.iter().ids().collect();
     workspace_command.check_rewritable(to_abandon_set.iter().copied())?;
+    args.checkpoint.check(workspace_command.repo().view())?;
 
     let mut tx = workspace_command.start_transaction();
     let options = RewriteRefsOptions {
         delete_abandoned_bookmarks: !args.retain_bookmarks,
+        ..Default::default()
+    };
+    let wc_commit_ids: HashSet<CommitId> = if args.keep_working_copy_content {
+        tx.repo().view().wc_commit_ids().values().cloned().collect()
+    } else {
+        HashSet::new()
     };
     let mut num_rebased = 0;
+    let mut rebased_commits = vec![];
+    let mut newly_conflicted = vec![];
+    let mut reparented_commits = vec![];
     tx.repo_mut().transform_descendants_with_options(
         to_abandon_set.iter().copied().cloned().collect(),
         &HashMap::new(),
@@ -108,16 +186,42 @@ pub(crate) fn cmd_abandon(
         |rewriter| {
             if to_abandon_set.contains(rewriter.old_commit().id()) {
                 rewriter.abandon();
-            } else if args.restore_descendants {
-                rewriter.reparent().write()?;
+            } else if args.restore_descendants.is_some()
+                || (wc_commit_ids.contains(rewriter.old_commit().id())
+                    && rewriter
+                        .old_commit()
+                        .parent_ids()
+                        .iter()
+                        .any(|id| to_abandon_set.contains(id)))
+            {
+                let new_commit = rewriter.reparent().write()?;
+                if args.subtree {
+                    rebased_commits.push(new_commit.clone());
+                }
+                if args.restore_descendants.is_some() {
+                    reparented_commits.push(new_commit);
+                }
                 num_rebased += 1;
             } else {
-                rewriter.rebase()?.write()?;
+                let old_commit = rewriter.old_commit().clone();
+                let new_commit = rewriter.rebase()?.write()?;
+                if args.subtree {
+                    rebased_commits.push(new_commit.clone());
+                }
+                // Checking the new commit first is cheap since conflicted trees
+                // can usually be told from the tree id.
+                if new_commit.has_conflict()? && !old_commit.has_conflict()? {
+                    newly_conflicted.push(new_commit);
+                }
                 num_rebased += 1;
             }
             Ok(())
         },
     )?;
+    if let Some(mode) = args.restore_descendants {
+        let diverged_merges = find_diverged_merges(tx.repo(), &reparented_commits)?;
+        mode.report_diverged_merges(ui, &tx, &diverged_merges)?;
+    }
 
     let deleted_bookmarks = diff_named_ref_targets(
         tx.base_repo().view().local_bookmarks(),
@@ -133,16 +237,19 @@ pub(crate) fn cmd_abandon(
             formatter.as_mut(),
             &tx.base_workspace_helper().commit_summary_template(),
             &to_abandon,
+            ui.term_width(),
         )?;
         if !deleted_bookmarks.is_empty() {
+            let prefix = "Deleted bookmarks: ";
+            let max_name_width = ui.term_width().saturating_sub(prefix.len());
             writeln!(
                 formatter,
-                "Deleted bookmarks: {}",
-                deleted_bookmarks.iter().map(|n| n.as_symbol()).join(", ")
+                "{prefix}{}",
+                format_bookmark_names(&deleted_bookmarks, max_name_width)
             )?;
         }
         if num_rebased > 0 {
-            if args.restore_descendants {
+            if args.restore_descendants.is_some() {
                 writeln!(
                     formatter,
                     "Rebased {num_rebased} descendant commits (while preserving their content) \
@@ -154,8 +261,35 @@ pub(crate) fn cmd_abandon(
                     "Rebased {num_rebased} descendant commits onto parents of abandoned commits",
                 )?;
             }
+            if !rebased_commits.is_empty() {
+                print_updated_commits(
+                    formatter.as_mut(),
+                    &tx.commit_summary_template(),
+                    &rebased_commits,
+                    ui.term_width(),
+                )?;
+            }
         }
     }
+    args.checkpoint
+        .set_bookmark(ui, &mut tx, &to_abandon.iter().ids().cloned().collect_vec())?;
+    if !newly_conflicted.is_empty() {
+        // Reported as a warning so it isn't suppressed by --quiet
+        writeln!(
+            ui.warning_default(),
+            "{} descendant commits now have conflicts",
+            newly_conflicted.len()
+        )?;
+        // Commit ids are used because change ids may be divergent
+        let revset = newly_conflicted
+            .iter()
+            .map(|commit| commit.id().hex())
+            .join(" | ");
+        writeln!(
+            ui.hint_default(),
+            "To list them, run `jj log -r '{revset}'`."
+        )?;
+    }
     let transaction_description = if to_abandon.len() == 1 {
         format!("abandon commit {}", to_abandon[0].id().hex())
     } else {
@@ -165,15 +299,46 @@ pub(crate) fn cmd_abandon(
             to_abandon.len() - 1
         )
     };
-    tx.finish(ui, transaction_description)?;
+    let abandoned_records = if args.write_recovery.is_some() {
+        to_abandon
+            .iter()
+            .map(abandoned_commit_record)
+            .try_collect()?
+    } else {
+        vec![]
+    };
+    tx.finish_rewriting(
+        ui,
+        args.op_description.description_or(transaction_description),
+        to_abandon.iter().ids(),
+    )?;
+
+    if let Some(path) = &args.write_recovery {
+        let record = RecoveryRecord {
+            operation_id: workspace_command.repo().op_id().hex(),
+            commits: abandoned_records,
+        };
+        let message = || format!("Failed to write recovery file {}", path.display());
+        let text = serde_json::to_string_pretty(&record)
+            .map_err(|err| user_error_with_message(message(), err))?;
+        fs::write(path, text + "\n").map_err(|err| user_error_with_message(message(), err))?;
+    }
 
-    #[cfg(feature = "git")]
     if jj_lib::git::get_git_backend(workspace_command.repo().store()).is_ok() {
         let view = workspace_command.repo().view();
-        if deleted_bookmarks
+        let tracked_deleted_bookmarks = deleted_bookmarks
             .iter()
-            .any(|name| has_tracked_remote_bookmarks(view, name))
-        {
+            .filter(|name| has_tracked_remote_bookmarks(view, name))
+            .collect_vec();
+        if !tracked_deleted_bookmarks.is_empty() {
+            writeln!(
+                ui.warning_default(),
+                "Deleted bookmarks still exist on tracked remotes: {}",
+                tracked_deleted_bookmarks
+                    .iter()
+                    .map(|n| n.as_symbol())
+                    .join(", ")
+            )?;
             writeln!(
                 ui.hint_default(),
                 "Deleted bookmarks can be pushed by name or all at once with `jj git push \
@@ -183,3 +348,43 @@ pub(crate) fn cmd_abandon(
     }
     Ok(())
 }
+
+fn abandoned_commit_record(commit: &Commit) -> Result<AbandonedCommitRecord, CommandError> {
+    let parent_change_ids = commit
+        .parents()
+        .map_ok(|parent| parent.change_id().reverse_hex())
+        .try_collect()?;
+    Ok(AbandonedCommitRecord {
+        commit_id: commit.id().hex(),
+        change_id: commit.change_id().reverse_hex(),
+        parent_change_ids,
+        description: commit.description().to_owned(),
+    })
+}
+
+/// Returns the given `roots` plus their descendants that aren't reachable from
+/// any commit outside of the subtree rooted at `roots`.
+fn subtree_expression(roots: &Rc<UserRevsetExpression>) -> Rc<UserRevsetExpression> {
+    let subtree = roots.descendants();
+    let non_roots = subtree.minus(roots);
+    // Commits outside the subtree that have children in the subtree, other
+    // than the parents of the roots themselves.
+    let other_parents = non_roots.parents().minus(&subtree);
+    // Only the merges into the subtree and their descendants are reachable
+    // from outside. The other parents may be ancestors of the roots.
+    let merges = non_roots.intersection(&other_parents.children());
+    roots.union(&subtree.minus(&merges.descendants()))
+}
+
+/// Formats bookmark names as a comma-separated list. Names wider than
+/// `max_width` are elided in the middle so that each of them fits in a line.
+fn format_bookmark_names(names: &[RefNameBuf], max_width: usize) -> String {
+    names
+        .iter()
+        .map(|name| {
+            let symbol = name.as_symbol().to_string();
+            let (elided, _) = text_util::elide_middle(&symbol, "...", max_width);
+            elided.into_owned()
+        })
+        .join(", ")
+}
@@ -13,8 +13,7 @@
 // limitations under the License.
 
 use clap_complete::ArgValueCompleter;
-use jj_lib::absorb::absorb_hunks;
-use jj_lib::absorb::split_hunks_to_trees;
+use jj_lib::absorb;
 use jj_lib::absorb::AbsorbSource;
 use jj_lib::matchers::EverythingMatcher;
 use pollster::FutureExt as _;
@@ -83,18 +82,28 @@ pub(crate) fn cmd_absorb(
 
     let repo = workspace_command.repo().as_ref();
     let source = AbsorbSource::from_commit(repo, source_commit)?;
-    let selected_trees = split_hunks_to_trees(repo, &source, &destinations, &matcher).block_on()?;
+    let plan = absorb::plan(repo, &source, &destinations, &matcher).block_on()?;
 
     let path_converter = workspace_command.path_converter();
-    for (path, reason) in selected_trees.skipped_paths {
-        let ui_path = path_converter.format_file_path(&path);
+    for (path, reason) in &plan.skipped_paths {
+        let ui_path = path_converter.format_file_path(path);
         writeln!(ui.warning_default(), "Skipping {ui_path}: {reason}")?;
     }
 
-    workspace_command.check_rewritable(selected_trees.target_commits.keys())?;
+    // Reject immutable destinations before rewriting anything.
+    let affected = plan.affected_commits(|commit_id| workspace_command.is_immutable(commit_id))?;
+    workspace_command
+        .check_rewritable(affected.immutable.iter().map(|(commit_id, _)| commit_id))
+        .map_err(|mut err| {
+            err.add_hint(format!(
+                "{} hunks would be absorbed into immutable commits.",
+                affected.num_immutable_changes()
+            ));
+            err
+        })?;
 
     let mut tx = workspace_command.start_transaction();
-    let stats = absorb_hunks(tx.repo_mut(), &source, selected_trees.target_commits)?;
+    let stats = absorb::apply(tx.repo_mut(), &plan).block_on()?;
 
     if let Some(mut formatter) = ui.status_formatter() {
         if !stats.rewritten_destinations.is_empty() {
@@ -107,6 +116,7 @@ pub(crate) fn cmd_absorb(
                 formatter.as_mut(),
                 &tx.commit_summary_template(),
                 stats.rewritten_destinations.iter().rev(),
+                ui.term_width(),
             )?;
         }
         if stats.num_rebased > 0 {
@@ -92,7 +92,7 @@ pub fn cmd_bookmark_create(
     let mut tx = workspace_command.start_transaction();
     for name in bookmark_names {
         tx.repo_mut()
-            .set_local_bookmark_target(name, RefTarget::normal(target_commit.id().clone()));
+            .set_local_bookmark_target(name, RefTarget::normal(target_commit.id().clone()))?;
     }
 
     if let Some(mut formatter) = ui.status_formatter() {
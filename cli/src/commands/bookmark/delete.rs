@@ -61,7 +61,7 @@ pub fn cmd_bookmark_delete(
     let mut tx = workspace_command.start_transaction();
     for (name, _) in &matched_bookmarks {
         tx.repo_mut()
-            .set_local_bookmark_target(name, RefTarget::absent());
+            .set_local_bookmark_target(name, RefTarget::absent())?;
     }
     writeln!(
         ui.status(),
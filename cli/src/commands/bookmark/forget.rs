@@ -70,7 +70,7 @@ pub fn cmd_bookmark_forget(
     let mut forgotten_remote: usize = 0;
     for (name, bookmark_target) in &matched_bookmarks {
         tx.repo_mut()
-            .set_local_bookmark_target(name, RefTarget::absent());
+            .set_local_bookmark_target(name, RefTarget::absent())?;
         for (remote, _) in &bookmark_target.remote_refs {
             let symbol = name.to_remote_symbol(remote);
             // If `--include-remotes` is specified, we forget the corresponding remote
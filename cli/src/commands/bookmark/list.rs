@@ -269,7 +269,6 @@ pub fn cmd_bookmark_list(
         .try_for_each(|commit_ref| template.format(commit_ref, formatter.as_mut()))?;
     drop(formatter);
 
-    #[cfg(feature = "git")]
     if jj_lib::git::get_git_backend(repo.store()).is_ok() {
         // Print only one of these hints. It's not important to mention unexported
         // bookmarks, but user might wonder why deleted bookmarks are still listed.
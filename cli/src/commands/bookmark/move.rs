@@ -163,7 +163,7 @@ pub fn cmd_bookmark_move(
     let mut tx = workspace_command.start_transaction();
     for (name, _) in &matched_bookmarks {
         tx.repo_mut()
-            .set_local_bookmark_target(name, RefTarget::normal(target_commit.id().clone()));
+            .set_local_bookmark_target(name, RefTarget::normal(target_commit.id().clone()))?;
     }
 
     if let Some(mut formatter) = ui.status_formatter() {
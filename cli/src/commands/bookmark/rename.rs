@@ -67,9 +67,9 @@ pub fn cmd_bookmark_rename(
 
     let mut tx = workspace_command.start_transaction();
     tx.repo_mut()
-        .set_local_bookmark_target(new_bookmark, ref_target);
+        .set_local_bookmark_target(new_bookmark, ref_target)?;
     tx.repo_mut()
-        .set_local_bookmark_target(old_bookmark, RefTarget::absent());
+        .set_local_bookmark_target(old_bookmark, RefTarget::absent())?;
     tx.finish(
         ui,
         format!(
@@ -102,7 +102,7 @@ pub fn cmd_bookmark_set(
         tx.repo_mut().set_local_bookmark_target(
             bookmark_name,
             RefTarget::normal(target_commit.id().clone()),
-        );
+        )?;
     }
 
     if let Some(mut formatter) = ui.status_formatter() {
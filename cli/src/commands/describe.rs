@@ -30,11 +30,10 @@ use crate::command_error::user_error;
 use crate::command_error::CommandError;
 use crate::complete;
 use crate::description_util::add_trailers_with_template;
-use crate::description_util::description_template;
 use crate::description_util::edit_description;
 use crate::description_util::edit_multiple_descriptions;
 use crate::description_util::join_message_paragraphs;
-use crate::description_util::parse_trailers_template;
+use crate::description_util::DescriptionTemplates;
 use crate::description_util::ParsedBulkEditMessage;
 use crate::text_util::parse_author;
 use crate::ui::Ui;
@@ -179,70 +178,75 @@ pub(crate) fn cmd_describe(
 
     let use_editor = args.edit || (shared_description.is_none() && !args.no_edit);
 
-    if let Some(trailer_template) = parse_trailers_template(ui, &tx)? {
-        for commit_builder in &mut commit_builders {
-            // The first trailer would become the first line of the description.
-            // Also, a commit with no description is treated in a special way in jujutsu: it
-            // can be discarded as soon as it's no longer the working copy. Adding a
-            // trailer to an empty description would break that logic.
-            if use_editor || !commit_builder.description().is_empty() {
-                let temp_commit = commit_builder.write_hidden()?;
-                let new_description = add_trailers_with_template(&trailer_template, &temp_commit)?;
-                commit_builder.set_description(new_description);
+    // The templates are parsed once and reused for all the commits.
+    {
+        let templates = DescriptionTemplates::new(&tx);
+        if let Some(trailer_template) = templates.trailers_template(ui)? {
+            for commit_builder in &mut commit_builders {
+                // The first trailer would become the first line of the description.
+                // Also, a commit with no description is treated in a special way in jujutsu: it
+                // can be discarded as soon as it's no longer the working copy. Adding a
+                // trailer to an empty description would break that logic.
+                if use_editor || !commit_builder.description().is_empty() {
+                    let temp_commit = commit_builder.write_hidden()?;
+                    let new_description =
+                        add_trailers_with_template(tx.settings(), trailer_template, &temp_commit)?;
+                    commit_builder.set_description(new_description);
+                }
             }
         }
-    }
 
-    if use_editor {
-        let temp_commits: Vec<_> = iter::zip(&commits, &commit_builders)
-            // Edit descriptions in topological order
-            .rev()
-            .map(|(commit, commit_builder)| {
-                commit_builder
-                    .write_hidden()
-                    .map(|temp_commit| (commit.id(), temp_commit))
-            })
-            .try_collect()?;
+        if use_editor {
+            let temp_commits: Vec<_> = iter::zip(&commits, &commit_builders)
+                // Edit descriptions in topological order
+                .rev()
+                .map(|(commit, commit_builder)| {
+                    commit_builder
+                        .write_hidden()
+                        .map(|temp_commit| (commit.id(), temp_commit))
+                })
+                .try_collect()?;
 
-        if let [(_, temp_commit)] = &*temp_commits {
-            let intro = "";
-            let template = description_template(ui, &tx, intro, temp_commit)?;
-            let description = edit_description(&text_editor, &template)?;
-            commit_builders[0].set_description(description);
-        } else {
-            let ParsedBulkEditMessage {
-                descriptions,
-                missing,
-                duplicates,
-                unexpected,
-            } = edit_multiple_descriptions(ui, &text_editor, &tx, &temp_commits)?;
-            if !missing.is_empty() {
-                return Err(user_error(format!(
-                    "The description for the following commits were not found in the edited \
-                     message: {}",
-                    missing.join(", ")
-                )));
-            }
-            if !duplicates.is_empty() {
-                return Err(user_error(format!(
-                    "The following commits were found in the edited message multiple times: {}",
-                    duplicates.join(", ")
-                )));
-            }
-            if !unexpected.is_empty() {
-                return Err(user_error(format!(
-                    "The following commits were not being edited, but were found in the edited \
-                     message: {}",
-                    unexpected.join(", ")
-                )));
-            }
+            if let [(_, temp_commit)] = &*temp_commits {
+                let intro = "";
+                let template = templates.description_template(ui, intro, temp_commit)?;
+                let description = edit_description(&text_editor, &template)?;
+                commit_builders[0].set_description(description);
+            } else {
+                let ParsedBulkEditMessage {
+                    descriptions,
+                    missing,
+                    duplicates,
+                    unexpected,
+                } = edit_multiple_descriptions(ui, &text_editor, &templates, &temp_commits)?;
+                if !missing.is_empty() {
+                    return Err(user_error(format!(
+                        "The description for the following commits were not found in the edited \
+                         message: {}",
+                        missing.join(", ")
+                    )));
+                }
+                if !duplicates.is_empty() {
+                    return Err(user_error(format!(
+                        "The following commits were found in the edited message multiple times: {}",
+                        duplicates.join(", ")
+                    )));
+                }
+                if !unexpected.is_empty() {
+                    return Err(user_error(format!(
+                        "The following commits were not being edited, but were found in the edited \
+                         message: {}",
+                        unexpected.join(", ")
+                    )));
+                }
 
-            for (commit, commit_builder) in iter::zip(&commits, &mut commit_builders) {
-                let description = descriptions.get(commit.id()).unwrap();
-                commit_builder.set_description(description);
+                for (commit, commit_builder) in iter::zip(&commits, &mut commit_builders) {
+                    let description = descriptions.get(commit.id()).unwrap();
+                    commit_builder.set_description(description);
+                }
             }
-        }
-    };
+        };
+    }
 
     // Filter out unchanged commits to avoid rebasing descendants in
     // `transform_descendants` below unnecessarily.
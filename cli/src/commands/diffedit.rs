@@ -13,18 +13,34 @@
 // limitations under the License.
 
 use std::io::Write as _;
+use std::slice;
 
 use clap_complete::ArgValueCompleter;
 use itertools::Itertools as _;
+use jj_lib::commit::Commit;
 use jj_lib::matchers::EverythingMatcher;
+use jj_lib::matchers::FilesMatcher;
+use jj_lib::matchers::Matcher;
 use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::ReadonlyRepo;
+use jj_lib::repo::Repo as _;
 use jj_lib::rewrite::merge_commit_trees;
 use tracing::instrument;
 
+use crate::cli_util::short_commit_hash;
+use crate::cli_util::CheckpointArgs;
 use crate::cli_util::CommandHelper;
+use crate::cli_util::OpDescriptionArgs;
+use crate::cli_util::RestoreDescendantsMode;
 use crate::cli_util::RevisionArg;
+use crate::command_error::user_error;
 use crate::command_error::CommandError;
 use crate::complete;
+use crate::description_util::add_trailers;
+use crate::description_util::description_template;
+use crate::description_util::edit_description;
+use crate::description_util::join_message_paragraphs;
+use crate::selection_plan::SelectionPlanArgs;
 use crate::ui::Ui;
 
 /// Touch up the content changes in a revision with a diff editor
@@ -42,6 +58,9 @@ use crate::ui::Ui;
 /// updated. Unless `--restore-descendants` is used, descendants will be
 /// rebased on top as usual, which may result in conflicts.
 ///
+/// The description of the revision can be updated in the same operation with
+/// `--message` or `--edit-description`.
+///
 /// See `jj restore` if you want to move entire files from one revision to
 /// another. For moving changes between revisions, see `jj squash -i`.
 #[derive(clap::Args, Clone, Debug)]
@@ -85,8 +104,40 @@ pub(crate) struct DiffeditArgs {
     /// compared to its parent(s) is normally preserved, i.e. the same way that
     /// descendants are always rebased. This flag makes it so the content/state
     /// is preserved instead of preserving the diff.
+    ///
+    /// If only some parents of a merge descendant were rewritten, its
+    /// preserved content may no longer be the merge of its parents. Such merge
+    /// commits are reported, or the command fails with
+    /// `--restore-descendants=strict`.
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn"
+    )]
+    restore_descendants: Option<RestoreDescendantsMode>,
+    /// Only edit the files that are conflicted in the revision
+    ///
+    /// The diff is still shown relative to the parent(s), so the conflicted
+    /// files contain conflict markers on the right side.
+    #[arg(long)]
+    conflicts_only: bool,
+    /// Update the change description to this message (don't open editor)
+    #[arg(long = "message", short, value_name = "MESSAGE")]
+    message_paragraphs: Vec<String>,
+    /// Open an editor to update the change description
+    ///
+    /// When used with `--message`, the message is used as the initial
+    /// description in the editor.
     #[arg(long)]
-    restore_descendants: bool,
+    edit_description: bool,
+    #[command(flatten)]
+    selection_plan: SelectionPlanArgs,
+    #[command(flatten)]
+    checkpoint: CheckpointArgs,
+    #[command(flatten)]
+    op_description: OpDescriptionArgs,
 }
 
 #[instrument(skip_all)]
@@ -114,9 +165,26 @@ pub(crate) fn cmd_diffedit(
         diff_description = "The diff initially shows the commit's changes.".to_string();
     };
     workspace_command.check_rewritable([target_commit.id()])?;
+    args.checkpoint.check(workspace_command.repo().view())?;
+    let tree = target_commit.tree()?;
+    let matcher: Box<dyn Matcher> = if args.conflicts_only {
+        let conflicted_paths = tree.conflicts().map(|(path, _)| path).collect_vec();
+        if conflicted_paths.is_empty() {
+            return Err(user_error("No conflicts found at this revision"));
+        }
+        Box::new(FilesMatcher::new(conflicted_paths))
+    } else {
+        Box::new(EverythingMatcher)
+    };
 
-    let diff_editor = workspace_command.diff_editor(ui, args.tool.as_deref())?;
-    let mut tx = workspace_command.start_transaction();
+    let diff_selector = workspace_command.diff_selector_with_plan(
+        ui,
+        args.tool.as_deref(),
+        true,
+        &args.selection_plan,
+    )?;
+    let text_editor = workspace_command.text_editor()?;
+    let update_description = !args.message_paragraphs.is_empty() || args.edit_description;
     let format_instructions = || {
         format!(
             "\
@@ -126,38 +194,116 @@ You are editing changes in: {}
 
 Adjust the right side until it shows the contents you want. If you
 don't make any changes, then the operation will be aborted.",
-            tx.format_commit_summary(&target_commit),
+            workspace_command.format_commit_summary(&target_commit),
         )
     };
-    let base_tree = merge_commit_trees(tx.repo(), base_commits.as_slice())?;
-    let tree = target_commit.tree()?;
-    let tree_id = diff_editor.edit(&base_tree, &tree, &EverythingMatcher, format_instructions)?;
-    if tree_id == *target_commit.tree_id() {
+    let base_tree = merge_commit_trees(workspace_command.repo().as_ref(), &base_commits)?;
+    let edited_tree_id =
+        diff_selector.select(&base_tree, &tree, matcher.as_ref(), format_instructions)?;
+    if edited_tree_id == *target_commit.tree_id() && !update_description {
         writeln!(ui.status(), "Nothing changed.")?;
-    } else {
-        tx.repo_mut()
-            .rewrite_commit(&target_commit)
-            .set_tree_id(tree_id)
-            .write()?;
-        // rebase_descendants early; otherwise `new_commit` would always have
-        // a conflicted change id at this point.
-        let (num_rebased, extra_msg) = if args.restore_descendants {
-            (
-                tx.repo_mut().reparent_descendants()?,
-                " (while preserving their content)",
-            )
+        return Ok(());
+    }
+
+    // Other processes may have committed operations while the diff editor was
+    // open. If the target revision was rewritten by them, apply the edits on
+    // top of its current version.
+    let (target_commit, tree_id) = if workspace_command.maybe_reload_at_head(ui)? {
+        let current_commit = find_current_commit(workspace_command.repo(), &target_commit)?;
+        workspace_command.check_rewritable([current_commit.id()])?;
+        if current_commit.id() == target_commit.id() {
+            (target_commit, edited_tree_id)
         } else {
-            (tx.repo_mut().rebase_descendants()?, "")
-        };
-        if let Some(mut formatter) = ui.status_formatter() {
-            if num_rebased > 0 {
-                writeln!(
-                    formatter,
-                    "Rebased {num_rebased} descendant commits{extra_msg}"
-                )?;
-            }
+            writeln!(
+                ui.warning_default(),
+                "The revision was rewritten while the diff editor was open. Applying the \
+                 changes to its current version: {}",
+                workspace_command.format_commit_summary(&current_commit)
+            )?;
+            let edited_tree = workspace_command
+                .repo()
+                .store()
+                .get_root_tree(&edited_tree_id)?;
+            let new_tree = current_commit.tree()?.merge(&tree, &edited_tree)?;
+            (current_commit, new_tree.id())
         }
-        tx.finish(ui, format!("edit commit {}", target_commit.id().hex()))?;
+    } else {
+        (target_commit, edited_tree_id)
+    };
+
+    let mut tx = workspace_command.start_transaction();
+    let mut commit_builder = tx.repo_mut().rewrite_commit(&target_commit).detach();
+    commit_builder.set_tree_id(tree_id);
+    if !args.message_paragraphs.is_empty() {
+        commit_builder.set_description(join_message_paragraphs(&args.message_paragraphs));
+    }
+    // The first trailer would become the first line of the description. Also,
+    // a commit with no description can be discarded as soon as it's no longer
+    // the working copy, so don't add trailers to an empty description.
+    if update_description && (args.edit_description || !commit_builder.description().is_empty()) {
+        let description = add_trailers(ui, &tx, &commit_builder)?;
+        commit_builder.set_description(description);
+    }
+    if args.edit_description {
+        let temp_commit = commit_builder.write_hidden()?;
+        let intro = "";
+        let template = description_template(ui, &tx, intro, &temp_commit)?;
+        let description = edit_description(&text_editor, &template)?;
+        commit_builder.set_description(description);
+    }
+    if commit_builder.tree_id() == target_commit.tree_id()
+        && commit_builder.description() == target_commit.description()
+    {
+        writeln!(ui.status(), "Nothing changed.")?;
+        return Ok(());
     }
+    commit_builder.write(tx.repo_mut())?;
+    // rebase_descendants early; otherwise `new_commit` would always have
+    // a conflicted change id at this point.
+    let (num_rebased, extra_msg) = if let Some(mode) = args.restore_descendants {
+        let stats = tx.repo_mut().reparent_descendants_with_stats()?;
+        mode.report_diverged_merges(ui, &tx, &stats.diverged_merges)?;
+        (stats.num_reparented, " (while preserving their content)")
+    } else {
+        (tx.repo_mut().rebase_descendants()?, "")
+    };
+    args.checkpoint
+        .set_bookmark(ui, &mut tx, slice::from_ref(target_commit.id()))?;
+    if let Some(mut formatter) = ui.status_formatter() {
+        if num_rebased > 0 {
+            writeln!(
+                formatter,
+                "Rebased {num_rebased} descendant commits{extra_msg}"
+            )?;
+        }
+    }
+    tx.finish_rewriting(
+        ui,
+        args.op_description
+            .description_or(format!("edit commit {}", target_commit.id().hex())),
+        [target_commit.id()],
+    )?;
     Ok(())
 }
+
+/// Returns the visible version of the `commit`, which may have been rewritten
+/// by concurrent operations.
+fn find_current_commit(repo: &ReadonlyRepo, commit: &Commit) -> Result<Commit, CommandError> {
+    let commit_ids = repo
+        .resolve_change_id(commit.change_id())
+        .unwrap_or_default();
+    if commit_ids.contains(commit.id()) {
+        return Ok(commit.clone());
+    }
+    match commit_ids.as_slice() {
+        [] => Err(user_error(format!(
+            "Revision {} was abandoned while the diff editor was open",
+            short_commit_hash(commit.id())
+        ))),
+        [commit_id] => Ok(repo.store().get_commit(commit_id)?),
+        _ => Err(user_error(format!(
+            "Revision {} became divergent while the diff editor was open",
+            short_commit_hash(commit.id())
+        ))),
+    }
+}
@@ -16,6 +16,8 @@ use std::collections::HashMap;
 use std::io::Write as _;
 use std::path::Path;
 use std::process::Stdio;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
 use clap_complete::ArgValueCompleter;
 use itertools::Itertools as _;
@@ -79,6 +81,10 @@ use crate::ui::Ui;
 ///  - `enabled`: Enables or disables the tool. If omitted, the tool is enabled.
 ///    This is useful for defining disabled tools in user configuration that can
 ///    be enabled in individual repositories with one config setting.
+///  - `changed-since`: A revset. If set, the tool is only applied to files
+///    that differ from the merge base of the revision being fixed and this
+///    revset. This is useful for running slow tools only on files changed
+///    relative to `trunk()`, for example.
 ///
 /// For example, the following configuration defines how two code formatters
 /// (`clang-format` and `black`) will apply to three different file extensions
@@ -130,6 +136,17 @@ pub(crate) fn cmd_fix(
     let mut workspace_command = command.workspace_helper(ui)?;
     let workspace_root = workspace_command.workspace_root().to_owned();
     let tools_config = get_tools_config(ui, workspace_command.settings())?;
+    // Resolve the `changed-since` revsets once, before any commits are fixed.
+    let changed_since: Vec<Vec<CommitId>> = tools_config
+        .changed_since
+        .iter()
+        .map(|revset| -> Result<_, CommandError> {
+            Ok(workspace_command
+                .parse_revset(ui, &RevisionArg::from(revset.clone()))?
+                .evaluate_to_commit_ids()?
+                .try_collect()?)
+        })
+        .try_collect()?;
     let root_commits: Vec<CommitId> = if args.source.is_empty() {
         let revs = workspace_command.settings().get_string("revsets.fix")?;
         workspace_command.parse_revset(ui, &RevisionArg::from(revs))?
@@ -151,6 +168,7 @@ pub(crate) fn cmd_fix(
         root_commits,
         &matcher,
         args.include_unchanged_files,
+        &changed_since,
         tx.repo_mut(),
         &mut parallel_fixer,
     )?;
@@ -160,6 +178,16 @@ pub(crate) fn cmd_fix(
         summary.num_fixed_commits,
         summary.num_checked_commits
     )?;
+    for tool_config in &tools_config.tools {
+        let num_skipped = tool_config.num_skipped_unchanged.load(Ordering::Relaxed);
+        if num_skipped > 0 {
+            writeln!(
+                ui.status(),
+                "Skipped {num_skipped} unchanged files for tool `{name}`.",
+                name = tool_config.name
+            )?;
+        }
+    }
     tx.finish(ui, format!("fixed {} commits", summary.num_fixed_commits))
 }
 
@@ -168,10 +196,11 @@ pub(crate) fn cmd_fix(
 /// returned. Returns None if the content is unchanged.
 ///
 /// The matching tools are invoked in order, with the result of one tool feeding
-/// into the next tool. Returns FixError if there is an error reading or writing
-/// the file. However, if a tool invocation fails for whatever reason, the tool
-/// is simply skipped and we proceed to invoke the next tool (this is
-/// indistinguishable from succeeding with no changes).
+/// into the next tool. Tools scoped with `changed-since` are skipped if the
+/// file is unchanged since their base revisions. Returns FixError if there is
+/// an error reading or writing the file. However, if a tool invocation fails
+/// for whatever reason, the tool is simply skipped and we proceed to invoke the
+/// next tool (this is indistinguishable from succeeding with no changes).
 ///
 /// TODO: Better error handling so we can tell the user what went wrong with
 /// each failed input.
@@ -185,6 +214,15 @@ async fn fix_one_file(
         .tools
         .iter()
         .filter(|tool_config| tool_config.matcher.matches(&file_to_fix.repo_path))
+        .filter(|tool_config| match tool_config.changed_since {
+            Some(index) if file_to_fix.unchanged_since.contains(&index) => {
+                tool_config
+                    .num_skipped_unchanged
+                    .fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            _ => true,
+        })
         .peekable();
     if matching_tools.peek().is_some() {
         // The first matching tool gets its input from the committed file, and any
@@ -264,14 +302,22 @@ fn run_tool(
 
 /// Represents an entry in the `fix.tools` config table.
 struct ToolConfig {
+    /// The name of the tool in the `fix.tools` table.
+    name: String,
     /// The command that will be run to fix a matching file.
     command: CommandNameAndArgs,
     /// The matcher that determines if this tool matches a file.
     matcher: Box<dyn Matcher>,
+    /// Index into `ToolsConfig::changed_since` if the tool only applies to
+    /// files changed since a revset.
+    changed_since: Option<usize>,
+    /// The number of files skipped because they were unchanged since the
+    /// `changed_since` revset.
+    num_skipped_unchanged: AtomicUsize,
     /// Whether the tool is enabled
     enabled: bool,
-    // TODO: Store the `name` field here and print it with the command's stderr, to clearly
-    // associate any errors/warnings with the tool and its configuration entry.
+    // TODO: Print the `name` field with the command's stderr, to clearly associate any
+    // errors/warnings with the tool and its configuration entry.
 }
 
 /// Represents the `fix.tools` config table.
@@ -279,6 +325,8 @@ struct ToolsConfig {
     /// Some tools, stored in the order they will be executed if more than one
     /// of them matches the same file.
     tools: Vec<ToolConfig>,
+    /// The distinct `changed-since` revsets of the enabled tools.
+    changed_since: Vec<String>,
 }
 
 /// Simplifies deserialization of the config values while building a ToolConfig.
@@ -289,6 +337,8 @@ struct RawToolConfig {
     patterns: Vec<String>,
     #[serde(default = "default_tool_enabled")]
     enabled: bool,
+    #[serde(default)]
+    changed_since: Option<String>,
 }
 
 fn default_tool_enabled() -> bool {
@@ -301,6 +351,7 @@ fn default_tool_enabled() -> bool {
 /// not check for issues that might still occur later like missing executables.
 /// This is a place where we could fail earlier in some cases, though.
 fn get_tools_config(ui: &mut Ui, settings: &UserSettings) -> Result<ToolsConfig, CommandError> {
+    let mut changed_since: Vec<String> = vec![];
     let mut tools: Vec<ToolConfig> = settings
         .table_keys("fix.tools")
         // Sort keys early so errors are deterministic.
@@ -324,9 +375,21 @@ fn get_tools_config(ui: &mut Ui, settings: &UserSettings) -> Result<ToolsConfig,
                     .try_collect()?,
             );
             print_parse_diagnostics(ui, &format!("In `fix.tools.{name}`"), &diagnostics)?;
+            let changed_since = tool.changed_since.filter(|_| tool.enabled).map(|revset| {
+                changed_since
+                    .iter()
+                    .position(|other| *other == revset)
+                    .unwrap_or_else(|| {
+                        changed_since.push(revset);
+                        changed_since.len() - 1
+                    })
+            });
             Ok(ToolConfig {
+                name: name.to_owned(),
                 command: tool.command,
                 matcher: expression.to_matcher(),
+                changed_since,
+                num_skipped_unchanged: AtomicUsize::new(0),
                 enabled: tool.enabled,
             })
         })
@@ -340,6 +403,9 @@ fn get_tools_config(ui: &mut Ui, settings: &UserSettings) -> Result<ToolsConfig,
             "At least one entry of `fix.tools` must be enabled.".to_string(),
         ))
     } else {
-        Ok(ToolsConfig { tools })
+        Ok(ToolsConfig {
+            tools,
+            changed_since,
+        })
     }
 }
@@ -869,7 +869,7 @@ fn create_explicitly_named_bookmarks(
         .base_workspace_helper()
         .resolve_single_rev(ui, &revision_str.to_string().into())?;
     tx.repo_mut()
-        .set_local_bookmark_target(&name, RefTarget::normal(revision.id().clone()));
+        .set_local_bookmark_target(&name, RefTarget::normal(revision.id().clone()))?;
     Ok(name)
 }
 
@@ -908,7 +908,7 @@ fn create_change_bookmarks(
                 "Creating bookmark {name} for revision {short_change_id}",
                 name = name.as_symbol()
             )?;
-            tx.repo_mut().set_local_bookmark_target(&name, target);
+            tx.repo_mut().set_local_bookmark_target(&name, target)?;
         }
         bookmark_names.push(name);
     }
@@ -14,7 +14,6 @@
 
 use std::collections::HashMap;
 use std::convert::Infallible;
-use std::sync::Arc;
 
 use clap_complete::ArgValueCandidates;
 use indexmap::IndexMap;
@@ -32,10 +31,10 @@ use jj_lib::op_store::RemoteRefState;
 use jj_lib::refs::diff_named_commit_ids;
 use jj_lib::refs::diff_named_ref_targets;
 use jj_lib::refs::diff_named_remote_refs;
-use jj_lib::repo::ReadonlyRepo;
 use jj_lib::repo::Repo;
 use jj_lib::revset;
 use jj_lib::revset::RevsetIteratorExt as _;
+use jj_lib::view::View;
 
 use crate::cli_util::CommandHelper;
 use crate::cli_util::LogContentFormat;
@@ -105,7 +104,7 @@ pub fn cmd_op_diff(
     } else {
         to_op = workspace_command.resolve_single_op(args.operation.as_deref().unwrap_or("@"))?;
         let to_op_parents: Vec<_> = to_op.parents().try_collect()?;
-        from_op = repo_loader.merge_operations(to_op_parents, None)?;
+        from_op = repo_loader.merge_operations(to_op_parents, None)?.0;
     }
     let graph_style = GraphStyle::from_settings(settings)?;
     let with_content_format = LogContentFormat::new(ui, settings)?;
@@ -124,8 +123,15 @@ pub fn cmd_op_diff(
         let formats = diff_formats_for_log(settings, &args.diff_format, args.patch)?;
         let path_converter = workspace_env.path_converter();
         let conflict_marker_style = workspace_env.conflict_marker_style();
-        (!formats.is_empty())
-            .then(|| DiffRenderer::new(merged_repo, path_converter, conflict_marker_style, formats))
+        (!formats.is_empty()).then(|| {
+            DiffRenderer::new(
+                merged_repo,
+                path_converter,
+                conflict_marker_style,
+                workspace_env.prefetch_concurrency(),
+                formats,
+            )
+        })
     };
     let id_prefix_context = workspace_env.new_id_prefix_context();
     let commit_summary_template = {
@@ -152,8 +158,8 @@ pub fn cmd_op_diff(
         ui,
         formatter.as_mut(),
         merged_repo,
-        &from_repo,
-        &to_repo,
+        from_repo.view(),
+        to_repo.view(),
         &commit_summary_template,
         (!args.no_graph).then_some(graph_style),
         &with_content_format,
@@ -162,22 +168,22 @@ pub fn cmd_op_diff(
 }
 
 /// Computes and shows the differences between two operations, using the given
-/// `ReadonlyRepo`s for the operations.
-/// `current_repo` should contain a `Repo` with the indices of both repos merged
-/// into it.
+/// `View`s for the operations.
+/// `current_repo` should contain a `Repo` with the indices of both operations
+/// merged into it.
 #[expect(clippy::too_many_arguments)]
 pub fn show_op_diff(
     ui: &Ui,
     formatter: &mut dyn Formatter,
     current_repo: &dyn Repo,
-    from_repo: &Arc<ReadonlyRepo>,
-    to_repo: &Arc<ReadonlyRepo>,
+    from_view: &View,
+    to_view: &View,
     commit_summary_template: &TemplateRenderer<Commit>,
     graph_style: Option<GraphStyle>,
     with_content_format: &LogContentFormat,
     diff_renderer: Option<&DiffRenderer>,
 ) -> Result<(), CommandError> {
-    let changes = compute_operation_commits_diff(current_repo, from_repo, to_repo)?;
+    let changes = compute_operation_commits_diff(current_repo, from_view, to_view)?;
 
     let commit_id_change_id_map: HashMap<CommitId, ChangeId> = changes
         .iter()
@@ -281,11 +287,8 @@ pub fn show_op_diff(
         }
     }
 
-    let changed_working_copies = diff_named_commit_ids(
-        from_repo.view().wc_commit_ids(),
-        to_repo.view().wc_commit_ids(),
-    )
-    .collect_vec();
+    let changed_working_copies =
+        diff_named_commit_ids(from_view.wc_commit_ids(), to_view.wc_commit_ids()).collect_vec();
     if !changed_working_copies.is_empty() {
         writeln!(formatter)?;
         for (name, (from_commit, to_commit)) in changed_working_copies {
@@ -315,11 +318,9 @@ pub fn show_op_diff(
         }
     }
 
-    let changed_local_bookmarks = diff_named_ref_targets(
-        from_repo.view().local_bookmarks(),
-        to_repo.view().local_bookmarks(),
-    )
-    .collect_vec();
+    let changed_local_bookmarks =
+        diff_named_ref_targets(from_view.local_bookmarks(), to_view.local_bookmarks())
+            .collect_vec();
     if !changed_local_bookmarks.is_empty() {
         writeln!(formatter)?;
         with_content_format.write(formatter, |formatter| {
@@ -348,8 +349,7 @@ pub fn show_op_diff(
         }
     }
 
-    let changed_tags =
-        diff_named_ref_targets(from_repo.view().tags(), to_repo.view().tags()).collect_vec();
+    let changed_tags = diff_named_ref_targets(from_view.tags(), to_view.tags()).collect_vec();
     if !changed_tags.is_empty() {
         writeln!(formatter)?;
         with_content_format.write(formatter, |formatter| writeln!(formatter, "Changed tags:"))?;
@@ -378,8 +378,8 @@ pub fn show_op_diff(
     }
 
     let changed_remote_bookmarks = diff_named_remote_refs(
-        from_repo.view().all_remote_bookmarks(),
-        to_repo.view().all_remote_bookmarks(),
+        from_view.all_remote_bookmarks(),
+        to_view.all_remote_bookmarks(),
     )
     // Skip updates to the local git repo, since they should typically be covered in
     // local branches.
@@ -535,16 +535,16 @@ struct ModifiedChange {
 /// and removed commits for the change ID.
 fn compute_operation_commits_diff(
     repo: &dyn Repo,
-    from_repo: &ReadonlyRepo,
-    to_repo: &ReadonlyRepo,
+    from_view: &View,
+    to_view: &View,
 ) -> Result<IndexMap<ChangeId, ModifiedChange>, CommandError> {
     let mut changes: IndexMap<ChangeId, ModifiedChange> = IndexMap::new();
 
-    let from_heads = from_repo.view().heads().iter().cloned().collect_vec();
-    let to_heads = to_repo.view().heads().iter().cloned().collect_vec();
+    let from_heads = from_view.heads().iter().cloned().collect_vec();
+    let to_heads = to_view.heads().iter().cloned().collect_vec();
 
-    // Find newly added commits in `to_repo` which were not present in
-    // `from_repo`.
+    // Find newly added commits in `to_view` which were not present in
+    // `from_view`.
     for commit in revset::walk_revs(repo, &to_heads, &from_heads)?
         .iter()
         .commits(repo.store())
@@ -559,7 +559,7 @@ fn compute_operation_commits_diff(
         modified_change.added_commits.push(commit);
     }
 
-    // Find commits which were hidden in `to_repo`.
+    // Find commits which were hidden in `to_view`.
     for commit in revset::walk_revs(repo, &from_heads, &to_heads)?
         .iter()
         .commits(repo.store())
@@ -150,8 +150,8 @@ fn do_op_log(
                          op: &Operation,
                          with_content_format: &LogContentFormat| {
             let parents: Vec<_> = op.parents().try_collect()?;
-            let parent_op = repo_loader.merge_operations(parents, None)?;
-            let parent_repo = repo_loader.load_at(&parent_op)?;
+            let (parent_op, _) = repo_loader.merge_operations(parents, None)?;
+            let parent_view = repo_loader.load_view_at(&parent_op)?;
             let repo = repo_loader.load_at(op)?;
 
             let id_prefix_context = workspace_env.new_id_prefix_context();
@@ -169,6 +169,7 @@ fn do_op_log(
                     repo.as_ref(),
                     path_converter,
                     conflict_marker_style,
+                    workspace_env.prefetch_concurrency(),
                     diff_formats.clone(),
                 )
             });
@@ -177,8 +178,8 @@ fn do_op_log(
                 ui,
                 formatter,
                 repo.as_ref(),
-                &parent_repo,
-                &repo,
+                &parent_view,
+                repo.view(),
                 &commit_summary_template,
                 (!args.no_graph).then_some(graph_style),
                 with_content_format,
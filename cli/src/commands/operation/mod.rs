@@ -104,5 +104,8 @@ fn view_with_desired_portions_restored(
         git_refs: current_view.git_refs.clone(),
         git_head: current_view.git_head.clone(),
         wc_commit_ids: repo_source.wc_commit_ids.clone(),
+        workspace_metadata: repo_source.workspace_metadata.clone(),
+        pinned_commits: repo_source.pinned_commits.clone(),
+        notes: repo_source.notes.clone(),
     }
 }
@@ -14,8 +14,10 @@
 
 use clap_complete::ArgValueCandidates;
 use jj_lib::object_id::ObjectId as _;
+use jj_lib::op_walk;
+use jj_lib::op_walk::RestoreScope;
+use jj_lib::str_util::StringPattern;
 
-use super::view_with_desired_portions_restored;
 use super::UndoWhatToRestore;
 use super::DEFAULT_UNDO_WHAT;
 use crate::cli_util::CommandHelper;
@@ -52,12 +54,9 @@ pub fn cmd_op_restore(
     let mut workspace_command = command.workspace_helper(ui)?;
     let target_op = workspace_command.resolve_single_op(&args.operation)?;
     let mut tx = workspace_command.start_transaction();
-    let new_view = view_with_desired_portions_restored(
-        target_op.view()?.store_view(),
-        tx.base_repo().view().store_view(),
-        &args.what,
-    );
-    tx.repo_mut().set_view(new_view);
+    for scope in restore_scopes(&args.what) {
+        op_walk::restore_view_partial(&mut tx, &target_op, &scope)?;
+    }
     if let Some(mut formatter) = ui.status_formatter() {
         write!(formatter, "Restored to operation: ")?;
         let template = tx.base_workspace_helper().operation_summary_template();
@@ -68,3 +67,27 @@ pub fn cmd_op_restore(
 
     Ok(())
 }
+
+/// Maps the `what` argument to the portions of the view to restore.
+fn restore_scopes(what: &[UndoWhatToRestore]) -> Vec<RestoreScope> {
+    let restore_repo = what.contains(&UndoWhatToRestore::Repo);
+    let restore_remote_tracking = what.contains(&UndoWhatToRestore::RemoteTracking);
+    if restore_repo && restore_remote_tracking {
+        return vec![RestoreScope::All];
+    }
+    let mut scopes = vec![];
+    if restore_remote_tracking {
+        scopes.push(RestoreScope::RemoteBookmarks(StringPattern::everything()));
+    }
+    if restore_repo {
+        // Heads are restored last as they keep the current bookmarked commits
+        // visible.
+        scopes.extend([
+            RestoreScope::LocalBookmarks(StringPattern::everything()),
+            RestoreScope::Tags,
+            RestoreScope::WorkspacePointers,
+            RestoreScope::Heads,
+        ]);
+    }
+    scopes
+}
@@ -59,8 +59,8 @@ pub fn cmd_op_show(
     let settings = workspace_command.settings();
     let op = workspace_command.resolve_single_op(&args.operation)?;
     let parents: Vec<_> = op.parents().try_collect()?;
-    let parent_op = repo_loader.merge_operations(parents, None)?;
-    let parent_repo = repo_loader.load_at(&parent_op)?;
+    let (parent_op, _) = repo_loader.merge_operations(parents, None)?;
+    let parent_view = repo_loader.load_view_at(&parent_op)?;
     let repo = repo_loader.load_at(&op)?;
 
     let id_prefix_context = workspace_env.new_id_prefix_context();
@@ -83,6 +83,7 @@ pub fn cmd_op_show(
                 repo.as_ref(),
                 path_converter,
                 conflict_marker_style,
+                workspace_env.prefetch_concurrency(),
                 formats,
             )
         })
@@ -104,8 +105,8 @@ pub fn cmd_op_show(
         ui,
         formatter.as_mut(),
         repo.as_ref(),
-        &parent_repo,
-        &repo,
+        &parent_view,
+        repo.view(),
         &commit_summary_template,
         (!args.no_graph).then_some(graph_style),
         &with_content_format,
@@ -16,6 +16,8 @@ use clap_complete::ArgValueCandidates;
 use itertools::Itertools as _;
 use jj_lib::object_id::ObjectId as _;
 use jj_lib::op_store::OpStoreError;
+use jj_lib::op_walk;
+use jj_lib::op_walk::UndoOptions;
 use jj_lib::operation::Operation;
 use jj_lib::repo::Repo as _;
 
@@ -78,10 +80,7 @@ pub fn cmd_op_undo(
     };
 
     let mut tx = workspace_command.start_transaction();
-    let repo_loader = tx.base_repo().loader();
-    let bad_repo = repo_loader.load_at(&bad_op)?;
-    let parent_repo = repo_loader.load_at(&parent_of_bad_op)?;
-    tx.repo_mut().merge(&bad_repo, &parent_repo)?;
+    op_walk::undo_operation(tx.inner_mut(), &bad_op, &UndoOptions::default())?;
     let new_view = view_with_desired_portions_restored(
         tx.repo().view().store_view(),
         tx.base_repo().view().store_view(),
@@ -380,8 +380,10 @@ pub(crate) fn cmd_rebase(
         },
         rewrite_refs: RewriteRefsOptions {
             delete_abandoned_bookmarks: false,
+            ..Default::default()
         },
         simplify_ancestor_merge: false,
+        ..Default::default()
     };
     let mut workspace_command = command.workspace_helper(ui)?;
     let loc = if !args.revisions.is_empty() {
@@ -409,6 +411,7 @@ pub(crate) fn cmd_rebase(
                     formatter.as_mut(),
                     &tx.base_workspace_helper().commit_summary_template(),
                     &abandoned_divergent,
+                    ui.term_width(),
                 )?;
             }
         }
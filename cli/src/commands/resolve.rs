@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::io::Write as _;
+
 use clap_complete::ArgValueCompleter;
 use itertools::Itertools as _;
 use jj_lib::object_id::ObjectId as _;
@@ -57,11 +59,18 @@ pub(crate) struct ResolveArgs {
     // `diff --summary`, but should be more verbose.
     #[arg(long, short)]
     list: bool,
+    /// Instead of resolving conflicts, write their conflict markers to the
+    /// files in the working copy
+    ///
+    /// This is only needed with `ui.lazy-conflicts` enabled, which leaves
+    /// placeholders in conflicted files when they're checked out.
+    #[arg(long, conflicts_with_all = ["list", "revision"])]
+    materialize: bool,
     /// Specify 3-way merge tool to be used
     ///
     /// The built-in merge tools `:ours` and `:theirs` can be used to choose
     /// side #1 and side #2 of the conflict respectively.
-    #[arg(long, conflicts_with = "list", value_name = "NAME")]
+    #[arg(long, conflicts_with_all = ["list", "materialize"], value_name = "NAME")]
     tool: Option<String>,
     /// Only resolve conflicts in these paths. You can use the `--list` argument
     /// to find paths to use here.
@@ -103,6 +112,22 @@ pub(crate) fn cmd_resolve(
             &workspace_command,
         );
     };
+    if args.materialize {
+        let stats = workspace_command.materialize_conflicts(matcher.as_ref())?;
+        writeln!(
+            ui.status(),
+            "Wrote conflict markers to {} files",
+            stats.updated_files
+        )?;
+        if stats.skipped_files != 0 {
+            writeln!(
+                ui.warning_default(),
+                "{} files were skipped because their placeholders were modified.",
+                stats.skipped_files
+            )?;
+        }
+        return Ok(());
+    }
 
     let repo_paths = conflicts
         .iter()
@@ -12,19 +12,55 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::io::Write as _;
+use std::slice;
 
 use clap_complete::ArgValueCompleter;
+use futures::future;
+use futures::StreamExt as _;
 use indoc::formatdoc;
 use itertools::Itertools as _;
+use jj_lib::commit::Commit;
+use jj_lib::commit::CommitIteratorExt as _;
+use jj_lib::copies::CopyTracking;
+use jj_lib::fileset::FilePattern;
+use jj_lib::fileset::FilesetExpression;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::matchers::FilesMatcher;
+use jj_lib::matchers::Matcher;
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::merged_tree::MergedTreeBuilder;
 use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::MutableRepo;
+use jj_lib::repo::Repo;
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::revset::RevsetExpression;
+use jj_lib::revset::RevsetIteratorExt as _;
+use jj_lib::rewrite::find_diverged_merges;
+use jj_lib::rewrite::merge_commit_trees;
+use jj_lib::rewrite::restore_tree;
+use jj_lib::rewrite::restore_tree_paths;
+use jj_lib::rewrite::RewriteRefsOptions;
+use pollster::FutureExt as _;
 use tracing::instrument;
 
+use crate::cli_util::short_commit_hash;
+use crate::cli_util::CheckpointArgs;
 use crate::cli_util::CommandHelper;
+use crate::cli_util::DiffSelector;
+use crate::cli_util::OpDescriptionArgs;
+use crate::cli_util::RestoreDescendantsMode;
 use crate::cli_util::RevisionArg;
+use crate::cli_util::WorkspaceCommandHelper;
+use crate::command_error::cli_error;
 use crate::command_error::user_error;
 use crate::command_error::CommandError;
 use crate::complete;
+use crate::diff_util::collect_copy_records;
+use crate::selection_plan::SelectionPlanArgs;
 use crate::ui::Ui;
 
 /// Restore paths from another revision
@@ -41,6 +77,9 @@ use crate::ui::Ui;
 /// to `jj abandon`, except that it leaves an empty revision with its
 /// description and other metadata preserved.
 ///
+/// With `--move`, the restored changes are also removed from the source, so
+/// they move from one revision to the other.
+///
 /// See `jj diffedit` if you'd like to restore portions of files rather than
 /// entire files.
 #[derive(clap::Args, Clone, Debug)]
@@ -83,6 +122,18 @@ pub(crate) struct RestoreArgs {
         add = ArgValueCompleter::new(complete::revset_expression_all),
     )]
     changes_in: Option<RevisionArg>,
+    /// Undo the changes in a merge revision as compared to only this parent
+    ///
+    /// This undoes the changes that can be seen with `jj diff --from REVSET
+    /// --to CHANGES_IN`, which drops the contribution of the other parents
+    /// while keeping the merge.
+    #[arg(
+        long,
+        value_name = "REVSET",
+        requires = "changes_in",
+        add = ArgValueCompleter::new(complete::revset_expression_all),
+    )]
+    against: Option<RevisionArg>,
     /// Prints an error. DO NOT USE.
     ///
     /// If we followed the pattern of `jj diff` and `jj diffedit`, we would use
@@ -98,9 +149,87 @@ pub(crate) struct RestoreArgs {
     /// Specify diff editor to be used (implies --interactive)
     #[arg(long, value_name = "NAME")]
     tool: Option<String>,
+    #[command(flatten)]
+    selection_plan: SelectionPlanArgs,
     /// Preserve the content (not the diff) when rebasing descendants
+    ///
+    /// If only some parents of a merge descendant were rewritten, its
+    /// preserved content may no longer be the merge of its parents. Such merge
+    /// commits are reported, or the command fails with
+    /// `--restore-descendants=strict`.
+    #[arg(
+        long,
+        value_name = "MODE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "warn"
+    )]
+    restore_descendants: Option<RestoreDescendantsMode>,
+    /// Don't restore paths that would cause new conflicts in the children of
+    /// the destination
+    ///
+    /// The skipped paths are reported.
+    #[arg(long, conflicts_with = "restore_descendants")]
+    skip_conflicting: bool,
+    /// Abandon the destination revision if it becomes empty
+    ///
+    /// The revision is abandoned like with `jj abandon`: bookmarks pointing to
+    /// it are deleted, and descendants are rebased onto its parents. Nothing
+    /// is abandoned if the destination still has changes, e.g. when only
+    /// some paths were restored.
+    #[arg(long)]
+    abandon_if_empty: bool,
+    /// Treat renamed files as a whole
+    ///
+    /// A renamed file is restored if either its old or new path is matched by
+    /// the filesets. Its new path then gets the content of the old path in
+    /// the source revision.
     #[arg(long)]
-    restore_descendants: bool,
+    track_renames: bool,
+    /// Restore paths even if they were renamed in the destination
+    ///
+    /// By default, a path which was renamed in the destination revision isn't
+    /// recreated when the renamed file still exists, since that would
+    /// duplicate the file. Such paths are skipped and reported.
+    #[arg(long)]
+    allow_resurrect: bool,
+    /// Restore a file from a different path in the source revision
+    ///
+    /// The argument has the form `SRC_PATH=DEST_PATH`, e.g. `--map
+    /// old/config.toml=new/config.toml`. The destination path gets the content
+    /// of the source path, regardless of the filesets. This option can be
+    /// repeated. If no filesets are given, only the mapped paths are restored.
+    #[arg(
+        long,
+        value_name = "SRC_PATH=DEST_PATH",
+        requires = "from",
+        value_hint = clap::ValueHint::AnyPath,
+    )]
+    map: Vec<String>,
+    /// Also reset the restored paths in the source revision
+    ///
+    /// The restored paths get the content of the source's parent(s) in the
+    /// source (`--from`), so the changes to them move from the source to the
+    /// destination. Both revisions must be mutable. If one of them is a
+    /// descendant of the other, the change is applied to its rebased version.
+    #[arg(
+        long = "move",
+        requires = "from",
+        conflicts_with_all = [
+            "changes_in",
+            "interactive",
+            "tool",
+            "selection_plan",
+            "restore_descendants",
+            "map",
+            "abandon_if_empty",
+        ],
+    )]
+    move_changes: bool,
+    #[command(flatten)]
+    checkpoint: CheckpointArgs,
+    #[command(flatten)]
+    op_description: OpDescriptionArgs,
 }
 
 #[instrument(skip_all)]
@@ -128,16 +257,46 @@ pub(crate) fn cmd_restore(
     } else {
         to_commit = workspace_command
             .resolve_single_rev(ui, args.changes_in.as_ref().unwrap_or(&RevisionArg::AT))?;
-        from_tree = to_commit.parent_tree(workspace_command.repo().as_ref())?;
-        from_commits = to_commit.parents().try_collect()?;
+        if let Some(against) = &args.against {
+            let against_commit = workspace_command.resolve_single_rev(ui, against)?;
+            check_merge_parent(&workspace_command, &to_commit, &against_commit)?;
+            from_tree = against_commit.tree()?;
+            from_commits = vec![against_commit];
+        } else {
+            from_tree = to_commit.parent_tree(workspace_command.repo().as_ref())?;
+            from_commits = to_commit.parents().try_collect()?;
+        }
     }
-    workspace_command.check_rewritable([to_commit.id()])?;
+    if args.move_changes {
+        let from_commit = &from_commits[0];
+        if from_commit.id() == to_commit.id() {
+            return Err(user_error(
+                "Cannot move changes from a revision into itself",
+            ));
+        }
+        workspace_command.check_rewritable([to_commit.id(), from_commit.id()])?;
+    } else {
+        workspace_command.check_rewritable([to_commit.id()])?;
+    }
+    args.checkpoint.check(workspace_command.repo().view())?;
 
-    let matcher = workspace_command
-        .parse_file_patterns(ui, &args.paths)?
-        .to_matcher();
-    let diff_selector =
-        workspace_command.diff_selector(ui, args.tool.as_deref(), args.interactive)?;
+    let path_mappings: Vec<_> = args
+        .map
+        .iter()
+        .map(|mapping| parse_path_mapping(&workspace_command, mapping))
+        .try_collect()?;
+    let fileset_expression = if args.paths.is_empty() && !path_mappings.is_empty() {
+        FilesetExpression::none()
+    } else {
+        workspace_command.parse_file_patterns(ui, &args.paths)?
+    };
+    let matcher = fileset_expression.to_matcher();
+    let diff_selector = workspace_command.diff_selector_with_plan(
+        ui,
+        args.tool.as_deref(),
+        args.interactive,
+        &args.selection_plan,
+    )?;
     let to_tree = to_commit.tree()?;
     let format_instructions = || {
         formatdoc! {"
@@ -155,26 +314,209 @@ pub(crate) fn cmd_restore(
             to_commit = workspace_command.format_commit_summary(&to_commit),
         }
     };
-    let new_tree_id = diff_selector.select(&to_tree, &from_tree, &matcher, format_instructions)?;
-    if &new_tree_id == to_commit.tree_id() {
+    let copy_tracking = if args.track_renames {
+        CopyTracking::Track
+    } else {
+        CopyTracking::None
+    };
+    let copy_records = collect_copy_records(
+        workspace_command.repo().store(),
+        &from_commits.iter().ids().cloned().collect_vec(),
+        slice::from_ref(to_commit.id()),
+        copy_tracking,
+    )?;
+    let prefix_paths = prefix_paths(&fileset_expression)
+        .filter(|_| matches!(diff_selector, DiffSelector::NonInteractive))
+        .filter(|_| copy_records.iter().next().is_none());
+    let mut new_tree_id = if let Some(paths) = prefix_paths {
+        // Whole paths can be restored without diffing the trees
+        restore_tree_paths(&from_tree, &to_tree, &paths)?
+    } else {
+        diff_selector.select_with_copies(
+            ui,
+            &to_tree,
+            &from_tree,
+            &matcher,
+            &copy_records,
+            format_instructions,
+        )?
+    };
+    if !path_mappings.is_empty() {
+        let mut tree_builder = MergedTreeBuilder::new(new_tree_id);
+        for (source_path, dest_path) in &path_mappings {
+            let value = from_tree.path_value(source_path)?;
+            if value.is_absent() {
+                return Err(user_error(format!(
+                    "Path {} doesn't exist in the source revision",
+                    workspace_command.format_file_path(source_path)
+                )));
+            } else if value.is_tree() {
+                return Err(user_error(format!(
+                    "Path {} is a directory in the source revision",
+                    workspace_command.format_file_path(source_path)
+                )));
+            }
+            tree_builder.set_or_remove(dest_path.clone(), value);
+        }
+        new_tree_id = tree_builder.write_tree(workspace_command.repo().store())?;
+    }
+    let mut resurrected_paths = vec![];
+    if !args.allow_resurrect && &new_tree_id != to_commit.tree_id() {
+        let repo = workspace_command.repo().as_ref();
+        let new_tree = repo.store().get_root_tree(&new_tree_id)?;
+        resurrected_paths = find_resurrected_renames(repo, &to_commit, &new_tree)?
+            .into_iter()
+            // Mapped paths were explicitly requested
+            .filter(|(old_path, _)| path_mappings.iter().all(|(_, dest)| dest != old_path))
+            .collect_vec();
+        if !resurrected_paths.is_empty() {
+            let mut tree_builder = MergedTreeBuilder::new(new_tree_id);
+            for (old_path, _) in &resurrected_paths {
+                tree_builder.set_or_remove(old_path.clone(), Merge::absent());
+            }
+            new_tree_id = tree_builder.write_tree(repo.store())?;
+            writeln!(
+                ui.warning_default(),
+                "Skipped restoring {} paths that were renamed in the destination:",
+                resurrected_paths.len()
+            )?;
+            for (old_path, new_path) in &resurrected_paths {
+                writeln!(
+                    ui.warning_no_heading(),
+                    "  {} (renamed to {})",
+                    workspace_command.format_file_path(old_path),
+                    workspace_command.format_file_path(new_path)
+                )?;
+            }
+            writeln!(
+                ui.hint_default(),
+                "Use --allow-resurrect to restore them anyway, or --track-renames to restore \
+                 the renamed paths."
+            )?;
+        }
+    }
+    let mut skipped_paths = vec![];
+    if args.skip_conflicting && &new_tree_id != to_commit.tree_id() {
+        let repo = workspace_command.repo().as_ref();
+        let new_tree = repo.store().get_root_tree(&new_tree_id)?;
+        skipped_paths = find_paths_conflicting_in_children(repo, &to_commit, &new_tree)?;
+        if !skipped_paths.is_empty() {
+            let mut tree_builder = MergedTreeBuilder::new(new_tree_id);
+            for path in &skipped_paths {
+                tree_builder.set_or_remove(path.clone(), to_tree.path_value(path)?);
+            }
+            new_tree_id = tree_builder.write_tree(repo.store())?;
+            writeln!(
+                ui.warning_default(),
+                "Skipped restoring {} paths that would conflict in child commits:",
+                skipped_paths.len()
+            )?;
+            for path in &skipped_paths {
+                writeln!(
+                    ui.warning_no_heading(),
+                    "  {}",
+                    workspace_command.format_file_path(path)
+                )?;
+            }
+        }
+    }
+    // The changes to the restored paths in the source, which are reset when
+    // moving. Paths that weren't restored must be kept in the source.
+    let moved_paths: Vec<RepoPathBuf> = if args.move_changes {
+        let from_parent_tree = from_commits[0].parent_tree(workspace_command.repo().as_ref())?;
+        from_parent_tree
+            .diff_stream(&from_tree, &matcher)
+            .map(|entry| entry.path)
+            .filter(|path| {
+                let restored = !skipped_paths.contains(path)
+                    && resurrected_paths
+                        .iter()
+                        .all(|(old_path, _)| old_path != path);
+                future::ready(restored)
+            })
+            .collect()
+            .block_on()
+    } else {
+        vec![]
+    };
+    if &new_tree_id == to_commit.tree_id() && moved_paths.is_empty() {
         writeln!(ui.status(), "Nothing changed.")?;
     } else {
         let mut tx = workspace_command.start_transaction();
-        tx.repo_mut()
-            .rewrite_commit(&to_commit)
-            .set_tree_id(new_tree_id)
-            .write()?;
-        // rebase_descendants early; otherwise the new commit would always have
-        // a conflicted change id at this point.
-        let (num_rebased, extra_msg) = if args.restore_descendants {
-            (
-                tx.repo_mut().reparent_descendants()?,
-                " (while preserving their content)",
-            )
+        let extra_msg = if args.restore_descendants.is_some() {
+            " (while preserving their content)"
+        } else {
+            ""
+        };
+        let abandon_empty =
+            args.abandon_if_empty && new_tree_id == to_commit.parent_tree(tx.repo())?.id();
+        let num_rebased = if args.move_changes {
+            let new_tree = tx.repo().store().get_root_tree(&new_tree_id)?;
+            move_restored_changes(
+                tx.repo_mut(),
+                &from_commits[0],
+                &to_commit,
+                &new_tree,
+                &matcher,
+                &moved_paths,
+            )?
+        } else if abandon_empty {
+            let options = RewriteRefsOptions {
+                delete_abandoned_bookmarks: true,
+                ..Default::default()
+            };
+            let mut num_rebased = 0;
+            let mut reparented_commits = vec![];
+            tx.repo_mut().transform_descendants_with_options(
+                vec![to_commit.id().clone()],
+                &HashMap::new(),
+                &options,
+                |rewriter| {
+                    if rewriter.old_commit().id() == to_commit.id() {
+                        rewriter.abandon();
+                    } else if args.restore_descendants.is_some() {
+                        reparented_commits.push(rewriter.reparent().write()?);
+                        num_rebased += 1;
+                    } else {
+                        rewriter.rebase()?.write()?;
+                        num_rebased += 1;
+                    }
+                    Ok(())
+                },
+            )?;
+            if let Some(mode) = args.restore_descendants {
+                let diverged_merges = find_diverged_merges(tx.repo(), &reparented_commits)?;
+                mode.report_diverged_merges(ui, &tx, &diverged_merges)?;
+            }
+            num_rebased
+        } else {
+            tx.repo_mut()
+                .rewrite_commit(&to_commit)
+                .set_tree_id(new_tree_id)
+                .write()?;
+            // rebase_descendants early; otherwise the new commit would always
+            // have a conflicted change id at this point.
+            if let Some(mode) = args.restore_descendants {
+                let stats = tx.repo_mut().reparent_descendants_with_stats()?;
+                mode.report_diverged_merges(ui, &tx, &stats.diverged_merges)?;
+                stats.num_reparented
+            } else {
+                tx.repo_mut().rebase_descendants()?
+            }
+        };
+        let rewritten_ids = if args.move_changes {
+            vec![to_commit.id().clone(), from_commits[0].id().clone()]
         } else {
-            (tx.repo_mut().rebase_descendants()?, "")
+            vec![to_commit.id().clone()]
         };
+        args.checkpoint.set_bookmark(ui, &mut tx, &rewritten_ids)?;
         if let Some(mut formatter) = ui.status_formatter() {
+            if abandon_empty {
+                write!(formatter, "Abandoned commit ")?;
+                tx.base_workspace_helper()
+                    .write_commit_summary(formatter.as_mut(), &to_commit)?;
+                writeln!(formatter, " because it became empty")?;
+            }
             if num_rebased > 0 {
                 writeln!(
                     formatter,
@@ -182,7 +524,274 @@ pub(crate) fn cmd_restore(
                 )?;
             }
         }
-        tx.finish(ui, format!("restore into commit {}", to_commit.id().hex()))?;
+        let description = if args.move_changes {
+            format!(
+                "move changes from commit {} into commit {}",
+                from_commits[0].id().hex(),
+                to_commit.id().hex()
+            )
+        } else {
+            format!("restore into commit {}", to_commit.id().hex())
+        };
+        tx.finish_rewriting(
+            ui,
+            args.op_description.description_or(description),
+            &rewritten_ids,
+        )?;
     }
     Ok(())
 }
+
+/// Rewrites `to_commit` to `new_to_tree` and resets `moved_paths` in
+/// `from_commit` to the content of its parents, rebasing descendants once. If
+/// one of the commits is a descendant of the other, the change is applied to
+/// its rebased version. Returns the number of rebased descendants.
+fn move_restored_changes(
+    mut_repo: &mut MutableRepo,
+    from_commit: &Commit,
+    to_commit: &Commit,
+    new_to_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    moved_paths: &[RepoPathBuf],
+) -> Result<usize, CommandError> {
+    let moved_matcher = FilesMatcher::new(moved_paths);
+    let index = mut_repo.index();
+    let from_is_descendant = index.is_ancestor(to_commit.id(), from_commit.id());
+    let to_is_descendant = index.is_ancestor(from_commit.id(), to_commit.id());
+    let mut roots = vec![];
+    if !from_is_descendant {
+        let parent_tree = from_commit.parent_tree(mut_repo)?;
+        let new_from_tree_id = restore_tree(&parent_tree, &from_commit.tree()?, &moved_matcher)?;
+        mut_repo
+            .rewrite_commit(from_commit)
+            .set_tree_id(new_from_tree_id)
+            .write()?;
+        roots.push(from_commit.id().clone());
+    }
+    if !to_is_descendant {
+        mut_repo
+            .rewrite_commit(to_commit)
+            .set_tree_id(new_to_tree.id())
+            .write()?;
+        roots.push(to_commit.id().clone());
+    }
+    let store = mut_repo.store().clone();
+    let mut num_rebased = 0;
+    mut_repo.transform_descendants(roots, |mut rewriter| {
+        if !rewriter.parents_changed() {
+            return Ok(());
+        }
+        let old_commit_id = rewriter.old_commit().id().clone();
+        if old_commit_id == *from_commit.id() {
+            // Reset the moved paths relative to the rebased parents, which may
+            // already contain the moved changes.
+            let new_parents: Vec<Commit> = rewriter
+                .new_parents()
+                .iter()
+                .map(|id| store.get_commit(id))
+                .try_collect()?;
+            let parent_tree = merge_commit_trees(rewriter.mut_repo(), &new_parents)?;
+            let builder = rewriter.rebase()?;
+            let rebased_tree = store.get_root_tree(builder.tree_id())?;
+            let new_tree_id = restore_tree(&parent_tree, &rebased_tree, &moved_matcher)?;
+            builder.set_tree_id(new_tree_id).write()?;
+        } else if old_commit_id == *to_commit.id() {
+            // Rebasing may have removed the moved changes from the destination.
+            let builder = rewriter.rebase()?;
+            let rebased_tree = store.get_root_tree(builder.tree_id())?;
+            let new_tree_id = restore_tree(new_to_tree, &rebased_tree, matcher)?;
+            builder.set_tree_id(new_tree_id).write()?;
+        } else {
+            rewriter.rebase()?.write()?;
+            num_rebased += 1;
+        }
+        Ok(())
+    })?;
+    Ok(num_rebased)
+}
+
+/// Returns the paths if the `expression` only consists of path prefixes.
+fn prefix_paths(expression: &FilesetExpression) -> Option<Vec<RepoPathBuf>> {
+    let expressions: &[FilesetExpression] = match expression {
+        FilesetExpression::None => &[],
+        FilesetExpression::UnionAll(expressions) => expressions.as_slice(),
+        expression => slice::from_ref(expression),
+    };
+    expressions
+        .iter()
+        .map(|expression| match expression {
+            FilesetExpression::Pattern(FilePattern::PrefixPath(path)) => Some(path.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Parses a single `--map` argument into the source and destination paths.
+fn parse_path_mapping(
+    workspace_command: &WorkspaceCommandHelper,
+    mapping: &str,
+) -> Result<(RepoPathBuf, RepoPathBuf), CommandError> {
+    let hint = "For example, `--map old/config.toml=new/config.toml` is valid syntax";
+    let Some((source_str, dest_str)) = mapping.split_once('=') else {
+        return Err(cli_error(format!(
+            "Argument '{mapping}' must include '=' and have the form SRC_PATH=DEST_PATH"
+        ))
+        .hinted(hint));
+    };
+    if source_str.is_empty() || dest_str.is_empty() {
+        return Err(cli_error(format!(
+            "Argument '{mapping}' must have the form SRC_PATH=DEST_PATH, with both SRC_PATH and \
+             DEST_PATH non-empty"
+        ))
+        .hinted(hint));
+    }
+    let source_path = workspace_command.parse_file_path(source_str)?;
+    let dest_path = workspace_command.parse_file_path(dest_str)?;
+    Ok((source_path, dest_path))
+}
+
+/// Checks that `parent` is a parent of the merge commit `commit`.
+fn check_merge_parent(
+    workspace_command: &WorkspaceCommandHelper,
+    commit: &Commit,
+    parent: &Commit,
+) -> Result<(), CommandError> {
+    if commit.parent_ids().len() < 2 {
+        return Err(user_error(format!(
+            "--against can only be used with a merge commit, but {} has a single parent",
+            short_commit_hash(commit.id())
+        )));
+    }
+    if !commit.parent_ids().contains(parent.id()) {
+        let mut error = user_error(format!(
+            "Revision {} is not a parent of {}",
+            short_commit_hash(parent.id()),
+            short_commit_hash(commit.id())
+        ));
+        let parents: Vec<Commit> = commit.parents().try_collect()?;
+        error.add_formatted_hint_with(|formatter| {
+            write!(formatter, "The parents are:")?;
+            for parent in &parents {
+                write!(formatter, "\n  ")?;
+                workspace_command.write_commit_summary(formatter, parent)?;
+            }
+            Ok(())
+        });
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Returns `(old_path, new_path)` for paths which `new_tree` would recreate
+/// although `to_commit` renamed them to `new_path`, and `new_path` still exists
+/// in `new_tree`.
+///
+/// Renames are found in the copy records between `to_commit` and its parents.
+/// For backends without copy tracking, a deleted file whose content was added
+/// at another path is considered renamed.
+fn find_resurrected_renames(
+    repo: &dyn Repo,
+    to_commit: &Commit,
+    new_tree: &MergedTree,
+) -> Result<Vec<(RepoPathBuf, RepoPathBuf)>, CommandError> {
+    let to_tree = to_commit.tree()?;
+    let mut added_paths = vec![];
+    let entries: Vec<_> = to_tree
+        .diff_stream(new_tree, &EverythingMatcher)
+        .collect()
+        .block_on();
+    for entry in entries {
+        let (before, after) = entry.values?;
+        if before.is_absent() && after.is_present() {
+            added_paths.push(entry.path);
+        }
+    }
+    if added_paths.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let parent_tree = to_commit.parent_tree(repo)?;
+    let copy_records = collect_copy_records(
+        repo.store(),
+        to_commit.parent_ids(),
+        slice::from_ref(to_commit.id()),
+        CopyTracking::Track,
+    )?;
+    let mut added_in_destination = vec![];
+    let entries: Vec<_> = parent_tree
+        .diff_stream(&to_tree, &EverythingMatcher)
+        .collect()
+        .block_on();
+    for entry in entries {
+        let (before, after) = entry.values?;
+        if before.is_absent() && after.is_present() {
+            added_in_destination.push((entry.path, after));
+        }
+    }
+
+    let mut renames = vec![];
+    for old_path in added_paths {
+        let old_value = parent_tree.path_value(&old_path)?;
+        if old_value.is_absent() {
+            continue;
+        }
+        let new_path = if let Some(record) = copy_records.for_source(&old_path) {
+            Some(record.target.clone())
+        } else {
+            added_in_destination
+                .iter()
+                .find(|(_, value)| *value == old_value)
+                .map(|(path, _)| path.clone())
+        };
+        if let Some(new_path) = new_path {
+            if new_tree.path_value(&new_path)?.is_present() {
+                renames.push((old_path, new_path));
+            }
+        }
+    }
+    Ok(renames)
+}
+
+/// Returns the paths changed between `to_commit`'s tree and `new_tree` which
+/// would introduce new conflicts in any child of `to_commit` if it were
+/// rewritten to `new_tree`.
+fn find_paths_conflicting_in_children(
+    repo: &dyn Repo,
+    to_commit: &Commit,
+    new_tree: &MergedTree,
+) -> Result<Vec<RepoPathBuf>, CommandError> {
+    let to_tree = to_commit.tree()?;
+    let changed_paths: Vec<RepoPathBuf> = to_tree
+        .diff_stream(new_tree, &EverythingMatcher)
+        .map(|entry| entry.path)
+        .collect()
+        .block_on();
+    let children: Vec<Commit> = RevsetExpression::commit(to_commit.id().clone())
+        .children()
+        .evaluate(repo)?
+        .iter()
+        .commits(repo.store())
+        .try_collect()?;
+    let mut conflicting_paths = HashSet::new();
+    for child in &children {
+        let child_tree = child.tree()?;
+        let old_conflicts: HashSet<RepoPathBuf> =
+            child_tree.conflicts().map(|(path, _)| path).collect();
+        let rebased_tree = child_tree.merge(&to_tree, new_tree)?;
+        for (conflict_path, _) in rebased_tree.conflicts() {
+            if old_conflicts.contains(&conflict_path) {
+                continue;
+            }
+            // A file/directory conflict is reported at the directory, so
+            // compare ancestry in both directions.
+            conflicting_paths.extend(changed_paths.iter().filter(|path| {
+                path.starts_with(&conflict_path) || conflict_path.starts_with(path)
+            }));
+        }
+    }
+    Ok(changed_paths
+        .iter()
+        .filter(|path| conflicting_paths.contains(path))
+        .cloned()
+        .collect())
+}
@@ -199,6 +199,7 @@ pub(crate) fn cmd_revert(
             formatter.as_mut(),
             &tx.commit_summary_template(),
             &reverted_commits,
+            ui.term_width(),
         )?;
         if num_rebased > 0 {
             writeln!(formatter, "Rebased {num_rebased} descendant commits")?;
@@ -119,6 +119,7 @@ pub fn cmd_sign(ui: &mut Ui, command: &CommandHelper, args: &SignArgs) -> Result
                 formatter.as_mut(),
                 &tx.commit_summary_template(),
                 &signed_commits,
+                ui.term_width(),
             )?;
         }
     }
@@ -12,15 +12,33 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
 use std::io::Write as _;
+use std::path::Path;
+use std::slice;
 
 use clap_complete::ArgValueCompleter;
+use futures::StreamExt as _;
+use itertools::Itertools as _;
+use jj_lib::absorb;
+use jj_lib::backend::ChangeId;
 use jj_lib::backend::CommitId;
 use jj_lib::commit::Commit;
+use jj_lib::copies::CopyRecords;
+use jj_lib::copies::CopyTracking;
+use jj_lib::matchers::EverythingMatcher;
 use jj_lib::matchers::Matcher;
 use jj_lib::object_id::ObjectId as _;
+use jj_lib::op_store::OperationId;
 use jj_lib::repo::Repo as _;
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::revset::RevsetExpression;
 use jj_lib::rewrite::move_commits;
+use jj_lib::rewrite::rebase_commit_with_options;
+use jj_lib::rewrite::split_copy_records;
 use jj_lib::rewrite::CommitWithSelection;
 use jj_lib::rewrite::EmptyBehaviour;
 use jj_lib::rewrite::MoveCommitsLocation;
@@ -28,21 +46,32 @@ use jj_lib::rewrite::MoveCommitsTarget;
 use jj_lib::rewrite::RebaseOptions;
 use jj_lib::rewrite::RebasedCommit;
 use jj_lib::rewrite::RewriteRefsOptions;
+use pollster::FutureExt as _;
 use tracing::instrument;
 
 use crate::cli_util::compute_commit_location;
+use crate::cli_util::short_change_hash;
+use crate::cli_util::short_commit_hash;
+use crate::cli_util::write_elided_line;
 use crate::cli_util::CommandHelper;
 use crate::cli_util::DiffSelector;
+use crate::cli_util::OpDescriptionArgs;
 use crate::cli_util::RevisionArg;
 use crate::cli_util::WorkspaceCommandHelper;
 use crate::cli_util::WorkspaceCommandTransaction;
+use crate::command_error::internal_error_with_message;
+use crate::command_error::user_error;
 use crate::command_error::user_error_with_hint;
 use crate::command_error::CommandError;
 use crate::complete;
-use crate::description_util::add_trailers;
-use crate::description_util::description_template;
 use crate::description_util::edit_description;
 use crate::description_util::join_message_paragraphs;
+use crate::description_util::DescriptionTemplates;
+use crate::diff_util::collect_copy_records;
+use crate::diff_util::show_diff_stats;
+use crate::diff_util::DiffStatOptions;
+use crate::diff_util::DiffStats;
+use crate::selection_plan::SelectionPlanArgs;
 use crate::ui::Ui;
 
 /// Split a revision in two
@@ -60,6 +89,13 @@ use crate::ui::Ui;
 /// description, the remaining changes will not get a description, and you will
 /// be asked for a description only for the selected changes.
 ///
+/// With `--into`, the selected changes are moved into an existing revision
+/// instead of a new one, and the remaining changes stay in the split revision.
+///
+/// If the description editor fails, the selected changes are saved, and the
+/// split can be resumed with `jj split --continue` as long as the repository
+/// hasn't changed in the meantime.
+///
 /// Splitting an empty commit is not supported because the same effect can be
 /// achieved with `jj new`.
 #[derive(clap::Args, Clone, Debug)]
@@ -72,6 +108,8 @@ pub(crate) struct SplitArgs {
     /// Specify diff editor to be used (implies --interactive)
     #[arg(long, value_name = "NAME")]
     tool: Option<String>,
+    #[command(flatten)]
+    selection_plan: SelectionPlanArgs,
     /// The revision to split
     #[arg(
         long, short,
@@ -124,6 +162,79 @@ pub(crate) struct SplitArgs {
     /// child
     #[arg(long, short)]
     parallel: bool,
+    /// Move the selected changes into an existing revision
+    ///
+    /// The selected changes are applied to the given revision, and the
+    /// remaining changes are kept in the split revision. The description of
+    /// the given revision is kept unchanged unless `--append-message` is used.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "destination",
+            "insert_after",
+            "insert_before",
+            "parallel",
+            "message_paragraphs",
+        ],
+        value_name = "REVSET",
+        add = ArgValueCompleter::new(complete::revset_expression_mutable),
+    )]
+    into: Option<RevisionArg>,
+    /// Append the description of the split revision to the description of
+    /// the `--into` revision
+    #[arg(long, requires = "into")]
+    append_message: bool,
+    /// Don't rebase the descendants of the split revision
+    ///
+    /// The descendants are left on the original revision, which stays visible
+    /// as their parent and becomes divergent with the revision keeping its
+    /// change ID. Bookmarks and working copies are still moved to the new
+    /// revisions. Use `jj rebase` to move the descendants later.
+    #[arg(
+        long,
+        conflicts_with_all = ["destination", "insert_after", "insert_before", "into"],
+    )]
+    no_rebase_descendants: bool,
+    /// Treat renamed files as a whole when selecting changes by filesets
+    ///
+    /// A renamed file is selected if either its old or new path is matched by
+    /// the filesets. The selected changes then contain the changes to the
+    /// file's content at its old path, and the rename is left in the remaining
+    /// changes.
+    #[arg(long)]
+    track_renames: bool,
+    /// Mark the remaining changes as a fixup of the given revision
+    ///
+    /// A `Fixup-Of` trailer with the change ID of the given revision is added
+    /// to the description of the commit with the remaining changes, so they
+    /// can be squashed into that revision later.
+    #[arg(
+        long,
+        conflicts_with = "into",
+        value_name = "REVSET",
+        add = ArgValueCompleter::new(complete::revset_expression_mutable),
+    )]
+    mark_remainder_fixup_of: Option<RevisionArg>,
+    /// Continue a split that was interrupted while editing the descriptions
+    ///
+    /// The changes selected by the interrupted split are reused, and only the
+    /// descriptions are prompted for.
+    #[arg(
+        long = "continue",
+        conflicts_with_all = [
+            "interactive",
+            "tool",
+            "selection_plan",
+            "message_paragraphs",
+            "into",
+            "paths",
+        ],
+    )]
+    continue_split: bool,
+    /// Show the changes that would be selected and remaining, without
+    /// splitting the revision
+    #[arg(long, requires = "paths", conflicts_with_all = ["interactive", "tool"])]
+    dry_run: bool,
     /// Files matching any of these filesets are put in the selected changes
     #[arg(
         value_name = "FILESETS",
@@ -131,6 +242,8 @@ pub(crate) struct SplitArgs {
         add = ArgValueCompleter::new(complete::modified_revision_files),
     )]
     paths: Vec<String>,
+    #[command(flatten)]
+    op_description: OpDescriptionArgs,
 }
 
 impl SplitArgs {
@@ -151,15 +264,42 @@ impl SplitArgs {
                 "Use `jj new` if you want to create another empty commit.",
             ));
         }
-        workspace_command.check_rewritable([target_commit.id()])?;
+        let into_commit = self
+            .into
+            .as_ref()
+            .map(|into| workspace_command.resolve_single_rev(ui, into))
+            .transpose()?;
+        if let Some(into_commit) = &into_commit {
+            if into_commit.id() == target_commit.id() {
+                return Err(user_error(
+                    "Cannot move the selected changes into the split revision",
+                ));
+            }
+            workspace_command.check_rewritable([target_commit.id(), into_commit.id()])?;
+        } else {
+            workspace_command.check_rewritable([target_commit.id()])?;
+        }
+        let fixup_target = self
+            .mark_remainder_fixup_of
+            .as_ref()
+            .map(|target| workspace_command.resolve_single_rev(ui, target))
+            .transpose()?;
         let matcher = workspace_command
             .parse_file_patterns(ui, &self.paths)?
             .to_matcher();
-        let diff_selector = workspace_command.diff_selector(
+        let diff_selector = workspace_command.diff_selector_with_plan(
             ui,
             self.tool.as_deref(),
-            self.interactive || self.paths.is_empty(),
+            !self.continue_split && (self.interactive || self.paths.is_empty()),
+            &self.selection_plan,
         )?;
+        // Renames are only paired up when the changes aren't selected
+        // interactively.
+        let copy_tracking = if self.track_renames && !diff_selector.is_interactive() {
+            CopyTracking::Track
+        } else {
+            CopyTracking::None
+        };
         let use_move_flags = self.destination.is_some()
             || self.insert_after.is_some()
             || self.insert_before.is_some();
@@ -179,10 +319,13 @@ impl SplitArgs {
             target_commit,
             matcher,
             diff_selector,
+            copy_tracking,
             parallel: self.parallel,
             use_move_flags,
             new_parent_ids,
             new_child_ids,
+            into_commit,
+            fixup_target,
         })
     }
 }
@@ -191,10 +334,13 @@ struct ResolvedSplitArgs {
     target_commit: Commit,
     matcher: Box<dyn Matcher>,
     diff_selector: DiffSelector,
+    copy_tracking: CopyTracking,
     parallel: bool,
     use_move_flags: bool,
     new_parent_ids: Vec<CommitId>,
     new_child_ids: Vec<CommitId>,
+    into_commit: Option<Commit>,
+    fixup_target: Option<Commit>,
 }
 
 #[instrument(skip_all)]
@@ -208,93 +354,148 @@ pub(crate) fn cmd_split(
         target_commit,
         matcher,
         diff_selector,
+        copy_tracking,
         parallel,
         use_move_flags,
         new_parent_ids,
         new_child_ids,
+        into_commit,
+        fixup_target,
     } = args.resolve(ui, &workspace_command)?;
     let text_editor = workspace_command.text_editor()?;
+    let saved_selection_path = workspace_command.repo_path().join(SAVED_SELECTION_FILE);
+
+    let target = if args.continue_split {
+        load_selection(&workspace_command, &saved_selection_path, &target_commit)?
+    } else {
+        // Prompt the user to select the changes they want for the first commit.
+        select_diff(
+            ui,
+            &workspace_command,
+            &target_commit,
+            &matcher,
+            &diff_selector,
+            copy_tracking,
+        )?
+    };
+    if args.dry_run {
+        return show_dry_run(ui, &workspace_command, &target);
+    }
+    if matches!(diff_selector, DiffSelector::NonInteractive) && !args.continue_split {
+        report_selected_files(ui, &workspace_command, &target)?;
+    }
     let mut tx = workspace_command.start_transaction();
 
-    // Prompt the user to select the changes they want for the first commit.
-    let target = select_diff(ui, &tx, &target_commit, &matcher, &diff_selector)?;
-
-    // Create the first commit, which includes the changes selected by the user.
-    let first_commit = {
-        let mut commit_builder = tx.repo_mut().rewrite_commit(&target.commit).detach();
-        commit_builder.set_tree_id(target.selected_tree.id());
-        if use_move_flags {
-            commit_builder
-                // Generate a new change id so that the commit being split doesn't
-                // become divergent.
-                .generate_new_change_id();
-        }
-        let description = if !args.message_paragraphs.is_empty() {
+    if let Some(into_commit) = into_commit {
+        return split_into(
+            ui,
+            tx,
+            &target,
+            &into_commit,
+            args.append_message,
+            &args.op_description,
+        );
+    }
+
+    // Prepare the first commit, which includes the changes selected by the
+    // user.
+    let mut first_builder = tx.repo_mut().rewrite_commit(&target.commit).detach();
+    first_builder.set_tree_id(target.selected_tree.id());
+    if use_move_flags {
+        first_builder
+            // Generate a new change id so that the commit being split doesn't
+            // become divergent.
+            .generate_new_change_id();
+    }
+
+    // Prepare the second commit, which includes everything the user didn't
+    // select.
+    let target_tree = target.commit.tree()?;
+    let second_tree = if parallel {
+        // Merge the original commit tree with its parent using the tree
+        // containing the user selected changes as the base for the merge.
+        // This results in a tree with the changes the user didn't select.
+        target_tree.merge(&target.selected_tree, &target.parent_tree)?
+    } else {
+        target_tree
+    };
+    let mut second_builder = tx.repo_mut().rewrite_commit(&target.commit).detach();
+    second_builder.set_tree_id(second_tree.id());
+    if !use_move_flags {
+        second_builder
+            // Generate a new change id so that the commit being split doesn't
+            // become divergent.
+            .generate_new_change_id();
+    }
+
+    // Save the selection before prompting for descriptions so it isn't lost if
+    // the editor fails.
+    let edit_split_description = |template: &str| {
+        edit_description(&text_editor, template)
+            .map_err(|err| err.hinted("Run `jj split --continue` to reuse the selected changes."))
+    };
+    if args.message_paragraphs.is_empty() {
+        let selection_commit = first_builder.write_hidden()?;
+        save_selection(&tx, &saved_selection_path, &target, &selection_commit)?;
+    }
+
+    // Both descriptions are generated before writing the commits, so the
+    // description templates are parsed only once.
+    let second_description = {
+        let templates = DescriptionTemplates::new(&tx);
+        let first_description = if !args.message_paragraphs.is_empty() {
             let description = join_message_paragraphs(&args.message_paragraphs);
             if !description.is_empty() {
-                commit_builder.set_description(description);
-                add_trailers(ui, &tx, &commit_builder)?
+                first_builder.set_description(description);
+                templates.add_trailers(ui, &first_builder)?
             } else {
                 description
             }
         } else {
-            let new_description = add_trailers(ui, &tx, &commit_builder)?;
-            commit_builder.set_description(new_description);
-            let temp_commit = commit_builder.write_hidden()?;
+            let new_description = templates.add_trailers(ui, &first_builder)?;
+            first_builder.set_description(new_description);
+            let temp_commit = first_builder.write_hidden()?;
             let intro = "Enter a description for the selected changes.";
-            let template = description_template(ui, &tx, intro, &temp_commit)?;
-            edit_description(&text_editor, &template)?
+            let template = templates.description_template(ui, intro, &temp_commit)?;
+            edit_split_description(&template)?
         };
-        commit_builder.set_description(description);
-        commit_builder.write(tx.repo_mut())?
-    };
+        first_builder.set_description(first_description);
 
-    // Create the second commit, which includes everything the user didn't
-    // select.
-    let second_commit = {
-        let target_tree = target.commit.tree()?;
-        let new_tree = if parallel {
-            // Merge the original commit tree with its parent using the tree
-            // containing the user selected changes as the base for the merge.
-            // This results in a tree with the changes the user didn't select.
-            target_tree.merge(&target.selected_tree, &target.parent_tree)?
-        } else {
-            target_tree
-        };
-        let parents = if parallel {
-            target.commit.parent_ids().to_vec()
-        } else {
-            vec![first_commit.id().clone()]
-        };
-        let mut commit_builder = tx.repo_mut().rewrite_commit(&target.commit).detach();
-        commit_builder
-            .set_parents(parents)
-            .set_tree_id(new_tree.id());
-        if !use_move_flags {
-            commit_builder
-                // Generate a new change id so that the commit being split doesn't
-                // become divergent.
-                .generate_new_change_id();
-        }
-        let description = if target.commit.description().is_empty() {
+        if target.commit.description().is_empty() {
             // If there was no description before, don't ask for one for the
             // second commit.
             "".to_string()
         } else if !args.message_paragraphs.is_empty() {
             // Just keep the original message unchanged
-            commit_builder.description().to_owned()
+            second_builder.description().to_owned()
         } else {
-            let new_description = add_trailers(ui, &tx, &commit_builder)?;
-            commit_builder.set_description(new_description);
-            let temp_commit = commit_builder.write_hidden()?;
+            if !parallel {
+                let temp_first_commit = first_builder.write_hidden()?;
+                second_builder.set_parents(vec![temp_first_commit.id().clone()]);
+            }
+            let new_description = templates.add_trailers(ui, &second_builder)?;
+            second_builder.set_description(new_description);
+            let temp_commit = second_builder.write_hidden()?;
             let intro = "Enter a description for the remaining changes.";
-            let template = description_template(ui, &tx, intro, &temp_commit)?;
-            edit_description(&text_editor, &template)?
-        };
-        commit_builder.set_description(description);
-        commit_builder.write(tx.repo_mut())?
+            let template = templates.description_template(ui, intro, &temp_commit)?;
+            edit_split_description(&template)?
+        }
     };
 
+    let first_commit = first_builder.write(tx.repo_mut())?;
+    if !parallel {
+        second_builder.set_parents(vec![first_commit.id().clone()]);
+    }
+    if let Some(fixup_target) = &fixup_target {
+        second_builder.set_description(absorb::add_fixup_trailer(
+            &second_description,
+            fixup_target.change_id(),
+        ));
+    } else {
+        second_builder.set_description(second_description);
+    }
+    let second_commit = second_builder.write(tx.repo_mut())?;
+
     let (first_commit, second_commit, num_rebased) = if use_move_flags {
         move_first_commit(
             &mut tx,
@@ -304,20 +505,253 @@ pub(crate) fn cmd_split(
             new_parent_ids,
             new_child_ids,
         )?
+    } else if args.no_rebase_descendants {
+        leave_descendants(&mut tx, &target, first_commit, second_commit)?
     } else {
         rewrite_descendants(&mut tx, &target, first_commit, second_commit, parallel)?
     };
+    // Records whose sources aren't in the new parents, such as when the first
+    // commit was moved elsewhere, are dropped.
+    split_copy_records(tx.repo(), &target.commit, &first_commit, &second_commit)?;
+    if let Some(mut formatter) = ui.status_formatter() {
+        if num_rebased > 0 {
+            writeln!(formatter, "Rebased {num_rebased} descendant commits")?;
+        }
+        write_elided_line(formatter.as_mut(), ui.term_width(), |formatter| {
+            write!(formatter, "Selected changes : ")?;
+            tx.write_commit_summary(formatter, &first_commit)
+        })?;
+        write_elided_line(formatter.as_mut(), ui.term_width(), |formatter| {
+            write!(formatter, "Remaining changes: ")?;
+            tx.write_commit_summary(formatter, &second_commit)
+        })?;
+    }
+    let target_has_children = args.no_rebase_descendants
+        && !RevsetExpression::commit(target.commit.id().clone())
+            .children()
+            .evaluate(tx.base_repo().as_ref())?
+            .is_empty();
+    tx.finish(
+        ui,
+        args.op_description
+            .description_or(format!("split commit {}", target.commit.id().hex())),
+    )?;
+    if target_has_children {
+        let target_hash = short_commit_hash(target.commit.id());
+        let destinations = if parallel {
+            format!(
+                "-d {} -d {}",
+                short_commit_hash(first_commit.id()),
+                short_commit_hash(second_commit.id())
+            )
+        } else {
+            format!("-d {}", short_commit_hash(second_commit.id()))
+        };
+        writeln!(
+            ui.hint_default(),
+            "The descendants were left on the original revision {target_hash}. Run `jj rebase \
+             -s 'children({target_hash})' {destinations}` to move them onto the split revisions.",
+        )?;
+    }
+    remove_saved_selection(&saved_selection_path)?;
+    Ok(())
+}
+
+/// Name of the file in the repo directory where the selection of a split is
+/// saved while the descriptions are edited.
+const SAVED_SELECTION_FILE: &str = "split_selection";
+
+/// Saves the selected changes of the `target` commit, so they can be reused by
+/// `jj split --continue` if the split is interrupted.
+///
+/// The selected tree is kept in the hidden `selection_commit`. The selection
+/// is only valid at the current operation.
+fn save_selection(
+    tx: &WorkspaceCommandTransaction,
+    path: &Path,
+    target: &CommitWithSelection,
+    selection_commit: &Commit,
+) -> Result<(), CommandError> {
+    let content = format!(
+        "{}\n{}\n{}\n",
+        tx.base_repo().op_id().hex(),
+        target.commit.change_id().hex(),
+        selection_commit.id().hex()
+    );
+    fs::write(path, content)
+        .map_err(|err| internal_error_with_message("Failed to save the split selection", err))
+}
+
+/// Loads the selection saved by an interrupted split of the `target_commit`.
+fn load_selection(
+    workspace_command: &WorkspaceCommandHelper,
+    path: &Path,
+    target_commit: &Commit,
+) -> Result<CommitWithSelection, CommandError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(user_error("There is no interrupted split to continue"));
+        }
+        Err(err) => {
+            return Err(internal_error_with_message(
+                "Failed to read the split selection",
+                err,
+            ));
+        }
+    };
+    let parse = || -> Option<(OperationId, ChangeId, CommitId)> {
+        let (op_id, change_id, commit_id) = content.lines().collect_tuple()?;
+        Some((
+            OperationId::try_from_hex(op_id).ok()?,
+            ChangeId::try_from_hex(change_id).ok()?,
+            CommitId::try_from_hex(commit_id).ok()?,
+        ))
+    };
+    let Some((op_id, change_id, selection_commit_id)) = parse() else {
+        return Err(user_error("The saved split selection is invalid"));
+    };
+    let repo = workspace_command.repo();
+    if &op_id != repo.op_id() {
+        return Err(user_error_with_hint(
+            "Cannot continue the split because the repository has changed since it was \
+             interrupted",
+            "Run `jj split` again to select the changes.",
+        ));
+    }
+    if &change_id != target_commit.change_id() {
+        return Err(user_error(format!(
+            "Cannot continue the split because it was interrupted while splitting a different \
+             revision: {}",
+            short_change_hash(&change_id)
+        )));
+    }
+    let selection_commit = repo.store().get_commit(&selection_commit_id)?;
+    Ok(CommitWithSelection {
+        commit: target_commit.clone(),
+        selected_tree: selection_commit.tree()?,
+        parent_tree: target_commit.parent_tree(repo.as_ref())?,
+    })
+}
+
+fn remove_saved_selection(path: &Path) -> Result<(), CommandError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(internal_error_with_message(
+            "Failed to remove the split selection",
+            err,
+        )),
+    }
+}
+
+/// Moves the selected changes into the `destination` commit, keeping the
+/// remaining changes in the split commit.
+fn split_into(
+    ui: &Ui,
+    mut tx: WorkspaceCommandTransaction,
+    target: &CommitWithSelection,
+    destination: &Commit,
+    append_message: bool,
+    op_description: &OpDescriptionArgs,
+) -> Result<(), CommandError> {
+    if target.is_empty_selection() {
+        return Err(user_error("No changes selected"));
+    }
+    // Remove the selected changes from the split commit.
+    let target_tree = target.commit.tree()?;
+    let remaining_tree = target_tree.merge(&target.selected_tree, &target.parent_tree)?;
+    let mut remaining_commit = tx
+        .repo_mut()
+        .rewrite_commit(&target.commit)
+        .set_tree_id(remaining_tree.id())
+        .write()?;
+
+    // Descendants may be rebased twice, so count them by change id.
+    let mut rebased_change_ids = HashSet::new();
+    let mut rewritten_destination = destination.clone();
+    if tx
+        .repo()
+        .index()
+        .is_ancestor(target.commit.id(), destination.id())
+    {
+        // If the destination is a descendant, rebase it first. Otherwise it
+        // would still contain the selected changes, and applying them again
+        // would have no effect.
+        tx.repo_mut().rebase_descendants_with_options(
+            &RebaseOptions::default(),
+            |old_commit, rebased_commit| {
+                let RebasedCommit::Rewritten(new_commit) = rebased_commit else {
+                    return;
+                };
+                if old_commit.id() == destination.id() {
+                    rewritten_destination = new_commit;
+                } else {
+                    rebased_change_ids.insert(new_commit.change_id().clone());
+                }
+            },
+        )?;
+    }
+
+    // Apply the selected changes onto the destination.
+    let destination_tree = rewritten_destination
+        .tree()?
+        .merge(&target.parent_tree, &target.selected_tree)?;
+    let mut commit_builder = tx
+        .repo_mut()
+        .rewrite_commit(&rewritten_destination)
+        .set_tree_id(destination_tree.id())
+        .set_predecessors(vec![destination.id().clone(), target.commit.id().clone()]);
+    if append_message && !target.commit.description().is_empty() {
+        let description = if destination.description().is_empty() {
+            target.commit.description().to_owned()
+        } else {
+            join_message_paragraphs(&[
+                destination.description().to_owned(),
+                target.commit.description().to_owned(),
+            ])
+        };
+        commit_builder = commit_builder.set_description(description);
+    }
+    let mut new_destination = commit_builder.write()?;
+
+    tx.repo_mut().rebase_descendants_with_options(
+        &RebaseOptions::default(),
+        |old_commit, rebased_commit| {
+            let RebasedCommit::Rewritten(new_commit) = rebased_commit else {
+                return;
+            };
+            if old_commit.id() == remaining_commit.id() {
+                remaining_commit = new_commit;
+            } else if old_commit.id() == new_destination.id() {
+                new_destination = new_commit;
+            } else {
+                rebased_change_ids.insert(new_commit.change_id().clone());
+            }
+        },
+    )?;
+    let num_rebased = rebased_change_ids.len();
     if let Some(mut formatter) = ui.status_formatter() {
         if num_rebased > 0 {
             writeln!(formatter, "Rebased {num_rebased} descendant commits")?;
         }
-        write!(formatter, "Selected changes : ")?;
-        tx.write_commit_summary(formatter.as_mut(), &first_commit)?;
-        write!(formatter, "\nRemaining changes: ")?;
-        tx.write_commit_summary(formatter.as_mut(), &second_commit)?;
-        writeln!(formatter)?;
+        write_elided_line(formatter.as_mut(), ui.term_width(), |formatter| {
+            write!(formatter, "Selected changes : ")?;
+            tx.write_commit_summary(formatter, &new_destination)
+        })?;
+        write_elided_line(formatter.as_mut(), ui.term_width(), |formatter| {
+            write!(formatter, "Remaining changes: ")?;
+            tx.write_commit_summary(formatter, &remaining_commit)
+        })?;
     }
-    tx.finish(ui, format!("split commit {}", target.commit.id().hex()))?;
+    tx.finish(
+        ui,
+        op_description.description_or(format!(
+            "split commit {} into commit {}",
+            target.commit.id().hex(),
+            destination.id().hex()
+        )),
+    )?;
     Ok(())
 }
 
@@ -360,8 +794,10 @@ fn move_first_commit(
             empty: EmptyBehaviour::Keep,
             rewrite_refs: RewriteRefsOptions {
                 delete_abandoned_bookmarks: false,
+                ..Default::default()
             },
             simplify_ancestor_merge: false,
+            ..Default::default()
         },
     )?;
 
@@ -395,7 +831,7 @@ fn rewrite_descendants(
     second_commit: Commit,
     parallel: bool,
 ) -> Result<(Commit, Commit, usize), CommandError> {
-    let legacy_bookmark_behavior = tx.settings().get_bool("split.legacy-bookmark-behavior")?;
+    let legacy_bookmark_behavior = tx.settings().split_legacy_bookmark_behavior()?;
     if legacy_bookmark_behavior {
         // Mark the commit being split as rewritten to the second commit. This
         // moves any bookmarks pointing to the target commit to the second
@@ -403,6 +839,18 @@ fn rewrite_descendants(
         tx.repo_mut()
             .set_rewritten_commit(target.commit.id().clone(), second_commit.id().clone());
     }
+    // Children of the target get both new commits as parents in parallel mode,
+    // which is redundant if one of their other parents already descends from
+    // the target.
+    let options = RebaseOptions {
+        empty: EmptyBehaviour::Keep,
+        rewrite_refs: RewriteRefsOptions {
+            delete_abandoned_bookmarks: false,
+            ..Default::default()
+        },
+        simplify_ancestor_merge: parallel,
+        ..Default::default()
+    };
     let mut num_rebased = 0;
     tx.repo_mut()
         .transform_descendants(vec![target.commit.id().clone()], |mut rewriter| {
@@ -416,7 +864,7 @@ fn rewrite_descendants(
             } else {
                 rewriter.replace_parent(first_commit.id(), [second_commit.id()]);
             }
-            rewriter.rebase()?.write()?;
+            rebase_commit_with_options(rewriter, &options)?;
             Ok(())
         })?;
     // Move the working copy commit (@) to the second commit for any workspaces
@@ -430,17 +878,58 @@ fn rewrite_descendants(
     Ok((first_commit, second_commit, num_rebased))
 }
 
+/// Records the split without rebasing the descendants of the target commit.
+///
+/// The target is recorded as divergently rewritten, so its descendants are
+/// left in place while bookmarks and working copies still follow the split.
+fn leave_descendants(
+    tx: &mut WorkspaceCommandTransaction,
+    target: &CommitWithSelection,
+    first_commit: Commit,
+    second_commit: Commit,
+) -> Result<(Commit, Commit, usize), CommandError> {
+    let legacy_bookmark_behavior = tx.settings().split_legacy_bookmark_behavior()?;
+    // Bookmarks pointing to the target commit are moved to a single commit,
+    // as they would be if the descendants were rebased.
+    let new_id = if legacy_bookmark_behavior {
+        second_commit.id()
+    } else {
+        first_commit.id()
+    };
+    tx.repo_mut()
+        .set_divergent_rewrite(target.commit.id().clone(), [new_id.clone()]);
+    for (name, working_copy_commit) in tx.base_repo().clone().view().wc_commit_ids() {
+        if working_copy_commit == target.commit.id() {
+            tx.repo_mut().edit(name.clone(), &second_commit)?;
+        }
+    }
+
+    Ok((first_commit, second_commit, 0))
+}
+
 /// Prompts the user to select the content they want in the first commit and
 /// returns the target commit and the tree corresponding to the selection.
 fn select_diff(
     ui: &Ui,
-    tx: &WorkspaceCommandTransaction,
+    workspace_command: &WorkspaceCommandHelper,
     target_commit: &Commit,
     matcher: &dyn Matcher,
     diff_selector: &DiffSelector,
+    copy_tracking: CopyTracking,
 ) -> Result<CommitWithSelection, CommandError> {
+    let repo = workspace_command.repo();
+    let parent_tree = target_commit.parent_tree(repo.as_ref())?;
+    let target_tree = target_commit.tree()?;
+    // External diff editors may not be able to select binary or large files
+    // as a whole, so they're listed in the instructions.
+    let binary_paths = match diff_selector {
+        DiffSelector::Interactive { editor, .. } if !editor.is_builtin() => {
+            editor.find_binary_files(&parent_tree, &target_tree, matcher)?
+        }
+        _ => vec![],
+    };
     let format_instructions = || {
-        format!(
+        let mut instructions = format!(
             "\
 You are splitting a commit into two: {}
 
@@ -450,19 +939,87 @@ Adjust the right side until it shows the contents you want to split into the
 new commit.
 The changes that are not selected will replace the original commit.
 ",
-            tx.format_commit_summary(target_commit)
-        )
+            workspace_command.format_commit_summary(target_commit)
+        );
+        if !binary_paths.is_empty() {
+            instructions.push_str(
+                "
+The following files are binary or large. Copy or revert each of them as a
+whole:
+",
+            );
+            for path in &binary_paths {
+                let path = workspace_command.format_file_path(path);
+                writeln!(instructions, "  {path}").unwrap();
+            }
+        }
+        instructions
     };
-    let parent_tree = target_commit.parent_tree(tx.repo())?;
-    let selected_tree_id = diff_selector.select(
-        &parent_tree,
-        &target_commit.tree()?,
-        matcher,
-        format_instructions,
+    // Conflicts can't be split by hunk without breaking their structure, so
+    // the builtin editor selects them as whole files.
+    let mut conflicted_paths = vec![];
+    if matches!(diff_selector, DiffSelector::Interactive { editor, .. } if editor.is_builtin()) {
+        for (path, value) in target_tree.conflicts() {
+            if matcher.matches(&path) && value? != parent_tree.path_value(&path)? {
+                conflicted_paths.push(path);
+            }
+        }
+    }
+    if !conflicted_paths.is_empty() {
+        writeln!(
+            ui.warning_default(),
+            "The revision has conflicts in {} paths, which can only be split as whole files:",
+            conflicted_paths.len()
+        )?;
+        for path in &conflicted_paths {
+            writeln!(
+                ui.warning_no_heading(),
+                "  {}",
+                workspace_command.format_file_path(path)
+            )?;
+        }
+    }
+    if !binary_paths.is_empty() {
+        writeln!(
+            ui.warning_default(),
+            "The revision has {} binary or large files, which the diff editor may not be able \
+             to split as whole files:",
+            binary_paths.len()
+        )?;
+        for path in &binary_paths {
+            writeln!(
+                ui.warning_no_heading(),
+                "  {}",
+                workspace_command.format_file_path(path)
+            )?;
+        }
+        writeln!(
+            ui.hint_default(),
+            "Use the builtin diff editor (`--tool :builtin`) to select them as whole files."
+        )?;
+    }
+    // The selection restores the target tree into the parent tree, so the
+    // copy records are collected in that direction.
+    let copy_records = collect_copy_records(
+        repo.store(),
+        slice::from_ref(target_commit.id()),
+        target_commit.parent_ids(),
+        copy_tracking,
     )?;
+    let selected_tree_id = diff_selector
+        .clone()
+        .with_whole_file_conflicts()
+        .select_with_copies(
+            ui,
+            &parent_tree,
+            &target_tree,
+            matcher,
+            &copy_records,
+            format_instructions,
+        )?;
     let selection = CommitWithSelection {
         commit: target_commit.clone(),
-        selected_tree: tx.repo().store().get_root_tree(&selected_tree_id)?,
+        selected_tree: repo.store().get_root_tree(&selected_tree_id)?,
         parent_tree,
     };
     if selection.is_full_selection() {
@@ -479,3 +1036,111 @@ The changes that are not selected will replace the original commit.
 
     Ok(selection)
 }
+
+/// Maximum number of selected files listed before the split.
+const MAX_LISTED_SELECTED_FILES: usize = 10;
+
+/// Lists the files changed by the selection, and asks for confirmation if more
+/// files than `split.confirm-threshold` were selected.
+///
+/// The files are computed from the diff of the selection, so files that are
+/// matched by the filesets but not changed by the commit aren't listed.
+fn report_selected_files(
+    ui: &Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    target: &CommitWithSelection,
+) -> Result<(), CommandError> {
+    let selected_paths: Vec<RepoPathBuf> = target
+        .parent_tree
+        .diff_stream(&target.selected_tree, &EverythingMatcher)
+        .map(|entry| entry.path)
+        .collect()
+        .block_on();
+    if selected_paths.is_empty() {
+        return Ok(());
+    }
+    if let Some(mut formatter) = ui.status_formatter() {
+        writeln!(
+            formatter,
+            "Selected {} changed {}:",
+            selected_paths.len(),
+            if selected_paths.len() == 1 {
+                "file"
+            } else {
+                "files"
+            }
+        )?;
+        for path in selected_paths.iter().take(MAX_LISTED_SELECTED_FILES) {
+            writeln!(formatter, "  {}", workspace_command.format_file_path(path))?;
+        }
+        if selected_paths.len() > MAX_LISTED_SELECTED_FILES {
+            writeln!(
+                formatter,
+                "  ... and {} more",
+                selected_paths.len() - MAX_LISTED_SELECTED_FILES
+            )?;
+        }
+    }
+    let threshold = workspace_command.settings().split_confirm_threshold()?;
+    if selected_paths.len() <= threshold {
+        return Ok(());
+    }
+    if !Ui::can_prompt() {
+        return Err(user_error_with_hint(
+            format!(
+                "Refusing to split {} selected files without confirmation (the threshold is \
+                 {threshold})",
+                selected_paths.len()
+            ),
+            "Use `--dry-run` to review the selection, and raise `split.confirm-threshold` to \
+             split anyway.",
+        ));
+    }
+    let prompt = format!("Split {} selected files?", selected_paths.len());
+    if !ui.prompt_yes_no(&prompt, None)? {
+        return Err(user_error("Split cancelled"));
+    }
+    Ok(())
+}
+
+/// Prints the diff stats of the selected and remaining changes without
+/// splitting the revision.
+fn show_dry_run(
+    ui: &mut Ui,
+    workspace_command: &WorkspaceCommandHelper,
+    target: &CommitWithSelection,
+) -> Result<(), CommandError> {
+    let target_tree = target.commit.tree()?;
+    let store = workspace_command.repo().store();
+    let conflict_marker_style = workspace_command.env().conflict_marker_style();
+    let copy_records = CopyRecords::default();
+    let width = ui.term_width();
+    ui.request_pager();
+    let mut formatter = ui.stdout_formatter();
+    for (heading, from_tree, to_tree) in [
+        (
+            "Selected changes:",
+            &target.parent_tree,
+            &target.selected_tree,
+        ),
+        ("Remaining changes:", &target.selected_tree, &target_tree),
+    ] {
+        writeln!(formatter, "{heading}")?;
+        let tree_diff =
+            from_tree.diff_stream_with_copies(to_tree, &EverythingMatcher, &copy_records);
+        let stats = DiffStats::calculate(
+            store,
+            tree_diff,
+            &DiffStatOptions::default(),
+            conflict_marker_style,
+        )
+        .block_on()?;
+        show_diff_stats(
+            formatter.as_mut(),
+            &stats,
+            workspace_command.path_converter(),
+            width,
+        )?;
+    }
+    Ok(())
+}
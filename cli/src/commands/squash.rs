@@ -32,12 +32,11 @@ use crate::command_error::user_error;
 use crate::command_error::user_error_with_hint;
 use crate::command_error::CommandError;
 use crate::complete;
-use crate::description_util::add_trailers;
 use crate::description_util::combine_messages_for_editing;
-use crate::description_util::description_template;
 use crate::description_util::edit_description;
 use crate::description_util::join_message_paragraphs;
 use crate::description_util::try_combine_messages;
+use crate::description_util::DescriptionTemplates;
 use crate::ui::Ui;
 
 /// Move changes from a revision into another revision
@@ -175,46 +174,50 @@ pub(crate) fn cmd_squash(
         args.keep_emptied,
     )? {
         let mut commit_builder = squashed.commit_builder.detach();
-        let new_description = match description {
-            SquashedDescription::Exact(description) => {
-                if description.is_empty() {
-                    description
-                } else {
-                    commit_builder.set_description(description);
-                    add_trailers(ui, &tx, &commit_builder)?
-                }
-            }
-            SquashedDescription::UseDestination => {
-                if destination.description().is_empty() {
-                    destination.description().to_owned()
-                } else {
-                    commit_builder.set_description(destination.description());
-                    add_trailers(ui, &tx, &commit_builder)?
-                }
-            }
-            SquashedDescription::Combine => {
-                let abandoned_commits = &squashed.abandoned_commits;
-                if let Some(description) = try_combine_messages(abandoned_commits, &destination) {
+        let new_description = {
+            let templates = DescriptionTemplates::new(&tx);
+            match description {
+                SquashedDescription::Exact(description) => {
                     if description.is_empty() {
                         description
                     } else {
                         commit_builder.set_description(description);
-                        add_trailers(ui, &tx, &commit_builder)?
+                        templates.add_trailers(ui, &commit_builder)?
+                    }
+                }
+                SquashedDescription::UseDestination => {
+                    if destination.description().is_empty() {
+                        destination.description().to_owned()
+                    } else {
+                        commit_builder.set_description(destination.description());
+                        templates.add_trailers(ui, &commit_builder)?
+                    }
+                }
+                SquashedDescription::Combine => {
+                    let abandoned_commits = &squashed.abandoned_commits;
+                    if let Some(description) = try_combine_messages(abandoned_commits, &destination)
+                    {
+                        if description.is_empty() {
+                            description
+                        } else {
+                            commit_builder.set_description(description);
+                            templates.add_trailers(ui, &commit_builder)?
+                        }
+                    } else {
+                        let combined = combine_messages_for_editing(
+                            ui,
+                            &tx,
+                            abandoned_commits,
+                            &destination,
+                            &commit_builder,
+                        )?;
+                        // It's weird that commit.description() contains "JJ: " lines, but works.
+                        commit_builder.set_description(combined);
+                        let temp_commit = commit_builder.write_hidden()?;
+                        let intro = "Enter a description for the combined commit.";
+                        let template = templates.description_template(ui, intro, &temp_commit)?;
+                        edit_description(&text_editor, &template)?
                     }
-                } else {
-                    let combined = combine_messages_for_editing(
-                        ui,
-                        &tx,
-                        abandoned_commits,
-                        &destination,
-                        &commit_builder,
-                    )?;
-                    // It's weird that commit.description() contains "JJ: " lines, but works.
-                    commit_builder.set_description(combined);
-                    let temp_commit = commit_builder.write_hidden()?;
-                    let intro = "Enter a description for the combined commit.";
-                    let template = description_template(ui, &tx, intro, &temp_commit)?;
-                    edit_description(&text_editor, &template)?
                 }
             }
         };
@@ -94,6 +94,7 @@ pub fn cmd_unsign(
                 formatter.as_mut(),
                 &tx.commit_summary_template(),
                 &unsigned_commits,
+                ui.term_width(),
             )?;
         }
     }
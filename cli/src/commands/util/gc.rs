@@ -16,9 +16,11 @@ use std::slice;
 use std::time::Duration;
 use std::time::SystemTime;
 
+use jj_lib::op_walk;
 use jj_lib::repo::Repo as _;
 
 use crate::cli_util::CommandHelper;
+use crate::command_error::internal_error;
 use crate::command_error::user_error;
 use crate::command_error::CommandError;
 use crate::ui::Ui;
@@ -64,6 +66,18 @@ pub fn cmd_util_gc(
     let repo = workspace_command.repo();
     repo.op_store()
         .gc(slice::from_ref(repo.op_id()), keep_newer)?;
-    repo.store().gc(repo.index(), keep_newer)?;
+    // Commits hidden in the current view can still be restored from the
+    // operation log. The backend keeps objects reachable from the index
+    // alive, so make sure the index covers all of them.
+    let summary =
+        op_walk::reachable_commits_across_ops(repo.loader(), slice::from_ref(repo.operation()))?;
+    let index = repo.index();
+    if let Some(commit_id) = summary.commit_ids.iter().find(|id| !index.has_id(id)) {
+        return Err(internal_error(format!(
+            "Commit {commit_id:.12} referenced by the operation log is not indexed; refusing to \
+             garbage-collect backend objects"
+        )));
+    }
+    repo.store().gc(index, keep_newer)?;
     Ok(())
 }
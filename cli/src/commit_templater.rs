@@ -1564,12 +1564,17 @@ fn builtin_repo_path_methods<'repo>() -> CommitTemplateBuildMethodFnMap<'repo, R
 
 trait ShortestIdPrefixLen {
     fn shortest_prefix_len(&self, repo: &dyn Repo, index: &IdPrefixIndex) -> usize;
+    fn record_prefix(&self, index: &IdPrefixIndex, prefix_len: usize);
 }
 
 impl ShortestIdPrefixLen for ChangeId {
     fn shortest_prefix_len(&self, repo: &dyn Repo, index: &IdPrefixIndex) -> usize {
         index.shortest_change_prefix_len(repo, self)
     }
+
+    fn record_prefix(&self, index: &IdPrefixIndex, prefix_len: usize) {
+        index.record_change_prefix(self, prefix_len);
+    }
 }
 
 impl Template for ChangeId {
@@ -1598,6 +1603,10 @@ impl ShortestIdPrefixLen for CommitId {
     fn shortest_prefix_len(&self, repo: &dyn Repo, index: &IdPrefixIndex) -> usize {
         index.shortest_commit_prefix_len(repo, self)
     }
+
+    fn record_prefix(&self, index: &IdPrefixIndex, prefix_len: usize) {
+        index.record_commit_prefix(self, prefix_len);
+    }
 }
 
 impl Template for CommitId {
@@ -1684,6 +1693,9 @@ where
             // `len` and the length of the shortest unique prefix.
             let out_property = (self_property, len_property).map(move |(id, len)| {
                 let prefix_len = id.shortest_prefix_len(repo, &index);
+                // Remember the printed prefix so it can be resolved later even
+                // if it becomes ambiguous.
+                id.record_prefix(&index, prefix_len);
                 let mut hex = format!("{id:.len$}", len = max(prefix_len, len.unwrap_or(0)));
                 let rest = hex.split_off(prefix_len);
                 ShortestIdPrefix { prefix: hex, rest }
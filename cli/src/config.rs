@@ -327,6 +327,7 @@ pub struct ConfigEnv {
     repo_path: Option<PathBuf>,
     user_config_paths: Vec<ConfigPath>,
     repo_config_path: Option<ConfigPath>,
+    workspace_config_path: Option<ConfigPath>,
     command: Option<String>,
 }
 
@@ -372,6 +373,7 @@ impl ConfigEnv {
             repo_path: None,
             user_config_paths: env.resolve(ui),
             repo_config_path: None,
+            workspace_config_path: None,
             command: None,
         }
     }
@@ -490,6 +492,62 @@ impl ConfigEnv {
         Ok(())
     }
 
+    /// Sets the root of the workspace whose config file should be loaded. The
+    /// config file is stored in the working-copy state directory
+    /// `.jj/working_copy`, so each workspace of a repo has its own.
+    pub fn reset_workspace_path(&mut self, workspace_root: &Path) {
+        let path = workspace_root
+            .join(".jj")
+            .join("working_copy")
+            .join("config.toml");
+        self.workspace_config_path = Some(ConfigPath::new(path));
+    }
+
+    /// Returns a path to the workspace-specific config file.
+    pub fn workspace_config_path(&self) -> Option<&Path> {
+        self.workspace_config_path.as_ref().map(|p| p.as_path())
+    }
+
+    /// Returns a path to the existing workspace-specific config file.
+    fn existing_workspace_config_path(&self) -> Option<&Path> {
+        match self.workspace_config_path {
+            Some(ref path) if path.exists() => Some(path.as_path()),
+            _ => None,
+        }
+    }
+
+    /// Returns workspace configuration files for modification. Instantiates
+    /// one if `config` has no workspace configuration layers.
+    ///
+    /// If the workspace path is unknown, this function returns an empty `Vec`.
+    pub fn workspace_config_files(
+        &self,
+        config: &RawConfig,
+    ) -> Result<Vec<ConfigFile>, ConfigLoadError> {
+        config_files_for(config, ConfigSource::Workspace, || {
+            self.new_workspace_config_file()
+        })
+    }
+
+    fn new_workspace_config_file(&self) -> Result<Option<ConfigFile>, ConfigLoadError> {
+        self.workspace_config_path()
+            // The path doesn't usually exist, but we shouldn't overwrite it
+            // with an empty config if it did exist.
+            .map(|path| ConfigFile::load_or_empty(ConfigSource::Workspace, path))
+            .transpose()
+    }
+
+    /// Loads workspace-specific config file into the given `config`. The old
+    /// workspace-config layer will be replaced if any.
+    #[instrument]
+    pub fn reload_workspace_config(&self, config: &mut RawConfig) -> Result<(), ConfigLoadError> {
+        config.as_mut().remove_layers(ConfigSource::Workspace);
+        if let Some(path) = self.existing_workspace_config_path() {
+            config.as_mut().load_file(ConfigSource::Workspace, path)?;
+        }
+        Ok(())
+    }
+
     /// Resolves conditional scopes within the current environment. Returns new
     /// resolved config.
     pub fn resolve_config(&self, config: &RawConfig) -> Result<StackedConfig, ConfigGetError> {
@@ -527,7 +585,7 @@ fn config_files_for(
 /// 2. Base environment variables
 /// 3. [User configs](https://jj-vcs.github.io/jj/latest/config/)
 /// 4. Repo config `.jj/repo/config.toml`
-/// 5. TODO: Workspace config `.jj/config.toml`
+/// 5. Workspace config `.jj/working_copy/config.toml`
 /// 6. Override environment variables
 /// 7. Command-line arguments `--config`, `--config-toml`, `--config-file`
 ///
@@ -624,6 +682,9 @@ fn env_overrides_layer() -> ConfigLayer {
     if let Ok(value) = env::var("JJ_EDITOR") {
         layer.set_value("ui.editor", value).unwrap();
     }
+    if let Ok(value) = env::var("JJ_DIFF_EDITOR") {
+        layer.set_value("ui.diff-editor", value).unwrap();
+    }
     layer
 }
 
@@ -1759,6 +1820,7 @@ mod tests {
             repo_path: None,
             user_config_paths: env.resolve(&Ui::null()),
             repo_config_path: None,
+            workspace_config_path: None,
             command: None,
         }
     }
@@ -1,3 +1,4 @@
+use std::cell::OnceCell;
 use std::collections::HashMap;
 use std::fs;
 use std::io;
@@ -15,9 +16,11 @@ use jj_lib::backend::CommitId;
 use jj_lib::commit::Commit;
 use jj_lib::commit_builder::DetachedCommitBuilder;
 use jj_lib::config::ConfigGetError;
+use jj_lib::config::ConfigGetResultExt as _;
 use jj_lib::file_util::IoResultExt as _;
 use jj_lib::file_util::PathError;
 use jj_lib::settings::UserSettings;
+use jj_lib::trailer::format_with_trailers;
 use jj_lib::trailer::parse_description_trailers;
 use jj_lib::trailer::parse_trailers;
 use thiserror::Error;
@@ -187,7 +190,7 @@ pub fn edit_description(editor: &TextEditor, description: &str) -> Result<String
 pub fn edit_multiple_descriptions(
     ui: &Ui,
     editor: &TextEditor,
-    tx: &WorkspaceCommandTransaction,
+    templates: &DescriptionTemplates,
     commits: &[(&CommitId, Commit)],
 ) -> Result<ParsedBulkEditMessage<CommitId>, CommandError> {
     let mut commits_map = IndexMap::new();
@@ -207,7 +210,7 @@ pub fn edit_multiple_descriptions(
         bulk_message.push_str(" -------\n");
         commits_map.insert(commit_hash, *commit_id);
         let intro = "";
-        let template = description_template(ui, tx, intro, temp_commit)?;
+        let template = templates.description_template(ui, intro, temp_commit)?;
         bulk_message.push_str(&template);
         append_blank_line(&mut bulk_message);
     }
@@ -392,15 +395,39 @@ pub fn parse_trailers_template<'a>(
     }
 }
 
+/// When a trailer rendered by `templates.commit_trailers` is added to a
+/// description, as configured by `trailers.<key>.add`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrailerAddPolicy {
+    /// Add the trailer unless the exact same trailer is already present.
+    #[default]
+    Always,
+    /// Add the trailer only if there's no trailer with the same key yet.
+    IfMissing,
+}
+
+impl TrailerAddPolicy {
+    fn from_settings(settings: &UserSettings, key: &str) -> Result<Self, ConfigGetError> {
+        Ok(settings
+            .get(["trailers", key, "add"])
+            .optional()?
+            .unwrap_or_default())
+    }
+}
+
 /// Add the trailers from the given `template` in the last paragraph of
 /// the description
 ///
 /// It just lets the description untouched if the trailers are already there.
+/// Trailers configured with `trailers.<key>.add = "if-missing"` are skipped if
+/// there's already a trailer with the same key.
 pub fn add_trailers_with_template(
+    settings: &UserSettings,
     template: &TemplateRenderer<'_, Commit>,
     commit: &Commit,
 ) -> Result<String, CommandError> {
-    let trailers = parse_description_trailers(commit.description());
+    let mut trailers = parse_description_trailers(commit.description());
     let mut output = Vec::new();
     template
         .format(commit, &mut PlainTextFormatter::new(&mut output))
@@ -408,22 +435,20 @@ pub fn add_trailers_with_template(
     let trailer_lines = output
         .into_string()
         .map_err(|_| user_error("Trailers should be valid utf-8"))?;
-    let new_trailers = parse_trailers(&trailer_lines)?;
-    let mut description = commit.description().to_owned();
-    if trailers.is_empty() && !new_trailers.is_empty() {
-        if description.is_empty() {
-            // a first empty line where the user will edit the commit summary
-            description.push('\n');
-        }
-        // create a new paragraph for the trailer
-        description.push('\n');
-    }
-    for new_trailer in new_trailers {
-        if !trailers.contains(&new_trailer) {
-            description.push_str(&format!("{}: {}\n", new_trailer.key, new_trailer.value));
+    let existing_len = trailers.len();
+    for new_trailer in parse_trailers(&trailer_lines)? {
+        let present = match TrailerAddPolicy::from_settings(settings, &new_trailer.key)? {
+            TrailerAddPolicy::Always => trailers[..existing_len].contains(&new_trailer),
+            TrailerAddPolicy::IfMissing => trailers.iter().any(|t| t.key == new_trailer.key),
+        };
+        if !present {
+            trailers.push(new_trailer);
         }
     }
-    Ok(description)
+    Ok(format_with_trailers(
+        commit.description(),
+        &trailers[existing_len..],
+    ))
 }
 
 /// Add the trailers from `templates.commit_trailers` in the last paragraph of
@@ -435,12 +460,7 @@ pub fn add_trailers(
     tx: &WorkspaceCommandTransaction,
     commit_builder: &DetachedCommitBuilder,
 ) -> Result<String, CommandError> {
-    if let Some(renderer) = parse_trailers_template(ui, tx)? {
-        let commit = commit_builder.write_hidden()?;
-        add_trailers_with_template(&renderer, &commit)
-    } else {
-        Ok(commit_builder.description().to_owned())
-    }
+    DescriptionTemplates::new(tx).add_trailers(ui, commit_builder)
 }
 
 /// Renders commit description template, which will be edited by user.
@@ -450,20 +470,89 @@ pub fn description_template(
     intro: &str,
     commit: &Commit,
 ) -> Result<String, CommandError> {
-    // Named as "draft" because the output can contain "JJ:" comment lines.
-    let template_key = "templates.draft_commit_description";
-    let template_text = tx.settings().get_string(template_key)?;
-    let template = tx.parse_commit_template(ui, &template_text)?;
+    DescriptionTemplates::new(tx).description_template(ui, intro, commit)
+}
 
-    let mut output = Vec::new();
-    if !intro.is_empty() {
-        writeln!(output, "JJ: {intro}").unwrap();
+/// Commit templates used to generate descriptions.
+///
+/// The templates are parsed on first use, and reused for all commits described
+/// by the same command.
+pub struct DescriptionTemplates<'a, 'h> {
+    tx: &'a WorkspaceCommandTransaction<'h>,
+    trailers: OnceCell<Option<TemplateRenderer<'a, Commit>>>,
+    draft: OnceCell<TemplateRenderer<'a, Commit>>,
+}
+
+impl<'a, 'h> DescriptionTemplates<'a, 'h> {
+    pub fn new(tx: &'a WorkspaceCommandTransaction<'h>) -> Self {
+        DescriptionTemplates {
+            tx,
+            trailers: OnceCell::new(),
+            draft: OnceCell::new(),
+        }
+    }
+
+    /// Returns the parsed `templates.commit_trailers`, or `None` if the
+    /// template is empty.
+    pub fn trailers_template(
+        &self,
+        ui: &Ui,
+    ) -> Result<Option<&TemplateRenderer<'a, Commit>>, CommandError> {
+        if let Some(template) = self.trailers.get() {
+            return Ok(template.as_ref());
+        }
+        let template = parse_trailers_template(ui, self.tx)?;
+        Ok(self.trailers.get_or_init(|| template).as_ref())
+    }
+
+    /// Returns the parsed `templates.draft_commit_description`.
+    fn draft_template(&self, ui: &Ui) -> Result<&TemplateRenderer<'a, Commit>, CommandError> {
+        if let Some(template) = self.draft.get() {
+            return Ok(template);
+        }
+        // Named as "draft" because the output can contain "JJ:" comment lines.
+        let template_key = "templates.draft_commit_description";
+        let template_text = self.tx.settings().get_string(template_key)?;
+        let template = self.tx.parse_commit_template(ui, &template_text)?;
+        Ok(self.draft.get_or_init(|| template))
+    }
+
+    /// Add the trailers from `templates.commit_trailers` in the last paragraph
+    /// of the description
+    ///
+    /// It just lets the description untouched if the trailers are already
+    /// there.
+    pub fn add_trailers(
+        &self,
+        ui: &Ui,
+        commit_builder: &DetachedCommitBuilder,
+    ) -> Result<String, CommandError> {
+        if let Some(renderer) = self.trailers_template(ui)? {
+            let commit = commit_builder.write_hidden()?;
+            add_trailers_with_template(self.tx.settings(), renderer, &commit)
+        } else {
+            Ok(commit_builder.description().to_owned())
+        }
+    }
+
+    /// Renders commit description template, which will be edited by user.
+    pub fn description_template(
+        &self,
+        ui: &Ui,
+        intro: &str,
+        commit: &Commit,
+    ) -> Result<String, CommandError> {
+        let template = self.draft_template(ui)?;
+        let mut output = Vec::new();
+        if !intro.is_empty() {
+            writeln!(output, "JJ: {intro}").unwrap();
+        }
+        template
+            .format(commit, &mut PlainTextFormatter::new(&mut output))
+            .expect("write() to vec backed formatter should never fail");
+        // Template output is usually UTF-8, but it can contain file content.
+        Ok(output.into_string_lossy())
     }
-    template
-        .format(commit, &mut PlainTextFormatter::new(&mut output))
-        .expect("write() to vec backed formatter should never fail");
-    // Template output is usually UTF-8, but it can contain file content.
-    Ok(output.into_string_lossy())
 }
 
 #[cfg(test)]
@@ -45,6 +45,7 @@ use jj_lib::copies::CopiesTreeDiffEntry;
 use jj_lib::copies::CopiesTreeDiffEntryPath;
 use jj_lib::copies::CopyOperation;
 use jj_lib::copies::CopyRecords;
+use jj_lib::copies::CopyTracking;
 use jj_lib::diff::find_line_ranges;
 use jj_lib::diff::CompareBytesExactly;
 use jj_lib::diff::CompareBytesIgnoreAllWhitespace;
@@ -57,6 +58,11 @@ use jj_lib::files::ConflictDiffHunk;
 use jj_lib::files::DiffLineHunkSide;
 use jj_lib::files::DiffLineIterator;
 use jj_lib::files::DiffLineNumber;
+use jj_lib::gitattributes::GitAttributesError;
+use jj_lib::gitattributes::TreeGitAttributes;
+use jj_lib::matchers::DifferenceMatcher;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::matchers::FilesMatcher;
 use jj_lib::matchers::Matcher;
 use jj_lib::merge::Merge;
 use jj_lib::merge::MergedTreeValue;
@@ -65,6 +71,7 @@ use jj_lib::object_id::ObjectId as _;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::InvalidRepoPathError;
 use jj_lib::repo_path::RepoPath;
+use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::repo_path::RepoPathUiConverter;
 use jj_lib::rewrite::rebase_to_dest_parent;
 use jj_lib::settings::UserSettings;
@@ -84,6 +91,7 @@ use crate::merge_tools::invoke_external_diff;
 use crate::merge_tools::new_utf8_temp_dir;
 use crate::merge_tools::DiffGenerateError;
 use crate::merge_tools::DiffToolMode;
+use crate::merge_tools::DriverTools;
 use crate::merge_tools::ExternalMergeTool;
 use crate::text_util;
 use crate::ui::Ui;
@@ -354,6 +362,8 @@ pub enum DiffRenderError {
     },
     #[error(transparent)]
     InvalidRepoPath(#[from] InvalidRepoPathError),
+    #[error("Failed to read .gitattributes")]
+    GitAttributes(#[from] GitAttributesError),
     #[error(transparent)]
     Io(#[from] io::Error),
 }
@@ -363,7 +373,9 @@ pub struct DiffRenderer<'a> {
     repo: &'a dyn Repo,
     path_converter: &'a RepoPathUiConverter,
     conflict_marker_style: ConflictMarkerStyle,
+    prefetch_concurrency: usize,
     formats: Vec<DiffFormat>,
+    driver_tools: DriverTools,
 }
 
 impl<'a> DiffRenderer<'a> {
@@ -371,16 +383,26 @@ impl<'a> DiffRenderer<'a> {
         repo: &'a dyn Repo,
         path_converter: &'a RepoPathUiConverter,
         conflict_marker_style: ConflictMarkerStyle,
+        prefetch_concurrency: usize,
         formats: Vec<DiffFormat>,
     ) -> Self {
         DiffRenderer {
             repo,
             path_converter,
             conflict_marker_style,
+            prefetch_concurrency,
             formats,
+            driver_tools: DriverTools::default(),
         }
     }
 
+    /// Renders files whose `diff` attribute names one of the `driver_tools`
+    /// with that tool instead of the external diff tool format.
+    pub fn with_driver_tools(mut self, driver_tools: DriverTools) -> Self {
+        self.driver_tools = driver_tools;
+        self
+    }
+
     /// Generates diff between `from_tree` and `to_tree`.
     #[expect(clippy::too_many_arguments)]
     pub fn show_diff(
@@ -468,40 +490,140 @@ impl<'a> DiffRenderer<'a> {
                     )?;
                 }
                 DiffFormat::Tool(tool) => {
-                    match tool.diff_invocation_mode {
-                        DiffToolMode::FileByFile => {
-                            let tree_diff =
-                                from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
-                            show_file_by_file_diff(
-                                ui,
-                                formatter,
-                                store,
-                                tree_diff,
-                                path_converter,
-                                tool,
-                                self.conflict_marker_style,
-                            )
-                        }
-                        DiffToolMode::Dir => {
-                            let mut writer = formatter.raw()?;
-                            generate_diff(
-                                ui,
-                                writer.as_mut(),
-                                from_tree,
-                                to_tree,
-                                matcher,
-                                tool,
-                                self.conflict_marker_style,
-                            )
-                            .map_err(DiffRenderError::DiffGenerate)
-                        }
-                    }?;
+                    self.show_tool_diffs(
+                        ui,
+                        formatter,
+                        from_tree,
+                        to_tree,
+                        matcher,
+                        copy_records,
+                        tool,
+                    )?;
                 }
             }
         }
         Ok(())
     }
 
+    /// Generates diff with the external `tool`, or with the driver tools
+    /// selected by the `diff` attribute of the changed files.
+    #[expect(clippy::too_many_arguments)]
+    fn show_tool_diffs(
+        &self,
+        ui: &Ui,
+        formatter: &mut dyn Formatter,
+        from_tree: &MergedTree,
+        to_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        copy_records: &CopyRecords,
+        tool: &ExternalMergeTool,
+    ) -> Result<(), DiffRenderError> {
+        if self.driver_tools.is_empty() {
+            return self.show_tool_diff(
+                ui,
+                formatter,
+                from_tree,
+                to_tree,
+                matcher,
+                copy_records,
+                tool,
+            );
+        }
+        let changed_paths: Vec<RepoPathBuf> = from_tree
+            .diff_stream(to_tree, matcher)
+            .map(|entry| entry.path)
+            .collect()
+            .block_on();
+        let mut attributes = TreeGitAttributes::new(to_tree.clone());
+        let groups = self
+            .driver_tools
+            .group_paths(&mut attributes, changed_paths.iter().map(AsRef::as_ref))?;
+        let driver_paths = groups
+            .iter()
+            .filter(|(driver_tool, _)| driver_tool.is_some())
+            .flat_map(|(_, paths)| paths)
+            .collect_vec();
+        if driver_paths.is_empty() {
+            return self.show_tool_diff(
+                ui,
+                formatter,
+                from_tree,
+                to_tree,
+                matcher,
+                copy_records,
+                tool,
+            );
+        }
+        for (driver_tool, paths) in &groups {
+            if let Some(driver_tool) = driver_tool {
+                let driver_matcher = FilesMatcher::new(paths);
+                self.show_tool_diff(
+                    ui,
+                    formatter,
+                    from_tree,
+                    to_tree,
+                    &driver_matcher,
+                    copy_records,
+                    driver_tool,
+                )?;
+            } else {
+                let default_matcher =
+                    DifferenceMatcher::new(matcher, FilesMatcher::new(&driver_paths));
+                self.show_tool_diff(
+                    ui,
+                    formatter,
+                    from_tree,
+                    to_tree,
+                    &default_matcher,
+                    copy_records,
+                    tool,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    #[expect(clippy::too_many_arguments)]
+    fn show_tool_diff(
+        &self,
+        ui: &Ui,
+        formatter: &mut dyn Formatter,
+        from_tree: &MergedTree,
+        to_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        copy_records: &CopyRecords,
+        tool: &ExternalMergeTool,
+    ) -> Result<(), DiffRenderError> {
+        match tool.diff_invocation_mode {
+            DiffToolMode::FileByFile => {
+                let tree_diff = from_tree.diff_stream_with_copies(to_tree, matcher, copy_records);
+                show_file_by_file_diff(
+                    ui,
+                    formatter,
+                    self.repo.store(),
+                    tree_diff,
+                    self.path_converter,
+                    tool,
+                    self.conflict_marker_style,
+                )
+            }
+            DiffToolMode::Dir => {
+                let mut writer = formatter.raw()?;
+                generate_diff(
+                    ui,
+                    writer.as_mut(),
+                    from_tree,
+                    to_tree,
+                    matcher,
+                    tool,
+                    self.conflict_marker_style,
+                    self.prefetch_concurrency,
+                )
+                .map_err(DiffRenderError::DiffGenerate)
+            }
+        }
+    }
+
     /// Generates diff between `from_commits` and `to_commit` based off their
     /// parents. The `from_commits` will temporarily be rebased onto the
     /// `to_commit` parents to exclude unrelated changes.
@@ -568,6 +690,24 @@ pub fn get_copy_records<'a>(
     Ok(block_on_stream(stream).filter_ok(|record| matcher.matches(&record.target)))
 }
 
+/// Collects copy records from each of the `sources` to each of the
+/// `destinations` if `copy_tracking` is enabled.
+pub fn collect_copy_records(
+    store: &Store,
+    sources: &[CommitId],
+    destinations: &[CommitId],
+    copy_tracking: CopyTracking,
+) -> BackendResult<CopyRecords> {
+    let mut copy_records = CopyRecords::default();
+    if copy_tracking == CopyTracking::Track {
+        for (source, destination) in sources.iter().cartesian_product(destinations) {
+            let records = get_copy_records(store, source, destination, &EverythingMatcher)?;
+            copy_records.add_records(records)?;
+        }
+    }
+    Ok(copy_records)
+}
+
 /// How conflicts are processed and rendered in diffs.
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
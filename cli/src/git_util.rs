@@ -49,6 +49,7 @@ use crate::command_error::cli_error;
 use crate::command_error::user_error;
 use crate::command_error::CommandError;
 use crate::formatter::Formatter;
+use crate::progress::draw_progress;
 use crate::ui::ProgressOutput;
 use crate::ui::Ui;
 
@@ -424,23 +425,6 @@ impl Progress {
     }
 }
 
-fn draw_progress(progress: f32, buffer: &mut String, width: usize) {
-    const CHARS: [char; 9] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
-    const RESOLUTION: usize = CHARS.len() - 1;
-    let ticks = (width as f32 * progress.clamp(0.0, 1.0) * RESOLUTION as f32).round() as usize;
-    let whole = ticks / RESOLUTION;
-    for _ in 0..whole {
-        buffer.push(CHARS[CHARS.len() - 1]);
-    }
-    if whole < width {
-        let fraction = ticks % RESOLUTION;
-        buffer.push(CHARS[fraction]);
-    }
-    for _ in (whole + 1)..width {
-        buffer.push(CHARS[0]);
-    }
-}
-
 struct RateEstimate {
     state: Option<RateEstimateState>,
 }
@@ -44,6 +44,7 @@ pub mod movement_util;
 pub mod operation_templater;
 mod progress;
 pub mod revset_util;
+pub mod selection_plan;
 pub mod template_builder;
 pub mod template_parser;
 pub mod templater;
@@ -5,6 +5,7 @@ use std::sync::Arc;
 use futures::stream::BoxStream;
 use futures::StreamExt as _;
 use itertools::Itertools as _;
+use jj_lib::backend::BackendError;
 use jj_lib::backend::BackendResult;
 use jj_lib::backend::CopyId;
 use jj_lib::backend::MergedTreeId;
@@ -13,6 +14,7 @@ use jj_lib::conflicts;
 use jj_lib::conflicts::materialize_merge_result_to_bytes;
 use jj_lib::conflicts::materialized_diff_stream;
 use jj_lib::conflicts::ConflictMarkerStyle;
+use jj_lib::conflicts::MaterializedFileValue;
 use jj_lib::conflicts::MaterializedTreeValue;
 use jj_lib::conflicts::MIN_CONFLICT_MARKER_LEN;
 use jj_lib::copies::CopiesTreeDiffEntry;
@@ -32,6 +34,7 @@ use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::store::Store;
 use pollster::FutureExt as _;
 use thiserror::Error;
+use tokio::io::AsyncReadExt as _;
 
 use super::MergeToolFile;
 
@@ -119,10 +122,44 @@ fn buf_to_file_contents(hash: Option<String>, buf: Vec<u8>) -> FileContents {
     }
 }
 
+/// Reads the file content if it's no larger than `max_text_size`. Larger files
+/// are presented as binary, and their content is only scanned to compute the
+/// size.
+async fn read_file_value_contents(
+    file: &mut MaterializedFileValue,
+    path: &RepoPath,
+    max_text_size: u64,
+) -> BackendResult<FileContents> {
+    let to_backend_error = |err: std::io::Error| BackendError::ReadFile {
+        path: path.to_owned(),
+        id: file.id.clone(),
+        source: err.into(),
+    };
+    let mut buf = Vec::new();
+    (&mut file.reader)
+        .take(max_text_size.saturating_add(1))
+        .read_to_end(&mut buf)
+        .await
+        .map_err(to_backend_error)?;
+    let hash = Some(file.id.hex());
+    let num_bytes: u64 = buf.len().try_into().unwrap();
+    if num_bytes > max_text_size {
+        let num_remaining_bytes = tokio::io::copy(&mut file.reader, &mut tokio::io::sink())
+            .await
+            .map_err(to_backend_error)?;
+        return Ok(FileContents::Binary {
+            hash,
+            num_bytes: num_bytes + num_remaining_bytes,
+        });
+    }
+    Ok(buf_to_file_contents(hash, buf))
+}
+
 fn read_file_contents(
     materialized_value: MaterializedTreeValue,
     path: &RepoPath,
     conflict_marker_style: ConflictMarkerStyle,
+    max_text_size: u64,
 ) -> Result<FileInfo, BuiltinToolError> {
     match materialized_value {
         MaterializedTreeValue::Absent => Ok(FileInfo {
@@ -139,13 +176,12 @@ fn read_file_contents(
         }),
 
         MaterializedTreeValue::File(mut file) => {
-            let buf = file.read_all(path).block_on()?;
+            let contents = read_file_value_contents(&mut file, path, max_text_size).block_on()?;
             let file_mode = if file.executable {
                 mode::EXECUTABLE
             } else {
                 mode::NORMAL
             };
-            let contents = buf_to_file_contents(Some(file.id.hex()), buf);
             Ok(FileInfo {
                 file_mode,
                 contents,
@@ -197,6 +233,42 @@ fn read_file_contents(
     }
 }
 
+fn is_conflict(value: &MaterializedTreeValue) -> bool {
+    matches!(
+        value,
+        MaterializedTreeValue::FileConflict(_) | MaterializedTreeValue::OtherConflict { .. }
+    )
+}
+
+/// Reads the file mode and the description of a file which can only be
+/// selected as a whole.
+fn read_whole_file_info(
+    materialized_value: MaterializedTreeValue,
+    path: &RepoPath,
+    conflict_marker_style: ConflictMarkerStyle,
+    max_text_size: u64,
+) -> Result<(scm_record::FileMode, Option<String>), BuiltinToolError> {
+    match materialized_value {
+        MaterializedTreeValue::FileConflict(file) => {
+            let file_mode = if file.executable == Some(true) {
+                mode::EXECUTABLE
+            } else {
+                mode::NORMAL
+            };
+            let description = format!("conflict ({} sides)", file.ids.num_sides());
+            Ok((file_mode, Some(description)))
+        }
+        MaterializedTreeValue::OtherConflict { id } => {
+            let description = format!("conflict ({} sides)", id.num_sides());
+            Ok((mode::NORMAL, Some(description)))
+        }
+        value => {
+            let info = read_file_contents(value, path, conflict_marker_style, max_text_size)?;
+            Ok((info.file_mode, info.contents.describe()))
+        }
+    }
+}
+
 fn make_section_changed_lines(
     contents: &str,
     change_type: scm_record::ChangeType,
@@ -264,6 +336,8 @@ async fn make_diff_files(
     store: &Arc<Store>,
     tree_diff: BoxStream<'_, CopiesTreeDiffEntry>,
     conflict_marker_style: ConflictMarkerStyle,
+    whole_file_conflicts: bool,
+    max_text_size: u64,
 ) -> Result<(Vec<RepoPathBuf>, Vec<scm_record::File<'static>>), BuiltinToolError> {
     let mut diff_stream = materialized_diff_stream(store, tree_diff);
     let mut changed_files = Vec::new();
@@ -272,8 +346,48 @@ async fn make_diff_files(
         let left_path = entry.path.source();
         let right_path = entry.path.target();
         let (left_value, right_value) = entry.values?;
-        let left_info = read_file_contents(left_value, left_path, conflict_marker_style)?;
-        let right_info = read_file_contents(right_value, right_path, conflict_marker_style)?;
+        if whole_file_conflicts && (is_conflict(&left_value) || is_conflict(&right_value)) {
+            // Selecting hunks across conflict markers would lose the
+            // structure of the conflict, so the file is presented as a single
+            // section which selects the right-side value as is.
+            let (left_mode, left_description) =
+                read_whole_file_info(left_value, left_path, conflict_marker_style, max_text_size)?;
+            let (right_mode, right_description) = read_whole_file_info(
+                right_value,
+                right_path,
+                conflict_marker_style,
+                max_text_size,
+            )?;
+            let mut sections = Vec::new();
+            if left_mode != right_mode {
+                sections.push(scm_record::Section::FileMode {
+                    is_checked: false,
+                    mode: right_mode,
+                });
+            }
+            sections.push(scm_record::Section::Binary {
+                is_checked: false,
+                old_description: left_description.map(Cow::Owned),
+                new_description: right_description.map(Cow::Owned),
+            });
+            files.push(scm_record::File {
+                old_path: None,
+                // Path for displaying purposes, not for file access.
+                path: Cow::Owned(right_path.to_fs_path_unchecked(Path::new(""))),
+                file_mode: left_mode,
+                sections,
+            });
+            changed_files.push(entry.path.target);
+            continue;
+        }
+        let left_info =
+            read_file_contents(left_value, left_path, conflict_marker_style, max_text_size)?;
+        let right_info = read_file_contents(
+            right_value,
+            right_path,
+            conflict_marker_style,
+            max_text_size,
+        )?;
         let mut sections = Vec::new();
 
         if left_info.file_mode != right_info.file_mode {
@@ -488,7 +602,14 @@ fn apply_changes(
                 old_description: _,
                 new_description: Some(_),
             } => {
-                let value = override_file_executable_bit(select_right(&path)?, executable);
+                let value = select_right(&path)?;
+                // Conflicts are selected as a whole, including the executable
+                // bits of their terms.
+                let value = if value.is_resolved() {
+                    override_file_executable_bit(value, executable)
+                } else {
+                    value
+                };
                 tree_builder.set_or_remove(path, value);
             }
             scm_record::SelectedContents::Binary {
@@ -523,19 +644,36 @@ fn override_file_executable_bit(
     merged_tree_value
 }
 
+/// Starts the builtin diff editor.
+///
+/// If `whole_file_conflicts` is true, conflicted files are presented as whole
+/// files instead of materialized conflict markers, and the selected conflicts
+/// are copied from the `right_tree` as is. Files larger than `max_text_size`
+/// are presented as binary files, which can only be selected as a whole.
 pub fn edit_diff_builtin(
     left_tree: &MergedTree,
     right_tree: &MergedTree,
     matcher: &dyn Matcher,
     conflict_marker_style: ConflictMarkerStyle,
+    whole_file_conflicts: bool,
+    max_text_size: u64,
 ) -> Result<MergedTreeId, BuiltinToolError> {
     let store = left_tree.store().clone();
     // TODO: handle copy tracking
     let copy_records = CopyRecords::default();
     let tree_diff = left_tree.diff_stream_with_copies(right_tree, matcher, &copy_records);
-    let (changed_files, files) =
-        make_diff_files(&store, tree_diff, conflict_marker_style).block_on()?;
+    let (changed_files, files) = make_diff_files(
+        &store,
+        tree_diff,
+        conflict_marker_style,
+        whole_file_conflicts,
+        max_text_size,
+    )
+    .block_on()?;
     let mut input = scm_record::helpers::CrosstermInput;
+    // TODO: Selecting all files under a directory with one action needs
+    // directory nodes with a tri-state checkbox in the file list, which is
+    // rendered by `scm-record`.
     let recorder = scm_record::Recorder::new(
         scm_record::RecordState {
             is_read_only: false,
@@ -557,6 +695,47 @@ pub fn edit_diff_builtin(
     Ok(tree_id)
 }
 
+/// Returns the changed files which the builtin diff editor would present as
+/// binary files, either because they contain binary data or because they're
+/// larger than `max_text_size`.
+pub fn find_binary_files(
+    left_tree: &MergedTree,
+    right_tree: &MergedTree,
+    matcher: &dyn Matcher,
+    max_text_size: u64,
+) -> BackendResult<Vec<RepoPathBuf>> {
+    async fn is_binary(
+        value: MaterializedTreeValue,
+        path: &RepoPath,
+        max_text_size: u64,
+    ) -> BackendResult<bool> {
+        match value {
+            MaterializedTreeValue::File(mut file) => {
+                let contents = read_file_value_contents(&mut file, path, max_text_size).await?;
+                Ok(matches!(contents, FileContents::Binary { .. }))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    let copy_records = CopyRecords::default();
+    let tree_diff = left_tree.diff_stream_with_copies(right_tree, matcher, &copy_records);
+    let mut diff_stream = materialized_diff_stream(left_tree.store(), tree_diff);
+    let mut paths = Vec::new();
+    async {
+        while let Some(entry) = diff_stream.next().await {
+            let (left_value, right_value) = entry.values?;
+            if is_binary(left_value, entry.path.source(), max_text_size).await?
+                || is_binary(right_value, entry.path.target(), max_text_size).await?
+            {
+                paths.push(entry.path.target);
+            }
+        }
+        Ok(paths)
+    }
+    .block_on()
+}
+
 fn make_merge_sections(
     merge_result: MergeResult,
 ) -> Result<Vec<scm_record::Section<'static>>, BuiltinToolError> {
@@ -728,7 +907,7 @@ mod tests {
         let copy_records = CopyRecords::default();
         let tree_diff =
             left_tree.diff_stream_with_copies(right_tree, &EverythingMatcher, &copy_records);
-        make_diff_files(store, tree_diff, ConflictMarkerStyle::Diff)
+        make_diff_files(store, tree_diff, ConflictMarkerStyle::Diff, false, u64::MAX)
             .block_on()
             .unwrap()
     }
@@ -1674,6 +1853,181 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_edit_diff_builtin_whole_file_conflicts() {
+        let test_repo = TestRepo::init();
+        let store = test_repo.repo.store();
+
+        let file_path = repo_path("file");
+        let left_tree = testutils::create_tree(&test_repo.repo, &[(file_path, "")]);
+        let right_tree = {
+            let base = testutils::create_single_tree(&test_repo.repo, &[(file_path, "")]);
+            let left = testutils::create_single_tree(&test_repo.repo, &[(file_path, "1\n")]);
+            let right = testutils::create_single_tree(&test_repo.repo, &[(file_path, "2\n")]);
+            MergedTree::new(Merge::from_vec(vec![left, base, right]))
+        };
+
+        let copy_records = CopyRecords::default();
+        let tree_diff =
+            left_tree.diff_stream_with_copies(&right_tree, &EverythingMatcher, &copy_records);
+        let (changed_files, files) =
+            make_diff_files(store, tree_diff, ConflictMarkerStyle::Diff, true, u64::MAX)
+                .block_on()
+                .unwrap();
+        insta::assert_debug_snapshot!(changed_files, @r#"
+        [
+            "file",
+        ]
+        "#);
+        insta::assert_debug_snapshot!(files, @r#"
+        [
+            File {
+                old_path: None,
+                path: "file",
+                file_mode: Unix(
+                    33188,
+                ),
+                sections: [
+                    Binary {
+                        is_checked: false,
+                        old_description: Some(
+                            "482ae5a29fbe856c7272 (0B)",
+                        ),
+                        new_description: Some(
+                            "conflict (2 sides)",
+                        ),
+                    },
+                ],
+            },
+        ]
+        "#);
+
+        let no_changes_tree_id = apply_diff(store, &left_tree, &right_tree, &changed_files, &files);
+        assert_eq!(
+            no_changes_tree_id,
+            left_tree.id(),
+            "no-changes tree was different",
+        );
+
+        let mut files = files;
+        for file in &mut files {
+            file.toggle_all();
+        }
+        let all_changes_tree_id =
+            apply_diff(store, &left_tree, &right_tree, &changed_files, &files);
+        assert_eq!(
+            all_changes_tree_id,
+            right_tree.id(),
+            "all-changes tree was different",
+        );
+    }
+
+    #[test]
+    fn test_edit_diff_builtin_large_file() {
+        let test_repo = TestRepo::init();
+        let store = test_repo.repo.store();
+
+        let binary_file_path = repo_path("binary_file");
+        let large_file_path = repo_path("large_file");
+        let text_file_path = repo_path("text_file");
+        let left_tree = testutils::create_tree_with(&test_repo.repo, |builder| {
+            builder.file(binary_file_path, vec![0xff, 0x00]);
+            builder.file(large_file_path, "a\nb\n");
+            builder.file(text_file_path, "a\n");
+        });
+        let right_tree = testutils::create_tree_with(&test_repo.repo, |builder| {
+            builder.file(binary_file_path, vec![0xff, 0x01]);
+            builder.file(large_file_path, "a\nc\n");
+            builder.file(text_file_path, "b\n");
+        });
+
+        let binary_paths =
+            find_binary_files(&left_tree, &right_tree, &EverythingMatcher, 3).unwrap();
+        insta::assert_debug_snapshot!(binary_paths, @r#"
+        [
+            "binary_file",
+            "large_file",
+        ]
+        "#);
+
+        let copy_records = CopyRecords::default();
+        let tree_diff =
+            left_tree.diff_stream_with_copies(&right_tree, &EverythingMatcher, &copy_records);
+        let (changed_files, files) =
+            make_diff_files(store, tree_diff, ConflictMarkerStyle::Diff, false, 3)
+                .block_on()
+                .unwrap();
+        insta::assert_debug_snapshot!(files.iter().map(|file| &file.sections).collect_vec(), @r#"
+        [
+            [
+                Binary {
+                    is_checked: false,
+                    old_description: Some(
+                        "fb296c879f1852c0dca0 (2B)",
+                    ),
+                    new_description: Some(
+                        "cc429d26cbaec338223b (2B)",
+                    ),
+                },
+            ],
+            [
+                Binary {
+                    is_checked: false,
+                    old_description: Some(
+                        "eaaac2532dbf2ca6fa07 (4B)",
+                    ),
+                    new_description: Some(
+                        "8a03215663ad07a1a9d4 (4B)",
+                    ),
+                },
+            ],
+            [
+                Changed {
+                    lines: [
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Removed,
+                            line: "a\n",
+                        },
+                        SectionChangedLine {
+                            is_checked: false,
+                            change_type: Added,
+                            line: "b\n",
+                        },
+                    ],
+                },
+            ],
+        ]
+        "#);
+
+        // Select each combination of the files
+        for selection in 0..(1 << files.len()) {
+            let mut files = files.clone();
+            for (i, file) in files.iter_mut().enumerate() {
+                if selection & (1 << i) != 0 {
+                    file.toggle_all();
+                }
+            }
+            let selected = |i: usize, path: &RepoPath| {
+                let tree = if selection & (1 << i) != 0 {
+                    &right_tree
+                } else {
+                    &left_tree
+                };
+                tree.path_value(path).unwrap()
+            };
+            let actual_tree_id = apply_diff(store, &left_tree, &right_tree, &changed_files, &files);
+            let actual_tree = store.get_root_tree(&actual_tree_id).unwrap();
+            for (i, path) in changed_files.iter().enumerate() {
+                assert_eq!(
+                    actual_tree.path_value(path).unwrap(),
+                    selected(i, path),
+                    "unexpected value of {path:?} with selection {selection:#b}"
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_edit_diff_builtin_replace_directory_with_file() {
         let test_repo = TestRepo::init();
@@ -7,7 +7,10 @@ use std::path::PathBuf;
 use std::sync::Arc;
 
 use futures::StreamExt as _;
+use itertools::Itertools as _;
+use jj_lib::backend::BackendError;
 use jj_lib::backend::MergedTreeId;
+use jj_lib::backend::TreeValue;
 use jj_lib::conflicts::ConflictMarkerStyle;
 use jj_lib::fsmonitor::FsmonitorSettings;
 use jj_lib::gitignore::GitIgnoreFile;
@@ -35,6 +38,8 @@ pub enum DiffCheckoutError {
     Checkout(#[from] CheckoutError),
     #[error("Error setting up temporary directory")]
     SetUpDir(#[source] std::io::Error),
+    #[error("Failed to prefetch files to diff")]
+    Prefetch(#[source] BackendError),
     #[error(transparent)]
     TreeState(#[from] TreeStateError),
 }
@@ -144,6 +149,10 @@ pub(crate) enum DiffSide {
 
 /// Check out the two trees in temporary directories. Only include changed files
 /// in the sparse checkout patterns.
+///
+/// The contents of the changed files are prefetched from the backend before
+/// the trees are checked out, with up to `prefetch_concurrency` concurrent
+/// requests.
 pub(crate) fn check_out_trees(
     store: &Arc<Store>,
     left_tree: &MergedTree,
@@ -151,12 +160,34 @@ pub(crate) fn check_out_trees(
     matcher: &dyn Matcher,
     output_is: Option<DiffSide>,
     options: &CheckoutOptions,
+    prefetch_concurrency: usize,
 ) -> Result<DiffWorkingCopies, DiffCheckoutError> {
-    let changed_files: Vec<_> = left_tree
+    let diff_entries: Vec<_> = left_tree
         .diff_stream(right_tree, matcher)
-        .map(|TreeDiffEntry { path, .. }| path)
         .collect()
         .block_on();
+    // Errors are reported when the files are checked out
+    let files_to_prefetch = diff_entries
+        .iter()
+        .filter_map(|TreeDiffEntry { path, values }| Some((path, values.as_ref().ok()?)))
+        .flat_map(|(path, (before, after))| {
+            itertools::chain(before.iter(), after.iter())
+                .flatten()
+                .filter_map(move |value| match value {
+                    TreeValue::File { id, .. } => Some((path.clone(), id.clone())),
+                    _ => None,
+                })
+        })
+        .unique()
+        .collect_vec();
+    store
+        .prefetch_files(&files_to_prefetch, prefetch_concurrency)
+        .block_on()
+        .map_err(DiffCheckoutError::Prefetch)?;
+    let changed_files = diff_entries
+        .into_iter()
+        .map(|TreeDiffEntry { path, .. }| path)
+        .collect_vec();
 
     let temp_dir = new_utf8_temp_dir("jj-diff-").map_err(DiffCheckoutError::SetUpDir)?;
     let left_wc_dir = temp_dir.path().join("left");
@@ -212,6 +243,7 @@ pub(crate) struct DiffEditWorkingCopies {
 impl DiffEditWorkingCopies {
     /// Checks out the trees, populates JJ_INSTRUCTIONS, and makes appropriate
     /// sides readonly.
+    #[expect(clippy::too_many_arguments)]
     pub fn check_out(
         store: &Arc<Store>,
         left_tree: &MergedTree,
@@ -220,8 +252,17 @@ impl DiffEditWorkingCopies {
         output_is: Option<DiffSide>,
         instructions: Option<&str>,
         options: &CheckoutOptions,
+        prefetch_concurrency: usize,
     ) -> Result<Self, DiffEditError> {
-        let diff_wc = check_out_trees(store, left_tree, right_tree, matcher, output_is, options)?;
+        let diff_wc = check_out_trees(
+            store,
+            left_tree,
+            right_tree,
+            matcher,
+            output_is,
+            options,
+            prefetch_concurrency,
+        )?;
         let got_output_field = output_is.is_some();
 
         set_readonly_recursively(diff_wc.left_working_copy_path())
@@ -315,3 +356,70 @@ diff editing in mind and be a little inaccurate.
         Ok(output_tree_state.current_tree_id().clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use jj_lib::repo::Repo as _;
+    use testutils::repo_path;
+    use testutils::test_backend::FileAccess;
+    use testutils::test_backend::TestBackend;
+    use testutils::TestRepo;
+
+    use super::*;
+
+    #[test]
+    fn test_check_out_trees_prefetches_files() {
+        let test_repo = TestRepo::init();
+        let store = test_repo.repo.store();
+
+        let unchanged_path = repo_path("unchanged");
+        let changed_path = repo_path("changed");
+        let added_path = repo_path("added");
+        let left_tree = testutils::create_tree(
+            &test_repo.repo,
+            &[(unchanged_path, "unchanged\n"), (changed_path, "left\n")],
+        );
+        let right_tree = testutils::create_tree(
+            &test_repo.repo,
+            &[
+                (unchanged_path, "unchanged\n"),
+                (changed_path, "right\n"),
+                (added_path, "added\n"),
+            ],
+        );
+
+        let _diff_wc = check_out_trees(
+            store,
+            &left_tree,
+            &right_tree,
+            &EverythingMatcher,
+            None,
+            &CheckoutOptions::empty_for_test(),
+            2,
+        )
+        .unwrap();
+
+        let test_backend: &TestBackend = store.backend_impl().downcast_ref().unwrap();
+        let accesses = test_backend.file_accesses();
+        // The files are prefetched in 2 batches before the first individual read
+        let (prefetches, reads) = accesses.split_at(2);
+        let prefetched_files: HashSet<_> = prefetches
+            .iter()
+            .flat_map(|access| match access {
+                FileAccess::Prefetch(files) => files.clone(),
+                FileAccess::Read(..) => panic!("unexpected read: {access:?}"),
+            })
+            .collect();
+        let read_files: HashSet<_> = reads
+            .iter()
+            .map(|access| match access {
+                FileAccess::Read(path, id) => (path.clone(), id.clone()),
+                FileAccess::Prefetch(_) => panic!("unexpected prefetch: {access:?}"),
+            })
+            .collect();
+        assert_eq!(prefetched_files.len(), 3);
+        assert_eq!(prefetched_files, read_files);
+    }
+}
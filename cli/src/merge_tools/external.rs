@@ -15,6 +15,7 @@ use jj_lib::backend::TreeValue;
 use jj_lib::conflicts;
 use jj_lib::conflicts::choose_materialized_conflict_marker_len;
 use jj_lib::conflicts::materialize_merge_result_to_bytes_with_marker_len;
+use jj_lib::conflicts::ConflictLabels;
 use jj_lib::conflicts::ConflictMarkerStyle;
 use jj_lib::conflicts::MIN_CONFLICT_MARKER_LEN;
 use jj_lib::gitignore::GitIgnoreFile;
@@ -376,6 +377,7 @@ pub fn run_mergetool_external(
     Ok((new_tree, partial_resolution_error))
 }
 
+#[expect(clippy::too_many_arguments)]
 pub fn edit_diff_external(
     editor: &ExternalMergeTool,
     left_tree: &MergedTree,
@@ -384,12 +386,16 @@ pub fn edit_diff_external(
     instructions: Option<&str>,
     base_ignores: Arc<GitIgnoreFile>,
     default_conflict_marker_style: ConflictMarkerStyle,
+    prefetch_concurrency: usize,
 ) -> Result<MergedTreeId, DiffEditError> {
     let conflict_marker_style = editor
         .conflict_marker_style
         .unwrap_or(default_conflict_marker_style);
     let options = CheckoutOptions {
         conflict_marker_style,
+        conflict_labels: ConflictLabels::unlabeled(),
+        lazy_conflicts: false,
+        progress: None,
     };
 
     let got_output_field = find_all_variables(&editor.edit_args).contains(&"output");
@@ -402,6 +408,7 @@ pub fn edit_diff_external(
         got_output_field.then_some(DiffSide::Right),
         instructions,
         &options,
+        prefetch_concurrency,
     )?;
 
     let patterns = diffedit_wc.working_copies.to_command_variables(false);
@@ -424,6 +431,7 @@ pub fn edit_diff_external(
 }
 
 /// Generates textual diff by the specified `tool` and writes into `writer`.
+#[expect(clippy::too_many_arguments)]
 pub fn generate_diff(
     ui: &Ui,
     writer: &mut dyn Write,
@@ -432,15 +440,27 @@ pub fn generate_diff(
     matcher: &dyn Matcher,
     tool: &ExternalMergeTool,
     default_conflict_marker_style: ConflictMarkerStyle,
+    prefetch_concurrency: usize,
 ) -> Result<(), DiffGenerateError> {
     let conflict_marker_style = tool
         .conflict_marker_style
         .unwrap_or(default_conflict_marker_style);
     let options = CheckoutOptions {
         conflict_marker_style,
+        conflict_labels: ConflictLabels::unlabeled(),
+        lazy_conflicts: false,
+        progress: None,
     };
     let store = left_tree.store();
-    let diff_wc = check_out_trees(store, left_tree, right_tree, matcher, None, &options)?;
+    let diff_wc = check_out_trees(
+        store,
+        left_tree,
+        right_tree,
+        matcher,
+        None,
+        &options,
+        prefetch_concurrency,
+    )?;
     set_readonly_recursively(diff_wc.left_working_copy_path())
         .map_err(ExternalToolError::SetUpDir)?;
     set_readonly_recursively(diff_wc.right_working_copy_path())
@@ -16,10 +16,13 @@ mod builtin;
 mod diff_working_copies;
 mod external;
 
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use itertools::Itertools as _;
 use jj_lib::backend::BackendError;
+use jj_lib::backend::BackendResult;
 use jj_lib::backend::CopyId;
 use jj_lib::backend::MergedTreeId;
 use jj_lib::backend::TreeValue;
@@ -29,6 +32,8 @@ use jj_lib::config::ConfigNamePathBuf;
 use jj_lib::conflicts::try_materialize_file_conflict_value;
 use jj_lib::conflicts::ConflictMarkerStyle;
 use jj_lib::conflicts::MaterializedFileConflictValue;
+use jj_lib::gitattributes::GitAttributesError;
+use jj_lib::gitattributes::TreeGitAttributes;
 use jj_lib::gitignore::GitIgnoreFile;
 use jj_lib::matchers::Matcher;
 use jj_lib::merge::Merge;
@@ -39,6 +44,7 @@ use jj_lib::repo_path::InvalidRepoPathError;
 use jj_lib::repo_path::RepoPath;
 use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::repo_path::RepoPathUiConverter;
+use jj_lib::settings::HumanByteSize;
 use jj_lib::settings::UserSettings;
 use jj_lib::working_copy::SnapshotError;
 use pollster::FutureExt as _;
@@ -46,6 +52,7 @@ use thiserror::Error;
 
 use self::builtin::edit_diff_builtin;
 use self::builtin::edit_merge_builtin;
+use self::builtin::find_binary_files;
 use self::builtin::BuiltinToolError;
 pub(crate) use self::diff_working_copies::new_utf8_temp_dir;
 use self::diff_working_copies::DiffCheckoutError;
@@ -113,6 +120,8 @@ pub enum ConflictResolveError {
     EmptyOrUnchanged,
     #[error(transparent)]
     Backend(#[from] jj_lib::backend::BackendError),
+    #[error("Failed to read .gitattributes")]
+    GitAttributes(#[from] GitAttributesError),
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
@@ -223,6 +232,84 @@ pub fn get_external_tool_config(
     Ok(Some(tool))
 }
 
+/// External tools selected per file by the `diff` or `merge` attribute in
+/// `.gitattributes`.
+///
+/// The attribute value (the Git "driver") names the tool in
+/// `[merge-tools.<name>]`. Files whose driver isn't a configured tool are left
+/// to the default tool.
+#[derive(Clone, Debug, Default)]
+pub struct DriverTools {
+    attribute: &'static str,
+    tools: HashMap<String, ExternalMergeTool>,
+}
+
+/// Group of files to be processed by the same tool. The tool is `None` for
+/// files which should be processed by the default tool.
+pub type DriverToolGroup<'a, 'p> = (Option<&'a ExternalMergeTool>, Vec<&'p RepoPath>);
+
+impl DriverTools {
+    /// Loads tools for the `diff` attribute.
+    pub fn diff_from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        Self::from_settings(settings, "diff", |_| true)
+    }
+
+    /// Loads tools for the `merge` attribute. Tools without `merge-args` are
+    /// ignored.
+    pub fn merge_from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        Self::from_settings(settings, "merge", |tool| !tool.merge_args.is_empty())
+    }
+
+    fn from_settings(
+        settings: &UserSettings,
+        attribute: &'static str,
+        is_usable: impl Fn(&ExternalMergeTool) -> bool,
+    ) -> Result<Self, ConfigGetError> {
+        let mut tools = HashMap::new();
+        for name in settings.table_keys("merge-tools") {
+            if let Some(tool) = get_external_tool_config(settings, name)? {
+                if is_usable(&tool) {
+                    tools.insert(name.to_owned(), tool);
+                }
+            }
+        }
+        Ok(DriverTools { attribute, tools })
+    }
+
+    /// Returns true if no tools can be selected by attributes.
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    /// Groups `paths` by the tool named by their attribute.
+    ///
+    /// The group of the default tool comes first, followed by the groups of
+    /// the driver tools sorted by name. Only non-empty groups are returned.
+    pub fn group_paths<'p>(
+        &self,
+        attributes: &mut TreeGitAttributes,
+        paths: impl IntoIterator<Item = &'p RepoPath>,
+    ) -> Result<Vec<DriverToolGroup<'_, 'p>>, GitAttributesError> {
+        let mut groups: BTreeMap<Option<&str>, Vec<&RepoPath>> = BTreeMap::new();
+        for path in paths {
+            let driver = if self.tools.is_empty() {
+                None
+            } else {
+                let state = attributes.get(path, self.attribute)?;
+                state
+                    .as_value()
+                    .and_then(|name| self.tools.get_key_value(name))
+                    .map(|(name, _)| name.as_str())
+            };
+            groups.entry(driver).or_default().push(path);
+        }
+        Ok(groups
+            .into_iter()
+            .map(|(driver, paths)| (driver.map(|name| &self.tools[name]), paths))
+            .collect())
+    }
+}
+
 /// Configured diff editor.
 #[derive(Clone, Debug)]
 pub struct DiffEditor {
@@ -230,6 +317,9 @@ pub struct DiffEditor {
     base_ignores: Arc<GitIgnoreFile>,
     use_instructions: bool,
     conflict_marker_style: ConflictMarkerStyle,
+    whole_file_conflicts: bool,
+    max_text_size: u64,
+    prefetch_concurrency: usize,
 }
 
 impl DiffEditor {
@@ -269,14 +359,37 @@ impl DiffEditor {
         base_ignores: Arc<GitIgnoreFile>,
         conflict_marker_style: ConflictMarkerStyle,
     ) -> Result<Self, MergeToolConfigError> {
+        let HumanByteSize(mut max_text_size) =
+            settings.get_value_with("ui.diff-editor-max-text-size", TryInto::try_into)?;
+        if max_text_size == 0 {
+            max_text_size = u64::MAX;
+        }
         Ok(DiffEditor {
             tool,
             base_ignores,
             use_instructions: settings.get_bool("ui.diff-instructions")?,
             conflict_marker_style,
+            whole_file_conflicts: false,
+            max_text_size,
+            prefetch_concurrency: settings.get("backend.prefetch-concurrency")?,
         })
     }
 
+    /// Returns true if this is the builtin diff editor.
+    pub fn is_builtin(&self) -> bool {
+        matches!(self.tool, DiffTool::Builtin)
+    }
+
+    /// Makes the builtin tool present conflicted files as a whole instead of
+    /// as materialized conflict markers, so that selecting them preserves the
+    /// structure of the conflicts.
+    ///
+    /// External tools still see the materialized conflicts.
+    pub fn with_whole_file_conflicts(mut self) -> Self {
+        self.whole_file_conflicts = true;
+        self
+    }
+
     /// Starts a diff editor on the two directories.
     // FIXME: edit_diff_builtin() applies diff on left_tree to create new tree,
     // whereas edit_diff_external() updates the right_tree. This means that the
@@ -293,12 +406,15 @@ impl DiffEditor {
         format_instructions: impl FnOnce() -> String,
     ) -> Result<MergedTreeId, DiffEditError> {
         match &self.tool {
-            DiffTool::Builtin => {
-                Ok(
-                    edit_diff_builtin(left_tree, right_tree, matcher, self.conflict_marker_style)
-                        .map_err(Box::new)?,
-                )
-            }
+            DiffTool::Builtin => Ok(edit_diff_builtin(
+                left_tree,
+                right_tree,
+                matcher,
+                self.conflict_marker_style,
+                self.whole_file_conflicts,
+                self.max_text_size,
+            )
+            .map_err(Box::new)?),
             DiffTool::External(editor) => {
                 let instructions = self.use_instructions.then(format_instructions);
                 edit_diff_external(
@@ -309,10 +425,22 @@ impl DiffEditor {
                     instructions.as_deref(),
                     self.base_ignores.clone(),
                     self.conflict_marker_style,
+                    self.prefetch_concurrency,
                 )
             }
         }
     }
+
+    /// Returns the changed files which can only be selected as a whole by the
+    /// builtin diff editor because they're binary or large.
+    pub fn find_binary_files(
+        &self,
+        left_tree: &MergedTree,
+        right_tree: &MergedTree,
+        matcher: &dyn Matcher,
+    ) -> BackendResult<Vec<RepoPathBuf>> {
+        find_binary_files(left_tree, right_tree, matcher, self.max_text_size)
+    }
 }
 
 /// A file to be merged by a merge tool.
@@ -363,6 +491,7 @@ impl MergeToolFile {
 #[derive(Clone, Debug)]
 pub struct MergeEditor {
     tool: MergeTool,
+    driver_tools: DriverTools,
     path_converter: RepoPathUiConverter,
     conflict_marker_style: ConflictMarkerStyle,
 }
@@ -378,10 +507,19 @@ impl MergeEditor {
     ) -> Result<Self, MergeToolConfigError> {
         let tool = MergeTool::get_tool_config(settings, name)?
             .unwrap_or_else(|| MergeTool::external(ExternalMergeTool::with_program(name)));
-        Self::new_inner(name, tool, path_converter, conflict_marker_style)
+        Self::new_inner(
+            name,
+            tool,
+            DriverTools::default(),
+            path_converter,
+            conflict_marker_style,
+        )
     }
 
     /// Loads the default 3-way merge editor from the settings.
+    ///
+    /// Files whose `merge` attribute names a configured merge tool will be
+    /// resolved by that tool instead.
     pub fn from_settings(
         ui: &Ui,
         settings: &UserSettings,
@@ -395,12 +533,20 @@ impl MergeEditor {
             None
         }
         .unwrap_or_else(|| MergeTool::external(ExternalMergeTool::with_merge_args(&args)));
-        Self::new_inner(&args, tool, path_converter, conflict_marker_style)
+        let driver_tools = DriverTools::merge_from_settings(settings)?;
+        Self::new_inner(
+            &args,
+            tool,
+            driver_tools,
+            path_converter,
+            conflict_marker_style,
+        )
     }
 
     fn new_inner(
         name: impl ToString,
         tool: MergeTool,
+        driver_tools: DriverTools,
         path_converter: RepoPathUiConverter,
         conflict_marker_style: ConflictMarkerStyle,
     ) -> Result<Self, MergeToolConfigError> {
@@ -411,34 +557,83 @@ impl MergeEditor {
         }
         Ok(MergeEditor {
             tool,
+            driver_tools,
             path_converter,
             conflict_marker_style,
         })
     }
 
     /// Starts a merge editor for the specified files.
+    ///
+    /// Files are grouped by the tool selected by their `merge` attribute, and
+    /// the tool of each group is started for the files in that group only.
     pub fn edit_files(
         &self,
         ui: &Ui,
         tree: &MergedTree,
         repo_paths: &[&RepoPath],
     ) -> Result<(MergedTreeId, Option<MergeToolPartialResolutionError>), ConflictResolveError> {
-        let merge_tool_files: Vec<MergeToolFile> = repo_paths
-            .iter()
-            .map(|&repo_path| MergeToolFile::from_tree_and_path(tree, repo_path))
+        let mut attributes = TreeGitAttributes::new(tree.clone());
+        let groups: Vec<(Option<MergeTool>, Vec<MergeToolFile>)> = self
+            .driver_tools
+            .group_paths(&mut attributes, repo_paths.iter().copied())?
+            .into_iter()
+            .map(|(driver_tool, paths)| {
+                let tool = driver_tool.map(|tool| MergeTool::external(tool.clone()));
+                let files = paths
+                    .into_iter()
+                    .map(|repo_path| MergeToolFile::from_tree_and_path(tree, repo_path))
+                    .try_collect()?;
+                Ok::<_, ConflictResolveError>((tool, files))
+            })
             .try_collect()?;
 
-        match &self.tool {
+        let mut tree = tree.clone();
+        let mut resolved_count = 0;
+        for (tool, merge_tool_files) in &groups {
+            let tool = tool.as_ref().unwrap_or(&self.tool);
+            match self.edit_files_with_tool(ui, tool, &tree, merge_tool_files) {
+                Ok((tree_id, None)) => {
+                    tree = tree.store().get_root_tree(&tree_id)?;
+                    resolved_count += merge_tool_files.len();
+                }
+                Ok((tree_id, Some(mut err))) => {
+                    err.resolved_count += resolved_count;
+                    return Ok((tree_id, Some(err)));
+                }
+                Err(err) if resolved_count == 0 => return Err(err),
+                Err(err) => {
+                    // Return the files resolved by the previous tools so that
+                    // the caller can save them.
+                    let err = MergeToolPartialResolutionError {
+                        source: err,
+                        resolved_count,
+                    };
+                    return Ok((tree.id(), Some(err)));
+                }
+            }
+        }
+        Ok((tree.id(), None))
+    }
+
+    fn edit_files_with_tool(
+        &self,
+        ui: &Ui,
+        tool: &MergeTool,
+        tree: &MergedTree,
+        merge_tool_files: &[MergeToolFile],
+    ) -> Result<(MergedTreeId, Option<MergeToolPartialResolutionError>), ConflictResolveError> {
+        match tool {
             MergeTool::Builtin => {
-                let tree_id = edit_merge_builtin(tree, &merge_tool_files).map_err(Box::new)?;
+                let tree_id = edit_merge_builtin(tree, merge_tool_files).map_err(Box::new)?;
                 Ok((tree_id, None))
             }
             MergeTool::Ours => {
-                let tree_id = pick_conflict_side(tree, &merge_tool_files, 0)?;
+                let tree_id = pick_conflict_side(tree, merge_tool_files, 0)?;
                 Ok((tree_id, None))
             }
             MergeTool::Theirs => {
-                let tree_id = pick_conflict_side(tree, &merge_tool_files, 1)?;
+                let tree_id = pick_conflict_side(tree, merge_tool_files, 1)?;
                 Ok((tree_id, None))
             }
             MergeTool::External(editor) => external::run_mergetool_external(
@@ -446,7 +641,7 @@ impl MergeEditor {
                 &self.path_converter,
                 editor,
                 tree,
-                &merge_tool_files,
+                merge_tool_files,
                 self.conflict_marker_style,
             ),
         }
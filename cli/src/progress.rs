@@ -1,3 +1,5 @@
+use std::fmt::Write as _;
+use std::ops::ControlFlow;
 use std::path::Path;
 use std::sync::Mutex;
 use std::time::Duration;
@@ -6,6 +8,7 @@ use std::time::Instant;
 use crossterm::terminal::Clear;
 use crossterm::terminal::ClearType;
 use jj_lib::repo_path::RepoPath;
+use jj_lib::working_copy::CheckoutProgress;
 
 use crate::text_util;
 use crate::ui::OutputGuard;
@@ -14,6 +17,8 @@ use crate::ui::Ui;
 
 pub const UPDATE_HZ: u32 = 30;
 pub const INITIAL_DELAY: Duration = Duration::from_millis(250);
+/// Checkouts of fewer files than this don't display progress.
+const CHECKOUT_MIN_FILES: usize = 1000;
 
 pub fn snapshot_progress(ui: &Ui) -> Option<impl Fn(&RepoPath) + use<>> {
     struct State {
@@ -64,3 +69,78 @@ pub fn snapshot_progress(ui: &Ui) -> Option<impl Fn(&RepoPath) + use<>> {
         _ = state.output.flush();
     })
 }
+
+pub fn checkout_progress(ui: &Ui) -> Option<impl Fn(CheckoutProgress) -> ControlFlow<()> + use<>> {
+    struct State {
+        guard: Option<OutputGuard>,
+        output: ProgressOutput<std::io::Stderr>,
+        next_display_time: Instant,
+        buffer: String,
+    }
+
+    let output = ui.progress_output()?;
+
+    // Don't clutter the output during fast operations.
+    let next_display_time = Instant::now() + INITIAL_DELAY;
+    let state = Mutex::new(State {
+        guard: None,
+        output,
+        next_display_time,
+        buffer: String::new(),
+    });
+
+    Some(move |progress: CheckoutProgress| {
+        if progress.total_files < CHECKOUT_MIN_FILES {
+            return ControlFlow::Continue(());
+        }
+        let mut state = state.lock().unwrap();
+        let now = Instant::now();
+        if now < state.next_display_time {
+            return ControlFlow::Continue(());
+        }
+        state.next_display_time = now + Duration::from_secs(1) / UPDATE_HZ;
+
+        if state.guard.is_none() {
+            state.guard = Some(
+                state
+                    .output
+                    .output_guard(format!("\r{}", Clear(ClearType::CurrentLine))),
+            );
+        }
+
+        let State { output, buffer, .. } = &mut *state;
+        buffer.clear();
+        write!(
+            buffer,
+            "Checking out {}/{} files ",
+            progress.processed_files, progress.total_files
+        )
+        .unwrap();
+        let line_width = output.term_width().map(usize::from).unwrap_or(80);
+        let bar_width = line_width.saturating_sub(buffer.len() + 2);
+        buffer.push('[');
+        let fraction = progress.processed_files as f32 / progress.total_files as f32;
+        draw_progress(fraction, buffer, bar_width);
+        buffer.push(']');
+        _ = write!(output, "\r{}{buffer}", Clear(ClearType::CurrentLine));
+        _ = output.flush();
+        ControlFlow::Continue(())
+    })
+}
+
+pub fn draw_progress(progress: f32, buffer: &mut String, width: usize) {
+    const CHARS: [char; 9] = [' ', '▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+    const RESOLUTION: usize = CHARS.len() - 1;
+    let ticks = (width as f32 * progress.clamp(0.0, 1.0) * RESOLUTION as f32).round() as usize;
+    let whole = ticks / RESOLUTION;
+    for _ in 0..whole {
+        buffer.push(CHARS[CHARS.len() - 1]);
+    }
+    if whole < width {
+        let fraction = ticks % RESOLUTION;
+        buffer.push(CHARS[fraction]);
+    }
+    for _ in (whole + 1)..width {
+        buffer.push(CHARS[0]);
+    }
+}
@@ -15,6 +15,7 @@
 //! Utility for parsing and evaluating user-provided revset expressions.
 
 use std::io;
+use std::iter;
 use std::rc::Rc;
 use std::sync::Arc;
 
@@ -34,6 +35,7 @@ use jj_lib::revset::ResolvedRevsetExpression;
 use jj_lib::revset::Revset;
 use jj_lib::revset::RevsetAliasesMap;
 use jj_lib::revset::RevsetDiagnostics;
+use jj_lib::revset::RevsetEvaluationContext;
 use jj_lib::revset::RevsetEvaluationError;
 use jj_lib::revset::RevsetExpression;
 use jj_lib::revset::RevsetExtensions;
@@ -45,6 +47,8 @@ use jj_lib::revset::SymbolResolverExtension;
 use jj_lib::revset::UserRevsetExpression;
 use thiserror::Error;
 
+use crate::cleanup_guard;
+use crate::cleanup_guard::InterruptScope;
 use crate::command_error::user_error;
 use crate::command_error::CommandError;
 use crate::formatter::Formatter;
@@ -106,10 +110,19 @@ impl<'repo> RevsetExpressionEvaluator<'repo> {
     }
 
     /// Evaluates the expression.
+    ///
+    /// The evaluation, and the iteration over the returned revset, fail with
+    /// `RevsetEvaluationError::Interrupted` if `SIGINT` is received while it
+    /// is running.
     pub fn evaluate(&self) -> Result<Box<dyn Revset + 'repo>, UserRevsetEvaluationError> {
+        let _scope = InterruptScope::enter();
+        let context = RevsetEvaluationContext {
+            interrupt: cleanup_guard::interrupt_flag(),
+            deadline: None,
+        };
         self.resolve()
             .map_err(UserRevsetEvaluationError::Resolution)?
-            .evaluate(self.repo)
+            .evaluate_with_context(self.repo, &context)
             .map_err(UserRevsetEvaluationError::Evaluation)
     }
 
@@ -121,7 +134,7 @@ impl<'repo> RevsetExpressionEvaluator<'repo> {
         Box<dyn Iterator<Item = Result<CommitId, RevsetEvaluationError>> + 'repo>,
         UserRevsetEvaluationError,
     > {
-        Ok(self.evaluate()?.iter())
+        Ok(Box::new(interruptible(self.evaluate()?.iter())))
     }
 
     /// Evaluates the expression to an iterator over commit objects. Entries are
@@ -132,10 +145,19 @@ impl<'repo> RevsetExpressionEvaluator<'repo> {
         impl Iterator<Item = Result<Commit, RevsetEvaluationError>> + use<'repo>,
         UserRevsetEvaluationError,
     > {
-        Ok(self.evaluate()?.iter().commits(self.repo.store()))
+        Ok(interruptible(self.evaluate()?.iter()).commits(self.repo.store()))
     }
 }
 
+/// Makes `SIGINT` interrupt the revset iteration while an item is being
+/// produced.
+fn interruptible<T>(mut iter: impl Iterator<Item = T>) -> impl Iterator<Item = T> {
+    iter::from_fn(move || {
+        let _scope = InterruptScope::enter();
+        iter.next()
+    })
+}
+
 fn warn_user_redefined_builtin(
     ui: &Ui,
     source: ConfigSource,
@@ -146,6 +168,7 @@ fn warn_user_redefined_builtin(
         ConfigSource::EnvBase
         | ConfigSource::User
         | ConfigSource::Repo
+        | ConfigSource::Workspace
         | ConfigSource::EnvOverrides
         | ConfigSource::CommandArg => {
             let checked_mutability_builtins =
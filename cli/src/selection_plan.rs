@@ -0,0 +1,464 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Plan files listing the changes to select from a diff.
+//!
+//! A plan can be recorded from an interactive selection, and replayed later
+//! without starting a diff editor. The plan is a TOML file like this:
+//!
+//! ```toml
+//! # Select the whole change
+//! [[file]]
+//! path = "src/lib.rs"
+//!
+//! # Select some of the hunks
+//! [[file]]
+//! path = "README.md"
+//! hunks = [{ old-start = 3, old-lines = 1, new-start = 3, new-lines = 2 }]
+//!
+//! # Select a hunk after editing its new text
+//! [[file]]
+//! path = "CHANGELOG.md"
+//! hunks = [{ old-start = 9, old-lines = 0, new-start = 9, new-lines = 1, edit = true }]
+//! ```
+//!
+//! Paths are relative to the workspace root, and use `/` as the separator.
+//! Hunks are specified by their line ranges in the left (old) and right (new)
+//! files, and must match the actual diff. The new text of the hunks marked as
+//! `edit` is opened in the text editor, and the edited text is selected
+//! instead.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+
+use futures::StreamExt as _;
+use itertools::Itertools as _;
+use jj_lib::backend::BackendError;
+use jj_lib::backend::BackendResult;
+use jj_lib::backend::CopyId;
+use jj_lib::backend::MergedTreeId;
+use jj_lib::backend::TreeValue;
+use jj_lib::diff::Diff;
+use jj_lib::diff::DiffHunkKind;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::merge::MergedTreeValue;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::merged_tree::MergedTreeBuilder;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::repo_path::RepoPathBuf;
+use jj_lib::store::Store;
+use pollster::FutureExt as _;
+use tokio::io::AsyncReadExt as _;
+
+use crate::command_error::user_error;
+use crate::command_error::user_error_with_message;
+use crate::command_error::CommandError;
+use crate::description_util::TextEditor;
+
+/// Arguments to select changes by a plan file.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct SelectionPlanArgs {
+    /// Select the changes listed in this plan file instead of starting a diff
+    /// editor
+    ///
+    /// The plan lists paths to select, and optionally the hunks to select
+    /// within the files. A plan can be recorded by
+    /// `--emit-selection-plan`.
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        conflicts_with = "tool",
+    )]
+    pub selection_plan: Option<PathBuf>,
+    /// Write the changes selected in the diff editor to this plan file
+    ///
+    /// The plan can be replayed by `--selection-plan`. Changes within files
+    /// can only be recorded if whole hunks are selected.
+    #[arg(
+        long,
+        value_name = "FILE",
+        value_hint = clap::ValueHint::FilePath,
+        conflicts_with = "selection_plan",
+    )]
+    pub emit_selection_plan: Option<PathBuf>,
+}
+
+/// Changes to select from a diff.
+#[derive(Clone, Debug, Default, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelectionPlan {
+    #[serde(default, rename = "file")]
+    pub files: Vec<FileSelection>,
+}
+
+/// Selected change in a file.
+#[derive(Clone, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileSelection {
+    /// Path relative to the workspace root.
+    pub path: String,
+    /// Hunks to select, or `None` to select the whole change.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hunks: Option<Vec<HunkSelection>>,
+}
+
+/// Line ranges of a hunk in the left (old) and right (new) files.
+///
+/// Line numbers are 1-based. If a range is empty, its start is the number of
+/// the line before which the lines are inserted or deleted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct HunkSelection {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    /// Whether to edit the new text of the hunk before selecting it.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub edit: bool,
+}
+
+impl HunkSelection {
+    fn new(old: &Range<usize>, new: &Range<usize>) -> Self {
+        HunkSelection {
+            old_start: old.start + 1,
+            old_lines: old.len(),
+            new_start: new.start + 1,
+            new_lines: new.len(),
+            edit: false,
+        }
+    }
+
+    /// Returns true if the line ranges are the same, ignoring the `edit` flag.
+    fn has_same_lines(&self, other: &Self) -> bool {
+        let edit = false;
+        HunkSelection { edit, ..*self } == HunkSelection { edit, ..*other }
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+impl fmt::Display for HunkSelection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let HunkSelection {
+            old_start,
+            old_lines,
+            new_start,
+            new_lines,
+            edit: _,
+        } = self;
+        write!(f, "-{old_start},{old_lines} +{new_start},{new_lines}")
+    }
+}
+
+impl SelectionPlan {
+    /// Reads plan from the TOML file.
+    pub fn load(path: &Path) -> Result<Self, CommandError> {
+        let message = || format!("Failed to read selection plan {}", path.display());
+        let text =
+            fs::read_to_string(path).map_err(|err| user_error_with_message(message(), err))?;
+        toml_edit::de::from_str(&text).map_err(|err| user_error_with_message(message(), err))
+    }
+
+    /// Writes plan to the TOML file.
+    pub fn save(&self, path: &Path) -> Result<(), CommandError> {
+        let message = || format!("Failed to write selection plan {}", path.display());
+        let text = toml_edit::ser::to_string_pretty(self)
+            .map_err(|err| user_error_with_message(message(), err))?;
+        fs::write(path, text).map_err(|err| user_error_with_message(message(), err))
+    }
+
+    /// Applies the selected changes from `left_tree` to `right_tree` to the
+    /// `left_tree`.
+    ///
+    /// All entries are validated against the actual diff, and the mismatches
+    /// are reported together since the plan may be stale. The hunks to be
+    /// edited are then opened in the `text_editor` one by one.
+    pub fn apply(
+        &self,
+        left_tree: &MergedTree,
+        right_tree: &MergedTree,
+        text_editor: &TextEditor,
+    ) -> Result<MergedTreeId, CommandError> {
+        let store = left_tree.store();
+        let changes = collect_changes(left_tree, right_tree)?;
+        let mut tree_builder = MergedTreeBuilder::new(left_tree.id());
+        let mut partial_files = vec![];
+        let mut mismatches = vec![];
+        for file in &self.files {
+            let Ok(path) = RepoPathBuf::from_internal_string(&file.path) else {
+                mismatches.push(format!("{}: invalid path", file.path));
+                continue;
+            };
+            let Some((left_value, right_value)) = changes.get(&path) else {
+                mismatches.push(format!("{}: no changes", file.path));
+                continue;
+            };
+            let Some(hunks) = &file.hunks else {
+                tree_builder.set_or_remove(path, right_value.clone());
+                continue;
+            };
+            let Some((left_file, right_file)) =
+                read_file_pair(store, &path, left_value, right_value)?
+            else {
+                mismatches.push(format!(
+                    "{}: hunks can only be selected in files",
+                    file.path
+                ));
+                continue;
+            };
+            let diff_hunks = diff_hunks(&left_file.contents, &right_file.contents);
+            let selected: BTreeMap<usize, HunkSelection> = hunks
+                .iter()
+                .filter_map(|hunk| {
+                    let index = diff_hunks
+                        .iter()
+                        .position(|diff_hunk| diff_hunk.selection().has_same_lines(hunk));
+                    if index.is_none() {
+                        mismatches.push(format!("{}: no hunk {hunk}", file.path));
+                    }
+                    Some((index?, *hunk))
+                })
+                .collect();
+            if selected.is_empty() {
+                continue;
+            } else if selected.len() == diff_hunks.len() && selected.values().all(|h| !h.edit) {
+                tree_builder.set_or_remove(path, right_value.clone());
+                continue;
+            }
+            partial_files.push((path, left_file, right_file, diff_hunks, selected));
+        }
+        if !mismatches.is_empty() {
+            return Err(user_error(format!(
+                "The selection plan doesn't match the changes:\n{}",
+                mismatches.iter().map(|line| format!("  {line}")).join("\n")
+            )));
+        }
+        for (path, left_file, right_file, diff_hunks, selected) in partial_files {
+            let mut contents = vec![];
+            let mut left_pos = 0;
+            for (index, diff_hunk) in diff_hunks.iter().enumerate() {
+                contents.extend_from_slice(&left_file.contents[left_pos..diff_hunk.left.start]);
+                let right_text = &right_file.contents[diff_hunk.right.clone()];
+                match selected.get(&index) {
+                    Some(hunk) if hunk.edit => {
+                        let name = format!("hunk {hunk} of {}", path.as_internal_file_string());
+                        let edited = text_editor
+                            .edit_str(right_text, None)
+                            .map_err(|err| err.with_name(name))?;
+                        contents.extend_from_slice(edited.as_bytes());
+                    }
+                    Some(_) => contents.extend_from_slice(right_text),
+                    None => {
+                        contents.extend_from_slice(&left_file.contents[diff_hunk.left.clone()]);
+                    }
+                }
+                left_pos = diff_hunk.left.end;
+            }
+            contents.extend_from_slice(&left_file.contents[left_pos..]);
+            let id = store
+                .write_file(&path, &mut contents.as_slice())
+                .block_on()?;
+            let executable = left_file
+                .executable
+                .or(right_file.executable)
+                .unwrap_or(false);
+            let value = TreeValue::File {
+                id,
+                executable,
+                copy_id: CopyId::placeholder(),
+            };
+            tree_builder.set_or_remove(path, MergedTreeValue::normal(value));
+        }
+        Ok(tree_builder.write_tree(store)?)
+    }
+
+    /// Creates a plan which selects the changes from `left_tree` to
+    /// `right_tree` as in the `selected_tree`.
+    pub fn from_selection(
+        left_tree: &MergedTree,
+        right_tree: &MergedTree,
+        selected_tree: &MergedTree,
+    ) -> Result<Self, CommandError> {
+        let store = left_tree.store();
+        let changes = collect_changes(left_tree, right_tree)?;
+        let mut files = vec![];
+        for (path, (left_value, right_value)) in changes {
+            let selected_value = selected_tree.path_value(&path)?;
+            if selected_value == right_value {
+                files.push(FileSelection {
+                    path: path.as_internal_file_string().to_owned(),
+                    hunks: None,
+                });
+                continue;
+            } else if selected_value == left_value {
+                continue;
+            }
+            let unrepresentable = || {
+                user_error(format!(
+                    "The changes selected in {} can't be recorded in a selection plan",
+                    path.as_internal_file_string()
+                ))
+            };
+            let (Some((left_file, right_file)), Some((_, selected_file))) = (
+                read_file_pair(store, &path, &left_value, &right_value)?,
+                read_file_pair(store, &path, &left_value, &selected_value)?,
+            ) else {
+                return Err(unrepresentable());
+            };
+            let right_hunks = diff_hunks(&left_file.contents, &right_file.contents);
+            let mut hunks = vec![];
+            for selected_hunk in diff_hunks(&left_file.contents, &selected_file.contents) {
+                let selected_lines = &selected_file.contents[selected_hunk.right.clone()];
+                let diff_hunk = right_hunks
+                    .iter()
+                    .find(|diff_hunk| {
+                        diff_hunk.left_lines == selected_hunk.left_lines
+                            && &right_file.contents[diff_hunk.right.clone()] == selected_lines
+                    })
+                    .ok_or_else(unrepresentable)?;
+                hunks.push(diff_hunk.selection());
+            }
+            files.push(FileSelection {
+                path: path.as_internal_file_string().to_owned(),
+                hunks: Some(hunks),
+            });
+        }
+        Ok(SelectionPlan { files })
+    }
+}
+
+fn collect_changes(
+    left_tree: &MergedTree,
+    right_tree: &MergedTree,
+) -> BackendResult<BTreeMap<RepoPathBuf, (MergedTreeValue, MergedTreeValue)>> {
+    async {
+        let mut changes = BTreeMap::new();
+        let mut diff_stream = left_tree.diff_stream(right_tree, &EverythingMatcher);
+        while let Some(entry) = diff_stream.next().await {
+            changes.insert(entry.path, entry.values?);
+        }
+        Ok(changes)
+    }
+    .block_on()
+}
+
+/// Contents of a file or an absent file.
+struct FileContents {
+    contents: Vec<u8>,
+    /// Executable bit, or `None` if the file is absent.
+    executable: Option<bool>,
+}
+
+/// Reads both sides if they're regular files or absent.
+fn read_file_pair(
+    store: &Store,
+    path: &RepoPath,
+    left_value: &MergedTreeValue,
+    right_value: &MergedTreeValue,
+) -> BackendResult<Option<(FileContents, FileContents)>> {
+    let (Some(left), Some(right)) = (
+        read_file_contents(store, path, left_value)?,
+        read_file_contents(store, path, right_value)?,
+    ) else {
+        return Ok(None);
+    };
+    Ok(Some((left, right)))
+}
+
+fn read_file_contents(
+    store: &Store,
+    path: &RepoPath,
+    value: &MergedTreeValue,
+) -> BackendResult<Option<FileContents>> {
+    match value.as_resolved() {
+        Some(None) => Ok(Some(FileContents {
+            contents: vec![],
+            executable: None,
+        })),
+        Some(Some(TreeValue::File { id, executable, .. })) => {
+            let mut contents = vec![];
+            async {
+                let mut reader = store.read_file(path, id).await?;
+                reader
+                    .read_to_end(&mut contents)
+                    .await
+                    .map_err(|err| BackendError::ReadFile {
+                        path: path.to_owned(),
+                        id: id.clone(),
+                        source: err.into(),
+                    })
+            }
+            .block_on()?;
+            Ok(Some(FileContents {
+                contents,
+                executable: Some(*executable),
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Changed region in a line-by-line diff.
+struct DiffHunk {
+    /// Byte range in the left file.
+    left: Range<usize>,
+    /// Byte range in the right file.
+    right: Range<usize>,
+    /// Line range in the left file.
+    left_lines: Range<usize>,
+    /// Line range in the right file.
+    right_lines: Range<usize>,
+}
+
+impl DiffHunk {
+    fn selection(&self) -> HunkSelection {
+        HunkSelection::new(&self.left_lines, &self.right_lines)
+    }
+}
+
+fn diff_hunks(left: &[u8], right: &[u8]) -> Vec<DiffHunk> {
+    let count_lines = |text: &[u8]| text.split_inclusive(|&b| b == b'\n').count();
+    let diff = Diff::by_line([left, right]);
+    let mut hunks = vec![];
+    let (mut left_pos, mut right_pos) = (0, 0);
+    let (mut left_line, mut right_line) = (0, 0);
+    for hunk in diff.hunks() {
+        let [left_text, right_text] = hunk.contents[..] else {
+            panic!("only two inputs were provided to the diff");
+        };
+        let left_end = left_pos + left_text.len();
+        let right_end = right_pos + right_text.len();
+        let left_line_end = left_line + count_lines(left_text);
+        let right_line_end = right_line + count_lines(right_text);
+        if hunk.kind == DiffHunkKind::Different {
+            hunks.push(DiffHunk {
+                left: left_pos..left_end,
+                right: right_pos..right_end,
+                left_lines: left_line..left_line_end,
+                right_lines: right_line..right_line_end,
+            });
+        }
+        (left_pos, right_pos) = (left_end, right_end);
+        (left_line, right_line) = (left_line_end, right_line_end);
+    }
+    hunks
+}
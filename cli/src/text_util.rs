@@ -93,33 +93,72 @@ pub fn elide_end<'a>(text: &'a str, ellipsis: &'a str, max_width: usize) -> (Cow
     (Cow::Owned([text, ellipsis].concat()), concat_width)
 }
 
+/// Shortens `text` to `max_width` by removing characters in the middle.
+/// `ellipsis` is inserted if the `text` gets truncated.
+///
+/// The available width is split evenly between the leading and trailing parts,
+/// and the trailing part takes over what the leading part couldn't fill. The
+/// returned string (including `ellipsis`) never exceeds the `max_width`.
+pub fn elide_middle<'a>(
+    text: &'a str,
+    ellipsis: &'a str,
+    max_width: usize,
+) -> (Cow<'a, str>, usize) {
+    let (text_end, text_width) = truncate_end_pos(text, max_width);
+    if text_end == text.len() {
+        return (Cow::Borrowed(text), text_width);
+    }
+
+    let (ellipsis_end, ellipsis_width) = truncate_end_pos(ellipsis, max_width);
+    if ellipsis_end != ellipsis.len() {
+        let ellipsis = &ellipsis[..ellipsis_end];
+        return (Cow::Borrowed(ellipsis), ellipsis_width);
+    }
+
+    let max_text_width = max_width - ellipsis_width;
+    let (head_end, head_width) = truncate_end_pos(text, max_text_width.div_ceil(2));
+    let rest = &text[head_end..];
+    let (tail_start, tail_width) = truncate_start_pos(rest, max_text_width - head_width);
+    let tail = trim_start_zero_width_chars(&rest[tail_start..]);
+    let concat_width = head_width + ellipsis_width + tail_width;
+    assert!(concat_width <= max_width);
+    (
+        Cow::Owned([&text[..head_end], ellipsis, tail].concat()),
+        concat_width,
+    )
+}
+
+/// Returns the first line of the `description` shortened to `max_width`.
+///
+/// Surrounding whitespace is trimmed, and `"..."` is appended if the line gets
+/// truncated.
+pub fn summary_line(description: &str, max_width: usize) -> (Cow<'_, str>, usize) {
+    let line = description.lines().next().unwrap_or("").trim();
+    elide_end(line, "...", max_width)
+}
+
 /// Shortens `text` to `max_width` by removing leading characters, returning
 /// `(start_index, width)`.
 ///
-/// The truncated string may have 0-width decomposed characters at start.
+/// The truncated string may have 0-width characters at start.
 fn truncate_start_pos(text: &str, max_width: usize) -> (usize, usize) {
-    truncate_start_pos_with_indices(
-        text.char_indices()
-            .rev()
-            .map(|(start, c)| (start + c.len_utf8(), c)),
-        max_width,
-    )
+    truncate_start_pos_bytes(text.as_bytes(), max_width)
 }
 
 fn truncate_start_pos_bytes(text: &[u8], max_width: usize) -> (usize, usize) {
     truncate_start_pos_with_indices(
-        text.char_indices().rev().map(|(_, end, c)| (end, c)),
+        text.grapheme_indices().rev().map(|(_, end, g)| (end, g)),
         max_width,
     )
 }
 
-fn truncate_start_pos_with_indices(
-    char_indices_rev: impl Iterator<Item = (usize, char)>,
+fn truncate_start_pos_with_indices<'a>(
+    grapheme_indices_rev: impl Iterator<Item = (usize, &'a str)>,
     max_width: usize,
 ) -> (usize, usize) {
     let mut acc_width = 0;
-    for (end, c) in char_indices_rev {
-        let new_width = acc_width + c.width().unwrap_or(0);
+    for (end, g) in grapheme_indices_rev {
+        let new_width = acc_width + g.width();
         if new_width > max_width {
             return (end, acc_width);
         }
@@ -131,25 +170,25 @@ fn truncate_start_pos_with_indices(
 /// Shortens `text` to `max_width` by removing trailing characters, returning
 /// `(end_index, width)`.
 fn truncate_end_pos(text: &str, max_width: usize) -> (usize, usize) {
-    truncate_end_pos_with_indices(text.char_indices(), text.len(), max_width)
+    truncate_end_pos_bytes(text.as_bytes(), max_width)
 }
 
 fn truncate_end_pos_bytes(text: &[u8], max_width: usize) -> (usize, usize) {
     truncate_end_pos_with_indices(
-        text.char_indices().map(|(start, _, c)| (start, c)),
+        text.grapheme_indices().map(|(start, _, g)| (start, g)),
         text.len(),
         max_width,
     )
 }
 
-fn truncate_end_pos_with_indices(
-    char_indices_fwd: impl Iterator<Item = (usize, char)>,
+fn truncate_end_pos_with_indices<'a>(
+    grapheme_indices_fwd: impl Iterator<Item = (usize, &'a str)>,
     text_len: usize,
     max_width: usize,
 ) -> (usize, usize) {
     let mut acc_width = 0;
-    for (start, c) in char_indices_fwd {
-        let new_width = acc_width + c.width().unwrap_or(0);
+    for (start, g) in grapheme_indices_fwd {
+        let new_width = acc_width + g.width();
         if new_width > max_width {
             return (start, acc_width);
         }
@@ -163,24 +202,16 @@ fn truncate_end_pos_with_indices(
 /// The `skipped_width` may exceed the given `width` if `width` is not at
 /// character boundary.
 ///
-/// The truncated string may have 0-width decomposed characters at start.
+/// The truncated string may have 0-width characters at start.
 fn skip_start_pos(text: &str, width: usize) -> (usize, usize) {
-    skip_start_pos_with_indices(text.char_indices(), text.len(), width)
-}
-
-fn skip_start_pos_with_indices(
-    char_indices_fwd: impl Iterator<Item = (usize, char)>,
-    text_len: usize,
-    width: usize,
-) -> (usize, usize) {
     let mut acc_width = 0;
-    for (start, c) in char_indices_fwd {
+    for (start, _, g) in text.as_bytes().grapheme_indices() {
         if acc_width >= width {
             return (start, acc_width);
         }
-        acc_width += c.width().unwrap_or(0);
+        acc_width += g.width();
     }
-    (text_len, acc_width)
+    (text.len(), acc_width)
 }
 
 /// Skips `width` trailing characters, returning `(end_index, skipped_width)`.
@@ -188,24 +219,12 @@ fn skip_start_pos_with_indices(
 /// The `skipped_width` may exceed the given `width` if `width` is not at
 /// character boundary.
 fn skip_end_pos(text: &str, width: usize) -> (usize, usize) {
-    skip_end_pos_with_indices(
-        text.char_indices()
-            .rev()
-            .map(|(start, c)| (start + c.len_utf8(), c)),
-        width,
-    )
-}
-
-fn skip_end_pos_with_indices(
-    char_indices_rev: impl Iterator<Item = (usize, char)>,
-    width: usize,
-) -> (usize, usize) {
     let mut acc_width = 0;
-    for (end, c) in char_indices_rev {
+    for (_, end, g) in text.as_bytes().grapheme_indices().rev() {
         if acc_width >= width {
             return (end, acc_width);
         }
-        acc_width += c.width().unwrap_or(0);
+        acc_width += g.width();
     }
     (0, acc_width)
 }
@@ -657,6 +676,20 @@ mod tests {
             elide_start("a\u{300}bcde\u{300}", "A\u{300}CE\u{300}", 2),
             ("CE\u{300}".into(), 2)
         );
+
+        // Emoji sequences are kept as a whole
+        assert_eq!(
+            elide_start("a\u{1f469}\u{200d}\u{1f4bb}b", "", 4),
+            ("a\u{1f469}\u{200d}\u{1f4bb}b".into(), 4)
+        );
+        assert_eq!(
+            elide_start("a\u{1f469}\u{200d}\u{1f4bb}b", "", 2),
+            ("b".into(), 1)
+        );
+        assert_eq!(
+            elide_start("\u{1f1ef}\u{1f1f5}\u{1f1f0}\u{1f1f7}", ".", 3),
+            (".\u{1f1f0}\u{1f1f7}".into(), 3)
+        );
     }
 
     #[test]
@@ -730,6 +763,84 @@ mod tests {
             elide_end("a\u{300}bcde\u{300}", "A\u{300}CE\u{300}", 2),
             ("A\u{300}C".into(), 2)
         );
+
+        // Emoji sequences are kept as a whole
+        assert_eq!(
+            elide_end("a\u{1f469}\u{200d}\u{1f4bb}b", "", 4),
+            ("a\u{1f469}\u{200d}\u{1f4bb}b".into(), 4)
+        );
+        assert_eq!(
+            elide_end("a\u{1f469}\u{200d}\u{1f4bb}b", "", 2),
+            ("a".into(), 1)
+        );
+        assert_eq!(
+            elide_end("\u{1f1ef}\u{1f1f5}\u{1f1f0}\u{1f1f7}", ".", 3),
+            ("\u{1f1ef}\u{1f1f5}.".into(), 3)
+        );
+    }
+
+    #[test]
+    fn test_elide_middle() {
+        // Empty string
+        assert_eq!(elide_middle("", "", 1), ("".into(), 0));
+
+        // Basic truncation
+        assert_eq!(elide_middle("abcdef", "", 6), ("abcdef".into(), 6));
+        assert_eq!(elide_middle("abcdef", "", 5), ("abcef".into(), 5));
+        assert_eq!(elide_middle("abcdef", "", 1), ("a".into(), 1));
+        assert_eq!(elide_middle("abcdef", "", 0), ("".into(), 0));
+        assert_eq!(elide_middle("abcdef", "-=~", 6), ("abcdef".into(), 6));
+        assert_eq!(elide_middle("abcdef", "-=~", 5), ("a-=~f".into(), 5));
+        assert_eq!(elide_middle("abcdef", "-=~", 4), ("a-=~".into(), 4));
+        assert_eq!(elide_middle("abcdef", "-=~", 3), ("-=~".into(), 3));
+        assert_eq!(elide_middle("abcdef", "-=~", 2), ("-=".into(), 2));
+        assert_eq!(elide_middle("abcdef", "-=~", 0), ("".into(), 0));
+
+        // East Asian characters (char.width() == 2)
+        assert_eq!(elide_middle("一二三四", "", 8), ("一二三四".into(), 8));
+        assert_eq!(elide_middle("一二三四", "", 7), ("一二四".into(), 6));
+        assert_eq!(elide_middle("一二三四", "", 5), ("一四".into(), 4));
+        assert_eq!(elide_middle("一二三四", ".", 7), ("一.三四".into(), 7));
+        assert_eq!(elide_middle("一二三四", ".", 6), ("一.四".into(), 5));
+        assert_eq!(elide_middle("一二三四", "略", 6), ("一略四".into(), 6));
+        assert_eq!(elide_middle("一二三四", "略", 3), ("略".into(), 2));
+        assert_eq!(elide_middle("a一二三b", ".", 5), ("a.三b".into(), 5));
+
+        // Decomposed character at boundary
+        assert_eq!(
+            elide_middle("a\u{300}bcde\u{300}", "", 4),
+            ("a\u{300}bde\u{300}".into(), 4)
+        );
+        assert_eq!(
+            elide_middle("a\u{300}bcde\u{300}", ".", 3),
+            ("a\u{300}.e\u{300}".into(), 3)
+        );
+
+        // Emoji sequences are kept as a whole
+        assert_eq!(
+            elide_middle("\u{1f469}\u{200d}\u{1f4bb}abc\u{1f1ef}\u{1f1f5}", ".", 6),
+            ("\u{1f469}\u{200d}\u{1f4bb}a.\u{1f1ef}\u{1f1f5}".into(), 6)
+        );
+        assert_eq!(
+            elide_middle("\u{1f469}\u{200d}\u{1f4bb}abc\u{1f1ef}\u{1f1f5}", ".", 4),
+            ("\u{1f469}\u{200d}\u{1f4bb}.".into(), 3)
+        );
+    }
+
+    #[test]
+    fn test_summary_line() {
+        assert_eq!(summary_line("", 10), ("".into(), 0));
+        assert_eq!(summary_line("  first  \nsecond\n", 10), ("first".into(), 5));
+        assert_eq!(summary_line("\nsecond\n", 10), ("".into(), 0));
+        assert_eq!(
+            summary_line("a long first line\n", 10),
+            ("a long ...".into(), 10)
+        );
+        assert_eq!(summary_line("日本語の説明\n", 10), ("日本語...".into(), 9));
+        assert_eq!(
+            summary_line("\u{1f469}\u{200d}\u{1f4bb} hacking\n", 6),
+            ("\u{1f469}\u{200d}\u{1f4bb} ...".into(), 6)
+        );
     }
 
     #[test]
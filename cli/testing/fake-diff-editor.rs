@@ -123,6 +123,19 @@ fn main() {
             ["write", file] => {
                 std::fs::write(args.after.join(file), payload).unwrap();
             }
+            ["run"] => {
+                // The program and its arguments are given one per line
+                let lines = payload.lines().collect_vec();
+                let (program, program_args) = lines.split_first().unwrap();
+                let status = std::process::Command::new(program)
+                    .args(program_args)
+                    .status()
+                    .unwrap();
+                if !status.success() {
+                    eprintln!("fake-diff-editor: command failed: {payload}");
+                    exit(1)
+                }
+            }
             _ => {
                 eprintln!("fake-diff-editor: unexpected command: {command}");
                 exit(1)
@@ -465,6 +465,76 @@ fn test_abandon_restore_descendants() {
     ");
 }
 
+#[test]
+fn test_abandon_keep_working_copy_content() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file", "foo\n");
+    work_dir
+        .run_jj(["bookmark", "create", "-r@", "a"])
+        .success();
+    work_dir.run_jj(["new"]).success();
+    work_dir.write_file("file", "bar\n");
+    work_dir
+        .run_jj(["bookmark", "create", "-r@", "b"])
+        .success();
+    work_dir.run_jj(["new"]).success();
+    work_dir.write_file("other", "c\n");
+    work_dir
+        .run_jj(["bookmark", "create", "-r@", "c"])
+        .success();
+    work_dir.run_jj(["new", "b"]).success();
+    work_dir.write_file("file", "baz\n");
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  [yqo]
+    │ ○  [mzv] c
+    ├─╯
+    ○  [kkm] b
+    ○  [qpv] a
+    ◆  [zzz]
+    [EOF]
+    ");
+
+    // The working-copy commit absorbs the changes of "b", while "c" is rebased
+    let output = work_dir.run_jj(["abandon", "b", "--keep-working-copy-content"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Abandoned 1 commits:
+      kkmpptxz 7ce013ac b | (no description set)
+    Deleted bookmarks: b
+    Rebased 2 descendant commits onto parents of abandoned commits
+    Working copy  (@) now at: yqosqzyt ae35697f (no description set)
+    Parent commit (@-)      : qpvuntsm d0c049cd a | (no description set)
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  [yqo]
+    │ ○  [mzv] c
+    ├─╯
+    ○  [qpv] a
+    ◆  [zzz]
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "--git"]);
+    insta::assert_snapshot!(output, @r"
+    diff --git a/file b/file
+    index 257cc5642c..76018072e0 100644
+    --- a/file
+    +++ b/file
+    @@ -1,1 +1,1 @@
+    -foo
+    +baz
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "-rc", "file"]);
+    insta::assert_snapshot!(output, @r"
+    foo
+    [EOF]
+    ");
+}
+
 #[test]
 fn test_abandon_tracking_bookmarks() {
     let test_env = TestEnvironment::default();
@@ -508,6 +578,7 @@ fn test_abandon_tracking_bookmarks() {
     Abandoned 1 commits:
       qpvuntsm e8849ae1 foo | (empty) (no description set)
     Deleted bookmarks: foo
+    Warning: Deleted bookmarks still exist on tracked remotes: foo
     Hint: Deleted bookmarks can be pushed by name or all at once with `jj git push --deleted`.
     [EOF]
     ");
@@ -523,6 +594,381 @@ fn test_abandon_tracking_bookmarks() {
     ");
 }
 
+#[test]
+fn test_abandon_reports_new_conflicts() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file", "a\n");
+    work_dir.run_jj(["describe", "-m", "a"]).success();
+    work_dir.run_jj(["new", "-m", "b"]).success();
+    work_dir.write_file("file", "b\n");
+    work_dir.run_jj(["new", "-m", "c"]).success();
+    work_dir.write_file("file", "c\n");
+    work_dir
+        .run_jj(["new", "description(a)", "-m", "d"])
+        .success();
+    work_dir.write_file("other", "d\n");
+    work_dir
+        .run_jj(["new", "description(b)", "-m", "e"])
+        .success();
+    work_dir.write_file("other", "e\n");
+
+    // Abandoning "b" makes "c" conflicted, but not "e"
+    let output = work_dir.run_jj(["abandon", "description(b)"]);
+    let c_commit_id = work_dir
+        .run_jj([
+            "log",
+            "--no-graph",
+            "-r",
+            "description(c)",
+            "-T",
+            "commit_id",
+        ])
+        .success()
+        .stdout
+        .into_raw();
+    let output = output.normalize_stderr_with(|s| s.replace(&c_commit_id, "<c>"));
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Abandoned 1 commits:
+      kkmpptxz 5430e214 b
+    Rebased 2 descendant commits onto parents of abandoned commits
+    Warning: 1 descendant commits now have conflicts
+    Hint: To list them, run `jj log -r '<c>'`.
+    Working copy  (@) now at: royxmykx 4c6bd24c e
+    Parent commit (@-)      : qpvuntsm 7febbd38 a
+    Added 0 files, modified 1 files, removed 0 files
+    New conflicts appeared in 1 commits:
+      zsuskuln fc7b63fb (conflict) c
+    Hint: To resolve the conflicts, start by creating a commit on top of
+    the conflicted commit:
+      jj new zsuskuln
+    Then use `jj resolve`, or edit the conflict markers in the file directly.
+    Once the conflicts are resolved, you can inspect the result with `jj diff`.
+    Then run `jj squash` to move the resolution into the conflicted commit.
+    [EOF]
+    ");
+
+    // The hinted revset resolves to the conflicted commit
+    let output = work_dir.run_jj(["log", "-r", &c_commit_id, "-T", "description"]);
+    insta::assert_snapshot!(output, @r"
+    ×  c
+    │
+    ~
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_abandon_subtree() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit(&work_dir, "base", &[]);
+    create_commit(&work_dir, "a", &["base"]);
+    create_commit(&work_dir, "b", &["a"]);
+    create_commit(&work_dir, "c", &["b"]);
+    create_commit(&work_dir, "d", &["a"]);
+    create_commit(&work_dir, "x", &["base"]);
+    create_commit(&work_dir, "e", &["d", "x"]);
+    create_commit(&work_dir, "f", &["e"]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  [nkm] f
+    ○    [lyl] e
+    ├─╮
+    │ ○  [kmk] x
+    ○ │  [znk] d
+    │ │ ○  [vru] c
+    │ │ ○  [roy] b
+    ├───╯
+    ○ │  [zsu] a
+    ├─╯
+    ○  [rlv] base
+    ◆  [zzz]
+    [EOF]
+    ");
+
+    // The merge commit e and its descendant are also reachable from x, so
+    // they are rebased instead of abandoned
+    let output = work_dir.run_jj(["abandon", "--subtree", "a"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Abandoned 4 commits:
+      znkkpsqq 25a59ea8 d | d
+      vruxwmqv 24b0b14a c | c
+      royxmykx cd95ca0b b | b
+      zsuskuln 68fbc443 a | a
+    Deleted bookmarks: a, b, c, d
+    Rebased 2 descendant commits onto parents of abandoned commits
+      lylxulpl 4a070d98 e | e
+      nkmrtpmo 6abebb6e f | f
+    Working copy  (@) now at: nkmrtpmo 6abebb6e f | f
+    Parent commit (@-)      : lylxulpl 4a070d98 e | e
+    Added 0 files, modified 0 files, removed 2 files
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  [nkm] f
+    ○    [lyl] e
+    ├─╮
+    │ ○  [kmk] x
+    ├─╯
+    ○  [rlv] base
+    ◆  [zzz]
+    [EOF]
+    ");
+
+    // A linear chain is abandoned entirely
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir.run_jj(["abandon", "--subtree", "b"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Abandoned 2 commits:
+      vruxwmqv 24b0b14a c | c
+      royxmykx cd95ca0b b | b
+    Deleted bookmarks: b, c
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  [nkm] f
+    ○    [lyl] e
+    ├─╮
+    │ ○  [kmk] x
+    ○ │  [znk] d
+    ○ │  [zsu] a
+    ├─╯
+    ○  [rlv] base
+    ◆  [zzz]
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_abandon_subtree_merge_with_parent_of_root() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit(&work_dir, "base", &[]);
+    create_commit(&work_dir, "a", &["base"]);
+    create_commit(&work_dir, "b", &["a"]);
+    create_commit(&work_dir, "e", &["b", "base"]);
+
+    // The merge commit e is also reachable from the parent of the root, which
+    // doesn't prevent the rest of the subtree from being abandoned
+    work_dir.run_jj(["abandon", "--subtree", "a"]).success();
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  [vru] e
+    ○  [rlv] base
+    ◆  [zzz]
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_abandon_quiet() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit(&work_dir, "a", &[]);
+    create_commit(&work_dir, "b", &["a"]);
+    create_commit(&work_dir, "c", &["b"]);
+    work_dir.write_file("b", "modified b\n");
+
+    // Nothing is printed on success
+    let output = work_dir.run_jj(["abandon", "--quiet", "a"]);
+    insta::assert_snapshot!(output, @"");
+
+    // New conflicts are still reported
+    let output = work_dir.run_jj(["abandon", "--quiet", "b"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Warning: 1 descendant commits now have conflicts
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_abandon_write_recovery() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    let recovery_path = test_env.env_root().join("recovery.json");
+
+    create_commit(&work_dir, "a", &[]);
+    create_commit(&work_dir, "b", &["a"]);
+    create_commit(&work_dir, "c", &["b"]);
+    work_dir
+        .run_jj(["describe", "-r", "b", "-m", "b\n\nmulti-line"])
+        .success();
+
+    let output = work_dir.run_jj([
+        "abandon",
+        "a",
+        "b",
+        "--write-recovery",
+        recovery_path.to_str().unwrap(),
+    ]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Abandoned 2 commits:
+      zsuskuln 2e9c2451 b | b
+      rlvkpnrz 7d980be7 a | a
+    Deleted bookmarks: a, b
+    Rebased 1 descendant commits onto parents of abandoned commits
+    Working copy  (@) now at: royxmykx 1550d250 c | c
+    Parent commit (@-)      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 0 files, removed 2 files
+    [EOF]
+    ");
+    insta::assert_snapshot!(std::fs::read_to_string(&recovery_path).unwrap(), @r#"
+    {
+      "operation_id": "817adf15d3c0702c178ed139baa679a83899196f3343ebbe3ebd86126ec3ae605c2bae1edb6331da3de13d7cfa5c36010f2a073e8f7d124b03336e2c9cbd7a13",
+      "commits": [
+        {
+          "commit_id": "2e9c2451ecaf4a7889896aee6fdc725850c33ecc",
+          "change_id": "zsuskulnrvyrovkzqrwmxqlsskqntxvp",
+          "parent_change_ids": [
+            "rlvkpnrzqnoowoytxnquwvuryrwnrmlp"
+          ],
+          "description": "b\n\nmulti-line\n"
+        },
+        {
+          "commit_id": "7d980be7a1d499e4d316ab4c01242885032f7eaf",
+          "change_id": "rlvkpnrzqnoowoytxnquwvuryrwnrmlp",
+          "parent_change_ids": [
+            "zzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzz"
+          ],
+          "description": "a\n"
+        }
+      ]
+    }
+    "#);
+    let output = work_dir.run_jj(["op", "log", "-n1", "--no-graph", "-T", "id"]);
+    insta::assert_snapshot!(output, @r"
+    817adf15d3c0702c178ed139baa679a83899196f3343ebbe3ebd86126ec3ae605c2bae1edb6331da3de13d7cfa5c36010f2a073e8f7d124b03336e2c9cbd7a13[EOF]
+    ");
+
+    // Nothing is written if the revisions can't be abandoned
+    std::fs::remove_file(&recovery_path).unwrap();
+    let output = work_dir.run_jj([
+        "abandon",
+        "root()",
+        "--write-recovery",
+        recovery_path.to_str().unwrap(),
+    ]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: The root commit 000000000000 is immutable
+    [EOF]
+    [exit status: 1]
+    ");
+    assert!(!recovery_path.exists());
+}
+
+#[test]
+fn test_abandon_op_description() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit(&work_dir, "a", &[]);
+    create_commit(&work_dir, "b", &["a"]);
+
+    work_dir
+        .run_jj(["abandon", "b", "--op-description", "nightly cleanup"])
+        .success();
+    let template = r#"description ++ "\n" ++ tags"#;
+    let output = work_dir.run_jj(["op", "log", "-n1", "--no-graph", "-T", template]);
+    insta::assert_snapshot!(output, @r"
+    nightly cleanup
+    args: jj abandon b --op-description 'nightly cleanup'
+    [EOF]
+    ");
+
+    // The description must not be empty
+    let output = work_dir.run_jj(["abandon", "a", "--op-description", " "]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    error: invalid value ' ' for '--op-description <TEXT>': Operation description must not be empty
+
+    For more information, try '--help'.
+    [EOF]
+    [exit status: 2]
+    ");
+}
+
+#[test]
+fn test_abandon_checkpoint() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    let get_commit_id = |rev: &str| {
+        let output = work_dir
+            .run_jj(["log", "--no-graph", "-r", rev, "-T", "commit_id"])
+            .success();
+        output.stdout.into_raw()
+    };
+
+    create_commit(&work_dir, "a", &[]);
+    create_commit(&work_dir, "b", &["a"]);
+    create_commit(&work_dir, "c", &["a"]);
+    create_commit(&work_dir, "d", &["a"]);
+    create_commit(&work_dir, "e", &["a"]);
+    create_commit(&work_dir, "f", &["a"]);
+    let b_id = get_commit_id("b");
+    let c_id = get_commit_id("c");
+
+    // The bookmark points to the abandoned commit, which stays visible
+    work_dir
+        .run_jj(["abandon", "b", "--checkpoint", "backup"])
+        .success();
+    assert_eq!(get_commit_id("backup"), b_id);
+
+    // An existing bookmark is only moved with --checkpoint-force
+    let output = work_dir.run_jj(["abandon", "c", "--checkpoint", "backup"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Bookmark already exists: backup
+    Hint: Use `--checkpoint-force` to move it.
+    [EOF]
+    [exit status: 1]
+    ");
+    work_dir
+        .run_jj([
+            "abandon",
+            "c",
+            "--checkpoint",
+            "backup",
+            "--checkpoint-force",
+        ])
+        .success();
+    assert_eq!(get_commit_id("backup"), c_id);
+
+    // The bookmark is conflicted if the abandoned commits have several heads
+    work_dir
+        .run_jj(["abandon", "d", "e", "--checkpoint", "multi"])
+        .success();
+    let output = work_dir.run_jj([
+        "log",
+        "--no-graph",
+        "-r",
+        "bookmarks(exact:multi)",
+        "-T",
+        r#"separate(" ", description.first_line(), bookmarks) ++ "\n""#,
+    ]);
+    insta::assert_snapshot!(output, @r"
+    e multi??
+    d multi??
+    [EOF]
+    ");
+}
+
 #[must_use]
 fn get_log_output(work_dir: &TestWorkDir) -> CommandOutput {
     let template = r#"separate(" ", "[" ++ change_id.short(3) ++ "]", bookmarks)"#;
@@ -233,6 +233,32 @@ fn test_bookmark_bad_name() {
     Created 1 bookmarks pointing to qpvuntsm e8849ae1 "foo@bar" | (empty) (no description set)
     [EOF]
     "#);
+
+    // quoted names must still be valid ref names
+    let output = work_dir.run_jj(["bookmark", "create", "-r@", "'foo bar'"]);
+    insta::assert_snapshot!(output, @r#"
+    ------- stderr -------
+    Error: Invalid name "foo bar"
+    Caused by: Invalid character ' '
+    [EOF]
+    [exit status: 1]
+    "#);
+    let output = work_dir.run_jj(["bookmark", "set", "-r@", "'foo..bar'"]);
+    insta::assert_snapshot!(output, @r#"
+    ------- stderr -------
+    Error: Invalid name "foo..bar"
+    Caused by: Name contains '..'
+    [EOF]
+    [exit status: 1]
+    "#);
+    let output = work_dir.run_jj(["bookmark", "rename", "'foo@bar'", "'-foo'"]);
+    insta::assert_snapshot!(output, @r#"
+    ------- stderr -------
+    Error: Invalid name "-foo"
+    Caused by: Name starts with '-'
+    [EOF]
+    [exit status: 1]
+    "#);
 }
 
 #[test]
@@ -541,6 +567,8 @@ fn test_bookmark_move_conflicting() {
     [EOF]
     ------- stderr -------
     Concurrent modification detected, resolving automatically.
+    Warning: Reconciliation created 1 bookmark conflict: foo
+    Hint: Use `jj bookmark list --conflicted` to see them, and `jj bookmark set` to resolve them.
     [EOF]
     ");
 
@@ -1946,13 +1974,13 @@ fn test_bookmark_list_quoted_name() {
     let work_dir = test_env.work_dir("repo");
 
     work_dir
-        .run_jj(["bookmark", "create", "-r@", "'with space'"])
+        .run_jj(["bookmark", "create", "-r@", "'with,comma'"])
         .success();
 
     // quoted by default
     let output = work_dir.run_jj(["bookmark", "list"]);
     insta::assert_snapshot!(output, @r#"
-    "with space": qpvuntsm e8849ae1 (empty) (no description set)
+    "with,comma": qpvuntsm e8849ae1 (empty) (no description set)
     [EOF]
     "#);
 
@@ -1966,7 +1994,7 @@ fn test_bookmark_list_quoted_name() {
     "#;
     let output = work_dir.run_jj(["bookmark", "list", "-T", template]);
     insta::assert_snapshot!(output, @r#"
-    "with space" false 10
+    "with,comma" false 10
     [EOF]
     "#);
 }
@@ -536,11 +536,11 @@ fn test_config_set_bad_opts() {
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
     error: the following required arguments were not provided:
-      <--user|--repo>
+      <--user|--repo|--workspace>
       <NAME>
       <VALUE>
 
-    Usage: jj config set <--user|--repo> <NAME> <VALUE>
+    Usage: jj config set <--user|--repo|--workspace> <NAME> <VALUE>
 
     For more information, try '--help'.
     [EOF]
@@ -677,6 +677,68 @@ fn test_config_set_for_repo() {
     "#);
 }
 
+#[test]
+fn test_config_set_for_workspace() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let main_dir = test_env.work_dir("repo");
+    let secondary_dir = test_env.work_dir("secondary");
+    main_dir
+        .run_jj(["workspace", "add", "--name", "second", "../secondary"])
+        .success();
+
+    main_dir
+        .run_jj(["config", "set", "--repo", "test-key", "repo-val"])
+        .success();
+    secondary_dir
+        .run_jj(["config", "set", "--workspace", "test-key", "workspace-val"])
+        .success();
+    let workspace_config_toml = secondary_dir.read_file(".jj/working_copy/config.toml");
+    insta::assert_snapshot!(workspace_config_toml, @r#"
+    "$schema" = "https://jj-vcs.github.io/jj/latest/config-schema.json"
+    test-key = "workspace-val"
+    "#);
+
+    // Each workspace observes its own value.
+    insta::assert_snapshot!(main_dir.run_jj(["config", "get", "test-key"]), @r"
+    repo-val
+    [EOF]
+    ");
+    insta::assert_snapshot!(secondary_dir.run_jj(["config", "get", "test-key"]), @r"
+    workspace-val
+    [EOF]
+    ");
+    let output = secondary_dir.run_jj([
+        "config",
+        "list",
+        "-Tbuiltin_config_list_detailed",
+        "--include-overridden",
+        "test-key",
+    ]);
+    insta::assert_snapshot!(output, @r#"
+    # test-key = "repo-val" # repo $TEST_ENV/repo/.jj/repo/config.toml
+    test-key = "workspace-val" # workspace $TEST_ENV/secondary/.jj/working_copy/config.toml
+    [EOF]
+    "#);
+
+    // The workspace config isn't loaded outside the workspace.
+    let output = test_env.run_jj_in(".", ["config", "path", "--workspace"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: No workspace config path found
+    [EOF]
+    [exit status: 1]
+    ");
+    let output = test_env.run_jj_in(".", ["config", "get", "test-key"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Config error: Value not found for test-key
+    For help, see https://jj-vcs.github.io/jj/latest/config/ or use `jj help -k config`.
+    [EOF]
+    [exit status: 1]
+    ");
+}
+
 #[test]
 fn test_config_set_toml_types() {
     let mut test_env = TestEnvironment::default();
@@ -899,9 +961,9 @@ fn test_config_edit_missing_opt() {
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
     error: the following required arguments were not provided:
-      <--user|--repo>
+      <--user|--repo|--workspace>
 
-    Usage: jj config edit <--user|--repo>
+    Usage: jj config edit <--user|--repo|--workspace>
 
     For more information, try '--help'.
     [EOF]
@@ -45,8 +45,7 @@ fn test_debug_fileset() {
       | ^----^
       |
       = Invalid file pattern
-    2: Path ".." is not in the repo "."
-    3: Invalid component ".." in repo-relative path "../"
+    2: Path ".." resolves to "$TEST_ENV", which is outside the workspace "."
     [EOF]
     [exit status: 1]
     "#);
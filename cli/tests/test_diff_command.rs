@@ -3153,6 +3153,63 @@ fn test_diff_external_tool() {
     ");
 }
 
+#[test]
+fn test_diff_external_tool_by_attributes() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_diff_editor();
+    let command_toml = to_toml_value(fake_diff_editor_path());
+    test_env.add_config(format!("merge-tools.notebook.program = {command_toml}"));
+    test_env.add_config(r#"merge-tools.notebook.diff-invocation-mode = "file-by-file""#);
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file(
+        ".gitattributes",
+        "*.ipynb diff=notebook\n*.txt diff=unknown\n",
+    );
+    work_dir.write_file("file1", "foo\n");
+    work_dir.run_jj(["new"]).success();
+    work_dir.write_file("file1", "foo\nbar\n");
+    work_dir.write_file("file2.txt", "foo\n");
+    work_dir.write_file("a.ipynb", "{}\n");
+    work_dir.write_file("b.ipynb", "{}\n");
+
+    std::fs::write(
+        &edit_script,
+        "print-files-before\0print --\0print-files-after",
+    )
+    .unwrap();
+
+    // The notebooks are passed to the notebook tool one by one, and the other
+    // files to the default tool
+    let output = work_dir.run_jj(["diff", "--config=ui.diff-formatter=fake-diff-editor"]);
+    insta::assert_snapshot!(output, @r"
+    file1
+    --
+    file1
+    file2.txt
+    a.ipynb
+    --
+    a.ipynb
+    b.ipynb
+    --
+    b.ipynb
+    [EOF]
+    ");
+
+    // Attributes are ignored if the tool is specified explicitly
+    let output = work_dir.run_jj(["diff", "--tool=fake-diff-editor"]);
+    insta::assert_snapshot!(output, @r"
+    file1
+    --
+    a.ipynb
+    b.ipynb
+    file1
+    file2.txt
+    [EOF]
+    ");
+}
+
 #[test]
 fn test_diff_do_chdir() {
     let mut test_env = TestEnvironment::default();
@@ -13,7 +13,11 @@
 // limitations under the License.
 
 use indoc::indoc;
+use itertools::Itertools as _;
 
+use crate::common::create_commit_with_files;
+use crate::common::fake_diff_editor_path;
+use crate::common::CommandOutput;
 use crate::common::TestEnvironment;
 
 #[test]
@@ -422,6 +426,97 @@ fn test_diffedit_external_tool_conflict_marker_style() {
     ");
 }
 
+#[test]
+fn test_diffedit_repo_config_tool() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "a\n");
+    work_dir.run_jj(["new"]).success();
+    work_dir.write_file("file1", "b\n");
+    std::fs::write(&edit_script, "fail").unwrap();
+
+    // The builtin diff editor fails without a terminal. Only the platform
+    // independent part of the error is kept.
+    let normalize_builtin_error = |output: CommandOutput| {
+        output
+            .normalize_stdout_with(|_| String::new())
+            .normalize_stderr_with(|s| s.split_inclusive('\n').take(3).collect())
+    };
+
+    // The builtin diff editor in the repo config overrides the external tool in
+    // the user config
+    work_dir
+        .run_jj(["config", "set", "--repo", "ui.diff-editor", ":builtin"])
+        .success();
+    let output = work_dir.run_jj(["diffedit"]);
+    insta::assert_snapshot!(normalize_builtin_error(output), @r"
+    [EOF]
+    ------- stderr -------
+    Error: Failed to edit diff
+    Caused by:
+    1: Failed to record changes
+    [EOF]
+    [exit status: 1]
+    ");
+
+    // The external tool in the repo config overrides the builtin diff editor in
+    // the user config
+    test_env.add_config("ui.diff-editor = ':builtin'");
+    work_dir
+        .run_jj([
+            "config",
+            "set",
+            "--repo",
+            "ui.diff-editor",
+            "fake-diff-editor",
+        ])
+        .success();
+    std::fs::write(&edit_script, "write file1\nedited\n").unwrap();
+    let output = work_dir.run_jj(["diffedit"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: rlvkpnrz 05493e48 (no description set)
+    Parent commit (@-)      : qpvuntsm eb7b8a1f (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+
+    // A tool defined in the repo config can be selected by --tool
+    work_dir
+        .run_jj([
+            "config",
+            "set",
+            "--repo",
+            "merge-tools.repo-tool.program",
+            fake_diff_editor_path().as_str(),
+        ])
+        .success();
+    std::fs::write(&edit_script, "write file1\nrepo tool\n").unwrap();
+    let output = work_dir.run_jj(["diffedit", "--tool=repo-tool"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: rlvkpnrz 5cc460aa (no description set)
+    Parent commit (@-)      : qpvuntsm eb7b8a1f (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+
+    // $JJ_DIFF_EDITOR overrides the repo config
+    let output = work_dir.run_jj_with(|cmd| cmd.arg("diffedit").env("JJ_DIFF_EDITOR", ":builtin"));
+    insta::assert_snapshot!(normalize_builtin_error(output), @r"
+    [EOF]
+    ------- stderr -------
+    Error: Failed to edit diff
+    Caused by:
+    1: Failed to record changes
+    [EOF]
+    [exit status: 1]
+    ");
+}
+
 #[test]
 fn test_diffedit_3pane() {
     let mut test_env = TestEnvironment::default();
@@ -600,6 +695,82 @@ fn test_diffedit_merge() {
     ");
 }
 
+#[test]
+fn test_diffedit_conflicts_only() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(&work_dir, "base", &[], &[("file1", "base\n")]);
+    create_commit_with_files(&work_dir, "a", &["base"], &[("file1", "a\n")]);
+    create_commit_with_files(
+        &work_dir,
+        "b",
+        &["base"],
+        &[("file1", "b\n"), ("file2", "b\n"), ("file3", "b\n")],
+    );
+    work_dir.run_jj(["rebase", "-r", "b", "-d", "a"]).success();
+    let output = work_dir.run_jj(["diff", "-s", "-r", "b"]);
+    insta::assert_snapshot!(output, @r"
+    M file1
+    A file2
+    A file3
+    [EOF]
+    ");
+
+    // Only the conflicted file is presented to the diff editor
+    std::fs::write(
+        &edit_script,
+        "files-before file1\0files-after JJ-INSTRUCTIONS file1\0write file1\nresolved\n",
+    )
+    .unwrap();
+    work_dir
+        .run_jj(["diffedit", "-r", "b", "--conflicts-only"])
+        .success();
+    let output = work_dir.run_jj(["resolve", "--list", "-r", "b"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: No conflicts found at this revision
+    [EOF]
+    [exit status: 2]
+    ");
+    let output = work_dir.run_jj(["diff", "--git", "-r", "b"]);
+    insta::assert_snapshot!(output, @r"
+    diff --git a/file1 b/file1
+    index 7898192261..2ab19ae607 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,1 +1,1 @@
+    -a
+    +resolved
+    diff --git a/file2 b/file2
+    new file mode 100644
+    index 0000000000..6178079822
+    --- /dev/null
+    +++ b/file2
+    @@ -0,0 +1,1 @@
+    +b
+    diff --git a/file3 b/file3
+    new file mode 100644
+    index 0000000000..6178079822
+    --- /dev/null
+    +++ b/file3
+    @@ -0,0 +1,1 @@
+    +b
+    [EOF]
+    ");
+
+    // Error out if there are no conflicts
+    let output = work_dir.run_jj(["diffedit", "-r", "b", "--conflicts-only"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: No conflicts found at this revision
+    [EOF]
+    [exit status: 1]
+    ");
+}
+
 #[test]
 fn test_diffedit_old_restore_interactive_tests() {
     let mut test_env = TestEnvironment::default();
@@ -736,3 +907,360 @@ fn test_diffedit_restore_descendants() {
     [EOF]
     "#);
 }
+
+#[test]
+fn test_diffedit_restore_descendants_merge() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.run_jj(["describe", "-m", "a"]).success();
+    work_dir.write_file("a", "a\n");
+    work_dir.run_jj(["new", "root()", "-m", "b"]).success();
+    work_dir.write_file("b", "b\n");
+    work_dir
+        .run_jj(["new", "description(a)", "description(b)", "-m", "merge"])
+        .success();
+
+    // The merge keeps the old content of "a", which is no longer the merge of
+    // its parents.
+    std::fs::write(&edit_script, "write a\na2\n").unwrap();
+    let output = work_dir.run_jj([
+        "diffedit",
+        "-r",
+        "description(a)",
+        "--restore-descendants=strict",
+    ]);
+    assert!(!output.status.success());
+    let stderr = output.stderr.raw();
+    assert!(stderr.contains(
+        "Error: The content of merge commits would no longer be the merge of their parents"
+    ));
+    assert!(stderr.contains("Hint: Run without `--restore-descendants` to rebase them instead."));
+
+    let output = work_dir.run_jj(["diffedit", "-r", "description(a)", "--restore-descendants"]);
+    assert!(output.status.success());
+    let stderr = output.stderr.raw();
+    assert!(stderr.contains(
+        "Warning: The content of 1 merge commits is no longer the merge of their parents:"
+    ));
+    assert!(stderr.contains("Rebased 1 descendant commits (while preserving their content)"));
+    let output = work_dir.run_jj(["diff", "--git"]);
+    insta::assert_snapshot!(output, @r"
+    diff --git a/a b/a
+    index c1827f07e1..7898192261 100644
+    --- a/a
+    +++ b/a
+    @@ -1,1 +1,1 @@
+    -a2
+    +a
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_diffedit_with_message() {
+    let mut test_env = TestEnvironment::default();
+    let diff_script = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "a\n");
+    work_dir.run_jj(["describe", "-m", "original"]).success();
+
+    // Update the content and the description in the same operation
+    std::fs::write(&diff_script, "write file1\nb\n").unwrap();
+    let output = work_dir.run_jj(["diffedit", "-m", "edited", "-m", "body"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: qpvuntsm a8064b44 edited
+    Parent commit (@-)      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["log", "--no-graph", "-r@", "-Tdescription"]);
+    insta::assert_snapshot!(output, @r"
+    edited
+
+    body
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    b
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["op", "log", "-n1", "-Tdescription"]);
+    insta::assert_snapshot!(output, @r"
+    @  edit commit 493f47b7c735d45290b3661dc88d723ca19c9872
+    [EOF]
+    ");
+
+    // Only the description is updated if the content is left unchanged
+    std::fs::write(&diff_script, "").unwrap();
+    let output = work_dir.run_jj(["diffedit", "-m", "message only"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: qpvuntsm ecd4da5a message only
+    Parent commit (@-)      : zzzzzzzz 00000000 (empty) (no description set)
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["log", "--no-graph", "-r@", "-Tdescription"]);
+    insta::assert_snapshot!(output, @r"
+    message only
+    [EOF]
+    ");
+
+    // Nothing changes if the description is the same
+    let output = work_dir.run_jj(["diffedit", "-m", "message only"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Nothing changed.
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_diffedit_with_message_trailers() {
+    let mut test_env = TestEnvironment::default();
+    let diff_script = test_env.set_up_fake_diff_editor();
+    let edit_script = test_env.set_up_fake_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    test_env.add_config(r#"templates.commit_trailers = '"Signed-off-by: " ++ committer'"#);
+
+    work_dir.write_file("file1", "a\n");
+    std::fs::write(&diff_script, "write file1\nb\n").unwrap();
+    let output = work_dir.run_jj(["diffedit", "-m", "edited"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: qpvuntsm a02d6fe4 edited
+    Parent commit (@-)      : zzzzzzzz 00000000 (empty) (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["log", "--no-graph", "-r@", "-Tdescription"]);
+    insta::assert_snapshot!(output, @r"
+    edited
+
+    Signed-off-by: Test User <test.user@example.com>
+    [EOF]
+    ");
+
+    // Trailers are added before the description is edited
+    std::fs::write(&diff_script, "").unwrap();
+    std::fs::write(
+        &edit_script,
+        ["dump editor", "write\nedited in editor\n"].join("\0"),
+    )
+    .unwrap();
+    let output = work_dir.run_jj(["diffedit", "-m", "from cli", "--edit-description"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: qpvuntsm 20ec5ad7 edited in editor
+    Parent commit (@-)      : zzzzzzzz 00000000 (empty) (no description set)
+    [EOF]
+    ");
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("editor")).unwrap(), @r#"
+    from cli
+
+    Signed-off-by: Test User <test.user@example.com>
+
+    JJ: This commit contains the following changes:
+    JJ:     A file1
+    JJ:
+    JJ: Lines starting with "JJ:" (like this one) will be removed.
+    "#);
+    let output = work_dir.run_jj(["log", "--no-graph", "-r@", "-Tdescription"]);
+    insta::assert_snapshot!(output, @r"
+    edited in editor
+    [EOF]
+    ");
+
+    // No trailers are added to an empty description
+    let output = work_dir.run_jj(["diffedit", "-m", ""]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: qpvuntsm c4d7931c (no description set)
+    Parent commit (@-)      : zzzzzzzz 00000000 (empty) (no description set)
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["log", "--no-graph", "-r@", "-Tdescription"]);
+    insta::assert_snapshot!(output, @"");
+}
+
+#[test]
+fn test_diffedit_concurrent_operation() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    let jj_path = assert_cmd::cargo::cargo_bin("jj");
+    // Script which runs `jj <args>` while the diff editor is open
+    let run_jj = |args: &[&str]| {
+        let repo_path = work_dir.root().to_str().unwrap();
+        ["run", jj_path.to_str().unwrap(), "-R", repo_path]
+            .iter()
+            .chain(args)
+            .join("\n")
+    };
+
+    work_dir.write_file("file1", "a\n");
+    work_dir.write_file("file2", "a\n");
+    work_dir.run_jj(["new"]).success();
+    work_dir.write_file("file1", "b\n");
+    work_dir.write_file("file2", "b\n");
+    work_dir.run_jj(["debug", "snapshot"]).success();
+    let setup_op_id = work_dir.current_operation_id();
+
+    // The target revision is rewritten while the editor is open. The edits are
+    // applied to the current version.
+    std::fs::write(
+        &edit_script,
+        [
+            "write file1\nedited\n".to_owned(),
+            run_jj(&["describe", "-m", "concurrent"]),
+        ]
+        .join("\0"),
+    )
+    .unwrap();
+    let output = work_dir.run_jj(["diffedit"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: rlvkpnrz e720a27a concurrent
+    Parent commit (@-)      : qpvuntsm fc6f5e82 (no description set)
+    Warning: The revision was rewritten while the diff editor was open. Applying the changes to its current version: rlvkpnrz e720a27a concurrent
+    Working copy  (@) now at: rlvkpnrz d8f0f45a concurrent
+    Parent commit (@-)      : qpvuntsm fc6f5e82 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["log", "-r", "@", "-T", "description", "--summary"]);
+    insta::assert_snapshot!(output, @r"
+    @  concurrent
+    │  M file1
+    ~  M file2
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    edited
+    [EOF]
+    ");
+
+    // The concurrent change conflicts with the edits
+    work_dir.run_jj(["op", "restore", &setup_op_id]).success();
+    std::fs::write(
+        &edit_script,
+        [
+            "write file1\nedited\n".to_owned(),
+            run_jj(&["restore", "--from", "@-", "file1"]),
+        ]
+        .join("\0"),
+    )
+    .unwrap();
+    let output = work_dir.run_jj(["diffedit"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: rlvkpnrz 0de0060b (no description set)
+    Parent commit (@-)      : qpvuntsm fc6f5e82 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Warning: The revision was rewritten while the diff editor was open. Applying the changes to its current version: rlvkpnrz 0de0060b (no description set)
+    Working copy  (@) now at: rlvkpnrz 249024ca (conflict) (no description set)
+    Parent commit (@-)      : qpvuntsm fc6f5e82 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    Warning: There are unresolved conflicts at these paths:
+    file1    2-sided conflict
+    New conflicts appeared in 1 commits:
+      rlvkpnrz 249024ca (conflict) (no description set)
+    Hint: To resolve the conflicts, start by creating a commit on top of
+    the conflicted commit:
+      jj new rlvkpnrz
+    Then use `jj resolve`, or edit the conflict markers in the file directly.
+    Once the conflicts are resolved, you can inspect the result with `jj diff`.
+    Then run `jj squash` to move the resolution into the conflicted commit.
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1
+    -b
+    +a
+    +++++++ Contents of side #2
+    edited
+    >>>>>>> Conflict 1 of 1 ends
+    [EOF]
+    ");
+
+    // The target revision is abandoned while the editor is open
+    work_dir.run_jj(["op", "restore", &setup_op_id]).success();
+    std::fs::write(
+        &edit_script,
+        [
+            "write file1\nedited\n".to_owned(),
+            run_jj(&["abandon", "-r", "@"]),
+        ]
+        .join("\0"),
+    )
+    .unwrap();
+    let output = work_dir.run_jj(["diffedit", "-r", "@"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Abandoned 1 commits:
+      rlvkpnrz 2004e7cf (no description set)
+    Working copy  (@) now at: kmkuslsw b3e07da6 (empty) (no description set)
+    Parent commit (@-)      : qpvuntsm fc6f5e82 (no description set)
+    Added 0 files, modified 2 files, removed 0 files
+    Error: Revision 2004e7cf0bf3 was abandoned while the diff editor was open
+    [EOF]
+    [exit status: 1]
+    ");
+}
+
+#[test]
+fn test_diffedit_checkpoint() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(&work_dir, "a", &[], &[("file1", "a\n")]);
+    create_commit_with_files(&work_dir, "b", &["a"], &[("file1", "b\n")]);
+    let get_commit_id = |rev: &str| {
+        let output = work_dir
+            .run_jj(["log", "--no-graph", "-r", rev, "-T", "commit_id"])
+            .success();
+        output.stdout.into_raw()
+    };
+    let b_id = get_commit_id("b");
+
+    std::fs::write(&edit_script, "write file1\nedited\n").unwrap();
+    work_dir
+        .run_jj(["diffedit", "-r", "b", "--checkpoint", "backup"])
+        .success();
+    assert_eq!(get_commit_id("backup"), b_id);
+    let output = work_dir.run_jj(["file", "show", "-r", "backup", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    b
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "-r", "b", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    edited
+    [EOF]
+    ");
+
+    // The bookmark isn't created if nothing changed
+    std::fs::write(&edit_script, "").unwrap();
+    let output = work_dir.run_jj(["diffedit", "-r", "b", "--checkpoint", "unchanged"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Nothing changed.
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["log", "--no-graph", "-r", "bookmarks(exact:unchanged)"]);
+    insta::assert_snapshot!(output, @"");
+}
@@ -1478,3 +1478,54 @@ fn test_all_files() {
     [EOF]
     ");
 }
+
+#[test]
+fn test_changed_since() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    let formatter_path = assert_cmd::cargo::cargo_bin("fake-formatter");
+    assert!(formatter_path.is_file());
+    let formatter = to_toml_value(formatter_path.to_str().unwrap());
+    test_env.add_config(format!(
+        r###"
+        [fix.tools.append]
+        command = [{formatter}, "--append", "fixed"]
+        patterns = ["all()"]
+
+        [fix.tools.upper]
+        command = [{formatter}, "--uppercase"]
+        patterns = ["all()"]
+        changed-since = "main"
+        "###,
+    ));
+
+    work_dir.write_file("a", "parent a\n");
+    work_dir.write_file("b", "parent b\n");
+    work_dir.run_jj(["commit", "-m", "parent"]).success();
+    work_dir
+        .run_jj(["bookmark", "create", "-r@-", "main"])
+        .success();
+    work_dir.write_file("a", "child a\n");
+
+    // Only the file changed since "main" is passed to the scoped tool
+    let output = work_dir
+        .run_jj(["fix", "-s", "@", "--include-unchanged-files"])
+        .success();
+    assert!(
+        output.stderr.raw().starts_with(
+            "Fixed 1 commits of 1 checked.\nSkipped 1 unchanged files for tool `upper`.\n"
+        ),
+        "{output}"
+    );
+    let output = work_dir.run_jj(["file", "show", "a", "-r", "@"]);
+    insta::assert_snapshot!(output, @r"
+    CHILD A
+    FIXED[EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "b", "-r", "@"]);
+    insta::assert_snapshot!(output, @r"
+    parent b
+    fixed[EOF]
+    ");
+}
@@ -314,7 +314,7 @@ fn test_git_import_export_stats_color() {
 
     work_dir.run_jj(["bookmark", "set", "-r@", "foo"]).success();
     work_dir
-        .run_jj(["bookmark", "set", "-r@", "'un:exportable'"])
+        .run_jj(["bookmark", "set", "-r@", "foo/bar"])
         .success();
     work_dir.run_jj(["new", "--no-edit", "root()"]).success();
     let other_commit_id = work_dir
@@ -326,15 +326,17 @@ fn test_git_import_export_stats_color() {
     let output = work_dir
         .run_jj(["git", "export", "--color=always"])
         .success();
-    insta::assert_snapshot!(output, @r#"
-    ------- stderr -------
-    [1m[38;5;3mWarning: [39mFailed to export some bookmarks:[0m
-      [38;5;5m"un:exportable"@git[39m: Failed to set: A reference must be a valid tag name as well: A ref must not contain invalid bytes or ascii control characters: ":"
-    [1m[38;5;6mHint: [0m[39mGit doesn't allow a branch name that looks like a parent directory of[39m
-    [39manother (e.g. `foo` and `foo/bar`). Try to rename the bookmarks that failed to[39m
-    [39mexport or their "parent" bookmarks.[39m
-    [EOF]
-    "#);
+    insta::with_settings!({filters => vec![("Failed to set: .*", "Failed to set: ...")]}, {
+        insta::assert_snapshot!(output, @r#"
+        ------- stderr -------
+        [1m[38;5;3mWarning: [39mFailed to export some bookmarks:[0m
+          [38;5;5mfoo/bar@git[39m: Failed to set: ...
+        [1m[38;5;6mHint: [0m[39mGit doesn't allow a branch name that looks like a parent directory of[39m
+        [39manother (e.g. `foo` and `foo/bar`). Try to rename the bookmarks that failed to[39m
+        [39mexport or their "parent" bookmarks.[39m
+        [EOF]
+        "#);
+    });
 
     let other_commit_id = gix::ObjectId::from_hex(other_commit_id.as_bytes()).unwrap();
     for name in ["refs/heads/foo", "refs/heads/bar", "refs/tags/baz"] {
@@ -286,8 +286,7 @@ fn test_bad_path() {
       | ^----^
       |
       = Invalid file pattern
-    2: Path "../out" is not in the repo "."
-    3: Invalid component ".." in repo-relative path "../out"
+    2: Path "../out" resolves to "$TEST_ENV/out", which is outside the workspace "."
     [EOF]
     [exit status: 1]
     "#);
@@ -304,8 +303,7 @@ fn test_bad_path() {
       | ^---^
       |
       = Invalid file pattern
-    2: Path "../.." is not in the repo "../"
-    3: Invalid component ".." in repo-relative path "../"
+    2: Path "../.." resolves to "$TEST_ENV", which is outside the workspace "../"
     [EOF]
     [exit status: 1]
     "#);
@@ -322,8 +320,7 @@ fn test_bad_path() {
       | ^-^
       |
       = Invalid file pattern
-    2: Path "out" is not in the repo "repo"
-    3: Invalid component ".." in repo-relative path "../out"
+    2: Path "out" resolves to "$TEST_ENV/out", which is outside the workspace "repo"
     Hint: Consider using root:"out" to specify repo-relative path
     [EOF]
     [exit status: 1]
@@ -550,8 +547,7 @@ fn test_color_ui_messages() {
     [39m  | ^^[39m
     [39m  |[39m
     [39m  = Invalid file pattern[39m
-    [1m[39m2: [0m[39mPath ".." is not in the repo "."[39m
-    [1m[39m3: [0m[39mInvalid component ".." in repo-relative path "../"[39m
+    [1m[39m2: [0m[39mPath ".." resolves to "$TEST_ENV", which is outside the workspace "."[39m
     [EOF]
     [exit status: 1]
     "#);
@@ -1061,12 +1057,18 @@ fn test_help() {
     Usage: jj diffedit [OPTIONS]
 
     Options:
-      -r, --revision <REVSET>    The revision to touch up
-      -f, --from <REVSET>        Show changes from this revision
-      -t, --to <REVSET>          Edit changes in this revision
-          --tool <NAME>          Specify diff editor to be used
-          --restore-descendants  Preserve the content (not the diff) when rebasing descendants
-      -h, --help                 Print help (see more with '--help')
+      -r, --revision <REVSET>           The revision to touch up
+      -f, --from <REVSET>               Show changes from this revision
+      -t, --to <REVSET>                 Edit changes in this revision
+          --tool <NAME>                 Specify diff editor to be used
+          --restore-descendants         Preserve the content (not the diff) when rebasing descendants
+      -m, --message <MESSAGE>           Update the change description to this message (don't open
+                                        editor)
+          --edit-description            Open an editor to update the change description
+          --selection-plan <FILE>       Select the changes listed in this plan file instead of starting
+                                        a diff editor
+          --emit-selection-plan <FILE>  Write the changes selected in the diff editor to this plan file
+      -h, --help                        Print help (see more with '--help')
 
     Global Options:
       -R, --repository <REPOSITORY>      Path to repository to operate on
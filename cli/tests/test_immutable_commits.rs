@@ -567,3 +567,54 @@ fn test_rewrite_immutable_commands() {
     [exit status: 1]
     "#);
 }
+
+#[test]
+fn test_rewrite_made_immutable_by_concurrent_operation() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_diff_editor();
+    std::fs::write(&edit_script, "write file\nedited\n").unwrap();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    test_env.add_config(r#"revset-aliases."immutable_heads()" = "main""#);
+    work_dir.write_file("file", "a");
+    work_dir.run_jj(["describe", "-m=a"]).success();
+    work_dir.run_jj(["new", "-m=b"]).success();
+    work_dir.write_file("file", "b");
+    work_dir.run_jj(["new", "-m=c"]).success();
+    work_dir.write_file("file", "c");
+    let setup_op_id = work_dir.current_operation_id();
+
+    // Another process makes "b" immutable while the commands below are running
+    // at the setup operation
+    work_dir
+        .run_jj(["bookmark", "create", "-rdescription(b)", "main"])
+        .success();
+    let concurrent_op_id = work_dir.current_operation_id();
+    for args in [
+        ["abandon", "description(b)"],
+        ["restore", "--into=description(b)"],
+        ["diffedit", "-rdescription(b)"],
+    ] {
+        let output = work_dir.run_jj([args[0], args[1], "--at-op", &setup_op_id]);
+        assert!(!output.status.success(), "{output}");
+        let stderr = output.stderr.raw();
+        assert!(
+            stderr.contains("became immutable in a concurrent operation"),
+            "{stderr}"
+        );
+        assert!(
+            stderr.contains("The operation was not published"),
+            "{stderr}"
+        );
+        assert_eq!(work_dir.current_operation_id(), concurrent_op_id);
+    }
+
+    // A concurrent operation that doesn't make the target immutable doesn't
+    // prevent the rewrite
+    work_dir
+        .run_jj(["bookmark", "create", "-rdescription(a)", "other"])
+        .success();
+    work_dir
+        .run_jj(["abandon", "description(c)", "--at-op", &concurrent_op_id])
+        .success();
+}
@@ -544,6 +544,65 @@ fn test_log_bad_short_prefixes() {
     ");
 }
 
+#[test]
+fn test_log_short_prefix_cache() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    test_env.add_config("short-prefix-cache.enabled = true");
+
+    work_dir.run_jj(["describe", "-m", "original"]).success();
+    work_dir.run_jj(["new"]).success();
+    let commit_id = work_dir
+        .run_jj(["log", "--no-graph", "-r@-", "-Tcommit_id"])
+        .success()
+        .stdout
+        .into_raw();
+    let prefix = work_dir
+        .run_jj(["log", "--no-graph", "-r@-", "-Tcommit_id.shortest()"])
+        .success()
+        .stdout
+        .into_raw();
+
+    // Create commits until the printed prefix becomes ambiguous
+    let resolve_without_cache = || {
+        work_dir.run_jj([
+            "log",
+            "--config=short-prefix-cache.enabled=false",
+            "-r",
+            &prefix,
+        ])
+    };
+    for i in 0.. {
+        assert!(i < 100, "prefix {prefix} should become ambiguous");
+        if !resolve_without_cache().status.success() {
+            break;
+        }
+        work_dir
+            .run_jj(["new", "root()", "-m", &format!("commit{i}")])
+            .success();
+    }
+    let output = resolve_without_cache();
+    assert!(
+        output.stderr.raw().contains("is ambiguous"),
+        "{}",
+        output.stderr.raw()
+    );
+
+    // The printed prefix still resolves to the original commit
+    let output = work_dir
+        .run_jj(["log", "--no-graph", "-r", &prefix, "-Tdescription"])
+        .success();
+    assert_eq!(output.stdout.raw(), "original\n");
+    assert_eq!(
+        output.stderr.raw(),
+        format!(
+            "Warning: Ambiguous commit ID prefix `{prefix}` was resolved to previously printed \
+             commit ID {commit_id}\n"
+        )
+    );
+}
+
 #[test]
 fn test_log_prefix_highlight_styled() {
     let test_env = TestEnvironment::default();
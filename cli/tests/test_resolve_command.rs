@@ -17,6 +17,8 @@ use std::path::Path;
 use indoc::indoc;
 
 use crate::common::create_commit_with_files;
+use crate::common::fake_editor_path;
+use crate::common::to_toml_value;
 use crate::common::CommandOutput;
 use crate::common::TestEnvironment;
 use crate::common::TestWorkDir;
@@ -1980,6 +1982,112 @@ fn test_multiple_conflicts_with_error() {
     ");
 }
 
+#[test]
+fn test_resolve_with_merge_attribute() {
+    let mut test_env = TestEnvironment::default();
+    let editor_script = test_env.set_up_fake_editor();
+    let editor_toml = to_toml_value(fake_editor_path());
+    test_env.add_config(format!("merge-tools.notebook.program = {editor_toml}"));
+    test_env.add_config(r#"merge-tools.notebook.merge-args = ["$output", "notebook"]"#);
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(
+        &work_dir,
+        "base",
+        &[],
+        &[
+            (
+                ".gitattributes",
+                "*.ipynb merge=notebook\n*.txt merge=unknown\n",
+            ),
+            ("file1.txt", "base1\n"),
+            ("file2.ipynb", "base2\n"),
+            ("file3", "base3\n"),
+            ("file4.ipynb", "base4\n"),
+        ],
+    );
+    create_commit_with_files(
+        &work_dir,
+        "a",
+        &["base"],
+        &[
+            ("file1.txt", "a1\n"),
+            ("file2.ipynb", "a2\n"),
+            ("file3", "a3\n"),
+            ("file4.ipynb", "a4\n"),
+        ],
+    );
+    create_commit_with_files(
+        &work_dir,
+        "b",
+        &["base"],
+        &[
+            ("file1.txt", "b1\n"),
+            ("file2.ipynb", "b2\n"),
+            ("file3", "b3\n"),
+            ("file4.ipynb", "b4\n"),
+        ],
+    );
+    create_commit_with_files(&work_dir, "conflict", &["a", "b"], &[]);
+
+    // The default tool is started for the files without a configured driver,
+    // and the notebook tool for the notebooks only. The extra argument tells
+    // which tool is started.
+    std::fs::write(
+        &editor_script,
+        [
+            "write\nresolution1\n",
+            "next invocation\n",
+            "write\nresolution3\n",
+            "next invocation\n",
+            "expect-arg 0\nnotebook",
+            "write\nresolution2\n",
+            "next invocation\n",
+            "expect-arg 0\nnotebook",
+            "write\nresolution4\n",
+        ]
+        .join("\0"),
+    )
+    .unwrap();
+    let output = work_dir.run_jj(["resolve"]);
+    assert!(
+        output.stderr.raw().contains(indoc! {"
+            Resolving conflicts in: file1.txt
+            Resolving conflicts in: file3
+            Resolving conflicts in: file2.ipynb
+            Resolving conflicts in: file4.ipynb
+        "}),
+        "{output}"
+    );
+    insta::assert_snapshot!(work_dir.read_file("file1.txt"), @"resolution1");
+    insta::assert_snapshot!(work_dir.read_file("file2.ipynb"), @"resolution2");
+    insta::assert_snapshot!(work_dir.read_file("file3"), @"resolution3");
+    insta::assert_snapshot!(work_dir.read_file("file4.ipynb"), @"resolution4");
+
+    // Attributes are ignored if the tool is specified explicitly
+    work_dir.run_jj(["undo"]).success();
+    std::fs::write(
+        &editor_script,
+        [
+            "write\nresolution1\n",
+            "next invocation\n",
+            "write\nresolution2\n",
+        ]
+        .join("\0"),
+    )
+    .unwrap();
+    let output = work_dir.run_jj(["resolve", "--tool=fake-editor", "file1.txt", "file2.ipynb"]);
+    assert!(
+        output.stderr.raw().contains(indoc! {"
+            Resolving conflicts in: file1.txt
+            Resolving conflicts in: file2.ipynb
+        "}),
+        "{output}"
+    );
+    insta::assert_snapshot!(work_dir.read_file("file2.ipynb"), @"resolution2");
+}
+
 #[test]
 fn test_resolve_with_contents_of_side() {
     let test_env = TestEnvironment::default();
@@ -2091,3 +2199,66 @@ fn test_resolve_with_contents_of_side() {
     [exit status: 2]
     "#);
 }
+
+#[test]
+fn test_resolve_materialize_lazy_conflicts() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    test_env.add_config("ui.lazy-conflicts = true");
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(
+        &work_dir,
+        "base",
+        &[],
+        &[("file1", "base\n"), ("file2", "base\n")],
+    );
+    create_commit_with_files(
+        &work_dir,
+        "a",
+        &["base"],
+        &[("file1", "a\n"), ("file2", "a\n")],
+    );
+    create_commit_with_files(
+        &work_dir,
+        "b",
+        &["base"],
+        &[("file1", "b\n"), ("file2", "b\n")],
+    );
+    create_commit_with_files(&work_dir, "conflict", &["a", "b"], &[]);
+    insta::assert_snapshot!(work_dir.read_file("file1"), @r"
+    This file has conflicts which haven't been written to the working copy yet.
+    ");
+    // The placeholders aren't snapshotted
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "--git"]), @"");
+
+    insta::assert_snapshot!(work_dir.run_jj(["resolve", "--materialize", "file1"]), @r"
+    ------- stderr -------
+    Wrote conflict markers to 1 files
+    [EOF]
+    ");
+    insta::assert_snapshot!(work_dir.read_file("file1"), @r"
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1
+    -base
+    +a
+    +++++++ Contents of side #2
+    b
+    >>>>>>> Conflict 1 of 1 ends
+    ");
+    insta::assert_snapshot!(work_dir.read_file("file2"), @r"
+    This file has conflicts which haven't been written to the working copy yet.
+    ");
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "--git"]), @"");
+
+    // Resolving the materialized conflict leaves the other one as it was
+    work_dir.write_file("file1", "resolved\n");
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "--summary"]), @r"
+    M file1
+    [EOF]
+    ");
+    insta::assert_snapshot!(work_dir.run_jj(["resolve", "--list"]), @r"
+    file2    2-sided conflict
+    [EOF]
+    ");
+}
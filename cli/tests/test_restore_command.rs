@@ -336,6 +336,642 @@ fn test_restore_restore_descendants() {
     ");
 }
 
+#[test]
+fn test_restore_skip_conflicting() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(
+        &work_dir,
+        "base",
+        &[],
+        &[("file1", "base\n"), ("file2", "base\n")],
+    );
+    create_commit_with_files(&work_dir, "child", &["base"], &[("file1", "child\n")]);
+    create_commit_with_files(
+        &work_dir,
+        "wc",
+        &["child"],
+        &[("file1", "wc\n"), ("file2", "wc\n")],
+    );
+
+    // Restoring "file1" into "base" would conflict in "child", so only "file2"
+    // is restored
+    let output = work_dir.run_jj(["restore", "--from=wc", "--into=base", "--skip-conflicting"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Warning: Skipped restoring 1 paths that would conflict in child commits:
+      file1
+    Rebased 2 descendant commits
+    Working copy  (@) now at: royxmykx bd11b2d4 wc | wc
+    Parent commit (@-)      : zsuskuln 2e03f980 child | child
+    [EOF]
+    ");
+    insta::assert_snapshot!(work_dir.run_jj(["file", "show", "-r=base", "file1"]), @r"
+    base
+    [EOF]
+    ");
+    insta::assert_snapshot!(work_dir.run_jj(["file", "show", "-r=base", "file2"]), @r"
+    wc
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  wc
+    ○  child
+    ○  base
+    ◆
+    [EOF]
+    ");
+
+    // Nothing is left to restore if all paths would conflict
+    let output = work_dir.run_jj([
+        "restore",
+        "--from=wc",
+        "--into=base",
+        "--skip-conflicting",
+        "file1",
+    ]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Warning: Skipped restoring 1 paths that would conflict in child commits:
+      file1
+    Nothing changed.
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_restore_against_parent() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(
+        &work_dir,
+        "base",
+        &[],
+        &[("file1", "base\n"), ("file2", "base\n")],
+    );
+    create_commit_with_files(&work_dir, "a", &["base"], &[("file1", "a\n")]);
+    create_commit_with_files(&work_dir, "b", &["base"], &[("file2", "b\n")]);
+    create_commit_with_files(&work_dir, "ab", &["a", "b"], &[("file3", "ab\n")]);
+    let file_contents = |work_dir: &TestWorkDir| {
+        ["file1", "file2", "file3"]
+            .map(|path| work_dir.run_jj(["file", "show", "-r=ab", path]).to_string())
+            .join("")
+    };
+    insta::assert_snapshot!(file_contents(&work_dir), @r"
+    a
+    [EOF]
+    b
+    [EOF]
+    ab
+    [EOF]
+    ");
+
+    // Restoring against the first parent drops the second parent's changes
+    let output = work_dir.run_jj(["restore", "--changes-in=ab", "--against=a"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: vruxwmqv 217b7066 ab | ab
+    Parent commit (@-)      : zsuskuln 4644b5f1 a | a
+    Parent commit (@-)      : royxmykx 099d6986 b | b
+    Added 0 files, modified 1 files, removed 1 files
+    [EOF]
+    ");
+    insta::assert_snapshot!(file_contents(&work_dir), @r"
+    a
+    [EOF]
+    base
+    [EOF]
+    ------- stderr -------
+    Error: No such path: file3
+    [EOF]
+    [exit status: 1]
+    ");
+    work_dir.run_jj(["undo"]).success();
+
+    // Restoring against the second parent drops the first parent's changes
+    let output = work_dir.run_jj(["restore", "--changes-in=ab", "--against=b"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: vruxwmqv a4d35e2b ab | ab
+    Parent commit (@-)      : zsuskuln 4644b5f1 a | a
+    Parent commit (@-)      : royxmykx 099d6986 b | b
+    Added 0 files, modified 1 files, removed 1 files
+    [EOF]
+    ");
+    insta::assert_snapshot!(file_contents(&work_dir), @r"
+    base
+    [EOF]
+    b
+    [EOF]
+    ------- stderr -------
+    Error: No such path: file3
+    [EOF]
+    [exit status: 1]
+    ");
+    work_dir.run_jj(["undo"]).success();
+
+    // Restoring only some paths
+    let output = work_dir.run_jj(["restore", "--changes-in=ab", "--against=b", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: vruxwmqv e69709ea ab | ab
+    Parent commit (@-)      : zsuskuln 4644b5f1 a | a
+    Parent commit (@-)      : royxmykx 099d6986 b | b
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+    insta::assert_snapshot!(file_contents(&work_dir), @r"
+    base
+    [EOF]
+    b
+    [EOF]
+    ab
+    [EOF]
+    ");
+    work_dir.run_jj(["undo"]).success();
+
+    // The revision must be a parent
+    let output = work_dir.run_jj(["restore", "--changes-in=ab", "--against=base"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Revision 5ef046822c5d is not a parent of 4b4844906443
+    Hint: The parents are:
+      zsuskuln 4644b5f1 a | a
+      royxmykx 099d6986 b | b
+    [EOF]
+    [exit status: 1]
+    ");
+
+    // The target must be a merge commit
+    let output = work_dir.run_jj(["restore", "--changes-in=a", "--against=base"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: --against can only be used with a merge commit, but 4644b5f1ad69 has a single parent
+    [EOF]
+    [exit status: 1]
+    ");
+
+    // --against requires --changes-in
+    let output = work_dir.run_jj(["restore", "--against=a"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    error: the following required arguments were not provided:
+      --changes-in <REVSET>
+
+    Usage: jj restore --changes-in <REVSET> --against <REVSET> [FILESETS]...
+
+    For more information, try '--help'.
+    [EOF]
+    [exit status: 2]
+    ");
+}
+
+#[test]
+fn test_restore_track_renames() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file", "a\nb\nc\nd\n");
+    work_dir.run_jj(["new"]).success();
+    work_dir.remove_file("file");
+    work_dir.write_file("renamed", "a\nb\nc\nD\n");
+    let output = work_dir.run_jj(["diff", "--summary"]);
+    insta::assert_snapshot!(output, @r"
+    R {file => renamed}
+    [EOF]
+    ");
+
+    // Without --track-renames, the new path is restored to its absent state
+    let output = work_dir.run_jj(["restore", "renamed"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: rlvkpnrz cff246d1 (no description set)
+    Parent commit (@-)      : qpvuntsm 83def197 (no description set)
+    Added 0 files, modified 0 files, removed 1 files
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "--summary"]);
+    insta::assert_snapshot!(output, @r"
+    D file
+    [EOF]
+    ");
+
+    // With --track-renames, the new path gets the content of the old path
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir.run_jj(["restore", "--track-renames", "renamed"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: rlvkpnrz 4886d14f (no description set)
+    Parent commit (@-)      : qpvuntsm 83def197 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "--git"]);
+    insta::assert_snapshot!(output, @r"
+    diff --git a/file b/renamed
+    rename from file
+    rename to renamed
+    [EOF]
+    ");
+
+    // The old path also matches the rename
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir.run_jj(["restore", "--track-renames", "file"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: rlvkpnrz a269a8a0 (no description set)
+    Parent commit (@-)      : qpvuntsm 83def197 (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "--summary"]);
+    insta::assert_snapshot!(output, @r"
+    R {file => renamed}
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_restore_renamed_in_destination() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("a.rs", "fn a() {}\n");
+    work_dir.write_file("c.rs", "fn c() {}\n");
+    work_dir.run_jj(["new"]).success();
+    work_dir.remove_file("a.rs");
+    work_dir.write_file("b.rs", "fn a() {}\n");
+    work_dir.remove_file("c.rs");
+    let output = work_dir.run_jj(["diff", "--summary"]);
+    insta::assert_snapshot!(output, @r"
+    R {a.rs => b.rs}
+    D c.rs
+    [EOF]
+    ");
+
+    // The renamed path isn't restored since its new path still exists
+    let output = work_dir.run_jj(["restore", "--from", "@-", "a.rs"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Warning: Skipped restoring 1 paths that were renamed in the destination:
+      a.rs (renamed to b.rs)
+    Hint: Use --allow-resurrect to restore them anyway, or --track-renames to restore the renamed paths.
+    Nothing changed.
+    [EOF]
+    ");
+
+    // A deleted path is restored as usual
+    let output = work_dir
+        .run_jj(["restore", "--from", "@-", "c.rs"])
+        .success();
+    assert!(!output.stderr.raw().contains("Skipped"));
+    let output = work_dir.run_jj(["file", "list"]);
+    insta::assert_snapshot!(output, @r"
+    b.rs
+    c.rs
+    [EOF]
+    ");
+
+    // The renamed path can be restored explicitly
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir
+        .run_jj(["restore", "--from", "@-", "--allow-resurrect", "a.rs"])
+        .success();
+    assert!(!output.stderr.raw().contains("Skipped"));
+    let output = work_dir.run_jj(["file", "list"]);
+    insta::assert_snapshot!(output, @r"
+    a.rs
+    b.rs
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_restore_map() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("old/config.toml", "old config\n");
+    work_dir.write_file("file", "a\n");
+    work_dir
+        .run_jj(["bookmark", "create", "-r@", "v1"])
+        .success();
+    work_dir.run_jj(["new"]).success();
+    work_dir.remove_file("old/config.toml");
+    work_dir.write_file("config.toml", "new config\n");
+    work_dir.write_file("file", "b\n");
+
+    // Restore a renamed file from its old path
+    let output = work_dir.run_jj(["restore", "--from=v1", "--map=old/config.toml=config.toml"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: kkmpptxz e24b5814 (no description set)
+    Parent commit (@-)      : qpvuntsm a7c477b2 v1 | (no description set)
+    Added 0 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "--git", "--from=v1"]);
+    insta::assert_snapshot!(output, @r"
+    diff --git a/old/config.toml b/config.toml
+    rename from old/config.toml
+    rename to config.toml
+    diff --git a/file b/file
+    index 7898192261..6178079822 100644
+    --- a/file
+    +++ b/file
+    @@ -1,1 +1,1 @@
+    -a
+    +b
+    [EOF]
+    ");
+
+    // The destination directory is created as needed, and the mapping can be
+    // combined with regular paths
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir.run_jj([
+        "restore",
+        "--from=v1",
+        "--map=old/config.toml=new/dir/config.toml",
+        "file",
+    ]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Working copy  (@) now at: kkmpptxz edc63035 (no description set)
+    Parent commit (@-)      : qpvuntsm a7c477b2 v1 | (no description set)
+    Added 1 files, modified 1 files, removed 0 files
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "--git", "--from=v1"]);
+    insta::assert_snapshot!(output, @r"
+    diff --git a/config.toml b/config.toml
+    new file mode 100644
+    index 0000000000..2404742a44
+    --- /dev/null
+    +++ b/config.toml
+    @@ -0,0 +1,1 @@
+    +new config
+    diff --git a/old/config.toml b/new/dir/config.toml
+    rename from old/config.toml
+    rename to new/dir/config.toml
+    [EOF]
+    ");
+
+    // The source path must exist
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir.run_jj(["restore", "--from=v1", "--map=missing=config.toml"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Path missing doesn't exist in the source revision
+    [EOF]
+    [exit status: 1]
+    ");
+    let output = work_dir.run_jj(["restore", "--from=v1", "--map=old=config.toml"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Path old is a directory in the source revision
+    [EOF]
+    [exit status: 1]
+    ");
+    let output = work_dir.run_jj(["restore", "--from=v1", "--map=config.toml"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Argument 'config.toml' must include '=' and have the form SRC_PATH=DEST_PATH
+    Hint: For example, `--map old/config.toml=new/config.toml` is valid syntax
+    [EOF]
+    [exit status: 2]
+    ");
+
+    // --map requires --from
+    let output = work_dir.run_jj(["restore", "--map=old/config.toml=config.toml"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    error: the following required arguments were not provided:
+      --from <REVSET>
+
+    Usage: jj restore --from <REVSET> --map <SRC_PATH=DEST_PATH> [FILESETS]...
+
+    For more information, try '--help'.
+    [EOF]
+    [exit status: 2]
+    ");
+}
+
+#[test]
+fn test_restore_move() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(&work_dir, "base", &[], &[("file1", "base\n")]);
+    create_commit_with_files(&work_dir, "a", &["base"], &[("file1", "a\n")]);
+    create_commit_with_files(
+        &work_dir,
+        "b",
+        &["a"],
+        &[("file1", "b\n"), ("file2", "b\n")],
+    );
+    create_commit_with_files(&work_dir, "c", &["base"], &[("file3", "c\n")]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  c
+    │ ○  b
+    │ ○  a
+    ├─╯
+    ○  base
+    ◆
+    [EOF]
+    ");
+    let setup_opid = work_dir.current_operation_id();
+
+    // Move a change from a child into its parent
+    work_dir
+        .run_jj(["restore", "--from=b", "--into=a", "--move", "file2"])
+        .success();
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "-s", "-r=a"]), @r"
+    M file1
+    A file2
+    [EOF]
+    ");
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "-s", "-r=b"]), @r"
+    M file1
+    [EOF]
+    ");
+    insta::assert_snapshot!(work_dir.run_jj(["file", "show", "-r=b", "file2"]), @r"
+    b
+    [EOF]
+    ");
+
+    // Move a change from a parent into its child. The child gets the content
+    // of the parent, and keeps it when it's rebased.
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    work_dir
+        .run_jj(["restore", "--from=a", "--into=b", "--move", "file1"])
+        .success();
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "-s", "-r=a"]), @"");
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "--git", "-r=b", "file1"]), @r"
+    diff --git a/file1 b/file1
+    index df967b96a5..7898192261 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,1 +1,1 @@
+    -base
+    +a
+    [EOF]
+    ");
+
+    // Move a change between siblings
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    work_dir
+        .run_jj(["restore", "--from=c", "--into=b", "--move", "file3"])
+        .success();
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "-s", "-r=c"]), @"");
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "-s", "-r=b"]), @r"
+    M file1
+    A file2
+    A file3
+    [EOF]
+    ");
+    assert!(!work_dir.root().join("file3").exists());
+
+    // The source and destination must be different
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    let output = work_dir.run_jj(["restore", "--from=a", "--into=a", "--move"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Cannot move changes from a revision into itself
+    [EOF]
+    [exit status: 1]
+    ");
+
+    // The source must be a single revision given by --from, not the parents of
+    // the destination
+    let output = work_dir.run_jj(["restore", "--move"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    error: the following required arguments were not provided:
+      --from <REVSET>
+
+    Usage: jj restore --from <REVSET> --move [FILESETS]...
+
+    For more information, try '--help'.
+    [EOF]
+    [exit status: 2]
+    ");
+}
+
+#[test]
+fn test_restore_move_renamed_in_destination() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(&work_dir, "base", &[], &[("a.rs", "fn a() {}\n")]);
+    create_commit_with_files(&work_dir, "dest", &["base"], &[("b.rs", "fn a() {}\n")]);
+    work_dir.remove_file("a.rs");
+    create_commit_with_files(&work_dir, "src", &["base"], &[("a.rs", "fn a() { 1 }\n")]);
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "-s", "-r=dest"]), @r"
+    R {a.rs => b.rs}
+    [EOF]
+    ");
+
+    // The path renamed in the destination isn't restored, so its change must
+    // be kept in the source
+    let output = work_dir.run_jj(["restore", "--from=src", "--into=dest", "--move", "a.rs"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Warning: Skipped restoring 1 paths that were renamed in the destination:
+      a.rs (renamed to b.rs)
+    Hint: Use --allow-resurrect to restore them anyway, or --track-renames to restore the renamed paths.
+    Nothing changed.
+    [EOF]
+    ");
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "-s", "-r=src"]), @r"
+    M a.rs
+    [EOF]
+    ");
+    insta::assert_snapshot!(work_dir.run_jj(["diff", "-s", "-r=dest"]), @r"
+    R {a.rs => b.rs}
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_restore_abandon_if_empty() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    // Leave out the commit ids so the snapshots show which commits are
+    // affected
+    test_env.add_config("templates.commit_summary = 'description.first_line()'");
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(&work_dir, "base", &[], &[("file", "base\n")]);
+    create_commit_with_files(
+        &work_dir,
+        "a",
+        &["base"],
+        &[("file", "a\n"), ("file2", "a\n")],
+    );
+    create_commit_with_files(&work_dir, "c", &["a"], &[("file3", "c\n")]);
+    // Test the setup
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  c
+    ○  a
+    ○  base
+    ◆
+    [EOF]
+    ");
+
+    // The commit is kept if only some of its changes are restored
+    let output = work_dir.run_jj(["restore", "-c", "a", "file2", "--abandon-if-empty"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Rebased 1 descendant commits
+    Working copy  (@) now at: c
+    Parent commit (@-)      : a
+    Added 0 files, modified 0 files, removed 1 files
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  c
+    ○  a
+    ○  base
+    ◆
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "-s", "-r", "a"]);
+    insta::assert_snapshot!(output, @r"
+    M file
+    [EOF]
+    ");
+
+    // The commit is abandoned and its bookmark deleted if all of its changes
+    // are restored
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir.run_jj(["restore", "-c", "a", "--abandon-if-empty"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Abandoned commit a because it became empty
+    Rebased 1 descendant commits
+    Working copy  (@) now at: c
+    Parent commit (@-)      : base
+    Added 0 files, modified 1 files, removed 1 files
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  c
+    ○  base
+    ◆
+    [EOF]
+    ");
+}
+
 #[test]
 fn test_restore_interactive() {
     let mut test_env = TestEnvironment::default();
@@ -571,6 +1207,48 @@ fn test_restore_interactive_with_paths() {
     ");
 }
 
+#[test]
+fn test_restore_checkpoint() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    create_commit_with_files(&work_dir, "a", &[], &[("file1", "a\n")]);
+    create_commit_with_files(&work_dir, "b", &["a"], &[("file1", "b\n")]);
+    let get_commit_id = |rev: &str| {
+        let output = work_dir
+            .run_jj(["log", "--no-graph", "-r", rev, "-T", "commit_id"])
+            .success();
+        output.stdout.into_raw()
+    };
+    let b_id = get_commit_id("b");
+
+    work_dir
+        .run_jj(["restore", "--checkpoint", "backup"])
+        .success();
+    assert_eq!(get_commit_id("backup"), b_id);
+    let output = work_dir.run_jj(["file", "show", "-r", "backup", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    b
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "-r", "b", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    a
+    [EOF]
+    ");
+
+    // An existing bookmark is only moved with --checkpoint-force
+    let output = work_dir.run_jj(["restore", "--from", "backup", "--checkpoint", "backup"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Bookmark already exists: backup
+    Hint: Use `--checkpoint-force` to move it.
+    [EOF]
+    [exit status: 1]
+    ");
+}
+
 #[must_use]
 fn get_log_output(work_dir: &TestWorkDir) -> CommandOutput {
     work_dir.run_jj(["log", "-T", "bookmarks"])
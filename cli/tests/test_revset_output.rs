@@ -234,8 +234,7 @@ fn test_bad_function_call() {
       | ^------^
       |
       = Invalid file pattern
-    3: Path "../out" is not in the repo "."
-    4: Invalid component ".." in repo-relative path "../out"
+    3: Path "../out" resolves to "$TEST_ENV/out", which is outside the workspace "."
     [EOF]
     [exit status: 1]
     "#);
@@ -16,6 +16,7 @@ use std::path::PathBuf;
 
 use test_case::test_case;
 
+use crate::common::force_interactive;
 use crate::common::CommandOutput;
 use crate::common::TestEnvironment;
 use crate::common::TestWorkDir;
@@ -73,6 +74,8 @@ fn test_split_by_paths() {
     let output = work_dir.run_jj(["split", "file2"]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file2
     Selected changes : qpvuntsm 6dbc7747 (no description set)
     Remaining changes: zsuskuln 42cbbc02 (no description set)
     Working copy  (@) now at: zsuskuln 42cbbc02 (no description set)
@@ -127,6 +130,8 @@ fn test_split_by_paths() {
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
     Warning: All changes have been selected, so the original revision will become empty
+    Selected 1 changed file:
+      file2
     Rebased 1 descendant commits
     Selected changes : qpvuntsm 9fd1c9e1 (no description set)
     Remaining changes: znkkpsqq 41e0da21 (empty) (no description set)
@@ -181,6 +186,30 @@ fn test_split_by_paths() {
     ");
 }
 
+#[test]
+fn test_split_quiet() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "foo");
+    work_dir.write_file("file2", "foo");
+
+    // Nothing is printed on success
+    std::fs::write(&edit_script, "").unwrap();
+    let output = work_dir.run_jj(["split", "--quiet", "file1"]);
+    insta::assert_snapshot!(output, @"");
+
+    // Warnings are still printed
+    let output = work_dir.run_jj(["split", "--quiet", "file2"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Warning: All changes have been selected, so the original revision will become empty
+    [EOF]
+    ");
+}
+
 #[test]
 fn test_split_with_non_empty_description() {
     let mut test_env = TestEnvironment::default();
@@ -208,6 +237,8 @@ fn test_split_with_non_empty_description() {
     insta::assert_snapshot!(output, @r#"
     ------- stderr -------
     Warning: Deprecated user-level config: ui.default-description is updated to template-aliases.default_commit_description = '"\n\nTESTED=TODO\n"'
+    Selected 1 changed file:
+      file1
     Selected changes : qpvuntsm c7f7b14b part 1
     Remaining changes: kkmpptxz ac33a5a9 part 2
     Working copy  (@) now at: kkmpptxz ac33a5a9 part 2
@@ -266,6 +297,8 @@ fn test_split_with_default_description() {
     insta::assert_snapshot!(output, @r#"
     ------- stderr -------
     Warning: Deprecated user-level config: ui.default-description is updated to template-aliases.default_commit_description = '"\n\nTESTED=TODO\n"'
+    Selected 1 changed file:
+      file1
     Selected changes : qpvuntsm ff633dcc TESTED=TODO
     Remaining changes: rlvkpnrz b1d20b7e (no description set)
     Working copy  (@) now at: rlvkpnrz b1d20b7e (no description set)
@@ -345,6 +378,8 @@ fn test_split_with_descendants() {
     let output = work_dir.run_jj(["split", "file1", "-r", "qpvu"]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 2 descendant commits
     Selected changes : qpvuntsm 74306e35 Add file1
     Remaining changes: royxmykx 0a37745e Add file2
@@ -393,7 +428,7 @@ fn test_split_with_descendants() {
     insta::assert_snapshot!(evolog_1, @r"
     ○  qpvuntsm test.user@example.com 2001-02-03 08:05:12 74306e35
     │  Add file1
-    │  -- operation 994b490f285d (2001-02-03 08:05:12) split commit 1d2499e72cefc8a2b87ebb47569140857b96189f
+    │  -- operation dea484c165de (2001-02-03 08:05:12) split commit 1d2499e72cefc8a2b87ebb47569140857b96189f
     ○  qpvuntsm hidden test.user@example.com 2001-02-03 08:05:08 1d2499e7
     │  Add file1 & file2
     │  -- operation adf4f33386c9 (2001-02-03 08:05:08) commit f5700f8ef89e290e4e90ae6adc0908707e0d8c85
@@ -412,7 +447,7 @@ fn test_split_with_descendants() {
     insta::assert_snapshot!(evolog_2, @r"
     ○  royxmykx test.user@example.com 2001-02-03 08:05:12 0a37745e
     │  Add file2
-    │  -- operation 994b490f285d (2001-02-03 08:05:12) split commit 1d2499e72cefc8a2b87ebb47569140857b96189f
+    │  -- operation dea484c165de (2001-02-03 08:05:12) split commit 1d2499e72cefc8a2b87ebb47569140857b96189f
     ○  qpvuntsm hidden test.user@example.com 2001-02-03 08:05:08 1d2499e7
     │  Add file1 & file2
     │  -- operation adf4f33386c9 (2001-02-03 08:05:08) commit f5700f8ef89e290e4e90ae6adc0908707e0d8c85
@@ -460,6 +495,8 @@ fn test_split_with_merge_child() {
     let output = work_dir.run_jj(["split", "-r", "description(a)", "file1"]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 1 descendant commits
     Selected changes : kkmpptxz cc199567 Add file1
     Remaining changes: royxmykx e488409f Add file2
@@ -511,6 +548,8 @@ fn test_split_parallel_no_descendants() {
     insta::assert_snapshot!(output, @r#"
     ------- stderr -------
     Warning: Deprecated user-level config: ui.default-description is updated to template-aliases.default_commit_description = '"\n\nTESTED=TODO\n"'
+    Selected 1 changed file:
+      file1
     Selected changes : qpvuntsm 7bcd474c TESTED=TODO
     Remaining changes: kkmpptxz 431886f6 (no description set)
     Working copy  (@) now at: kkmpptxz 431886f6 (no description set)
@@ -636,6 +675,8 @@ fn test_split_parallel_with_descendants() {
     let output = work_dir.run_jj(["split", "--parallel", "file1"]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 2 descendant commits
     Selected changes : qpvuntsm 18c85f56 Add file1
     Remaining changes: vruxwmqv cbdfd9cf Add file2
@@ -713,6 +754,8 @@ fn test_split_parallel_with_merge_child() {
     let output = work_dir.run_jj(["split", "-r", "description(a)", "--parallel", "file1"]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 1 descendant commits
     Selected changes : kkmpptxz cc199567 Add file1
     Remaining changes: royxmykx 82a5c527 Add file2
@@ -735,6 +778,64 @@ fn test_split_parallel_with_merge_child() {
     ");
 }
 
+// If the other parent of a merge child descends from the commit being split,
+// the merge child shouldn't get the new commits as redundant parents.
+#[test]
+fn test_split_parallel_with_merge_child_of_ancestor() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    work_dir.run_jj(["describe", "-m=a"]).success();
+    work_dir.write_file("file1", "foo\n");
+    work_dir.write_file("file2", "bar\n");
+    work_dir.run_jj(["new", "-m=b"]).success();
+    work_dir.write_file("file3", "baz\n");
+    work_dir
+        .run_jj(["new", "description(a)", "description(b)", "-m=merge"])
+        .success();
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @    zsuskulnrvyr true merge
+    ├─╮
+    │ ○  kkmpptxzrspx false b
+    ├─╯
+    ○  qpvuntsmwlqt false a
+    ◆  zzzzzzzzzzzz true
+    [EOF]
+    ");
+
+    // Set up the editor and do the split.
+    std::fs::write(
+        edit_script,
+        ["write\nAdd file1", "next invocation\n", "write\nAdd file2"].join("\0"),
+    )
+    .unwrap();
+    let output = work_dir.run_jj(["split", "-r", "description(a)", "--parallel", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 1 changed file:
+      file1
+    Rebased 2 descendant commits
+    Selected changes : qpvuntsm 74306e35 Add file1
+    Remaining changes: royxmykx 99ab2ade Add file2
+    Working copy  (@) now at: zsuskuln 8bc87e61 (empty) merge
+    Parent commit (@-)      : kkmpptxz 1715268f b
+    [EOF]
+    ");
+    // The merge child only keeps `b` as parent, which descends from both new
+    // commits.
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  zsuskulnrvyr true merge
+    ○    kkmpptxzrspx false b
+    ├─╮
+    │ ○  royxmykxtrkr false Add file2
+    ○ │  qpvuntsmwlqt false Add file1
+    ├─╯
+    ◆  zzzzzzzzzzzz true
+    [EOF]
+    ");
+}
+
 // Make sure `jj split` would refuse to split an empty commit.
 #[test]
 fn test_split_empty() {
@@ -835,6 +936,98 @@ fn test_split_interactive() {
     ");
 }
 
+#[test]
+fn test_split_continue() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_editor();
+    let diff_editor = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    let get_descriptions_and_summaries = || {
+        let template = r#"description.first_line() ++ "\n""#;
+        work_dir.run_jj([
+            "log",
+            "--no-graph",
+            "-T",
+            template,
+            "--summary",
+            "-r",
+            "..@",
+        ])
+    };
+
+    work_dir.write_file("file1", "foo\n");
+    work_dir.write_file("file2", "bar\n");
+    work_dir.run_jj(["describe", "-m", "original"]).success();
+
+    // The selection is kept if the description editor fails
+    std::fs::write(&diff_editor, "rm file2").unwrap();
+    std::fs::write(&edit_script, "fail").unwrap();
+    let output = work_dir.run_jj(["split"]);
+    insta::with_settings!({
+        filters => [
+            (r"\bEditor '[^']*'", "Editor '<redacted>'"),
+            (r"in .*(editor-)[^.]*(\.jjdescription)\b", "in <redacted>$1<redacted>$2"),
+            ("exit code", "exit status"), // Windows
+        ],
+    }, {
+        insta::assert_snapshot!(output, @r"
+        ------- stderr -------
+        Error: Failed to edit description
+        Caused by: Editor '<redacted>' exited with exit status: 1
+        Hint: Edited description is left in <redacted>editor-<redacted>.jjdescription
+        Hint: Run `jj split --continue` to reuse the selected changes.
+        [EOF]
+        [exit status: 1]
+        ");
+    });
+    insta::assert_snapshot!(get_descriptions_and_summaries(), @r"
+    original
+    A file1
+    A file2
+    [EOF]
+    ");
+
+    // The diff editor isn't started again
+    std::fs::write(&diff_editor, "fail").unwrap();
+    std::fs::write(
+        &edit_script,
+        ["write\nselected", "next invocation\n", "write\nremaining"].join("\0"),
+    )
+    .unwrap();
+    work_dir.run_jj(["split", "--continue"]).success();
+    insta::assert_snapshot!(get_descriptions_and_summaries(), @r"
+    remaining
+    A file2
+    selected
+    A file1
+    [EOF]
+    ");
+
+    // The saved selection is removed once the split succeeds
+    let output = work_dir.run_jj(["split", "--continue"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: There is no interrupted split to continue
+    [EOF]
+    [exit status: 1]
+    ");
+
+    // The split can't be continued once the repo has changed
+    std::fs::write(&edit_script, "fail").unwrap();
+    let output = work_dir.run_jj(["split", "file2"]);
+    assert!(!output.status.success());
+    work_dir.write_file("file3", "baz\n");
+    let output = work_dir.run_jj(["split", "--continue"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Cannot continue the split because the repository has changed since it was interrupted
+    Hint: Run `jj split` again to select the changes.
+    [EOF]
+    [exit status: 1]
+    ");
+}
+
 #[test]
 fn test_split_interactive_with_paths() {
     let mut test_env = TestEnvironment::default();
@@ -1065,6 +1258,8 @@ fn test_split_with_non_empty_description_and_trailers() {
     insta::assert_snapshot!(output, @r#"
     ------- stderr -------
     Warning: Deprecated user-level config: ui.default-description is updated to template-aliases.default_commit_description = '"\n\nTESTED=TODO\n"'
+    Selected 1 changed file:
+      file1
     Selected changes : qpvuntsm c7f7b14b part 1
     Remaining changes: kkmpptxz ac33a5a9 part 2
     Working copy  (@) now at: kkmpptxz ac33a5a9 part 2
@@ -1107,6 +1302,240 @@ fn test_split_with_non_empty_description_and_trailers() {
     "#);
 }
 
+#[test]
+fn test_split_with_existing_signed_off_by_trailer() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "foo\n");
+    work_dir.write_file("file2", "bar\n");
+    work_dir
+        .run_jj([
+            "describe",
+            "-m",
+            "test",
+            "-m",
+            "Signed-off-by: Alice <alice@example.com>",
+        ])
+        .success();
+    std::fs::write(
+        edit_script,
+        ["dump editor1", "next invocation\n", "dump editor2"].join("\0"),
+    )
+    .unwrap();
+
+    test_env.add_config(
+        r#"
+        templates.commit_trailers = 'format_signed_off_by_trailer(self)'
+        trailers.Signed-off-by.add = "if-missing"
+        "#,
+    );
+    work_dir.run_jj(["split", "file1"]).success();
+
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("editor1")).unwrap(), @r#"
+    JJ: Enter a description for the selected changes.
+    test
+
+    Signed-off-by: Alice <alice@example.com>
+
+    JJ: This commit contains the following changes:
+    JJ:     A file1
+    JJ:
+    JJ: Lines starting with "JJ:" (like this one) will be removed.
+    "#);
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("editor2")).unwrap(), @r#"
+    JJ: Enter a description for the remaining changes.
+    test
+
+    Signed-off-by: Alice <alice@example.com>
+
+    JJ: This commit contains the following changes:
+    JJ:     A file2
+    JJ:
+    JJ: Lines starting with "JJ:" (like this one) will be removed.
+    "#);
+    let output = work_dir.run_jj([
+        "log",
+        "--no-graph",
+        "-r",
+        "::@ ~ root()",
+        "-T",
+        "description",
+    ]);
+    insta::assert_snapshot!(output, @r"
+    test
+
+    Signed-off-by: Alice <alice@example.com>
+    test
+
+    Signed-off-by: Alice <alice@example.com>
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_split_parses_description_templates_once() {
+    let mut test_env = TestEnvironment::default();
+    let edit_script = test_env.set_up_fake_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "foo\n");
+    work_dir.write_file("file2", "bar\n");
+    work_dir.run_jj(["describe", "-m", "test"]).success();
+    std::fs::write(
+        edit_script,
+        ["write\npart 1", "next invocation\n", "write\npart 2"].join("\0"),
+    )
+    .unwrap();
+
+    // Deprecated functions produce a warning each time the template is parsed
+    test_env.add_config(
+        r#"[templates]
+        commit_trailers = '''"Reviewed-by: " ++ committer.username()'''
+        draft_commit_description = '''description ++ author.username()'''"#,
+    );
+    let output = work_dir.run_jj(["split", "file1"]);
+    insta::assert_snapshot!(output, @r#"
+    ------- stderr -------
+    Selected 1 changed file:
+      file1
+    Warning: In template expression
+     --> 1:30
+      |
+    1 | "Reviewed-by: " ++ committer.username()
+      |                              ^------^
+      |
+      = username() is deprecated; use email().local() instead
+    Warning: In template expression
+     --> 1:23
+      |
+    1 | description ++ author.username()
+      |                       ^------^
+      |
+      = username() is deprecated; use email().local() instead
+    Selected changes : qpvuntsm c7f7b14b part 1
+    Remaining changes: kkmpptxz ac33a5a9 part 2
+    Working copy  (@) now at: kkmpptxz ac33a5a9 part 2
+    Parent commit (@-)      : qpvuntsm c7f7b14b part 1
+    [EOF]
+    "#);
+}
+
+#[test]
+fn test_split_mark_remainder_fixup_of() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "foo\n");
+    work_dir
+        .run_jj(["bookmark", "create", "-r@", "target"])
+        .success();
+    work_dir.run_jj(["new", "-m", "my feature"]).success();
+    work_dir.write_file("file1", "foo\nbar\n");
+    work_dir.write_file("file2", "baz\n");
+    let target_change_id = work_dir
+        .run_jj(["log", "--no-graph", "-r", "target", "-T", "change_id"])
+        .success()
+        .stdout
+        .into_raw();
+
+    work_dir
+        .run_jj([
+            "split",
+            "-m",
+            "add file2",
+            "--mark-remainder-fixup-of",
+            "target",
+            "file2",
+        ])
+        .success();
+    let output = work_dir.run_jj(["log", "--no-graph", "-r", "@-", "-T", "description"]);
+    insta::assert_snapshot!(output, @r"
+    add file2
+    [EOF]
+    ");
+    // The remaining changes are marked as a fixup of the target
+    let output = work_dir
+        .run_jj(["log", "--no-graph", "-r", "@", "-T", "description"])
+        .success();
+    assert_eq!(
+        output.stdout.into_raw(),
+        format!("my feature\n\nFixup-Of: {target_change_id}\n")
+    );
+}
+
+#[test]
+fn test_split_no_rebase_descendants() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    let get_commit_id = |revset: &str| {
+        work_dir
+            .run_jj(["log", "--no-graph", "-r", revset, "-T", "commit_id"])
+            .success()
+            .stdout
+            .into_raw()
+    };
+
+    work_dir.write_file("file1", "foo\n");
+    work_dir.write_file("file2", "bar\n");
+    work_dir.run_jj(["describe", "-m", "target"]).success();
+    work_dir
+        .run_jj(["bookmark", "create", "-r@", "target"])
+        .success();
+    work_dir.run_jj(["new", "-m", "child"]).success();
+    work_dir.write_file("file3", "baz\n");
+    let target_id = get_commit_id("target");
+    let child_id = get_commit_id("@");
+
+    let output = work_dir.run_jj([
+        "split",
+        "-r",
+        "target",
+        "--no-rebase-descendants",
+        "-m",
+        "selected",
+        "file1",
+    ]);
+    let stderr = output.stderr.raw();
+    assert!(!stderr.contains("Rebased"), "{stderr}");
+    assert!(
+        stderr.contains(&format!(
+            "Hint: The descendants were left on the original revision {}.",
+            &target_id[..12]
+        )),
+        "{stderr}"
+    );
+    assert!(stderr.contains("jj rebase -s"), "{stderr}");
+
+    // The child is left on the original revision, which stays visible.
+    assert_eq!(get_commit_id("@"), child_id);
+    assert_eq!(get_commit_id("@-"), target_id);
+    // The bookmark still follows the remaining changes.
+    let output = work_dir.run_jj(["log", "--no-graph", "-r", "target", "-T", "description"]);
+    insta::assert_snapshot!(output, @r"
+    target
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["log", "--no-graph", "-r", "target-", "-T", "description"]);
+    insta::assert_snapshot!(output, @r"
+    selected
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "list", "-r", "target"]);
+    insta::assert_snapshot!(output, @r"
+    file1
+    file2
+    [EOF]
+    ");
+}
+
 #[test]
 fn test_split_with_message() {
     let test_env = TestEnvironment::default();
@@ -1120,6 +1549,8 @@ fn test_split_with_message() {
     let output = work_dir.run_jj(["split", "-m", "fix in file1", "file1"]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Selected changes : qpvuntsm f2a70519 fix in file1
     Remaining changes: kkmpptxz cac11766 my feature
     Working copy  (@) now at: kkmpptxz cac11766 my feature
@@ -1146,6 +1577,8 @@ fn test_split_with_message() {
     ]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Selected changes : qpvuntsm d01cf12d fix in file1
     Remaining changes: royxmykx b1556ed9 my feature
     Working copy  (@) now at: royxmykx b1556ed9 my feature
@@ -1164,38 +1597,185 @@ fn test_split_with_message() {
 }
 
 #[test]
-fn test_split_move_first_commit() {
+fn test_split_track_renames() {
     let test_env = TestEnvironment::default();
     test_env.run_jj_in(".", ["git", "init", "repo"]).success();
     let work_dir = test_env.work_dir("repo");
 
-    work_dir.write_file("file1", "foo\n");
-    work_dir.write_file("file2", "bar\n");
-    work_dir.run_jj(["commit", "-m", "file2"]).success();
-    work_dir.write_file("file3", "bar\n");
-    work_dir.run_jj(["commit", "-m", "file3"]).success();
-    work_dir.write_file("file4", "bar\n");
-    work_dir.run_jj(["commit", "-m", "file4"]).success();
-    work_dir.run_jj(["new", "root()"]).success();
-    work_dir.write_file("file5", "bar\n");
-    work_dir.run_jj(["commit", "-m", "file5"]).success();
+    work_dir.write_file("file", "a\nb\nc\nd\n");
+    work_dir.run_jj(["new"]).success();
+    work_dir.remove_file("file");
+    work_dir.write_file("renamed", "a\nb\nc\nD\n");
+    work_dir.write_file("other", "foo\n");
+    work_dir.run_jj(["describe", "-m", "my feature"]).success();
 
+    // The content change is selected at the old path, and the rename is left
+    // in the remaining changes
+    let output = work_dir.run_jj(["split", "--track-renames", "-m", "first", "renamed"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 1 changed file:
+      file
+    Selected changes : rlvkpnrz 05de4d06 first
+    Remaining changes: zsuskuln 58e9ece4 my feature
+    Working copy  (@) now at: zsuskuln 58e9ece4 my feature
+    Parent commit (@-)      : rlvkpnrz 05de4d06 first
+    [EOF]
+    ");
     insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
-    @  royxmykxtrkr
-    ○  mzvwutvlkqwt file5
-    │  A file5
-    │ ○  kkmpptxzrspx file4
-    │ │  A file4
-    │ ○  rlvkpnrzqnoo file3
-    │ │  A file3
-    │ ○  qpvuntsmwlqt file2
-    ├─╯  A file1
-    │    A file2
+    @  zsuskulnrvyr my feature
+    │  A other
+    │  R {file => renamed}
+    ○  rlvkpnrzqnoo first
+    │  M file
+    ○  qpvuntsmwlqt
+    │  A file
     ◆  zzzzzzzzzzzz
     [EOF]
     ");
+}
 
-    // insert the commit before the source commit
+#[test]
+fn test_split_dry_run() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("unchanged", "foo\n");
+    work_dir.run_jj(["new"]).success();
+    work_dir.write_file("file1", "foo\n");
+    work_dir.write_file("file2", "bar\nbaz\n");
+    work_dir.write_file("file3", "qux\n");
+    work_dir.run_jj(["describe", "-m", "my feature"]).success();
+    let op_log_before = work_dir.run_jj(["op", "log"]).success();
+
+    // Files that are matched but not changed aren't part of the selection
+    let output = work_dir.run_jj(["split", "--dry-run", "file1", "file2", "unchanged"]);
+    insta::assert_snapshot!(output, @r"
+    Selected changes:
+    file1 | 1 +
+    file2 | 2 ++
+    2 files changed, 3 insertions(+), 0 deletions(-)
+    Remaining changes:
+    file3 | 1 +
+    1 file changed, 1 insertion(+), 0 deletions(-)
+    [EOF]
+    ");
+
+    // No operation is recorded by a dry run
+    let op_log_after = work_dir.run_jj(["op", "log"]).success();
+    assert_eq!(op_log_before.stdout.raw(), op_log_after.stdout.raw());
+}
+
+#[test]
+fn test_split_confirm_threshold() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    test_env.add_config("templates.commit_summary = 'description.first_line()'");
+    test_env.add_config("split.confirm-threshold = 11");
+    let work_dir = test_env.work_dir("repo");
+
+    for i in 1..=12 {
+        work_dir.write_file(format!("file{i:02}"), "foo\n");
+    }
+    work_dir.write_file("other", "bar\n");
+    work_dir.run_jj(["describe", "-m", "my feature"]).success();
+
+    // Only the first few selected files are listed, and splitting more files
+    // than the threshold fails if it can't prompt
+    let output = work_dir.run_jj(["split", "-m", "first", "glob:file*"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 12 changed files:
+      file01
+      file02
+      file03
+      file04
+      file05
+      file06
+      file07
+      file08
+      file09
+      file10
+      ... and 2 more
+    Error: Refusing to split 12 selected files without confirmation (the threshold is 11)
+    Hint: Use `--dry-run` to review the selection, and raise `split.confirm-threshold` to split anyway.
+    [EOF]
+    [exit status: 1]
+    ");
+
+    // The split can be cancelled at the prompt
+    let output = work_dir.run_jj_with(|cmd| {
+        force_interactive(cmd)
+            .args(["split", "--quiet", "-m", "first", "glob:file*"])
+            .write_stdin("n\n")
+    });
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Split 12 selected files? (yn): Error: Split cancelled
+    [EOF]
+    [exit status: 1]
+    ");
+
+    let output = work_dir.run_jj_with(|cmd| {
+        force_interactive(cmd)
+            .args(["split", "--quiet", "-m", "first", "glob:file*"])
+            .write_stdin("y\n")
+    });
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Split 12 selected files? (yn): [EOF]
+    ");
+
+    // Selections within the threshold are split without confirmation
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir.run_jj(["split", "-m", "first", "file01", "other"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 2 changed files:
+      file01
+      other
+    Selected changes : first
+    Remaining changes: my feature
+    Working copy  (@) now at: my feature
+    Parent commit (@-)      : first
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_split_move_first_commit() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "foo\n");
+    work_dir.write_file("file2", "bar\n");
+    work_dir.run_jj(["commit", "-m", "file2"]).success();
+    work_dir.write_file("file3", "bar\n");
+    work_dir.run_jj(["commit", "-m", "file3"]).success();
+    work_dir.write_file("file4", "bar\n");
+    work_dir.run_jj(["commit", "-m", "file4"]).success();
+    work_dir.run_jj(["new", "root()"]).success();
+    work_dir.write_file("file5", "bar\n");
+    work_dir.run_jj(["commit", "-m", "file5"]).success();
+
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  royxmykxtrkr
+    ○  mzvwutvlkqwt file5
+    │  A file5
+    │ ○  kkmpptxzrspx file4
+    │ │  A file4
+    │ ○  rlvkpnrzqnoo file3
+    │ │  A file3
+    │ ○  qpvuntsmwlqt file2
+    ├─╯  A file1
+    │    A file2
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+
+    // insert the commit before the source commit
     let output = work_dir.run_jj([
         "split",
         "-m",
@@ -1208,6 +1788,8 @@ fn test_split_move_first_commit() {
     ]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 2 descendant commits
     Selected changes : vruxwmqv bf94c29a file1
     Remaining changes: qpvuntsm 66b1d4f1 file2
@@ -1244,6 +1826,8 @@ fn test_split_move_first_commit() {
     ]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 2 descendant commits
     Selected changes : kpqxywon 08294e90 file1
     Remaining changes: qpvuntsm 76ebcbb8 file2
@@ -1280,6 +1864,8 @@ fn test_split_move_first_commit() {
     ]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 2 descendant commits
     Selected changes : lylxulpl b42b2604 file1
     Remaining changes: qpvuntsm 0f76cbf0 file2
@@ -1318,6 +1904,8 @@ fn test_split_move_first_commit() {
     ]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 2 descendant commits
     Selected changes : uyznsvlq d0338445 file1
     Remaining changes: qpvuntsm 16d41320 file2
@@ -1354,6 +1942,8 @@ fn test_split_move_first_commit() {
     ]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 3 descendant commits
     Selected changes : nmzmmopx 72225233 file1
     Remaining changes: qpvuntsm 98b70782 file2
@@ -1395,6 +1985,8 @@ fn test_split_move_first_commit() {
     ]);
     insta::assert_snapshot!(output, @r"
     ------- stderr -------
+    Selected 1 changed file:
+      file1
     Rebased 3 descendant commits
     Selected changes : nlrtlrxv 1b6975b0 file1
     Remaining changes: qpvuntsm 905586dd file2
@@ -1452,11 +2044,11 @@ fn test_split_with_bookmarks(bookmark_behavior: BookmarkBehavior) {
     main_dir.write_file("file1", "foo");
     main_dir.write_file("file2", "foo");
     main_dir
-        .run_jj(["bookmark", "set", "'*le-signet*'", "-r", "@"])
+        .run_jj(["bookmark", "set", "'le,signet'", "-r", "@"])
         .success();
     insta::allow_duplicates! {
     insta::assert_snapshot!(get_log_output(&main_dir), @r#"
-    @  qpvuntsmwlqt false "*le-signet*" first-commit
+    @  qpvuntsmwlqt false "le,signet" first-commit
     ◆  zzzzzzzzzzzz true
     [EOF]
     "#);
@@ -1474,17 +2066,19 @@ fn test_split_with_bookmarks(bookmark_behavior: BookmarkBehavior) {
             insta::allow_duplicates! {
             insta::assert_snapshot!(output, @r#"
             ------- stderr -------
-            Selected changes : qpvuntsm a481fe8a "*le-signet*" | first-commit
+            Selected 1 changed file:
+              file2
+            Selected changes : qpvuntsm a481fe8a "le,signet" | first-commit
             Remaining changes: mzvwutvl 5f597a6e second-commit
             Working copy  (@) now at: mzvwutvl 5f597a6e second-commit
-            Parent commit (@-)      : qpvuntsm a481fe8a "*le-signet*" | first-commit
+            Parent commit (@-)      : qpvuntsm a481fe8a "le,signet" | first-commit
             [EOF]
             "#);
             }
             insta::allow_duplicates! {
             insta::assert_snapshot!(get_log_output(&main_dir), @r#"
             @  mzvwutvlkqwt false second-commit
-            ○  qpvuntsmwlqt false "*le-signet*" first-commit
+            ○  qpvuntsmwlqt false "le,signet" first-commit
             ◆  zzzzzzzzzzzz true
             [EOF]
             "#);
@@ -1494,16 +2088,18 @@ fn test_split_with_bookmarks(bookmark_behavior: BookmarkBehavior) {
             insta::allow_duplicates! {
             insta::assert_snapshot!(output, @r#"
             ------- stderr -------
+            Selected 1 changed file:
+              file2
             Selected changes : qpvuntsm a481fe8a first-commit
-            Remaining changes: mzvwutvl 5f597a6e "*le-signet*" | second-commit
-            Working copy  (@) now at: mzvwutvl 5f597a6e "*le-signet*" | second-commit
+            Remaining changes: mzvwutvl 5f597a6e "le,signet" | second-commit
+            Working copy  (@) now at: mzvwutvl 5f597a6e "le,signet" | second-commit
             Parent commit (@-)      : qpvuntsm a481fe8a first-commit
             [EOF]
             "#);
             }
             insta::allow_duplicates! {
             insta::assert_snapshot!(get_log_output(&main_dir), @r#"
-            @  mzvwutvlkqwt false "*le-signet*" second-commit
+            @  mzvwutvlkqwt false "le,signet" second-commit
             ○  qpvuntsmwlqt false first-commit
             ◆  zzzzzzzzzzzz true
             [EOF]
@@ -1525,7 +2121,7 @@ fn test_split_with_bookmarks(bookmark_behavior: BookmarkBehavior) {
             insta::allow_duplicates! {
             insta::assert_snapshot!(get_log_output(&main_dir), @r#"
             @  vruxwmqvtpmx false second-commit
-            │ ○  qpvuntsmwlqt false "*le-signet*" first-commit
+            │ ○  qpvuntsmwlqt false "le,signet" first-commit
             ├─╯
             ◆  zzzzzzzzzzzz true
             [EOF]
@@ -1535,7 +2131,7 @@ fn test_split_with_bookmarks(bookmark_behavior: BookmarkBehavior) {
         BookmarkBehavior::Default | BookmarkBehavior::MoveBookmarkToChild => {
             insta::allow_duplicates! {
             insta::assert_snapshot!(get_log_output(&main_dir), @r#"
-            @  vruxwmqvtpmx false "*le-signet*" second-commit
+            @  vruxwmqvtpmx false "le,signet" second-commit
             │ ○  qpvuntsmwlqt false first-commit
             ├─╯
             ◆  zzzzzzzzzzzz true
@@ -1545,3 +2141,672 @@ fn test_split_with_bookmarks(bookmark_behavior: BookmarkBehavior) {
         }
     }
 }
+
+#[test]
+fn test_split_into_parent() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "foo\n");
+    work_dir.run_jj(["commit", "-m", "parent"]).success();
+    work_dir.write_file("file2", "bar\n");
+    work_dir.write_file("file3", "baz\n");
+    work_dir.run_jj(["commit", "-m", "child"]).success();
+    work_dir.write_file("file4", "qux\n");
+    work_dir.run_jj(["describe", "-m", "grandchild"]).success();
+
+    // Move file2 from the child into the parent
+    let output = work_dir.run_jj(["split", "-r", "@-", "--into", "@--", "file2"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 1 changed file:
+      file2
+    Rebased 1 descendant commits
+    Selected changes : qpvuntsm 32ff2142 parent
+    Remaining changes: rlvkpnrz 440f3cd5 child
+    Working copy  (@) now at: kkmpptxz e6aa2c8d grandchild
+    Parent commit (@-)      : rlvkpnrz 440f3cd5 child
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  kkmpptxzrspx grandchild
+    │  A file4
+    ○  rlvkpnrzqnoo child
+    │  A file3
+    ○  qpvuntsmwlqt parent
+    │  A file1
+    │  A file2
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+
+    // The description of the split revision can be appended
+    work_dir.run_jj(["undo"]).success();
+    let output = work_dir.run_jj([
+        "split",
+        "-r",
+        "@-",
+        "--into",
+        "@--",
+        "--append-message",
+        "file2",
+    ]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 1 changed file:
+      file2
+    Rebased 1 descendant commits
+    Selected changes : qpvuntsm 37adfc04 parent
+    Remaining changes: rlvkpnrz b957868f child
+    Working copy  (@) now at: kkmpptxz c0a25f8e grandchild
+    Parent commit (@-)      : rlvkpnrz b957868f child
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_output(&work_dir), @r"
+    @  kkmpptxzrspx false grandchild
+    ○  rlvkpnrzqnoo false child
+    ○  qpvuntsmwlqt false parent
+    │
+    │  child
+    ◆  zzzzzzzzzzzz true
+    [EOF]
+    ");
+
+    // Can't move changes into the split revision itself
+    let output = work_dir.run_jj(["split", "-r", "@", "--into", "@", "file4"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: Cannot move the selected changes into the split revision
+    [EOF]
+    [exit status: 1]
+    ");
+}
+
+#[test]
+fn test_split_into_descendant() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "foo\n");
+    work_dir.write_file("file2", "bar\n");
+    work_dir.run_jj(["commit", "-m", "source"]).success();
+    work_dir.write_file("file3", "baz\n");
+    work_dir.run_jj(["commit", "-m", "destination"]).success();
+    work_dir.write_file("file4", "qux\n");
+    work_dir.run_jj(["commit", "-m", "child"]).success();
+    work_dir.write_file("file5", "quux\n");
+    work_dir.run_jj(["describe", "-m", "grandchild"]).success();
+
+    // The descendants of the destination are rebased twice, but each of them
+    // is only counted once
+    let output = work_dir.run_jj([
+        "split",
+        "-r",
+        "description(source)",
+        "--into",
+        "description(destination)",
+        "file2",
+    ]);
+    let stderr = output.stderr.raw();
+    assert!(
+        stderr.starts_with("Rebased 2 descendant commits\n"),
+        "unexpected output: {stderr}"
+    );
+    let output = work_dir.run_jj(["diff", "--summary", "-r", "description(destination)"]);
+    insta::assert_snapshot!(output, @r"
+    A file2
+    A file3
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_split_into_sibling_with_conflicts() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "a\n");
+    work_dir.run_jj(["commit", "-m", "base"]).success();
+    work_dir.write_file("file1", "b\n");
+    work_dir.write_file("file2", "foo\n");
+    work_dir.run_jj(["bookmark", "create", "-r@", "source"]).success();
+    work_dir.run_jj(["commit", "-m", "source"]).success();
+    work_dir.run_jj(["new", "description(base)"]).success();
+    work_dir.write_file("file1", "c\n");
+    work_dir.run_jj(["bookmark", "create", "-r@", "sibling"]).success();
+    work_dir.run_jj(["describe", "-m", "sibling"]).success();
+
+    // Moving the change to file1 into the sibling conflicts with its changes
+    let output = work_dir.run_jj(["split", "-r", "source", "--into", "sibling", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 1 changed file:
+      file1
+    Selected changes : mzvwutvl 5f103e7b sibling | (conflict) sibling
+    Remaining changes: rlvkpnrz 39e5f7d2 source | source
+    Working copy  (@) now at: mzvwutvl 5f103e7b sibling | (conflict) sibling
+    Parent commit (@-)      : qpvuntsm d4e04913 base
+    Added 0 files, modified 1 files, removed 0 files
+    Warning: There are unresolved conflicts at these paths:
+    file1    2-sided conflict
+    New conflicts appeared in 1 commits:
+      mzvwutvl 5f103e7b sibling | (conflict) sibling
+    Hint: To resolve the conflicts, start by creating a commit on top of
+    the conflicted commit:
+      jj new mzvwutvl
+    Then use `jj resolve`, or edit the conflict markers in the file directly.
+    Once the conflicts are resolved, you can inspect the result with `jj diff`.
+    Then run `jj squash` to move the resolution into the conflicted commit.
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  mzvwutvlkqwt sibling sibling
+    │  M file1
+    │ ○  rlvkpnrzqnoo source source
+    ├─╯  A file2
+    ○  qpvuntsmwlqt base
+    │  A file1
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "-r", "sibling", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1
+    -a
+    +c
+    +++++++ Contents of side #2
+    b
+    >>>>>>> Conflict 1 of 1 ends
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_split_with_conflicts() {
+    let test_env = TestEnvironment::default();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("file1", "a\n");
+    work_dir.run_jj(["commit", "-m", "base"]).success();
+    work_dir.write_file("file1", "b\n");
+    work_dir.run_jj(["describe", "-m", "parent"]).success();
+    work_dir.run_jj(["new", "description(base)"]).success();
+    work_dir.write_file("file1", "c\n");
+    work_dir.write_file("file2", "foo\n");
+    work_dir.run_jj(["describe", "-m", "target"]).success();
+    // Rebasing the target onto the parent makes file1 conflicted
+    work_dir
+        .run_jj(["rebase", "-r@", "-d", "description(parent)"])
+        .success();
+    let setup_opid = work_dir.current_operation_id();
+
+    // Select the conflicted file
+    let output = work_dir.run_jj(["split", "-m", "first", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 1 changed file:
+      file1
+    Selected changes : zsuskuln 474f4d9d (conflict) first
+    Remaining changes: vruxwmqv 238ecef7 (conflict) target
+    Working copy  (@) now at: vruxwmqv 238ecef7 (conflict) target
+    Parent commit (@-)      : zsuskuln 474f4d9d (conflict) first
+    Warning: There are unresolved conflicts at these paths:
+    file1    2-sided conflict
+    New conflicts appeared in 1 commits:
+      vruxwmqv 238ecef7 (conflict) target
+    Hint: To resolve the conflicts, start by creating a commit on top of
+    the first conflicted commit:
+      jj new zsuskuln
+    Then use `jj resolve`, or edit the conflict markers in the file directly.
+    Once the conflicts are resolved, you can inspect the result with `jj diff`.
+    Then run `jj squash` to move the resolution into the conflicted commit.
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  vruxwmqvtpmx target
+    │  A file2
+    ×  zsuskulnrvyr first
+    │  M file1
+    ○  rlvkpnrzqnoo parent
+    │  M file1
+    ○  qpvuntsmwlqt base
+    │  A file1
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "-r", "description(first)", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1
+    -a
+    +b
+    +++++++ Contents of side #2
+    c
+    >>>>>>> Conflict 1 of 1 ends
+    [EOF]
+    ");
+
+    // Select the clean file
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    let output = work_dir.run_jj(["split", "-m", "first", "file2"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 1 changed file:
+      file2
+    Selected changes : zsuskuln aa579c08 first
+    Remaining changes: kmkuslsw d7bdb9c4 (conflict) target
+    Working copy  (@) now at: kmkuslsw d7bdb9c4 (conflict) target
+    Parent commit (@-)      : zsuskuln aa579c08 first
+    Warning: There are unresolved conflicts at these paths:
+    file1    2-sided conflict
+    Existing conflicts were resolved or abandoned from 1 commits.
+    New conflicts appeared in 1 commits:
+      kmkuslsw d7bdb9c4 (conflict) target
+    Hint: To resolve the conflicts, start by creating a commit on top of
+    the conflicted commit:
+      jj new kmkuslsw
+    Then use `jj resolve`, or edit the conflict markers in the file directly.
+    Once the conflicts are resolved, you can inspect the result with `jj diff`.
+    Then run `jj squash` to move the resolution into the conflicted commit.
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  kmkuslswpqwq target
+    │  M file1
+    ○  zsuskulnrvyr first
+    │  A file2
+    ○  rlvkpnrzqnoo parent
+    │  M file1
+    ○  qpvuntsmwlqt base
+    │  A file1
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "-r", "@", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1
+    -a
+    +b
+    +++++++ Contents of side #2
+    c
+    >>>>>>> Conflict 1 of 1 ends
+    [EOF]
+    ");
+
+    // The remaining changes of a parallel split preserve the conflict
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    let output = work_dir.run_jj(["split", "--parallel", "-m", "first", "file2"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Selected 1 changed file:
+      file2
+    Selected changes : zsuskuln 0b348f7f first
+    Remaining changes: nkmrtpmo 41f182d0 (conflict) target
+    Working copy  (@) now at: nkmrtpmo 41f182d0 (conflict) target
+    Parent commit (@-)      : rlvkpnrz 25f0a6a4 parent
+    Added 0 files, modified 0 files, removed 1 files
+    Warning: There are unresolved conflicts at these paths:
+    file1    2-sided conflict
+    Existing conflicts were resolved or abandoned from 1 commits.
+    New conflicts appeared in 1 commits:
+      nkmrtpmo 41f182d0 (conflict) target
+    Hint: To resolve the conflicts, start by creating a commit on top of
+    the conflicted commit:
+      jj new nkmrtpmo
+    Then use `jj resolve`, or edit the conflict markers in the file directly.
+    Once the conflicts are resolved, you can inspect the result with `jj diff`.
+    Then run `jj squash` to move the resolution into the conflicted commit.
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  nkmrtpmomlro target
+    │  M file1
+    │ ○  zsuskulnrvyr first
+    ├─╯  A file2
+    ○  rlvkpnrzqnoo parent
+    │  M file1
+    ○  qpvuntsmwlqt base
+    │  A file1
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["file", "show", "-r", "@", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1
+    -a
+    +b
+    +++++++ Contents of side #2
+    c
+    >>>>>>> Conflict 1 of 1 ends
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_split_interactive_binary_files() {
+    let mut test_env = TestEnvironment::default();
+    let diff_editor = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+
+    work_dir.write_file("binary", [0xff, 0x00]);
+    work_dir.write_file("large", "a\nb\n");
+    work_dir.write_file("text", "a\n");
+    work_dir.run_jj(["commit", "-m", "base"]).success();
+    work_dir.write_file("binary", [0xff, 0x01]);
+    work_dir.write_file("large", "a\nc\n");
+    work_dir.write_file("text", "b\n");
+    work_dir.run_jj(["describe", "-m", "target"]).success();
+    let setup_opid = work_dir.current_operation_id();
+
+    // Select the binary file. Files larger than the threshold are listed too.
+    let diff_script = ["reset large", "reset text", "dump JJ-INSTRUCTIONS instrs"].join("\0");
+    std::fs::write(&diff_editor, diff_script).unwrap();
+    let output = work_dir.run_jj([
+        "split",
+        "-m",
+        "first",
+        "--config=ui.diff-editor-max-text-size=3",
+    ]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Warning: The revision has 2 binary or large files, which the diff editor may not be able to split as whole files:
+      binary
+      large
+    Hint: Use the builtin diff editor (`--tool :builtin`) to select them as whole files.
+    Selected changes : rlvkpnrz 5f526270 first
+    Remaining changes: mzvwutvl b4afda0c target
+    Working copy  (@) now at: mzvwutvl b4afda0c target
+    Parent commit (@-)      : rlvkpnrz 5f526270 first
+    [EOF]
+    ");
+    insta::assert_snapshot!(
+        std::fs::read_to_string(test_env.env_root().join("instrs")).unwrap(), @r"
+    You are splitting a commit into two: rlvkpnrz ccbd362f target
+
+    The diff initially shows the changes in the commit you're splitting.
+
+    Adjust the right side until it shows the contents you want to split into the
+    new commit.
+    The changes that are not selected will replace the original commit.
+
+    The following files are binary or large. Copy or revert each of them as a
+    whole:
+      binary
+      large
+    ");
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  mzvwutvlkqwt target
+    │  M large
+    │  M text
+    ○  rlvkpnrzqnoo first
+    │  M binary
+    ○  qpvuntsmwlqt base
+    │  A binary
+    │  A large
+    │  A text
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+
+    // Select the text files
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    std::fs::write(&diff_editor, "reset binary").unwrap();
+    let output = work_dir.run_jj(["split", "-m", "first"]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Warning: The revision has 1 binary or large files, which the diff editor may not be able to split as whole files:
+      binary
+    Hint: Use the builtin diff editor (`--tool :builtin`) to select them as whole files.
+    Selected changes : rlvkpnrz 4f5b3f1c first
+    Remaining changes: vruxwmqv 1d301948 target
+    Working copy  (@) now at: vruxwmqv 1d301948 target
+    Parent commit (@-)      : rlvkpnrz 4f5b3f1c first
+    [EOF]
+    ");
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  vruxwmqvtpmx target
+    │  M binary
+    ○  rlvkpnrzqnoo first
+    │  M large
+    │  M text
+    ○  qpvuntsmwlqt base
+    │  A binary
+    │  A large
+    │  A text
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "--git", "-r", "description(first)"]);
+    insta::assert_snapshot!(output, @r"
+    diff --git a/large b/large
+    index 422c2b7ab3..0f7bc76605 100644
+    --- a/large
+    +++ b/large
+    @@ -1,2 +1,2 @@
+     a
+    -b
+    +c
+    diff --git a/text b/text
+    index 7898192261..6178079822 100644
+    --- a/text
+    +++ b/text
+    @@ -1,1 +1,1 @@
+    -a
+    +b
+    [EOF]
+    ");
+}
+
+#[test]
+fn test_split_selection_plan() {
+    let mut test_env = TestEnvironment::default();
+    let diff_editor = test_env.set_up_fake_diff_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    let plan_path = test_env.env_root().join("plan.toml");
+
+    work_dir.write_file("file1", "a\nb\nc\nd\ne\n");
+    work_dir.write_file("file2", "a\n");
+    work_dir.run_jj(["commit", "-m", "base"]).success();
+    work_dir.write_file("file1", "A\nb\nc\nd\nE\n");
+    work_dir.write_file("file2", "b\n");
+    work_dir.write_file("file3", "c\n");
+    work_dir.run_jj(["describe", "-m", "target"]).success();
+    let setup_opid = work_dir.current_operation_id();
+
+    // Record the selection of the first hunk of file1 and the whole file3
+    let diff_script = ["write file1\nA\nb\nc\nd\ne\n", "reset file2"].join("\0");
+    std::fs::write(&diff_editor, diff_script).unwrap();
+    work_dir
+        .run_jj([
+            "split",
+            "-m",
+            "first",
+            "--emit-selection-plan",
+            plan_path.to_str().unwrap(),
+        ])
+        .success();
+    insta::assert_snapshot!(std::fs::read_to_string(&plan_path).unwrap(), @r#"
+    [[file]]
+    path = "file1"
+
+    [[file.hunks]]
+    old-start = 1
+    old-lines = 1
+    new-start = 1
+    new-lines = 1
+
+    [[file]]
+    path = "file3"
+    "#);
+    let recorded_tree = work_dir.run_jj(["debug", "tree", "-r", "@-"]).success();
+    insta::assert_snapshot!(get_log_with_summary(&work_dir), @r"
+    @  mzvwutvlkqwt target
+    │  M file1
+    │  M file2
+    ○  rlvkpnrzqnoo first
+    │  M file1
+    │  A file3
+    ○  qpvuntsmwlqt base
+    │  A file1
+    │  A file2
+    ◆  zzzzzzzzzzzz
+    [EOF]
+    ");
+
+    // Replaying the plan selects the same changes without the diff editor
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    std::fs::write(&diff_editor, "fail").unwrap();
+    work_dir
+        .run_jj([
+            "split",
+            "-m",
+            "first",
+            "--selection-plan",
+            plan_path.to_str().unwrap(),
+        ])
+        .success();
+    let replayed_tree = work_dir.run_jj(["debug", "tree", "-r", "@-"]).success();
+    assert_eq!(recorded_tree.stdout.raw(), replayed_tree.stdout.raw());
+
+    // The plan doesn't match the changes once the target is modified
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    work_dir.write_file("file1", "a\nb\nc\nd\nE\n");
+    work_dir.remove_file("file3");
+    let output = work_dir.run_jj([
+        "split",
+        "-m",
+        "first",
+        "--selection-plan",
+        plan_path.to_str().unwrap(),
+    ]);
+    insta::assert_snapshot!(output, @r"
+    ------- stderr -------
+    Error: The selection plan doesn't match the changes:
+      file1: no hunk -1,1 +1,1
+      file3: no changes
+    [EOF]
+    [exit status: 1]
+    ");
+
+    // Unknown fields are rejected
+    std::fs::write(&plan_path, "[[file]]\npath = 'file1'\nhunk = []\n").unwrap();
+    let output = work_dir.run_jj([
+        "split",
+        "-m",
+        "first",
+        "--selection-plan",
+        plan_path.to_str().unwrap(),
+    ]);
+    insta::assert_snapshot!(output.normalize_backslash(), @r"
+    ------- stderr -------
+    Error: Failed to read selection plan $TEST_ENV/plan.toml
+    Caused by: TOML parse error at line 3, column 1
+      |
+    3 | hunk = []
+      | ^^^^
+    unknown field `hunk`, expected `path` or `hunks`
+
+    [EOF]
+    [exit status: 1]
+    ");
+}
+
+#[test]
+fn test_split_selection_plan_edit_hunk() {
+    let mut test_env = TestEnvironment::default();
+    let diff_editor = test_env.set_up_fake_diff_editor();
+    let edit_script = test_env.set_up_fake_editor();
+    test_env.run_jj_in(".", ["git", "init", "repo"]).success();
+    let work_dir = test_env.work_dir("repo");
+    let plan_path = test_env.env_root().join("plan.toml");
+
+    work_dir.write_file("file1", "a\nb\nc\nd\ne\n");
+    work_dir.run_jj(["commit", "-m", "base"]).success();
+    work_dir.write_file("file1", "A\nb\nc\nd\nE\n");
+    work_dir.write_file("file2", "b\n");
+    work_dir.run_jj(["describe", "-m", "target"]).success();
+    let setup_opid = work_dir.current_operation_id();
+
+    // The new text of the first hunk is edited before it's selected
+    let plan = [
+        "[[file]]",
+        "path = 'file1'",
+        "hunks = [{ old-start = 1, old-lines = 1, new-start = 1, new-lines = 1, edit = true }]",
+    ];
+    std::fs::write(&plan_path, plan.join("\n")).unwrap();
+    std::fs::write(&edit_script, ["expect\nA\n", "write\nA1\n"].join("\0")).unwrap();
+    work_dir
+        .run_jj([
+            "split",
+            "-m",
+            "first",
+            "--selection-plan",
+            plan_path.to_str().unwrap(),
+        ])
+        .success();
+    let output = work_dir.run_jj(["file", "show", "-r", "@-", "file1"]);
+    insta::assert_snapshot!(output, @r"
+    A1
+    b
+    c
+    d
+    e
+    [EOF]
+    ");
+    let output = work_dir.run_jj(["diff", "--git", "-r", "@"]);
+    insta::assert_snapshot!(output, @r"
+    diff --git a/file1 b/file1
+    index 8c7098382b..084d8ddca4 100644
+    --- a/file1
+    +++ b/file1
+    @@ -1,5 +1,5 @@
+    -A1
+    +A
+     b
+     c
+     d
+    -e
+    +E
+    diff --git a/file2 b/file2
+    new file mode 100644
+    index 0000000000..6178079822
+    --- /dev/null
+    +++ b/file2
+    @@ -0,0 +1,1 @@
+    +b
+    [EOF]
+    ");
+
+    // Failure to edit the hunk aborts the split
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    std::fs::write(&edit_script, "fail").unwrap();
+    let output = work_dir.run_jj([
+        "split",
+        "-m",
+        "first",
+        "--selection-plan",
+        plan_path.to_str().unwrap(),
+    ]);
+    assert!(output
+        .stderr
+        .normalized()
+        .contains("Error: Failed to edit hunk -1,1 +1,1 of file1"));
+
+    // Files changed outside of the diff are reported
+    work_dir.run_jj(["op", "restore", &setup_opid]).success();
+    std::fs::write(&diff_editor, ["reset file2", "write file3\nc\n"].join("\0")).unwrap();
+    let output = work_dir.run_jj(["split", "-m", "first", "file1", "file2", "-i"]);
+    assert!(output
+        .stderr
+        .normalized()
+        .contains("Warning: The diff editor changed files outside of the diff:\n  file3\n"));
+}
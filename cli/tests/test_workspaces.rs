@@ -779,7 +779,7 @@ fn test_workspaces_current_op_discarded_by_other(automatic: bool) {
     ]);
     insta::allow_duplicates! {
         insta::assert_snapshot!(output, @r"
-        @  b0789def13 abandon commit de90575a14d8b9198dc0930f9de4a69f846ded36
+        @  89d7b91ed7 abandon commit de90575a14d8b9198dc0930f9de4a69f846ded36
         ○  778c9aae54 create initial working-copy commit in workspace secondary
         ○  219d4aca5c add workspace 'secondary'
         ○  31ad55e98c new empty commit
@@ -890,20 +890,20 @@ fn test_workspaces_current_op_discarded_by_other(automatic: bool) {
         insta::assert_snapshot!(output, @r"
         @  kmkuslsw test.user@example.com 2001-02-03 08:05:18 secondary@ 18851b39
         │  RECOVERY COMMIT FROM `jj workspace update-stale`
-        │  -- operation 90fc02cc90ab (2001-02-03 08:05:18) snapshot working copy
+        │  -- operation 64af45120933 (2001-02-03 08:05:18) snapshot working copy
         ○  kmkuslsw hidden test.user@example.com 2001-02-03 08:05:18 866928d1
            (empty) RECOVERY COMMIT FROM `jj workspace update-stale`
-           -- operation 83f707034db1 (2001-02-03 08:05:18) recovery commit
+           -- operation 193644fad812 (2001-02-03 08:05:18) recovery commit
         [EOF]
         ");
     } else {
         insta::assert_snapshot!(output, @r"
         @  kmkuslsw test.user@example.com 2001-02-03 08:05:18 secondary@ 18851b39
         │  RECOVERY COMMIT FROM `jj workspace update-stale`
-        │  -- operation 0f876590219e (2001-02-03 08:05:18) snapshot working copy
+        │  -- operation 184ccdb8e9d2 (2001-02-03 08:05:18) snapshot working copy
         ○  kmkuslsw hidden test.user@example.com 2001-02-03 08:05:18 866928d1
            (empty) RECOVERY COMMIT FROM `jj workspace update-stale`
-           -- operation 83f707034db1 (2001-02-03 08:05:18) recovery commit
+           -- operation 193644fad812 (2001-02-03 08:05:18) recovery commit
         [EOF]
         ");
     }
@@ -1262,7 +1262,7 @@ fn test_debug_snapshot() {
     work_dir.run_jj(["describe", "-m", "initial"]).success();
     let output = work_dir.run_jj(["op", "log"]);
     insta::assert_snapshot!(output, @r"
-    @  81e4a0f2e793 test-username@host.example.com 2001-02-03 04:05:10.000 +07:00 - 2001-02-03 04:05:10.000 +07:00
+    @  343191869ce6 test-username@host.example.com 2001-02-03 04:05:10.000 +07:00 - 2001-02-03 04:05:10.000 +07:00
     │  describe commit 006bd1130b84e90ab082adeabd7409270d5a86da
     │  args: jj describe -m initial
     ○  594dfebf2565 test-username@host.example.com 2001-02-03 04:05:08.000 +07:00 - 2001-02-03 04:05:08.000 +07:00
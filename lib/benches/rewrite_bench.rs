@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use criterion::BatchSize;
+use criterion::BenchmarkId;
+use criterion::Criterion;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use itertools::Itertools as _;
+use jj_lib::backend::CommitId;
+use jj_lib::op_store::RefTarget;
+use jj_lib::repo::ReadonlyRepo;
+use jj_lib::repo::Repo as _;
+use jj_lib::rewrite::RewriteRefsOptions;
+use testutils::TestRepo;
+
+/// Creates `count` commits with a bookmark on every 10th commit.
+fn setup_commits(test_repo: &TestRepo, count: usize) -> (Arc<ReadonlyRepo>, Vec<CommitId>) {
+    let repo = &test_repo.repo;
+    let root_id = repo.store().root_commit_id().clone();
+    let empty_tree_id = repo.store().empty_merged_tree_id();
+    let mut tx = repo.start_transaction();
+    let commit_ids = (0..count)
+        .map(|i| {
+            let commit = tx
+                .repo_mut()
+                .new_commit(vec![root_id.clone()], empty_tree_id.clone())
+                .set_description(format!("commit {i}"))
+                .write()
+                .unwrap();
+            commit.id().clone()
+        })
+        .collect_vec();
+    for i in (0..count).step_by(10) {
+        tx.repo_mut()
+            .set_local_bookmark_target(
+                format!("b{i}").as_ref(),
+                RefTarget::normal(commit_ids[i].clone()),
+            )
+            .unwrap();
+    }
+    (tx.commit("setup").unwrap(), commit_ids)
+}
+
+fn bench_update_rewritten_references(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_update_rewritten_references");
+    group.sample_size(10);
+    for count in [10_000, 50_000] {
+        let label = format!("{}k", count / 1000);
+        let test_repo = TestRepo::init();
+        let (repo, commit_ids) = setup_commits(&test_repo, count);
+        // Each commit but the last of each group of 10 is rewritten to the
+        // next commit.
+        group.bench_function(BenchmarkId::new("chains", &label), |b| {
+            b.iter_batched(
+                || {
+                    let mut tx = repo.start_transaction();
+                    for (i, old_id) in commit_ids.iter().enumerate() {
+                        if i % 10 != 9 {
+                            tx.repo_mut()
+                                .set_rewritten_commit(old_id.clone(), commit_ids[i + 1].clone());
+                        }
+                    }
+                    tx
+                },
+                |mut tx| {
+                    tx.repo_mut()
+                        .update_rewritten_references(&RewriteRefsOptions::default())
+                        .unwrap();
+                    tx
+                },
+                BatchSize::LargeInput,
+            );
+        });
+    }
+}
+
+criterion_group!(benches, bench_update_rewritten_references);
+criterion_main!(benches);
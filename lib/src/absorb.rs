@@ -17,35 +17,53 @@
 
 use std::cmp;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::ops::Range;
 use std::rc::Rc;
 
 use bstr::BString;
+use bstr::ByteSlice as _;
+use futures::Stream;
 use futures::StreamExt as _;
+use futures::TryStreamExt as _;
 use itertools::Itertools as _;
 use thiserror::Error;
 
 use crate::annotate::FileAnnotator;
 use crate::backend::BackendError;
 use crate::backend::BackendResult;
+use crate::backend::ChangeId;
 use crate::backend::CommitId;
+use crate::backend::CopyId;
 use crate::backend::TreeValue;
 use crate::commit::Commit;
 use crate::conflicts::materialized_diff_stream;
 use crate::conflicts::MaterializedFileValue;
+use crate::conflicts::MaterializedTreeDiffEntry;
 use crate::conflicts::MaterializedTreeValue;
 use crate::copies::CopyRecords;
 use crate::diff::Diff;
 use crate::diff::DiffHunkKind;
+use crate::hex_util::to_forward_hex;
+use crate::matchers::FilesMatcher;
 use crate::matchers::Matcher;
 use crate::merge::Merge;
 use crate::merged_tree::MergedTree;
 use crate::merged_tree::MergedTreeBuilder;
+use crate::object_id::ObjectId as _;
+use crate::op_store::OperationId;
 use crate::repo::MutableRepo;
 use crate::repo::Repo;
+use crate::repo_path::RepoPath;
 use crate::repo_path::RepoPathBuf;
 use crate::revset::ResolvedRevsetExpression;
 use crate::revset::RevsetEvaluationError;
+use crate::revset::RevsetIteratorExt as _;
+use crate::rewrite::CommitWithSelection;
+use crate::rewrite_policy::AffectedCommits;
+use crate::trailer::format_with_trailers;
+use crate::trailer::parse_description_trailers;
+use crate::trailer::Trailer;
 
 /// The source commit to absorb into its ancestry.
 #[derive(Clone, Debug)]
@@ -74,103 +92,304 @@ pub enum AbsorbError {
     /// Error resolving commit ancestry.
     #[error(transparent)]
     RevsetEvaluation(#[from] RevsetEvaluationError),
+    /// The plan was computed at a different operation.
+    #[error(
+        "Absorb plan was computed at operation {}, but the repo is at operation {}",
+        plan_operation_id.hex(),
+        repo_operation_id.hex()
+    )]
+    StalePlan {
+        /// Operation the plan was computed at.
+        plan_operation_id: OperationId,
+        /// Operation the repo is based on.
+        repo_operation_id: OperationId,
+    },
 }
 
-/// An absorb 'plan' indicating which commits should be modified and what they
-/// should be modified to.
-#[derive(Default)]
-pub struct SelectedTrees {
-    /// Commits to be modified, to be passed to `absorb_hunks`.
-    pub target_commits: HashMap<CommitId, MergedTreeBuilder>,
+/// An absorb plan indicating which hunks of the source commit would be moved
+/// to which destination commits. Use [`apply()`] to carry it out.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AbsorbPlan {
+    /// Operation the repo was loaded at when the plan was computed. The plan
+    /// can only be applied to a repo loaded at the same operation.
+    pub operation_id: OperationId,
+    /// Source commit to absorb from.
+    pub source_commit_id: CommitId,
+    /// Destination commits and the hunks to be moved into them, in reverse
+    /// topological order.
+    pub destinations: Vec<AbsorbDestination>,
     /// Paths that were not absorbed for various error reasons.
     pub skipped_paths: Vec<(RepoPathBuf, String)>,
 }
 
-/// Builds trees to be merged into destination commits by splitting source
-/// changes based on file annotation.
-pub async fn split_hunks_to_trees(
+impl AbsorbPlan {
+    /// Partitions the destination commits by the `is_immutable` predicate,
+    /// counting the hunks to be moved into each. This can be used to reject
+    /// or skip immutable destinations before applying the plan.
+    pub fn affected_commits<E>(
+        &self,
+        is_immutable: impl FnMut(&CommitId) -> Result<bool, E>,
+    ) -> Result<AffectedCommits, E> {
+        let targets = self.destinations.iter().map(|destination| {
+            let num_hunks = destination.files.iter().map(|file| file.hunks.len()).sum();
+            (destination.commit_id.clone(), num_hunks)
+        });
+        AffectedCommits::partition(targets, is_immutable)
+    }
+
+    /// Removes the destination commits not matching the `predicate`. The hunks
+    /// targeting them will be left in the source commit.
+    pub fn retain_destinations(&mut self, mut predicate: impl FnMut(&CommitId) -> bool) {
+        self.destinations
+            .retain(|destination| predicate(&destination.commit_id));
+    }
+}
+
+/// Hunks to be absorbed into a single destination commit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AbsorbDestination {
+    /// Destination commit.
+    pub commit_id: CommitId,
+    /// Files to be modified, sorted by path.
+    pub files: Vec<AbsorbFile>,
+}
+
+/// Hunks of a single file to be absorbed into a destination commit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AbsorbFile {
+    /// Path of the file.
+    pub path: RepoPathBuf,
+    /// Hunks to be moved, in file order.
+    pub hunks: Vec<AbsorbHunk>,
+}
+
+/// A hunk of the source commit to be absorbed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AbsorbHunk {
+    /// 0-based line range in the source parent to be replaced.
+    pub left_lines: Range<usize>,
+    /// 0-based line range in the source commit to be moved.
+    pub right_lines: Range<usize>,
+    /// Byte range in the source parent to be replaced.
+    pub left_range: Range<usize>,
+    /// Byte range in the source commit to be moved.
+    pub right_range: Range<usize>,
+    /// First changed line of the hunk, without the line terminator. The added
+    /// line is preferred over the removed one.
+    pub preview: BString,
+}
+
+impl AbsorbHunk {
+    fn new(left_text: &[u8], right_text: &[u8], (left_range, right_range): SelectedRange) -> Self {
+        let line_range = |text: &[u8], range: &Range<usize>| {
+            let start = text[..range.start].find_iter("\n").count();
+            start..start + text[range.clone()].lines_with_terminator().count()
+        };
+        let preview_text = if right_range.is_empty() {
+            &left_text[left_range.clone()]
+        } else {
+            &right_text[right_range.clone()]
+        };
+        let preview = preview_text.lines().next().unwrap_or_default().into();
+        AbsorbHunk {
+            left_lines: line_range(left_text, &left_range),
+            right_lines: line_range(right_text, &right_range),
+            left_range,
+            right_range,
+            preview,
+        }
+    }
+}
+
+/// Computes a plan to move changes in the source commit to the `destinations`
+/// based on file annotation.
+pub async fn plan(
     repo: &dyn Repo,
     source: &AbsorbSource,
     destinations: &Rc<ResolvedRevsetExpression>,
     matcher: &dyn Matcher,
-) -> Result<SelectedTrees, AbsorbError> {
-    let mut selected_trees = SelectedTrees::default();
-
-    let left_tree = &source.parent_tree;
-    let right_tree = source.commit.tree()?;
+) -> Result<AbsorbPlan, AbsorbError> {
+    let mut files_by_commit: HashMap<CommitId, Vec<AbsorbFile>> = HashMap::new();
+    let mut skipped_paths = Vec::new();
     // TODO: enable copy tracking if we add support for annotate and merge
     let copy_records = CopyRecords::default();
-    let tree_diff = left_tree.diff_stream_with_copies(&right_tree, matcher, &copy_records);
-    let mut diff_stream = materialized_diff_stream(repo.store(), tree_diff);
+    let mut diff_stream = materialized_source_diff(repo, source, matcher, &copy_records)?;
     while let Some(entry) = diff_stream.next().await {
-        let left_path = entry.path.source();
-        let right_path = entry.path.target();
-        let (left_value, right_value) = entry.values?;
-        let (left_text, executable, copy_id) = match to_file_value(left_value) {
-            Ok(Some(mut value)) => (
-                value.read_all(left_path).await?,
-                value.executable,
-                value.copy_id,
-            ),
+        let (path, file_diff) = match read_file_diff(entry).await? {
+            Ok(Some(file_diff)) => file_diff,
             // New file should have no destinations
             Ok(None) => continue,
-            Err(reason) => {
-                selected_trees
-                    .skipped_paths
-                    .push((left_path.to_owned(), reason));
-                continue;
-            }
-        };
-        let (right_text, deleted) = match to_file_value(right_value) {
-            Ok(Some(mut value)) => (value.read_all(right_path).await?, false),
-            Ok(None) => (vec![], true),
-            Err(reason) => {
-                selected_trees
-                    .skipped_paths
-                    .push((right_path.to_owned(), reason));
+            Err(skipped) => {
+                skipped_paths.push(skipped);
                 continue;
             }
         };
+        let FileDiff {
+            left_text,
+            right_text,
+            ..
+        } = &file_diff;
 
         // Compute annotation of parent (= left) content to map right hunks
         let mut annotator =
-            FileAnnotator::with_file_content(source.commit.id(), left_path, left_text.clone());
+            FileAnnotator::with_file_content(source.commit.id(), &path, left_text.clone());
         annotator.compute(repo, destinations)?;
         let annotation = annotator.to_annotation();
         let annotation_ranges = annotation
             .compact_line_ranges()
             .filter_map(|(commit_id, range)| Some((commit_id.ok()?, range)))
             .collect_vec();
-        let diff = Diff::by_line([&left_text, &right_text]);
+        let diff = Diff::by_line([left_text, right_text]);
         let selected_ranges = split_file_hunks(&annotation_ranges, &diff);
-        // Build trees containing parent (= left) contents + selected hunks
-        for (&commit_id, ranges) in &selected_ranges {
-            let tree_builder = selected_trees
-                .target_commits
+        for (commit_id, ranges) in selected_ranges {
+            let hunks = ranges
+                .into_iter()
+                .map(|ranges| AbsorbHunk::new(left_text, right_text, ranges))
+                .collect();
+            files_by_commit
                 .entry(commit_id.clone())
-                .or_insert_with(|| MergedTreeBuilder::new(left_tree.id().clone()));
-            let new_text = combine_texts(&left_text, &right_text, ranges);
+                .or_default()
+                .push(AbsorbFile {
+                    path: path.clone(),
+                    hunks,
+                });
+        }
+    }
+
+    let destinations = ResolvedRevsetExpression::commits(files_by_commit.keys().cloned().collect())
+        .evaluate(repo)?
+        .iter()
+        .map_ok(|commit_id| {
+            let files = files_by_commit.remove(&commit_id).unwrap();
+            AbsorbDestination { commit_id, files }
+        })
+        .try_collect()?;
+    Ok(AbsorbPlan {
+        operation_id: repo.base_repo().op_id().clone(),
+        source_commit_id: source.commit.id().clone(),
+        destinations,
+        skipped_paths,
+    })
+}
+
+/// Moves the hunks listed in the `plan` to the destination commits. Abandons
+/// the source commit if it becomes discardable.
+///
+/// Returns [`AbsorbError::StalePlan`] if the `repo` isn't based on the
+/// operation the plan was computed at.
+pub async fn apply(repo: &mut MutableRepo, plan: &AbsorbPlan) -> Result<AbsorbStats, AbsorbError> {
+    let base_operation_id = repo.base_repo().op_id();
+    if *base_operation_id != plan.operation_id {
+        return Err(AbsorbError::StalePlan {
+            plan_operation_id: plan.operation_id.clone(),
+            repo_operation_id: base_operation_id.clone(),
+        });
+    }
+    let source_commit = repo.store().get_commit(&plan.source_commit_id)?;
+    let source = AbsorbSource::from_commit(repo, source_commit)?;
+
+    let files_to_read: HashSet<&RepoPath> = plan
+        .destinations
+        .iter()
+        .flat_map(|destination| &destination.files)
+        .map(|file| file.path.as_ref())
+        .collect();
+    let matcher = FilesMatcher::new(files_to_read);
+    let copy_records = CopyRecords::default();
+    let file_diffs: HashMap<_, _> =
+        materialized_source_diff(repo, &source, &matcher, &copy_records)?
+            .then(read_file_diff)
+            .try_filter_map(|result| async { Ok(result.ok().flatten()) })
+            .try_collect()
+            .await?;
+
+    let mut selected_trees = HashMap::new();
+    for destination in &plan.destinations {
+        let mut tree_builder = MergedTreeBuilder::new(source.parent_tree.id());
+        for file in &destination.files {
+            // The plan was computed at the same operation, so the source diff
+            // can't have changed.
+            let file_diff = &file_diffs[&file.path];
+            let ranges = file
+                .hunks
+                .iter()
+                .map(|hunk| (hunk.left_range.clone(), hunk.right_range.clone()))
+                .collect_vec();
+            let new_text = combine_texts(&file_diff.left_text, &file_diff.right_text, &ranges);
             // Since changes to be absorbed are represented as diffs relative to
             // the source parent, we can propagate file deletion only if the
             // whole file content is deleted at a single destination commit.
-            let new_tree_value = if new_text.is_empty() && deleted {
+            let new_tree_value = if new_text.is_empty() && file_diff.deleted {
                 Merge::absent()
             } else {
                 let id = repo
                     .store()
-                    .write_file(left_path, &mut new_text.as_slice())
+                    .write_file(&file.path, &mut new_text.as_slice())
                     .await?;
                 Merge::normal(TreeValue::File {
                     id,
-                    executable,
-                    copy_id: copy_id.clone(),
+                    executable: file_diff.executable,
+                    copy_id: file_diff.copy_id.clone(),
                 })
             };
-            tree_builder.set_or_remove(left_path.to_owned(), new_tree_value);
+            tree_builder.set_or_remove(file.path.clone(), new_tree_value);
         }
+        selected_trees.insert(destination.commit_id.clone(), tree_builder);
     }
+    Ok(absorb_hunks(repo, &source, selected_trees)?)
+}
 
-    Ok(selected_trees)
+/// Contents of a file modified by the source commit.
+struct FileDiff {
+    left_text: Vec<u8>,
+    right_text: Vec<u8>,
+    executable: bool,
+    copy_id: CopyId,
+    deleted: bool,
+}
+
+fn materialized_source_diff<'a>(
+    repo: &'a dyn Repo,
+    source: &AbsorbSource,
+    matcher: &'a dyn Matcher,
+    copy_records: &'a CopyRecords,
+) -> BackendResult<impl Stream<Item = MaterializedTreeDiffEntry> + use<'a>> {
+    let left_tree = &source.parent_tree;
+    let right_tree = source.commit.tree()?;
+    let tree_diff = left_tree.diff_stream_with_copies(&right_tree, matcher, copy_records);
+    Ok(materialized_diff_stream(repo.store(), tree_diff))
+}
+
+/// Reads the file contents of a diff entry. Returns `Ok(None)` if the file is
+/// new, or the path and the reason if it can't be absorbed.
+async fn read_file_diff(
+    entry: MaterializedTreeDiffEntry,
+) -> BackendResult<Result<Option<(RepoPathBuf, FileDiff)>, (RepoPathBuf, String)>> {
+    let left_path = entry.path.source();
+    let right_path = entry.path.target();
+    let (left_value, right_value) = entry.values?;
+    let (left_text, executable, copy_id) = match to_file_value(left_value) {
+        Ok(Some(mut value)) => (
+            value.read_all(left_path).await?,
+            value.executable,
+            value.copy_id,
+        ),
+        Ok(None) => return Ok(Ok(None)),
+        Err(reason) => return Ok(Err((left_path.to_owned(), reason))),
+    };
+    let (right_text, deleted) = match to_file_value(right_value) {
+        Ok(Some(mut value)) => (value.read_all(right_path).await?, false),
+        Ok(None) => (vec![], true),
+        Err(reason) => return Ok(Err((right_path.to_owned(), reason))),
+    };
+    let file_diff = FileDiff {
+        left_text,
+        right_text,
+        executable,
+        copy_id,
+        deleted,
+    };
+    Ok(Ok(Some((left_path.to_owned(), file_diff))))
 }
 
 type SelectedRange = (Range<usize>, Range<usize>);
@@ -334,6 +553,126 @@ pub fn absorb_hunks(
     })
 }
 
+/// Trailer key marking a commit whose changes should eventually be squashed
+/// into another change. The value is the change id of that change, in the
+/// usual reverse-hex form.
+pub const FIXUP_OF_TRAILER_KEY: &str = "Fixup-Of";
+
+/// Returns the `description` with a trailer marking the commit as a fixup of
+/// the change `target`. The description is returned unchanged if it already
+/// has the trailer.
+pub fn add_fixup_trailer(description: &str, target: &ChangeId) -> String {
+    let trailer = Trailer {
+        key: FIXUP_OF_TRAILER_KEY.to_owned(),
+        value: target.reverse_hex(),
+    };
+    if parse_description_trailers(description).contains(&trailer) {
+        description.to_owned()
+    } else {
+        format_with_trailers(description, &[trailer])
+    }
+}
+
+/// A commit marked with a `Fixup-Of` trailer whose entire diff should be
+/// squashed into the target commit.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Fixup {
+    /// Commit marked as a fixup.
+    pub commit_id: CommitId,
+    /// Visible commit of the change named by the trailer.
+    pub target_commit_id: CommitId,
+}
+
+impl Fixup {
+    /// Selects all changes in the fixup commit, to be passed to
+    /// [`squash_commits()`](crate::rewrite::squash_commits) along with the
+    /// target commit.
+    pub fn squash_source(&self, repo: &dyn Repo) -> BackendResult<CommitWithSelection> {
+        let commit = repo.store().get_commit(&self.commit_id)?;
+        let selected_tree = commit.tree()?;
+        let parent_tree = commit.parent_tree(repo)?;
+        Ok(CommitWithSelection {
+            commit,
+            selected_tree,
+            parent_tree,
+        })
+    }
+}
+
+/// Fixup commits found by [`find_fixups()`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FixupPlan {
+    /// Operation the repo was loaded at when the plan was computed.
+    pub operation_id: OperationId,
+    /// Fixups to squash, in reverse topological order of the fixup commits.
+    pub fixups: Vec<Fixup>,
+    /// Marked commits that can't be squashed, with the reason.
+    pub skipped_commits: Vec<(CommitId, String)>,
+}
+
+/// Finds the commits in `candidates` that are marked with a `Fixup-Of`
+/// trailer, and resolves the change they should be squashed into.
+pub fn find_fixups(
+    repo: &dyn Repo,
+    candidates: &Rc<ResolvedRevsetExpression>,
+) -> Result<FixupPlan, AbsorbError> {
+    let mut fixups = Vec::new();
+    let mut skipped_commits = Vec::new();
+    for commit in candidates
+        .clone()
+        .evaluate(repo)?
+        .iter()
+        .commits(repo.store())
+    {
+        let commit = commit?;
+        let targets = parse_description_trailers(commit.description())
+            .into_iter()
+            .filter(|trailer| trailer.key == FIXUP_OF_TRAILER_KEY)
+            .map(|trailer| trailer.value)
+            .unique()
+            .collect_vec();
+        let target_commit_id = match &targets[..] {
+            [] => continue,
+            [target] => resolve_fixup_target(repo, &commit, target),
+            _ => Err(format!("Multiple {FIXUP_OF_TRAILER_KEY} trailers")),
+        };
+        match target_commit_id {
+            Ok(target_commit_id) => fixups.push(Fixup {
+                commit_id: commit.id().clone(),
+                target_commit_id,
+            }),
+            Err(reason) => skipped_commits.push((commit.id().clone(), reason)),
+        }
+    }
+    Ok(FixupPlan {
+        operation_id: repo.base_repo().op_id().clone(),
+        fixups,
+        skipped_commits,
+    })
+}
+
+fn resolve_fixup_target(
+    repo: &dyn Repo,
+    commit: &Commit,
+    target: &str,
+) -> Result<CommitId, String> {
+    let change_id = to_forward_hex(target)
+        .and_then(|hex| ChangeId::try_from_hex(&hex).ok())
+        .filter(|change_id| !change_id.as_bytes().is_empty())
+        .ok_or_else(|| format!("Invalid change id {target}"))?;
+    if &change_id == commit.change_id() {
+        return Err("Change is marked as a fixup of itself".to_owned());
+    }
+    match repo.resolve_change_id(&change_id).as_deref() {
+        None | Some([]) => Err(format!("Change {target} is not visible")),
+        Some([commit_id]) if commit_id == repo.store().root_commit_id() => {
+            Err("Cannot squash into the root commit".to_owned())
+        }
+        Some([commit_id]) => Ok(commit_id.clone()),
+        Some(_) => Err(format!("Change {target} is divergent")),
+    }
+}
+
 fn to_file_value(value: MaterializedTreeValue) -> Result<Option<MaterializedFileValue>, String> {
     match value {
         MaterializedTreeValue::Absent => Ok(None), // New or deleted file
@@ -154,6 +154,21 @@ impl FileAnnotator {
         Self::with_source(starting_commit_id, file_path, source)
     }
 
+    /// Restricts the annotation to the lines in `line_range` of the file at
+    /// the starting commit.
+    ///
+    /// The origins of the other lines aren't searched for, so the ancestors
+    /// can be visited only until all lines in the range are resolved. The
+    /// other lines are reported as unresolved at the starting commit.
+    pub fn with_line_range(mut self, line_range: Range<usize>) -> Self {
+        for source in self.state.commit_source_map.values_mut() {
+            source
+                .line_map
+                .retain(|(_, original)| line_range.contains(original));
+        }
+        self
+    }
+
     fn with_source(
         starting_commit_id: &CommitId,
         file_path: &RepoPath,
@@ -183,7 +198,19 @@ impl FileAnnotator {
         repo: &dyn Repo,
         domain: &Rc<ResolvedRevsetExpression>,
     ) -> Result<(), RevsetEvaluationError> {
-        process_commits(repo, &mut self.state, domain, &self.file_path)
+        let mut session = AnnotationSession::uncached();
+        process_commits(repo, &mut self.state, domain, &self.file_path, &mut session)
+    }
+
+    /// Like [`Self::compute()`], but reuses the file contents and line
+    /// mappings cached in the `session` by previous annotations.
+    pub fn compute_in_session(
+        &mut self,
+        repo: &dyn Repo,
+        domain: &Rc<ResolvedRevsetExpression>,
+        session: &mut AnnotationSession,
+    ) -> Result<(), RevsetEvaluationError> {
+        process_commits(repo, &mut self.state, domain, &self.file_path, session)
     }
 
     /// Remaining commit ids to visit from.
@@ -203,6 +230,104 @@ impl FileAnnotator {
     }
 }
 
+/// Cache shared by consecutive annotations, e.g. of the line ranges of a file
+/// which are visible as the user scrolls through it.
+///
+/// The file contents at the visited commits and the lines the contents have in
+/// common with the parent commits are cached by commit and path, so the
+/// history walked by previous annotations doesn't have to be read and diffed
+/// again.
+#[derive(Debug)]
+pub struct AnnotationSession {
+    caching: bool,
+    texts: HashMap<(CommitId, RepoPathBuf), BString>,
+    /// `(current_start, parent_start, count)` ranges of lines in common, keyed
+    /// by `(current_commit, parent_commit, path)`.
+    same_lines: HashMap<(CommitId, CommitId, RepoPathBuf), Vec<(usize, usize, usize)>>,
+    num_loaded_files: usize,
+}
+
+impl AnnotationSession {
+    /// Creates an empty session.
+    pub fn new() -> Self {
+        AnnotationSession {
+            caching: true,
+            texts: HashMap::new(),
+            same_lines: HashMap::new(),
+            num_loaded_files: 0,
+        }
+    }
+
+    /// Session for a single annotation, which doesn't keep anything.
+    fn uncached() -> Self {
+        AnnotationSession {
+            caching: false,
+            ..Self::new()
+        }
+    }
+
+    /// Number of file contents loaded from the store by the annotations in
+    /// this session.
+    pub fn num_loaded_files(&self) -> usize {
+        self.num_loaded_files
+    }
+
+    fn load_source(
+        &mut self,
+        repo: &dyn Repo,
+        commit_id: &CommitId,
+        file_path: &RepoPath,
+    ) -> BackendResult<Source> {
+        let key = (commit_id.clone(), file_path.to_owned());
+        if let Some(text) = self.texts.get(&key) {
+            return Ok(Source::new(text.clone()));
+        }
+        let commit = repo.store().get_commit(commit_id)?;
+        let source = Source::load(&commit, file_path)?;
+        self.num_loaded_files += 1;
+        if self.caching {
+            self.texts.insert(key, source.text.clone());
+        }
+        Ok(source)
+    }
+
+    fn same_lines(
+        &mut self,
+        current_commit_id: &CommitId,
+        parent_commit_id: &CommitId,
+        file_path: &RepoPath,
+        current_text: &[u8],
+        parent_text: &[u8],
+    ) -> Vec<(usize, usize, usize)> {
+        let key = (
+            current_commit_id.clone(),
+            parent_commit_id.clone(),
+            file_path.to_owned(),
+        );
+        if let Some(same_lines) = self.same_lines.get(&key) {
+            return same_lines.clone();
+        }
+        let mut same_lines = Vec::new();
+        copy_same_lines_with(
+            current_text,
+            parent_text,
+            |current_start, parent_start, count| {
+                same_lines.push((current_start, parent_start, count));
+            },
+        );
+        if self.caching {
+            self.same_lines.insert(key, same_lines.clone());
+        }
+        same_lines
+    }
+}
+
+impl Default for AnnotationSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Intermediate state of file annotation.
 #[derive(Clone, Debug)]
 struct AnnotationState {
@@ -254,6 +379,7 @@ fn process_commits(
     state: &mut AnnotationState,
     domain: &Rc<ResolvedRevsetExpression>,
     file_name: &RepoPath,
+    session: &mut AnnotationSession,
 ) -> Result<(), RevsetEvaluationError> {
     let predicate = RevsetFilterPredicate::File(FilesetExpression::file_path(file_name.to_owned()));
     // TODO: If the domain isn't a contiguous range, changes masked out by it
@@ -269,7 +395,7 @@ fn process_commits(
     state.num_unresolved_roots = 0;
     for node in revset.iter_graph() {
         let (commit_id, edge_list) = node?;
-        process_commit(repo, file_name, state, &commit_id, &edge_list)?;
+        process_commit(repo, file_name, state, session, &commit_id, &edge_list)?;
         if state.commit_source_map.len() == state.num_unresolved_roots {
             // No more lines to propagate to ancestors.
             break;
@@ -285,6 +411,7 @@ fn process_commit(
     repo: &dyn Repo,
     file_name: &RepoPath,
     state: &mut AnnotationState,
+    session: &mut AnnotationSession,
     current_commit_id: &CommitId,
     edges: &[GraphEdge<CommitId>],
 ) -> Result<(), BackendError> {
@@ -297,8 +424,7 @@ fn process_commit(
         let parent_source = match state.commit_source_map.entry(parent_commit_id.clone()) {
             hash_map::Entry::Occupied(entry) => entry.into_mut(),
             hash_map::Entry::Vacant(entry) => {
-                let commit = repo.store().get_commit(entry.key())?;
-                entry.insert(Source::load(&commit, file_name)?)
+                entry.insert(session.load_source(repo, parent_commit_id, file_name)?)
             }
         };
 
@@ -313,20 +439,23 @@ fn process_commit(
         let mut current_lines = current_source.line_map.iter().copied().peekable();
         let mut new_current_line_map = Vec::new();
         let mut new_parent_line_map = Vec::new();
-        copy_same_lines_with(
+        let same_lines = session.same_lines(
+            current_commit_id,
+            parent_commit_id,
+            file_name,
             &current_source.text,
             &parent_source.text,
-            |current_start, parent_start, count| {
-                new_current_line_map
-                    .extend(current_lines.peeking_take_while(|&(cur, _)| cur < current_start));
-                while let Some((current, original)) =
-                    current_lines.next_if(|&(cur, _)| cur < current_start + count)
-                {
-                    let parent = parent_start + (current - current_start);
-                    new_parent_line_map.push((parent, original));
-                }
-            },
         );
+        for (current_start, parent_start, count) in same_lines {
+            new_current_line_map
+                .extend(current_lines.peeking_take_while(|&(cur, _)| cur < current_start));
+            while let Some((current, original)) =
+                current_lines.next_if(|&(cur, _)| cur < current_start + count)
+            {
+                let parent = parent_start + (current - current_start);
+                new_parent_line_map.push((parent, original));
+            }
+        }
         new_current_line_map.extend(current_lines);
         current_source.line_map = new_current_line_map;
         parent_source.line_map = if parent_source.line_map.is_empty() {
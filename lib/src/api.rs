@@ -0,0 +1,181 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Curated entry points for programs embedding `jj_lib`.
+//!
+//! The other modules in this crate are organized around the implementation,
+//! and items are moved between them as the implementation evolves. This module
+//! re-exports a small set of items which cover the common tasks of loading a
+//! repo, creating and rewriting commits, evaluating revsets, and updating
+//! refs. Moving an item out of this module is treated as a breaking change.
+//!
+//! Rewriting descendants is done through [`MutableRepo::rebase_descendants()`]
+//! and [`MutableRepo::transform_descendants()`], which are methods on the
+//! re-exported [`MutableRepo`].
+//!
+//! Deliberately not re-exported are the extension points (backends, op stores,
+//! index stores, working-copy implementations), the revset and fileset
+//! parsers' internals, and the protobuf types. Those are still available from
+//! their own modules but change more often.
+//!
+//! # Examples
+//!
+//! Create a repo, add a commit, point a bookmark at it, and publish the
+//! operation:
+//!
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use jj_lib::api::ConfigLayer;
+//! use jj_lib::api::ConfigSource;
+//! use jj_lib::api::RefName;
+//! use jj_lib::api::RefTarget;
+//! use jj_lib::api::Repo as _;
+//! use jj_lib::api::StackedConfig;
+//! use jj_lib::api::UserSettings;
+//! use jj_lib::api::Workspace;
+//!
+//! # let temp_dir = tempfile::tempdir()?;
+//! # let workspace_root = temp_dir.path();
+//! let mut config = StackedConfig::with_defaults();
+//! config.add_layer(ConfigLayer::parse(
+//!     ConfigSource::User,
+//!     r#"
+//!     user.name = "Test User"
+//!     user.email = "test.user@example.com"
+//!     "#,
+//! )?);
+//! let settings = UserSettings::from_config(config)?;
+//! let (_workspace, repo) = Workspace::init_simple(&settings, workspace_root)?;
+//!
+//! let mut tx = repo.start_transaction();
+//! let commit = tx
+//!     .repo_mut()
+//!     .new_commit(
+//!         vec![repo.store().root_commit_id().clone()],
+//!         repo.store().empty_merged_tree_id(),
+//!     )
+//!     .set_description("initial commit")
+//!     .write()?;
+//! let bookmark = RefName::new("main");
+//! tx.repo_mut()
+//!     .set_local_bookmark_target(bookmark, RefTarget::normal(commit.id().clone()))?;
+//! let repo = tx.commit("create main bookmark")?;
+//!
+//! assert_eq!(
+//!     repo.view().get_local_bookmark(bookmark).as_normal(),
+//!     Some(commit.id())
+//! );
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Rewrite a commit and rebase its descendants onto the new version:
+//!
+//! ```
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! use jj_lib::api::Repo as _;
+//! use jj_lib::api::RevsetExpression;
+//! use jj_lib::api::RevsetIteratorExt as _;
+//! use jj_lib::api::Workspace;
+//!
+//! # let temp_dir = tempfile::tempdir()?;
+//! # let config = jj_lib::api::StackedConfig::with_defaults();
+//! # let settings = jj_lib::api::UserSettings::from_config(config)?;
+//! let (_workspace, repo) = Workspace::init_simple(&settings, temp_dir.path())?;
+//!
+//! let mut tx = repo.start_transaction();
+//! let empty_tree_id = repo.store().empty_merged_tree_id();
+//! let root_id = repo.store().root_commit_id().clone();
+//! let parent = tx
+//!     .repo_mut()
+//!     .new_commit(vec![root_id], empty_tree_id.clone())
+//!     .set_description("parent")
+//!     .write()?;
+//! let child = tx
+//!     .repo_mut()
+//!     .new_commit(vec![parent.id().clone()], empty_tree_id)
+//!     .set_description("child")
+//!     .write()?;
+//! let repo = tx.commit("create commits")?;
+//!
+//! let mut tx = repo.start_transaction();
+//! let new_parent = tx
+//!     .repo_mut()
+//!     .rewrite_commit(&parent)
+//!     .set_description("new parent")
+//!     .write()?;
+//! let num_rebased = tx.repo_mut().rebase_descendants()?;
+//! assert_eq!(num_rebased, 1);
+//! let repo = tx.commit("reword parent")?;
+//!
+//! // The rebased child is the only child of the new parent
+//! let children: Vec<_> = RevsetExpression::commit(new_parent.id().clone())
+//!     .children()
+//!     .evaluate(repo.as_ref())?
+//!     .iter()
+//!     .commits(repo.store())
+//!     .collect::<Result<_, _>>()?;
+//! assert_eq!(children.len(), 1);
+//! assert_eq!(children[0].change_id(), child.change_id());
+//! # Ok(())
+//! # }
+//! ```
+
+pub use crate::backend::BackendError;
+pub use crate::backend::BackendResult;
+pub use crate::backend::ChangeId;
+pub use crate::backend::CommitId;
+pub use crate::backend::MergedTreeId;
+pub use crate::commit::Commit;
+pub use crate::commit_builder::CommitBuilder;
+pub use crate::config::ConfigLayer;
+pub use crate::config::ConfigSource;
+pub use crate::config::StackedConfig;
+pub use crate::merged_tree::MergedTree;
+pub use crate::merged_tree::MergedTreeBuilder;
+pub use crate::object_id::ObjectId;
+pub use crate::op_store::OperationId;
+pub use crate::op_store::RefTarget;
+pub use crate::operation::Operation;
+pub use crate::ref_name::RefName;
+pub use crate::ref_name::RefNameBuf;
+pub use crate::ref_name::RemoteName;
+pub use crate::ref_name::WorkspaceName;
+pub use crate::ref_name::WorkspaceNameBuf;
+pub use crate::repo::MutableRepo;
+pub use crate::repo::ReadonlyRepo;
+pub use crate::repo::RefUpdateError;
+pub use crate::repo::Repo;
+pub use crate::repo::RepoLoader;
+pub use crate::repo::RepoLoaderError;
+pub use crate::repo::StoreFactories;
+pub use crate::repo::StoreLoadError;
+pub use crate::repo_path::RepoPath;
+pub use crate::repo_path::RepoPathBuf;
+pub use crate::revset::Revset;
+pub use crate::revset::RevsetEvaluationError;
+pub use crate::revset::RevsetExpression;
+pub use crate::revset::RevsetIteratorExt;
+pub use crate::rewrite::CommitRewriter;
+pub use crate::rewrite::EmptyBehaviour;
+pub use crate::rewrite::RebaseOptions;
+pub use crate::rewrite::RebasedCommit;
+pub use crate::settings::UserSettings;
+pub use crate::store::Store;
+pub use crate::transaction::Transaction;
+pub use crate::transaction::TransactionCommitError;
+pub use crate::workspace::default_working_copy_factories;
+pub use crate::workspace::Workspace;
+pub use crate::workspace::WorkspaceInitError;
+pub use crate::workspace::WorkspaceLoadError;
@@ -291,10 +291,31 @@ pub enum BackendError {
     /// the particular backend.
     #[error("{0}")]
     Unsupported(String),
+    /// An operation failed for a reason that may go away if it's retried, such
+    /// as a timeout or a dropped connection to a remote backend.
+    #[error(transparent)]
+    Transient(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl BackendError {
+    /// Returns true if retrying the failed operation may succeed.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, BackendError::Transient(_))
+    }
 }
 
 pub type BackendResult<T> = Result<T, BackendError>;
 
+/// Storage used by a commit backend, as reported by
+/// [`Backend::storage_stats()`]. Fields the backend can't compute are `None`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct StorageStats {
+    /// Number of stored objects.
+    pub num_objects: Option<u64>,
+    /// Size of the stored objects in bytes.
+    pub size_bytes: Option<u64>,
+}
+
 #[derive(ContentHash, Debug, PartialEq, Eq, Clone, Hash)]
 pub enum TreeValue {
     // TODO: When there's a CopyId here, the copy object's path must match
@@ -464,6 +485,16 @@ pub trait Backend: Send + Sync + Debug {
         id: &FileId,
     ) -> BackendResult<Pin<Box<dyn AsyncRead>>>;
 
+    /// Hints that the given files are about to be read, so that the backend
+    /// can fetch them in bulk ahead of the individual `read_file()` calls.
+    ///
+    /// This is only an optimization. Backends that read from local storage,
+    /// like the Git backend, can ignore it, which is what the default
+    /// implementation does.
+    async fn prefetch_files(&self, _files: &[(RepoPathBuf, FileId)]) -> BackendResult<()> {
+        Ok(())
+    }
+
     async fn write_file(
         &self,
         path: &RepoPath,
@@ -528,6 +559,42 @@ pub trait Backend: Send + Sync + Debug {
         sign_with: Option<&mut SigningFn>,
     ) -> BackendResult<(CommitId, Commit)>;
 
+    /// Computes the ID `write_commit()` would assign to the commit without
+    /// making the commit readable.
+    ///
+    /// Backends that can do this cheaply may return `Some`, which allows the
+    /// store to queue commit writes and send them to `write_commits_batch()`
+    /// later. Such backends may adjust the `contents` the way `write_commit()`
+    /// would (e.g. truncate timestamps), and must then store them unmodified.
+    /// They may write data which isn't referenced until the commit is written,
+    /// such as GC-able objects. The default implementation returns `None`,
+    /// meaning commits are always written immediately.
+    fn compute_commit_id(&self, _contents: &mut Commit) -> Option<CommitId> {
+        None
+    }
+
+    /// Writes commits previously queued by the store. Each commit's ID was
+    /// computed by `compute_commit_id()`, and commits are in the order they
+    /// were created, so parents precede their children.
+    ///
+    /// The default implementation writes the commits one by one.
+    async fn write_commits_batch(&self, commits: Vec<(CommitId, Commit)>) -> BackendResult<()> {
+        for (id, contents) in commits {
+            let (written_id, _) = self.write_commit(contents, None).await?;
+            if written_id != id {
+                return Err(BackendError::Other(
+                    format!(
+                        "Queued commit {id} was written as {written_id}",
+                        id = id.hex(),
+                        written_id = written_id.hex()
+                    )
+                    .into(),
+                ));
+            }
+        }
+        Ok(())
+    }
+
     /// Get copy records for the dag range `root..head`.  If `paths` is None
     /// include all paths, otherwise restrict to only `paths`.
     ///
@@ -547,6 +614,22 @@ pub trait Backend: Send + Sync + Debug {
         head: &CommitId,
     ) -> BackendResult<BoxStream<BackendResult<CopyRecord>>>;
 
+    /// Records the copies described by `records`, so they're returned by
+    /// `get_copy_records()` for ranges including their `target_commit`.
+    ///
+    /// This is used to carry over copy records when a commit is rewritten.
+    /// Backends that detect copies from the trees, like the Git backend, don't
+    /// store copy records and ignore them.
+    fn write_copy_records(&self, _records: &[CopyRecord]) -> BackendResult<()> {
+        Ok(())
+    }
+
+    /// Returns statistics about the storage used by the backend, or `None` if
+    /// the backend can't report them. This may be expensive.
+    fn storage_stats(&self) -> BackendResult<Option<StorageStats>> {
+        Ok(None)
+    }
+
     /// Perform garbage collection.
     ///
     /// All commits found in the `index` won't be removed. In addition to that,
@@ -0,0 +1,806 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Conformance tests for implementations of the storage traits.
+//!
+//! Implementations of [`Backend`], [`OpStore`], and [`OpHeadsStore`] outside
+//! of this crate can run the same checks as the built-in implementations by
+//! calling [`conformance_tests()`], [`op_store_conformance_tests()`], or
+//! [`op_heads_store_conformance_tests()`] from their own tests. Each check
+//! gets a fresh instance from the given factory function, so the factory
+//! should create the instance in a new empty location every time.
+//!
+//! The checks don't panic on failure. They return a [`ConformanceReport`],
+//! which can be inspected or turned into a test failure by
+//! [`ConformanceReport::assert_success()`]:
+//!
+//! ```ignore
+//! #[test]
+//! fn test_my_backend_conformance() {
+//!     let temp_dir = tempfile::tempdir().unwrap();
+//!     let counter = std::cell::Cell::new(0);
+//!     let report = conformance_tests(|| {
+//!         counter.set(counter.get() + 1);
+//!         let path = temp_dir.path().join(counter.get().to_string());
+//!         Box::new(MyBackend::init(&path))
+//!     });
+//!     report.assert_success();
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+use std::slice;
+use std::thread;
+
+use futures::TryStreamExt as _;
+use pollster::FutureExt as _;
+use tokio::io::AsyncReadExt as _;
+
+use crate::backend::Backend;
+use crate::backend::BackendError;
+use crate::backend::ChangeId;
+use crate::backend::Commit;
+use crate::backend::CommitId;
+use crate::backend::CopyHistory;
+use crate::backend::CopyId;
+use crate::backend::FileId;
+use crate::backend::MergedTreeId;
+use crate::backend::MillisSinceEpoch;
+use crate::backend::Signature;
+use crate::backend::SymlinkId;
+use crate::backend::Timestamp;
+use crate::backend::Tree;
+use crate::backend::TreeId;
+use crate::backend::TreeValue;
+use crate::object_id::HexPrefix;
+use crate::object_id::ObjectId as _;
+use crate::object_id::PrefixResolution;
+use crate::op_heads_store::OpHeadsStore;
+use crate::op_store::CommitRewrites;
+use crate::op_store::OpStore;
+use crate::op_store::OpStoreError;
+use crate::op_store::Operation;
+use crate::op_store::OperationId;
+use crate::op_store::OperationMetadata;
+use crate::op_store::RefTarget;
+use crate::op_store::View;
+use crate::op_store::ViewId;
+use crate::repo_path::RepoPath;
+use crate::repo_path::RepoPathBuf;
+use crate::repo_path::RepoPathComponentBuf;
+
+/// Number of threads used by the checks for concurrent writes.
+const NUM_CONCURRENT_WRITERS: usize = 8;
+
+/// Result of a single conformance check.
+#[derive(Clone, Debug)]
+pub struct ConformanceCheck {
+    /// Name of the check.
+    pub name: &'static str,
+    /// Description of the failure, or `None` if the check passed.
+    pub failure: Option<String>,
+}
+
+/// Results of a conformance test suite.
+#[derive(Clone, Debug, Default)]
+pub struct ConformanceReport {
+    checks: Vec<ConformanceCheck>,
+}
+
+impl ConformanceReport {
+    /// All checks that were run, in order.
+    pub fn checks(&self) -> &[ConformanceCheck] {
+        &self.checks
+    }
+
+    /// Checks that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &ConformanceCheck> {
+        self.checks.iter().filter(|check| check.failure.is_some())
+    }
+
+    /// Whether all checks passed.
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    /// Panics with a description of the failed checks if any check failed.
+    #[track_caller]
+    pub fn assert_success(&self) {
+        assert!(self.is_success(), "{self}");
+    }
+
+    fn run<T>(
+        &mut self,
+        name: &'static str,
+        instance: T,
+        check: impl FnOnce(T) -> Result<(), CheckFailure>,
+    ) {
+        let failure = match panic::catch_unwind(AssertUnwindSafe(|| check(instance))) {
+            Ok(Ok(())) => None,
+            Ok(Err(CheckFailure(message))) => Some(message),
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Some(format!("panicked: {message}"))
+            }
+        };
+        self.checks.push(ConformanceCheck { name, failure });
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let num_failures = self.failures().count();
+        writeln!(
+            f,
+            "{num_failures} of {} conformance checks failed",
+            self.checks.len()
+        )?;
+        for check in self.failures() {
+            writeln!(f, "  {}: {}", check.name, check.failure.as_ref().unwrap())?;
+        }
+        Ok(())
+    }
+}
+
+/// Reason for a failed check. Any error returned by the implementation under
+/// test fails the check.
+#[derive(Debug)]
+struct CheckFailure(String);
+
+impl<E: std::error::Error> From<E> for CheckFailure {
+    fn from(err: E) -> Self {
+        CheckFailure(format!("unexpected error: {err}"))
+    }
+}
+
+fn ensure(condition: bool, message: impl FnOnce() -> String) -> Result<(), CheckFailure> {
+    if condition {
+        Ok(())
+    } else {
+        Err(CheckFailure(message()))
+    }
+}
+
+/// Runs the conformance checks for a [`Backend`] implementation.
+pub fn conformance_tests(factory: impl Fn() -> Box<dyn Backend>) -> ConformanceReport {
+    let mut report = ConformanceReport::default();
+    report.run("root_commit", factory(), check_root_commit);
+    report.run("file_round_trip", factory(), check_file_round_trip);
+    report.run("symlink_round_trip", factory(), check_symlink_round_trip);
+    report.run("tree_round_trip", factory(), check_tree_round_trip);
+    report.run("commit_round_trip", factory(), check_commit_round_trip);
+    report.run("concurrent_writes", factory(), check_concurrent_writes);
+    report.run("copy_histories", factory(), check_copy_histories);
+    report.run("copy_records", factory(), check_copy_records);
+    report.run("missing_objects", factory(), check_missing_objects);
+    report
+}
+
+fn test_path(value: &str) -> RepoPathBuf {
+    RepoPathBuf::from_internal_string(value).unwrap()
+}
+
+fn test_signature() -> Signature {
+    Signature {
+        name: "Test User".to_owned(),
+        email: "test.user@example.com".to_owned(),
+        timestamp: Timestamp {
+            timestamp: MillisSinceEpoch(1_000_000),
+            tz_offset: 60,
+        },
+    }
+}
+
+fn test_commit(backend: &dyn Backend, tree_id: TreeId, seed: u8, description: &str) -> Commit {
+    Commit {
+        parents: vec![backend.root_commit_id().clone()],
+        predecessors: vec![],
+        root_tree: MergedTreeId::resolved(tree_id),
+        change_id: ChangeId::new(vec![seed; backend.change_id_length()]),
+        description: description.to_owned(),
+        author: test_signature(),
+        committer: test_signature(),
+        secure_sig: None,
+    }
+}
+
+fn write_file(
+    backend: &dyn Backend,
+    path: &RepoPath,
+    contents: &[u8],
+) -> Result<FileId, CheckFailure> {
+    Ok(backend.write_file(path, &mut &*contents).block_on()?)
+}
+
+fn read_file(backend: &dyn Backend, path: &RepoPath, id: &FileId) -> Result<Vec<u8>, CheckFailure> {
+    let mut reader = backend.read_file(path, id).block_on()?;
+    let mut contents = vec![];
+    reader.read_to_end(&mut contents).block_on()?;
+    Ok(contents)
+}
+
+/// Writes a tree with a file at `path` and returns its id.
+fn write_single_file_tree(
+    backend: &dyn Backend,
+    path: &str,
+    contents: &[u8],
+) -> Result<TreeId, CheckFailure> {
+    let file_id = write_file(backend, &test_path(path), contents)?;
+    let mut tree = Tree::default();
+    tree.set(
+        RepoPathComponentBuf::new(path).unwrap(),
+        TreeValue::File {
+            id: file_id,
+            executable: false,
+            copy_id: CopyId::placeholder(),
+        },
+    );
+    Ok(backend.write_tree(RepoPath::root(), &tree).block_on()?)
+}
+
+fn check_root_commit(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    let root_commit_id = backend.root_commit_id();
+    ensure(
+        root_commit_id.as_bytes().len() == backend.commit_id_length(),
+        || "root commit id doesn't have the commit id length".to_owned(),
+    )?;
+    ensure(
+        backend.root_change_id().as_bytes().len() == backend.change_id_length(),
+        || "root change id doesn't have the change id length".to_owned(),
+    )?;
+    let root_commit = backend.read_commit(root_commit_id).block_on()?;
+    ensure(root_commit.parents.is_empty(), || {
+        format!("root commit has parents {:?}", root_commit.parents)
+    })?;
+    ensure(root_commit.change_id == *backend.root_change_id(), || {
+        format!("root commit has change id {}", root_commit.change_id)
+    })?;
+    let empty_tree_id = MergedTreeId::resolved(backend.empty_tree_id().clone());
+    ensure(root_commit.root_tree == empty_tree_id, || {
+        format!("root commit has tree {:?}", root_commit.root_tree)
+    })?;
+    let empty_tree = backend
+        .read_tree(RepoPath::root(), backend.empty_tree_id())
+        .block_on()?;
+    ensure(empty_tree.is_empty(), || {
+        format!("empty tree has entries {empty_tree:?}")
+    })?;
+    let written_id = backend
+        .write_tree(RepoPath::root(), &Tree::default())
+        .block_on()?;
+    ensure(written_id == *backend.empty_tree_id(), || {
+        format!("empty tree was written as {written_id}")
+    })
+}
+
+fn check_file_round_trip(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    let path = test_path("dir/file");
+    let binary_contents: Vec<u8> = (0..=255).collect();
+    for contents in [&b""[..], b"contents\n", b"no newline", &binary_contents] {
+        let id = write_file(backend.as_ref(), &path, contents)?;
+        let read_contents = read_file(backend.as_ref(), &path, &id)?;
+        ensure(read_contents == contents, || {
+            format!("file {id} was read back as {read_contents:?}, expected {contents:?}")
+        })?;
+    }
+    Ok(())
+}
+
+fn check_symlink_round_trip(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    let path = test_path("dir/link");
+    for target in ["target", "../other/target", "/absolute/target"] {
+        let id = backend.write_symlink(&path, target).block_on()?;
+        let read_target = backend.read_symlink(&path, &id).block_on()?;
+        ensure(read_target == target, || {
+            format!("symlink {id} was read back as {read_target:?}, expected {target:?}")
+        })?;
+    }
+    Ok(())
+}
+
+fn check_tree_round_trip(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    let file_id = write_file(backend.as_ref(), &test_path("dir/file"), b"contents\n")?;
+    let mut subtree = Tree::default();
+    subtree.set(
+        RepoPathComponentBuf::new("file").unwrap(),
+        TreeValue::File {
+            id: file_id.clone(),
+            executable: false,
+            copy_id: CopyId::placeholder(),
+        },
+    );
+    let subtree_path = test_path("dir");
+    let subtree_id = backend.write_tree(&subtree_path, &subtree).block_on()?;
+
+    let symlink_id = backend
+        .write_symlink(&test_path("link"), "dir/file")
+        .block_on()?;
+    let mut tree = Tree::default();
+    tree.set(
+        RepoPathComponentBuf::new("dir").unwrap(),
+        TreeValue::Tree(subtree_id.clone()),
+    );
+    tree.set(
+        RepoPathComponentBuf::new("exec").unwrap(),
+        TreeValue::File {
+            id: file_id,
+            executable: true,
+            copy_id: CopyId::placeholder(),
+        },
+    );
+    tree.set(
+        RepoPathComponentBuf::new("link").unwrap(),
+        TreeValue::Symlink(symlink_id),
+    );
+    let tree_id = backend.write_tree(RepoPath::root(), &tree).block_on()?;
+
+    let read_subtree = backend.read_tree(&subtree_path, &subtree_id).block_on()?;
+    ensure(read_subtree == subtree, || {
+        format!("tree {subtree_id} was read back as {read_subtree:?}, expected {subtree:?}")
+    })?;
+    let read_tree = backend.read_tree(RepoPath::root(), &tree_id).block_on()?;
+    ensure(read_tree == tree, || {
+        format!("tree {tree_id} was read back as {read_tree:?}, expected {tree:?}")
+    })
+}
+
+fn check_commit_round_trip(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    let tree_id = write_single_file_tree(backend.as_ref(), "file", b"contents\n")?;
+    let commit = test_commit(backend.as_ref(), tree_id.clone(), 1, "first\n");
+    let (id, written_commit) = backend.write_commit(commit, None).block_on()?;
+    ensure(id.as_bytes().len() == backend.commit_id_length(), || {
+        format!("commit {id} doesn't have the commit id length")
+    })?;
+    let read_commit = backend.read_commit(&id).block_on()?;
+    ensure(read_commit == written_commit, || {
+        format!("commit {id} was read back as {read_commit:?}, expected {written_commit:?}")
+    })?;
+
+    // A child commit refers to the written commit
+    let mut child = test_commit(backend.as_ref(), tree_id, 2, "second\n");
+    child.parents = vec![id.clone()];
+    let (child_id, written_child) = backend.write_commit(child, None).block_on()?;
+    ensure(child_id != id, || {
+        format!("different commits were both written as {id}")
+    })?;
+    let read_child = backend.read_commit(&child_id).block_on()?;
+    ensure(read_child == written_child, || {
+        format!("commit {child_id} was read back as {read_child:?}, expected {written_child:?}")
+    })
+}
+
+fn check_concurrent_writes(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    let backend = backend.as_ref();
+    let path = test_path("file");
+    let results: Vec<Result<_, CheckFailure>> = thread::scope(|s| {
+        let handles: Vec<_> = (0..NUM_CONCURRENT_WRITERS)
+            .map(|i| {
+                let path = &path;
+                s.spawn(move || -> Result<_, CheckFailure> {
+                    // Every writer writes the same shared file and a file of
+                    // its own, then commits it.
+                    let shared_id = write_file(backend, path, b"shared\n")?;
+                    let contents = format!("writer {i}\n");
+                    let tree_id = write_single_file_tree(backend, "file", contents.as_bytes())?;
+                    let seed = u8::try_from(i + 1).unwrap();
+                    let commit = test_commit(backend, tree_id, seed, &contents);
+                    let (commit_id, _) = backend.write_commit(commit, None).block_on()?;
+                    Ok((shared_id, contents, commit_id))
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut commit_ids = HashSet::new();
+    for result in results {
+        let (shared_id, contents, commit_id) = result?;
+        let shared_contents = read_file(backend, &path, &shared_id)?;
+        ensure(shared_contents == b"shared\n", || {
+            format!("concurrently written file {shared_id} was read back as {shared_contents:?}")
+        })?;
+        let commit = backend.read_commit(&commit_id).block_on()?;
+        ensure(commit.description == contents, || {
+            format!("concurrently written commit {commit_id} has the wrong description")
+        })?;
+        commit_ids.insert(commit_id);
+    }
+    ensure(commit_ids.len() == NUM_CONCURRENT_WRITERS, || {
+        "concurrently written commits got the same id".to_owned()
+    })
+}
+
+fn check_copy_histories(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    let original = CopyHistory {
+        current_path: test_path("original"),
+        parents: vec![],
+        salt: vec![],
+    };
+    let original_id = match backend.write_copy(&original).block_on() {
+        Ok(id) => id,
+        // Copy tracking is optional
+        Err(BackendError::Unsupported(_)) => return Ok(()),
+        Err(err) => return Err(err.into()),
+    };
+    let read_original = backend.read_copy(&original_id).block_on()?;
+    ensure(read_original == original, || {
+        format!("copy {original_id} was read back as {read_original:?}")
+    })?;
+
+    let renamed = CopyHistory {
+        current_path: test_path("renamed"),
+        parents: vec![original_id.clone()],
+        salt: vec![],
+    };
+    let renamed_id = backend.write_copy(&renamed).block_on()?;
+    let related = backend.get_related_copies(&original_id).block_on()?;
+    for copy in [&original, &renamed] {
+        ensure(related.contains(copy), || {
+            format!("related copies of {original_id} don't include {copy:?}")
+        })?;
+    }
+    let renamed_index = related.iter().position(|copy| *copy == renamed).unwrap();
+    let original_index = related.iter().position(|copy| *copy == original).unwrap();
+    ensure(renamed_index < original_index, || {
+        format!("copy {renamed_id} isn't returned before its parent {original_id}")
+    })
+}
+
+fn check_copy_records(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    let root_tree_id = write_single_file_tree(backend.as_ref(), "old", b"contents\n")?;
+    let root_commit = test_commit(backend.as_ref(), root_tree_id, 1, "root\n");
+    let (root_id, _) = backend.write_commit(root_commit, None).block_on()?;
+    let head_tree_id = write_single_file_tree(backend.as_ref(), "new", b"contents\n")?;
+    let mut head_commit = test_commit(backend.as_ref(), head_tree_id, 2, "head\n");
+    head_commit.parents = vec![root_id.clone()];
+    let (head_id, _) = backend.write_commit(head_commit, None).block_on()?;
+
+    // Backends aren't required to detect copies, but any records returned must
+    // be valid.
+    let records: Vec<_> = backend
+        .get_copy_records(None, &root_id, &head_id)?
+        .try_collect()
+        .block_on()?;
+    for record in &records {
+        ensure(record.target == test_path("new"), || {
+            format!("unexpected copy record {record:?}")
+        })?;
+        ensure(record.source == test_path("old"), || {
+            format!("unexpected copy record {record:?}")
+        })?;
+    }
+    let paths = [test_path("old")];
+    let records: Vec<_> = backend
+        .get_copy_records(Some(&paths), &root_id, &head_id)?
+        .try_collect()
+        .block_on()?;
+    ensure(records.is_empty(), || {
+        format!("copy records aren't restricted to the given paths: {records:?}")
+    })
+}
+
+fn check_missing_objects(backend: Box<dyn Backend>) -> Result<(), CheckFailure> {
+    fn ensure_not_found<T: fmt::Debug>(
+        object_type: &str,
+        result: Result<T, BackendError>,
+    ) -> Result<(), CheckFailure> {
+        match result {
+            Err(BackendError::ObjectNotFound { .. }) => Ok(()),
+            Err(err) => Err(CheckFailure(format!(
+                "reading a missing {object_type} should fail with ObjectNotFound, got: {err}"
+            ))),
+            Ok(value) => Err(CheckFailure(format!(
+                "reading a missing {object_type} succeeded with {value:?}"
+            ))),
+        }
+    }
+
+    let path = test_path("file");
+    let hash_length = backend.empty_tree_id().as_bytes().len();
+    let missing_hash = vec![0x42; hash_length];
+    ensure_not_found(
+        "commit",
+        backend
+            .read_commit(&CommitId::new(vec![0x42; backend.commit_id_length()]))
+            .block_on(),
+    )?;
+    ensure_not_found(
+        "tree",
+        backend
+            .read_tree(&path, &TreeId::new(missing_hash.clone()))
+            .block_on(),
+    )?;
+    ensure_not_found(
+        "symlink",
+        backend
+            .read_symlink(&path, &SymlinkId::new(missing_hash.clone()))
+            .block_on(),
+    )?;
+    ensure_not_found(
+        "file",
+        backend
+            .read_file(&path, &FileId::new(missing_hash))
+            .block_on()
+            .map(|_| ()),
+    )
+}
+
+/// Runs the conformance checks for an [`OpStore`] implementation.
+pub fn op_store_conformance_tests(factory: impl Fn() -> Box<dyn OpStore>) -> ConformanceReport {
+    let mut report = ConformanceReport::default();
+    report.run("root_operation", factory(), check_root_operation);
+    report.run("view_round_trip", factory(), check_view_round_trip);
+    report.run(
+        "operation_round_trip",
+        factory(),
+        check_operation_round_trip,
+    );
+    report.run("resolve_prefix", factory(), check_resolve_operation_prefix);
+    report.run(
+        "concurrent_writes",
+        factory(),
+        check_concurrent_operation_writes,
+    );
+    report.run("missing_objects", factory(), check_missing_operations);
+    report
+}
+
+fn test_view(seed: u8) -> View {
+    let commit_id = CommitId::new(vec![seed; 20]);
+    let mut view = View::empty();
+    view.head_ids.insert(commit_id.clone());
+    view.local_bookmarks
+        .insert("main".into(), RefTarget::normal(commit_id.clone()));
+    view.wc_commit_ids.insert("default".into(), commit_id);
+    view.notes
+        .insert(ChangeId::new(vec![seed; 16]), format!("note {seed}"));
+    view
+}
+
+fn test_operation(view_id: ViewId, parents: Vec<OperationId>, description: &str) -> Operation {
+    let timestamp = Timestamp {
+        timestamp: MillisSinceEpoch(1_000_000),
+        tz_offset: 60,
+    };
+    Operation {
+        view_id,
+        parents,
+        metadata: OperationMetadata {
+            start_time: timestamp,
+            end_time: timestamp,
+            description: description.to_owned(),
+            hostname: "host".to_owned(),
+            username: "user".to_owned(),
+            is_snapshot: false,
+            tags: HashMap::from([("args".to_owned(), "jj test".to_owned())]),
+        },
+        commit_predecessors: Some(BTreeMap::new()),
+        commit_rewrites: CommitRewrites::default(),
+    }
+}
+
+fn check_root_operation(op_store: Box<dyn OpStore>) -> Result<(), CheckFailure> {
+    let root_operation = op_store.read_operation(op_store.root_operation_id())?;
+    ensure(root_operation.parents.is_empty(), || {
+        format!("root operation has parents {:?}", root_operation.parents)
+    })?;
+    op_store.read_view(&root_operation.view_id)?;
+    Ok(())
+}
+
+fn check_view_round_trip(op_store: Box<dyn OpStore>) -> Result<(), CheckFailure> {
+    let view = test_view(1);
+    let id = op_store.write_view(&view)?;
+    let read_view = op_store.read_view(&id)?;
+    ensure(read_view == view, || {
+        format!("view {id} was read back as {read_view:?}, expected {view:?}")
+    })
+}
+
+fn check_operation_round_trip(op_store: Box<dyn OpStore>) -> Result<(), CheckFailure> {
+    let view_id = op_store.write_view(&test_view(1))?;
+    let parents = vec![op_store.root_operation_id().clone()];
+    let operation = test_operation(view_id, parents, "first");
+    let id = op_store.write_operation(&operation)?;
+    let read_operation = op_store.read_operation(&id)?;
+    ensure(read_operation == operation, || {
+        format!("operation {id} was read back as {read_operation:?}, expected {operation:?}")
+    })
+}
+
+fn check_resolve_operation_prefix(op_store: Box<dyn OpStore>) -> Result<(), CheckFailure> {
+    let view_id = op_store.write_view(&test_view(1))?;
+    let parents = vec![op_store.root_operation_id().clone()];
+    let id = op_store.write_operation(&test_operation(view_id, parents, "first"))?;
+    let resolution = op_store.resolve_operation_id_prefix(&HexPrefix::new(&id.hex()).unwrap())?;
+    ensure(
+        resolution == PrefixResolution::SingleMatch(id.clone()),
+        || format!("full id of operation {id} resolved to {resolution:?}"),
+    )?;
+    let missing_hex = id
+        .hex()
+        .chars()
+        .map(|c| if c == '0' { '1' } else { '0' })
+        .collect::<String>();
+    let resolution =
+        op_store.resolve_operation_id_prefix(&HexPrefix::new(&missing_hex).unwrap())?;
+    ensure(resolution == PrefixResolution::NoMatch, || {
+        format!("id of missing operation resolved to {resolution:?}")
+    })
+}
+
+fn check_concurrent_operation_writes(op_store: Box<dyn OpStore>) -> Result<(), CheckFailure> {
+    let op_store = op_store.as_ref();
+    let results: Vec<Result<_, CheckFailure>> = thread::scope(|s| {
+        let handles: Vec<_> = (0..NUM_CONCURRENT_WRITERS)
+            .map(|i| {
+                s.spawn(move || -> Result<_, CheckFailure> {
+                    let seed = u8::try_from(i + 1).unwrap();
+                    let view_id = op_store.write_view(&test_view(seed))?;
+                    let parents = vec![op_store.root_operation_id().clone()];
+                    let operation = test_operation(view_id, parents, &format!("writer {i}"));
+                    let id = op_store.write_operation(&operation)?;
+                    Ok((id, operation))
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    let mut ids = HashSet::new();
+    for result in results {
+        let (id, operation) = result?;
+        let read_operation = op_store.read_operation(&id)?;
+        ensure(read_operation == operation, || {
+            format!("concurrently written operation {id} was read back as {read_operation:?}")
+        })?;
+        ids.insert(id);
+    }
+    ensure(ids.len() == NUM_CONCURRENT_WRITERS, || {
+        "concurrently written operations got the same id".to_owned()
+    })
+}
+
+fn check_missing_operations(op_store: Box<dyn OpStore>) -> Result<(), CheckFailure> {
+    let hash_length = op_store.root_operation_id().as_bytes().len();
+    match op_store.read_operation(&OperationId::new(vec![0x42; hash_length])) {
+        Err(OpStoreError::ObjectNotFound { .. }) => {}
+        Err(err) => {
+            return Err(CheckFailure(format!(
+                "reading a missing operation should fail with ObjectNotFound, got: {err}"
+            )));
+        }
+        Ok(operation) => {
+            return Err(CheckFailure(format!(
+                "reading a missing operation succeeded with {operation:?}"
+            )));
+        }
+    }
+    match op_store.read_view(&ViewId::new(vec![0x42; hash_length])) {
+        Err(OpStoreError::ObjectNotFound { .. }) => Ok(()),
+        Err(err) => Err(CheckFailure(format!(
+            "reading a missing view should fail with ObjectNotFound, got: {err}"
+        ))),
+        Ok(view) => Err(CheckFailure(format!(
+            "reading a missing view succeeded with {view:?}"
+        ))),
+    }
+}
+
+/// Runs the conformance checks for an [`OpHeadsStore`] implementation.
+///
+/// The store should initially have no heads.
+pub fn op_heads_store_conformance_tests(
+    factory: impl Fn() -> Box<dyn OpHeadsStore>,
+) -> ConformanceReport {
+    let mut report = ConformanceReport::default();
+    report.run("update_op_heads", factory(), check_update_op_heads);
+    report.run(
+        "concurrent_updates",
+        factory(),
+        check_concurrent_op_heads_updates,
+    );
+    report.run("lock", factory(), check_op_heads_lock);
+    report
+}
+
+fn test_operation_id(seed: u8) -> OperationId {
+    OperationId::new(vec![seed; 64])
+}
+
+fn sorted_op_heads(op_heads_store: &dyn OpHeadsStore) -> Result<Vec<OperationId>, CheckFailure> {
+    let mut op_heads = op_heads_store.get_op_heads()?;
+    op_heads.sort();
+    Ok(op_heads)
+}
+
+fn check_update_op_heads(op_heads_store: Box<dyn OpHeadsStore>) -> Result<(), CheckFailure> {
+    let op_heads_store = op_heads_store.as_ref();
+    let [id1, id2, id3] = [1, 2, 3].map(test_operation_id);
+    op_heads_store.update_op_heads(&[], &id1)?;
+    let op_heads = sorted_op_heads(op_heads_store)?;
+    ensure(op_heads == [id1.clone()], || {
+        format!("op heads are {op_heads:?} after adding the first head")
+    })?;
+
+    // Adding a head without removing the old one keeps both
+    op_heads_store.update_op_heads(&[], &id2)?;
+    let op_heads = sorted_op_heads(op_heads_store)?;
+    ensure(op_heads == [id1.clone(), id2.clone()], || {
+        format!("op heads are {op_heads:?} after adding a second head")
+    })?;
+
+    // Merging both heads leaves a single head
+    op_heads_store.update_op_heads(&[id1, id2], &id3)?;
+    let op_heads = sorted_op_heads(op_heads_store)?;
+    ensure(op_heads == [id3], || {
+        format!("op heads are {op_heads:?} after replacing the heads")
+    })
+}
+
+fn check_concurrent_op_heads_updates(
+    op_heads_store: Box<dyn OpHeadsStore>,
+) -> Result<(), CheckFailure> {
+    let op_heads_store = op_heads_store.as_ref();
+    let base_id = test_operation_id(0);
+    op_heads_store.update_op_heads(&[], &base_id)?;
+
+    // Concurrent operations on top of the same head must all be kept, so the
+    // divergent operations can be merged later.
+    let results: Vec<Result<(), CheckFailure>> = thread::scope(|s| {
+        let handles: Vec<_> = (0..NUM_CONCURRENT_WRITERS)
+            .map(|i| {
+                let base_id = &base_id;
+                s.spawn(move || -> Result<(), CheckFailure> {
+                    let new_id = test_operation_id(u8::try_from(i + 1).unwrap());
+                    op_heads_store.update_op_heads(slice::from_ref(base_id), &new_id)?;
+                    Ok(())
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    results.into_iter().try_for_each(|result| result)?;
+
+    let op_heads = sorted_op_heads(op_heads_store)?;
+    let expected: Vec<_> = (1..=NUM_CONCURRENT_WRITERS)
+        .map(|i| test_operation_id(u8::try_from(i).unwrap()))
+        .collect();
+    ensure(op_heads == expected, || {
+        format!("op heads are {op_heads:?} after concurrent updates, expected {expected:?}")
+    })
+}
+
+fn check_op_heads_lock(op_heads_store: Box<dyn OpHeadsStore>) -> Result<(), CheckFailure> {
+    let id = test_operation_id(1);
+    {
+        let _lock = op_heads_store.lock()?;
+        op_heads_store.update_op_heads(&[], &id)?;
+    }
+    // The lock is released when dropped
+    let _lock = op_heads_store.lock()?;
+    let op_heads = sorted_op_heads(op_heads_store.as_ref())?;
+    ensure(op_heads == [id], || {
+        format!("op heads are {op_heads:?} after updating under the lock")
+    })
+}
@@ -114,6 +114,12 @@ impl Commit {
 
     /// Returns whether commit's content is empty. Commit description is not
     /// taken into consideration.
+    /// Whether this is a stub for a commit outside a shallow history. See
+    /// [`Store::set_shallow_boundaries()`].
+    pub fn is_shallow_boundary(&self) -> bool {
+        self.store.is_shallow_boundary(&self.id)
+    }
+
     pub fn is_empty(&self, repo: &dyn Repo) -> BackendResult<bool> {
         is_backend_commit_empty(repo, &self.store, &self.data)
     }
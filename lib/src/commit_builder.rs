@@ -24,15 +24,17 @@ use crate::backend::ChangeId;
 use crate::backend::CommitId;
 use crate::backend::MergedTreeId;
 use crate::backend::Signature;
+use crate::backend::SigningFn;
 use crate::commit::is_backend_commit_empty;
 use crate::commit::Commit;
 use crate::repo::MutableRepo;
 use crate::repo::Repo;
-use crate::settings::JJRng;
+use crate::settings::ChangeIdGenerator;
 use crate::settings::SignSettings;
 use crate::settings::UserSettings;
 use crate::signing::SignBehavior;
 use crate::store::Store;
+use crate::store::WriteBatch;
 
 #[must_use]
 pub struct CommitBuilder<'repo> {
@@ -93,6 +95,14 @@ impl CommitBuilder<'_> {
         self
     }
 
+    /// Sets the generator used by [`Self::generate_new_change_id()`]. If this
+    /// builder is for a new commit, its change id is regenerated by the
+    /// `generator`.
+    pub fn set_change_id_generator(mut self, generator: Arc<dyn ChangeIdGenerator>) -> Self {
+        self.inner.set_change_id_generator(generator);
+        self
+    }
+
     pub fn description(&self) -> &str {
         self.inner.description()
     }
@@ -159,7 +169,7 @@ impl CommitBuilder<'_> {
 #[derive(Debug)]
 pub struct DetachedCommitBuilder {
     store: Arc<Store>,
-    rng: Arc<JJRng>,
+    change_id_generator: Arc<dyn ChangeIdGenerator>,
     commit: backend::Commit,
     rewrite_source: Option<Commit>,
     sign_settings: SignSettings,
@@ -176,8 +186,8 @@ impl DetachedCommitBuilder {
         let store = repo.store().clone();
         let signature = settings.signature();
         assert!(!parents.is_empty());
-        let rng = settings.get_rng();
-        let change_id = rng.new_change_id(store.change_id_length());
+        let change_id_generator = settings.change_id_generator().clone();
+        let change_id = change_id_generator.new_change_id(store.change_id_length());
         let commit = backend::Commit {
             parents,
             predecessors: vec![],
@@ -190,7 +200,7 @@ impl DetachedCommitBuilder {
         };
         DetachedCommitBuilder {
             store,
-            rng,
+            change_id_generator,
             commit,
             rewrite_source: None,
             sign_settings: settings.sign_settings(),
@@ -234,7 +244,7 @@ impl DetachedCommitBuilder {
         DetachedCommitBuilder {
             store,
             commit,
-            rng: settings.get_rng(),
+            change_id_generator: settings.change_id_generator().clone(),
             rewrite_source: Some(predecessor.clone()),
             sign_settings: settings.sign_settings(),
         }
@@ -292,7 +302,20 @@ impl DetachedCommitBuilder {
     }
 
     pub fn generate_new_change_id(&mut self) -> &mut Self {
-        self.commit.change_id = self.rng.new_change_id(self.store.change_id_length());
+        self.commit.change_id = self
+            .change_id_generator
+            .new_change_id(self.store.change_id_length());
+        self
+    }
+
+    /// Sets the generator used by [`Self::generate_new_change_id()`]. If this
+    /// builder is for a new commit, its change id is regenerated by the
+    /// `generator`.
+    pub fn set_change_id_generator(&mut self, generator: Arc<dyn ChangeIdGenerator>) -> &mut Self {
+        self.change_id_generator = generator;
+        if self.rewrite_source.is_none() {
+            self.generate_new_change_id();
+        }
         self
     }
 
@@ -350,7 +373,13 @@ impl DetachedCommitBuilder {
     /// Writes new commit and makes it visible in the `mut_repo`.
     pub fn write(self, mut_repo: &mut MutableRepo) -> BackendResult<Commit> {
         let predecessors = self.commit.predecessors.clone();
-        let commit = write_to_store(&self.store, self.commit, &self.sign_settings)?;
+        let commit = write_to_store(
+            &self.store,
+            self.commit,
+            &self.sign_settings,
+            mut_repo.write_batch_mut(),
+        )?;
+        mut_repo.metrics_mut().record_commit_written();
         mut_repo.add_head(&commit)?;
         mut_repo.set_predecessors(commit.id().clone(), predecessors);
         if let Some(rewrite_source) = self.rewrite_source {
@@ -366,7 +395,7 @@ impl DetachedCommitBuilder {
     /// This does not consume the builder, so you can reuse the current
     /// configuration to create another commit later.
     pub fn write_hidden(&self) -> BackendResult<Commit> {
-        write_to_store(&self.store, self.commit.clone(), &self.sign_settings)
+        write_to_store(&self.store, self.commit.clone(), &self.sign_settings, None)
     }
 
     /// Records the old commit as abandoned in the `mut_repo`.
@@ -390,16 +419,22 @@ fn write_to_store(
     store: &Arc<Store>,
     mut commit: backend::Commit,
     sign_settings: &SignSettings,
+    write_batch: Option<&mut WriteBatch>,
 ) -> BackendResult<Commit> {
     let should_sign = store.signer().can_sign() && sign_settings.should_sign(&commit);
-    let sign_fn = |data: &[u8]| store.signer().sign(data, sign_settings.key.as_deref());
+    let mut sign_fn = |data: &[u8]| store.signer().sign(data, sign_settings.key.as_deref());
 
     // Commit backend doesn't use secure_sig for writing and enforces it with an
     // assert, but sign_settings.should_sign check above will want to know
     // if we're rewriting a signed commit
     commit.secure_sig = None;
 
-    store
-        .write_commit(commit, should_sign.then_some(&mut &sign_fn))
-        .block_on()
+    let sign_with: Option<&mut SigningFn> = should_sign.then_some(&mut sign_fn);
+    if let Some(batch) = write_batch {
+        store
+            .write_commit_batched(batch, commit, sign_with)
+            .block_on()
+    } else {
+        store.write_commit(commit, sign_with).block_on()
+    }
 }
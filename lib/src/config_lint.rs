@@ -0,0 +1,241 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks expressions stored in config without evaluating them.
+//!
+//! Revsets and filesets in config are otherwise parsed only when a command
+//! uses them, so typos are reported late. Templates are parsed by the CLI, so
+//! template-valued settings aren't checked here.
+
+#![warn(missing_docs)]
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use itertools::Itertools as _;
+use thiserror::Error;
+
+use crate::config::ConfigGetError;
+use crate::config::ConfigGetResultExt as _;
+use crate::config::ConfigLayer;
+use crate::config::ConfigNamePathBuf;
+use crate::config::ConfigSource;
+use crate::config::StackedConfig;
+use crate::dsl_util::AliasDeclaration;
+use crate::dsl_util::AliasDeclarationParser as _;
+use crate::dsl_util::AliasId;
+use crate::fileset;
+use crate::fileset::FilesetDiagnostics;
+use crate::fileset::FilesetParseError;
+use crate::repo_path::RepoPathUiConverter;
+use crate::revset;
+use crate::revset::RevsetAliasParser;
+use crate::revset::RevsetAliasesMap;
+use crate::revset::RevsetDiagnostics;
+use crate::revset::RevsetExtensions;
+use crate::revset::RevsetParseContext;
+use crate::revset::RevsetParseError;
+use crate::revset::RevsetWorkspaceContext;
+use crate::settings::UserSettings;
+
+/// Error found in a config value.
+#[derive(Debug, Error)]
+pub enum LintError {
+    /// The value isn't of the expected type.
+    #[error(transparent)]
+    Config(#[from] ConfigGetError),
+    /// The value isn't a valid revset expression.
+    #[error(transparent)]
+    Revset(#[from] RevsetParseError),
+    /// The value isn't a valid fileset expression.
+    #[error(transparent)]
+    Fileset(#[from] FilesetParseError),
+}
+
+/// Invalid config value and where it is defined.
+#[derive(Debug)]
+pub struct LintIssue {
+    /// Name of the config variable.
+    pub key: ConfigNamePathBuf,
+    /// Source of the config layer defining the value.
+    pub source: ConfigSource,
+    /// Path to the config file defining the value if any.
+    pub path: Option<PathBuf>,
+    /// Error found in the value.
+    pub error: LintError,
+}
+
+impl LintIssue {
+    fn in_layer(layer: &ConfigLayer, key: ConfigNamePathBuf, error: LintError) -> Self {
+        LintIssue {
+            key,
+            source: layer.source,
+            path: layer.path.clone(),
+            error,
+        }
+    }
+
+    fn in_config(config: &StackedConfig, key: ConfigNamePathBuf, error: LintError) -> Self {
+        // The uppermost layer defining the key provides the value.
+        let layer = config
+            .layers()
+            .iter()
+            .rev()
+            .find(|layer| matches!(layer.look_up_item(&key), Ok(Some(_))));
+        LintIssue {
+            source: layer.map_or(ConfigSource::Default, |layer| layer.source),
+            path: layer.and_then(|layer| layer.path.clone()),
+            key,
+            error,
+        }
+    }
+}
+
+/// Parses revset and fileset expressions in the `settings`, and returns the
+/// errors found.
+///
+/// This checks `revset-aliases` including recursive aliases, `revsets.*`,
+/// `snapshot.auto-track`, and `fix.tools.*.patterns`. Nothing is evaluated,
+/// so unknown symbols aren't reported. Functions provided by revset extensions
+/// aren't known here, and are reported as errors.
+pub fn validate_settings(
+    settings: &UserSettings,
+    workspace_context: Option<RevsetWorkspaceContext>,
+) -> Vec<LintIssue> {
+    let config = settings.config();
+    let mut issues = vec![];
+
+    let (aliases_map, alias_keys) = load_revset_aliases(config, &mut issues);
+    let extensions = RevsetExtensions::default();
+    let context = RevsetParseContext {
+        aliases_map: &aliases_map,
+        local_variables: HashMap::new(),
+        user_email: settings.user_email(),
+        date_pattern_context: chrono::Local::now().into(),
+        extensions: &extensions,
+        workspace: workspace_context,
+    };
+    for (id, error) in revset::check_aliases(&context) {
+        let alias_key = match id {
+            AliasId::Symbol(name) => (name.to_owned(), None),
+            AliasId::Function(name, params) => (name.to_owned(), Some(params.len())),
+            AliasId::Parameter(_) => panic!("unexpected alias id {id:?}"),
+        };
+        let (key, layer) = &alias_keys[&alias_key];
+        issues.push(LintIssue::in_layer(layer, key.clone(), error.into()));
+    }
+    for name in config.table_keys("revsets").sorted() {
+        let key = ConfigNamePathBuf::from_iter(["revsets", name]);
+        let result = config
+            .get::<String>(&key)
+            .map_err(LintError::from)
+            .and_then(|text| {
+                let mut diagnostics = RevsetDiagnostics::new();
+                revset::parse_with_modifier(&mut diagnostics, &text, &context)?;
+                Ok(())
+            });
+        if let Err(error) = result {
+            issues.push(LintIssue::in_config(config, key, error));
+        }
+    }
+
+    let path_converter = RepoPathUiConverter::Fs {
+        cwd: "".into(),
+        base: "".into(),
+    };
+    let check_filesets = |texts: &[String]| -> Result<(), LintError> {
+        for text in texts {
+            let mut diagnostics = FilesetDiagnostics::new();
+            fileset::parse(&mut diagnostics, text, &path_converter)?;
+        }
+        Ok(())
+    };
+    let key = ConfigNamePathBuf::from_iter(["snapshot", "auto-track"]);
+    let result = config
+        .get::<String>(&key)
+        .optional()
+        .map_err(LintError::from)
+        .and_then(|text| check_filesets(text.as_slice()));
+    if let Err(error) = result {
+        issues.push(LintIssue::in_config(config, key, error));
+    }
+    for name in config.table_keys("fix.tools").sorted() {
+        let key = ConfigNamePathBuf::from_iter(["fix", "tools", name, "patterns"]);
+        let result = config
+            .get::<Vec<String>>(&key)
+            .optional()
+            .map_err(LintError::from)
+            .and_then(|texts| check_filesets(texts.as_deref().unwrap_or_default()));
+        if let Err(error) = result {
+            issues.push(LintIssue::in_config(config, key, error));
+        }
+    }
+
+    issues
+}
+
+/// Alias name and arity (`None` for symbol aliases.)
+type AliasKey = (String, Option<usize>);
+
+/// Loads `revset-aliases` from all layers in order, recording the config key
+/// and layer defining each alias.
+fn load_revset_aliases<'a>(
+    config: &'a StackedConfig,
+    issues: &mut Vec<LintIssue>,
+) -> (
+    RevsetAliasesMap,
+    HashMap<AliasKey, (ConfigNamePathBuf, &'a ConfigLayer)>,
+) {
+    let table_name = ConfigNamePathBuf::from_iter(["revset-aliases"]);
+    let mut aliases_map = RevsetAliasesMap::new();
+    let mut alias_keys = HashMap::new();
+    for layer in config.layers() {
+        let table = match layer.look_up_table(&table_name) {
+            Ok(Some(table)) => table,
+            Ok(None) => continue,
+            Err(item) => {
+                let error = ConfigGetError::Type {
+                    name: table_name.to_string(),
+                    error: format!("Expected a table, but is {}", item.type_name()).into(),
+                    source_path: layer.path.clone(),
+                };
+                issues.push(LintIssue::in_layer(layer, table_name.clone(), error.into()));
+                continue;
+            }
+        };
+        for (decl, item) in table.iter() {
+            let key = ConfigNamePathBuf::from_iter(["revset-aliases", decl]);
+            let Some(defn) = item.as_str() else {
+                let error = ConfigGetError::Type {
+                    name: key.to_string(),
+                    error: format!("Expected a string, but is {}", item.type_name()).into(),
+                    source_path: layer.path.clone(),
+                };
+                issues.push(LintIssue::in_layer(layer, key, error.into()));
+                continue;
+            };
+            let alias_key = match RevsetAliasParser.parse_declaration(decl) {
+                Ok(AliasDeclaration::Symbol(name)) => (name, None),
+                Ok(AliasDeclaration::Function(name, params)) => (name, Some(params.len())),
+                Err(error) => {
+                    issues.push(LintIssue::in_layer(layer, key, error.into()));
+                    continue;
+                }
+            };
+            aliases_map.insert(decl, defn).unwrap();
+            alias_keys.insert(alias_key, (key, layer.as_ref()));
+        }
+    }
+    (aliases_map, alias_keys)
+}
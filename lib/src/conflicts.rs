@@ -38,6 +38,7 @@ use crate::backend::FileId;
 use crate::backend::SymlinkId;
 use crate::backend::TreeId;
 use crate::backend::TreeValue;
+use crate::commit::Commit;
 use crate::copies::CopiesTreeDiffEntry;
 use crate::copies::CopiesTreeDiffEntryPath;
 use crate::diff::Diff;
@@ -47,6 +48,7 @@ use crate::files;
 use crate::files::MergeResult;
 use crate::merge::Merge;
 use crate::merge::MergedTreeValue;
+use crate::merged_tree::MergedTree;
 use crate::repo_path::RepoPath;
 use crate::store::Store;
 
@@ -283,6 +285,121 @@ pub fn resolve_file_executable(merge: &Merge<Option<bool>>) -> Option<bool> {
     }
 }
 
+/// Content of a term of a conflict, as extracted by [`extract_sides()`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ConflictSideContent {
+    /// The path doesn't exist in this term, e.g. because it was deleted.
+    Absent,
+    /// Regular file with its content.
+    File {
+        id: FileId,
+        executable: bool,
+        content: BString,
+    },
+    /// Symbolic link with its target.
+    Symlink { id: SymlinkId, target: String },
+    /// Git submodule.
+    GitSubmodule(CommitId),
+    /// Directory. The content of the directory isn't extracted.
+    Tree(TreeId),
+}
+
+/// Term of a conflict along with the commits it may have come from.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConflictSide {
+    pub content: ConflictSideContent,
+    /// Candidate commits which have the same value at the path as this term.
+    pub commit_ids: Vec<CommitId>,
+}
+
+/// Bases and sides of a path as separate terms, which can be passed to a
+/// three-way merge tool without parsing conflict markers.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConflictSides {
+    /// Simplified terms of the path value. A path without conflicts has a
+    /// single resolved term.
+    pub terms: Merge<ConflictSide>,
+}
+
+impl ConflictSides {
+    /// Returns the bases of the conflict.
+    pub fn bases(&self) -> impl ExactSizeIterator<Item = &ConflictSide> {
+        self.terms.removes()
+    }
+
+    /// Returns the sides of the conflict.
+    pub fn sides(&self) -> impl ExactSizeIterator<Item = &ConflictSide> {
+        self.terms.adds()
+    }
+}
+
+/// Extracts the bases and sides of the value at `path` in `tree`.
+///
+/// The value is simplified first, so pairs of identical bases and sides are
+/// dropped. Each term is attributed to the `candidates` (e.g. the parents of a
+/// merge and their merge base) whose tree has the same value at `path`.
+pub async fn extract_sides(
+    store: &Store,
+    tree: &MergedTree,
+    path: &RepoPath,
+    candidates: &[Commit],
+) -> BackendResult<ConflictSides> {
+    let value = tree.path_value(path)?.simplify();
+    let candidate_values: Vec<(&CommitId, MergedTreeValue)> = candidates
+        .iter()
+        .map(|commit| Ok::<_, BackendError>((commit.id(), commit.tree()?.path_value(path)?)))
+        .try_collect()?;
+    let terms = value
+        .try_map_async(|term| async {
+            let content = extract_side_content(store, path, term).await?;
+            let commit_ids = candidate_values
+                .iter()
+                .filter(|(_, candidate_value)| candidate_value.as_resolved() == Some(term))
+                .map(|(id, _)| (*id).clone())
+                .collect();
+            Ok::<_, BackendError>(ConflictSide {
+                content,
+                commit_ids,
+            })
+        })
+        .await?;
+    Ok(ConflictSides { terms })
+}
+
+async fn extract_side_content(
+    store: &Store,
+    path: &RepoPath,
+    term: &Option<TreeValue>,
+) -> BackendResult<ConflictSideContent> {
+    match term {
+        None => Ok(ConflictSideContent::Absent),
+        Some(TreeValue::File {
+            id,
+            executable,
+            copy_id: _,
+        }) => {
+            let content = get_file_contents(store, path, &Some(id.clone())).await?;
+            Ok(ConflictSideContent::File {
+                id: id.clone(),
+                executable: *executable,
+                content,
+            })
+        }
+        Some(TreeValue::Symlink(id)) => {
+            let target = store.read_symlink(path, id).await?;
+            Ok(ConflictSideContent::Symlink {
+                id: id.clone(),
+                target,
+            })
+        }
+        Some(TreeValue::GitSubmodule(id)) => Ok(ConflictSideContent::GitSubmodule(id.clone())),
+        Some(TreeValue::Tree(id)) => Ok(ConflictSideContent::Tree(id.clone())),
+        Some(TreeValue::Conflict(_)) => {
+            panic!("cannot extract legacy conflict object at path {path:?}");
+        }
+    }
+}
+
 /// Describes what style should be used when materializing conflicts.
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Default, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
@@ -296,6 +413,69 @@ pub enum ConflictMarkerStyle {
     Git,
 }
 
+/// Labels identifying where the sides of a conflict came from, such as the
+/// parent commits of a merge.
+///
+/// The labels are written after the conflict markers of each side. They are
+/// only informational, and are ignored when parsing materialized conflicts.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConflictLabels {
+    sides: Vec<String>,
+}
+
+impl ConflictLabels {
+    /// Labels which don't annotate any side.
+    pub fn unlabeled() -> Self {
+        Self::default()
+    }
+
+    /// Creates labels for each side of a conflict. Only the first line of each
+    /// label is used.
+    pub fn from_sides(sides: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let sides = sides
+            .into_iter()
+            .map(|label| {
+                label
+                    .as_ref()
+                    .lines()
+                    .next()
+                    .unwrap_or("")
+                    .trim()
+                    .to_owned()
+            })
+            .collect();
+        ConflictLabels { sides }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sides.is_empty()
+    }
+
+    /// Returns the label of the side at `index`, if any.
+    pub fn get_side(&self, index: usize) -> Option<&str> {
+        self.sides
+            .get(index)
+            .map(String::as_str)
+            .filter(|label| !label.is_empty())
+    }
+
+    /// Returns the labels if they can be applied to a conflict with
+    /// `num_sides` sides. The sides of simplified conflicts no longer match
+    /// the labels, so they are left unlabeled.
+    fn for_num_sides(&self, num_sides: usize) -> Option<&Self> {
+        (self.sides.len() == num_sides).then_some(self)
+    }
+
+    /// Formats the label of the side at `index` to be appended to the side
+    /// name in a conflict marker.
+    fn format_side_suffix(labels: Option<&Self>, index: usize) -> String {
+        match labels.and_then(|labels| labels.get_side(index)) {
+            Some(label) => format!(" \"{label}\""),
+            None => String::new(),
+        }
+    }
+}
+
 /// Characters which can be repeated to form a conflict marker line when
 /// materializing and parsing conflicts.
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -405,7 +585,13 @@ pub fn materialize_merge_result<T: AsRef<[u8]>>(
         MergeResult::Resolved(content) => output.write_all(content),
         MergeResult::Conflict(hunks) => {
             let conflict_marker_len = choose_materialized_conflict_marker_len(single_hunk);
-            materialize_conflict_hunks(hunks, conflict_marker_style, conflict_marker_len, output)
+            materialize_conflict_hunks(
+                hunks,
+                conflict_marker_style,
+                conflict_marker_len,
+                None,
+                output,
+            )
         }
     }
 }
@@ -419,9 +605,13 @@ pub fn materialize_merge_result_with_marker_len<T: AsRef<[u8]>>(
     let merge_result = files::merge_hunks(single_hunk);
     match &merge_result {
         MergeResult::Resolved(content) => output.write_all(content),
-        MergeResult::Conflict(hunks) => {
-            materialize_conflict_hunks(hunks, conflict_marker_style, conflict_marker_len, output)
-        }
+        MergeResult::Conflict(hunks) => materialize_conflict_hunks(
+            hunks,
+            conflict_marker_style,
+            conflict_marker_len,
+            None,
+            output,
+        ),
     }
 }
 
@@ -439,6 +629,7 @@ pub fn materialize_merge_result_to_bytes<T: AsRef<[u8]>>(
                 &hunks,
                 conflict_marker_style,
                 conflict_marker_len,
+                None,
                 &mut output,
             )
             .expect("writing to an in-memory buffer should never fail");
@@ -451,6 +642,25 @@ pub fn materialize_merge_result_to_bytes_with_marker_len<T: AsRef<[u8]>>(
     single_hunk: &Merge<T>,
     conflict_marker_style: ConflictMarkerStyle,
     conflict_marker_len: usize,
+) -> BString {
+    materialize_merge_result_to_bytes_with_labels(
+        single_hunk,
+        conflict_marker_style,
+        conflict_marker_len,
+        &ConflictLabels::unlabeled(),
+    )
+}
+
+/// Materializes the conflict, annotating the conflict markers of each side
+/// with its label.
+///
+/// The labels are ignored if they don't match the number of sides of the
+/// conflict.
+pub fn materialize_merge_result_to_bytes_with_labels<T: AsRef<[u8]>>(
+    single_hunk: &Merge<T>,
+    conflict_marker_style: ConflictMarkerStyle,
+    conflict_marker_len: usize,
+    labels: &ConflictLabels,
 ) -> BString {
     let merge_result = files::merge_hunks(single_hunk);
     match merge_result {
@@ -461,6 +671,7 @@ pub fn materialize_merge_result_to_bytes_with_marker_len<T: AsRef<[u8]>>(
                 &hunks,
                 conflict_marker_style,
                 conflict_marker_len,
+                labels.for_num_sides(single_hunk.num_sides()),
                 &mut output,
             )
             .expect("writing to an in-memory buffer should never fail");
@@ -473,6 +684,7 @@ fn materialize_conflict_hunks(
     hunks: &[Merge<BString>],
     conflict_marker_style: ConflictMarkerStyle,
     conflict_marker_len: usize,
+    labels: Option<&ConflictLabels>,
     output: &mut dyn Write,
 ) -> io::Result<()> {
     let num_conflicts = hunks
@@ -496,6 +708,7 @@ fn materialize_conflict_hunks(
                         right,
                         &conflict_info,
                         conflict_marker_len,
+                        labels,
                         output,
                     )?;
                 }
@@ -505,6 +718,7 @@ fn materialize_conflict_hunks(
                         &conflict_info,
                         conflict_marker_style,
                         conflict_marker_len,
+                        labels,
                         output,
                     )?;
                 }
@@ -520,13 +734,17 @@ fn materialize_git_style_conflict(
     right: &[u8],
     conflict_info: &str,
     conflict_marker_len: usize,
+    labels: Option<&ConflictLabels>,
     output: &mut dyn Write,
 ) -> io::Result<()> {
     write_conflict_marker(
         output,
         ConflictMarkerLineChar::ConflictStart,
         conflict_marker_len,
-        &format!("Side #1 ({conflict_info})"),
+        &format!(
+            "Side #1{} ({conflict_info})",
+            ConflictLabels::format_side_suffix(labels, 0)
+        ),
     )?;
     write_and_ensure_newline(output, left)?;
 
@@ -551,7 +769,10 @@ fn materialize_git_style_conflict(
         output,
         ConflictMarkerLineChar::ConflictEnd,
         conflict_marker_len,
-        &format!("Side #2 ({conflict_info} ends)"),
+        &format!(
+            "Side #2{} ({conflict_info} ends)",
+            ConflictLabels::format_side_suffix(labels, 1)
+        ),
     )?;
 
     Ok(())
@@ -562,6 +783,7 @@ fn materialize_jj_style_conflict(
     conflict_info: &str,
     conflict_marker_style: ConflictMarkerStyle,
     conflict_marker_len: usize,
+    labels: Option<&ConflictLabels>,
     output: &mut dyn Write,
 ) -> io::Result<()> {
     // Write a positive snapshot (side) of a conflict
@@ -571,8 +793,9 @@ fn materialize_jj_style_conflict(
             ConflictMarkerLineChar::Add,
             conflict_marker_len,
             &format!(
-                "Contents of side #{}{}",
+                "Contents of side #{}{}{}",
                 add_index + 1,
+                ConflictLabels::format_side_suffix(labels, add_index),
                 maybe_no_eol_comment(data)
             ),
         )?;
@@ -610,8 +833,9 @@ fn materialize_jj_style_conflict(
                 ConflictMarkerLineChar::Diff,
                 conflict_marker_len,
                 &format!(
-                    "Changes from {base_str} to side #{}{no_eol_comment}",
-                    add_index + 1
+                    "Changes from {base_str} to side #{}{}{no_eol_comment}",
+                    add_index + 1,
+                    ConflictLabels::format_side_suffix(labels, add_index)
                 ),
             )?;
             write_diff_hunks(diff, output)
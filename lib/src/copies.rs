@@ -90,6 +90,16 @@ impl CopyRecords {
     }
 }
 
+/// Whether copies and renames are taken into account when comparing trees.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CopyTracking {
+    /// Copies and renames are treated as deletions and additions.
+    #[default]
+    None,
+    /// Copies and renames recorded by the backend are paired up.
+    Track,
+}
+
 /// Whether or not the source path was deleted.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum CopyOperation {
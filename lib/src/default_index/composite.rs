@@ -43,6 +43,7 @@ use crate::backend::CommitId;
 use crate::hex_util;
 use crate::index::AllHeadsForGcUnsupported;
 use crate::index::ChangeIdIndex;
+use crate::index::CommitsNotIndexedError;
 use crate::index::Index;
 use crate::index::IndexError;
 use crate::object_id::HexPrefix;
@@ -50,6 +51,7 @@ use crate::object_id::ObjectId as _;
 use crate::object_id::PrefixResolution;
 use crate::revset::ResolvedExpression;
 use crate::revset::Revset;
+use crate::revset::RevsetEvaluationContext;
 use crate::revset::RevsetEvaluationError;
 use crate::store::Store;
 
@@ -150,6 +152,20 @@ impl CompositeIndex {
         self.0.num_parent_commits() + self.0.num_local_commits()
     }
 
+    /// Returns the number of commits in each segment, from the oldest to the
+    /// newest. Unlike [`Self::stats()`], this doesn't scan the entries.
+    pub fn level_stats(&self) -> Vec<IndexLevelStats> {
+        let mut levels = self
+            .ancestor_index_segments()
+            .map(|segment| IndexLevelStats {
+                num_commits: segment.num_local_commits(),
+                name: segment.name(),
+            })
+            .collect_vec();
+        levels.reverse();
+        levels
+    }
+
     pub fn stats(&self) -> IndexStats {
         let num_commits = self.num_commits();
         let mut num_merges = 0;
@@ -164,15 +180,7 @@ impl CompositeIndex {
             change_ids.insert(entry.change_id());
         }
         let num_heads = u32::try_from(self.all_heads_pos().count()).unwrap();
-
-        let mut levels = self
-            .ancestor_index_segments()
-            .map(|segment| IndexLevelStats {
-                num_commits: segment.num_local_commits(),
-                name: segment.name(),
-            })
-            .collect_vec();
-        levels.reverse();
+        let levels = self.level_stats();
 
         IndexStats {
             num_commits,
@@ -420,12 +428,71 @@ impl CompositeIndex {
         candidate_positions
     }
 
+    /// Returns the subset of positions in `candidate_positions` which don't
+    /// have ancestors in `candidate_positions`.
+    pub fn roots_pos(
+        &self,
+        candidate_positions: &BTreeSet<IndexPosition>,
+    ) -> BTreeSet<IndexPosition> {
+        let (Some(&min_pos), Some(&max_pos)) =
+            (candidate_positions.first(), candidate_positions.last())
+        else {
+            return BTreeSet::new();
+        };
+        // Parents are always stored before their children, so the descendants of
+        // the candidates can be marked in a single pass over the positions
+        // between the first and the last candidate.
+        let mut marked = HashSet::new();
+        let mut roots = BTreeSet::new();
+        for pos in (min_pos.0..=max_pos.0).map(IndexPosition) {
+            let has_marked_parent = self
+                .entry_by_pos(pos)
+                .parent_positions()
+                .iter()
+                .any(|parent_pos| marked.contains(parent_pos));
+            let is_candidate = candidate_positions.contains(&pos);
+            if is_candidate && !has_marked_parent {
+                roots.insert(pos);
+            }
+            if is_candidate || has_marked_parent {
+                marked.insert(pos);
+            }
+        }
+        roots
+    }
+
+    /// Looks up the positions of `commit_ids`, failing with the ids which aren't
+    /// present in the index.
+    fn commit_ids_to_positions(
+        &self,
+        commit_ids: &mut dyn Iterator<Item = &CommitId>,
+    ) -> Result<BTreeSet<IndexPosition>, IndexError> {
+        let mut positions = BTreeSet::new();
+        let mut missing_ids = vec![];
+        for id in commit_ids {
+            match self.commit_id_to_pos(id) {
+                Some(pos) => {
+                    positions.insert(pos);
+                }
+                None => missing_ids.push(id.clone()),
+            }
+        }
+        if missing_ids.is_empty() {
+            Ok(positions)
+        } else {
+            missing_ids.sort();
+            missing_ids.dedup();
+            Err(IndexError(Box::new(CommitsNotIndexedError(missing_ids))))
+        }
+    }
+
     pub(super) fn evaluate_revset(
         &self,
         expression: &ResolvedExpression,
         store: &Arc<Store>,
+        context: &RevsetEvaluationContext,
     ) -> Result<Box<dyn Revset + '_>, RevsetEvaluationError> {
-        let revset_impl = revset_engine::evaluate(expression, store, self)?;
+        let revset_impl = revset_engine::evaluate(expression, store, self, context)?;
         Ok(Box::new(revset_impl))
     }
 }
@@ -499,10 +566,7 @@ impl Index for &CompositeIndex {
         &self,
         candidate_ids: &mut dyn Iterator<Item = &CommitId>,
     ) -> Result<Vec<CommitId>, IndexError> {
-        let candidate_positions: BTreeSet<_> = candidate_ids
-            .map(|id| self.commit_id_to_pos(id).unwrap())
-            .collect();
-
+        let candidate_positions = self.commit_ids_to_positions(candidate_ids)?;
         Ok(self
             .heads_pos(candidate_positions)
             .iter()
@@ -510,12 +574,25 @@ impl Index for &CompositeIndex {
             .collect())
     }
 
+    fn roots_of_set(
+        &self,
+        candidate_ids: &mut dyn Iterator<Item = &CommitId>,
+    ) -> Result<Vec<CommitId>, IndexError> {
+        let candidate_positions = self.commit_ids_to_positions(candidate_ids)?;
+        Ok(self
+            .roots_pos(&candidate_positions)
+            .iter()
+            .map(|pos| self.entry_by_pos(*pos).commit_id())
+            .collect())
+    }
+
     fn evaluate_revset<'index>(
         &'index self,
         expression: &ResolvedExpression,
         store: &Arc<Store>,
+        context: &RevsetEvaluationContext,
     ) -> Result<Box<dyn Revset + 'index>, RevsetEvaluationError> {
-        CompositeIndex::evaluate_revset(self, expression, store)
+        CompositeIndex::evaluate_revset(self, expression, store, context)
     }
 }
 
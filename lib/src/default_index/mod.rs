@@ -48,6 +48,7 @@ pub use self::store::DefaultIndexStoreInitError;
 mod tests {
     use std::sync::Arc;
 
+    use assert_matches::assert_matches;
     use itertools::Itertools as _;
     use smallvec::smallvec_inline;
     use test_case::test_case;
@@ -61,6 +62,7 @@ mod tests {
     use crate::backend::CommitId;
     use crate::default_index::entry::LocalPosition;
     use crate::default_index::entry::SmallLocalPositionsVec;
+    use crate::index::CommitsNotIndexedError;
     use crate::index::Index as _;
     use crate::object_id::HexPrefix;
     use crate::object_id::ObjectId as _;
@@ -1220,4 +1222,94 @@ mod tests {
             vec![id_3.clone(), id_5.clone()]
         );
     }
+
+    #[test]
+    fn test_roots_of_set() {
+        let mut new_change_id = change_id_generator();
+        let mut index = DefaultMutableIndex::full(3, 16);
+        // 5
+        // |\
+        // 4 | 3
+        // | |/
+        // 1 2
+        // |/
+        // 0
+        let id_0 = CommitId::from_hex("000000");
+        let id_1 = CommitId::from_hex("111111");
+        let id_2 = CommitId::from_hex("222222");
+        let id_3 = CommitId::from_hex("333333");
+        let id_4 = CommitId::from_hex("444444");
+        let id_5 = CommitId::from_hex("555555");
+        index.add_commit_data(id_0.clone(), new_change_id(), &[]);
+        index.add_commit_data(id_1.clone(), new_change_id(), &[id_0.clone()]);
+        index.add_commit_data(id_2.clone(), new_change_id(), &[id_0.clone()]);
+        index.add_commit_data(id_3.clone(), new_change_id(), &[id_2.clone()]);
+        index.add_commit_data(id_4.clone(), new_change_id(), &[id_1.clone()]);
+        index.add_commit_data(id_5.clone(), new_change_id(), &[id_4.clone(), id_2.clone()]);
+
+        // Empty input
+        assert!(index.roots_of_set(&mut [].iter()).unwrap().is_empty());
+        // Single root
+        assert_eq!(
+            index.roots_of_set(&mut [id_4.clone()].iter()).unwrap(),
+            vec![id_4.clone()]
+        );
+        // Duplicated ids
+        assert_eq!(
+            index
+                .roots_of_set(&mut [id_4.clone(), id_5.clone(), id_4.clone()].iter())
+                .unwrap(),
+            vec![id_4.clone()]
+        );
+        // Root and grand-child
+        assert_eq!(
+            index
+                .roots_of_set(&mut [id_5.clone(), id_1.clone()].iter())
+                .unwrap(),
+            vec![id_1.clone()]
+        );
+        // Non-connected set
+        assert_eq!(
+            index
+                .roots_of_set(&mut [id_3.clone(), id_1.clone(), id_5.clone()].iter())
+                .unwrap(),
+            vec![id_1.clone(), id_3.clone()]
+        );
+        // Merge commit with one ancestor in the set
+        assert_eq!(
+            index
+                .roots_of_set(&mut [id_5.clone(), id_2.clone(), id_4.clone()].iter())
+                .unwrap(),
+            vec![id_2.clone(), id_4.clone()]
+        );
+        // Root commit
+        assert_eq!(
+            index
+                .roots_of_set(&mut [id_0.clone(), id_3, id_5].iter())
+                .unwrap(),
+            vec![id_0]
+        );
+    }
+
+    #[test]
+    fn test_heads_and_roots_of_unknown_commits() {
+        let mut new_change_id = change_id_generator();
+        let mut index = DefaultMutableIndex::full(3, 16);
+        let id_0 = CommitId::from_hex("000000");
+        let id_1 = CommitId::from_hex("111111");
+        let id_2 = CommitId::from_hex("222222");
+        index.add_commit_data(id_0.clone(), new_change_id(), &[]);
+
+        let candidates = [id_2.clone(), id_0, id_1.clone(), id_2.clone()];
+        let err = index.heads(&mut candidates.iter()).unwrap_err();
+        assert_matches!(
+            err.0.downcast_ref::<CommitsNotIndexedError>(),
+            Some(CommitsNotIndexedError(ids)) if *ids == [id_1.clone(), id_2.clone()]
+        );
+        let err = index.roots_of_set(&mut candidates.iter()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Commits not found in the index: 111111, 222222"
+        );
+    }
 }
@@ -59,6 +59,7 @@ use crate::object_id::ObjectId;
 use crate::object_id::PrefixResolution;
 use crate::revset::ResolvedExpression;
 use crate::revset::Revset;
+use crate::revset::RevsetEvaluationContext;
 use crate::revset::RevsetEvaluationError;
 use crate::store::Store;
 
@@ -355,6 +356,8 @@ impl MutableIndexSegment {
 
         let mut buf = Vec::new();
         buf.extend(INDEX_SEGMENT_FILE_FORMAT_VERSION.to_le_bytes());
+        buf.extend(u32::try_from(self.commit_id_length).unwrap().to_le_bytes());
+        buf.extend(u32::try_from(self.change_id_length).unwrap().to_le_bytes());
         self.serialize_parent_filename(&mut buf);
         let local_entries_offset = buf.len();
         self.serialize_local_entries(&mut buf);
@@ -371,6 +374,7 @@ impl MutableIndexSegment {
         Ok(ReadonlyIndexSegment::load_with_parent_file(
             &mut &buf[local_entries_offset..],
             index_file_id_hex,
+            INDEX_SEGMENT_FILE_FORMAT_VERSION,
             self.parent_file,
             self.commit_id_length,
             self.change_id_length,
@@ -525,12 +529,21 @@ impl Index for DefaultMutableIndex {
         self.as_composite().heads(candidates)
     }
 
+    fn roots_of_set(
+        &self,
+        candidates: &mut dyn Iterator<Item = &CommitId>,
+    ) -> Result<Vec<CommitId>, IndexError> {
+        self.as_composite().roots_of_set(candidates)
+    }
+
     fn evaluate_revset<'index>(
         &'index self,
         expression: &ResolvedExpression,
         store: &Arc<Store>,
+        context: &RevsetEvaluationContext,
     ) -> Result<Box<dyn Revset + 'index>, RevsetEvaluationError> {
-        self.as_composite().evaluate_revset(expression, store)
+        self.as_composite()
+            .evaluate_revset(expression, store, context)
     }
 }
 
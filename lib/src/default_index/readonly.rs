@@ -21,6 +21,7 @@ use std::fmt::Formatter;
 use std::fs::File;
 use std::io;
 use std::io::Read;
+use std::iter;
 use std::path::Path;
 use std::sync::Arc;
 
@@ -49,6 +50,7 @@ use crate::object_id::ObjectId;
 use crate::object_id::PrefixResolution;
 use crate::revset::ResolvedExpression;
 use crate::revset::Revset;
+use crate::revset::RevsetEvaluationContext;
 use crate::revset::RevsetEvaluationError;
 use crate::store::Store;
 
@@ -104,7 +106,13 @@ impl ReadonlyIndexLoadError {
 }
 
 /// Current format version of the index segment file.
-pub(crate) const INDEX_SEGMENT_FILE_FORMAT_VERSION: u32 = 6;
+pub(crate) const INDEX_SEGMENT_FILE_FORMAT_VERSION: u32 = 7;
+
+/// Oldest format version of the index segment file which can still be read.
+///
+/// Segment files of older readable versions are migrated incrementally by
+/// `DefaultIndexStore::rebuild_incrementally()`.
+pub(crate) const INDEX_SEGMENT_FILE_MIN_READABLE_FORMAT_VERSION: u32 = 6;
 
 /// If set, the value is stored in the overflow table.
 pub(crate) const OVERFLOW_FLAG: u32 = 0x8000_0000;
@@ -179,6 +187,8 @@ impl CommitGraphEntry<'_> {
 /// File format:
 /// ```text
 /// u32: file format version
+/// u32: commit id length (since version 7)
+/// u32: change id length (since version 7)
 /// u32: parent segment file name length (0 means root)
 /// <length number of bytes>: parent segment file name
 ///
@@ -222,6 +232,7 @@ pub(super) struct ReadonlyIndexSegment {
     parent_file: Option<Arc<ReadonlyIndexSegment>>,
     num_parent_commits: u32,
     name: String,
+    format_version: u32,
     commit_id_length: usize,
     change_id_length: usize,
     // Number of commits not counting the parent file
@@ -246,48 +257,102 @@ impl Debug for ReadonlyIndexSegment {
     }
 }
 
-impl ReadonlyIndexSegment {
-    /// Loads both parent segments and local entries from the given file `name`.
+/// Header fields of the index segment file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) struct IndexSegmentHeader {
+    pub format_version: u32,
+    pub parent_filename: Option<String>,
+}
+
+impl IndexSegmentHeader {
+    /// Reads the header of the given file `name` without loading the entries.
     pub(super) fn load(
         dir: &Path,
-        name: String,
+        name: &str,
         commit_id_length: usize,
         change_id_length: usize,
-    ) -> Result<Arc<ReadonlyIndexSegment>, ReadonlyIndexLoadError> {
-        let mut file = File::open(dir.join(&name))
-            .map_err(|err| ReadonlyIndexLoadError::from_io_err(&name, err))?;
-        Self::load_from(&mut file, dir, name, commit_id_length, change_id_length)
+    ) -> Result<Self, ReadonlyIndexLoadError> {
+        let mut file = File::open(dir.join(name))
+            .map_err(|err| ReadonlyIndexLoadError::from_io_err(name, err))?;
+        Self::load_from(&mut file, name, commit_id_length, change_id_length)
     }
 
-    /// Loads both parent segments and local entries from the given `file`.
-    pub(super) fn load_from(
+    fn load_from(
         file: &mut dyn Read,
-        dir: &Path,
-        name: String,
+        name: &str,
         commit_id_length: usize,
         change_id_length: usize,
-    ) -> Result<Arc<ReadonlyIndexSegment>, ReadonlyIndexLoadError> {
-        let from_io_err = |err| ReadonlyIndexLoadError::from_io_err(&name, err);
+    ) -> Result<Self, ReadonlyIndexLoadError> {
+        let from_io_err = |err| ReadonlyIndexLoadError::from_io_err(name, err);
         let read_u32 = |file: &mut dyn Read| {
             let mut buf = [0; 4];
             file.read_exact(&mut buf).map_err(from_io_err)?;
             Ok(u32::from_le_bytes(buf))
         };
         let format_version = read_u32(file)?;
-        if format_version != INDEX_SEGMENT_FILE_FORMAT_VERSION {
+        if !(INDEX_SEGMENT_FILE_MIN_READABLE_FORMAT_VERSION..=INDEX_SEGMENT_FILE_FORMAT_VERSION)
+            .contains(&format_version)
+        {
             return Err(ReadonlyIndexLoadError::UnexpectedVersion {
                 found_version: format_version,
                 expected_version: INDEX_SEGMENT_FILE_FORMAT_VERSION,
             });
         }
+        if format_version >= 7 {
+            let file_commit_id_length = read_u32(file)?;
+            let file_change_id_length = read_u32(file)?;
+            if file_commit_id_length as usize != commit_id_length
+                || file_change_id_length as usize != change_id_length
+            {
+                return Err(ReadonlyIndexLoadError::invalid_data(
+                    name,
+                    "unexpected commit or change id length",
+                ));
+            }
+        }
         let parent_filename_len = read_u32(file)?;
-        let maybe_parent_file = if parent_filename_len > 0 {
+        let parent_filename = if parent_filename_len > 0 {
             let mut parent_filename_bytes = vec![0; parent_filename_len as usize];
             file.read_exact(&mut parent_filename_bytes)
                 .map_err(from_io_err)?;
             let parent_filename = String::from_utf8(parent_filename_bytes).map_err(|_| {
-                ReadonlyIndexLoadError::invalid_data(&name, "parent file name is not valid UTF-8")
+                ReadonlyIndexLoadError::invalid_data(name, "parent file name is not valid UTF-8")
             })?;
+            Some(parent_filename)
+        } else {
+            None
+        };
+        Ok(IndexSegmentHeader {
+            format_version,
+            parent_filename,
+        })
+    }
+}
+
+impl ReadonlyIndexSegment {
+    /// Loads both parent segments and local entries from the given file `name`.
+    pub(super) fn load(
+        dir: &Path,
+        name: String,
+        commit_id_length: usize,
+        change_id_length: usize,
+    ) -> Result<Arc<ReadonlyIndexSegment>, ReadonlyIndexLoadError> {
+        let mut file = File::open(dir.join(&name))
+            .map_err(|err| ReadonlyIndexLoadError::from_io_err(&name, err))?;
+        Self::load_from(&mut file, dir, name, commit_id_length, change_id_length)
+    }
+
+    /// Loads both parent segments and local entries from the given `file`.
+    pub(super) fn load_from(
+        file: &mut dyn Read,
+        dir: &Path,
+        name: String,
+        commit_id_length: usize,
+        change_id_length: usize,
+    ) -> Result<Arc<ReadonlyIndexSegment>, ReadonlyIndexLoadError> {
+        let header =
+            IndexSegmentHeader::load_from(file, &name, commit_id_length, change_id_length)?;
+        let maybe_parent_file = if let Some(parent_filename) = header.parent_filename {
             let parent_file = ReadonlyIndexSegment::load(
                 dir,
                 parent_filename,
@@ -301,6 +366,7 @@ impl ReadonlyIndexSegment {
         Self::load_with_parent_file(
             file,
             name,
+            header.format_version,
             maybe_parent_file,
             commit_id_length,
             change_id_length,
@@ -312,6 +378,7 @@ impl ReadonlyIndexSegment {
     pub(super) fn load_with_parent_file(
         file: &mut dyn Read,
         name: String,
+        format_version: u32,
         parent_file: Option<Arc<ReadonlyIndexSegment>>,
         commit_id_length: usize,
         change_id_length: usize,
@@ -359,6 +426,7 @@ impl ReadonlyIndexSegment {
             parent_file,
             num_parent_commits,
             name,
+            format_version,
             commit_id_length,
             change_id_length,
             num_local_commits,
@@ -381,6 +449,9 @@ impl ReadonlyIndexSegment {
         &self.name
     }
 
+    pub(super) fn format_version(&self) -> u32 {
+        self.format_version
+    }
     pub(super) fn commit_id_length(&self) -> usize {
         self.commit_id_length
     }
@@ -601,6 +672,15 @@ impl DefaultReadonlyIndex {
     pub(super) fn as_segment(&self) -> &Arc<ReadonlyIndexSegment> {
         &self.0
     }
+
+    /// Returns the names of the segment files this index is loaded from,
+    /// starting from the newest one.
+    pub fn segment_names(&self) -> Vec<String> {
+        iter::once(&self.0)
+            .chain(self.as_composite().ancestor_files_without_local())
+            .map(|segment| segment.name().to_owned())
+            .collect()
+    }
 }
 
 impl AsCompositeIndex for DefaultReadonlyIndex {
@@ -644,12 +724,21 @@ impl Index for DefaultReadonlyIndex {
         self.as_composite().heads(candidates)
     }
 
+    fn roots_of_set(
+        &self,
+        candidates: &mut dyn Iterator<Item = &CommitId>,
+    ) -> Result<Vec<CommitId>, IndexError> {
+        self.as_composite().roots_of_set(candidates)
+    }
+
     fn evaluate_revset<'index>(
         &'index self,
         expression: &ResolvedExpression,
         store: &Arc<Store>,
+        context: &RevsetEvaluationContext,
     ) -> Result<Box<dyn Revset + 'index>, RevsetEvaluationError> {
-        self.as_composite().evaluate_revset(expression, store)
+        self.as_composite()
+            .evaluate_revset(expression, store, context)
     }
 }
 
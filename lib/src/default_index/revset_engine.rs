@@ -55,12 +55,14 @@ use crate::matchers::Matcher;
 use crate::matchers::Visit;
 use crate::merge::Merge;
 use crate::merged_tree::resolve_file_values;
+use crate::merged_tree::MergedTree;
 use crate::object_id::ObjectId as _;
 use crate::repo_path::RepoPath;
 use crate::revset::ResolvedExpression;
 use crate::revset::ResolvedPredicateExpression;
 use crate::revset::Revset;
 use crate::revset::RevsetContainingFn;
+use crate::revset::RevsetEvaluationContext;
 use crate::revset::RevsetEvaluationError;
 use crate::revset::RevsetFilterPredicate;
 use crate::revset::GENERATION_RANGE_FULL;
@@ -765,14 +767,93 @@ where
     }
 }
 
+/// Revset or predicate that checks for interruption before producing each
+/// item.
+struct InterruptibleRevset<S> {
+    inner: S,
+    interrupt: RevsetEvaluationContext,
+}
+
+impl<S: fmt::Debug> fmt::Debug for InterruptibleRevset<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<S> InternalRevset for InterruptibleRevset<S>
+where
+    S: InternalRevset,
+{
+    fn positions<'a>(&self) -> BoxedRevWalk<'a>
+    where
+        Self: 'a,
+    {
+        let mut checker = InterruptChecker::new(self.interrupt.clone());
+        Box::new(self.inner.positions().map(move |_, pos| {
+            checker.check()?;
+            pos
+        }))
+    }
+
+    fn into_predicate<'a>(self: Box<Self>) -> Box<dyn ToPredicateFn + 'a>
+    where
+        Self: 'a,
+    {
+        self
+    }
+}
+
+impl<S> ToPredicateFn for InterruptibleRevset<S>
+where
+    S: ToPredicateFn,
+{
+    fn to_predicate_fn<'a>(&self) -> BoxedPredicateFn<'a>
+    where
+        Self: 'a,
+    {
+        let mut checker = InterruptChecker::new(self.interrupt.clone());
+        let mut f = self.inner.to_predicate_fn();
+        Box::new(move |index, pos| {
+            checker.check()?;
+            f(index, pos)
+        })
+    }
+}
+
+/// Number of items between checks of the `RevsetEvaluationContext`.
+const INTERRUPT_CHECK_INTERVAL: usize = 16;
+
+struct InterruptChecker {
+    context: RevsetEvaluationContext,
+    count: usize,
+}
+
+impl InterruptChecker {
+    fn new(context: RevsetEvaluationContext) -> Self {
+        InterruptChecker { context, count: 0 }
+    }
+
+    fn check(&mut self) -> Result<(), RevsetEvaluationError> {
+        let due = self.count % INTERRUPT_CHECK_INTERVAL == 0;
+        self.count += 1;
+        if due {
+            self.context.check()
+        } else {
+            Ok(())
+        }
+    }
+}
+
 pub fn evaluate<I: AsCompositeIndex + Clone>(
     expression: &ResolvedExpression,
     store: &Arc<Store>,
     index: I,
+    interrupt: &RevsetEvaluationContext,
 ) -> Result<RevsetImpl<I>, RevsetEvaluationError> {
     let context = EvaluationContext {
         store: store.clone(),
         index: index.as_composite(),
+        interrupt: interrupt.clone(),
     };
     let internal_revset = context.evaluate(expression)?;
     Ok(RevsetImpl::new(internal_revset, index))
@@ -781,6 +862,7 @@ pub fn evaluate<I: AsCompositeIndex + Clone>(
 struct EvaluationContext<'index> {
     store: Arc<Store>,
     index: &'index CompositeIndex,
+    interrupt: RevsetEvaluationContext,
 }
 
 fn to_u32_generation_range(range: &Range<u64>) -> Result<Range<u32>, RevsetEvaluationError> {
@@ -797,6 +879,18 @@ impl EvaluationContext<'_> {
     fn evaluate(
         &self,
         expression: &ResolvedExpression,
+    ) -> Result<Box<dyn InternalRevset>, RevsetEvaluationError> {
+        self.interrupt.check()?;
+        let inner = self.evaluate_uninterruptible(expression)?;
+        Ok(Box::new(InterruptibleRevset {
+            inner,
+            interrupt: self.interrupt.clone(),
+        }))
+    }
+
+    fn evaluate_uninterruptible(
+        &self,
+        expression: &ResolvedExpression,
     ) -> Result<Box<dyn InternalRevset>, RevsetEvaluationError> {
         let index = self.index;
         match expression {
@@ -1016,8 +1110,35 @@ impl EvaluationContext<'_> {
         expression: &ResolvedPredicateExpression,
     ) -> Result<Box<dyn ToPredicateFn>, RevsetEvaluationError> {
         match expression {
-            ResolvedPredicateExpression::Filter(predicate) => {
-                Ok(build_predicate_fn(self.store.clone(), predicate))
+            ResolvedPredicateExpression::Filter(predicate) => Ok(Box::new(InterruptibleRevset {
+                inner: build_predicate_fn(self.store.clone(), predicate),
+                interrupt: self.interrupt.clone(),
+            })),
+            ResolvedPredicateExpression::ChangesAgainst { base, files } => {
+                let base_set = self.evaluate(base)?;
+                let base_positions: Vec<_> = base_set
+                    .positions()
+                    .attach(self.index)
+                    .take(2)
+                    .try_collect()?;
+                let [base_pos] = base_positions[..] else {
+                    return Err(RevsetEvaluationError::Other(
+                        "Base of changes_against() should resolve to exactly one commit".into(),
+                    ));
+                };
+                let base_id = self.index.entry_by_pos(base_pos).commit_id();
+                // The base tree is loaded once for all candidates.
+                let base_tree = self.store.get_commit(&base_id)?.tree()?;
+                let matcher: Rc<dyn Matcher> = files.to_matcher().into();
+                let store = self.store.clone();
+                Ok(Box::new(InterruptibleRevset {
+                    inner: box_pure_predicate_fn(move |index, pos| {
+                        let entry = index.entry_by_pos(pos);
+                        let commit = store.get_commit(&entry.commit_id())?;
+                        Ok(has_diff_from_tree(&base_tree, &commit, &*matcher)?)
+                    }),
+                    interrupt: self.interrupt.clone(),
+                }))
             }
             ResolvedPredicateExpression::Set(expression) => {
                 Ok(self.evaluate(expression)?.into_predicate())
@@ -1304,6 +1425,33 @@ fn has_diff_from_parent(
     .block_on()
 }
 
+/// Returns true if the tree of `commit` differs from `base_tree` in the paths
+/// matched by `matcher`. Subtrees with the same ids on both sides aren't
+/// visited.
+fn has_diff_from_tree(
+    base_tree: &MergedTree,
+    commit: &Commit,
+    matcher: &dyn Matcher,
+) -> BackendResult<bool> {
+    // Fast path: no need to load the commit tree
+    let unchanged = *commit.tree_id() == base_tree.id();
+    if unchanged || matcher.visit(RepoPath::root()) == Visit::AllRecursively {
+        return Ok(!unchanged);
+    }
+    let to_tree = commit.tree()?;
+    let mut tree_diff = base_tree.diff_stream(&to_tree, matcher);
+    async {
+        while let Some(entry) = tree_diff.next().await {
+            let (from_value, to_value) = entry.values?;
+            if from_value != to_value {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+    .block_on()
+}
+
 fn matches_diff_from_parent(
     store: &Arc<Store>,
     index: &CompositeIndex,
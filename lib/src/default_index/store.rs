@@ -16,33 +16,39 @@
 
 use std::any::Any;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
 use std::io;
-use std::io::Write as _;
+use std::ops::ControlFlow;
 use std::path::Path;
 use std::path::PathBuf;
 use std::slice;
 use std::sync::Arc;
 
 use itertools::Itertools as _;
-use tempfile::NamedTempFile;
 use thiserror::Error;
 
+use super::composite::IndexSegment as _;
 use super::mutable::DefaultMutableIndex;
+use super::mutable::MutableIndexSegment;
 use super::readonly::DefaultReadonlyIndex;
+use super::readonly::IndexSegmentHeader;
 use super::readonly::ReadonlyIndexLoadError;
 use super::readonly::ReadonlyIndexSegment;
+use super::readonly::INDEX_SEGMENT_FILE_FORMAT_VERSION;
 use crate::backend::BackendError;
 use crate::backend::BackendInitError;
 use crate::backend::CommitId;
 use crate::commit::CommitByCommitterTimestamp;
 use crate::dag_walk;
 use crate::file_util;
-use crate::file_util::persist_content_addressed_temp_file;
+use crate::file_util::persist_content_atomically;
+use crate::file_util::Durability;
 use crate::file_util::IoResultExt as _;
 use crate::file_util::PathError;
 use crate::index::Index as _;
 use crate::index::IndexReadError;
+use crate::index::IndexRebuildProgress;
 use crate::index::IndexStore;
 use crate::index::IndexWriteError;
 use crate::index::MutableIndex;
@@ -52,6 +58,8 @@ use crate::op_store::OpStoreError;
 use crate::op_store::OperationId;
 use crate::op_walk;
 use crate::operation::Operation;
+use crate::repo_pin::RepoPinError;
+use crate::repo_pin::RepoPinStore;
 use crate::store::Store;
 
 // BLAKE2b-512 hash length in hex string
@@ -59,8 +67,12 @@ const SEGMENT_FILE_NAME_LENGTH: usize = 64 * 2;
 
 /// Error that may occur during `DefaultIndexStore` initialization.
 #[derive(Debug, Error)]
-#[error("Failed to initialize index store")]
-pub struct DefaultIndexStoreInitError(#[from] pub PathError);
+pub enum DefaultIndexStoreInitError {
+    #[error("Failed to initialize index store")]
+    Path(#[from] PathError),
+    #[error("Failed to initialize index store")]
+    Pins(#[from] RepoPinError),
+}
 
 impl From<DefaultIndexStoreInitError> for BackendInitError {
     fn from(err: DefaultIndexStoreInitError) -> Self {
@@ -73,7 +85,7 @@ pub enum DefaultIndexStoreError {
     #[error("Failed to associate commit index file with an operation {op_id}")]
     AssociateIndex {
         op_id: OperationId,
-        source: io::Error,
+        source: PathError,
     },
     #[error("Failed to load associated commit index file name")]
     LoadAssociation(#[source] io::Error),
@@ -119,9 +131,19 @@ impl DefaultIndexStore {
         self.ensure_base_dirs()?;
         // Remove all operation links to trigger rebuilding.
         file_util::remove_dir_contents(&self.operations_dir())?;
-        // Remove index segments to save disk space. If raced, new segment file
-        // will be created by the other process.
-        file_util::remove_dir_contents(&self.segments_dir())?;
+        // Remove index segments to save disk space, except for the ones that
+        // are still in use by pinned repos. If raced, new segment file will be
+        // created by the other process.
+        let pinned_segments = self.pinned_segment_names()?;
+        let segments_dir = self.segments_dir();
+        for entry in segments_dir.read_dir().context(&segments_dir)? {
+            let entry = entry.context(&segments_dir)?;
+            if pinned_segments.contains(entry.file_name().to_str().unwrap_or_default()) {
+                continue;
+            }
+            let path = entry.path();
+            fs::remove_file(&path).context(&path)?;
+        }
         // jj <= 0.14 created segment files in the top directory
         for entry in self.dir.read_dir().context(&self.dir)? {
             let entry = entry.context(&self.dir)?;
@@ -135,6 +157,19 @@ impl DefaultIndexStore {
         Ok(())
     }
 
+    /// Returns the names of the segment files recorded by the active pins of
+    /// the repo this index store belongs to.
+    fn pinned_segment_names(&self) -> Result<HashSet<String>, RepoPinError> {
+        let Some(repo_dir) = self.dir.parent() else {
+            return Ok(HashSet::new());
+        };
+        let pins = RepoPinStore::for_repo(repo_dir).active_pins()?;
+        Ok(pins
+            .into_iter()
+            .flat_map(|pin| pin.index_segments)
+            .collect())
+    }
+
     fn ensure_base_dirs(&self) -> Result<(), PathError> {
         for dir in [self.operations_dir(), self.segments_dir()] {
             file_util::create_or_reuse_dir(&dir).context(&dir)?;
@@ -150,6 +185,13 @@ impl DefaultIndexStore {
         self.dir.join("segments")
     }
 
+    /// Returns the size in bytes of the saved index segment file `name`.
+    pub fn segment_file_size(&self, name: &str) -> Result<u64, PathError> {
+        let path = self.segments_dir().join(name);
+        let metadata = path.metadata().context(&path)?;
+        Ok(metadata.len())
+    }
+
     fn load_index_segments_at_operation(
         &self,
         op_id: &OperationId,
@@ -299,6 +341,104 @@ impl DefaultIndexStore {
         Ok(index_file)
     }
 
+    /// Returns true if any index segment at the given operation is stored in
+    /// an older format.
+    ///
+    /// Only the headers of the segment files are read. If the operation hasn't
+    /// been indexed yet, the index will be built in the current format.
+    pub fn needs_rebuild_at_operation(
+        &self,
+        op_id: &OperationId,
+        store: &Store,
+    ) -> Result<bool, DefaultIndexStoreError> {
+        let op_id_file = self.operations_dir().join(op_id.hex());
+        let mut maybe_name = match fs::read_to_string(op_id_file) {
+            Ok(name) => Some(name),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(DefaultIndexStoreError::LoadAssociation(err)),
+        };
+        let segments_dir = self.segments_dir();
+        while let Some(name) = maybe_name {
+            let header = IndexSegmentHeader::load(
+                &segments_dir,
+                &name,
+                store.commit_id_length(),
+                store.change_id_length(),
+            )
+            .map_err(DefaultIndexStoreError::LoadIndex)?;
+            if header.format_version < INDEX_SEGMENT_FILE_FORMAT_VERSION {
+                return Ok(true);
+            }
+            maybe_name = header.parent_filename;
+        }
+        Ok(false)
+    }
+
+    /// Rewrites the index segments at the given operation in the current
+    /// format, one segment at a time.
+    ///
+    /// The segments are migrated from the most recent one, which is usually
+    /// the smallest. Since a segment refers to its parent by file name, the
+    /// segments on top of the migrated segment are rewritten as well.
+    pub fn rebuild_incrementally_at_operation(
+        &self,
+        op_id: &OperationId,
+        store: &Store,
+        progress: &mut dyn FnMut(IndexRebuildProgress) -> ControlFlow<()>,
+    ) -> Result<IndexRebuildProgress, DefaultIndexStoreError> {
+        let mut head = self.load_index_segments_at_operation(
+            op_id,
+            store.commit_id_length(),
+            store.change_id_length(),
+        )?;
+        let mut rebuild_progress = IndexRebuildProgress::default();
+        loop {
+            let chain = {
+                let mut chain = vec![head.clone()];
+                while let Some(parent_file) = chain.last().unwrap().parent_file() {
+                    chain.push(parent_file.clone());
+                }
+                chain.reverse();
+                chain
+            };
+            let is_old = |segment: &Arc<ReadonlyIndexSegment>| {
+                segment.format_version() < INDEX_SEGMENT_FILE_FORMAT_VERSION
+            };
+            let Some(index) = chain.iter().rposition(is_old) else {
+                rebuild_progress.remaining = 0;
+                break;
+            };
+            let mut maybe_parent_file = index.checked_sub(1).map(|i| chain[i].clone());
+            for segment in &chain[index..] {
+                let mut mutable_segment = match maybe_parent_file {
+                    Some(parent_file) => MutableIndexSegment::incremental(parent_file),
+                    None => MutableIndexSegment::full(
+                        store.commit_id_length(),
+                        store.change_id_length(),
+                    ),
+                };
+                mutable_segment.add_commits_from(segment.as_ref());
+                maybe_parent_file = Some(
+                    mutable_segment
+                        .save_in(&self.segments_dir())
+                        .map_err(DefaultIndexStoreError::SaveIndex)?,
+                );
+            }
+            head = maybe_parent_file.unwrap();
+            self.associate_file_with_operation(&head, op_id)
+                .map_err(|source| DefaultIndexStoreError::AssociateIndex {
+                    op_id: op_id.clone(),
+                    source,
+                })?;
+            rebuild_progress.migrated += 1;
+            rebuild_progress.remaining = chain[..index].iter().filter(|s| is_old(s)).count();
+            if progress(rebuild_progress).is_break() {
+                break;
+            }
+        }
+        Ok(rebuild_progress)
+    }
+
     fn save_mutable_index(
         &self,
         mutable_index: DefaultMutableIndex,
@@ -320,12 +460,14 @@ impl DefaultIndexStore {
         &self,
         index: &ReadonlyIndexSegment,
         op_id: &OperationId,
-    ) -> io::Result<()> {
+    ) -> Result<(), PathError> {
         let dir = self.operations_dir();
-        let mut temp_file = NamedTempFile::new_in(&dir)?;
-        let file = temp_file.as_file_mut();
-        file.write_all(index.name().as_bytes())?;
-        persist_content_addressed_temp_file(temp_file, dir.join(op_id.hex()))?;
+        persist_content_atomically(
+            &dir,
+            &op_id.hex(),
+            index.name().as_bytes(),
+            Durability::Synced,
+        )?;
         Ok(())
     }
 }
@@ -339,6 +481,7 @@ impl IndexStore for DefaultIndexStore {
         Self::name()
     }
 
+    #[tracing::instrument(skip_all, fields(op_id = %op.id().hex()))]
     fn get_index_at_op(
         &self,
         op: &Operation,
@@ -394,4 +537,19 @@ impl IndexStore for DefaultIndexStore {
             .map_err(|err| IndexWriteError(err.into()))?;
         Ok(Box::new(DefaultReadonlyIndex::from_segment(index_segment)))
     }
+
+    fn needs_rebuild(&self, op: &Operation, store: &Arc<Store>) -> Result<bool, IndexReadError> {
+        self.needs_rebuild_at_operation(op.id(), store)
+            .map_err(|err| IndexReadError(err.into()))
+    }
+
+    fn rebuild_incrementally(
+        &self,
+        op: &Operation,
+        store: &Arc<Store>,
+        progress: &mut dyn FnMut(IndexRebuildProgress) -> ControlFlow<()>,
+    ) -> Result<IndexRebuildProgress, IndexWriteError> {
+        self.rebuild_incrementally_at_operation(op.id(), store, progress)
+            .map_err(|err| IndexWriteError(err.into()))
+    }
 }
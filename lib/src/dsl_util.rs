@@ -147,6 +147,18 @@ impl<'i, T> FunctionCallNode<'i, T> {
         Ok(args)
     }
 
+    /// Extracts exactly `n` required arguments. Unlike
+    /// [`expect_exact_arguments()`](Self::expect_exact_arguments), `n` doesn't
+    /// have to be known at compile time.
+    pub fn expect_arity(&self, n: usize) -> Result<&[ExpressionNode<'i, T>], InvalidArguments<'i>> {
+        self.ensure_no_keyword_arguments()?;
+        if self.args.len() == n {
+            Ok(&self.args)
+        } else {
+            Err(self.invalid_arguments_count(n, Some(n)))
+        }
+    }
+
     /// Extracts N required arguments and remainders.
     #[expect(clippy::type_complexity)]
     pub fn expect_some_arguments<const N: usize>(
@@ -852,6 +864,62 @@ where
     expander.fold_expression(node)
 }
 
+/// Alias definition expanded by [`expand_alias_definitions()`].
+pub type ExpandedAliasResult<'i, T, E> = Result<ExpressionNode<'i, T>, E>;
+
+/// Expands the definition of each alias without using it in an expression.
+///
+/// This detects syntax errors in the definitions and recursive aliases, which
+/// would otherwise be reported only when the alias is substituted. Parameters
+/// of function aliases are substituted with identifiers of the same names.
+/// Symbol aliases come first, then function aliases, each sorted by name.
+pub fn expand_alias_definitions<'i, T, P>(
+    aliases_map: &'i AliasesMap<P, String>,
+) -> Vec<(AliasId<'i>, ExpandedAliasResult<'i, T, P::Error>)>
+where
+    T: AliasExpandableExpression<'i> + Clone,
+    P: AliasDefinitionParser<Output<'i> = T>,
+    P::Error: AliasExpandError,
+{
+    let no_locals = HashMap::new();
+    let mut expander = AliasExpander {
+        aliases_map,
+        locals: &no_locals,
+        states: Vec::new(),
+    };
+    let symbols = aliases_map
+        .symbol_aliases
+        .iter()
+        .sorted_unstable_by_key(|(name, _)| *name)
+        .map(|(name, defn)| (AliasId::Symbol(name), &[][..], defn));
+    let functions = aliases_map
+        .function_aliases
+        .iter()
+        .sorted_unstable_by_key(|(name, _)| *name)
+        .flat_map(|(name, overloads)| {
+            overloads
+                .iter()
+                .map(move |(params, defn)| (AliasId::Function(name, params), &params[..], defn))
+        });
+    symbols
+        .chain(functions)
+        .map(|(id, params, defn)| {
+            let span = pest::Span::new(defn, 0, defn.len()).unwrap();
+            let locals = params
+                .iter()
+                .map(|param| {
+                    let node = ExpressionNode::new(T::identifier(param), span);
+                    (param.as_str(), node)
+                })
+                .collect();
+            let result = expander
+                .expand_defn(id, defn, locals, span)
+                .map(|kind| ExpressionNode::new(kind, span));
+            (id, result)
+        })
+        .collect()
+}
+
 /// Collects similar names from the `candidates` list.
 pub fn collect_similar<I>(name: &str, candidates: I) -> Vec<String>
 where
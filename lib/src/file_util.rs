@@ -14,6 +14,8 @@
 
 #![allow(missing_docs)]
 
+#[cfg(test)]
+use std::cell::RefCell;
 use std::fs;
 use std::fs::File;
 use std::io;
@@ -68,6 +70,15 @@ pub fn create_or_reuse_dir(dirname: &Path) -> io::Result<()> {
     }
 }
 
+/// Returns true if the file system containing the `dir` directory appears to
+/// be case-insensitive. The `dir` must be writable.
+pub fn check_case_insensitive_fs(dir: &Path) -> io::Result<bool> {
+    let test_file = tempfile::Builder::new().prefix("icase-").tempfile_in(dir)?;
+    let orig_name = test_file.path().file_name().unwrap().to_string_lossy();
+    let upper_name = orig_name.to_ascii_uppercase();
+    dir.join(upper_name).try_exists()
+}
+
 /// Removes all files in the directory, but not the directory itself.
 ///
 /// The directory must exist, and there should be no sub directories.
@@ -138,24 +149,101 @@ pub fn normalize_path(path: &Path) -> PathBuf {
     }
 }
 
+/// Maximum number of symlinks followed by `resolve_symlinks()`. This matches
+/// the limit of Linux.
+const MAX_SYMLINK_FOLLOWS: usize = 40;
+
+#[derive(Debug, Error)]
+#[error("Too many levels of symbolic links in {path}")]
+pub struct SymlinkLoopError {
+    pub path: PathBuf,
+}
+
+/// Resolves symlinks in the existing part of the absolute `path`, and consumes
+/// `.` and `..` in the same manner as the file system would.
+///
+/// Unlike `fs::canonicalize()`, the path doesn't have to exist. Components
+/// after the first one that can't be read are appended without resolution.
+pub fn resolve_symlinks(path: &Path) -> Result<PathBuf, SymlinkLoopError> {
+    // Remaining components in reverse order
+    let mut pending: Vec<PathBuf> = path
+        .components()
+        .rev()
+        .map(|c| PathBuf::from(c.as_os_str()))
+        .collect();
+    let mut resolved = PathBuf::new();
+    let mut follows = 0;
+    let mut exists = true;
+    while let Some(component) = pending.pop() {
+        match component.components().next() {
+            None | Some(Component::CurDir) => {}
+            Some(Component::ParentDir) => {
+                resolved.pop();
+            }
+            Some(Component::Normal(name)) if exists => {
+                let candidate = resolved.join(name);
+                match candidate.symlink_metadata() {
+                    Ok(metadata) if metadata.is_symlink() => {
+                        follows += 1;
+                        if follows > MAX_SYMLINK_FOLLOWS {
+                            return Err(SymlinkLoopError {
+                                path: path.to_owned(),
+                            });
+                        }
+                        let Ok(target) = fs::read_link(&candidate) else {
+                            resolved = candidate;
+                            exists = false;
+                            continue;
+                        };
+                        if target.is_absolute() {
+                            resolved = PathBuf::new();
+                        }
+                        pending.extend(
+                            target
+                                .components()
+                                .rev()
+                                .map(|c| PathBuf::from(c.as_os_str())),
+                        );
+                    }
+                    Ok(_) => resolved = candidate,
+                    Err(_) => {
+                        resolved = candidate;
+                        exists = false;
+                    }
+                }
+            }
+            Some(c) => resolved.push(c),
+        }
+    }
+    Ok(resolved)
+}
+
 /// Like `NamedTempFile::persist()`, but doesn't try to overwrite the existing
 /// target on Windows.
 pub fn persist_content_addressed_temp_file<P: AsRef<Path>>(
     temp_file: NamedTempFile,
     new_path: P,
 ) -> io::Result<File> {
+    persist_temp_file_keeping_existing(temp_file, new_path.as_ref())
+        .map_err(|PersistError { error, file: _ }| error)
+}
+
+fn persist_temp_file_keeping_existing(
+    temp_file: NamedTempFile,
+    new_path: &Path,
+) -> Result<File, PersistError> {
     if cfg!(windows) {
         // On Windows, overwriting file can fail if the file is opened without
         // FILE_SHARE_DELETE for example. We don't need to take a risk if the
         // file already exists.
-        match temp_file.persist_noclobber(&new_path) {
+        match temp_file.persist_noclobber(new_path) {
             Ok(file) => Ok(file),
-            Err(PersistError { error, file: _ }) => {
+            Err(err) => {
                 if let Ok(existing_file) = File::open(new_path) {
                     // TODO: Update mtime to help GC keep this file
                     Ok(existing_file)
                 } else {
-                    Err(error)
+                    Err(err)
                 }
             }
         }
@@ -163,12 +251,184 @@ pub fn persist_content_addressed_temp_file<P: AsRef<Path>>(
         // On Unix, rename() is atomic and should succeed even if the
         // destination file exists. Checking if the target exists might involve
         // non-atomic operation, so don't use persist_noclobber().
-        temp_file
-            .persist(new_path)
-            .map_err(|PersistError { error, file: _ }| error)
+        temp_file.persist(new_path)
+    }
+}
+
+fn persist_temp_file_replacing_existing(
+    mut temp_file: NamedTempFile,
+    new_path: &Path,
+) -> Result<File, PersistError> {
+    // On Windows, replacing a file fails if it happens to be open without
+    // FILE_SHARE_DELETE (by a concurrent reader or a virus scanner for
+    // example), so retry a few times before giving up.
+    const MAX_ATTEMPTS: usize = if cfg!(windows) { 10 } else { 1 };
+    let mut attempt = 1;
+    loop {
+        match temp_file.persist(new_path) {
+            Ok(file) => return Ok(file),
+            Err(err)
+                if attempt < MAX_ATTEMPTS
+                    && err.error.kind() == io::ErrorKind::PermissionDenied =>
+            {
+                temp_file = err.file;
+                attempt += 1;
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+            Err(err) => return Err(err),
+        }
     }
 }
 
+/// Whether files written by [`persist_content_atomically()`] and
+/// [`persist_atomically_with()`] should be flushed to stable storage.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Durability {
+    /// Leaves it to the OS when to flush the data. The file may be lost on
+    /// power failure, but it will never be observed partially written.
+    #[default]
+    Buffered,
+    /// Syncs the file contents before renaming it into place, and the parent
+    /// directory after.
+    Synced,
+}
+
+/// What to do if the target of an atomic write already exists.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum ExistingTarget {
+    /// The existing file is known to have the same content.
+    Keep,
+    Replace,
+}
+
+/// Low-level file system operations used by atomic writes. Abstracted out so
+/// tests can observe and fail them.
+trait PersistOps {
+    fn sync_file(&mut self, file: &File) -> io::Result<()>;
+    fn sync_dir(&mut self, dir: &Path) -> io::Result<()>;
+    fn rename(
+        &mut self,
+        temp_file: NamedTempFile,
+        new_path: &Path,
+        existing: ExistingTarget,
+    ) -> Result<(), PersistError>;
+}
+
+struct OsPersistOps;
+
+#[cfg(test)]
+thread_local! {
+    /// Directories synced by [`OsPersistOps`] on this thread.
+    static SYNCED_DIRS: RefCell<Vec<PathBuf>> = const { RefCell::new(vec![]) };
+}
+
+/// Returns the directories synced by durable writes on this thread since the
+/// last call.
+#[cfg(test)]
+pub(crate) fn take_synced_dirs() -> Vec<PathBuf> {
+    SYNCED_DIRS.take()
+}
+
+impl PersistOps for OsPersistOps {
+    fn sync_file(&mut self, file: &File) -> io::Result<()> {
+        file.sync_all()
+    }
+
+    fn sync_dir(&mut self, dir: &Path) -> io::Result<()> {
+        #[cfg(test)]
+        SYNCED_DIRS.with_borrow_mut(|dirs| dirs.push(dir.to_owned()));
+        if cfg!(unix) {
+            File::open(dir)?.sync_all()
+        } else {
+            // Directories can't be opened as files on Windows, and renames
+            // are journaled by NTFS anyway.
+            Ok(())
+        }
+    }
+
+    fn rename(
+        &mut self,
+        temp_file: NamedTempFile,
+        new_path: &Path,
+        existing: ExistingTarget,
+    ) -> Result<(), PersistError> {
+        match existing {
+            ExistingTarget::Keep => persist_temp_file_keeping_existing(temp_file, new_path)?,
+            ExistingTarget::Replace => persist_temp_file_replacing_existing(temp_file, new_path)?,
+        };
+        Ok(())
+    }
+}
+
+/// Atomically writes `content` to the file `name` in `dir`, and returns the
+/// path to the file.
+///
+/// The file is assumed to be content-addressed: if it already exists, it must
+/// have the same content, and it may be left untouched on Windows.
+pub fn persist_content_atomically(
+    dir: &Path,
+    name: &str,
+    content: &[u8],
+    durability: Durability,
+) -> Result<PathBuf, PathError> {
+    persist_atomically_impl(
+        &mut OsPersistOps,
+        dir,
+        name,
+        ExistingTarget::Keep,
+        durability,
+        |writer| writer.write_all(content),
+    )
+}
+
+/// Atomically replaces the file `name` in `dir` with the data written by
+/// `write_content`, and returns the path to the file.
+///
+/// The data is first written to a temporary file in `dir`, so readers will
+/// observe either the old or the new content.
+pub fn persist_atomically_with(
+    dir: &Path,
+    name: &str,
+    durability: Durability,
+    write_content: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> Result<PathBuf, PathError> {
+    persist_atomically_impl(
+        &mut OsPersistOps,
+        dir,
+        name,
+        ExistingTarget::Replace,
+        durability,
+        write_content,
+    )
+}
+
+fn persist_atomically_impl(
+    ops: &mut dyn PersistOps,
+    dir: &Path,
+    name: &str,
+    existing: ExistingTarget,
+    durability: Durability,
+    write_content: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> Result<PathBuf, PathError> {
+    let mut temp_file = NamedTempFile::new_in(dir).context(dir)?;
+    let mut writer = io::BufWriter::new(temp_file.as_file_mut());
+    let result = write_content(&mut writer).and_then(|()| writer.flush());
+    drop(writer);
+    result.context(temp_file.path())?;
+    let new_path = dir.join(name);
+    if durability == Durability::Synced {
+        ops.sync_file(temp_file.as_file())
+            .context(temp_file.path())?;
+    }
+    ops.rename(temp_file, &new_path, existing)
+        .map_err(|PersistError { error, file: _ }| error)
+        .context(&new_path)?;
+    if durability == Durability::Synced {
+        ops.sync_dir(dir).context(dir)?;
+    }
+    Ok(new_path)
+}
+
 /// Reads from an async source and writes to a sync destination. Does not spawn
 /// a task, so writes will block.
 pub async fn copy_async_to_sync<R: AsyncRead, W: Write + ?Sized>(
@@ -313,6 +573,88 @@ mod tests {
         assert!(persist_content_addressed_temp_file(temp_file, &target).is_ok());
     }
 
+    /// Records calls.
+    #[derive(Default)]
+    struct RecordingPersistOps {
+        calls: Vec<String>,
+    }
+
+    impl PersistOps for RecordingPersistOps {
+        fn sync_file(&mut self, _file: &File) -> io::Result<()> {
+            self.calls.push("sync_file".to_owned());
+            Ok(())
+        }
+
+        fn sync_dir(&mut self, _dir: &Path) -> io::Result<()> {
+            self.calls.push("sync_dir".to_owned());
+            Ok(())
+        }
+
+        fn rename(
+            &mut self,
+            temp_file: NamedTempFile,
+            new_path: &Path,
+            existing: ExistingTarget,
+        ) -> Result<(), PersistError> {
+            self.calls.push("rename".to_owned());
+            OsPersistOps.rename(temp_file, new_path, existing)
+        }
+    }
+
+    #[test]
+    fn test_persist_content_atomically_target_exists() {
+        let temp_dir = new_temp_dir();
+        fs::write(temp_dir.path().join("file"), b"contents").unwrap();
+        let path =
+            persist_content_atomically(temp_dir.path(), "file", b"contents", Durability::Buffered)
+                .unwrap();
+        assert_eq!(path, temp_dir.path().join("file"));
+        assert_eq!(fs::read(&path).unwrap(), b"contents");
+    }
+
+    #[test]
+    fn test_persist_atomically_with_overwrites_existing() {
+        let temp_dir = new_temp_dir();
+        fs::write(temp_dir.path().join("file"), b"old").unwrap();
+        let path = persist_atomically_with(temp_dir.path(), "file", Durability::Synced, |w| {
+            w.write_all(b"new ")?;
+            w.write_all(b"contents")
+        })
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new contents");
+        // No temporary files should be left behind
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_persist_atomically_write_error() {
+        let temp_dir = new_temp_dir();
+        let err = persist_atomically_with(temp_dir.path(), "file", Durability::Buffered, |_| {
+            Err(io::Error::other("failed"))
+        })
+        .unwrap_err();
+        assert_eq!(err.path.parent(), Some(temp_dir.path()));
+        assert!(!temp_dir.path().join("file").exists());
+    }
+
+    #[test_case(Durability::Buffered, &["rename"] ; "buffered")]
+    #[test_case(Durability::Synced, &["sync_file", "rename", "sync_dir"] ; "synced")]
+    fn test_persist_atomically_sync(durability: Durability, expected_calls: &[&str]) {
+        let temp_dir = new_temp_dir();
+        let mut ops = RecordingPersistOps::default();
+        persist_atomically_impl(
+            &mut ops,
+            temp_dir.path(),
+            "file",
+            ExistingTarget::Replace,
+            durability,
+            |w| w.write_all(b"contents"),
+        )
+        .unwrap();
+        assert_eq!(ops.calls, expected_calls);
+        assert_eq!(fs::read(temp_dir.path().join("file")).unwrap(), b"contents");
+    }
+
     #[test]
     fn test_copy_async_to_sync_small() {
         let input = b"hello";
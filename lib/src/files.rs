@@ -15,6 +15,7 @@
 #![allow(missing_docs)]
 
 use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::iter;
 use std::mem;
@@ -23,10 +24,18 @@ use bstr::BStr;
 use bstr::BString;
 use itertools::Itertools as _;
 
+use crate::backend::BackendResult;
+use crate::backend::CommitId;
+use crate::commit::Commit;
 use crate::diff::Diff;
 use crate::diff::DiffHunk;
 use crate::diff::DiffHunkKind;
 use crate::merge::Merge;
+use crate::merged_tree::MergedTree;
+use crate::repo::Repo;
+use crate::repo_path::RepoPath;
+use crate::repo_path::RepoPathBuf;
+use crate::revset::RevsetExpression;
 
 /// A diff line which may contain small hunks originating from both sides.
 #[derive(PartialEq, Eq, Clone, Debug)]
@@ -409,6 +418,124 @@ fn resolve_diff_hunks<'a, 'input>(
     })
 }
 
+/// Finds the most recent ancestor of `commit` (inclusive) that modified each
+/// of the `paths`.
+///
+/// A commit is considered to have modified a path if the value at the path
+/// differs from the value in all of its parents. If the value matches one of
+/// the parents, the history of that parent is followed instead, so changes
+/// brought in by merges are attributed to the commit that originally made
+/// them. Paths which don't exist in any commit along the walk are omitted
+/// from the result.
+///
+/// This walks the history once for all paths, so it's much cheaper than
+/// querying the history of each path separately.
+pub fn last_modified(
+    repo: &dyn Repo,
+    commit: &Commit,
+    paths: &[RepoPathBuf],
+) -> BackendResult<HashMap<RepoPathBuf, CommitId>> {
+    let mut result = HashMap::new();
+    // Paths whose value at the commit is still to be traced back to its origin
+    let mut pending: HashMap<CommitId, Vec<RepoPathBuf>> = HashMap::new();
+    let unique_paths = paths.iter().unique().cloned().collect_vec();
+    if unique_paths.is_empty() {
+        return Ok(result);
+    }
+    pending.insert(commit.id().clone(), unique_paths);
+
+    let revset = RevsetExpression::commit(commit.id().clone())
+        .ancestors()
+        .evaluate(repo)
+        .map_err(|err| err.into_backend_error())?;
+    // The index order guarantees that a commit is visited after all its
+    // descendants, so all paths pending at a commit have been collected by
+    // the time it's visited.
+    for commit_id in revset.iter() {
+        let commit_id = commit_id.map_err(|err| err.into_backend_error())?;
+        let Some(mut remaining) = pending.remove(&commit_id) else {
+            continue;
+        };
+        let commit = repo.store().get_commit(&commit_id)?;
+        let mut tree = None;
+        for parent in commit.parents() {
+            if remaining.is_empty() {
+                break;
+            }
+            let parent = parent?;
+            let unchanged = if parent.tree_id() == commit.tree_id() {
+                mem::take(&mut remaining)
+            } else {
+                let tree = match &tree {
+                    Some(tree) => tree,
+                    None => tree.insert(commit.tree()?),
+                };
+                let parent_tree = parent.tree()?;
+                let mut unchanged = vec![];
+                let mut changed = vec![];
+                for path in remaining {
+                    if is_path_unchanged(tree, &parent_tree, &path)? {
+                        unchanged.push(path);
+                    } else {
+                        changed.push(path);
+                    }
+                }
+                remaining = changed;
+                unchanged
+            };
+            if !unchanged.is_empty() {
+                pending
+                    .entry(parent.id().clone())
+                    .or_default()
+                    .extend(unchanged);
+            }
+        }
+        // Root commits have no parents, so the paths don't exist there.
+        if !commit.parent_ids().is_empty() {
+            for path in remaining {
+                result.insert(path, commit_id.clone());
+            }
+        }
+        if pending.is_empty() {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+/// Returns true if the value at `path` is the same in both trees. Subtrees
+/// along the path are compared by id, so identical directories are never
+/// read.
+fn is_path_unchanged(
+    tree: &MergedTree,
+    parent_tree: &MergedTree,
+    path: &RepoPath,
+) -> BackendResult<bool> {
+    let Some((dir, basename)) = path.split() else {
+        return Ok(tree.id() == parent_tree.id());
+    };
+    let mut current_tree = tree.clone();
+    let mut current_parent_tree = parent_tree.clone();
+    for name in dir.components() {
+        if current_tree.id() == current_parent_tree.id() {
+            return Ok(true);
+        }
+        match (
+            current_tree.sub_tree(name)?,
+            current_parent_tree.sub_tree(name)?,
+        ) {
+            (Some(sub_tree), Some(parent_sub_tree)) => {
+                current_tree = sub_tree;
+                current_parent_tree = parent_sub_tree;
+            }
+            (None, None) => return Ok(true),
+            _ => return Ok(tree.path_value(path)? == parent_tree.path_value(path)?),
+        }
+    }
+    Ok(current_tree.id() == current_parent_tree.id()
+        || current_tree.value(basename) == current_parent_tree.value(basename))
+}
+
 #[cfg(test)]
 mod tests {
     use indoc::indoc;
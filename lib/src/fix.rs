@@ -25,7 +25,9 @@ use jj_lib::backend::BackendError;
 use jj_lib::backend::CommitId;
 use jj_lib::backend::FileId;
 use jj_lib::backend::TreeValue;
+use jj_lib::commit::Commit;
 use jj_lib::matchers::Matcher;
+use jj_lib::merge::MergedTreeValue;
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::merged_tree::MergedTreeBuilder;
 use jj_lib::merged_tree::TreeDiffEntry;
@@ -34,6 +36,7 @@ use jj_lib::repo::Repo as _;
 use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::revset::RevsetExpression;
 use jj_lib::revset::RevsetIteratorExt as _;
+use jj_lib::rewrite::merge_commit_trees;
 use jj_lib::store::Store;
 use jj_lib::tree::Tree;
 use pollster::FutureExt as _;
@@ -56,6 +59,11 @@ pub struct FileToFix {
     ///  - Update parts of the file's content that should be derived from the
     ///    file's path.
     pub repo_path: RepoPathBuf,
+
+    /// Indices into the `changed_since` bases passed to [`fix_files()`] for
+    /// which the file is unchanged, in ascending order. A FileFixer should
+    /// not apply tools scoped to those bases to the file.
+    pub unchanged_since: Vec<usize>,
 }
 
 /// Error fixing files.
@@ -173,10 +181,16 @@ where
 /// that the fixes are not lost. This will never result in new conflicts. Files
 /// with existing conflicts are updated on all sides of the conflict, which
 /// can potentially increase or decrease the number of conflict markers.
+///
+/// Each entry of `changed_since` is a set of base commits. A file is marked
+/// as unchanged since the `i`th entry (see [`FileToFix::unchanged_since`]) if
+/// its value in the fixed commit is the same as in the merge base of the
+/// commit and the base commits.
 pub fn fix_files(
     root_commits: Vec<CommitId>,
     matcher: &dyn Matcher,
     include_unchanged_files: bool,
+    changed_since: &[Vec<CommitId>],
     repo_mut: &mut MutableRepo,
     file_fixer: &mut impl FileFixer,
 ) -> Result<FixSummary, FixError> {
@@ -210,9 +224,15 @@ pub fn fix_files(
     );
 
     let mut unique_files_to_fix: HashSet<FileToFix> = HashSet::new();
-    let mut commit_paths: HashMap<CommitId, HashSet<RepoPathBuf>> = HashMap::new();
+    // Maps each path to fix in a commit to its `FileToFix::unchanged_since`.
+    let mut commit_paths: HashMap<CommitId, HashMap<RepoPathBuf, Vec<usize>>> = HashMap::new();
+    let mut base_tree_cache = BaseTreeCache::default();
     for commit in commits.iter().rev() {
-        let mut paths: HashSet<RepoPathBuf> = HashSet::new();
+        let mut paths: HashMap<RepoPathBuf, Vec<usize>> = HashMap::new();
+        let base_trees: Vec<MergedTree> = changed_since
+            .iter()
+            .map(|base_ids| base_tree_cache.get(repo_mut, commit, base_ids))
+            .try_collect()?;
 
         // If --include-unchanged-files, we always fix every matching file in the tree.
         // Otherwise, we fix the matching changed files in this commit, plus any that
@@ -223,7 +243,13 @@ pub fn fix_files(
         } else {
             for parent_id in commit.parent_ids() {
                 if let Some(parent_paths) = commit_paths.get(parent_id) {
-                    paths.extend(parent_paths.iter().cloned());
+                    // Paths propagated from ancestors keep the scopes they were
+                    // fixed with, so the same fixes are applied here.
+                    for (path, unchanged_since) in parent_paths {
+                        paths
+                            .entry(path.clone())
+                            .or_insert_with(|| unchanged_since.clone());
+                    }
                 }
             }
             commit.parent_tree(repo_mut)?
@@ -237,6 +263,7 @@ pub fn fix_files(
             }) = diff_stream.next().await
             {
                 let (_before, after) = values?;
+                let unchanged_since = unchanged_since_indices(&base_trees, &repo_path, &after)?;
                 // Deleted files have no file content to fix, and they have no terms in `after`,
                 // so we don't add any files-to-fix for them. Conflicted files produce one
                 // file-to-fix for each side of the conflict.
@@ -254,9 +281,10 @@ pub fn fix_files(
                         let file_to_fix = FileToFix {
                             file_id: id.clone(),
                             repo_path: repo_path.clone(),
+                            unchanged_since: unchanged_since.clone(),
                         };
-                        unique_files_to_fix.insert(file_to_fix.clone());
-                        paths.insert(repo_path.clone());
+                        unique_files_to_fix.insert(file_to_fix);
+                        paths.insert(repo_path.clone(), unchanged_since.clone());
                     }
                 }
             }
@@ -288,7 +316,7 @@ pub fn fix_files(
         let old_tree = rewriter.old_commit().tree()?;
         let mut tree_builder = MergedTreeBuilder::new(old_tree.id().clone());
         let mut has_changes = false;
-        for repo_path in repo_paths {
+        for (repo_path, unchanged_since) in repo_paths {
             let old_value = old_tree.path_value(repo_path)?;
             let new_value = old_value.map(|old_term| {
                 if let Some(TreeValue::File {
@@ -300,6 +328,7 @@ pub fn fix_files(
                     let file_to_fix = FileToFix {
                         file_id: id.clone(),
                         repo_path: repo_path.clone(),
+                        unchanged_since: unchanged_since.clone(),
                     };
                     if let Some(new_id) = fixed_file_ids.get(&file_to_fix) {
                         return Some(TreeValue::File {
@@ -337,3 +366,54 @@ pub fn fix_files(
     tracing::debug!(?summary);
     Ok(summary)
 }
+
+/// Caches the trees of the merge bases between fixed commits and the
+/// `changed_since` bases, keyed by the merge base commit ids.
+#[derive(Default)]
+struct BaseTreeCache {
+    trees: HashMap<Vec<CommitId>, MergedTree>,
+}
+
+impl BaseTreeCache {
+    /// Returns the tree of the merge base of `commit` and `base_ids`.
+    fn get(
+        &mut self,
+        repo: &MutableRepo,
+        commit: &Commit,
+        base_ids: &[CommitId],
+    ) -> Result<MergedTree, FixError> {
+        let merge_base_ids: Vec<CommitId> = RevsetExpression::commit(commit.id().clone())
+            .ancestors()
+            .intersection(&RevsetExpression::commits(base_ids.to_vec()).ancestors())
+            .heads()
+            .evaluate(repo)?
+            .iter()
+            .try_collect()?;
+        if let Some(tree) = self.trees.get(&merge_base_ids) {
+            return Ok(tree.clone());
+        }
+        let merge_bases: Vec<Commit> = merge_base_ids
+            .iter()
+            .map(|id| repo.store().get_commit(id))
+            .try_collect()?;
+        let tree = merge_commit_trees(repo, &merge_bases)?;
+        self.trees.insert(merge_base_ids, tree.clone());
+        Ok(tree)
+    }
+}
+
+/// Returns the indices of the `base_trees` in which `repo_path` has the same
+/// value as `value`.
+fn unchanged_since_indices(
+    base_trees: &[MergedTree],
+    repo_path: &RepoPathBuf,
+    value: &MergedTreeValue,
+) -> Result<Vec<usize>, BackendError> {
+    let mut indices = vec![];
+    for (index, base_tree) in base_trees.iter().enumerate() {
+        if base_tree.path_value(repo_path)? == *value {
+            indices.push(index);
+        }
+    }
+    Ok(indices)
+}
@@ -26,6 +26,7 @@ use std::path::PathBuf;
 
 use crate::config::ConfigGetError;
 use crate::settings::UserSettings;
+use crate::settings_schema::get_or_default;
 
 /// Config for Watchman filesystem monitor (<https://facebook.github.io/watchman/>).
 #[derive(Eq, PartialEq, Clone, Debug)]
@@ -58,9 +59,9 @@ impl FsmonitorSettings {
     /// Creates an `FsmonitorSettings` from a `config`.
     pub fn from_settings(settings: &UserSettings) -> Result<FsmonitorSettings, ConfigGetError> {
         let name = "core.fsmonitor";
-        match settings.get_string(name)?.as_ref() {
+        match get_or_default::<String>(settings.config(), name)?.as_ref() {
             "watchman" => Ok(Self::Watchman(WatchmanConfig {
-                register_trigger: settings.get_bool("core.watchman.register-snapshot-trigger")?,
+                register_trigger: settings.core_watchman_register_snapshot_trigger()?,
             })),
             "test" => Err(ConfigGetError::Type {
                 name: name.to_owned(),
@@ -40,6 +40,11 @@ use crate::commit::Commit;
 use crate::file_util::IoResultExt as _;
 use crate::file_util::PathError;
 use crate::git_backend::GitBackend;
+pub use crate::git_refs::is_special_git_remote;
+pub use crate::git_refs::parse_git_ref;
+pub use crate::git_refs::GitRefKind;
+pub use crate::git_refs::REMOTE_NAME_FOR_LOCAL_GIT_REPO;
+pub use crate::git_refs::RESERVED_REMOTE_REF_NAMESPACE;
 use crate::git_subprocess::GitSubprocessContext;
 use crate::git_subprocess::GitSubprocessError;
 use crate::matchers::EverythingMatcher;
@@ -68,10 +73,6 @@ use crate::store::Store;
 use crate::str_util::StringPattern;
 use crate::view::View;
 
-/// Reserved remote name for the backing Git repo.
-pub const REMOTE_NAME_FOR_LOCAL_GIT_REPO: &RemoteName = RemoteName::new("git");
-/// Git ref prefix that would conflict with the reserved "git" remote.
-pub const RESERVED_REMOTE_REF_NAMESPACE: &str = "refs/remotes/git/";
 /// Ref name used as a placeholder to unset HEAD without a commit.
 const UNBORN_ROOT_REF_NAME: &str = "refs/jj/root";
 /// Dummy file to be added to the index to indicate that the user is editing a
@@ -99,13 +100,6 @@ fn validate_remote_name(name: &RemoteName) -> Result<(), GitRemoteNameError> {
     }
 }
 
-/// Type of Git ref to be imported or exported.
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub enum GitRefKind {
-    Bookmark,
-    Tag,
-}
-
 /// Stats from a git push
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub struct GitPushStats {
@@ -226,35 +220,6 @@ impl<'a> RefToPush<'a> {
     }
 }
 
-/// Translates Git ref name to jj's `name@remote` symbol. Returns `None` if the
-/// ref cannot be represented in jj.
-pub fn parse_git_ref(full_name: &GitRefName) -> Option<(GitRefKind, RemoteRefSymbol<'_>)> {
-    if let Some(name) = full_name.as_str().strip_prefix("refs/heads/") {
-        // Git CLI says 'HEAD' is not a valid branch name
-        if name == "HEAD" {
-            return None;
-        }
-        let name = RefName::new(name);
-        let remote = REMOTE_NAME_FOR_LOCAL_GIT_REPO;
-        Some((GitRefKind::Bookmark, RemoteRefSymbol { name, remote }))
-    } else if let Some(remote_and_name) = full_name.as_str().strip_prefix("refs/remotes/") {
-        let (remote, name) = remote_and_name.split_once('/')?;
-        // "refs/remotes/origin/HEAD" isn't a real remote-tracking branch
-        if remote == REMOTE_NAME_FOR_LOCAL_GIT_REPO || name == "HEAD" {
-            return None;
-        }
-        let name = RefName::new(name);
-        let remote = RemoteName::new(remote);
-        Some((GitRefKind::Bookmark, RemoteRefSymbol { name, remote }))
-    } else if let Some(name) = full_name.as_str().strip_prefix("refs/tags/") {
-        let name = RefName::new(name);
-        let remote = REMOTE_NAME_FOR_LOCAL_GIT_REPO;
-        Some((GitRefKind::Tag, RemoteRefSymbol { name, remote }))
-    } else {
-        None
-    }
-}
-
 fn to_git_ref_name(kind: GitRefKind, symbol: RemoteRefSymbol<'_>) -> Option<GitRefNameBuf> {
     let RemoteRefSymbol { name, remote } = symbol;
     let name = name.as_str();
@@ -566,7 +531,7 @@ fn abandon_unreachable_commits(
         .map_err(|err| err.into_backend_error())?;
     for id in &abandoned_commit_ids {
         let commit = mut_repo.store().get_commit(id)?;
-        mut_repo.record_abandoned_commit(&commit);
+        mut_repo.record_externally_abandoned_commit(&commit);
     }
     Ok(abandoned_commit_ids)
 }
@@ -1602,14 +1567,6 @@ impl GitRemoteManagementError {
     }
 }
 
-/// Determine, by its name, if a remote refers to the special local-only "git"
-/// remote that is used in the Git backend.
-///
-/// This function always returns false if the "git" feature is not enabled.
-pub fn is_special_git_remote(remote: &RemoteName) -> bool {
-    remote == REMOTE_NAME_FOR_LOCAL_GIT_REPO
-}
-
 fn default_fetch_refspec(remote: &RemoteName) -> String {
     format!(
         "+refs/heads/*:refs/remotes/{remote}/*",
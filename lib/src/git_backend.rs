@@ -69,6 +69,7 @@ use crate::backend::MillisSinceEpoch;
 use crate::backend::SecureSig;
 use crate::backend::Signature;
 use crate::backend::SigningFn;
+use crate::backend::StorageStats;
 use crate::backend::SymlinkId;
 use crate::backend::Timestamp;
 use crate::backend::Tree;
@@ -89,6 +90,7 @@ use crate::repo_path::RepoPathComponentBuf;
 use crate::settings::GitSettings;
 use crate::settings::UserSettings;
 use crate::stacked_table::MutableTable;
+use crate::stacked_table::QuarantinedSegment;
 use crate::stacked_table::ReadonlyTable;
 use crate::stacked_table::TableSegment as _;
 use crate::stacked_table::TableStore;
@@ -350,15 +352,82 @@ impl GitBackend {
         self.base_repo.work_dir()
     }
 
+    /// Builds the unsigned Git commit object for `contents`. Conflicted root
+    /// trees are written to the object store.
+    fn to_git_commit(
+        &self,
+        locked_repo: &gix::Repository,
+        contents: &Commit,
+    ) -> BackendResult<gix::objs::Commit> {
+        let git_tree_id = match &contents.root_tree {
+            MergedTreeId::Legacy(tree_id) => validate_git_object_id(tree_id)?,
+            MergedTreeId::Merge(tree_ids) => match tree_ids.as_resolved() {
+                Some(tree_id) => validate_git_object_id(tree_id)?,
+                None => write_tree_conflict(locked_repo, tree_ids)?,
+            },
+        };
+        if contents.parents.is_empty() {
+            return Err(BackendError::Other(
+                "Cannot write a commit with no parents".into(),
+            ));
+        }
+        let mut parents = SmallVec::new();
+        for parent_id in &contents.parents {
+            if *parent_id == self.root_commit_id {
+                // Git doesn't have a root commit, so if the parent is the root commit, we don't
+                // add it to the list of parents to write in the Git commit. We also check that
+                // there are no other parents since Git cannot represent a merge between a root
+                // commit and another commit.
+                if contents.parents.len() > 1 {
+                    return Err(BackendError::Unsupported(
+                        "The Git backend does not support creating merge commits with the root \
+                         commit as one of the parents."
+                            .to_owned(),
+                    ));
+                }
+            } else {
+                parents.push(validate_git_object_id(parent_id)?);
+            }
+        }
+        let mut extra_headers = vec![];
+        if let MergedTreeId::Merge(tree_ids) = &contents.root_tree {
+            if !tree_ids.is_resolved() {
+                let value = tree_ids.iter().map(|id| id.hex()).join(" ").into_bytes();
+                extra_headers.push((
+                    BString::new(JJ_TREES_COMMIT_HEADER.to_vec()),
+                    BString::new(value),
+                ));
+            }
+        }
+        if self.write_change_id_header {
+            extra_headers.push((
+                BString::new(CHANGE_ID_COMMIT_HEADER.to_vec()),
+                BString::new(contents.change_id.reverse_hex().into()),
+            ));
+        }
+
+        Ok(gix::objs::Commit {
+            message: contents.description.clone().into(),
+            tree: git_tree_id,
+            author: signature_to_git(&contents.author).into(),
+            committer: signature_to_git(&contents.committer).into(),
+            encoding: None,
+            parents,
+            extra_headers,
+        })
+    }
+
     fn cached_extra_metadata_table(&self) -> BackendResult<Arc<ReadonlyTable>> {
         let mut locked_head = self.cached_extra_metadata.lock().unwrap();
         match locked_head.as_ref() {
             Some(head) => Ok(head.clone()),
             None => {
-                let table = self
+                let recovered = self
                     .extra_metadata_store
-                    .get_head()
+                    .get_head_with_recovery()
                     .map_err(GitBackendError::ReadMetadata)?;
+                warn_quarantined_extra_metadata(&recovered.quarantined);
+                let table = recovered.value;
                 *locked_head = Some(table.clone());
                 Ok(table)
             }
@@ -366,11 +435,12 @@ impl GitBackend {
     }
 
     fn read_extra_metadata_table_locked(&self) -> BackendResult<(Arc<ReadonlyTable>, FileLock)> {
-        let table = self
+        let recovered = self
             .extra_metadata_store
-            .get_head_locked()
+            .get_head_locked_with_recovery()
             .map_err(GitBackendError::ReadMetadata)?;
-        Ok(table)
+        warn_quarantined_extra_metadata(&recovered.quarantined);
+        Ok(recovered.value)
     }
 
     fn save_extra_metadata_table(
@@ -836,6 +906,21 @@ fn recreate_no_gc_refs(
     Ok(())
 }
 
+/// Returns the total size of the files in `dir` and its subdirectories.
+fn dir_size(dir: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in dir.read_dir()? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
 fn run_git_gc(program: &OsStr, git_dir: &Path) -> Result<(), GitGcError> {
     let mut git = Command::new(program);
     git.arg("--git-dir=."); // turn off discovery
@@ -931,6 +1016,20 @@ fn import_extra_metadata_entries_from_heads(
     Ok(())
 }
 
+/// Logs the extra metadata segments that were lost because they were corrupt.
+/// Commits whose metadata was lost are re-imported from Git when they're read
+/// next time, which may assign them different change ids.
+fn warn_quarantined_extra_metadata(quarantined: &[QuarantinedSegment]) {
+    for segment in quarantined {
+        tracing::warn!(
+            name = %segment.name,
+            quarantine_path = ?segment.quarantine_path,
+            error = %segment.error,
+            "quarantined corrupt extra metadata segment"
+        );
+    }
+}
+
 impl Debug for GitBackend {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
         f.debug_struct("GitBackend")
@@ -1244,56 +1343,7 @@ impl Backend for GitBackend {
         assert!(contents.secure_sig.is_none(), "commit.secure_sig was set");
 
         let locked_repo = self.lock_git_repo();
-        let git_tree_id = match &contents.root_tree {
-            MergedTreeId::Legacy(tree_id) => validate_git_object_id(tree_id)?,
-            MergedTreeId::Merge(tree_ids) => match tree_ids.as_resolved() {
-                Some(tree_id) => validate_git_object_id(tree_id)?,
-                None => write_tree_conflict(&locked_repo, tree_ids)?,
-            },
-        };
-        let author = signature_to_git(&contents.author);
-        let mut committer = signature_to_git(&contents.committer);
-        let message = &contents.description;
-        if contents.parents.is_empty() {
-            return Err(BackendError::Other(
-                "Cannot write a commit with no parents".into(),
-            ));
-        }
-        let mut parents = SmallVec::new();
-        for parent_id in &contents.parents {
-            if *parent_id == self.root_commit_id {
-                // Git doesn't have a root commit, so if the parent is the root commit, we don't
-                // add it to the list of parents to write in the Git commit. We also check that
-                // there are no other parents since Git cannot represent a merge between a root
-                // commit and another commit.
-                if contents.parents.len() > 1 {
-                    return Err(BackendError::Unsupported(
-                        "The Git backend does not support creating merge commits with the root \
-                         commit as one of the parents."
-                            .to_owned(),
-                    ));
-                }
-            } else {
-                parents.push(validate_git_object_id(parent_id)?);
-            }
-        }
-        let mut extra_headers = vec![];
-        if let MergedTreeId::Merge(tree_ids) = &contents.root_tree {
-            if !tree_ids.is_resolved() {
-                let value = tree_ids.iter().map(|id| id.hex()).join(" ").into_bytes();
-                extra_headers.push((
-                    BString::new(JJ_TREES_COMMIT_HEADER.to_vec()),
-                    BString::new(value),
-                ));
-            }
-        }
-        if self.write_change_id_header {
-            extra_headers.push((
-                BString::new(CHANGE_ID_COMMIT_HEADER.to_vec()),
-                BString::new(contents.change_id.reverse_hex().into()),
-            ));
-        }
-
+        let mut git_commit = self.to_git_commit(&locked_repo, &contents)?;
         let extras = serialize_extras(&contents);
 
         // If two writers write commits of the same id with different metadata, they
@@ -1304,15 +1354,7 @@ impl Backend for GitBackend {
         // repository is rsync-ed.
         let (table, table_lock) = self.read_extra_metadata_table_locked()?;
         let id = loop {
-            let mut commit = gix::objs::Commit {
-                message: message.to_owned().into(),
-                tree: git_tree_id,
-                author: author.into(),
-                committer: committer.into(),
-                encoding: None,
-                parents: parents.clone(),
-                extra_headers: extra_headers.clone(),
-            };
+            let mut commit = git_commit.clone();
 
             if let Some(sign) = &mut sign_with {
                 // we don't use gix pool, but at least use their heuristic
@@ -1341,7 +1383,7 @@ impl Backend for GitBackend {
                 Some(existing_extras) if existing_extras != extras => {
                     // It's possible a commit already exists with the same commit id but different
                     // change id. Adjust the timestamp until this is no longer the case.
-                    committer.time.seconds -= 1;
+                    git_commit.committer.time.seconds -= 1;
                 }
                 _ => break CommitId::from_bytes(git_id.as_bytes()),
             }
@@ -1355,13 +1397,69 @@ impl Backend for GitBackend {
 
         // Update the signature to match the one that was actually written to the object
         // store
-        contents.committer.timestamp.timestamp = MillisSinceEpoch(committer.time.seconds * 1000);
+        contents.committer.timestamp.timestamp =
+            MillisSinceEpoch(git_commit.committer.time.seconds * 1000);
         let mut mut_table = table.start_mutation();
         mut_table.add_entry(id.to_bytes(), extras);
         self.save_extra_metadata_table(mut_table, &table_lock)?;
         Ok((id, contents))
     }
 
+    fn compute_commit_id(&self, contents: &mut Commit) -> Option<CommitId> {
+        assert!(contents.secure_sig.is_none(), "commit.secure_sig was set");
+        // Writing the Git object has no permanent effect on the repo, and is
+        // cheap compared to updating the refs and the extra metadata table.
+        let locked_repo = self.lock_git_repo();
+        let commit = self.to_git_commit(&locked_repo, contents).ok()?;
+        let git_id = locked_repo.write_object(&commit).ok()?;
+        let table = self.cached_extra_metadata_table().ok()?;
+        if let Some(existing_extras) = table.get_value(git_id.as_bytes()) {
+            if existing_extras != serialize_extras(contents) {
+                // The timestamp has to be adjusted, which write_commit() takes
+                // care of.
+                return None;
+            }
+        }
+        // Git stores the timestamps in seconds
+        contents.committer.timestamp.timestamp =
+            MillisSinceEpoch(commit.committer.time.seconds * 1000);
+        Some(CommitId::from_bytes(git_id.as_bytes()))
+    }
+
+    async fn write_commits_batch(&self, commits: Vec<(CommitId, Commit)>) -> BackendResult<()> {
+        // The Git objects were written by compute_commit_id(). The table lock is
+        // held while updating the refs for the same reason as in write_commit().
+        let locked_repo = self.lock_git_repo();
+        let (table, table_lock) = self.read_extra_metadata_table_locked()?;
+        let mut mut_table = table.start_mutation();
+        for (id, contents) in &commits {
+            let extras = serialize_extras(contents);
+            match table.get_value(id.as_bytes()) {
+                Some(existing_extras) if existing_extras != extras => {
+                    return Err(BackendError::Other(
+                        format!(
+                            "Commit {id} was concurrently written with different metadata",
+                            id = id.hex()
+                        )
+                        .into(),
+                    ));
+                }
+                _ => mut_table.add_entry(id.to_bytes(), extras),
+            }
+        }
+        locked_repo
+            .edit_references(
+                commits
+                    .iter()
+                    .map(|(id, _)| id)
+                    .unique()
+                    .map(to_no_gc_ref_update),
+            )
+            .map_err(|err| BackendError::Other(Box::new(err)))?;
+        self.save_extra_metadata_table(mut_table, &table_lock)?;
+        Ok(())
+    }
+
     fn get_copy_records(
         &self,
         paths: Option<&[RepoPathBuf]>,
@@ -1441,6 +1539,17 @@ impl Backend for GitBackend {
         Ok(Box::pin(futures::stream::iter(records)))
     }
 
+    fn storage_stats(&self) -> BackendResult<Option<StorageStats>> {
+        // Packed objects can't be counted without reading the pack indexes, so
+        // only the size of the object database is reported.
+        let objects_dir = self.git_repo_path().join("objects");
+        let size_bytes = dir_size(&objects_dir).map_err(|err| BackendError::Other(err.into()))?;
+        Ok(Some(StorageStats {
+            num_objects: None,
+            size_bytes: Some(size_bytes),
+        }))
+    }
+
     #[tracing::instrument(skip(self, index))]
     fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
         let git_repo = self.lock_git_repo();
@@ -0,0 +1,72 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mapping between Git ref names and jj symbols.
+//!
+//! This doesn't depend on Git itself, so it's available even if the "git"
+//! feature is disabled. The items are re-exported from the `git` module.
+
+use crate::ref_name::GitRefName;
+use crate::ref_name::RefName;
+use crate::ref_name::RemoteName;
+use crate::ref_name::RemoteRefSymbol;
+
+/// Reserved remote name for the backing Git repo.
+pub const REMOTE_NAME_FOR_LOCAL_GIT_REPO: &RemoteName = RemoteName::new("git");
+/// Git ref prefix that would conflict with the reserved "git" remote.
+pub const RESERVED_REMOTE_REF_NAMESPACE: &str = "refs/remotes/git/";
+
+/// Type of Git ref to be imported or exported.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum GitRefKind {
+    /// Branch, which is mapped to a bookmark.
+    Bookmark,
+    /// Tag.
+    Tag,
+}
+
+/// Translates Git ref name to jj's `name@remote` symbol. Returns `None` if the
+/// ref cannot be represented in jj.
+pub fn parse_git_ref(full_name: &GitRefName) -> Option<(GitRefKind, RemoteRefSymbol<'_>)> {
+    if let Some(name) = full_name.as_str().strip_prefix("refs/heads/") {
+        // Git CLI says 'HEAD' is not a valid branch name
+        if name == "HEAD" {
+            return None;
+        }
+        let name = RefName::new(name);
+        let remote = REMOTE_NAME_FOR_LOCAL_GIT_REPO;
+        Some((GitRefKind::Bookmark, RemoteRefSymbol { name, remote }))
+    } else if let Some(remote_and_name) = full_name.as_str().strip_prefix("refs/remotes/") {
+        let (remote, name) = remote_and_name.split_once('/')?;
+        // "refs/remotes/origin/HEAD" isn't a real remote-tracking branch
+        if remote == REMOTE_NAME_FOR_LOCAL_GIT_REPO || name == "HEAD" {
+            return None;
+        }
+        let name = RefName::new(name);
+        let remote = RemoteName::new(remote);
+        Some((GitRefKind::Bookmark, RemoteRefSymbol { name, remote }))
+    } else if let Some(name) = full_name.as_str().strip_prefix("refs/tags/") {
+        let name = RefName::new(name);
+        let remote = REMOTE_NAME_FOR_LOCAL_GIT_REPO;
+        Some((GitRefKind::Tag, RemoteRefSymbol { name, remote }))
+    } else {
+        None
+    }
+}
+
+/// Determine, by its name, if a remote refers to the special local-only "git"
+/// remote that is used in the Git backend.
+pub fn is_special_git_remote(remote: &RemoteName) -> bool {
+    remote == REMOTE_NAME_FOR_LOCAL_GIT_REPO
+}
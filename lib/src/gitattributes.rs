@@ -0,0 +1,348 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Lookup of attributes defined in `.gitattributes` files.
+
+use std::collections::HashMap;
+use std::iter;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use ignore::gitignore;
+use pollster::FutureExt as _;
+use thiserror::Error;
+use tokio::io::AsyncReadExt as _;
+
+use crate::backend::BackendError;
+use crate::backend::TreeValue;
+use crate::merged_tree::MergedTree;
+use crate::repo_path::RepoPath;
+use crate::repo_path::RepoPathBuf;
+use crate::repo_path::RepoPathComponent;
+
+/// Error while loading `.gitattributes` files.
+#[derive(Debug, Error)]
+pub enum GitAttributesError {
+    /// The file isn't valid UTF-8.
+    #[error("Invalid UTF-8 for attributes in {path} on line #{line_num_for_display}: {line}")]
+    InvalidUtf8 {
+        /// Path to the attributes file.
+        path: PathBuf,
+        /// 1-based line number.
+        line_num_for_display: usize,
+        /// The invalid line.
+        line: String,
+        /// Underlying error.
+        source: std::str::Utf8Error,
+    },
+    /// The pattern couldn't be parsed.
+    #[error("Failed to parse attributes pattern from file {path}")]
+    Underlying {
+        /// Path to the attributes file.
+        path: PathBuf,
+        /// Underlying error.
+        source: ignore::Error,
+    },
+    /// The file couldn't be read from the tree.
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+}
+
+/// State of an attribute for a path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AttributeState {
+    /// The attribute is set (`name`).
+    Set,
+    /// The attribute is unset (`-name`).
+    Unset,
+    /// The attribute is set to a value (`name=value`).
+    Value(String),
+    /// The attribute isn't specified, or was reset by `!name`.
+    Unspecified,
+}
+
+impl AttributeState {
+    /// Returns the value if the attribute is set to a value.
+    pub fn as_value(&self) -> Option<&str> {
+        match self {
+            AttributeState::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct AttributesRule {
+    matcher: gitignore::Gitignore,
+    attributes: Vec<(String, AttributeState)>,
+}
+
+/// Models the effective contents of multiple `.gitattributes` files.
+#[derive(Debug)]
+pub struct GitAttributesFile {
+    parent: Option<Arc<GitAttributesFile>>,
+    prefix: String,
+    rules: Vec<AttributesRule>,
+}
+
+impl GitAttributesFile {
+    /// Returns attributes without any rules.
+    pub fn empty() -> Arc<GitAttributesFile> {
+        Arc::new(GitAttributesFile {
+            parent: None,
+            prefix: String::new(),
+            rules: vec![],
+        })
+    }
+
+    /// Concatenates new `.gitattributes` content at the `prefix` directory.
+    ///
+    /// The `prefix` should be a slash-separated path relative to the workspace
+    /// root. Negative patterns and macro definitions are ignored, as they are
+    /// by Git.
+    pub fn chain(
+        self: &Arc<GitAttributesFile>,
+        prefix: &str,
+        attributes_path: &Path,
+        input: &[u8],
+    ) -> Result<Arc<GitAttributesFile>, GitAttributesError> {
+        let mut rules = vec![];
+        for (i, input_line) in input.split(|b| *b == b'\n').enumerate() {
+            let line =
+                std::str::from_utf8(input_line).map_err(|err| GitAttributesError::InvalidUtf8 {
+                    path: attributes_path.to_path_buf(),
+                    line_num_for_display: i + 1,
+                    line: String::from_utf8_lossy(input_line).to_string(),
+                    source: err,
+                })?;
+            let mut words = line.split_whitespace();
+            let Some(pattern) = words.next() else {
+                continue;
+            };
+            if pattern.starts_with(['#', '!']) || pattern.starts_with("[attr]") {
+                continue;
+            }
+            let attributes = words.flat_map(parse_attribute).collect();
+            // Each line is matched separately because all matching lines
+            // contribute attributes, not only the last one.
+            let mut builder = gitignore::GitignoreBuilder::new(prefix);
+            builder
+                .add_line(None, pattern)
+                .map_err(|err| GitAttributesError::Underlying {
+                    path: attributes_path.to_path_buf(),
+                    source: err,
+                })?;
+            let matcher = builder
+                .build()
+                .map_err(|err| GitAttributesError::Underlying {
+                    path: attributes_path.to_path_buf(),
+                    source: err,
+                })?;
+            rules.push(AttributesRule {
+                matcher,
+                attributes,
+            });
+        }
+        let parent = if self.rules.is_empty() {
+            self.parent.clone() // omit the empty root
+        } else {
+            Some(self.clone())
+        };
+        Ok(Arc::new(GitAttributesFile {
+            parent,
+            prefix: prefix.to_owned(),
+            rules,
+        }))
+    }
+
+    /// Returns the state of the attribute `name` for the file at `path`.
+    ///
+    /// Later lines take precedence over earlier ones, and files in
+    /// subdirectories take precedence over files in their parents.
+    pub fn get(&self, path: &str, name: &str) -> AttributeState {
+        iter::successors(Some(self), |file| file.parent.as_deref())
+            .filter(|file| path.starts_with(&file.prefix))
+            .flat_map(|file| file.rules.iter().rev())
+            .filter(|rule| rule.matcher.matched(path, false).is_ignore())
+            .find_map(|rule| {
+                rule.attributes
+                    .iter()
+                    .rev()
+                    .find(|(attr_name, _)| attr_name == name)
+                    .map(|(_, state)| state.clone())
+            })
+            .unwrap_or(AttributeState::Unspecified)
+    }
+}
+
+fn parse_attribute(word: &str) -> Vec<(String, AttributeState)> {
+    if let Some(name) = word.strip_prefix('-') {
+        vec![(name.to_owned(), AttributeState::Unset)]
+    } else if let Some(name) = word.strip_prefix('!') {
+        vec![(name.to_owned(), AttributeState::Unspecified)]
+    } else if let Some((name, value)) = word.split_once('=') {
+        vec![(name.to_owned(), AttributeState::Value(value.to_owned()))]
+    } else if word == "binary" {
+        // The only built-in macro
+        ["diff", "merge", "text"]
+            .into_iter()
+            .map(|name| (name.to_owned(), AttributeState::Unset))
+            .chain([(word.to_owned(), AttributeState::Set)])
+            .collect()
+    } else {
+        vec![(word.to_owned(), AttributeState::Set)]
+    }
+}
+
+/// Looks up attributes of paths in a tree, loading the `.gitattributes` files
+/// from the tree as needed.
+///
+/// Conflicted `.gitattributes` files are ignored.
+#[derive(Debug)]
+pub struct TreeGitAttributes {
+    tree: MergedTree,
+    dirs: HashMap<RepoPathBuf, Arc<GitAttributesFile>>,
+}
+
+impl TreeGitAttributes {
+    /// Creates attributes lookup for the `tree`.
+    pub fn new(tree: MergedTree) -> Self {
+        TreeGitAttributes {
+            tree,
+            dirs: HashMap::new(),
+        }
+    }
+
+    /// Returns the state of the attribute `name` for the file at `path`.
+    pub fn get(
+        &mut self,
+        path: &RepoPath,
+        name: &str,
+    ) -> Result<AttributeState, GitAttributesError> {
+        let dir = path.parent().unwrap_or(RepoPath::root());
+        let file = self.load_dir(dir)?;
+        Ok(file.get(path.as_internal_file_string(), name))
+    }
+
+    fn load_dir(&mut self, dir: &RepoPath) -> Result<Arc<GitAttributesFile>, GitAttributesError> {
+        if let Some(file) = self.dirs.get(dir) {
+            return Ok(file.clone());
+        }
+        let parent = match dir.parent() {
+            Some(parent_dir) => self.load_dir(parent_dir)?,
+            None => GitAttributesFile::empty(),
+        };
+        let attributes_path = dir.join(RepoPathComponent::new(".gitattributes").unwrap());
+        let file = match self.tree.path_value(&attributes_path)?.into_resolved() {
+            Ok(Some(TreeValue::File { id, .. })) => {
+                let store = self.tree.store();
+                let mut reader = store.read_file(&attributes_path, &id).block_on()?;
+                let mut content = vec![];
+                reader.read_to_end(&mut content).block_on().map_err(|err| {
+                    BackendError::ReadFile {
+                        path: attributes_path.clone(),
+                        id: id.clone(),
+                        source: err.into(),
+                    }
+                })?;
+                parent.chain(
+                    &dir.to_internal_dir_string(),
+                    Path::new(attributes_path.as_internal_file_string()),
+                    &content,
+                )?
+            }
+            _ => parent,
+        };
+        self.dirs.insert(dir.to_owned(), file.clone());
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(input: &[u8], path: &str, name: &str) -> AttributeState {
+        let file = GitAttributesFile::empty()
+            .chain("", Path::new(""), input)
+            .unwrap();
+        file.get(path, name)
+    }
+
+    #[test]
+    fn test_gitattributes_states() {
+        let input = b"*.txt text -diff merge=union !eol\n";
+        assert_eq!(get(input, "foo.txt", "text"), AttributeState::Set);
+        assert_eq!(get(input, "foo.txt", "diff"), AttributeState::Unset);
+        assert_eq!(
+            get(input, "foo.txt", "merge"),
+            AttributeState::Value("union".to_owned())
+        );
+        assert_eq!(get(input, "foo.txt", "eol"), AttributeState::Unspecified);
+        assert_eq!(get(input, "foo.txt", "other"), AttributeState::Unspecified);
+        assert_eq!(get(input, "foo.rs", "text"), AttributeState::Unspecified);
+    }
+
+    #[test]
+    fn test_gitattributes_comments_and_blank_lines() {
+        let input = b"# *.txt diff=foo\n\n  \n[attr]foo diff=foo\n!*.txt diff=foo\n";
+        assert_eq!(get(input, "a.txt", "diff"), AttributeState::Unspecified);
+    }
+
+    #[test]
+    fn test_gitattributes_binary_macro() {
+        let input = b"*.png binary\n";
+        assert_eq!(get(input, "a.png", "binary"), AttributeState::Set);
+        assert_eq!(get(input, "a.png", "diff"), AttributeState::Unset);
+        assert_eq!(get(input, "a.png", "merge"), AttributeState::Unset);
+    }
+
+    #[test]
+    fn test_gitattributes_later_lines_override() {
+        let input = b"* diff=a merge=a\n*.txt diff=b\nfoo.txt !diff\n";
+        assert_eq!(get(input, "a.rs", "diff").as_value(), Some("a"));
+        assert_eq!(get(input, "a.txt", "diff").as_value(), Some("b"));
+        assert_eq!(get(input, "a.txt", "merge").as_value(), Some("a"));
+        assert_eq!(get(input, "foo.txt", "diff"), AttributeState::Unspecified);
+        assert_eq!(
+            get(input, "dir/foo.txt", "diff"),
+            AttributeState::Unspecified
+        );
+    }
+
+    #[test]
+    fn test_gitattributes_rooted_and_dir_patterns() {
+        let input = b"/foo diff=a\ndir/ diff=b\ndir/** merge=c\n";
+        assert_eq!(get(input, "foo", "diff").as_value(), Some("a"));
+        assert_eq!(get(input, "sub/foo", "diff"), AttributeState::Unspecified);
+        // Directory patterns don't apply to the files in the directory.
+        assert_eq!(get(input, "dir/x", "diff"), AttributeState::Unspecified);
+        assert_eq!(get(input, "dir/x", "merge").as_value(), Some("c"));
+    }
+
+    #[test]
+    fn test_gitattributes_chained() {
+        let file = GitAttributesFile::empty()
+            .chain("", Path::new(""), b"*.txt diff=a merge=a\n")
+            .unwrap()
+            .chain("dir/", Path::new(""), b"*.txt diff=b\n/c.txt -merge\n")
+            .unwrap();
+        assert_eq!(file.get("a.txt", "diff").as_value(), Some("a"));
+        assert_eq!(file.get("dir/a.txt", "diff").as_value(), Some("b"));
+        assert_eq!(file.get("dir/a.txt", "merge").as_value(), Some("a"));
+        assert_eq!(file.get("dir/c.txt", "merge"), AttributeState::Unset);
+        assert_eq!(file.get("dir/sub/c.txt", "merge").as_value(), Some("a"));
+    }
+}
@@ -152,8 +152,8 @@ impl GpgBackend {
     }
 
     pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
-        let program = settings.get_string("signing.backends.gpg.program")?;
-        let allow_expired_keys = settings.get_bool("signing.backends.gpg.allow-expired-keys")?;
+        let program = settings.signing_gpg_program()?;
+        let allow_expired_keys = settings.signing_gpg_allow_expired_keys()?;
         let default_key = settings.user_email().to_owned();
         Ok(Self::new(program.into(), allow_expired_keys, default_key))
     }
@@ -240,8 +240,8 @@ impl GpgsmBackend {
     }
 
     pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
-        let program = settings.get_string("signing.backends.gpgsm.program")?;
-        let allow_expired_keys = settings.get_bool("signing.backends.gpgsm.allow-expired-keys")?;
+        let program = settings.signing_gpgsm_program()?;
+        let allow_expired_keys = settings.signing_gpgsm_allow_expired_keys()?;
         let default_key = settings.user_email().to_owned();
         Ok(Self::new(program.into(), allow_expired_keys, default_key))
     }
@@ -332,6 +332,158 @@ where
     }
 }
 
+/// Edge of a [`GraphRowLayout`] together with the lane it leaves the row in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GraphRowEdge<N> {
+    pub edge: GraphEdge<N>,
+    pub lane: usize,
+}
+
+/// Renderer-agnostic layout of a single row of a graph.
+///
+/// The graph is laid out in vertical lanes. Each lane carries an edge from a
+/// row above down to the edge's target. A lane ends at the row of its target,
+/// at which point it can be reused by another edge.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GraphRowLayout<N> {
+    /// The node of this row.
+    pub node: N,
+    /// The lane the node is placed in.
+    pub lane: usize,
+    /// The edges occupying each lane as it enters the row from above. The
+    /// lanes whose target is `node` end at the node.
+    pub lanes_above: Vec<Option<GraphEdge<N>>>,
+    /// The edges of the node, in input order, with the lane each of them
+    /// continues in. An edge may join a lane that already leads to its
+    /// target. Missing edges are only drawn in this row, so they don't occupy
+    /// a lane below it.
+    pub edges: Vec<GraphRowEdge<N>>,
+    /// The edges occupying each lane as it leaves the row below.
+    pub lanes_below: Vec<Option<GraphEdge<N>>>,
+}
+
+impl<N> GraphRowLayout<N> {
+    /// Returns the number of lanes used by this row.
+    pub fn width(&self) -> usize {
+        let edges_width = self.edges.iter().map(|edge| edge.lane + 1).max();
+        let lanes_width = self.lanes_above.len().max(self.lanes_below.len());
+        lanes_width.max(self.lane + 1).max(edges_width.unwrap_or(0))
+    }
+}
+
+/// Assigns graph nodes and edges to lanes row by row.
+///
+/// The nodes should be passed in topological order, children first, e.g. as
+/// emitted by [`TopoGroupedGraphIterator`]. The assignment only depends on the
+/// order of the input, so identical inputs produce identical layouts.
+///
+/// This isn't the layout `jj log` uses. The CLI draws graphs with the
+/// `sapling-renderdag` crate, which computes its own layout while drawing and
+/// can't be given a precomputed one, so the lanes assigned here may differ from
+/// the columns of `jj log`. This layout is for embedders that draw graphs
+/// themselves and need the lanes without any text rendering.
+#[derive(Clone, Debug)]
+pub struct GraphLayout<N> {
+    lanes: Vec<Option<GraphEdge<N>>>,
+}
+
+impl<N> Default for GraphLayout<N> {
+    fn default() -> Self {
+        Self { lanes: vec![] }
+    }
+}
+
+impl<N: Clone + Eq> GraphLayout<N> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Computes the layout of the row of `node`, which has the given `edges`.
+    pub fn next_row(&mut self, node: N, edges: &[GraphEdge<N>]) -> GraphRowLayout<N> {
+        let lanes_above = self.lanes.clone();
+        let incoming_lanes: Vec<usize> = (0..self.lanes.len())
+            .filter(|&i| self.lane_target(i) == Some(&node))
+            .collect();
+        for &i in &incoming_lanes {
+            self.lanes[i] = None;
+        }
+        // A new head is placed in the leftmost free lane
+        let lane = match incoming_lanes.first() {
+            Some(&lane) => lane,
+            None => self.free_lane(),
+        };
+
+        let mut row_edges = Vec::with_capacity(edges.len());
+        let mut missing_lanes = vec![];
+        for edge in edges {
+            let existing_lane = if edge.edge_type == GraphEdgeType::Missing {
+                None
+            } else {
+                (0..self.lanes.len()).find(|&i| self.lane_target(i) == Some(&edge.target))
+            };
+            let edge_lane = existing_lane.unwrap_or_else(|| {
+                // Continue straight down from the node if possible
+                let edge_lane = if self.lanes.get(lane).is_some_and(Option::is_none) {
+                    lane
+                } else {
+                    self.free_lane()
+                };
+                self.lanes[edge_lane] = Some(edge.clone());
+                edge_lane
+            });
+            if edge.edge_type == GraphEdgeType::Missing {
+                missing_lanes.push(edge_lane);
+            }
+            row_edges.push(GraphRowEdge {
+                edge: edge.clone(),
+                lane: edge_lane,
+            });
+        }
+        for i in missing_lanes {
+            self.lanes[i] = None;
+        }
+        while self.lanes.last().is_some_and(Option::is_none) {
+            self.lanes.pop();
+        }
+
+        GraphRowLayout {
+            node,
+            lane,
+            lanes_above,
+            edges: row_edges,
+            lanes_below: self.lanes.clone(),
+        }
+    }
+
+    fn lane_target(&self, lane: usize) -> Option<&N> {
+        self.lanes[lane].as_ref().map(|edge| &edge.target)
+    }
+
+    /// Returns the leftmost free lane, adding a lane if all are in use.
+    fn free_lane(&mut self) -> usize {
+        if let Some(lane) = self.lanes.iter().position(Option::is_none) {
+            lane
+        } else {
+            self.lanes.push(None);
+            self.lanes.len() - 1
+        }
+    }
+}
+
+/// Computes the layout of all rows of the given graph.
+pub fn layout_graph<N: Clone + Eq, E>(
+    input: impl IntoIterator<Item = Result<GraphNode<N>, E>>,
+) -> Result<Vec<GraphRowLayout<N>>, E> {
+    let mut layout = GraphLayout::new();
+    input
+        .into_iter()
+        .map(|item| {
+            let (node, edges) = item?;
+            Ok(layout.next_row(node, &edges))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use std::convert::Infallible;
@@ -1854,4 +2006,81 @@ mod tests {
         assert!(iter.next().is_none());
         assert!(iter.emittable_ids.is_empty());
     }
+
+    fn format_layout(rows: &[GraphRowLayout<char>]) -> String {
+        let format_lanes = |lanes: &[Option<GraphEdge<char>>]| {
+            lanes
+                .iter()
+                .map(|lane| lane.as_ref().map_or("-".to_owned(), format_edge))
+                .join(", ")
+        };
+        rows.iter()
+            .map(|row| {
+                let edges = row
+                    .edges
+                    .iter()
+                    .map(|edge| format!("{}@{}", format_edge(&edge.edge), edge.lane))
+                    .join(", ");
+                format!(
+                    "{} lane={} above=[{}] edges=[{}] below=[{}]\n",
+                    row.node,
+                    row.lane,
+                    format_lanes(&row.lanes_above),
+                    edges,
+                    format_lanes(&row.lanes_below),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_layout_fork_and_merge() {
+        let graph = [
+            ('F', vec![direct('D')]),
+            ('E', vec![direct('D')]),
+            ('D', vec![direct('B'), direct('C')]),
+            ('C', vec![direct('A')]),
+            ('B', vec![direct('A')]),
+            ('A', vec![]),
+        ]
+        .map(Ok::<_, Infallible>);
+        let rows = layout_graph(graph.iter().cloned()).unwrap();
+        insta::assert_snapshot!(format_layout(&rows), @r"
+        F lane=0 above=[] edges=[direct(D)@0] below=[direct(D)]
+        E lane=1 above=[direct(D)] edges=[direct(D)@0] below=[direct(D)]
+        D lane=0 above=[direct(D)] edges=[direct(B)@0, direct(C)@1] below=[direct(B), direct(C)]
+        C lane=1 above=[direct(B), direct(C)] edges=[direct(A)@1] below=[direct(B), direct(A)]
+        B lane=0 above=[direct(B), direct(A)] edges=[direct(A)@1] below=[-, direct(A)]
+        A lane=1 above=[-, direct(A)] edges=[] below=[]
+        ");
+        assert_eq!(
+            rows.iter().map(GraphRowLayout::width).collect_vec(),
+            vec![1, 2, 2, 2, 2, 2]
+        );
+
+        // Identical inputs are laid out identically
+        assert_eq!(layout_graph(graph.iter().cloned()).unwrap(), rows);
+    }
+
+    #[test]
+    fn test_layout_elided_edges() {
+        let graph = [
+            ('D', vec![indirect('B'), missing('X')]),
+            ('C', vec![direct('B')]),
+            ('B', vec![indirect('A')]),
+            ('A', vec![]),
+        ]
+        .map(Ok::<_, Infallible>);
+        let rows = layout_graph(graph.iter().cloned()).unwrap();
+        // The missing edge only occupies a lane in its own row
+        insta::assert_snapshot!(format_layout(&rows), @r"
+        D lane=0 above=[] edges=[indirect(B)@0, missing(X)@1] below=[indirect(B)]
+        C lane=1 above=[indirect(B)] edges=[direct(B)@0] below=[indirect(B)]
+        B lane=0 above=[indirect(B)] edges=[indirect(A)@0] below=[indirect(A)]
+        A lane=0 above=[indirect(A)] edges=[] below=[]
+        ");
+
+        // Identical inputs are laid out identically
+        assert_eq!(layout_graph(graph.iter().cloned()).unwrap(), rows);
+    }
 }
@@ -47,6 +47,21 @@ pub fn encode_reverse_hex(data: &[u8]) -> String {
     String::from_utf8(encoded).unwrap()
 }
 
+/// Strips the noise commonly included when an id is copied from a terminal or
+/// a web page: surrounding whitespace and a single trailing punctuation
+/// character such as the period ending a sentence.
+///
+/// Returns `None` if nothing is left. This should only be applied to user
+/// input, never to ids read from the repository.
+pub fn trim_user_id_input(input: &str) -> Option<&str> {
+    let trimmed = input.trim();
+    let trimmed = trimmed
+        .strip_suffix(['.', ',', ';', ':', '!', '?', ')', ']', '\'', '"'])
+        .unwrap_or(trimmed)
+        .trim_end();
+    (!trimmed.is_empty()).then_some(trimmed)
+}
+
 /// Calculates common prefix length of two byte sequences. The length
 /// to be returned is a number of hexadecimal digits.
 pub fn common_hex_len(bytes_a: &[u8], bytes_b: &[u8]) -> usize {
@@ -64,6 +79,17 @@ pub fn common_hex_len(bytes_a: &[u8], bytes_b: &[u8]) -> usize {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_trim_user_id_input() {
+        assert_eq!(trim_user_id_input("abc"), Some("abc"));
+        assert_eq!(trim_user_id_input("  abc\n"), Some("abc"));
+        assert_eq!(trim_user_id_input("abc."), Some("abc"));
+        assert_eq!(trim_user_id_input(" abc). "), Some("abc)"));
+        assert_eq!(trim_user_id_input("a bc"), Some("a bc"));
+        assert_eq!(trim_user_id_input(""), None);
+        assert_eq!(trim_user_id_input(" . "), None);
+    }
+
     #[test]
     fn test_reverse_hex() {
         // Empty string
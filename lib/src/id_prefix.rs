@@ -14,10 +14,18 @@
 
 #![allow(missing_docs)]
 
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
 use std::iter;
 use std::marker::PhantomData;
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use itertools::Itertools as _;
 use once_cell::unsync::OnceCell;
@@ -25,6 +33,12 @@ use thiserror::Error;
 
 use crate::backend::ChangeId;
 use crate::backend::CommitId;
+use crate::backend::MillisSinceEpoch;
+use crate::backend::Timestamp;
+use crate::file_util::persist_atomically_with;
+use crate::file_util::Durability;
+use crate::file_util::IoResultExt as _;
+use crate::file_util::PathError;
 use crate::hex_util;
 use crate::object_id::HexPrefix;
 use crate::object_id::ObjectId;
@@ -112,6 +126,7 @@ impl IdIndexSourceEntry<ChangeId> for &'_ (CommitId, ChangeId) {
 #[derive(Default)]
 pub struct IdPrefixContext {
     disambiguation: Option<DisambiguationData>,
+    prefix_cache: Option<Rc<PrefixCache>>,
     extensions: Arc<RevsetExtensions>,
 }
 
@@ -119,6 +134,7 @@ impl IdPrefixContext {
     pub fn new(extensions: Arc<RevsetExtensions>) -> Self {
         Self {
             disambiguation: None,
+            prefix_cache: None,
             extensions,
         }
     }
@@ -131,6 +147,13 @@ impl IdPrefixContext {
         self
     }
 
+    /// Attaches the cache of previously printed prefixes, which is consulted
+    /// when a prefix is ambiguous.
+    pub fn with_prefix_cache(mut self, prefix_cache: Rc<PrefixCache>) -> Self {
+        self.prefix_cache = Some(prefix_cache);
+        self
+    }
+
     /// Loads disambiguation index once, returns a borrowed index to
     /// disambiguate commit/change IDs.
     pub fn populate(&self, repo: &dyn Repo) -> Result<IdPrefixIndex<'_>, IdPrefixIndexLoadError> {
@@ -139,19 +162,26 @@ impl IdPrefixContext {
         } else {
             None
         };
-        Ok(IdPrefixIndex { indexes })
+        Ok(IdPrefixIndex {
+            indexes,
+            prefix_cache: self.prefix_cache.as_deref(),
+        })
     }
 }
 
 /// Loaded index to disambiguate commit/change IDs.
 pub struct IdPrefixIndex<'a> {
     indexes: Option<&'a Indexes>,
+    prefix_cache: Option<&'a PrefixCache>,
 }
 
 impl IdPrefixIndex<'_> {
     /// Returns an empty index that just falls back to a provided `repo`.
     pub const fn empty() -> IdPrefixIndex<'static> {
-        IdPrefixIndex { indexes: None }
+        IdPrefixIndex {
+            indexes: None,
+            prefix_cache: None,
+        }
     }
 
     /// Resolve an unambiguous commit ID prefix.
@@ -174,7 +204,16 @@ impl IdPrefixIndex<'_> {
                 }
             }
         }
-        repo.index().resolve_commit_id_prefix(prefix)
+        match repo.index().resolve_commit_id_prefix(prefix) {
+            PrefixResolution::AmbiguousMatch => self
+                .prefix_cache
+                .and_then(|cache| cache.resolve_commit_prefix(repo, prefix))
+                .map_or(
+                    PrefixResolution::AmbiguousMatch,
+                    PrefixResolution::SingleMatch,
+                ),
+            resolution => resolution,
+        }
     }
 
     /// Returns the shortest length of a prefix of `commit_id` that
@@ -210,7 +249,16 @@ impl IdPrefixIndex<'_> {
                 };
             }
         }
-        repo.resolve_change_id_prefix(prefix)
+        match repo.resolve_change_id_prefix(prefix) {
+            PrefixResolution::AmbiguousMatch => self
+                .prefix_cache
+                .and_then(|cache| cache.resolve_change_prefix(repo, prefix))
+                .map_or(
+                    PrefixResolution::AmbiguousMatch,
+                    PrefixResolution::SingleMatch,
+                ),
+            resolution => resolution,
+        }
     }
 
     /// Returns the shortest length of a prefix of `change_id` that
@@ -226,6 +274,230 @@ impl IdPrefixIndex<'_> {
         }
         repo.shortest_unique_change_id_prefix_len(change_id)
     }
+
+    /// Records that the first `prefix_len` hex digits of `commit_id` were
+    /// printed, if the prefix cache is enabled.
+    pub fn record_commit_prefix(&self, commit_id: &CommitId, prefix_len: usize) {
+        if let Some(cache) = self.prefix_cache {
+            cache.record(PrefixKind::Commit, commit_id.hex(), prefix_len);
+        }
+    }
+
+    /// Records that the first `prefix_len` hex digits of `change_id` were
+    /// printed, if the prefix cache is enabled.
+    pub fn record_change_prefix(&self, change_id: &ChangeId, prefix_len: usize) {
+        if let Some(cache) = self.prefix_cache {
+            cache.record(PrefixKind::Change, change_id.hex(), prefix_len);
+        }
+    }
+}
+
+const PREFIX_CACHE_FILE_NAME: &str = "prefix_cache";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum PrefixKind {
+    Commit,
+    Change,
+}
+
+impl PrefixKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Commit => "commit",
+            Self::Change => "change",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "commit" => Some(Self::Commit),
+            "change" => Some(Self::Change),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PrefixCacheEntry {
+    kind: PrefixKind,
+    /// Shortest prefix printed for the ID, in forward hex.
+    prefix: String,
+    /// Full ID in forward hex.
+    id_hex: String,
+    recorded_at: MillisSinceEpoch,
+}
+
+impl PrefixCacheEntry {
+    fn parse(line: &str) -> Option<Self> {
+        let [kind, prefix, id_hex, recorded_at] = line.split_ascii_whitespace().collect_array()?;
+        let entry = Self {
+            kind: PrefixKind::parse(kind)?,
+            prefix: prefix.to_owned(),
+            id_hex: id_hex.to_owned(),
+            recorded_at: MillisSinceEpoch(recorded_at.parse().ok()?),
+        };
+        entry.id_hex.starts_with(&entry.prefix).then_some(entry)
+    }
+}
+
+/// Ambiguous prefix that was resolved by [`PrefixCache`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PrefixCacheNotice {
+    Commit { prefix_len: usize, id: CommitId },
+    Change { prefix_len: usize, id: ChangeId },
+}
+
+/// Per-workspace table of short commit/change ID prefixes printed previously.
+///
+/// A prefix that was unique when it was printed may become ambiguous as new
+/// commits are added. If the cache is attached to [`IdPrefixContext`], such a
+/// prefix keeps resolving to the recorded ID while the entry is alive. Entries
+/// are evicted in least-recently-recorded order once the table gets larger
+/// than `max_entries`, and dropped once they get older than `max_age`.
+#[derive(Debug)]
+pub struct PrefixCache {
+    dir: PathBuf,
+    max_entries: usize,
+    max_age: Duration,
+    /// Entries sorted by recorded time, oldest first.
+    entries: RefCell<Vec<PrefixCacheEntry>>,
+    dirty: Cell<bool>,
+    notices: RefCell<Vec<PrefixCacheNotice>>,
+}
+
+impl PrefixCache {
+    /// Loads the cache stored in `dir`. A missing cache file is treated as
+    /// empty, and malformed lines are ignored.
+    pub fn load(dir: &Path, max_entries: usize, max_age: Duration) -> Result<Self, PathError> {
+        let path = dir.join(PREFIX_CACHE_FILE_NAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err).context(&path),
+        };
+        let entries = content
+            .lines()
+            .filter_map(PrefixCacheEntry::parse)
+            .collect();
+        let cache = Self {
+            dir: dir.to_owned(),
+            max_entries,
+            max_age,
+            entries: RefCell::new(entries),
+            dirty: Cell::new(false),
+            notices: RefCell::new(vec![]),
+        };
+        cache.expire(Timestamp::now().timestamp);
+        Ok(cache)
+    }
+
+    /// Writes the cache back to the disk if it has been modified.
+    pub fn save(&self) -> Result<(), PathError> {
+        if !self.dirty.get() {
+            return Ok(());
+        }
+        let entries = self.entries.borrow();
+        persist_atomically_with(
+            &self.dir,
+            PREFIX_CACHE_FILE_NAME,
+            Durability::Buffered,
+            |writer| {
+                for entry in entries.iter() {
+                    let kind = entry.kind.as_str();
+                    let PrefixCacheEntry {
+                        prefix,
+                        id_hex,
+                        recorded_at,
+                        ..
+                    } = entry;
+                    writeln!(writer, "{kind} {prefix} {id_hex} {}", recorded_at.0)?;
+                }
+                Ok(())
+            },
+        )?;
+        self.dirty.set(false);
+        Ok(())
+    }
+
+    /// Returns the ambiguous prefixes resolved by this cache since the last
+    /// call.
+    pub fn take_notices(&self) -> Vec<PrefixCacheNotice> {
+        mem::take(&mut *self.notices.borrow_mut())
+    }
+
+    fn record(&self, kind: PrefixKind, id_hex: String, prefix_len: usize) {
+        let now = Timestamp::now().timestamp;
+        let mut prefix = id_hex[..prefix_len.min(id_hex.len())].to_owned();
+        let mut entries = self.entries.borrow_mut();
+        if let Some(pos) = entries
+            .iter()
+            .position(|entry| entry.kind == kind && entry.id_hex == id_hex)
+        {
+            let old_entry = entries.remove(pos);
+            // The shortest prefix also covers the longer ones.
+            if old_entry.prefix.len() < prefix.len() {
+                prefix = old_entry.prefix;
+            }
+        }
+        entries.push(PrefixCacheEntry {
+            kind,
+            prefix,
+            id_hex,
+            recorded_at: now,
+        });
+        drop(entries);
+        self.dirty.set(true);
+        self.expire(now);
+    }
+
+    fn expire(&self, now: MillisSinceEpoch) {
+        let max_age_millis = i64::try_from(self.max_age.as_millis()).unwrap_or(i64::MAX);
+        let mut entries = self.entries.borrow_mut();
+        let old_len = entries.len();
+        entries.retain(|entry| now.0.saturating_sub(entry.recorded_at.0) <= max_age_millis);
+        let excess = entries.len().saturating_sub(self.max_entries);
+        entries.drain(..excess);
+        if entries.len() != old_len {
+            self.dirty.set(true);
+        }
+    }
+
+    /// Looks up the ID which was printed with a prefix matching `prefix`.
+    fn lookup(&self, kind: PrefixKind, prefix: &HexPrefix) -> Option<String> {
+        let hex = prefix.hex();
+        let entries = self.entries.borrow();
+        let (entry,) = entries
+            .iter()
+            .filter(|entry| {
+                entry.kind == kind
+                    && entry.id_hex.starts_with(&hex)
+                    && hex.starts_with(&entry.prefix)
+            })
+            .collect_tuple()?;
+        Some(entry.id_hex.clone())
+    }
+
+    fn resolve_commit_prefix(&self, repo: &dyn Repo, prefix: &HexPrefix) -> Option<CommitId> {
+        let id = CommitId::try_from_hex(&self.lookup(PrefixKind::Commit, prefix)?).ok()?;
+        if !repo.index().has_id(&id) {
+            return None;
+        }
+        self.notices.borrow_mut().push(PrefixCacheNotice::Commit {
+            prefix_len: prefix.hex().len(),
+            id: id.clone(),
+        });
+        Some(id)
+    }
+
+    fn resolve_change_prefix(&self, repo: &dyn Repo, prefix: &HexPrefix) -> Option<Vec<CommitId>> {
+        let id = ChangeId::try_from_hex(&self.lookup(PrefixKind::Change, prefix)?).ok()?;
+        let commit_ids = repo.resolve_change_id(&id)?;
+        self.notices.borrow_mut().push(PrefixCacheNotice::Change {
+            prefix_len: prefix.hex().len(),
+            id,
+        });
+        Some(commit_ids)
+    }
 }
 
 /// In-memory immutable index to do prefix lookup of key `K` through `P`.
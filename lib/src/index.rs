@@ -16,18 +16,22 @@
 
 use std::any::Any;
 use std::fmt::Debug;
+use std::ops::ControlFlow;
 use std::sync::Arc;
 
+use itertools::Itertools as _;
 use thiserror::Error;
 
 use crate::backend::ChangeId;
 use crate::backend::CommitId;
 use crate::commit::Commit;
 use crate::object_id::HexPrefix;
+use crate::object_id::ObjectId as _;
 use crate::object_id::PrefixResolution;
 use crate::operation::Operation;
 use crate::revset::ResolvedExpression;
 use crate::revset::Revset;
+use crate::revset::RevsetEvaluationContext;
 use crate::revset::RevsetEvaluationError;
 use crate::store::Store;
 
@@ -46,12 +50,27 @@ pub struct IndexWriteError(pub Box<dyn std::error::Error + Send + Sync>);
 #[error(transparent)]
 pub struct IndexError(pub Box<dyn std::error::Error + Send + Sync>);
 
+/// Wrapped in [`IndexError`] if some of the commits passed to an [`Index`]
+/// aren't present in it.
+#[derive(Debug, Error)]
+#[error("Commits not found in the index: {}", .0.iter().map(|id| id.hex()).join(", "))]
+pub struct CommitsNotIndexedError(pub Vec<CommitId>);
+
 /// An error returned if `Index::all_heads_for_gc()` is not supported by the
 /// index backend.
 #[derive(Debug, Error)]
 #[error("Cannot collect all heads by index of this type")]
 pub struct AllHeadsForGcUnsupported;
 
+/// Progress of [`IndexStore::rebuild_incrementally()`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct IndexRebuildProgress {
+    /// Number of index parts migrated to the current format so far.
+    pub migrated: usize,
+    /// Number of index parts still stored in an older format.
+    pub remaining: usize,
+}
+
 /// Defines the interface for types that provide persistent storage for an
 /// index.
 pub trait IndexStore: Send + Sync + Debug {
@@ -77,6 +96,29 @@ pub trait IndexStore: Send + Sync + Debug {
         index: Box<dyn MutableIndex>,
         op: &Operation,
     ) -> Result<Box<dyn ReadonlyIndex>, IndexWriteError>;
+
+    /// Returns true if the index at the specified operation is stored in an
+    /// older format which can still be read, but should be migrated by
+    /// [`Self::rebuild_incrementally()`].
+    fn needs_rebuild(&self, _op: &Operation, _store: &Arc<Store>) -> Result<bool, IndexReadError> {
+        Ok(false)
+    }
+
+    /// Migrates the index at the specified operation to the current format
+    /// part by part.
+    ///
+    /// The `progress` callback is invoked after each migrated part, and can
+    /// stop the migration early by returning [`ControlFlow::Break`]. The
+    /// remaining parts can be migrated by a later call. Returns the progress
+    /// at the time the migration finished or was stopped.
+    fn rebuild_incrementally(
+        &self,
+        _op: &Operation,
+        _store: &Arc<Store>,
+        _progress: &mut dyn FnMut(IndexRebuildProgress) -> ControlFlow<()>,
+    ) -> Result<IndexRebuildProgress, IndexWriteError> {
+        Ok(IndexRebuildProgress::default())
+    }
 }
 
 /// Defines the interface for types that provide an index of the commits in a
@@ -122,17 +164,33 @@ pub trait Index: Send + Sync {
     /// Returns the subset of commit IDs in `candidates` which are not ancestors
     /// of other commits in `candidates`. If a commit id is duplicated in the
     /// `candidates` list it will appear at most once in the output.
+    ///
+    /// Fails with [`CommitsNotIndexedError`] listing the candidates which
+    /// aren't present in the index.
     fn heads(
         &self,
         candidates: &mut dyn Iterator<Item = &CommitId>,
     ) -> Result<Vec<CommitId>, IndexError>;
 
+    /// Returns the subset of commit IDs in `candidates` which are not
+    /// descendants of other commits in `candidates`. If a commit id is
+    /// duplicated in the `candidates` list it will appear at most once in the
+    /// output.
+    ///
+    /// Fails with [`CommitsNotIndexedError`] listing the candidates which
+    /// aren't present in the index.
+    fn roots_of_set(
+        &self,
+        candidates: &mut dyn Iterator<Item = &CommitId>,
+    ) -> Result<Vec<CommitId>, IndexError>;
+
     /// Resolves the revset `expression` against the index and corresponding
-    /// `store`.
+    /// `store`. The evaluation can be interrupted through the `context`.
     fn evaluate_revset<'index>(
         &'index self,
         expression: &ResolvedExpression,
         store: &Arc<Store>,
+        context: &RevsetEvaluationContext,
     ) -> Result<Box<dyn Revset + 'index>, RevsetEvaluationError>;
 }
 
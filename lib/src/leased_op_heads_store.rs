@@ -0,0 +1,485 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Operation heads store which serializes updates by lease files.
+//!
+//! [`SimpleOpHeadsStore`] relies on atomic renames and on removed files
+//! disappearing from directory listings immediately, which some network file
+//! systems don't guarantee. [`LeasedOpHeadsStore`] stores the heads in the same
+//! layout, but only uses exclusive file creation: heads are created with
+//! [`File::create_new()`], and an update first takes a lease by exclusively
+//! creating the `lease` file, which records the owner and the time the lease
+//! expires. Concurrent updates wait for the lease to be released, and leases
+//! left behind by crashed processes are broken once they expire.
+
+use std::any::Any;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::ErrorKind;
+use std::io::Write as _;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use thiserror::Error;
+
+use crate::config::ConfigGetError;
+use crate::object_id::ObjectId as _;
+use crate::op_heads_store::OpHeadsStore;
+use crate::op_heads_store::OpHeadsStoreError;
+use crate::op_heads_store::OpHeadsStoreLock;
+use crate::op_store::OperationId;
+use crate::settings::UserSettings;
+use crate::simple_op_heads_store::SimpleOpHeadsStore;
+use crate::simple_op_heads_store::SimpleOpHeadsStoreInitError;
+
+/// Strategy to serialize updates of the operation heads, selected by the
+/// `core.op-heads-locking` setting when a repo is created.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OpHeadsLocking {
+    /// Lock files with atomic renames, implemented by [`SimpleOpHeadsStore`].
+    LockFile,
+    /// Lease files, implemented by [`LeasedOpHeadsStore`].
+    Lease,
+}
+
+/// Parameters of the leases taken by [`LeasedOpHeadsStore`].
+#[derive(Clone, Debug)]
+pub struct LeaseOptions {
+    /// Time after which a lease that wasn't released or renewed expires.
+    pub duration: Duration,
+    /// Maximum number of attempts to take a lease held by someone else.
+    pub max_attempts: usize,
+    /// Delay before the first retry. The delay grows exponentially with
+    /// random jitter.
+    pub initial_backoff: Duration,
+}
+
+impl Default for LeaseOptions {
+    fn default() -> Self {
+        LeaseOptions {
+            duration: Duration::from_secs(10),
+            max_attempts: 40,
+            initial_backoff: Duration::from_millis(5),
+        }
+    }
+}
+
+impl LeaseOptions {
+    /// Loads the options from the `core.op-heads-lease-*` settings.
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        Ok(LeaseOptions {
+            duration: Duration::from_millis(settings.core_op_heads_lease_duration_ms()?),
+            ..LeaseOptions::default()
+        })
+    }
+
+    /// Returns the delay before retrying after `attempt` failed attempts.
+    fn backoff(&self, attempt: usize) -> Duration {
+        let exponent = i32::try_from(attempt - 1).unwrap_or(i32::MAX);
+        let max_backoff = Duration::from_secs(1).max(self.initial_backoff);
+        let backoff = self
+            .initial_backoff
+            .mul_f32(1.5_f32.powi(exponent))
+            .min(max_backoff);
+        backoff.mul_f32(rand::random::<f32>() + 0.5)
+    }
+}
+
+/// Error while taking or holding a lease.
+#[derive(Debug, Error)]
+pub enum LeaseError {
+    /// The lease is held by someone else, and it didn't expire while waiting.
+    #[error("Timed out waiting for the operation heads lease held by {owner}")]
+    Timeout {
+        /// Owner of the lease.
+        owner: String,
+    },
+    /// The lease expired and was taken by someone else.
+    #[error("Lost the operation heads lease")]
+    Lost,
+    /// I/O error.
+    #[error("Failed to access the operation heads lease {path}")]
+    Io {
+        /// Path to the lease file.
+        path: PathBuf,
+        /// Underlying error.
+        source: io::Error,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+struct LeaseData {
+    owner: String,
+    expires_millis: i64,
+}
+
+impl LeaseData {
+    fn new(owner: String, duration: Duration) -> Self {
+        LeaseData {
+            owner,
+            expires_millis: millis_since_epoch(SystemTime::now() + duration),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expires_millis <= millis_since_epoch(SystemTime::now())
+    }
+}
+
+fn millis_since_epoch(time: SystemTime) -> i64 {
+    let millis = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    i64::try_from(millis).unwrap_or(i64::MAX)
+}
+
+/// Content of the lease file as read from disk.
+enum LeaseState {
+    /// There's no lease.
+    Free,
+    /// A valid lease file.
+    Held { data: LeaseData, raw: Vec<u8> },
+    /// The lease file can't be parsed, probably because it's being written.
+    /// The file modification time is used to expire it.
+    Invalid { modified: SystemTime, raw: Vec<u8> },
+}
+
+/// Operation heads store which takes a lease on each update. See the module
+/// documentation for details.
+#[derive(Debug)]
+pub struct LeasedOpHeadsStore {
+    heads: SimpleOpHeadsStore,
+    dir: PathBuf,
+    options: LeaseOptions,
+}
+
+impl LeasedOpHeadsStore {
+    /// Name of the store recorded in the `type` file.
+    pub fn name() -> &'static str {
+        "leased_op_heads_store"
+    }
+
+    /// Creates the heads directory in `dir`.
+    pub fn init(dir: &Path, options: LeaseOptions) -> Result<Self, SimpleOpHeadsStoreInitError> {
+        let heads = SimpleOpHeadsStore::init(dir)?;
+        Ok(LeasedOpHeadsStore {
+            heads,
+            dir: dir.to_owned(),
+            options,
+        })
+    }
+
+    /// Loads the store from `dir`.
+    pub fn load(dir: &Path, options: LeaseOptions) -> Self {
+        LeasedOpHeadsStore {
+            heads: SimpleOpHeadsStore::load(dir),
+            dir: dir.to_owned(),
+            options,
+        }
+    }
+
+    /// Path to the lease file.
+    pub fn lease_path(&self) -> PathBuf {
+        self.dir.join("lease")
+    }
+
+    /// Path to the lease file that backs [`OpHeadsStore::lock()`].
+    pub fn lock_path(&self) -> PathBuf {
+        self.dir.join("lock")
+    }
+
+    /// Records `id` as a head by exclusively creating its file.
+    fn add_op_head(&self, id: &OperationId) -> io::Result<()> {
+        let path = self.dir.join("heads").join(id.hex());
+        match File::create_new(path) {
+            Ok(mut file) => file.flush(),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn read_lease(&self, path: &Path) -> io::Result<LeaseState> {
+        let raw = match fs::read(path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(LeaseState::Free),
+            Err(err) => return Err(err),
+        };
+        if let Ok(data) = serde_json::from_slice(&raw) {
+            return Ok(LeaseState::Held { data, raw });
+        }
+        match fs::metadata(path) {
+            Ok(metadata) => Ok(LeaseState::Invalid {
+                modified: metadata.modified()?,
+                raw,
+            }),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(LeaseState::Free),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Takes the lease stored at `lease_path`, waiting for a valid lease held
+    /// by someone else to be released or to expire.
+    fn acquire_lease(&self, lease_path: PathBuf) -> Result<Lease<'_>, LeaseError> {
+        let io_error = |source| LeaseError::Io {
+            path: lease_path.clone(),
+            source,
+        };
+        let owner = format!("{}-{:016x}", process::id(), rand::random::<u64>());
+        let mut attempt = 0;
+        loop {
+            let data = LeaseData::new(owner.clone(), self.options.duration);
+            match create_lease_file(&lease_path, &data) {
+                Ok(()) => {
+                    let acquired_at = Instant::now();
+                    let lease = Lease {
+                        store: self,
+                        path: lease_path.clone(),
+                        data,
+                        acquired_at,
+                    };
+                    // Creating the file may not be atomic on every file system,
+                    // so make sure that nobody else thinks they own the lease.
+                    lease.check()?;
+                    return Ok(lease);
+                }
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(io_error(err)),
+            }
+
+            let holder = match self.read_lease(&lease_path).map_err(io_error)? {
+                LeaseState::Free => continue,
+                LeaseState::Held { data, raw } => {
+                    if data.is_expired() {
+                        tracing::info!(owner = data.owner, "breaking expired op heads lease");
+                        if self.break_lease(&lease_path, &raw).map_err(io_error)? {
+                            continue;
+                        }
+                    }
+                    data.owner
+                }
+                LeaseState::Invalid { modified, raw } => {
+                    if self.is_older_than_lease(modified) {
+                        tracing::info!("breaking invalid op heads lease");
+                        if self.break_lease(&lease_path, &raw).map_err(io_error)? {
+                            continue;
+                        }
+                    }
+                    "<unknown>".to_owned()
+                }
+            };
+            attempt += 1;
+            if attempt >= self.options.max_attempts {
+                return Err(LeaseError::Timeout { owner: holder });
+            }
+            std::thread::sleep(self.options.backoff(attempt));
+        }
+    }
+
+    fn is_older_than_lease(&self, modified: SystemTime) -> bool {
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        age > self.options.duration
+    }
+
+    /// Removes the lease file at `lease_path` if it still has the `expected`
+    /// content. Returns whether the lease is gone.
+    ///
+    /// Only one process at a time may break a lease, which is ensured by
+    /// exclusively creating a breaker file next to it. A new lease can still be
+    /// created between the comparison and the removal if the expired lease is
+    /// released concurrently, but its owner will notice that they lost the
+    /// lease before touching the heads.
+    fn break_lease(&self, lease_path: &Path, expected: &[u8]) -> io::Result<bool> {
+        let breaker_path = lease_path.with_extension("breaker");
+        match File::create_new(&breaker_path) {
+            Ok(_) => {}
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                // Somebody else is breaking the lease. The breaker file is
+                // removed if they seem to have crashed while doing that.
+                let modified = match fs::metadata(&breaker_path) {
+                    Ok(metadata) => metadata.modified()?,
+                    Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+                    Err(err) => return Err(err),
+                };
+                if self.is_older_than_lease(modified) {
+                    remove_file_if_exists(&breaker_path)?;
+                }
+                return Ok(false);
+            }
+            Err(err) => return Err(err),
+        }
+        let result = match fs::read(lease_path) {
+            Ok(raw) if raw == expected => remove_file_if_exists(lease_path).map(|()| true),
+            // A new lease was taken
+            Ok(_) => Ok(false),
+            // Somebody else released it
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(true),
+            Err(err) => Err(err),
+        };
+        remove_file_if_exists(&breaker_path)?;
+        result
+    }
+}
+
+/// Exclusively creates the lease file at `path`. Fails with
+/// [`ErrorKind::AlreadyExists`] if somebody else holds the lease.
+fn create_lease_file(path: &Path, data: &LeaseData) -> io::Result<()> {
+    let mut file = File::create_new(path)?;
+    serde_json::to_writer(&mut file, data).map_err(io::Error::other)?;
+    file.flush()
+}
+
+fn remove_file_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Lease held by this process. The lease file is removed when dropped.
+struct Lease<'a> {
+    store: &'a LeasedOpHeadsStore,
+    path: PathBuf,
+    data: LeaseData,
+    acquired_at: Instant,
+}
+
+impl Lease<'_> {
+    fn io_error(&self, source: io::Error) -> LeaseError {
+        LeaseError::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    /// Checks that the lease file is still ours.
+    fn check(&self) -> Result<(), LeaseError> {
+        let state = self
+            .store
+            .read_lease(&self.path)
+            .map_err(|err| self.io_error(err))?;
+        match state {
+            LeaseState::Held { data, .. } if data == self.data => Ok(()),
+            _ => Err(LeaseError::Lost),
+        }
+    }
+
+    /// Checks that the lease is still ours, and extends it if more than half
+    /// of its duration has passed.
+    fn renew(&mut self) -> Result<(), LeaseError> {
+        self.check()?;
+        if self.acquired_at.elapsed() < self.store.options.duration / 2 {
+            return Ok(());
+        }
+        // The lease file is replaced by removing it and exclusively creating
+        // it again, so that renames aren't needed. If somebody else takes the
+        // lease in between, the renewal fails. Readers that see the new file
+        // partially written consider the lease held, as in acquire_lease().
+        let data = LeaseData::new(self.data.owner.clone(), self.store.options.duration);
+        remove_file_if_exists(&self.path).map_err(|err| self.io_error(err))?;
+        match create_lease_file(&self.path, &data) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => return Err(LeaseError::Lost),
+            Err(err) => return Err(self.io_error(err)),
+        }
+        self.data = data;
+        self.acquired_at = Instant::now();
+        self.check()
+    }
+}
+
+impl Drop for Lease<'_> {
+    fn drop(&mut self) {
+        if self.check().is_err() {
+            return;
+        }
+        if let Err(err) = remove_file_if_exists(&self.path) {
+            tracing::warn!(?err, path = ?self.path, "failed to release op heads lease");
+        }
+    }
+}
+
+impl OpHeadsStore for LeasedOpHeadsStore {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        Self::name()
+    }
+
+    /// Updates the heads while holding the lease.
+    ///
+    /// If the lease is lost before the old heads are removed, e.g. because the
+    /// process was suspended for longer than the lease duration, the update
+    /// fails. The new head may have been recorded already, in which case the
+    /// old heads are kept and the divergent heads will be resolved when the
+    /// repo is loaded next.
+    fn update_op_heads(
+        &self,
+        old_ids: &[OperationId],
+        new_id: &OperationId,
+    ) -> Result<(), OpHeadsStoreError> {
+        assert!(!old_ids.contains(new_id));
+        let to_write_err =
+            |err: Box<dyn std::error::Error + Send + Sync>| OpHeadsStoreError::Write {
+                new_op_id: new_id.clone(),
+                source: err,
+            };
+        let mut lease = self
+            .acquire_lease(self.lease_path())
+            .map_err(|err| to_write_err(err.into()))?;
+        self.add_op_head(new_id)
+            .map_err(|err| to_write_err(err.into()))?;
+        for old_id in old_ids {
+            lease.renew().map_err(|err| to_write_err(err.into()))?;
+            self.heads
+                .remove_op_head(old_id)
+                .map_err(|err| to_write_err(err.into()))?;
+        }
+        Ok(())
+    }
+
+    fn get_op_heads(&self) -> Result<Vec<OperationId>, OpHeadsStoreError> {
+        self.heads.get_op_heads()
+    }
+
+    /// Takes a separate lease which isn't renewed while it's held.
+    ///
+    /// The lock only prevents concurrent processes from doing the same work,
+    /// so it's fine for it to be broken once it expires.
+    fn lock(&self) -> Result<Box<dyn OpHeadsStoreLock + '_>, OpHeadsStoreError> {
+        // The update lease is taken by update_op_heads() while this lock is
+        // held, so this must be a different file.
+        let lease = self
+            .acquire_lease(self.lock_path())
+            .map_err(|err| OpHeadsStoreError::Lock(err.into()))?;
+        Ok(Box::new(LeasedOpHeadsStoreLock { _lease: lease }))
+    }
+}
+
+struct LeasedOpHeadsStoreLock<'a> {
+    _lease: Lease<'a>,
+}
+
+impl OpHeadsStoreLock for LeasedOpHeadsStoreLock<'_> {}
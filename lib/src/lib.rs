@@ -30,10 +30,14 @@ pub mod content_hash;
 
 pub mod absorb;
 pub mod annotate;
+pub mod api;
 pub mod backend;
+#[cfg(feature = "testing")]
+pub mod backend_test_suite;
 pub mod commit;
 pub mod commit_builder;
 pub mod config;
+pub mod config_lint;
 mod config_resolver;
 pub mod conflicts;
 pub mod copies;
@@ -54,33 +58,51 @@ pub mod fsmonitor;
 #[cfg(feature = "git")]
 pub mod git;
 #[cfg(not(feature = "git"))]
-/// A stub module that provides a no-op implementation of some of the functions
-/// in the `git` module.
+/// A stub module that provides the read-only queries of the `git` module.
+///
+/// Repos created by a build with the "git" feature can still be opened. Their
+/// Git refs are kept in the view, but can't be imported or exported.
 pub mod git {
-    use crate::ref_name::RemoteName;
-    /// Determine, by its name, if a remote refers to the special local-only
-    /// "git" remote that is used in the Git backend.
-    ///
-    /// This function always returns false if the "git" feature is not enabled.
-    pub fn is_special_git_remote(_remote: &RemoteName) -> bool {
-        false
+    use std::convert::Infallible;
+
+    use thiserror::Error;
+
+    pub use crate::git_refs::is_special_git_remote;
+    pub use crate::git_refs::parse_git_ref;
+    pub use crate::git_refs::GitRefKind;
+    pub use crate::git_refs::REMOTE_NAME_FOR_LOCAL_GIT_REPO;
+    pub use crate::git_refs::RESERVED_REMOTE_REF_NAMESPACE;
+    use crate::store::Store;
+
+    /// Error returned when the Git backend is requested.
+    #[derive(Debug, Error)]
+    #[error("The repo is not backed by a Git repo (jj was built without Git support)")]
+    pub struct UnexpectedGitBackendError;
+
+    /// Always fails because the Git backend isn't available.
+    pub fn get_git_backend(_store: &Store) -> Result<&Infallible, UnexpectedGitBackendError> {
+        Err(UnexpectedGitBackendError)
     }
 }
 #[cfg(feature = "git")]
 pub mod git_backend;
+mod git_refs;
 #[cfg(feature = "git")]
 mod git_subprocess;
+pub mod gitattributes;
 pub mod gitignore;
 pub mod gpg_signing;
 pub mod graph;
 pub mod hex_util;
 pub mod id_prefix;
 pub mod index;
+pub mod leased_op_heads_store;
 pub mod local_working_copy;
 pub mod lock;
 pub mod matchers;
 pub mod merge;
 pub mod merged_tree;
+pub mod metrics;
 pub mod object_id;
 pub mod op_heads_store;
 pub mod op_store;
@@ -92,12 +114,16 @@ pub mod ref_name;
 pub mod refs;
 pub mod repo;
 pub mod repo_path;
+pub mod repo_pin;
+pub mod retrying_backend;
 pub mod revset;
 mod revset_parser;
 pub mod rewrite;
+pub mod rewrite_policy;
 #[cfg(feature = "testing")]
 pub mod secret_backend;
 pub mod settings;
+pub mod settings_schema;
 pub mod signing;
 // TODO: This file is mostly used for testing, whenever we no longer require it
 // in the lib it should be moved to the examples (e.g
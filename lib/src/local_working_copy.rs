@@ -17,8 +17,11 @@
 
 use std::any::Any;
 use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
 use std::collections::HashSet;
 use std::error::Error;
+use std::ffi::OsString;
 use std::fs;
 use std::fs::DirEntry;
 use std::fs::File;
@@ -51,7 +54,6 @@ use prost::Message as _;
 use rayon::iter::IntoParallelIterator as _;
 use rayon::prelude::IndexedParallelIterator as _;
 use rayon::prelude::ParallelIterator as _;
-use tempfile::NamedTempFile;
 use thiserror::Error;
 use tokio::io::AsyncRead;
 use tracing::instrument;
@@ -69,15 +71,22 @@ use crate::backend::TreeValue;
 use crate::commit::Commit;
 use crate::conflicts;
 use crate::conflicts::choose_materialized_conflict_marker_len;
-use crate::conflicts::materialize_merge_result_to_bytes_with_marker_len;
+use crate::conflicts::materialize_merge_result_to_bytes_with_labels;
 use crate::conflicts::materialize_tree_value;
+use crate::conflicts::try_materialize_file_conflict_value;
+use crate::conflicts::ConflictLabels;
 use crate::conflicts::ConflictMarkerStyle;
+use crate::conflicts::MaterializedFileConflictValue;
 use crate::conflicts::MaterializedTreeValue;
 use crate::conflicts::MIN_CONFLICT_MARKER_LEN;
+use crate::file_util::check_case_insensitive_fs;
 use crate::file_util::check_symlink_support;
 use crate::file_util::copy_async_to_sync;
+use crate::file_util::persist_atomically_with;
 use crate::file_util::try_symlink;
 use crate::file_util::BlockingAsyncReader;
+use crate::file_util::Durability;
+use crate::file_util::PathError;
 #[cfg(feature = "watchman")]
 use crate::fsmonitor::watchman;
 use crate::fsmonitor::FsmonitorSettings;
@@ -106,8 +115,10 @@ use crate::repo_path::RepoPathBuf;
 use crate::repo_path::RepoPathComponent;
 use crate::store::Store;
 use crate::tree::Tree;
+use crate::working_copy::ChangedPathsOptions;
 use crate::working_copy::CheckoutError;
 use crate::working_copy::CheckoutOptions;
+use crate::working_copy::CheckoutProgress;
 use crate::working_copy::CheckoutStats;
 use crate::working_copy::LockedWorkingCopy;
 use crate::working_copy::ResetError;
@@ -161,12 +172,20 @@ pub struct MaterializedConflictData {
     pub conflict_marker_len: u32,
 }
 
+/// Contents of the file written in place of a conflicted file when conflicts
+/// are materialized lazily. The file is never snapshotted with these contents.
+pub const CONFLICT_PLACEHOLDER: &[u8] =
+    b"This file has conflicts which haven't been written to the working copy yet.\n";
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct FileState {
     pub file_type: FileType,
     pub mtime: MillisSinceEpoch,
     pub size: u64,
     pub materialized_conflict_data: Option<MaterializedConflictData>,
+    /// Whether the file is a placeholder for a conflict which will be
+    /// materialized on demand.
+    pub needs_materialization: bool,
     /* TODO: What else do we need here? Git stores a lot of fields.
      * TODO: Could possibly handle case-insensitive file systems keeping an
      *       Option<PathBuf> with the actual path here. */
@@ -174,7 +193,8 @@ pub struct FileState {
 
 impl FileState {
     /// Check whether a file state appears clean compared to a previous file
-    /// state, ignoring materialized conflict data.
+    /// state, ignoring materialized conflict data and whether the conflict
+    /// needs materialization.
     pub fn is_clean(&self, old_file_state: &Self) -> bool {
         self.file_type == old_file_state.file_type
             && self.mtime == old_file_state.mtime
@@ -190,6 +210,7 @@ impl FileState {
             mtime: MillisSinceEpoch(0),
             size: 0,
             materialized_conflict_data: None,
+            needs_materialization: false,
         }
     }
 
@@ -205,6 +226,7 @@ impl FileState {
             mtime: mtime_from_metadata(metadata),
             size,
             materialized_conflict_data,
+            needs_materialization: false,
         }
     }
 
@@ -217,6 +239,7 @@ impl FileState {
             mtime: mtime_from_metadata(metadata),
             size: metadata.len(),
             materialized_conflict_data: None,
+            needs_materialization: false,
         }
     }
 
@@ -226,6 +249,7 @@ impl FileState {
             mtime: MillisSinceEpoch(0),
             size: 0,
             materialized_conflict_data: None,
+            needs_materialization: false,
         }
     }
 }
@@ -443,11 +467,16 @@ pub struct TreeState {
     working_copy_path: PathBuf,
     state_path: PathBuf,
     tree_id: MergedTreeId,
+    /// The tree from which a checkout to `tree_id` was interrupted, if any.
+    /// Files which differ between the two trees may not be up to date on disk.
+    interrupted_checkout: Option<MergedTreeId>,
     file_states: FileStatesMap,
     // Currently only path prefixes
     sparse_patterns: Vec<RepoPathBuf>,
     own_mtime: MillisSinceEpoch,
     symlink_support: bool,
+    /// Whether the file system is case-insensitive, probed on first checkout.
+    case_insensitive_fs: OnceLock<bool>,
 
     /// The most recent clock value returned by Watchman. Will only be set if
     /// the repo is configured to use the Watchman filesystem monitor and
@@ -480,6 +509,7 @@ fn file_state_from_proto(proto: &crate::protos::working_copy::FileState) -> File
                 conflict_marker_len: data.conflict_marker_len,
             }
         }),
+        needs_materialization: proto.needs_materialization,
     }
 }
 
@@ -504,6 +534,7 @@ fn file_state_to_proto(file_state: &FileState) -> crate::protos::working_copy::F
             conflict_marker_len: data.conflict_marker_len,
         }
     });
+    proto.needs_materialization = file_state.needs_materialization;
     proto
 }
 
@@ -549,6 +580,46 @@ fn sparse_patterns_from_proto(
     sparse_patterns
 }
 
+/// New value of a path to be written by `TreeState::update()`.
+enum CheckoutValue {
+    Materialized(MaterializedTreeValue),
+    /// File conflict to be materialized on demand. Only a placeholder is
+    /// written to the working copy.
+    PendingConflict {
+        executable: bool,
+    },
+}
+
+impl CheckoutValue {
+    fn is_absent(&self) -> bool {
+        match self {
+            CheckoutValue::Materialized(value) => value.is_absent(),
+            CheckoutValue::PendingConflict { .. } => false,
+        }
+    }
+}
+
+/// Returns the contents to be written for the file conflict, and the data to
+/// parse the markers back on snapshot.
+fn materialize_file_conflict(
+    file: &MaterializedFileConflictValue,
+    conflict_marker_style: ConflictMarkerStyle,
+    conflict_labels: &ConflictLabels,
+) -> (Vec<u8>, MaterializedConflictData) {
+    let conflict_marker_len = choose_materialized_conflict_marker_len(&file.contents);
+    let data = materialize_merge_result_to_bytes_with_labels(
+        &file.contents,
+        conflict_marker_style,
+        conflict_marker_len,
+        conflict_labels,
+    )
+    .into();
+    let materialized_conflict_data = MaterializedConflictData {
+        conflict_marker_len: conflict_marker_len.try_into().unwrap_or(u32::MAX),
+    };
+    (data, materialized_conflict_data)
+}
+
 /// Creates intermediate directories from the `working_copy_path` to the
 /// `repo_path` parent. Returns disk path for the `repo_path` file.
 ///
@@ -629,6 +700,20 @@ fn remove_old_file(disk_path: &Path) -> Result<bool, CheckoutError> {
     }
 }
 
+/// Returns true if `disk_path` is a regular file containing the placeholder
+/// written in place of a conflict by a lazy checkout.
+fn is_conflict_placeholder(disk_path: &Path) -> io::Result<bool> {
+    let metadata = match disk_path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    if !metadata.is_file() || metadata.len() != CONFLICT_PLACEHOLDER.len() as u64 {
+        return Ok(false);
+    }
+    Ok(fs::read(disk_path)? == CONFLICT_PLACEHOLDER)
+}
+
 /// Checks if new file or symlink named `disk_path` can be created.
 ///
 /// If the file already exists, this function return `Ok(false)` to signal
@@ -675,6 +760,32 @@ fn can_create_new_file(disk_path: &Path) -> Result<bool, CheckoutError> {
     Ok(new_file_created)
 }
 
+/// Renames `old_disk_path` to `new_disk_path` which only differ in casing.
+///
+/// Since renaming directly may be a no-op on case-insensitive file systems,
+/// the entry is moved through a temporary name.
+fn rename_case_only(old_disk_path: &Path, new_disk_path: &Path) -> Result<(), CheckoutError> {
+    reject_reserved_existing_path(old_disk_path)?;
+    let mut temp_name = OsString::from(".jj-rename-");
+    temp_name.push(new_disk_path.file_name().unwrap());
+    let temp_path = new_disk_path.with_file_name(temp_name);
+    let rename_error = |err: io::Error| CheckoutError::Other {
+        message: format!(
+            "Failed to rename {} to {}",
+            old_disk_path.display(),
+            new_disk_path.display()
+        ),
+        err: err.into(),
+    };
+    match fs::rename(old_disk_path, &temp_path) {
+        Ok(()) => {}
+        // The file may have been deleted by user
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(rename_error(err)),
+    }
+    fs::rename(&temp_path, new_disk_path).map_err(rename_error)
+}
+
 const RESERVED_DIR_NAMES: &[&str] = &[".git", ".jj"];
 
 /// Suppose the `disk_path` exists, checks if the last component points to
@@ -743,6 +854,7 @@ fn file_state(metadata: &Metadata) -> Option<FileState> {
             mtime,
             size,
             materialized_conflict_data: None,
+            needs_materialization: false,
         }
     })
 }
@@ -778,6 +890,12 @@ impl TreeState {
         &self.tree_id
     }
 
+    /// Returns true if a checkout was interrupted and hasn't been finished
+    /// since.
+    pub fn has_interrupted_checkout(&self) -> bool {
+        self.interrupted_checkout.is_some()
+    }
+
     pub fn file_states(&self) -> FileStates<'_> {
         self.file_states.all()
     }
@@ -807,10 +925,12 @@ impl TreeState {
             working_copy_path,
             state_path,
             tree_id,
+            interrupted_checkout: None,
             file_states: FileStatesMap::new(),
             sparse_patterns: vec![RepoPathBuf::root()],
             own_mtime: MillisSinceEpoch(0),
             symlink_support: check_symlink_support().unwrap_or(false),
+            case_insensitive_fs: OnceLock::new(),
             watchman_clock: None,
         }
     }
@@ -871,6 +991,14 @@ impl TreeState {
                 .collect();
             self.tree_id = MergedTreeId::Merge(tree_ids_builder.build());
         }
+        if !proto.interrupted_checkout_tree_ids.is_empty() {
+            let tree_ids_builder: MergeBuilder<TreeId> = proto
+                .interrupted_checkout_tree_ids
+                .iter()
+                .map(|id| TreeId::new(id.clone()))
+                .collect();
+            self.interrupted_checkout = Some(MergedTreeId::Merge(tree_ids_builder.build()));
+        }
         self.file_states =
             FileStatesMap::from_proto(proto.file_states, proto.is_file_states_sorted);
         self.sparse_patterns = sparse_patterns_from_proto(proto.sparse_patterns.as_ref());
@@ -889,6 +1017,10 @@ impl TreeState {
                 proto.tree_ids = tree_ids.iter().map(|id| id.to_bytes()).collect();
             }
         }
+        if let Some(tree_id) = &self.interrupted_checkout {
+            proto.interrupted_checkout_tree_ids =
+                tree_id.to_merge().iter().map(|id| id.to_bytes()).collect();
+        }
 
         proto.file_states = self.file_states.data.clone();
         // `FileStatesMap` is guaranteed to be sorted.
@@ -902,28 +1034,29 @@ impl TreeState {
         proto.sparse_patterns = Some(sparse_patterns);
         proto.watchman_clock = self.watchman_clock.clone();
 
-        let mut temp_file = NamedTempFile::new_in(&self.state_path).unwrap();
-        temp_file
-            .as_file_mut()
-            .write_all(&proto.encode_to_vec())
-            .map_err(|err| TreeStateError::WriteTreeState {
-                path: self.state_path.clone(),
-                source: err,
-            })?;
         // update own write time while we before we rename it, so we know
         // there is no unknown data in it
         self.update_own_mtime();
-        // TODO: Retry if persisting fails (it will on Windows if the file happened to
-        // be open for read).
         let target_path = self.state_path.join("tree_state");
-        temp_file
-            .persist(&target_path)
-            .map_err(|tempfile::PersistError { error, file: _ }| {
+        persist_atomically_with(
+            &self.state_path,
+            "tree_state",
+            Durability::Buffered,
+            |writer| writer.write_all(&proto.encode_to_vec()),
+        )
+        .map_err(|PathError { path, error }| {
+            if path == target_path {
                 TreeStateError::PersistTreeState {
-                    path: target_path.clone(),
+                    path,
                     source: error,
                 }
-            })?;
+            } else {
+                TreeStateError::WriteTreeState {
+                    path,
+                    source: error,
+                }
+            }
+        })?;
         Ok(())
     }
 
@@ -988,6 +1121,10 @@ impl TreeState {
             conflict_marker_style,
         } = options;
 
+        if self.interrupted_checkout.is_some() {
+            return Err(SnapshotError::InterruptedCheckout);
+        }
+
         let sparse_matcher = self.sparse_matcher();
 
         let fsmonitor_clock_needs_save = *fsmonitor_settings != FsmonitorSettings::None;
@@ -1140,6 +1277,200 @@ impl TreeState {
             watchman_clock,
         })
     }
+
+    /// Returns paths matching the `matcher` that might differ between the
+    /// `base_tree` and the working copy. Unlike `snapshot()`, this doesn't
+    /// write files to the store nor update the tree state.
+    #[instrument(skip_all)]
+    pub fn changed_paths_since(
+        &self,
+        base_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        options: &ChangedPathsOptions,
+    ) -> Result<Vec<RepoPathBuf>, SnapshotError> {
+        let ChangedPathsOptions {
+            base_ignores,
+            fsmonitor_settings,
+            compare_contents,
+        } = options;
+
+        if self.interrupted_checkout.is_some() {
+            return Err(SnapshotError::InterruptedCheckout);
+        }
+
+        // Paths that changed up to the last snapshot
+        let current_tree = self.current_tree()?;
+        let mut changed_paths: BTreeSet<RepoPathBuf> = base_tree
+            .diff_stream(&current_tree, matcher)
+            .map(|TreeDiffEntry { path, .. }| path)
+            .collect::<Vec<_>>()
+            .block_on()
+            .into_iter()
+            .collect();
+
+        // Paths that changed on disk since the last snapshot
+        let sparse_matcher = self.sparse_matcher();
+        let FsmonitorMatcher {
+            matcher: fsmonitor_matcher,
+            watchman_clock: _,
+        } = self.make_fsmonitor_matcher(fsmonitor_settings)?;
+        let fsmonitor_matcher = match fsmonitor_matcher.as_ref() {
+            None => &EverythingMatcher,
+            Some(fsmonitor_matcher) => fsmonitor_matcher.as_ref(),
+        };
+        let disk_matcher = IntersectionMatcher::new(
+            matcher,
+            IntersectionMatcher::new(sparse_matcher.as_ref(), fsmonitor_matcher),
+        );
+        if disk_matcher.visit(RepoPath::root()).is_nothing() {
+            return Ok(changed_paths.into_iter().collect());
+        }
+        for (path, file_state) in self.file_states.all() {
+            if file_state.file_type == FileType::GitSubmodule
+                || !disk_matcher.matches(path)
+                || changed_paths.contains(path)
+            {
+                continue;
+            }
+            if self.is_path_changed_on_disk(&current_tree, path, &file_state, *compare_contents)? {
+                changed_paths.insert(path.to_owned());
+            }
+        }
+        self.collect_untracked_paths(
+            RepoPath::root(),
+            &self.working_copy_path,
+            base_ignores,
+            &disk_matcher,
+            &mut changed_paths,
+        )?;
+        Ok(changed_paths.into_iter().collect())
+    }
+
+    /// Checks whether the tracked file at `path` might have changed since the
+    /// last snapshot. If the stat information is inconclusive and
+    /// `compare_contents` is set, the file contents are compared.
+    fn is_path_changed_on_disk(
+        &self,
+        current_tree: &MergedTree,
+        path: &RepoPath,
+        current_file_state: &FileState,
+        compare_contents: bool,
+    ) -> Result<bool, SnapshotError> {
+        let disk_path = path.to_fs_path(&self.working_copy_path)?;
+        let metadata = match disk_path.symlink_metadata() {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(true),
+            Err(err) => {
+                return Err(SnapshotError::Other {
+                    message: format!("Failed to stat file {}", disk_path.display()),
+                    err: err.into(),
+                });
+            }
+        };
+        let Some(new_file_state) = file_state(&metadata) else {
+            return Ok(true);
+        };
+        // See get_updated_tree_value() for why the own mtime is compared.
+        if new_file_state.is_clean(current_file_state) && current_file_state.mtime < self.own_mtime
+        {
+            return Ok(false);
+        }
+        // Only the mtime tells the file apart. Materialized conflicts are
+        // always reported since the markers would have to be parsed.
+        let is_stat_ambiguous = new_file_state.file_type == current_file_state.file_type
+            && new_file_state.size == current_file_state.size
+            && current_file_state.materialized_conflict_data.is_none();
+        if !compare_contents || !is_stat_ambiguous {
+            return Ok(true);
+        }
+        let value = current_tree.path_value(path)?;
+        match (&new_file_state.file_type, value.as_resolved()) {
+            (FileType::Normal { .. }, Some(Some(TreeValue::File { .. })))
+            | (FileType::Symlink, Some(Some(TreeValue::Symlink(_)))) => {}
+            _ => return Ok(true),
+        }
+        match materialize_tree_value(&self.store, path, value).block_on()? {
+            MaterializedTreeValue::File(mut file) => {
+                let expected = file.read_all(path).block_on()?;
+                let actual = fs::read(&disk_path).map_err(|err| SnapshotError::Other {
+                    message: format!("Failed to read file {}", disk_path.display()),
+                    err: err.into(),
+                })?;
+                Ok(actual != expected)
+            }
+            MaterializedTreeValue::Symlink { id: _, target } => {
+                let actual = disk_path.read_link().map_err(|err| SnapshotError::Other {
+                    message: format!("Failed to read symlink {}", disk_path.display()),
+                    err: err.into(),
+                })?;
+                Ok(actual.to_str() != Some(target.as_str()))
+            }
+            _ => Ok(true),
+        }
+    }
+
+    /// Walks the directory `dir` to collect new files which aren't tracked nor
+    /// ignored.
+    fn collect_untracked_paths(
+        &self,
+        dir: &RepoPath,
+        disk_dir: &Path,
+        git_ignore: &Arc<GitIgnoreFile>,
+        matcher: &dyn Matcher,
+        untracked_paths: &mut BTreeSet<RepoPathBuf>,
+    ) -> Result<(), SnapshotError> {
+        let git_ignore = git_ignore
+            .chain_with_file(&dir.to_internal_dir_string(), disk_dir.join(".gitignore"))?;
+        let dir_entries: Vec<_> = disk_dir
+            .read_dir()
+            .and_then(|entries| entries.try_collect())
+            .map_err(|err| SnapshotError::Other {
+                message: format!("Failed to read directory {}", disk_dir.display()),
+                err: err.into(),
+            })?;
+        let file_states = self.file_states.all();
+        for entry in dir_entries {
+            let name_string = entry
+                .file_name()
+                .into_string()
+                .map_err(|path| SnapshotError::InvalidUtf8Path { path })?;
+            if RESERVED_DIR_NAMES.contains(&name_string.as_str()) {
+                continue;
+            }
+            let name = RepoPathComponent::new(&name_string).unwrap();
+            let path = dir.join(name);
+            let file_type = entry.file_type().map_err(|err| SnapshotError::Other {
+                message: format!("Failed to stat file {}", entry.path().display()),
+                err: err.into(),
+            })?;
+            if file_type.is_dir() {
+                // Tracked paths in ignored directories have been checked by
+                // the caller.
+                let is_submodule = file_states
+                    .get(&path)
+                    .is_some_and(|state| state.file_type == FileType::GitSubmodule);
+                if !is_submodule
+                    && !git_ignore.matches(&path.to_internal_dir_string())
+                    && !matcher.visit(&path).is_nothing()
+                {
+                    self.collect_untracked_paths(
+                        &path,
+                        &entry.path(),
+                        &git_ignore,
+                        matcher,
+                        untracked_paths,
+                    )?;
+                }
+            } else if (file_type.is_file() || file_type.is_symlink())
+                && matcher.matches(&path)
+                && !file_states.contains_path(&path)
+                && !git_ignore.matches(path.as_internal_file_string())
+            {
+                untracked_paths.insert(path);
+            }
+        }
+        Ok(())
+    }
 }
 
 struct DirectoryToVisit<'a> {
@@ -1383,6 +1714,18 @@ impl FileSnapshotter<'_> {
         maybe_current_file_state: Option<&FileState>,
         mut new_file_state: FileState,
     ) -> Result<(), SnapshotError> {
+        if let Some(current_file_state) =
+            maybe_current_file_state.filter(|state| state.needs_materialization)
+        {
+            if self.is_unmaterialized_conflict(disk_path, current_file_state, &new_file_state)? {
+                // The placeholder must never be recorded as the file contents.
+                new_file_state.needs_materialization = true;
+                if new_file_state != *current_file_state {
+                    self.file_states_tx.send((path, new_file_state)).ok();
+                }
+                return Ok(());
+            }
+        }
         let update = self.get_updated_tree_value(
             &path,
             disk_path,
@@ -1438,6 +1781,25 @@ impl FileSnapshotter<'_> {
             .ok();
     }
 
+    /// Returns true if the file is still the placeholder of a conflict which
+    /// hasn't been materialized.
+    fn is_unmaterialized_conflict(
+        &self,
+        disk_path: &Path,
+        current_file_state: &FileState,
+        new_file_state: &FileState,
+    ) -> Result<bool, SnapshotError> {
+        if new_file_state.is_clean(current_file_state)
+            && current_file_state.mtime < self.tree_state.own_mtime
+        {
+            return Ok(true);
+        }
+        is_conflict_placeholder(disk_path).map_err(|err| SnapshotError::Other {
+            message: format!("Failed to read file {}", disk_path.display()),
+            err: err.into(),
+        })
+    }
+
     fn get_updated_tree_value(
         &self,
         repo_path: &RepoPath,
@@ -1731,6 +2093,51 @@ impl TreeState {
         ))
     }
 
+    /// Writes a placeholder for a file conflict which will be materialized by
+    /// `materialize_conflicts()`.
+    fn write_conflict_placeholder(
+        &self,
+        disk_path: &Path,
+        executable: bool,
+    ) -> Result<FileState, CheckoutError> {
+        let file_state =
+            self.write_conflict(disk_path, CONFLICT_PLACEHOLDER.to_vec(), executable, None)?;
+        Ok(FileState {
+            needs_materialization: true,
+            ..file_state
+        })
+    }
+
+    /// Atomically replaces the placeholder at `disk_path` with the
+    /// materialized conflict.
+    fn replace_conflict_placeholder(
+        &self,
+        disk_path: &Path,
+        conflict_data: &[u8],
+        executable: bool,
+        materialized_conflict_data: Option<MaterializedConflictData>,
+    ) -> Result<FileState, CheckoutError> {
+        let dir = disk_path.parent().unwrap();
+        let name = disk_path.file_name().unwrap().to_str().unwrap();
+        persist_atomically_with(dir, name, Durability::Buffered, |writer| {
+            writer.write_all(conflict_data)
+        })
+        .map_err(|PathError { path, error }| CheckoutError::Other {
+            message: format!("Failed to write conflict to file {}", path.display()),
+            err: error.into(),
+        })?;
+        self.set_executable(disk_path, executable)?;
+        let metadata = disk_path
+            .symlink_metadata()
+            .map_err(|err| checkout_error_for_stat_error(err, disk_path))?;
+        Ok(FileState::for_file(
+            executable,
+            conflict_data.len() as u64,
+            &metadata,
+            materialized_conflict_data,
+        ))
+    }
+
     #[cfg_attr(windows, allow(unused_variables))]
     fn set_executable(&self, disk_path: &Path, executable: bool) -> Result<(), CheckoutError> {
         #[cfg(unix)]
@@ -1747,21 +2154,55 @@ impl TreeState {
         new_tree: &MergedTree,
         options: &CheckoutOptions,
     ) -> Result<CheckoutStats, CheckoutError> {
-        let old_tree = self.current_tree().map_err(|err| match err {
-            err @ BackendError::ObjectNotFound { .. } => CheckoutError::SourceNotFound {
-                source: Box::new(err),
-            },
-            other => CheckoutError::InternalBackendError(other),
-        })?;
-        let stats = self
-            .update(
-                &old_tree,
-                new_tree,
-                self.sparse_matcher().as_ref(),
-                options.conflict_marker_style,
-            )
-            .block_on()?;
+        let mut stats = CheckoutStats::default();
+        if let Some(interrupted_tree_id) = &self.interrupted_checkout {
+            // Redo the interrupted checkout. Files which were already updated
+            // are either rewritten or skipped as if they were untracked.
+            let interrupted_tree = self
+                .store
+                .get_root_tree(interrupted_tree_id)
+                .map_err(checkout_error_for_current_tree_error)?;
+            let old_tree = self
+                .current_tree()
+                .map_err(checkout_error_for_current_tree_error)?;
+            stats = self
+                .update(
+                    &interrupted_tree,
+                    &old_tree,
+                    self.sparse_matcher().as_ref(),
+                    options,
+                )
+                .block_on()?;
+            self.interrupted_checkout = None;
+        }
+        if new_tree.id() == self.tree_id {
+            return Ok(stats);
+        }
+        let old_tree = self
+            .current_tree()
+            .map_err(checkout_error_for_current_tree_error)?;
+        // Record that the checkout is in progress before touching any files,
+        // so an interrupted checkout will be finished by the next one.
+        self.interrupted_checkout = Some(old_tree.id());
         self.tree_id = new_tree.id();
+        self.save().map_err(|err| CheckoutError::Other {
+            message: "Failed to write working copy state".to_string(),
+            err: err.into(),
+        })?;
+        let result = self
+            .update(&old_tree, new_tree, self.sparse_matcher().as_ref(), options)
+            .block_on();
+        if let Err(err) = &result {
+            if !matches!(err, CheckoutError::Cancelled) {
+                // Retrying a failed checkout would likely fail again, so
+                // record the previous state as if the checkout never started.
+                self.interrupted_checkout = None;
+                self.tree_id = old_tree.id();
+                self.save().ok();
+            }
+        }
+        let stats = result?;
+        self.interrupted_checkout = None;
         Ok(stats)
     }
 
@@ -1770,32 +2211,19 @@ impl TreeState {
         sparse_patterns: Vec<RepoPathBuf>,
         options: &CheckoutOptions,
     ) -> Result<CheckoutStats, CheckoutError> {
-        let tree = self.current_tree().map_err(|err| match err {
-            err @ BackendError::ObjectNotFound { .. } => CheckoutError::SourceNotFound {
-                source: Box::new(err),
-            },
-            other => CheckoutError::InternalBackendError(other),
-        })?;
+        let tree = self
+            .current_tree()
+            .map_err(checkout_error_for_current_tree_error)?;
         let old_matcher = PrefixMatcher::new(&self.sparse_patterns);
         let new_matcher = PrefixMatcher::new(&sparse_patterns);
         let added_matcher = DifferenceMatcher::new(&new_matcher, &old_matcher);
         let removed_matcher = DifferenceMatcher::new(&old_matcher, &new_matcher);
         let empty_tree = MergedTree::resolved(Tree::empty(self.store.clone(), RepoPathBuf::root()));
         let added_stats = self
-            .update(
-                &empty_tree,
-                &tree,
-                &added_matcher,
-                options.conflict_marker_style,
-            )
+            .update(&empty_tree, &tree, &added_matcher, options)
             .block_on()?;
         let removed_stats = self
-            .update(
-                &tree,
-                &empty_tree,
-                &removed_matcher,
-                options.conflict_marker_style,
-            )
+            .update(&tree, &empty_tree, &removed_matcher, options)
             .block_on()?;
         self.sparse_patterns = sparse_patterns;
         assert_eq!(added_stats.updated_files, 0);
@@ -1811,13 +2239,104 @@ impl TreeState {
         })
     }
 
+    /// Writes the conflict markers of the placeholders matching `matcher`
+    /// which were left by a checkout with `CheckoutOptions::lazy_conflicts`.
+    pub fn materialize_conflicts(
+        &mut self,
+        matcher: &dyn Matcher,
+        options: &CheckoutOptions,
+    ) -> Result<CheckoutStats, CheckoutError> {
+        let mut stats = CheckoutStats::default();
+        let pending_paths = self
+            .file_states
+            .all()
+            .iter()
+            .filter(|(path, state)| state.needs_materialization && matcher.matches(path))
+            .map(|(path, state)| (path.to_owned(), state))
+            .collect_vec();
+        if pending_paths.is_empty() {
+            return Ok(stats);
+        }
+        let tree = self
+            .current_tree()
+            .map_err(checkout_error_for_current_tree_error)?;
+        let mut conflicts = Vec::new();
+        for (path, file_state) in pending_paths {
+            let value = tree.path_value(&path)?;
+            let file =
+                try_materialize_file_conflict_value(&self.store, &path, &value).block_on()?;
+            let disk_path = create_parent_dirs(&self.working_copy_path, &path)?;
+            let (Some(file), Some(disk_path)) = (file, disk_path) else {
+                stats.skipped_files += 1;
+                continue;
+            };
+            // Placeholders which have been modified will be snapshotted as
+            // regular content.
+            if !is_conflict_placeholder(&disk_path)
+                .map_err(|err| checkout_error_for_stat_error(err, &disk_path))?
+            {
+                stats.skipped_files += 1;
+                continue;
+            }
+            let (data, materialized_conflict_data) = materialize_file_conflict(
+                &file,
+                options.conflict_marker_style,
+                &options.conflict_labels,
+            );
+            let file_state = FileState {
+                materialized_conflict_data: Some(materialized_conflict_data),
+                ..file_state
+            };
+            conflicts.push((path, file_state, disk_path, file.executable, data));
+        }
+        // Record how to parse the markers before writing them, so that a file
+        // written by an interrupted call is snapshotted as the same conflict.
+        self.file_states.merge_in(
+            conflicts
+                .iter()
+                .map(|(path, file_state, ..)| (path.clone(), file_state.clone()))
+                .collect(),
+            &HashSet::new(),
+        );
+        self.save().map_err(|err| CheckoutError::Other {
+            message: "Failed to write working copy state".to_string(),
+            err: err.into(),
+        })?;
+        let mut changed_file_states = Vec::new();
+        for (path, file_state, disk_path, executable, data) in conflicts {
+            let file_state = self.replace_conflict_placeholder(
+                &disk_path,
+                &data,
+                executable.unwrap_or(false),
+                file_state.materialized_conflict_data,
+            )?;
+            changed_file_states.push((path, file_state));
+            stats.updated_files += 1;
+        }
+        self.file_states
+            .merge_in(changed_file_states, &HashSet::new());
+        Ok(stats)
+    }
+
     async fn update(
         &mut self,
         old_tree: &MergedTree,
         new_tree: &MergedTree,
         matcher: &dyn Matcher,
-        conflict_marker_style: ConflictMarkerStyle,
+        options: &CheckoutOptions<'_>,
     ) -> Result<CheckoutStats, CheckoutError> {
+        let &CheckoutOptions {
+            conflict_marker_style,
+            ref conflict_labels,
+            lazy_conflicts,
+            progress,
+        } = options;
+        let total_files = if progress.is_some() {
+            old_tree.diff_stream(new_tree, matcher).count().await
+        } else {
+            0
+        };
+        let mut processed_files = 0;
         // TODO: maybe it's better not include the skipped counts in the "intended"
         // counts
         let mut stats = CheckoutStats {
@@ -1826,6 +2345,12 @@ impl TreeState {
             removed_files: 0,
             skipped_files: 0,
         };
+        let case_renamed_paths = if self.is_case_insensitive_fs() {
+            self.rename_case_changed_paths(old_tree, new_tree, matcher)
+                .await?
+        } else {
+            HashSet::new()
+        };
         let mut changed_file_states = Vec::new();
         let mut deleted_files = HashSet::new();
         let mut diff_stream = old_tree
@@ -1833,14 +2358,39 @@ impl TreeState {
             .map(|TreeDiffEntry { path, values }| async {
                 match values {
                     Ok((before, after)) => {
+                        if lazy_conflicts && !after.is_resolved() && after.to_file_merge().is_some()
+                        {
+                            let executable = after
+                                .to_executable_merge()
+                                .and_then(|merge| conflicts::resolve_file_executable(&merge))
+                                .unwrap_or(false);
+                            let value = CheckoutValue::PendingConflict { executable };
+                            return (path, Ok((before, value)));
+                        }
                         let result = materialize_tree_value(&self.store, &path, after).await;
-                        (path, result.map(|value| (before, value)))
+                        (
+                            path,
+                            result.map(|value| (before, CheckoutValue::Materialized(value))),
+                        )
                     }
                     Err(err) => (path, Err(err)),
                 }
             })
             .buffered(self.store.concurrency().max(1));
         while let Some((path, data)) = diff_stream.next().await {
+            if let Some(progress) = progress {
+                let progress_update = CheckoutProgress {
+                    processed_files,
+                    total_files,
+                };
+                if progress(progress_update).is_break() {
+                    // Keep the states of the files updated so far.
+                    self.file_states
+                        .merge_in(changed_file_states, &deleted_files);
+                    return Err(CheckoutError::Cancelled);
+                }
+            }
+            processed_files += 1;
             let (before, after) = data?;
             if after.is_absent() {
                 stats.removed_files += 1;
@@ -1858,7 +2408,10 @@ impl TreeState {
             // paths excluded by .gitignore can be marked as such so that
             // newly-"unignored" paths won't be snapshotted automatically.
             if matches!(before.as_normal(), Some(TreeValue::GitSubmodule(_)))
-                && matches!(after, MaterializedTreeValue::GitSubmodule(_))
+                && matches!(
+                    after,
+                    CheckoutValue::Materialized(MaterializedTreeValue::GitSubmodule(_))
+                )
             {
                 eprintln!("ignoring git submodule at {path:?}");
                 // Not updating the file state as if there were no diffs. Leave
@@ -1866,6 +2419,15 @@ impl TreeState {
                 continue;
             }
 
+            // If the path only differs in casing from another path, the file
+            // on disk has already been renamed. The old path should be
+            // forgotten, and the file at the new path should be overwritten.
+            let is_case_renamed = case_renamed_paths.contains(&path);
+            if is_case_renamed && after.is_absent() {
+                deleted_files.insert(path);
+                continue;
+            }
+
             // Create parent directories no matter if after.is_present(). This
             // ensures that the path never traverses symlinks.
             let Some(disk_path) = create_parent_dirs(&self.working_copy_path, &path)? else {
@@ -1874,7 +2436,8 @@ impl TreeState {
                 continue;
             };
             // If the path was present, check reserved path first and delete it.
-            let present_file_deleted = before.is_present() && remove_old_file(&disk_path)?;
+            let present_file_deleted =
+                (before.is_present() || is_case_renamed) && remove_old_file(&disk_path)?;
             // If not, create temporary file to test the path validity.
             if !present_file_deleted && !can_create_new_file(&disk_path)? {
                 changed_file_states.push((path, FileState::placeholder()));
@@ -1882,6 +2445,14 @@ impl TreeState {
                 continue;
             }
 
+            let after = match after {
+                CheckoutValue::Materialized(value) => value,
+                CheckoutValue::PendingConflict { executable } => {
+                    let file_state = self.write_conflict_placeholder(&disk_path, executable)?;
+                    changed_file_states.push((path, file_state));
+                    continue;
+                }
+            };
             // TODO: Check that the file has not changed before overwriting/removing it.
             let file_state = match after {
                 MaterializedTreeValue::Absent | MaterializedTreeValue::AccessDenied(_) => {
@@ -1913,17 +2484,8 @@ impl TreeState {
                     panic!("unexpected tree entry in diff at {path:?}");
                 }
                 MaterializedTreeValue::FileConflict(file) => {
-                    let conflict_marker_len =
-                        choose_materialized_conflict_marker_len(&file.contents);
-                    let data = materialize_merge_result_to_bytes_with_marker_len(
-                        &file.contents,
-                        conflict_marker_style,
-                        conflict_marker_len,
-                    )
-                    .into();
-                    let materialized_conflict_data = MaterializedConflictData {
-                        conflict_marker_len: conflict_marker_len.try_into().unwrap_or(u32::MAX),
-                    };
+                    let (data, materialized_conflict_data) =
+                        materialize_file_conflict(&file, conflict_marker_style, conflict_labels);
                     self.write_conflict(
                         &disk_path,
                         data,
@@ -1946,7 +2508,93 @@ impl TreeState {
         Ok(stats)
     }
 
+    /// Returns true if the working copy is on a case-insensitive file system.
+    /// The result is cached.
+    fn is_case_insensitive_fs(&self) -> bool {
+        *self.case_insensitive_fs.get_or_init(|| {
+            check_case_insensitive_fs(&self.state_path).unwrap_or_else(|err| {
+                tracing::warn!(?err, "Failed to probe file system case sensitivity");
+                false
+            })
+        })
+    }
+
+    /// Renames files and directories on disk which are removed from the
+    /// `old_tree` and added to the `new_tree` with only the casing changed.
+    /// Returns both the old and the new paths of the renamed files.
+    ///
+    /// On case-insensitive file systems, the new path would otherwise refer to
+    /// the old file, which would be deleted after the new file is skipped.
+    async fn rename_case_changed_paths(
+        &self,
+        old_tree: &MergedTree,
+        new_tree: &MergedTree,
+        matcher: &dyn Matcher,
+    ) -> Result<HashSet<RepoPathBuf>, CheckoutError> {
+        let fold_case = |path: &RepoPath| path.as_internal_file_string().to_lowercase();
+        let mut removed_paths = HashMap::new();
+        let mut added_paths = Vec::new();
+        let mut diff_stream = old_tree.diff_stream(new_tree, matcher);
+        while let Some(TreeDiffEntry { path, values }) = diff_stream.next().await {
+            let (before, after) = values?;
+            if after.is_absent() {
+                removed_paths.insert(fold_case(&path), path);
+            } else if before.is_absent() {
+                added_paths.push(path);
+            }
+        }
+
+        let mut case_renamed_paths = HashSet::new();
+        let mut renamed_dirs = HashSet::new();
+        'paths: for new_path in added_paths {
+            let Some(old_path) = removed_paths.get(&fold_case(&new_path)) else {
+                continue;
+            };
+            // Rename each differing component from the outermost one. Since
+            // the parent components have been renamed, the disk path of the
+            // old entry is the new parent path joined with the old name.
+            let mut old_prefix = RepoPathBuf::root();
+            let mut new_prefix = RepoPathBuf::root();
+            for (old_name, new_name) in old_path.components().zip(new_path.components()) {
+                let disk_prefix = new_prefix.join(old_name);
+                old_prefix = old_prefix.join(old_name);
+                new_prefix = new_prefix.join(new_name);
+                if old_name == new_name || renamed_dirs.contains(&new_prefix) {
+                    continue;
+                }
+                let is_dir = new_prefix != new_path;
+                // A directory can be renamed only if all paths in it are
+                // renamed.
+                if is_dir
+                    && (new_tree.path_value(&old_prefix)?.is_present()
+                        || old_tree.path_value(&new_prefix)?.is_present())
+                {
+                    continue 'paths;
+                }
+                let Some(old_disk_path) =
+                    create_parent_dirs(&self.working_copy_path, &disk_prefix)?
+                else {
+                    continue 'paths;
+                };
+                let new_disk_path = new_prefix.to_fs_path(&self.working_copy_path)?;
+                rename_case_only(&old_disk_path, &new_disk_path)?;
+                if is_dir {
+                    renamed_dirs.insert(new_prefix.clone());
+                }
+            }
+            case_renamed_paths.insert(old_path.clone());
+            case_renamed_paths.insert(new_path);
+        }
+        Ok(case_renamed_paths)
+    }
+
     pub async fn reset(&mut self, new_tree: &MergedTree) -> Result<(), ResetError> {
+        if self.interrupted_checkout.take().is_some() {
+            // The recorded file states can't be trusted, so start over as if
+            // recovering from a missing tree.
+            self.file_states.clear();
+            self.tree_id = self.store.empty_merged_tree_id();
+        }
         let old_tree = self.current_tree().map_err(|err| match err {
             err @ BackendError::ObjectNotFound { .. } => ResetError::SourceNotFound {
                 source: Box::new(err),
@@ -1996,6 +2644,7 @@ impl TreeState {
                     mtime: MillisSinceEpoch(0),
                     size: 0,
                     materialized_conflict_data: None,
+                    needs_materialization: false,
                 };
                 changed_file_states.push((path, file_state));
             }
@@ -2013,6 +2662,15 @@ impl TreeState {
     }
 }
 
+fn checkout_error_for_current_tree_error(err: BackendError) -> CheckoutError {
+    match err {
+        err @ BackendError::ObjectNotFound { .. } => CheckoutError::SourceNotFound {
+            source: Box::new(err),
+        },
+        other => CheckoutError::InternalBackendError(other),
+    }
+}
+
 fn checkout_error_for_stat_error(err: io::Error, path: &Path) -> CheckoutError {
     CheckoutError::Other {
         message: format!("Failed to stat file {}", path.display()),
@@ -2060,6 +2718,19 @@ impl WorkingCopy for LocalWorkingCopy {
         Ok(self.tree_state()?.sparse_patterns())
     }
 
+    fn changed_paths_since(
+        &self,
+        base_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        options: &ChangedPathsOptions,
+    ) -> Result<Vec<RepoPathBuf>, SnapshotError> {
+        let tree_state = self.tree_state().map_err(|err| SnapshotError::Other {
+            message: "Failed to read the working copy state".to_string(),
+            err: err.into(),
+        })?;
+        tree_state.changed_paths_since(base_tree, matcher, options)
+    }
+
     fn start_mutation(&self) -> Result<Box<dyn LockedWorkingCopy>, WorkingCopyStateError> {
         let lock_path = self.state_path.join("working_copy.lock");
         let lock = FileLock::lock(lock_path).map_err(|err| WorkingCopyStateError {
@@ -2150,14 +2821,13 @@ impl LocalWorkingCopy {
     }
 
     fn write_proto(&self, proto: crate::protos::working_copy::Checkout) {
-        let mut temp_file = NamedTempFile::new_in(&self.state_path).unwrap();
-        temp_file
-            .as_file_mut()
-            .write_all(&proto.encode_to_vec())
-            .unwrap();
-        // TODO: Retry if persisting fails (it will on Windows if the file happened to
-        // be open for read).
-        temp_file.persist(self.state_path.join("checkout")).unwrap();
+        persist_atomically_with(
+            &self.state_path,
+            "checkout",
+            Durability::Buffered,
+            |writer| writer.write_all(&proto.encode_to_vec()),
+        )
+        .unwrap();
     }
 
     fn checkout_state(&self) -> &CheckoutState {
@@ -2326,8 +2996,6 @@ impl LockedWorkingCopy for LockedLocalWorkingCopy {
         commit: &Commit,
         options: &CheckoutOptions,
     ) -> Result<CheckoutStats, CheckoutError> {
-        // TODO: Write a "pending_checkout" file with the new TreeId so we can
-        // continue an interrupted update if we find such a file.
         let new_tree = commit.tree()?;
         let tree_state = self
             .wc
@@ -2336,15 +3004,32 @@ impl LockedWorkingCopy for LockedLocalWorkingCopy {
                 message: "Failed to load the working copy state".to_string(),
                 err: err.into(),
             })?;
-        if tree_state.tree_id != *commit.tree_id() {
-            let stats = tree_state.check_out(&new_tree, options)?;
+        if tree_state.tree_id != *commit.tree_id() || tree_state.has_interrupted_checkout() {
+            // The state is updated even if the checkout fails midway.
             self.tree_state_dirty = true;
-            Ok(stats)
+            tree_state.check_out(&new_tree, options)
         } else {
             Ok(CheckoutStats::default())
         }
     }
 
+    fn materialize_conflicts(
+        &mut self,
+        matcher: &dyn Matcher,
+        options: &CheckoutOptions,
+    ) -> Result<CheckoutStats, CheckoutError> {
+        let stats = self
+            .wc
+            .tree_state_mut()
+            .map_err(|err| CheckoutError::Other {
+                message: "Failed to load the working copy state".to_string(),
+                err: err.into(),
+            })?
+            .materialize_conflicts(matcher, options)?;
+        self.tree_state_dirty = true;
+        Ok(stats)
+    }
+
     fn rename_workspace(&mut self, new_name: WorkspaceNameBuf) {
         self.new_workspace_name = Some(new_name);
     }
@@ -2464,6 +3149,7 @@ mod tests {
             mtime: MillisSinceEpoch(0),
             size,
             materialized_conflict_data: None,
+            needs_materialization: false,
         };
         let new_static_entry = |path: &'static str, size| (repo_path(path), new_state(size));
         let new_owned_entry = |path: &str, size| (repo_path(path).to_owned(), new_state(size));
@@ -2513,6 +3199,7 @@ mod tests {
             mtime: MillisSinceEpoch(0),
             size,
             materialized_conflict_data: None,
+            needs_materialization: false,
         };
         let new_proto_entry = |path: &str, size| {
             file_state_entry_to_proto(repo_path(path).to_owned(), &new_state(size))
@@ -2578,6 +3265,7 @@ mod tests {
             mtime: MillisSinceEpoch(0),
             size,
             materialized_conflict_data: None,
+            needs_materialization: false,
         };
         let new_proto_entry = |path: &str, size| {
             file_state_entry_to_proto(repo_path(path).to_owned(), &new_state(size))
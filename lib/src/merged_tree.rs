@@ -186,6 +186,17 @@ impl MergedTree {
         ConflictIterator::new(self)
     }
 
+    /// Like [`MergedTree::conflicts()`], but only yields conflicts at paths
+    /// matching `matcher`, and classifies each conflict as a [`ConflictInfo`].
+    pub fn conflicts_ex<'matcher>(
+        &self,
+        matcher: &'matcher dyn Matcher,
+    ) -> impl Iterator<Item = BackendResult<ConflictInfo>> + use<'matcher> {
+        ConflictIterator::new(self)
+            .filter(|(path, _)| matcher.matches(path))
+            .map(|(path, value)| Ok(ConflictInfo::new(path, value?)))
+    }
+
     /// Whether this tree has conflicts.
     pub fn has_conflict(&self) -> bool {
         !self.trees.is_resolved()
@@ -679,6 +690,70 @@ impl Iterator for ConflictIterator {
     }
 }
 
+/// The kind of a conflict at a single path, as reported by
+/// [`MergedTree::conflicts_ex()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// The sides have different contents that couldn't be merged. This also
+    /// includes conflicts involving symlinks and submodules.
+    ContentsConflict,
+    /// Some sides have a directory at the path and others have something
+    /// else.
+    FileDirectoryConflict,
+    /// The file contents can be merged, but the sides disagree about the
+    /// executable bit.
+    ExecBitConflict,
+    /// Some sides deleted the path while others modified it.
+    DeleteModify,
+}
+
+/// Structured information about a conflict at a single path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConflictInfo {
+    /// The conflicted path.
+    pub path: RepoPathBuf,
+    /// The simplified conflicted value at the path.
+    pub value: MergedTreeValue,
+    /// The kind of conflict.
+    pub kind: ConflictKind,
+    /// Indices of the sides (positive terms of `value`) where the path is
+    /// absent.
+    pub absent_sides: Vec<usize>,
+}
+
+impl ConflictInfo {
+    fn new(path: RepoPathBuf, value: MergedTreeValue) -> Self {
+        let value = value.simplify();
+        let absent_sides = value.adds().positions(|term| term.is_none()).collect_vec();
+        let present_terms = || value.iter().flatten();
+        let kind = if present_terms().any(|term| matches!(term, TreeValue::Tree(_)))
+            && present_terms().any(|term| !matches!(term, TreeValue::Tree(_)))
+        {
+            ConflictKind::FileDirectoryConflict
+        } else if !absent_sides.is_empty() {
+            ConflictKind::DeleteModify
+        } else if value
+            .to_file_merge()
+            .is_some_and(|file_ids| file_ids.resolve_trivial().is_some())
+        {
+            ConflictKind::ExecBitConflict
+        } else {
+            ConflictKind::ContentsConflict
+        };
+        ConflictInfo {
+            path,
+            value,
+            kind,
+            absent_sides,
+        }
+    }
+
+    /// The number of sides of the (simplified) conflict.
+    pub fn num_sides(&self) -> usize {
+        self.value.num_sides()
+    }
+}
+
 /// Iterator over the differences between two trees.
 pub struct TreeDiffIterator<'matcher> {
     store: Arc<Store>,
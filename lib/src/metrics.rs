@@ -0,0 +1,175 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Counters for observing the cost of repo operations.
+//!
+//! The counters are only maintained if the `metrics` feature is enabled.
+//! Otherwise, they always read as zero and recording them compiles to nothing.
+
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+/// Whether the counters are maintained.
+pub const ENABLED: bool = cfg!(feature = "metrics");
+
+/// Counter which can be incremented through a shared reference.
+#[derive(Debug, Default)]
+struct Counter(AtomicU64);
+
+impl Counter {
+    #[inline]
+    fn increment(&self) {
+        if ENABLED {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Counts of backend accesses made by a [`Store`](crate::store::Store).
+///
+/// Reads served from the store's caches are not counted.
+#[derive(Debug, Default)]
+pub struct StoreMetrics {
+    commit_reads: Counter,
+    commit_writes: Counter,
+    tree_reads: Counter,
+    tree_writes: Counter,
+}
+
+impl StoreMetrics {
+    #[inline]
+    pub(crate) fn record_commit_read(&self) {
+        self.commit_reads.increment();
+    }
+
+    #[inline]
+    pub(crate) fn record_commit_write(&self) {
+        self.commit_writes.increment();
+    }
+
+    #[inline]
+    pub(crate) fn record_tree_read(&self) {
+        self.tree_reads.increment();
+    }
+
+    #[inline]
+    pub(crate) fn record_tree_write(&self) {
+        self.tree_writes.increment();
+    }
+
+    /// Returns the current counts.
+    pub fn snapshot(&self) -> StoreMetricsSnapshot {
+        StoreMetricsSnapshot {
+            commit_reads: self.commit_reads.get(),
+            commit_writes: self.commit_writes.get(),
+            tree_reads: self.tree_reads.get(),
+            tree_writes: self.tree_writes.get(),
+        }
+    }
+}
+
+/// Point-in-time copy of [`StoreMetrics`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StoreMetricsSnapshot {
+    /// Number of commits read from the backend.
+    pub commit_reads: u64,
+    /// Number of commits written to the store, including ones queued by a
+    /// write batch.
+    pub commit_writes: u64,
+    /// Number of trees read from the backend.
+    pub tree_reads: u64,
+    /// Number of trees written to the backend.
+    pub tree_writes: u64,
+}
+
+impl StoreMetricsSnapshot {
+    /// Returns the counts accumulated since the `earlier` snapshot.
+    pub fn since(&self, earlier: &Self) -> Self {
+        StoreMetricsSnapshot {
+            commit_reads: self.commit_reads.saturating_sub(earlier.commit_reads),
+            commit_writes: self.commit_writes.saturating_sub(earlier.commit_writes),
+            tree_reads: self.tree_reads.saturating_sub(earlier.tree_reads),
+            tree_writes: self.tree_writes.saturating_sub(earlier.tree_writes),
+        }
+    }
+}
+
+/// Counters collected over the lifetime of a
+/// [`MutableRepo`](crate::repo::MutableRepo).
+///
+/// A new set of counters is started for each transaction.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TransactionMetrics {
+    /// Number of commits written by commit builders attached to the repo.
+    pub commits_written: u64,
+    /// Number of commits visited while transforming descendants.
+    pub commits_visited: u64,
+    /// Number of tree merges performed while rebasing commits.
+    pub tree_merges: u64,
+    /// Time spent merging trees while rebasing commits.
+    pub tree_merge_time: Duration,
+    /// Backend accesses made through the store since the transaction started.
+    ///
+    /// The store may be shared with other repos, so this includes accesses
+    /// made on their behalf in the meantime.
+    pub store: StoreMetricsSnapshot,
+}
+
+impl TransactionMetrics {
+    #[inline]
+    pub(crate) fn record_commit_written(&mut self) {
+        if ENABLED {
+            self.commits_written += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_commit_visited(&mut self) {
+        if ENABLED {
+            self.commits_visited += 1;
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_tree_merge(&mut self, elapsed: Duration) {
+        if ENABLED {
+            self.tree_merges += 1;
+            self.tree_merge_time += elapsed;
+        }
+    }
+}
+
+/// Runs `f` and returns its result along with the elapsed time.
+///
+/// The elapsed time is zero if metrics are disabled.
+#[inline]
+pub(crate) fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    #[cfg(feature = "metrics")]
+    {
+        let start = Instant::now();
+        let value = f();
+        (value, start.elapsed())
+    }
+    #[cfg(not(feature = "metrics"))]
+    {
+        (f(), Duration::ZERO)
+    }
+}
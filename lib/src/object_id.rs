@@ -14,6 +14,8 @@
 
 #![allow(missing_docs)]
 
+use crate::hex_util;
+
 pub trait ObjectId {
     fn object_type(&self) -> String;
     fn as_bytes(&self) -> &[u8];
@@ -137,6 +139,17 @@ impl HexPrefix {
         })
     }
 
+    /// Returns a new `HexPrefix` from an id typed or pasted by the user, or
+    /// `None` if it cannot be decoded from hex to bytes.
+    ///
+    /// Surrounding whitespace and a trailing punctuation character are
+    /// ignored, and uppercase hex digits are accepted. Whitespace within the
+    /// prefix is still rejected.
+    pub fn new_lenient(prefix: &str) -> Option<HexPrefix> {
+        let prefix = hex_util::trim_user_id_input(prefix)?;
+        HexPrefix::new(&prefix.to_ascii_lowercase())
+    }
+
     pub fn from_bytes(bytes: &[u8]) -> Self {
         HexPrefix {
             min_prefix_bytes: bytes.to_owned(),
@@ -259,6 +272,23 @@ mod tests {
         assert_eq!(bad_prefix, None);
     }
 
+    #[test]
+    fn test_hex_prefix_new_lenient() {
+        assert_eq!(HexPrefix::new_lenient("12ab"), HexPrefix::new("12ab"));
+        assert_eq!(HexPrefix::new_lenient("12AB"), HexPrefix::new("12ab"));
+        assert_eq!(HexPrefix::new_lenient(" 12aB\n"), HexPrefix::new("12ab"));
+        assert_eq!(HexPrefix::new_lenient("123."), HexPrefix::new("123"));
+        assert_eq!(HexPrefix::new_lenient("123,"), HexPrefix::new("123"));
+
+        // Only a single trailing punctuation character is stripped
+        assert_eq!(HexPrefix::new_lenient("123.."), None);
+        assert_eq!(HexPrefix::new_lenient("12 34"), None);
+        assert_eq!(HexPrefix::new_lenient("0x123"), None);
+        // Unlike HexPrefix::new(""), this doesn't match everything
+        assert_eq!(HexPrefix::new_lenient(" "), None);
+        assert_eq!(HexPrefix::new_lenient("."), None);
+    }
+
     #[test]
     fn test_hex_prefix_matches() {
         let id = CommitId::from_hex("1234");
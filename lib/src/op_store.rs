@@ -16,6 +16,7 @@
 
 use std::any::Any;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt::Debug;
@@ -26,10 +27,12 @@ use itertools::Itertools as _;
 use once_cell::sync::Lazy;
 use thiserror::Error;
 
+use crate::backend::ChangeId;
 use crate::backend::CommitId;
 use crate::backend::MillisSinceEpoch;
 use crate::backend::Timestamp;
 use crate::content_hash::ContentHash;
+use crate::content_hash::DigestUpdate;
 use crate::merge::Merge;
 use crate::object_id::id_type;
 use crate::object_id::HexPrefix;
@@ -244,7 +247,7 @@ pub struct BookmarkTarget<'a> {
 
 /// Represents the way the repo looks at a given time, just like how a Tree
 /// object represents how the file system looks at a given time.
-#[derive(ContentHash, PartialEq, Eq, Clone, Debug)]
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub struct View {
     /// All head commits
     pub head_ids: HashSet<CommitId>,
@@ -260,6 +263,53 @@ pub struct View {
     // (.jj/working_copy/) has the source of truth about which commit *is* checked out (to be
     // precise: the commit to which we most recently completed an update to).
     pub wc_commit_ids: BTreeMap<WorkspaceNameBuf, CommitId>,
+    /// Additional information about workspaces. Workspaces created before
+    /// this was recorded have no entry.
+    pub workspace_metadata: BTreeMap<WorkspaceNameBuf, WorkspaceMetadata>,
+    /// Commits that should stay visible even if they're not reachable from
+    /// `head_ids`. They are only relevant for visibility; descendants of a
+    /// pinned commit aren't kept in sync with it.
+    pub pinned_commits: BTreeSet<CommitId>,
+    /// Notes attached to changes. They're keyed by change id so that they
+    /// survive rewrites of the commits.
+    pub notes: BTreeMap<ChangeId, String>,
+}
+
+impl ContentHash for View {
+    fn hash(&self, state: &mut impl DigestUpdate) {
+        let View {
+            head_ids,
+            local_bookmarks,
+            tags,
+            remote_views,
+            git_refs,
+            git_head,
+            wc_commit_ids,
+            workspace_metadata,
+            pinned_commits,
+            notes,
+        } = self;
+        head_ids.hash(state);
+        local_bookmarks.hash(state);
+        tags.hash(state);
+        remote_views.hash(state);
+        git_refs.hash(state);
+        git_head.hash(state);
+        wc_commit_ids.hash(state);
+        // Empty workspace metadata isn't hashed so that ids of views without
+        // metadata don't depend on whether this field exists.
+        if !workspace_metadata.is_empty() {
+            workspace_metadata.hash(state);
+        }
+        // Same for pinned commits
+        if !pinned_commits.is_empty() {
+            pinned_commits.hash(state);
+        }
+        // Same for notes
+        if !notes.is_empty() {
+            notes.hash(state);
+        }
+    }
 }
 
 impl View {
@@ -276,6 +326,9 @@ impl View {
             git_refs: BTreeMap::new(),
             git_head: RefTarget::absent(),
             wc_commit_ids: BTreeMap::new(),
+            workspace_metadata: BTreeMap::new(),
+            pinned_commits: BTreeSet::new(),
+            notes: BTreeMap::new(),
         }
     }
 
@@ -289,10 +342,23 @@ impl View {
             git_refs: BTreeMap::new(),
             git_head: RefTarget::absent(),
             wc_commit_ids: BTreeMap::new(),
+            workspace_metadata: BTreeMap::new(),
+            pinned_commits: BTreeSet::new(),
+            notes: BTreeMap::new(),
         }
     }
 }
 
+/// Information about a workspace, other than the commit checked out in it.
+#[derive(ContentHash, Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct WorkspaceMetadata {
+    /// User-provided description of the workspace.
+    pub description: Option<String>,
+    /// The operation the workspace was created on top of, if known. (The id
+    /// of the creating operation itself depends on this metadata.)
+    pub created_op_id: Option<OperationId>,
+}
+
 /// Represents the state of the remote repo.
 #[derive(ContentHash, Clone, Debug, Default, Eq, PartialEq)]
 pub struct RemoteView {
@@ -385,6 +451,8 @@ pub struct Operation {
     // BTreeMap for ease of deterministic serialization. If the deserialization
     // cost matters, maybe this can be changed to sorted Vec.
     pub commit_predecessors: Option<BTreeMap<CommitId, Vec<CommitId>>>,
+    /// Descendant commits rebased by this operation.
+    pub commit_rewrites: CommitRewrites,
 }
 
 impl Operation {
@@ -411,7 +479,57 @@ impl Operation {
             // may be other commits created within the abandoned operations.
             // They don't have any predecessors records as well.
             commit_predecessors: Some(BTreeMap::new()),
+            commit_rewrites: CommitRewrites::default(),
+        }
+    }
+}
+
+/// Maximum number of pairs recorded in [`CommitRewrites`].
+pub const MAX_COMMIT_REWRITES: usize = 1000;
+
+/// Mapping from old to new commits rebased by an operation.
+///
+/// Unlike `commit_predecessors`, this only records descendants rewritten by
+/// `MutableRepo::transform_commits()` and its callers such as
+/// `MutableRepo::rebase_descendants()`. At most [`MAX_COMMIT_REWRITES`] pairs
+/// are recorded.
+#[derive(PartialEq, Eq, Clone, Debug, Default)]
+pub struct CommitRewrites {
+    /// `(old_commit_id, new_commit_id)` pairs in the order of rewriting.
+    pub pairs: Vec<(CommitId, CommitId)>,
+    /// Whether more pairs than [`MAX_COMMIT_REWRITES`] were rewritten.
+    pub truncated: bool,
+}
+
+impl CommitRewrites {
+    /// Returns true if no commits were rewritten.
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty() && !self.truncated
+    }
+
+    /// Records the rewrite unless the maximum number of pairs is reached.
+    pub fn push(&mut self, old_commit_id: CommitId, new_commit_id: CommitId) {
+        if self.pairs.len() < MAX_COMMIT_REWRITES {
+            self.pairs.push((old_commit_id, new_commit_id));
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+impl ContentHash for CommitRewrites {
+    fn hash(&self, state: &mut impl DigestUpdate) {
+        // Empty rewrites aren't hashed so that ids of operations without
+        // rebased commits don't depend on whether this field exists.
+        if self.is_empty() {
+            return;
+        }
+        state.update(&(self.pairs.len() as u64).to_le_bytes());
+        for (old_commit_id, new_commit_id) in &self.pairs {
+            old_commit_id.hash(state);
+            new_commit_id.hash(state);
         }
+        self.truncated.hash(state);
     }
 }
 
@@ -15,14 +15,23 @@
 //! Utility for operation id resolution and traversal.
 
 use std::cmp::Ordering;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::fmt;
+use std::fmt::Debug;
+use std::num::NonZeroUsize;
 use std::slice;
 use std::sync::Arc;
+use std::sync::Mutex;
 
+use clru::CLruCache;
 use itertools::Itertools as _;
 use thiserror::Error;
 
+use crate::backend::BackendResult;
+use crate::backend::CommitId;
+use crate::commit::Commit;
 use crate::dag_walk;
 use crate::object_id::HexPrefix;
 use crate::object_id::PrefixResolution;
@@ -30,14 +39,28 @@ use crate::op_heads_store;
 use crate::op_heads_store::OpHeadResolutionError;
 use crate::op_heads_store::OpHeadsStore;
 use crate::op_heads_store::OpHeadsStoreError;
+use crate::op_store;
 use crate::op_store::OpStore;
 use crate::op_store::OpStoreError;
 use crate::op_store::OpStoreResult;
 use crate::op_store::OperationId;
+use crate::op_store::ViewId;
 use crate::operation::Operation;
+use crate::ref_name::GitRefNameBuf;
+use crate::ref_name::RefNameBuf;
+use crate::ref_name::RemoteRefSymbolBuf;
+use crate::refs::diff_named_ref_targets;
+use crate::refs::diff_named_remote_refs;
+use crate::repo::MutableRepo;
 use crate::repo::ReadonlyRepo;
-use crate::repo::Repo as _;
+use crate::repo::Repo;
 use crate::repo::RepoLoader;
+use crate::repo::RepoLoaderError;
+use crate::revset::ResolvedRevsetExpression;
+use crate::revset::RevsetEvaluationError;
+use crate::str_util::StringPattern;
+use crate::transaction::Transaction;
+use crate::view::View;
 
 /// Error that may occur during evaluation of operation set expression.
 #[derive(Debug, Error)]
@@ -141,6 +164,7 @@ fn resolve_single_op(
     get_head_ops: impl FnOnce() -> Result<Vec<Operation>, OpsetEvaluationError>,
     op_str: &str,
 ) -> Result<Operation, OpsetEvaluationError> {
+    let op_str = op_str.trim();
     let op_symbol = op_str.trim_end_matches(['-', '+']);
     let op_postfix = &op_str[op_symbol.len()..];
     let head_ops = op_postfix.contains('+').then(get_head_ops).transpose()?;
@@ -183,7 +207,7 @@ fn resolve_single_op_from_store(
     if op_str.is_empty() {
         return Err(OpsetResolutionError::InvalidIdPrefix(op_str.to_owned()).into());
     }
-    let prefix = HexPrefix::new(op_str)
+    let prefix = HexPrefix::new_lenient(op_str)
         .ok_or_else(|| OpsetResolutionError::InvalidIdPrefix(op_str.to_owned()))?;
     match op_store.resolve_operation_id_prefix(&prefix)? {
         PrefixResolution::NoMatch => {
@@ -272,6 +296,142 @@ pub fn walk_ancestors(
     .map_ok(|OperationByEndTime(op)| op)
 }
 
+const OPERATION_CACHE_CAPACITY: usize = 1000;
+
+/// Memoizing view of the operation graph for ancestry queries.
+///
+/// Operations are read from the op store on demand. Deserialized operations
+/// are kept in an LRU cache so that repeated queries over a large operation
+/// log don't have to read the same operations again, while the memory usage
+/// stays bounded.
+pub struct OpGraph {
+    op_store: Arc<dyn OpStore>,
+    cache: Mutex<CLruCache<OperationId, Arc<op_store::Operation>>>,
+}
+
+impl Debug for OpGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpGraph")
+            .field("op_store", &self.op_store)
+            .finish_non_exhaustive()
+    }
+}
+
+impl OpGraph {
+    /// Creates graph with the default cache capacity.
+    pub fn new(op_store: Arc<dyn OpStore>) -> Self {
+        Self::with_capacity(op_store, OPERATION_CACHE_CAPACITY.try_into().unwrap())
+    }
+
+    /// Creates graph which caches up to `capacity` operations.
+    pub fn with_capacity(op_store: Arc<dyn OpStore>, capacity: NonZeroUsize) -> Self {
+        OpGraph {
+            op_store,
+            cache: Mutex::new(CLruCache::new(capacity)),
+        }
+    }
+
+    /// Returns the underlying op store.
+    pub fn op_store(&self) -> &Arc<dyn OpStore> {
+        &self.op_store
+    }
+
+    /// Returns the number of operations currently cached.
+    pub fn cached_len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    fn read_data(&self, id: &OperationId) -> OpStoreResult<Arc<op_store::Operation>> {
+        if let Some(data) = self.cache.lock().unwrap().get(id).cloned() {
+            return Ok(data);
+        }
+        let data = Arc::new(self.op_store.read_operation(id)?);
+        self.cache.lock().unwrap().put(id.clone(), data.clone());
+        Ok(data)
+    }
+
+    /// Loads the specified operation, possibly from the cache.
+    pub fn operation(&self, id: &OperationId) -> OpStoreResult<Operation> {
+        let data = self.read_data(id)?;
+        Ok(Operation::new(self.op_store.clone(), id.clone(), data))
+    }
+
+    fn parent_ids(&self, id: &OperationId) -> OpStoreResult<Vec<OperationId>> {
+        Ok(self.read_data(id)?.parents.clone())
+    }
+
+    /// Walks the specified operation and its ancestors in reverse topological
+    /// order.
+    pub fn ancestors(
+        &self,
+        id: &OperationId,
+    ) -> impl Iterator<Item = OpStoreResult<Operation>> + use<'_> {
+        let head = self.operation(id).map(OperationByEndTime);
+        dag_walk::topo_order_reverse_lazy_ok(
+            [head],
+            |OperationByEndTime(op)| op.id().clone(),
+            |OperationByEndTime(op)| {
+                op.parent_ids()
+                    .iter()
+                    .map(|id| self.operation(id).map(OperationByEndTime))
+                    .collect_vec()
+            },
+        )
+        .map_ok(|OperationByEndTime(op)| op)
+    }
+
+    /// Returns true if `ancestor_id` is an ancestor of (or the same as)
+    /// `descendant_id`.
+    pub fn is_ancestor(
+        &self,
+        ancestor_id: &OperationId,
+        descendant_id: &OperationId,
+    ) -> OpStoreResult<bool> {
+        let ids = dag_walk::dfs_ok(
+            [Ok(descendant_id.clone())],
+            |id: &OperationId| id.clone(),
+            |id| match self.parent_ids(id) {
+                Ok(ids) => ids.into_iter().map(Ok).collect_vec(),
+                Err(err) => vec![Err(err)],
+            },
+        );
+        for id in ids {
+            if id? == *ancestor_id {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns the closest common ancestor of the two operations.
+    pub fn common_ancestor(
+        &self,
+        id1: &OperationId,
+        id2: &OperationId,
+    ) -> OpStoreResult<Option<Operation>> {
+        self.common_ancestor_of(slice::from_ref(id1), slice::from_ref(id2))
+    }
+
+    /// Returns the closest operation which is an ancestor of both any of
+    /// `ids1` and any of `ids2`.
+    pub fn common_ancestor_of(
+        &self,
+        ids1: &[OperationId],
+        ids2: &[OperationId],
+    ) -> OpStoreResult<Option<Operation>> {
+        let common_id = dag_walk::closest_common_node_ok(
+            ids1.iter().cloned().map(Ok),
+            ids2.iter().cloned().map(Ok),
+            |id: &OperationId| id.clone(),
+            |id| match self.parent_ids(id) {
+                Ok(ids) => ids.into_iter().map(Ok).collect_vec(),
+                Err(err) => vec![Err(err)],
+            },
+        )?;
+        common_id.map(|id| self.operation(&id)).transpose()
+    }
+}
+
 /// Stats about `reparent_range()`.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ReparentStats {
@@ -344,3 +504,369 @@ pub fn reparent_range(
         unreachable_count: unreachable_ids.len(),
     })
 }
+
+/// Commits referenced by the views of a range of operations. See
+/// [`reachable_commits_across_ops()`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ReachabilitySummary {
+    /// Commits referenced by any of the views. The ancestors and predecessors
+    /// of these commits are also reachable.
+    pub commit_ids: HashSet<CommitId>,
+    /// Number of operations walked.
+    pub op_count: usize,
+    /// Number of distinct views read.
+    pub view_count: usize,
+}
+
+impl ReachabilitySummary {
+    /// Returns true if the `commit_id` is referenced by any of the views.
+    pub fn contains(&self, commit_id: &CommitId) -> bool {
+        self.commit_ids.contains(commit_id)
+    }
+
+    /// Returns the referenced commits that aren't visible in the `repo`, in
+    /// reverse topological order. Commits unknown to the `repo` are excluded.
+    pub fn hidden_commit_ids(
+        &self,
+        repo: &dyn Repo,
+    ) -> Result<Vec<CommitId>, RevsetEvaluationError> {
+        let index = repo.index();
+        let known_ids = self
+            .commit_ids
+            .iter()
+            .filter(|id| index.has_id(id))
+            .cloned()
+            .collect();
+        ResolvedRevsetExpression::commits(known_ids)
+            .minus(&ResolvedRevsetExpression::visible_heads().ancestors())
+            .evaluate(repo)?
+            .iter()
+            .try_collect()
+    }
+}
+
+/// Collects commits referenced by the views of `head_ops` and their
+/// ancestors.
+///
+/// Commits that are hidden in the current view can still be recovered by
+/// restoring an old operation, so their objects have to be kept alive by the
+/// backend GC. The views are read one by one, and views shared by multiple
+/// operations are read once.
+pub fn reachable_commits_across_ops(
+    repo_loader: &RepoLoader,
+    head_ops: &[Operation],
+) -> OpStoreResult<ReachabilitySummary> {
+    let op_store = repo_loader.op_store();
+    let mut summary = ReachabilitySummary::default();
+    let mut seen_view_ids: HashSet<ViewId> = HashSet::new();
+    for op in walk_ancestors(head_ops) {
+        let op = op?;
+        summary.op_count += 1;
+        if !seen_view_ids.insert(op.view_id().clone()) {
+            continue;
+        }
+        let view = View::new(op_store.read_view(op.view_id())?);
+        summary
+            .commit_ids
+            .extend(view.all_referenced_commit_ids().cloned());
+        summary.view_count += 1;
+    }
+    Ok(summary)
+}
+
+/// Error that may occur while undoing an operation.
+#[derive(Debug, Error)]
+pub enum UndoOperationError {
+    /// The root operation has no parent view to restore.
+    #[error("Cannot undo root operation")]
+    RootOperation,
+    /// Merge operations have more than one parent view.
+    #[error("Cannot undo a merge operation")]
+    MergeOperation,
+    /// Failed to load the repo at the operation or its parent.
+    #[error(transparent)]
+    RepoLoader(#[from] RepoLoaderError),
+}
+
+/// How to handle refs that were changed by the undone operation and then
+/// changed again by a later operation.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum UndoChangedRefBehavior {
+    /// Merge the inverse change in, which may result in conflicted refs.
+    #[default]
+    Conflict,
+    /// Leave such refs pointing to their current targets.
+    Skip,
+}
+
+/// Options for `undo_operation()`.
+#[derive(Clone, Debug, Default)]
+pub struct UndoOptions {
+    /// How to handle refs that were changed again since the undone operation.
+    pub changed_ref_behavior: UndoChangedRefBehavior,
+}
+
+/// Name of a ref touched by `undo_operation()`.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum UndoRefName {
+    /// Local bookmark.
+    LocalBookmark(RefNameBuf),
+    /// Remote bookmark.
+    RemoteBookmark(RemoteRefSymbolBuf),
+    /// Tag.
+    Tag(RefNameBuf),
+    /// Git ref.
+    GitRef(GitRefNameBuf),
+}
+
+/// Stats about `undo_operation()`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct UndoStats {
+    /// Refs that were changed by the undone operation and have been cleanly
+    /// restored to their previous targets.
+    pub reverted_refs: Vec<UndoRefName>,
+    /// Refs that were changed by the undone operation and changed again by
+    /// later operations. Depending on `UndoChangedRefBehavior`, these are
+    /// either merged (and possibly conflicted) or left untouched.
+    pub changed_refs: Vec<UndoRefName>,
+}
+
+/// Applies the inverse of the `target_op` onto the transaction's current view.
+///
+/// The changes made by `target_op` relative to its parent are merged in
+/// reverse, so changes made by later operations are preserved. Refs that were
+/// changed by `target_op` and then changed again by later operations can't be
+/// cleanly inverted. They are handled according to
+/// `options.changed_ref_behavior` and reported in `UndoStats::changed_refs`.
+pub fn undo_operation(
+    tx: &mut Transaction,
+    target_op: &Operation,
+    options: &UndoOptions,
+) -> Result<UndoStats, UndoOperationError> {
+    let parent_op = match target_op.parents().at_most_one() {
+        Ok(Some(parent_op)) => parent_op.map_err(RepoLoaderError::from)?,
+        Ok(None) => return Err(UndoOperationError::RootOperation),
+        Err(_) => return Err(UndoOperationError::MergeOperation),
+    };
+    let repo_loader = tx.base_repo().loader();
+    let target_repo = repo_loader.load_at(target_op)?;
+    let parent_repo = repo_loader.load_at(&parent_op)?;
+
+    let (reverted_refs, changed_refs) =
+        classify_undone_refs(tx.repo().view(), target_repo.view(), parent_repo.view());
+    // Remember the current targets of the refs changed since, so they can be
+    // put back if the caller doesn't want them to be merged.
+    let current_view = tx.repo().view().clone();
+    tx.repo_mut()
+        .merge(&target_repo, &parent_repo)
+        .map_err(RepoLoaderError::from)?;
+    if options.changed_ref_behavior == UndoChangedRefBehavior::Skip {
+        let mut_repo = tx.repo_mut();
+        for name in &changed_refs {
+            match name {
+                UndoRefName::LocalBookmark(name) => {
+                    let target = current_view.get_local_bookmark(name).clone();
+                    mut_repo.set_local_bookmark_target_unchecked(name, target);
+                }
+                UndoRefName::RemoteBookmark(symbol) => {
+                    let remote_ref = current_view.get_remote_bookmark(symbol.as_ref()).clone();
+                    mut_repo.set_remote_bookmark(symbol.as_ref(), remote_ref);
+                }
+                UndoRefName::Tag(name) => {
+                    let target = current_view.get_tag(name).clone();
+                    mut_repo.set_tag_target_unchecked(name, target);
+                }
+                UndoRefName::GitRef(name) => {
+                    let target = current_view.get_git_ref(name).clone();
+                    mut_repo.set_git_ref_target(name, target);
+                }
+            }
+        }
+    }
+    Ok(UndoStats {
+        reverted_refs,
+        changed_refs,
+    })
+}
+
+/// Splits refs changed between `parent_view` and `target_view` into ones that
+/// are unchanged in `current_view` since, and ones that were changed again.
+fn classify_undone_refs(
+    current_view: &View,
+    target_view: &View,
+    parent_view: &View,
+) -> (Vec<UndoRefName>, Vec<UndoRefName>) {
+    let mut reverted_refs = vec![];
+    let mut changed_refs = vec![];
+    let mut classify = |name: UndoRefName, unchanged_since: bool| {
+        if unchanged_since {
+            reverted_refs.push(name);
+        } else {
+            changed_refs.push(name);
+        }
+    };
+    let undone =
+        diff_named_ref_targets(parent_view.local_bookmarks(), target_view.local_bookmarks());
+    for (name, (_, target)) in undone {
+        let unchanged_since = current_view.get_local_bookmark(name) == target;
+        classify(UndoRefName::LocalBookmark(name.to_owned()), unchanged_since);
+    }
+    let undone = diff_named_remote_refs(
+        parent_view.all_remote_bookmarks(),
+        target_view.all_remote_bookmarks(),
+    );
+    for (symbol, (_, remote_ref)) in undone {
+        let unchanged_since = current_view.get_remote_bookmark(symbol) == remote_ref;
+        classify(
+            UndoRefName::RemoteBookmark(symbol.to_owned()),
+            unchanged_since,
+        );
+    }
+    let undone = diff_named_ref_targets(parent_view.tags(), target_view.tags());
+    for (name, (_, target)) in undone {
+        let unchanged_since = current_view.get_tag(name) == target;
+        classify(UndoRefName::Tag(name.to_owned()), unchanged_since);
+    }
+    let undone = diff_named_ref_targets(parent_view.git_refs(), target_view.git_refs());
+    for (name, (_, target)) in undone {
+        let unchanged_since = current_view.get_git_ref(name) == target;
+        classify(UndoRefName::GitRef(name.to_owned()), unchanged_since);
+    }
+    (reverted_refs, changed_refs)
+}
+
+/// Portion of the view to be restored by `restore_view_partial()`.
+#[derive(Clone, Debug)]
+pub enum RestoreScope {
+    /// All portions below. Git-tracking refs are left untouched.
+    All,
+    /// Visible heads and pinned commits. Commits pointed to by the current
+    /// local bookmarks are kept visible.
+    Heads,
+    /// Local bookmarks matching the pattern.
+    LocalBookmarks(StringPattern),
+    /// Remote bookmarks of remotes matching the pattern.
+    RemoteBookmarks(StringPattern),
+    /// Tags.
+    Tags,
+    /// Working-copy commits and metadata of workspaces.
+    WorkspacePointers,
+}
+
+/// Restores the `scope` portion of the view at `target_op` onto the
+/// transaction's current view.
+///
+/// Unlike replacing the whole view, the other portions of the current view are
+/// preserved. For example, remote bookmarks fetched since `target_op` are kept
+/// if only local bookmarks are restored.
+pub fn restore_view_partial(
+    tx: &mut Transaction,
+    target_op: &Operation,
+    scope: &RestoreScope,
+) -> Result<(), RepoLoaderError> {
+    let target_repo = tx.base_repo().loader().load_at(target_op)?;
+    let mut_repo = tx.repo_mut();
+    // Commits of the target view might not be indexed if the operation isn't
+    // an ancestor.
+    mut_repo.merge_index(&target_repo);
+    restore_view_scope(mut_repo, target_repo.view(), scope)?;
+    Ok(())
+}
+
+fn restore_view_scope(
+    mut_repo: &mut MutableRepo,
+    target_view: &View,
+    scope: &RestoreScope,
+) -> BackendResult<()> {
+    match scope {
+        RestoreScope::All => {
+            // Heads are restored last so the commits pointed to by the
+            // restored bookmarks determine which commits stay visible.
+            let scopes = [
+                RestoreScope::LocalBookmarks(StringPattern::everything()),
+                RestoreScope::RemoteBookmarks(StringPattern::everything()),
+                RestoreScope::Tags,
+                RestoreScope::WorkspacePointers,
+                RestoreScope::Heads,
+            ];
+            for scope in &scopes {
+                restore_view_scope(mut_repo, target_view, scope)?;
+            }
+        }
+        RestoreScope::Heads => {
+            let current_heads = mut_repo.view().heads().clone();
+            for id in current_heads.difference(target_view.heads()) {
+                mut_repo.remove_head(id);
+            }
+            let store = mut_repo.store().clone();
+            let bookmarked_ids: HashSet<_> = mut_repo
+                .view()
+                .local_bookmarks()
+                .flat_map(|(_, target)| target.added_ids())
+                .cloned()
+                .collect();
+            let new_heads: Vec<Commit> = target_view
+                .heads()
+                .difference(&current_heads)
+                .chain(&bookmarked_ids)
+                .map(|id| store.get_commit(id))
+                .try_collect()?;
+            mut_repo.add_heads(&new_heads)?;
+            let current_pinned_ids = mut_repo.view().pinned_commits().clone();
+            for id in current_pinned_ids.difference(target_view.pinned_commits()) {
+                mut_repo.unpin_commit(id);
+            }
+            for id in target_view.pinned_commits().difference(&current_pinned_ids) {
+                mut_repo.pin_commit(id.clone());
+            }
+        }
+        RestoreScope::LocalBookmarks(pattern) => {
+            let current_view = mut_repo.view();
+            let names: BTreeSet<_> = current_view
+                .local_bookmarks_matching(pattern)
+                .chain(target_view.local_bookmarks_matching(pattern))
+                .map(|(name, _)| name.to_owned())
+                .collect();
+            for name in &names {
+                let target = target_view.get_local_bookmark(name).clone();
+                mut_repo.set_local_bookmark_target_unchecked(name, target);
+            }
+        }
+        RestoreScope::RemoteBookmarks(remote_pattern) => {
+            let current_view = mut_repo.view();
+            let everything = StringPattern::everything();
+            let symbols: BTreeSet<_> = current_view
+                .remote_bookmarks_matching(&everything, remote_pattern)
+                .chain(target_view.remote_bookmarks_matching(&everything, remote_pattern))
+                .map(|(symbol, _)| symbol.to_owned())
+                .collect();
+            for symbol in &symbols {
+                let remote_ref = target_view.get_remote_bookmark(symbol.as_ref()).clone();
+                mut_repo.set_remote_bookmark(symbol.as_ref(), remote_ref);
+            }
+        }
+        RestoreScope::Tags => {
+            let names: BTreeSet<_> = mut_repo
+                .view()
+                .tags()
+                .keys()
+                .chain(target_view.tags().keys())
+                .cloned()
+                .collect();
+            for name in &names {
+                let target = target_view.get_tag(name).clone();
+                mut_repo.set_tag_target_unchecked(name, target);
+            }
+        }
+        RestoreScope::WorkspacePointers => {
+            // MutableRepo::remove_wc_commit() would abandon the working-copy
+            // commit, so replace the pointers in the view data instead.
+            let mut view = mut_repo.view().store_view().clone();
+            let target_view = target_view.store_view();
+            view.wc_commit_ids = target_view.wc_commit_ids.clone();
+            view.workspace_metadata = target_view.workspace_metadata.clone();
+            mut_repo.set_view(view);
+        }
+    }
+    Ok(())
+}
@@ -24,6 +24,7 @@ use std::sync::Arc;
 
 use crate::backend::CommitId;
 use crate::op_store;
+use crate::op_store::CommitRewrites;
 use crate::op_store::OpStore;
 use crate::op_store::OpStoreResult;
 use crate::op_store::OperationId;
@@ -131,6 +132,11 @@ impl Operation {
         Some(map.get(commit_id)?)
     }
 
+    /// Returns the descendant commits rebased by this operation.
+    pub fn commit_rewrites(&self) -> &CommitRewrites {
+        &self.data.commit_rewrites
+    }
+
     pub fn store_operation(&self) -> &op_store::Operation {
         &self.data
     }
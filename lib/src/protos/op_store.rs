@@ -92,8 +92,8 @@ pub struct View {
     #[deprecated]
     #[prost(bytes = "vec", tag = "2")]
     pub wc_commit_id: ::prost::alloc::vec::Vec<u8>,
-    #[prost(map = "string, bytes", tag = "8")]
-    pub wc_commit_ids: ::std::collections::HashMap<
+    #[prost(btree_map = "string, bytes", tag = "8")]
+    pub wc_commit_ids: ::prost::alloc::collections::BTreeMap<
         ::prost::alloc::string::String,
         ::prost::alloc::vec::Vec<u8>,
     >,
@@ -112,6 +112,36 @@ pub struct View {
     pub git_head_legacy: ::prost::alloc::vec::Vec<u8>,
     #[prost(message, optional, tag = "9")]
     pub git_head: ::core::option::Option<RefTarget>,
+    /// Introduced in jj 0.30.
+    #[prost(btree_map = "string, message", tag = "11")]
+    pub workspace_metadata: ::prost::alloc::collections::BTreeMap<
+        ::prost::alloc::string::String,
+        WorkspaceMetadata,
+    >,
+    #[prost(bytes = "vec", repeated, tag = "12")]
+    pub pinned_commit_ids: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+    #[prost(message, repeated, tag = "13")]
+    pub notes: ::prost::alloc::vec::Vec<Note>,
+    /// Ids of the chunks in the "view_chunks" directory that store the rest of
+    /// the bookmarks, tags, and git refs. Chunks are encoded as `View` with only
+    /// those fields set.
+    #[prost(bytes = "vec", repeated, tag = "14")]
+    pub ref_chunk_ids: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct WorkspaceMetadata {
+    #[prost(string, optional, tag = "1")]
+    pub description: ::core::option::Option<::prost::alloc::string::String>,
+    /// Empty if unknown.
+    #[prost(bytes = "vec", tag = "2")]
+    pub created_op_id: ::prost::alloc::vec::Vec<u8>,
+}
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Note {
+    #[prost(bytes = "vec", tag = "1")]
+    pub change_id: ::prost::alloc::vec::Vec<u8>,
+    #[prost(string, tag = "2")]
+    pub text: ::prost::alloc::string::String,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Operation {
@@ -127,6 +157,12 @@ pub struct Operation {
     /// Whether or not `commit_predecessors` is recorded.
     #[prost(bool, tag = "5")]
     pub stores_commit_predecessors: bool,
+    /// Introduced in jj 0.30.
+    #[prost(message, repeated, tag = "6")]
+    pub commit_rewrites: ::prost::alloc::vec::Vec<CommitRewrite>,
+    /// Whether `commit_rewrites` was truncated.
+    #[prost(bool, tag = "7")]
+    pub commit_rewrites_truncated: bool,
 }
 /// TODO: Share with store.proto? Do we even need the timezone here?
 #[derive(Clone, Copy, PartialEq, ::prost::Message)]
@@ -163,6 +199,13 @@ pub struct CommitPredecessors {
     #[prost(bytes = "vec", repeated, tag = "2")]
     pub predecessor_ids: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CommitRewrite {
+    #[prost(bytes = "vec", tag = "1")]
+    pub old_commit_id: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub new_commit_id: ::prost::alloc::vec::Vec<u8>,
+}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum RemoteRefState {
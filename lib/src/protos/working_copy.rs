@@ -19,6 +19,10 @@ pub struct FileState {
     pub conflict_id: ::prost::alloc::vec::Vec<u8>,
     #[prost(message, optional, tag = "5")]
     pub materialized_conflict_data: ::core::option::Option<MaterializedConflictData>,
+    /// Set if the file is a placeholder for a conflict which hasn't been
+    /// materialized yet
+    #[prost(bool, tag = "6")]
+    pub needs_materialization: bool,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct FileStateEntry {
@@ -48,6 +52,10 @@ pub struct TreeState {
     pub sparse_patterns: ::core::option::Option<SparsePatterns>,
     #[prost(message, optional, tag = "4")]
     pub watchman_clock: ::core::option::Option<WatchmanClock>,
+    /// Set if a checkout from these trees to `tree_ids` was interrupted. Files
+    /// which differ between the two trees may not be up to date on disk.
+    #[prost(bytes = "vec", repeated, tag = "7")]
+    pub interrupted_checkout_tree_ids: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct WatchmanClock {
@@ -30,14 +30,26 @@
 //! # use jj_lib::ref_name::*;
 //! let _: &RemoteName = RefName::new("main").as_ref();
 //! ```
+//!
+//! Conversion from a string doesn't validate the name. New bookmark or tag
+//! names should be validated by [`RefNameBuf::try_from_str()`] or
+//! [`RefName::validate()`]:
+//! ```
+//! # use jj_lib::ref_name::*;
+//! assert!(RefNameBuf::try_from_str("main").is_ok());
+//! assert!(RefNameBuf::try_from_str("foo..bar").is_err());
+//! ```
 
 use std::borrow::Borrow;
 use std::fmt;
 use std::fmt::Display;
 use std::ops::Deref;
+use std::str;
 
+use itertools::Itertools as _;
 use ref_cast::ref_cast_custom;
 use ref_cast::RefCastCustom;
+use thiserror::Error;
 
 use crate::content_hash::ContentHash;
 use crate::revset;
@@ -302,11 +314,59 @@ impl_name_type!(RefNameBuf, RefName);
 impl_name_type!(RemoteNameBuf, RemoteName);
 impl_name_type!(WorkspaceNameBuf, WorkspaceName);
 
+impl RefNameBuf {
+    /// Constructs a validated name from a string.
+    pub fn try_from_str(name: &str) -> Result<Self, InvalidRefNameError> {
+        RefName::new(name).validate()?;
+        Ok(name.into())
+    }
+
+    /// Constructs a validated name from bytes, which must be valid UTF-8.
+    pub fn try_from_bytes(name: &[u8]) -> Result<Self, InvalidRefNameError> {
+        let name = str::from_utf8(name).map_err(|_| InvalidRefNameError {
+            name: String::from_utf8_lossy(name).into_owned(),
+            kind: InvalidRefNameErrorKind::InvalidUtf8,
+        })?;
+        Self::try_from_str(name)
+    }
+
+    /// Normalizes and validates the name.
+    ///
+    /// Surrounding whitespace and leading and trailing `/` are removed, and
+    /// consecutive `/` are collapsed. Other invalid names are rejected.
+    pub fn normalize(name: &str) -> Result<Self, InvalidRefNameError> {
+        let normalized = name
+            .trim()
+            .split('/')
+            .filter(|component| !component.is_empty())
+            .join("/");
+        Self::try_from_str(&normalized)
+    }
+}
+
 impl RefName {
     /// Constructs a remote symbol with this local name.
     pub fn to_remote_symbol<'a>(&'a self, remote: &'a RemoteName) -> RemoteRefSymbol<'a> {
         RemoteRefSymbol { name: self, remote }
     }
+
+    /// Checks if this name can be used as a new bookmark or tag name.
+    ///
+    /// The rules approximate `git check-ref-format`. In addition, names
+    /// starting with `-` are rejected since they would be parsed as command
+    /// options.
+    pub fn validate(&self) -> Result<(), InvalidRefNameError> {
+        validate_name(&self.0)
+    }
+}
+
+impl RemoteName {
+    /// Checks if this name can be used as a new remote name.
+    ///
+    /// The same rules as [`RefName::validate()`] apply.
+    pub fn validate(&self) -> Result<(), InvalidRefNameError> {
+        validate_name(&self.0)
+    }
 }
 
 impl WorkspaceName {
@@ -368,6 +428,12 @@ pub struct RemoteRefSymbol<'a> {
 }
 
 impl RemoteRefSymbol<'_> {
+    /// Checks if both the local and the remote names are valid.
+    pub fn validate(&self) -> Result<(), InvalidRefNameError> {
+        self.name.validate()?;
+        self.remote.validate()
+    }
+
     /// Converts to owned type.
     pub fn to_owned(self) -> RemoteRefSymbolBuf {
         RemoteRefSymbolBuf {
@@ -419,3 +485,87 @@ impl Display for RemoteRefSymbol<'_> {
         f.pad(&revset::format_remote_symbol(&name.0, &remote.0))
     }
 }
+
+/// Error occurred when validating a bookmark, tag, or remote name.
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+#[error("Invalid name {}", RefSymbol::new(.name))]
+pub struct InvalidRefNameError {
+    /// The invalid name. Invalid UTF-8 sequences are replaced.
+    pub name: String,
+    /// The rule the name violates.
+    #[source]
+    pub kind: InvalidRefNameErrorKind,
+}
+
+/// Rule violated by an invalid name.
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum InvalidRefNameErrorKind {
+    /// The name is empty.
+    #[error("Name is empty")]
+    Empty,
+    /// The name contains invalid UTF-8 sequences.
+    #[error("Name is not valid UTF-8")]
+    InvalidUtf8,
+    /// The name contains a whitespace, control, or special character.
+    #[error("Invalid character {0:?}")]
+    InvalidChar(char),
+    /// The name starts with `-`.
+    #[error("Name starts with '-'")]
+    LeadingDash,
+    /// The name ends with `.`.
+    #[error("Name ends with '.'")]
+    TrailingDot,
+    /// The name contains `..`.
+    #[error("Name contains '..'")]
+    ConsecutiveDots,
+    /// The name contains `@{`.
+    #[error("Name contains '@{{'")]
+    AtBrace,
+    /// The name is `@`.
+    #[error("Name is '@'")]
+    SingleAt,
+    /// The name starts or ends with `/`, or contains `//`.
+    #[error("Name contains empty path component")]
+    EmptyComponent,
+    /// A `/`-separated component starts with `.`.
+    #[error("Path component starts with '.'")]
+    LeadingDotInComponent,
+    /// A `/`-separated component ends with `.lock`.
+    #[error("Path component ends with '.lock'")]
+    LockSuffix,
+}
+
+fn validate_name(name: &str) -> Result<(), InvalidRefNameError> {
+    let kind = if name.is_empty() {
+        InvalidRefNameErrorKind::Empty
+    } else if let Some(c) = name.chars().find(|&c| {
+        c.is_control() || c.is_whitespace() || matches!(c, '~' | '^' | ':' | '?' | '*' | '[' | '\\')
+    }) {
+        InvalidRefNameErrorKind::InvalidChar(c)
+    } else if name.starts_with('-') {
+        InvalidRefNameErrorKind::LeadingDash
+    } else if name.ends_with('.') {
+        InvalidRefNameErrorKind::TrailingDot
+    } else if name.contains("..") {
+        InvalidRefNameErrorKind::ConsecutiveDots
+    } else if name.contains("@{") {
+        InvalidRefNameErrorKind::AtBrace
+    } else if name == "@" {
+        InvalidRefNameErrorKind::SingleAt
+    } else if name.split('/').any(|component| component.is_empty()) {
+        InvalidRefNameErrorKind::EmptyComponent
+    } else if name.split('/').any(|component| component.starts_with('.')) {
+        InvalidRefNameErrorKind::LeadingDotInComponent
+    } else if name
+        .split('/')
+        .any(|component| component.ends_with(".lock"))
+    {
+        InvalidRefNameErrorKind::LockSuffix
+    } else {
+        return Ok(());
+    };
+    Err(InvalidRefNameError {
+        name: name.to_owned(),
+        kind,
+    })
+}
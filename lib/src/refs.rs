@@ -15,6 +15,7 @@
 #![allow(missing_docs)]
 
 use itertools::EitherOrBoth;
+use itertools::Itertools as _;
 
 use crate::backend::CommitId;
 use crate::index::Index;
@@ -22,6 +23,7 @@ use crate::merge::trivial_merge;
 use crate::merge::Merge;
 use crate::op_store::RefTarget;
 use crate::op_store::RemoteRef;
+use crate::ref_name::RefNameBuf;
 
 /// Compares `refs1` and `refs2` targets, yields entry if they differ.
 ///
@@ -86,6 +88,23 @@ pub fn diff_named_commit_ids<'a, 'b, K: Ord>(
     .filter(|(_, (target1, target2))| target1 != target2)
 }
 
+/// Compares `values1` and `values2` of arbitrary type, yields entry if they
+/// differ.
+///
+/// `values1` and `values2` must be sorted by `K`.
+pub fn diff_named_values<'a, 'b, K: Ord, V: PartialEq + 'a + 'b>(
+    values1: impl IntoIterator<Item = (K, &'a V)>,
+    values2: impl IntoIterator<Item = (K, &'b V)>,
+) -> impl Iterator<Item = (K, (Option<&'a V>, Option<&'b V>))> {
+    iter_named_pairs(
+        values1.into_iter().map(|(k, v)| (k, Some(v))),
+        values2.into_iter().map(|(k, v)| (k, Some(v))),
+        || None,
+        || None,
+    )
+    .filter(|(_, (value1, value2))| value1 != value2)
+}
+
 fn iter_named_pairs<K: Ord, V1, V2>(
     refs1: impl IntoIterator<Item = (K, V1)>,
     refs2: impl IntoIterator<Item = (K, V2)>,
@@ -101,6 +120,48 @@ fn iter_named_pairs<K: Ord, V1, V2>(
     )
 }
 
+/// Kind of ref reported by [`RefMergeConflict`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum RefMergeConflictKind {
+    LocalBookmark,
+    Tag,
+}
+
+/// Bookmark or tag which became conflicted while merging concurrent
+/// operations.
+///
+/// Refs that were already conflicted on either side of the merge aren't
+/// reported.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RefMergeConflict {
+    pub kind: RefMergeConflictKind,
+    pub name: RefNameBuf,
+    /// Target at the common ancestor operation.
+    pub base: RefTarget,
+    /// Targets at the merged operations.
+    pub sides: Vec<RefTarget>,
+}
+
+impl RefMergeConflict {
+    /// Builds a summary such as "2 bookmark conflicts, 1 tag conflict".
+    /// Returns `None` if `conflicts` is empty.
+    pub fn summarize(conflicts: &[Self]) -> Option<String> {
+        let count = |kind| conflicts.iter().filter(|c| c.kind == kind).count();
+        let parts = [
+            (count(RefMergeConflictKind::LocalBookmark), "bookmark"),
+            (count(RefMergeConflictKind::Tag), "tag"),
+        ]
+        .into_iter()
+        .filter(|&(n, _)| n > 0)
+        .map(|(n, noun)| {
+            let suffix = if n == 1 { "" } else { "s" };
+            format!("{n} {noun} conflict{suffix}")
+        })
+        .collect_vec();
+        (!parts.is_empty()).then(|| parts.join(", "))
+    }
+}
+
 pub fn merge_ref_targets(
     index: &dyn Index,
     left: &RefTarget,
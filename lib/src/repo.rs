@@ -21,6 +21,8 @@ use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fs;
+use std::iter;
+use std::ops::Range;
 use std::path::Path;
 use std::slice;
 use std::sync::Arc;
@@ -39,24 +41,33 @@ use crate::backend::BackendResult;
 use crate::backend::ChangeId;
 use crate::backend::CommitId;
 use crate::backend::MergedTreeId;
+use crate::backend::StorageStats;
 use crate::commit::Commit;
 use crate::commit::CommitByCommitterTimestamp;
 use crate::commit_builder::CommitBuilder;
 use crate::commit_builder::DetachedCommitBuilder;
 use crate::dag_walk;
+use crate::default_index::AsCompositeIndex as _;
 use crate::default_index::DefaultIndexStore;
 use crate::default_index::DefaultMutableIndex;
+use crate::default_index::DefaultReadonlyIndex;
 use crate::default_submodule_store::DefaultSubmoduleStore;
 use crate::file_util::IoResultExt as _;
 use crate::file_util::PathError;
 use crate::index::ChangeIdIndex;
 use crate::index::Index;
+use crate::index::IndexError;
 use crate::index::IndexReadError;
 use crate::index::IndexStore;
 use crate::index::MutableIndex;
 use crate::index::ReadonlyIndex;
+use crate::leased_op_heads_store::LeaseOptions;
+use crate::leased_op_heads_store::LeasedOpHeadsStore;
+use crate::leased_op_heads_store::OpHeadsLocking;
 use crate::merge::trivial_merge;
 use crate::merge::MergeBuilder;
+use crate::metrics::StoreMetricsSnapshot;
+use crate::metrics::TransactionMetrics;
 use crate::object_id::HexPrefix;
 use crate::object_id::ObjectId as _;
 use crate::object_id::PrefixResolution;
@@ -65,6 +76,7 @@ use crate::op_heads_store::OpHeadResolutionError;
 use crate::op_heads_store::OpHeadsStore;
 use crate::op_heads_store::OpHeadsStoreError;
 use crate::op_store;
+use crate::op_store::CommitRewrites;
 use crate::op_store::OpStore;
 use crate::op_store::OpStoreError;
 use crate::op_store::OpStoreResult;
@@ -73,9 +85,14 @@ use crate::op_store::RefTarget;
 use crate::op_store::RemoteRef;
 use crate::op_store::RemoteRefState;
 use crate::op_store::RootOperationData;
+use crate::op_store::WorkspaceMetadata;
+use crate::op_walk;
+use crate::op_walk::OpGraph;
 use crate::operation::Operation;
 use crate::ref_name::GitRefName;
+use crate::ref_name::InvalidRefNameError;
 use crate::ref_name::RefName;
+use crate::ref_name::RefNameBuf;
 use crate::ref_name::RemoteName;
 use crate::ref_name::RemoteRefSymbol;
 use crate::ref_name::WorkspaceName;
@@ -83,18 +100,32 @@ use crate::ref_name::WorkspaceNameBuf;
 use crate::refs::diff_named_commit_ids;
 use crate::refs::diff_named_ref_targets;
 use crate::refs::diff_named_remote_refs;
+use crate::refs::diff_named_values;
 use crate::refs::merge_ref_targets;
 use crate::refs::merge_remote_refs;
+use crate::refs::RefMergeConflict;
+use crate::refs::RefMergeConflictKind;
+use crate::repo_pin::RepoPin;
+use crate::repo_pin::RepoPinError;
+use crate::repo_pin::RepoPinStore;
+use crate::retrying_backend::RetryPolicy;
+use crate::retrying_backend::RetryingBackend;
 use crate::revset;
 use crate::revset::RevsetEvaluationError;
 use crate::revset::RevsetExpression;
 use crate::revset::RevsetIteratorExt as _;
+use crate::rewrite::find_diverged_merges;
 use crate::rewrite::merge_commit_trees;
 use crate::rewrite::rebase_commit_with_options;
+use crate::rewrite::BookmarkMovePolicy;
 use crate::rewrite::CommitRewriter;
 use crate::rewrite::RebaseOptions;
 use crate::rewrite::RebasedCommit;
 use crate::rewrite::RewriteRefsOptions;
+use crate::rewrite_policy::RewriteDeniedError;
+use crate::rewrite_policy::RewriteOverride;
+use crate::rewrite_policy::RewritePermission;
+use crate::rewrite_policy::RewritePolicy;
 use crate::settings::UserSettings;
 use crate::signing::SignInitError;
 use crate::signing::Signer;
@@ -102,9 +133,11 @@ use crate::simple_backend::SimpleBackend;
 use crate::simple_op_heads_store::SimpleOpHeadsStore;
 use crate::simple_op_store::SimpleOpStore;
 use crate::store::Store;
+use crate::store::WriteBatch;
 use crate::submodule_store::SubmoduleStore;
 use crate::transaction::Transaction;
 use crate::transaction::TransactionCommitError;
+use crate::transaction::TransactionHooks;
 use crate::view::RenameWorkspaceError;
 use crate::view::View;
 
@@ -136,6 +169,30 @@ pub trait Repo {
     fn resolve_change_id_prefix(&self, prefix: &HexPrefix) -> PrefixResolution<Vec<CommitId>>;
 
     fn shortest_unique_change_id_prefix_len(&self, target_id_bytes: &ChangeId) -> usize;
+
+    /// Returns the commits in `ids` which aren't ancestors of other commits in
+    /// `ids`, in index order. Duplicated ids appear at most once.
+    ///
+    /// Fails with [`CommitsNotIndexedError`](crate::index::CommitsNotIndexedError)
+    /// listing the ids which aren't in the index.
+    fn heads_of_set(
+        &self,
+        ids: &mut dyn Iterator<Item = &CommitId>,
+    ) -> Result<Vec<CommitId>, IndexError> {
+        self.index().heads(ids)
+    }
+
+    /// Returns the commits in `ids` which aren't descendants of other commits
+    /// in `ids`, in index order. Duplicated ids appear at most once.
+    ///
+    /// Fails with [`CommitsNotIndexedError`](crate::index::CommitsNotIndexedError)
+    /// listing the ids which aren't in the index.
+    fn roots_of_set(
+        &self,
+        ids: &mut dyn Iterator<Item = &CommitId>,
+    ) -> Result<Vec<CommitId>, IndexError> {
+        self.index().roots_of_set(ids)
+    }
 }
 
 pub struct ReadonlyRepo {
@@ -173,7 +230,19 @@ impl ReadonlyRepo {
     }
 
     pub fn default_op_heads_store_initializer() -> &'static OpHeadsStoreInitializer<'static> {
-        &|_settings, store_path| Ok(Box::new(SimpleOpHeadsStore::init(store_path)?))
+        &|settings, store_path| {
+            let locking = settings
+                .core_op_heads_locking()
+                .map_err(|err| BackendInitError(err.into()))?;
+            match locking {
+                OpHeadsLocking::LockFile => Ok(Box::new(SimpleOpHeadsStore::init(store_path)?)),
+                OpHeadsLocking::Lease => {
+                    let options = LeaseOptions::from_settings(settings)
+                        .map_err(|err| BackendInitError(err.into()))?;
+                    Ok(Box::new(LeasedOpHeadsStore::init(store_path, options)?))
+                }
+            }
+        }
     }
 
     pub fn default_index_store_initializer() -> &'static IndexStoreInitializer<'static> {
@@ -203,6 +272,7 @@ impl ReadonlyRepo {
         let backend_path = store_path.join("type");
         fs::write(&backend_path, backend.name()).context(&backend_path)?;
         let store = Store::new(backend, signer);
+        store.load_shallow_boundaries(&store_path)?;
 
         let op_store_path = repo_path.join("op_store");
         fs::create_dir(&op_store_path).context(&op_store_path)?;
@@ -240,10 +310,13 @@ impl ReadonlyRepo {
         let loader = RepoLoader {
             settings: settings.clone(),
             store,
+            op_graph: Arc::new(OpGraph::new(op_store.clone())),
             op_store,
             op_heads_store,
             index_store,
             submodule_store,
+            pin_store: Some(RepoPinStore::for_repo(&repo_path)),
+            transaction_hooks: None,
         };
 
         let root_operation = loader.root_operation();
@@ -284,11 +357,27 @@ impl ReadonlyRepo {
         self.index.as_ref()
     }
 
+    /// Records the operation and index segments of this snapshot in the repo
+    /// so they aren't garbage-collected while the returned pin is alive.
+    ///
+    /// Callers that keep a `ReadonlyRepo` around for a long time should pin
+    /// it, and renew the pin before it expires.
+    pub fn pin(&self) -> Result<RepoPin, RepoPinError> {
+        let pin_store = self.loader.pin_store().ok_or(RepoPinError::Unsupported)?;
+        let index_segments = self
+            .readonly_index()
+            .as_any()
+            .downcast_ref::<DefaultReadonlyIndex>()
+            .map(|index| index.segment_names())
+            .unwrap_or_default();
+        pin_store.create(self.op_id(), index_segments)
+    }
+
     fn change_id_index(&self) -> &dyn ChangeIdIndex {
         self.change_id_index
             .get_or_init(|| {
                 self.readonly_index()
-                    .change_id_index(&mut self.view().heads().iter())
+                    .change_id_index(&mut self.view().visible_head_ids())
             })
             .as_ref()
     }
@@ -318,6 +407,180 @@ impl ReadonlyRepo {
     pub fn reload_at(&self, operation: &Operation) -> Result<Arc<ReadonlyRepo>, RepoLoaderError> {
         self.loader().load_at(operation)
     }
+
+    /// Gathers statistics about the repo. Statistics not enabled by `options`
+    /// are left as `None`.
+    pub fn gather_stats(&self, options: &RepoStatsOptions) -> Result<RepoStats, RepoStatsError> {
+        let view = options.view.then(|| ViewStats {
+            num_heads: self.view.heads().len(),
+            num_local_bookmarks: self.view.local_bookmarks().count(),
+            num_remote_bookmarks: self.view.all_remote_bookmarks().count(),
+            num_tags: self.view.tags().len(),
+            num_workspaces: self.view.wc_commit_ids().len(),
+        });
+        let index = if options.index {
+            self.gather_index_stats()?
+        } else {
+            None
+        };
+        let op_log_length = if options.op_log {
+            let head_ops: Vec<Operation> = self
+                .op_heads_store()
+                .get_op_heads()?
+                .iter()
+                .map(|id| self.loader.load_operation(id))
+                .try_collect()?;
+            Some(op_walk::walk_ancestors(&head_ops).process_results(|ops| ops.count())?)
+        } else {
+            None
+        };
+        let storage = if options.storage {
+            self.store().backend().storage_stats()?
+        } else {
+            None
+        };
+        Ok(RepoStats {
+            view,
+            index,
+            op_log_length,
+            storage,
+        })
+    }
+
+    fn gather_index_stats(&self) -> Result<Option<RepoIndexStats>, RepoStatsError> {
+        let Some(index) = self
+            .readonly_index()
+            .as_any()
+            .downcast_ref::<DefaultReadonlyIndex>()
+        else {
+            return Ok(None);
+        };
+        let index_store = self
+            .index_store()
+            .as_any()
+            .downcast_ref::<DefaultIndexStore>();
+        let composite = index.as_composite();
+        let segments = composite
+            .level_stats()
+            .into_iter()
+            .map(|level| {
+                let file_size = match (&level.name, index_store) {
+                    (Some(name), Some(store)) => Some(store.segment_file_size(name)?),
+                    _ => None,
+                };
+                Ok::<_, PathError>(IndexSegmentStats {
+                    name: level.name,
+                    num_commits: level.num_commits,
+                    file_size,
+                })
+            })
+            .try_collect()?;
+        Ok(Some(RepoIndexStats {
+            num_commits: composite.num_commits(),
+            segments,
+        }))
+    }
+}
+
+/// Selects the statistics gathered by [`ReadonlyRepo::gather_stats()`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RepoStatsOptions {
+    /// Count heads, bookmarks, tags, and workspaces in the view.
+    pub view: bool,
+    /// Count indexed commits and inspect the index segments.
+    pub index: bool,
+    /// Count the operations reachable from the operation heads. This walks the
+    /// whole operation log.
+    pub op_log: bool,
+    /// Ask the backend how much storage it uses. This may scan the whole
+    /// store.
+    pub storage: bool,
+}
+
+impl RepoStatsOptions {
+    /// Gathers all statistics, including expensive ones.
+    pub fn all() -> Self {
+        RepoStatsOptions {
+            view: true,
+            index: true,
+            op_log: true,
+            storage: true,
+        }
+    }
+}
+
+impl Default for RepoStatsOptions {
+    /// Gathers only the statistics that are cheap to compute.
+    fn default() -> Self {
+        RepoStatsOptions {
+            view: true,
+            index: true,
+            op_log: false,
+            storage: false,
+        }
+    }
+}
+
+/// Statistics about a repo, as returned by [`ReadonlyRepo::gather_stats()`].
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RepoStats {
+    /// Counts of the refs and heads in the view.
+    pub view: Option<ViewStats>,
+    /// Statistics of the commit index. Only available for the default index.
+    pub index: Option<RepoIndexStats>,
+    /// Number of operations reachable from the operation heads.
+    pub op_log_length: Option<usize>,
+    /// Storage used by the backend, if it can report it.
+    pub storage: Option<StorageStats>,
+}
+
+/// Counts of the refs and heads in a view.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ViewStats {
+    /// Number of visible heads.
+    pub num_heads: usize,
+    /// Number of local bookmarks, including conflicted ones.
+    pub num_local_bookmarks: usize,
+    /// Number of remote bookmarks across all remotes.
+    pub num_remote_bookmarks: usize,
+    /// Number of tags.
+    pub num_tags: usize,
+    /// Number of workspaces with a working-copy commit.
+    pub num_workspaces: usize,
+}
+
+/// Statistics of the default commit index.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RepoIndexStats {
+    /// Number of indexed commits. Since hidden commits are indexed too, this is
+    /// an upper bound of the number of reachable commits.
+    pub num_commits: u32,
+    /// Index segments, from the oldest to the newest.
+    pub segments: Vec<IndexSegmentStats>,
+}
+
+/// Statistics of a single index segment.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct IndexSegmentStats {
+    /// Name of the segment file, or `None` if the segment isn't saved.
+    pub name: Option<String>,
+    /// Number of commits added by this segment.
+    pub num_commits: u32,
+    /// Size of the segment file in bytes, if it's saved.
+    pub file_size: Option<u64>,
+}
+
+/// Error that may occur while gathering repo statistics.
+#[derive(Debug, Error)]
+pub enum RepoStatsError {
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error(transparent)]
+    OpHeadsStore(#[from] OpHeadsStoreError),
+    #[error(transparent)]
+    OpStore(#[from] OpStoreError),
+    #[error("Failed to read index segment")]
+    IndexSegment(#[from] PathError),
 }
 
 impl Repo for ReadonlyRepo {
@@ -398,6 +661,12 @@ pub struct StoreFactories {
     op_heads_store_factories: HashMap<String, OpHeadsStoreFactory>,
     index_store_factories: HashMap<String, IndexStoreFactory>,
     submodule_store_factories: HashMap<String, SubmoduleStoreFactory>,
+    backend_aliases: HashMap<String, String>,
+    op_store_aliases: HashMap<String, String>,
+    op_heads_store_aliases: HashMap<String, String>,
+    index_store_aliases: HashMap<String, String>,
+    submodule_store_aliases: HashMap<String, String>,
+    migrate_type_files: bool,
 }
 
 impl Default for StoreFactories {
@@ -441,6 +710,14 @@ impl Default for StoreFactories {
             SimpleOpHeadsStore::name(),
             Box::new(|_settings, store_path| Ok(Box::new(SimpleOpHeadsStore::load(store_path)))),
         );
+        factories.add_op_heads_store(
+            LeasedOpHeadsStore::name(),
+            Box::new(|settings, store_path| {
+                let options = LeaseOptions::from_settings(settings)
+                    .map_err(|err| BackendLoadError(err.into()))?;
+                Ok(Box::new(LeasedOpHeadsStore::load(store_path, options)))
+            }),
+        );
 
         // Index
         factories.add_index_store(
@@ -460,10 +737,15 @@ impl Default for StoreFactories {
 
 #[derive(Debug, Error)]
 pub enum StoreLoadError {
-    #[error("Unsupported {store} backend type '{store_type}'")]
+    #[error(
+        "Unsupported {store} backend type '{store_type}' (known types: {})",
+        .known_types.join(", ")
+    )]
     UnsupportedType {
         store: &'static str,
         store_type: String,
+        /// Sorted names of the types this build can load.
+        known_types: Vec<String>,
     },
     #[error("Failed to read {store} backend type")]
     ReadError {
@@ -484,6 +766,12 @@ impl StoreFactories {
             op_heads_store_factories: HashMap::new(),
             index_store_factories: HashMap::new(),
             submodule_store_factories: HashMap::new(),
+            backend_aliases: HashMap::new(),
+            op_store_aliases: HashMap::new(),
+            op_heads_store_aliases: HashMap::new(),
+            index_store_aliases: HashMap::new(),
+            submodule_store_aliases: HashMap::new(),
+            migrate_type_files: false,
         }
     }
 
@@ -494,6 +782,12 @@ impl StoreFactories {
             op_heads_store_factories,
             index_store_factories,
             submodule_store_factories,
+            backend_aliases,
+            op_store_aliases,
+            op_heads_store_aliases,
+            index_store_aliases,
+            submodule_store_aliases,
+            migrate_type_files,
         } = ext;
 
         merge_factories_map(&mut self.backend_factories, backend_factories);
@@ -504,45 +798,96 @@ impl StoreFactories {
             &mut self.submodule_store_factories,
             submodule_store_factories,
         );
+        merge_factories_map(&mut self.backend_aliases, backend_aliases);
+        merge_factories_map(&mut self.op_store_aliases, op_store_aliases);
+        merge_factories_map(&mut self.op_heads_store_aliases, op_heads_store_aliases);
+        merge_factories_map(&mut self.index_store_aliases, index_store_aliases);
+        merge_factories_map(&mut self.submodule_store_aliases, submodule_store_aliases);
+        self.migrate_type_files |= migrate_type_files;
+    }
+
+    /// Whether to rewrite the `type` file of a store that was loaded through
+    /// an alias so that it contains the current name. Disabled by default so
+    /// that loading a repo doesn't write to it.
+    pub fn set_migrate_type_files(&mut self, migrate: bool) {
+        self.migrate_type_files = migrate;
+    }
+
+    fn migrate_type_file(&self, type_path: &Path, store_type: &StoreType) {
+        if let StoreType::Alias(new_name) = store_type {
+            if self.migrate_type_files {
+                // The store has been loaded, so failing to update the file is
+                // not fatal. We'll try again next time.
+                if let Err(err) = fs::write(type_path, new_name) {
+                    tracing::warn!(?err, path = ?type_path, "failed to migrate store type file");
+                }
+            }
+        }
     }
 
     pub fn add_backend(&mut self, name: &str, factory: BackendFactory) {
         self.backend_factories.insert(name.to_string(), factory);
     }
 
+    /// Makes `old_name` in a commit store's `type` file load the backend
+    /// registered as `new_name`.
+    pub fn add_backend_alias(&mut self, old_name: &str, new_name: &str) {
+        self.backend_aliases
+            .insert(old_name.to_string(), new_name.to_string());
+    }
+
     pub fn load_backend(
         &self,
         settings: &UserSettings,
         store_path: &Path,
     ) -> Result<Box<dyn Backend>, StoreLoadError> {
-        let backend_type = read_store_type("commit", store_path.join("type"))?;
-        let backend_factory = self.backend_factories.get(&backend_type).ok_or_else(|| {
-            StoreLoadError::UnsupportedType {
-                store: "commit",
-                store_type: backend_type.to_string(),
-            }
-        })?;
-        Ok(backend_factory(settings, store_path)?)
+        let type_path = store_path.join("type");
+        let backend_type = read_store_type("commit", &type_path)?;
+        let (backend_factory, backend_type) = resolve_store_type(
+            "commit",
+            backend_type,
+            &self.backend_factories,
+            &self.backend_aliases,
+        )?;
+        let backend = backend_factory(settings, store_path)?;
+        self.migrate_type_file(&type_path, &backend_type);
+        let retry_policy =
+            RetryPolicy::from_settings(settings).map_err(|err| BackendLoadError(err.into()))?;
+        if retry_policy.is_enabled() {
+            Ok(Box::new(RetryingBackend::new(backend, retry_policy)))
+        } else {
+            Ok(backend)
+        }
     }
 
     pub fn add_op_store(&mut self, name: &str, factory: OpStoreFactory) {
         self.op_store_factories.insert(name.to_string(), factory);
     }
 
+    /// Makes `old_name` in an operation store's `type` file load the store
+    /// registered as `new_name`.
+    pub fn add_op_store_alias(&mut self, old_name: &str, new_name: &str) {
+        self.op_store_aliases
+            .insert(old_name.to_string(), new_name.to_string());
+    }
+
     pub fn load_op_store(
         &self,
         settings: &UserSettings,
         store_path: &Path,
         root_data: RootOperationData,
     ) -> Result<Box<dyn OpStore>, StoreLoadError> {
-        let op_store_type = read_store_type("operation", store_path.join("type"))?;
-        let op_store_factory = self.op_store_factories.get(&op_store_type).ok_or_else(|| {
-            StoreLoadError::UnsupportedType {
-                store: "operation",
-                store_type: op_store_type.to_string(),
-            }
-        })?;
-        Ok(op_store_factory(settings, store_path, root_data)?)
+        let type_path = store_path.join("type");
+        let op_store_type = read_store_type("operation", &type_path)?;
+        let (op_store_factory, op_store_type) = resolve_store_type(
+            "operation",
+            op_store_type,
+            &self.op_store_factories,
+            &self.op_store_aliases,
+        )?;
+        let op_store = op_store_factory(settings, store_path, root_data)?;
+        self.migrate_type_file(&type_path, &op_store_type);
+        Ok(op_store)
     }
 
     pub fn add_op_heads_store(&mut self, name: &str, factory: OpHeadsStoreFactory) {
@@ -550,40 +895,58 @@ impl StoreFactories {
             .insert(name.to_string(), factory);
     }
 
+    /// Makes `old_name` in an operation heads store's `type` file load the store
+    /// registered as `new_name`.
+    pub fn add_op_heads_store_alias(&mut self, old_name: &str, new_name: &str) {
+        self.op_heads_store_aliases
+            .insert(old_name.to_string(), new_name.to_string());
+    }
+
     pub fn load_op_heads_store(
         &self,
         settings: &UserSettings,
         store_path: &Path,
     ) -> Result<Box<dyn OpHeadsStore>, StoreLoadError> {
-        let op_heads_store_type = read_store_type("operation heads", store_path.join("type"))?;
-        let op_heads_store_factory = self
-            .op_heads_store_factories
-            .get(&op_heads_store_type)
-            .ok_or_else(|| StoreLoadError::UnsupportedType {
-                store: "operation heads",
-                store_type: op_heads_store_type.to_string(),
-            })?;
-        Ok(op_heads_store_factory(settings, store_path)?)
+        let type_path = store_path.join("type");
+        let op_heads_store_type = read_store_type("operation heads", &type_path)?;
+        let (op_heads_store_factory, op_heads_store_type) = resolve_store_type(
+            "operation heads",
+            op_heads_store_type,
+            &self.op_heads_store_factories,
+            &self.op_heads_store_aliases,
+        )?;
+        let op_heads_store = op_heads_store_factory(settings, store_path)?;
+        self.migrate_type_file(&type_path, &op_heads_store_type);
+        Ok(op_heads_store)
     }
 
     pub fn add_index_store(&mut self, name: &str, factory: IndexStoreFactory) {
         self.index_store_factories.insert(name.to_string(), factory);
     }
 
+    /// Makes `old_name` in an index store's `type` file load the store
+    /// registered as `new_name`.
+    pub fn add_index_store_alias(&mut self, old_name: &str, new_name: &str) {
+        self.index_store_aliases
+            .insert(old_name.to_string(), new_name.to_string());
+    }
+
     pub fn load_index_store(
         &self,
         settings: &UserSettings,
         store_path: &Path,
     ) -> Result<Box<dyn IndexStore>, StoreLoadError> {
-        let index_store_type = read_store_type("index", store_path.join("type"))?;
-        let index_store_factory = self
-            .index_store_factories
-            .get(&index_store_type)
-            .ok_or_else(|| StoreLoadError::UnsupportedType {
-                store: "index",
-                store_type: index_store_type.to_string(),
-            })?;
-        Ok(index_store_factory(settings, store_path)?)
+        let type_path = store_path.join("type");
+        let index_store_type = read_store_type("index", &type_path)?;
+        let (index_store_factory, index_store_type) = resolve_store_type(
+            "index",
+            index_store_type,
+            &self.index_store_factories,
+            &self.index_store_aliases,
+        )?;
+        let index_store = index_store_factory(settings, store_path)?;
+        self.migrate_type_file(&type_path, &index_store_type);
+        Ok(index_store)
     }
 
     pub fn add_submodule_store(&mut self, name: &str, factory: SubmoduleStoreFactory) {
@@ -591,24 +954,61 @@ impl StoreFactories {
             .insert(name.to_string(), factory);
     }
 
+    /// Makes `old_name` in a submodule store's `type` file load the store
+    /// registered as `new_name`.
+    pub fn add_submodule_store_alias(&mut self, old_name: &str, new_name: &str) {
+        self.submodule_store_aliases
+            .insert(old_name.to_string(), new_name.to_string());
+    }
+
     pub fn load_submodule_store(
         &self,
         settings: &UserSettings,
         store_path: &Path,
     ) -> Result<Box<dyn SubmoduleStore>, StoreLoadError> {
-        let submodule_store_type = read_store_type("submodule_store", store_path.join("type"))?;
-        let submodule_store_factory = self
-            .submodule_store_factories
-            .get(&submodule_store_type)
-            .ok_or_else(|| StoreLoadError::UnsupportedType {
-                store: "submodule_store",
-                store_type: submodule_store_type.to_string(),
-            })?;
-
-        Ok(submodule_store_factory(settings, store_path)?)
+        let type_path = store_path.join("type");
+        let submodule_store_type = read_store_type("submodule_store", &type_path)?;
+        let (submodule_store_factory, submodule_store_type) = resolve_store_type(
+            "submodule_store",
+            submodule_store_type,
+            &self.submodule_store_factories,
+            &self.submodule_store_aliases,
+        )?;
+        let submodule_store = submodule_store_factory(settings, store_path)?;
+        self.migrate_type_file(&type_path, &submodule_store_type);
+        Ok(submodule_store)
     }
 }
 
+/// How a store type name read from a `type` file was resolved.
+enum StoreType {
+    Current,
+    /// Loaded through an alias. Contains the current name.
+    Alias(String),
+}
+
+fn resolve_store_type<'a, F>(
+    store: &'static str,
+    store_type: String,
+    factories: &'a HashMap<String, F>,
+    aliases: &HashMap<String, String>,
+) -> Result<(&'a F, StoreType), StoreLoadError> {
+    if let Some(factory) = factories.get(&store_type) {
+        return Ok((factory, StoreType::Current));
+    }
+    if let Some((new_name, factory)) = aliases
+        .get(&store_type)
+        .and_then(|new_name| Some((new_name, factories.get(new_name)?)))
+    {
+        return Ok((factory, StoreType::Alias(new_name.clone())));
+    }
+    Err(StoreLoadError::UnsupportedType {
+        store,
+        store_type,
+        known_types: factories.keys().cloned().sorted().collect(),
+    })
+}
+
 pub fn read_store_type(
     store: &'static str,
     path: impl AsRef<Path>,
@@ -635,6 +1035,44 @@ pub enum RepoLoaderError {
     TransactionCommit(#[from] TransactionCommitError),
 }
 
+/// Snapshot of the repo's view at a given operation, loaded without the index.
+///
+/// This is cheaper than a `ReadonlyRepo` when only refs and heads are needed,
+/// such as when comparing views of operations. Since there's no index, it
+/// cannot be used to evaluate revsets or to start a transaction.
+#[derive(Clone)]
+pub struct ViewOnlyRepo {
+    store: Arc<Store>,
+    operation: Operation,
+    view: View,
+}
+
+impl Debug for ViewOnlyRepo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("ViewOnlyRepo")
+            .field("store", &self.store)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ViewOnlyRepo {
+    pub fn store(&self) -> &Arc<Store> {
+        &self.store
+    }
+
+    pub fn op_id(&self) -> &OperationId {
+        self.operation.id()
+    }
+
+    pub fn operation(&self) -> &Operation {
+        &self.operation
+    }
+
+    pub fn view(&self) -> &View {
+        &self.view
+    }
+}
+
 /// Helps create `ReadonlyRepo` instances of a repo at the head operation or at
 /// a given operation.
 #[derive(Clone)]
@@ -642,9 +1080,12 @@ pub struct RepoLoader {
     settings: UserSettings,
     store: Arc<Store>,
     op_store: Arc<dyn OpStore>,
+    op_graph: Arc<OpGraph>,
     op_heads_store: Arc<dyn OpHeadsStore>,
     index_store: Arc<dyn IndexStore>,
     submodule_store: Arc<dyn SubmoduleStore>,
+    pin_store: Option<RepoPinStore>,
+    transaction_hooks: Option<Arc<dyn TransactionHooks>>,
 }
 
 impl RepoLoader {
@@ -659,10 +1100,13 @@ impl RepoLoader {
         Self {
             settings,
             store,
+            op_graph: Arc::new(OpGraph::new(op_store.clone())),
             op_store,
             op_heads_store,
             index_store,
             submodule_store,
+            pin_store: None,
+            transaction_hooks: None,
         }
     }
 
@@ -674,14 +1118,21 @@ impl RepoLoader {
         repo_path: &Path,
         store_factories: &StoreFactories,
     ) -> Result<Self, StoreLoadError> {
+        let store_path = repo_path.join("store");
         let store = Store::new(
-            store_factories.load_backend(settings, &repo_path.join("store"))?,
+            store_factories.load_backend(settings, &store_path)?,
             Signer::from_settings(settings)?,
         );
+        store
+            .load_shallow_boundaries(&store_path)
+            .map_err(|source| StoreLoadError::ReadError {
+                store: "commit",
+                source,
+            })?;
         let root_op_data = RootOperationData {
             root_commit_id: store.root_commit_id().clone(),
         };
-        let op_store = Arc::from(store_factories.load_op_store(
+        let op_store: Arc<dyn OpStore> = Arc::from(store_factories.load_op_store(
             settings,
             &repo_path.join("op_store"),
             root_op_data,
@@ -696,10 +1147,13 @@ impl RepoLoader {
         Ok(Self {
             settings: settings.clone(),
             store,
+            op_graph: Arc::new(OpGraph::new(op_store.clone())),
             op_store,
             op_heads_store,
             index_store,
             submodule_store,
+            pin_store: Some(RepoPinStore::for_repo(repo_path)),
+            transaction_hooks: None,
         })
     }
 
@@ -707,6 +1161,16 @@ impl RepoLoader {
         &self.settings
     }
 
+    /// Registers hooks to be invoked when transactions of repos loaded by this
+    /// loader are committed.
+    pub fn set_transaction_hooks(&mut self, hooks: Arc<dyn TransactionHooks>) {
+        self.transaction_hooks = Some(hooks);
+    }
+
+    pub fn transaction_hooks(&self) -> Option<&Arc<dyn TransactionHooks>> {
+        self.transaction_hooks.as_ref()
+    }
+
     pub fn store(&self) -> &Arc<Store> {
         &self.store
     }
@@ -715,10 +1179,21 @@ impl RepoLoader {
         &self.index_store
     }
 
+    /// Store of the pins in `.jj/repo/pins/`, if the repo was loaded from the
+    /// file system.
+    pub fn pin_store(&self) -> Option<&RepoPinStore> {
+        self.pin_store.as_ref()
+    }
+
     pub fn op_store(&self) -> &Arc<dyn OpStore> {
         &self.op_store
     }
 
+    /// Memoizing ancestry queries over the operations in `op_store()`.
+    pub fn op_graph(&self) -> &Arc<OpGraph> {
+        &self.op_graph
+    }
+
     pub fn op_heads_store(&self) -> &Arc<dyn OpHeadsStore> {
         &self.op_heads_store
     }
@@ -728,11 +1203,21 @@ impl RepoLoader {
     }
 
     pub fn load_at_head(&self) -> Result<Arc<ReadonlyRepo>, RepoLoaderError> {
+        let mut merged = false;
         let op = op_heads_store::resolve_op_heads(
             self.op_heads_store.as_ref(),
             &self.op_store,
-            |op_heads| self._resolve_op_heads(op_heads),
+            |op_heads| {
+                merged = true;
+                self._resolve_op_heads(op_heads)
+            },
         )?;
+        // The merge operation is published by resolve_op_heads()
+        if merged {
+            if let Some(hooks) = &self.transaction_hooks {
+                hooks.post_commit(&op);
+            }
+        }
         let view = op.view()?;
         self._finish_load(op, view)
     }
@@ -743,6 +1228,22 @@ impl RepoLoader {
         self._finish_load(op.clone(), view)
     }
 
+    /// Loads the view at the given operation without loading the index.
+    pub fn load_view_at(&self, op: &Operation) -> OpStoreResult<View> {
+        op.view()
+    }
+
+    /// Loads a [`ViewOnlyRepo`] at the given operation without loading the
+    /// index.
+    pub fn load_view_only_at(&self, op: &Operation) -> OpStoreResult<ViewOnlyRepo> {
+        let view = self.load_view_at(op)?;
+        Ok(ViewOnlyRepo {
+            store: self.store.clone(),
+            operation: op.clone(),
+            view,
+        })
+    }
+
     pub fn create_from(
         &self,
         operation: Operation,
@@ -776,16 +1277,20 @@ impl RepoLoader {
 
     /// Merges the given `operations` into a single operation. Returns the root
     /// operation if the `operations` is empty.
+    ///
+    /// Also returns the bookmarks and tags which became conflicted by the
+    /// merge. A summary of them is recorded in the merge operation's tags.
     pub fn merge_operations(
         &self,
         operations: Vec<Operation>,
         tx_description: Option<&str>,
-    ) -> Result<Operation, RepoLoaderError> {
+    ) -> Result<(Operation, Vec<RefMergeConflict>), RepoLoaderError> {
         let num_operations = operations.len();
         let mut operations = operations.into_iter();
         let Some(base_op) = operations.next() else {
-            return Ok(self.root_operation());
+            return Ok((self.root_operation(), vec![]));
         };
+        let mut ref_conflicts = vec![];
         let final_op = if num_operations > 1 {
             let base_repo = self.load_at(&base_op)?;
             let mut tx = base_repo.start_transaction();
@@ -797,18 +1302,20 @@ impl RepoLoader {
                 || format!("merge {num_operations} operations"),
                 |tx_description| tx_description.to_string(),
             );
+            ref_conflicts = tx.repo().ref_merge_conflicts().to_vec();
             let merged_repo = tx.write(tx_description)?.leave_unpublished();
             merged_repo.operation().clone()
         } else {
             base_op
         };
 
-        Ok(final_op)
+        Ok((final_op, ref_conflicts))
     }
 
     fn _resolve_op_heads(&self, op_heads: Vec<Operation>) -> Result<Operation, RepoLoaderError> {
         assert!(!op_heads.is_empty());
-        self.merge_operations(op_heads, Some("reconcile divergent operations"))
+        let (op, _) = self.merge_operations(op_heads, Some("reconcile divergent operations"))?;
+        Ok(op)
     }
 
     fn _finish_load(
@@ -851,6 +1358,63 @@ impl Rewrite {
     }
 }
 
+/// Transitively resolved replacements of rewritten commits.
+///
+/// Commit ids are interned into indices, and the resolved ids of all rewritten
+/// commits are stored in a single arena. This keeps resolution of very large
+/// mappings cheap.
+#[derive(Debug)]
+struct ResolvedRewriteMapping<'a> {
+    /// Commit ids by interned index.
+    ids: Vec<&'a CommitId>,
+    /// Interned indices by commit id.
+    indices: HashMap<&'a CommitId, u32>,
+    /// Ranges of the resolved ids in `arena` by interned index, or `None` if
+    /// the commit isn't rewritten.
+    ranges: Vec<Option<Range<u32>>>,
+    arena: Vec<u32>,
+}
+
+impl<'a> ResolvedRewriteMapping<'a> {
+    fn with_capacity(capacity: usize) -> Self {
+        ResolvedRewriteMapping {
+            ids: Vec::with_capacity(capacity),
+            indices: HashMap::with_capacity(capacity),
+            ranges: Vec::with_capacity(capacity),
+            arena: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn intern(&mut self, id: &'a CommitId) -> u32 {
+        *self.indices.entry(id).or_insert_with(|| {
+            self.ids.push(id);
+            self.ranges.push(None);
+            to_u32(self.ids.len() - 1)
+        })
+    }
+
+    fn get_by_index(
+        &self,
+        index: u32,
+    ) -> impl ExactSizeIterator<Item = &'a CommitId> + use<'_, 'a> {
+        let range = self.ranges[index as usize].clone().unwrap_or_default();
+        self.arena[range.start as usize..range.end as usize]
+            .iter()
+            .map(|&i| self.ids[i as usize])
+    }
+
+    /// Returns the resolved new ids if the commit `id` is rewritten.
+    fn get(&self, id: &CommitId) -> Option<Vec<CommitId>> {
+        let &index = self.indices.get(id)?;
+        self.ranges[index as usize].as_ref()?;
+        Some(self.get_by_index(index).cloned().collect())
+    }
+}
+
+fn to_u32(index: usize) -> u32 {
+    u32::try_from(index).expect("too many commits in the parent mapping")
+}
+
 pub struct MutableRepo {
     base_repo: Arc<ReadonlyRepo>,
     index: Box<dyn MutableIndex>,
@@ -860,6 +1424,8 @@ pub struct MutableRepo {
     /// This is similar to (the reverse of) `parent_mapping`, but
     /// `commit_predecessors` will never be cleared on `rebase_descendants()`.
     commit_predecessors: BTreeMap<CommitId, Vec<CommitId>>,
+    /// Descendant commits rebased by `transform_commits()`.
+    commit_rewrites: CommitRewrites,
     // The commit identified by the key has been replaced by all the ones in the value.
     // * Bookmarks pointing to the old commit should be updated to the new commit, resulting in a
     //   conflict if there multiple new commits.
@@ -869,6 +1435,18 @@ pub struct MutableRepo {
     //   commits. However, if the type is `Abandoned`, a new working-copy commit should be created
     //   on top of all of the new commits instead.
     parent_mapping: HashMap<CommitId, Rewrite>,
+    metrics: TransactionMetrics,
+    /// Store metrics at the time this repo was created.
+    base_store_metrics: StoreMetricsSnapshot,
+    rewrite_policies: Vec<Arc<dyn RewritePolicy>>,
+    rewrite_override: Option<RewriteOverride>,
+    /// The first rewrite denied by the policies. Reported when the transaction
+    /// is committed.
+    rewrite_denied: Option<RewriteDeniedError>,
+    /// Bookmarks and tags which became conflicted by `merge()`.
+    ref_merge_conflicts: Vec<RefMergeConflict>,
+    /// Commit writes queued while rewriting commits.
+    write_batch: Option<WriteBatch>,
 }
 
 impl MutableRepo {
@@ -879,12 +1457,21 @@ impl MutableRepo {
     ) -> MutableRepo {
         let mut_view = view.clone();
         let mut_index = index.start_modification();
+        let base_store_metrics = base_repo.store().metrics().snapshot();
         MutableRepo {
             base_repo,
             index: mut_index,
             view: DirtyCell::with_clean(mut_view),
             commit_predecessors: Default::default(),
+            commit_rewrites: Default::default(),
             parent_mapping: Default::default(),
+            metrics: TransactionMetrics::default(),
+            base_store_metrics,
+            rewrite_policies: vec![],
+            rewrite_override: None,
+            rewrite_denied: None,
+            ref_merge_conflicts: vec![],
+            write_batch: None,
         }
     }
 
@@ -900,6 +1487,104 @@ impl MutableRepo {
         self.index.as_ref()
     }
 
+    /// Returns the metrics collected since this repo was created.
+    ///
+    /// The counters are only maintained if the `metrics` feature is enabled.
+    pub fn metrics(&self) -> TransactionMetrics {
+        TransactionMetrics {
+            store: self
+                .store()
+                .metrics()
+                .snapshot()
+                .since(&self.base_store_metrics),
+            ..self.metrics
+        }
+    }
+
+    pub(crate) fn metrics_mut(&mut self) -> &mut TransactionMetrics {
+        &mut self.metrics
+    }
+
+    pub(crate) fn write_batch_mut(&mut self) -> Option<&mut WriteBatch> {
+        self.write_batch.as_mut()
+    }
+
+    /// Installs a policy to be consulted before commits are rewritten or
+    /// abandoned.
+    pub fn add_rewrite_policy(&mut self, policy: Arc<dyn RewritePolicy>) {
+        self.rewrite_policies.push(policy);
+    }
+
+    /// Sets the token which allows rewriting commits that would otherwise
+    /// require an override.
+    pub fn set_rewrite_override(&mut self, token: Option<RewriteOverride>) {
+        self.rewrite_override = token;
+    }
+
+    /// Returns the most restrictive permission of the installed policies for
+    /// rewriting the given commit. The root commit is always forbidden.
+    ///
+    /// The [`RewriteOverride`] isn't taken into account.
+    pub fn check_rewrite(&self, id: &CommitId) -> Result<RewritePermission, RewriteDeniedError> {
+        if id == self.store().root_commit_id() {
+            return Ok(RewritePermission::Forbidden("root commit".to_owned()));
+        }
+        let mut permission = RewritePermission::Allowed;
+        for policy in &self.rewrite_policies {
+            let policy_permission =
+                policy
+                    .check(self, id)
+                    .map_err(|source| RewriteDeniedError::Policy {
+                        commit_id: id.clone(),
+                        source,
+                    })?;
+            permission = permission.restrict(policy_permission);
+        }
+        Ok(permission)
+    }
+
+    /// Returns an error if the given commit may not be rewritten, taking the
+    /// [`RewriteOverride`] into account.
+    pub fn ensure_rewritable(&self, id: &CommitId) -> Result<(), RewriteDeniedError> {
+        match self.check_rewrite(id)? {
+            RewritePermission::Allowed => Ok(()),
+            RewritePermission::RequiresOverride(_) if self.rewrite_override.is_some() => Ok(()),
+            RewritePermission::RequiresOverride(reason) => {
+                Err(RewriteDeniedError::RequiresOverride {
+                    commit_id: id.clone(),
+                    reason,
+                })
+            }
+            RewritePermission::Forbidden(reason) => Err(RewriteDeniedError::Forbidden {
+                commit_id: id.clone(),
+                reason,
+            }),
+        }
+    }
+
+    /// Checks the rewrite of `id` against the installed policies, and
+    /// remembers the first denial.
+    fn audit_rewrite(&mut self, id: &CommitId) {
+        if self.rewrite_policies.is_empty() || self.rewrite_denied.is_some() {
+            return;
+        }
+        if let Err(err) = self.ensure_rewritable(id) {
+            self.rewrite_denied = Some(err);
+        }
+    }
+
+    /// Returns the first rewrite denied by the installed policies since the
+    /// last call.
+    pub fn take_rewrite_denied(&mut self) -> Option<RewriteDeniedError> {
+        self.rewrite_denied.take()
+    }
+
+    /// Returns bookmarks and tags which became conflicted by merging
+    /// concurrent operations into this repo.
+    pub fn ref_merge_conflicts(&self) -> &[RefMergeConflict] {
+        &self.ref_merge_conflicts
+    }
+
     pub fn has_changes(&self) -> bool {
         self.view.ensure_clean(|v| self.enforce_view_invariants(v));
         !(self.commit_predecessors.is_empty()
@@ -913,9 +1598,15 @@ impl MutableRepo {
         Box<dyn MutableIndex>,
         View,
         BTreeMap<CommitId, Vec<CommitId>>,
+        CommitRewrites,
     ) {
         self.view.ensure_clean(|v| self.enforce_view_invariants(v));
-        (self.index, self.view.into_inner(), self.commit_predecessors)
+        (
+            self.index,
+            self.view.into_inner(),
+            self.commit_predecessors,
+            self.commit_rewrites,
+        )
     }
 
     /// Returns a [`CommitBuilder`] to write new commit to the repo.
@@ -944,6 +1635,7 @@ impl MutableRepo {
     /// docstring for `record_rewritten_commit` for details.
     pub fn set_rewritten_commit(&mut self, old_id: CommitId, new_id: CommitId) {
         assert_ne!(old_id, *self.store().root_commit_id());
+        self.audit_rewrite(&old_id);
         self.parent_mapping
             .insert(old_id, Rewrite::Rewritten(new_id));
     }
@@ -961,6 +1653,7 @@ impl MutableRepo {
         new_ids: impl IntoIterator<Item = CommitId>,
     ) {
         assert_ne!(old_id, *self.store().root_commit_id());
+        self.audit_rewrite(&old_id);
         self.parent_mapping.insert(
             old_id.clone(),
             Rewrite::Divergent(new_ids.into_iter().collect()),
@@ -996,12 +1689,25 @@ impl MutableRepo {
         new_parent_ids: impl IntoIterator<Item = CommitId>,
     ) {
         assert_ne!(old_id, *self.store().root_commit_id());
+        self.audit_rewrite(&old_id);
         self.parent_mapping.insert(
             old_id,
             Rewrite::Abandoned(new_parent_ids.into_iter().collect()),
         );
     }
 
+    /// Like [`Self::record_abandoned_commit()`], but doesn't consult the
+    /// rewrite policies. This is for commits that disappeared from an external
+    /// source such as the Git repo, which the policies have no say in.
+    #[cfg(feature = "git")]
+    pub(crate) fn record_externally_abandoned_commit(&mut self, old_commit: &Commit) {
+        assert_ne!(old_commit.id(), self.store().root_commit_id());
+        self.parent_mapping.insert(
+            old_commit.id().clone(),
+            Rewrite::Abandoned(old_commit.parent_ids().to_vec()),
+        );
+    }
+
     pub fn has_rewrites(&self) -> bool {
         !self.parent_mapping.is_empty()
     }
@@ -1022,6 +1728,21 @@ impl MutableRepo {
         mut predicate: impl FnMut(&Rewrite) -> bool,
     ) -> Vec<CommitId> {
         assert!(!old_ids.is_empty());
+        // Fast path for a single parent which isn't rewritten, or is rewritten
+        // to a single commit. The iteration is bounded so that a cycle falls
+        // back to the general path.
+        if let [old_id] = old_ids {
+            let mut id = old_id;
+            for _ in 0..=self.parent_mapping.len() {
+                match self.parent_mapping.get(id).filter(|&v| predicate(v)) {
+                    None => return vec![id.clone()],
+                    Some(rewrite) => match rewrite.new_parent_ids() {
+                        [new_id] => id = new_id,
+                        _ => break,
+                    },
+                }
+            }
+        }
         let mut new_ids = Vec::with_capacity(old_ids.len());
         let mut to_visit = old_ids.iter().rev().collect_vec();
         let mut visited = HashSet::new();
@@ -1061,110 +1782,220 @@ impl MutableRepo {
     fn resolve_rewrite_mapping_with(
         &self,
         mut predicate: impl FnMut(&Rewrite) -> bool,
-    ) -> HashMap<CommitId, Vec<CommitId>> {
-        let sorted_ids = dag_walk::topo_order_forward(
-            self.parent_mapping.keys(),
-            |&id| id,
-            |&id| match self.parent_mapping.get(id).filter(|&v| predicate(v)) {
-                None => &[],
-                Some(rewrite) => rewrite.new_parent_ids(),
-            },
-        );
-        let mut new_mapping: HashMap<CommitId, Vec<CommitId>> = HashMap::new();
-        for old_id in sorted_ids {
-            let Some(rewrite) = self.parent_mapping.get(old_id).filter(|&v| predicate(v)) else {
+    ) -> ResolvedRewriteMapping<'_> {
+        let mut mapping = ResolvedRewriteMapping::with_capacity(self.parent_mapping.len());
+        // Intern the rewritten commits and their direct replacements.
+        let mut rewritten = Vec::new();
+        let mut replacements = Vec::new();
+        for (old_id, rewrite) in &self.parent_mapping {
+            if !predicate(rewrite) {
                 continue;
-            };
-            let lookup = |id| new_mapping.get(id).map_or(slice::from_ref(id), |ids| ids);
-            let new_ids = match rewrite.new_parent_ids() {
-                [id] => lookup(id).to_vec(), // unique() not needed
-                ids => ids.iter().flat_map(lookup).unique().cloned().collect(),
-            };
+            }
+            let old_index = mapping.intern(old_id);
+            let start = replacements.len();
+            for new_id in rewrite.new_parent_ids() {
+                replacements.push(mapping.intern(new_id));
+            }
+            rewritten.push((old_index, start..replacements.len()));
+        }
+        let mut direct_ranges = vec![None; mapping.ids.len()];
+        for (old_index, range) in &rewritten {
+            direct_ranges[*old_index as usize] = Some(range.clone());
+        }
+
+        // Sort the rewritten commits so that replacements come first.
+        let mut sorted_indices = Vec::with_capacity(rewritten.len());
+        let mut visiting = vec![false; mapping.ids.len()];
+        let mut emitted = vec![false; mapping.ids.len()];
+        let mut stack = Vec::new();
+        for (old_index, _) in &rewritten {
+            stack.push((*old_index, false));
+            while let Some((index, replacements_visited)) = stack.pop() {
+                let i = index as usize;
+                if emitted[i] {
+                    continue;
+                }
+                let Some(range) = &direct_ranges[i] else {
+                    continue;
+                };
+                if replacements_visited {
+                    emitted[i] = true;
+                    sorted_indices.push(index);
+                } else {
+                    assert!(!visiting[i], "graph has cycle");
+                    visiting[i] = true;
+                    stack.push((index, true));
+                    stack.extend(
+                        replacements[range.clone()]
+                            .iter()
+                            .rev()
+                            .map(|&j| (j, false)),
+                    );
+                }
+            }
+        }
+
+        // Resolve in that order. Each resolved list is deduplicated by marking
+        // the ids with the commit being resolved.
+        let mut seen_by = vec![u32::MAX; mapping.ids.len()];
+        let mut push_unique = |arena: &mut Vec<u32>, old_index: u32, index: u32| {
+            if seen_by[index as usize] != old_index {
+                seen_by[index as usize] = old_index;
+                arena.push(index);
+            }
+        };
+        for old_index in sorted_indices {
+            let start = mapping.arena.len();
+            let direct_range = direct_ranges[old_index as usize].clone().unwrap();
+            for &new_index in &replacements[direct_range] {
+                match mapping.ranges[new_index as usize].clone() {
+                    Some(resolved_range) => {
+                        for k in resolved_range {
+                            let index = mapping.arena[k as usize];
+                            push_unique(&mut mapping.arena, old_index, index);
+                        }
+                    }
+                    None => push_unique(&mut mapping.arena, old_index, new_index),
+                }
+            }
+            let end = mapping.arena.len();
+            mapping.ranges[old_index as usize] = Some(to_u32(start)..to_u32(end));
             debug_assert_eq!(
-                new_ids,
-                self.rewritten_ids_with(slice::from_ref(old_id), &mut predicate)
+                mapping.get_by_index(old_index).cloned().collect_vec(),
+                self.rewritten_ids_with(
+                    slice::from_ref(mapping.ids[old_index as usize]),
+                    &mut predicate
+                )
             );
-            new_mapping.insert(old_id.clone(), new_ids);
         }
-        new_mapping
+        mapping
     }
 
     /// Updates bookmarks, working copies, and anonymous heads after rewriting
     /// and/or abandoning commits.
+    ///
+    /// Git refs and the Git HEAD aren't updated. They record the last known
+    /// state of the Git repo, which is compared against by the next export or
+    /// import. Moving them would make the export skip the update, and the
+    /// import resurrect the rewritten commits. They're updated when the new
+    /// bookmark targets are exported instead.
+    ///
+    /// Returns the names of the bookmarks which were left pointing to
+    /// divergently rewritten commits because of
+    /// [`BookmarkMovePolicy::LeaveBehind`].
     pub fn update_rewritten_references(
         &mut self,
         options: &RewriteRefsOptions,
-    ) -> BackendResult<()> {
-        self.update_all_references(options)?;
+    ) -> BackendResult<Vec<RefNameBuf>> {
+        let left_behind_bookmarks = self.update_all_references(options)?;
         self.update_heads()
             .map_err(|err| err.into_backend_error())?;
-        Ok(())
-    }
-
-    fn update_all_references(&mut self, options: &RewriteRefsOptions) -> BackendResult<()> {
-        let rewrite_mapping = self.resolve_rewrite_mapping_with(|_| true);
-        self.update_local_bookmarks(&rewrite_mapping, options);
-        self.update_wc_commits(&rewrite_mapping)?;
-        Ok(())
+        Ok(left_behind_bookmarks)
     }
 
-    fn update_local_bookmarks(
+    fn update_all_references(
         &mut self,
-        rewrite_mapping: &HashMap<CommitId, Vec<CommitId>>,
         options: &RewriteRefsOptions,
-    ) {
-        let changed_branches = self
-            .view()
+    ) -> BackendResult<Vec<RefNameBuf>> {
+        // Resolving the whole mapping can be expensive, so check first if any
+        // reference points to a rewritten commit.
+        let view = self.view();
+        let has_rewritten_refs = view
+            .local_bookmarks()
+            .flat_map(|(_, target)| target.added_ids())
+            .chain(view.wc_commit_ids().values())
+            .any(|id| self.parent_mapping.contains_key(id));
+        if !has_rewritten_refs {
+            // A cycle in the mapping would be detected while resolving it.
+            // Since that's a bug, only check it in debug builds.
+            if cfg!(debug_assertions) {
+                self.resolve_rewrite_mapping_with(|_| true);
+            }
+            return Ok(vec![]);
+        }
+        let rewrite_mapping = self.resolve_rewrite_mapping_with(|_| true);
+        let changed_bookmarks = view
             .local_bookmarks()
             .flat_map(|(name, target)| {
                 target.added_ids().filter_map(|id| {
-                    let change = rewrite_mapping.get_key_value(id)?;
-                    Some((name.to_owned(), change))
+                    let new_ids = rewrite_mapping.get(id)?;
+                    Some((name.to_owned(), id.clone(), new_ids))
                 })
             })
             .collect_vec();
-        for (bookmark_name, (old_commit_id, new_commit_ids)) in changed_branches {
+        let changed_wc_commits = view
+            .wc_commit_ids()
+            .iter()
+            .filter_map(|(name, id)| {
+                let new_ids = rewrite_mapping.get(id)?;
+                Some((name.to_owned(), id.clone(), new_ids))
+            })
+            .collect_vec();
+        let left_behind_bookmarks = self.update_local_bookmarks(changed_bookmarks, options);
+        self.update_wc_commits(changed_wc_commits)?;
+        Ok(left_behind_bookmarks)
+    }
+
+    fn update_local_bookmarks(
+        &mut self,
+        changed_bookmarks: Vec<(RefNameBuf, CommitId, Vec<CommitId>)>,
+        options: &RewriteRefsOptions,
+    ) -> Vec<RefNameBuf> {
+        let mut left_behind_bookmarks = vec![];
+        for (bookmark_name, old_commit_id, new_commit_ids) in changed_bookmarks {
+            let rewrite = self.parent_mapping.get(&old_commit_id);
             let should_delete = options.delete_abandoned_bookmarks
-                && matches!(
-                    self.parent_mapping.get(old_commit_id),
-                    Some(Rewrite::Abandoned(_))
-                );
+                && matches!(rewrite, Some(Rewrite::Abandoned(_)));
+            let divergent_policy = match rewrite {
+                Some(Rewrite::Divergent(_)) if new_commit_ids.len() > 1 => {
+                    options.bookmark_move_policy
+                }
+                _ => BookmarkMovePolicy::Conflict,
+            };
             let old_target = RefTarget::normal(old_commit_id.clone());
             let new_target = if should_delete {
                 RefTarget::absent()
             } else {
-                let ids = itertools::intersperse(new_commit_ids, old_commit_id)
-                    .map(|id| Some(id.clone()));
-                RefTarget::from_merge(MergeBuilder::from_iter(ids).build())
+                match divergent_policy {
+                    BookmarkMovePolicy::Conflict => {
+                        let ids = itertools::intersperse(&new_commit_ids, &old_commit_id)
+                            .map(|id| Some(id.clone()));
+                        RefTarget::from_merge(MergeBuilder::from_iter(ids).build())
+                    }
+                    BookmarkMovePolicy::FollowFirst => RefTarget::normal(new_commit_ids[0].clone()),
+                    BookmarkMovePolicy::LeaveBehind => {
+                        // A conflicted bookmark may have multiple added ids
+                        // left behind, but they're visited in sequence.
+                        if left_behind_bookmarks.last() != Some(&bookmark_name) {
+                            left_behind_bookmarks.push(bookmark_name);
+                        }
+                        continue;
+                    }
+                }
             };
 
             self.merge_local_bookmark(&bookmark_name, &old_target, &new_target);
         }
+        left_behind_bookmarks
     }
 
     fn update_wc_commits(
         &mut self,
-        rewrite_mapping: &HashMap<CommitId, Vec<CommitId>>,
+        changed_wc_commits: Vec<(WorkspaceNameBuf, CommitId, Vec<CommitId>)>,
     ) -> BackendResult<()> {
-        let changed_wc_commits = self
-            .view()
-            .wc_commit_ids()
-            .iter()
-            .filter_map(|(name, commit_id)| {
-                let change = rewrite_mapping.get_key_value(commit_id)?;
-                Some((name.to_owned(), change))
-            })
-            .collect_vec();
-        let mut recreated_wc_commits: HashMap<&CommitId, Commit> = HashMap::new();
-        for (name, (old_commit_id, new_commit_ids)) in changed_wc_commits {
+        // Workspaces are visited in name order, so commits are recreated in the
+        // same order (and get the same ids) regardless of how the view was
+        // built.
+        let mut recreated_wc_commits: HashMap<CommitId, Commit> = HashMap::new();
+        for (name, old_commit_id, new_commit_ids) in changed_wc_commits {
             let abandoned_old_commit = matches!(
-                self.parent_mapping.get(old_commit_id),
+                self.parent_mapping.get(&old_commit_id),
                 Some(Rewrite::Abandoned(_))
             );
             let new_wc_commit = if !abandoned_old_commit {
                 // We arbitrarily pick a new working-copy commit among the candidates.
                 self.store().get_commit(&new_commit_ids[0])?
-            } else if let Some(commit) = recreated_wc_commits.get(old_commit_id) {
+            } else if let Some(commit) = recreated_wc_commits.get(&old_commit_id) {
                 commit.clone()
             } else {
                 let new_commits: Vec<_> = new_commit_ids
@@ -1173,7 +2004,7 @@ impl MutableRepo {
                     .try_collect()?;
                 let merged_parents_tree = merge_commit_trees(self, &new_commits)?;
                 let commit = self
-                    .new_commit(new_commit_ids.clone(), merged_parents_tree.id().clone())
+                    .new_commit(new_commit_ids, merged_parents_tree.id().clone())
                     .write()?;
                 recreated_wc_commits.insert(old_commit_id, commit.clone());
                 commit
@@ -1321,15 +2152,19 @@ impl MutableRepo {
         options: &RewriteRefsOptions,
         mut callback: impl FnMut(CommitRewriter) -> BackendResult<()>,
     ) -> BackendResult<()> {
-        let mut to_visit = self.order_commits_for_rebase(commits, new_parents_map)?;
-        while let Some(old_commit) = to_visit.pop() {
-            let parent_ids = new_parents_map
-                .get(old_commit.id())
-                .map_or(old_commit.parent_ids(), |parent_ids| parent_ids);
-            let new_parent_ids = self.new_parents(parent_ids);
-            let rewriter = CommitRewriter::new(self, old_commit, new_parent_ids);
-            callback(rewriter)?;
+        let to_visit = self.order_commits_for_rebase(commits, new_parents_map)?;
+        // Each rewritten commit is needed only by its descendants, so the
+        // writes can be deferred if the backend supports that. The batch is
+        // owned by the outermost call if this is called from the callback.
+        let owns_write_batch = self.write_batch.is_none();
+        if owns_write_batch {
+            self.write_batch = Some(self.store().begin_write_batch());
+        }
+        let result = self.visit_commits_to_transform(to_visit, new_parents_map, &mut callback);
+        if owns_write_batch {
+            self.write_batch.take().unwrap().finish()?;
         }
+        result?;
         self.update_rewritten_references(options)?;
         // Since we didn't necessarily visit all descendants of rewritten commits (e.g.
         // if they were rewritten in the callback), there can still be commits left to
@@ -1343,6 +2178,34 @@ impl MutableRepo {
         Ok(())
     }
 
+    fn visit_commits_to_transform(
+        &mut self,
+        mut to_visit: Vec<Commit>,
+        new_parents_map: &HashMap<CommitId, Vec<CommitId>>,
+        callback: &mut impl FnMut(CommitRewriter) -> BackendResult<()>,
+    ) -> BackendResult<()> {
+        while let Some(old_commit) = to_visit.pop() {
+            self.metrics.record_commit_visited();
+            let parent_ids = new_parents_map
+                .get(old_commit.id())
+                .map_or(old_commit.parent_ids(), |parent_ids| parent_ids);
+            let new_parent_ids = self.new_parents(parent_ids);
+            let old_commit_id = old_commit.id().clone();
+            let was_rewritten = self.parent_mapping.contains_key(&old_commit_id);
+            let rewriter = CommitRewriter::new(self, old_commit, new_parent_ids);
+            callback(rewriter)?;
+            if !was_rewritten {
+                if let Some(Rewrite::Rewritten(new_commit_id)) =
+                    self.parent_mapping.get(&old_commit_id)
+                {
+                    self.commit_rewrites
+                        .push(old_commit_id, new_commit_id.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Rebase descendants of the rewritten commits with options and callback.
     ///
     /// The descendants of the commits registered in `self.parent_mappings` will
@@ -1363,7 +2226,14 @@ impl MutableRepo {
         options: &RebaseOptions,
         mut progress: impl FnMut(Commit, RebasedCommit),
     ) -> BackendResult<()> {
-        let roots = self.parent_mapping.keys().cloned().collect();
+        let roots: Vec<_> = self.parent_mapping.keys().cloned().collect();
+        let span = tracing::info_span!(
+            "rebase_descendants",
+            roots = roots.len(),
+            commits_written = tracing::field::Empty,
+        );
+        let _entered = span.enter();
+        let commits_written_before = self.metrics.commits_written;
         self.transform_descendants_with_options(
             roots,
             &HashMap::new(),
@@ -1378,6 +2248,10 @@ impl MutableRepo {
             },
         )?;
         self.parent_mapping.clear();
+        span.record(
+            "commits_written",
+            self.metrics.commits_written - commits_written_before,
+        );
         Ok(())
     }
 
@@ -1406,18 +2280,34 @@ impl MutableRepo {
     /// The content of those descendants will remain untouched.
     /// Returns the number of reparented descendants.
     pub fn reparent_descendants(&mut self) -> BackendResult<usize> {
+        let stats = self.reparent_descendants_with_stats()?;
+        Ok(stats.num_reparented)
+    }
+
+    /// Like [`Self::reparent_descendants()`], but also reports the reparented
+    /// merge commits whose content is no longer the merge of their new
+    /// parents. See [`find_diverged_merges()`].
+    pub fn reparent_descendants_with_stats(&mut self) -> BackendResult<ReparentDescendantsStats> {
         let roots = self.parent_mapping.keys().cloned().collect_vec();
         let mut num_reparented = 0;
+        let mut reparented_merges = vec![];
         self.transform_descendants(roots, |rewriter| {
             if rewriter.parents_changed() {
                 let builder = rewriter.reparent();
-                builder.write()?;
+                let new_commit = builder.write()?;
+                if new_commit.parent_ids().len() > 1 {
+                    reparented_merges.push(new_commit);
+                }
                 num_reparented += 1;
             }
             Ok(())
         })?;
         self.parent_mapping.clear();
-        Ok(num_reparented)
+        let diverged_merges = find_diverged_merges(self, &reparented_merges)?;
+        Ok(ReparentDescendantsStats {
+            num_reparented,
+            diverged_merges,
+        })
     }
 
     pub fn set_wc_commit(
@@ -1466,6 +2356,57 @@ impl MutableRepo {
         }
     }
 
+    pub fn set_workspace_metadata(&mut self, name: WorkspaceNameBuf, metadata: WorkspaceMetadata) {
+        self.view_mut().set_workspace_metadata(name, metadata);
+    }
+
+    /// Merges workspace metadata. If both sides changed the metadata
+    /// differently, the greater of the two is picked so that the result
+    /// doesn't depend on the merge order. The metadata of workspaces removed by
+    /// the merge is dropped.
+    fn merge_workspace_metadata(
+        &mut self,
+        name: &WorkspaceName,
+        base: Option<&WorkspaceMetadata>,
+        other: Option<&WorkspaceMetadata>,
+    ) {
+        let view = self.view.get_mut();
+        let own = view.workspace_metadata(name);
+        let new_metadata = if view.get_wc_commit_id(name).is_none() {
+            None
+        } else if let Some(resolved) = trivial_merge(&[own, base, other]) {
+            resolved.cloned()
+        } else if own.is_none() || other.is_none() {
+            None
+        } else {
+            own.max(other).cloned()
+        };
+        match new_metadata {
+            Some(metadata) => view.set_workspace_metadata(name.to_owned(), metadata),
+            None => view.remove_workspace_metadata(name),
+        }
+    }
+
+    fn merge_note(&mut self, change_id: &ChangeId, base: Option<&String>, other: Option<&String>) {
+        let view = self.view.get_mut();
+        let own = view.get_note(change_id);
+        let base = base.map(String::as_str);
+        let other = other.map(String::as_str);
+        let new_note = if let Some(resolved) = trivial_merge(&[own, base, other]) {
+            resolved.map(ToOwned::to_owned)
+        } else if let (Some(own), Some(other)) = (own, other) {
+            Some(conflicted_note(own, other))
+        } else {
+            // One side removed the note while the other side changed it. Keep
+            // the changed note.
+            own.or(other).map(ToOwned::to_owned)
+        };
+        match new_note {
+            Some(text) => view.set_note(change_id.clone(), text),
+            None => view.remove_note(change_id),
+        }
+    }
+
     pub fn rename_workspace(
         &mut self,
         old_name: &WorkspaceName,
@@ -1545,8 +2486,7 @@ impl MutableRepo {
             // but it can definitely fail for non-default implementations.
             // TODO: propagate errors.
             view.head_ids = self
-                .index()
-                .heads(&mut view.head_ids.iter())
+                .heads_of_set(&mut view.head_ids.iter())
                 .unwrap()
                 .into_iter()
                 .collect();
@@ -1620,11 +2560,63 @@ impl MutableRepo {
         self.view.mark_dirty();
     }
 
+    /// Keeps the commit visible even if it isn't reachable from the visible
+    /// heads.
+    ///
+    /// The commit should exist in the index. Pinning only affects visibility;
+    /// descendants aren't rebased if the pinned commit is rewritten.
+    pub fn pin_commit(&mut self, commit_id: CommitId) {
+        self.view_mut().pin_commit(commit_id);
+    }
+
+    pub fn unpin_commit(&mut self, commit_id: &CommitId) {
+        self.view_mut().unpin_commit(commit_id);
+    }
+
+    /// Attaches a note to the change, replacing its existing note if any.
+    ///
+    /// Notes are stored in the view, so they can be changed without rewriting
+    /// the change's commit, and they're kept when the commit is rewritten. If
+    /// concurrent operations change the same note differently, the merged note
+    /// contains both versions between conflict markers.
+    pub fn set_note(&mut self, change_id: ChangeId, text: String) -> Result<(), NoteTooLargeError> {
+        if text.len() > MAX_NOTE_SIZE {
+            return Err(NoteTooLargeError { size: text.len() });
+        }
+        self.view_mut().set_note(change_id, text);
+        Ok(())
+    }
+
+    pub fn remove_note(&mut self, change_id: &ChangeId) {
+        self.view_mut().remove_note(change_id);
+    }
+
     pub fn get_local_bookmark(&self, name: &RefName) -> RefTarget {
         self.view.with_ref(|v| v.get_local_bookmark(name).clone())
     }
 
-    pub fn set_local_bookmark_target(&mut self, name: &RefName, target: RefTarget) {
+    /// Sets the target of the local bookmark.
+    ///
+    /// The name is validated if a new bookmark would be created. Existing
+    /// bookmarks can be updated or deleted even if their names are invalid.
+    pub fn set_local_bookmark_target(
+        &mut self,
+        name: &RefName,
+        target: RefTarget,
+    ) -> Result<(), RefUpdateError> {
+        if target.is_present() && self.get_local_bookmark(name).is_absent() {
+            name.validate()?;
+        }
+        self.set_local_bookmark_target_unchecked(name, target);
+        Ok(())
+    }
+
+    /// Sets the target of the local bookmark without validating the name.
+    pub(crate) fn set_local_bookmark_target_unchecked(
+        &mut self,
+        name: &RefName,
+        target: RefTarget,
+    ) {
         let view = self.view_mut();
         for id in target.added_ids() {
             view.add_head(id);
@@ -1643,7 +2635,7 @@ impl MutableRepo {
         let index = self.index.as_index();
         let self_target = view.get_local_bookmark(name);
         let new_target = merge_ref_targets(index, self_target, base_target, other_target);
-        self.set_local_bookmark_target(name, new_target);
+        self.set_local_bookmark_target_unchecked(name, new_target);
     }
 
     pub fn get_remote_bookmark(&self, symbol: RemoteRefSymbol<'_>) -> RemoteRef {
@@ -1697,7 +2689,24 @@ impl MutableRepo {
         self.view.with_ref(|v| v.get_tag(name).clone())
     }
 
-    pub fn set_tag_target(&mut self, name: &RefName, target: RefTarget) {
+    /// Sets the target of the tag.
+    ///
+    /// The name is validated if a new tag would be created. Existing tags can
+    /// be updated or deleted even if their names are invalid.
+    pub fn set_tag_target(
+        &mut self,
+        name: &RefName,
+        target: RefTarget,
+    ) -> Result<(), RefUpdateError> {
+        if target.is_present() && self.get_tag(name).is_absent() {
+            name.validate()?;
+        }
+        self.set_tag_target_unchecked(name, target);
+        Ok(())
+    }
+
+    /// Sets the target of the tag without validating the name.
+    pub(crate) fn set_tag_target_unchecked(&mut self, name: &RefName, target: RefTarget) {
         self.view_mut().set_tag_target(name, target);
     }
 
@@ -1765,11 +2774,19 @@ impl MutableRepo {
         self.index.merge_in(other_repo.readonly_index());
     }
 
+    #[instrument(skip_all)]
     fn merge_view(&mut self, base: &View, other: &View) -> BackendResult<()> {
         let changed_wc_commits = diff_named_commit_ids(base.wc_commit_ids(), other.wc_commit_ids());
         for (name, (base_id, other_id)) in changed_wc_commits {
             self.merge_wc_commit(name, base_id, other_id);
         }
+        let changed_metadata = diff_named_values(
+            base.all_workspace_metadata(),
+            other.all_workspace_metadata(),
+        );
+        for (name, (base_metadata, other_metadata)) in changed_metadata {
+            self.merge_workspace_metadata(name, base_metadata, other_metadata);
+        }
 
         let base_heads = base.heads().iter().cloned().collect_vec();
         let own_heads = self.view().heads().iter().cloned().collect_vec();
@@ -1795,15 +2812,45 @@ impl MutableRepo {
             self.view_mut().add_head(added_head);
         }
 
+        for unpinned_id in base.pinned_commits().difference(other.pinned_commits()) {
+            self.view_mut().unpin_commit(unpinned_id);
+        }
+        for pinned_id in other.pinned_commits().difference(base.pinned_commits()) {
+            self.view_mut().pin_commit(pinned_id.clone());
+        }
+
+        let changed_notes = diff_named_values(base.notes(), other.notes());
+        for (change_id, (base_note, other_note)) in changed_notes {
+            self.merge_note(change_id, base_note, other_note);
+        }
+
         let changed_local_bookmarks =
             diff_named_ref_targets(base.local_bookmarks(), other.local_bookmarks());
         for (name, (base_target, other_target)) in changed_local_bookmarks {
+            let self_target = self.get_local_bookmark(name);
             self.merge_local_bookmark(name, base_target, other_target);
+            self.record_ref_merge_conflict(
+                RefMergeConflictKind::LocalBookmark,
+                name,
+                base_target,
+                self_target,
+                other_target,
+                self.get_local_bookmark(name),
+            );
         }
 
         let changed_tags = diff_named_ref_targets(base.tags(), other.tags());
         for (name, (base_target, other_target)) in changed_tags {
+            let self_target = self.get_tag(name);
             self.merge_tag(name, base_target, other_target);
+            self.record_ref_merge_conflict(
+                RefMergeConflictKind::Tag,
+                name,
+                base_target,
+                self_target,
+                other_target,
+                self.get_tag(name),
+            );
         }
 
         let changed_git_refs = diff_named_ref_targets(base.git_refs(), other.git_refs());
@@ -1828,6 +2875,26 @@ impl MutableRepo {
         Ok(())
     }
 
+    fn record_ref_merge_conflict(
+        &mut self,
+        kind: RefMergeConflictKind,
+        name: &RefName,
+        base_target: &RefTarget,
+        self_target: RefTarget,
+        other_target: &RefTarget,
+        new_target: RefTarget,
+    ) {
+        if !new_target.has_conflict() || self_target.has_conflict() || other_target.has_conflict() {
+            return;
+        }
+        self.ref_merge_conflicts.push(RefMergeConflict {
+            kind,
+            name: name.to_owned(),
+            base: base_target.clone(),
+            sides: vec![self_target, other_target.clone()],
+        });
+    }
+
     /// Finds and records commits that were rewritten or abandoned between
     /// `old_heads` and `new_heads`.
     fn record_rewrites(
@@ -1869,10 +2936,21 @@ impl MutableRepo {
         }
         for (old_commit, new_commits) in rewritten_commits {
             if new_commits.len() == 1 {
-                self.set_rewritten_commit(
-                    old_commit.clone(),
-                    new_commits.into_iter().next().unwrap(),
-                );
+                let new_commit = new_commits.into_iter().next().unwrap();
+                if let Some(other_commit) = self.find_identical_rewrite(&old_commit, &new_commit)? {
+                    // Both sides made the same rewrite (e.g. when two
+                    // processes snapshot the same working copy). Keep one of
+                    // them so the change doesn't become divergent.
+                    let (survivor, loser) = if other_commit < new_commit {
+                        (other_commit, new_commit)
+                    } else {
+                        (new_commit, other_commit)
+                    };
+                    self.set_rewritten_commit(old_commit.clone(), survivor.clone());
+                    self.set_rewritten_commit(loser, survivor);
+                } else {
+                    self.set_rewritten_commit(old_commit.clone(), new_commit);
+                }
             } else {
                 self.set_divergent_rewrite(old_commit.clone(), new_commits);
             }
@@ -1889,6 +2967,49 @@ impl MutableRepo {
 
         Ok(())
     }
+
+    /// Returns the commit `old_id` was already recorded to be rewritten into
+    /// if that commit is equivalent to `new_id`.
+    fn find_identical_rewrite(
+        &self,
+        old_id: &CommitId,
+        new_id: &CommitId,
+    ) -> BackendResult<Option<CommitId>> {
+        let Some(Rewrite::Rewritten(other_id)) = self.parent_mapping.get(old_id) else {
+            return Ok(None);
+        };
+        if other_id == new_id || !self.is_equivalent_commit(other_id, new_id)? {
+            return Ok(None);
+        }
+        Ok(Some(other_id.clone()))
+    }
+
+    /// Returns true if the two commits only differ in their committer, and
+    /// their parents are pairwise equivalent.
+    fn is_equivalent_commit(&self, id1: &CommitId, id2: &CommitId) -> BackendResult<bool> {
+        if id1 == id2 {
+            return Ok(true);
+        }
+        let commit1 = self.store().get_commit(id1)?;
+        let commit2 = self.store().get_commit(id2)?;
+        let data1 = commit1.store_commit();
+        let data2 = commit2.store_commit();
+        if data1.change_id != data2.change_id
+            || data1.root_tree != data2.root_tree
+            || data1.predecessors != data2.predecessors
+            || data1.description != data2.description
+            || data1.author != data2.author
+            || data1.parents.len() != data2.parents.len()
+        {
+            return Ok(false);
+        }
+        for (parent1, parent2) in iter::zip(&data1.parents, &data2.parents) {
+            if !self.is_equivalent_commit(parent1, parent2)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
 }
 
 impl Repo for MutableRepo {
@@ -1918,21 +3039,68 @@ impl Repo for MutableRepo {
     }
 
     fn resolve_change_id_prefix(&self, prefix: &HexPrefix) -> PrefixResolution<Vec<CommitId>> {
-        let change_id_index = self.index.change_id_index(&mut self.view().heads().iter());
+        let change_id_index = self
+            .index
+            .change_id_index(&mut self.view().visible_head_ids());
         change_id_index.resolve_prefix(prefix)
     }
 
     fn shortest_unique_change_id_prefix_len(&self, target_id: &ChangeId) -> usize {
-        let change_id_index = self.index.change_id_index(&mut self.view().heads().iter());
+        let change_id_index = self
+            .index
+            .change_id_index(&mut self.view().visible_head_ids());
         change_id_index.shortest_unique_prefix_len(target_id)
     }
 }
 
+/// Result of [`MutableRepo::reparent_descendants_with_stats()`].
+#[derive(Clone, Debug, Default)]
+pub struct ReparentDescendantsStats {
+    /// The number of reparented descendants.
+    pub num_reparented: usize,
+    /// The reparented merge commits whose content differs from the merge of
+    /// their new parents.
+    pub diverged_merges: Vec<Commit>,
+}
+
 /// Error from attempts to check out the root commit for editing
 #[derive(Debug, Error)]
 #[error("Cannot rewrite the root commit")]
 pub struct RewriteRootCommit;
 
+/// Combines two conflicting versions of a note into one note with conflict
+/// markers. The versions are sorted so the result doesn't depend on the order
+/// in which operations are merged.
+fn conflicted_note(note1: &str, note2: &str) -> String {
+    let (first, second) = if note1 <= note2 {
+        (note1, note2)
+    } else {
+        (note2, note1)
+    };
+    let with_newline = |text: &str| {
+        if text.is_empty() || text.ends_with('\n') {
+            text.to_owned()
+        } else {
+            format!("{text}\n")
+        }
+    };
+    format!(
+        "<<<<<<< Conflicting note\n{}=======\n{}>>>>>>> Conflicting note ends\n",
+        with_newline(first),
+        with_newline(second)
+    )
+}
+
+/// Maximum size of a note attached to a change, in bytes.
+pub const MAX_NOTE_SIZE: usize = 64 * 1024;
+
+/// Error from attempts to attach a note larger than [`MAX_NOTE_SIZE`].
+#[derive(Debug, Error)]
+#[error("Note of {size} bytes exceeds the maximum size of {MAX_NOTE_SIZE} bytes")]
+pub struct NoteTooLargeError {
+    pub size: usize,
+}
+
 /// Error from attempts to edit a commit
 #[derive(Debug, Error)]
 pub enum EditCommitError {
@@ -1953,6 +3121,13 @@ pub enum CheckOutCommitError {
     EditCommit(#[from] EditCommitError),
 }
 
+/// Error from attempts to create or update a bookmark or tag
+#[derive(Debug, Error)]
+pub enum RefUpdateError {
+    #[error(transparent)]
+    InvalidName(#[from] InvalidRefNameError),
+}
+
 mod dirty_cell {
     use std::cell::OnceCell;
     use std::cell::RefCell;
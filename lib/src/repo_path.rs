@@ -24,6 +24,8 @@ use std::ops::Deref;
 use std::path::Component;
 use std::path::Path;
 use std::path::PathBuf;
+use std::path::Prefix;
+use std::path::PrefixComponent;
 
 use itertools::Itertools as _;
 use ref_cast::ref_cast_custom;
@@ -309,6 +311,79 @@ impl RepoPathBuf {
         })
     }
 
+    /// Parses an `input` path into a `RepoPathBuf` relative to
+    /// `workspace_root`, reporting why the path can't be used if it isn't in
+    /// the workspace.
+    ///
+    /// The `cwd` and `workspace_root` paths are supposed to be absolute. The
+    /// `input` path may be either relative to `cwd` or absolute. `..` in the
+    /// input is resolved lexically, but a path that appears to escape the
+    /// workspace is checked again with symlinks resolved, so a `cwd` reached
+    /// through a symlink is still accepted.
+    pub fn parse_fs_path_strict(
+        cwd: &Path,
+        workspace_root: &Path,
+        input: impl AsRef<Path>,
+    ) -> Result<Self, UiPathParseError> {
+        let input = input.as_ref();
+        if input.to_str().is_none() {
+            return Err(UiPathParseError::NonUtf8 {
+                input: input.into(),
+            });
+        }
+        let abs_input_path = match input.components().next() {
+            Some(Component::Prefix(prefix)) => {
+                let root_prefix = match workspace_root.components().next() {
+                    Some(Component::Prefix(p)) if is_same_drive(p, prefix) => p,
+                    _ => {
+                        return Err(UiPathParseError::DriveMismatch {
+                            base: file_util::relative_path(cwd, workspace_root).into(),
+                            input: input.into(),
+                        });
+                    }
+                };
+                let rest: PathBuf = input.components().skip(1).collect();
+                if input.has_root() {
+                    // Spell the drive the same way as the workspace root does
+                    Path::new(root_prefix.as_os_str()).join(rest)
+                } else {
+                    // Drive-relative path such as "C:foo"
+                    cwd.join(rest)
+                }
+            }
+            _ => cwd.join(input),
+        };
+        let normalized = file_util::normalize_path(&abs_input_path);
+        let to_repo_path = |relative_path: &Path| {
+            Self::from_relative_path(relative_path).map_err(|source| match source {
+                RelativePathParseError::InvalidUtf8 { .. } => UiPathParseError::NonUtf8 {
+                    input: input.into(),
+                },
+                source => UiPathParseError::Fs(FsPathParseError {
+                    base: file_util::relative_path(cwd, workspace_root).into(),
+                    input: input.into(),
+                    source,
+                }),
+            })
+        };
+        if let Ok(relative_path) = normalized.strip_prefix(workspace_root) {
+            return to_repo_path(relative_path);
+        }
+        let symlink_loop = |_| UiPathParseError::SymlinkLoop {
+            input: input.into(),
+        };
+        let resolved_root = file_util::resolve_symlinks(workspace_root).map_err(symlink_loop)?;
+        let resolved = file_util::resolve_symlinks(&normalized).map_err(symlink_loop)?;
+        if let Ok(relative_path) = resolved.strip_prefix(&resolved_root) {
+            return to_repo_path(relative_path);
+        }
+        Err(UiPathParseError::EscapesWorkspace {
+            base: file_util::relative_path(cwd, workspace_root).into(),
+            input: input.into(),
+            normalized: normalized.into(),
+        })
+    }
+
     /// Consumes this and returns the underlying string representation.
     pub fn into_internal_string(self) -> String {
         self.value
@@ -580,6 +655,50 @@ fn is_valid_repo_path_str(value: &str) -> bool {
 pub enum UiPathParseError {
     #[error(transparent)]
     Fs(FsPathParseError),
+    /// The path points outside of the workspace, even after resolving
+    /// symlinks.
+    #[error(
+        r#"Path "{input}" resolves to "{normalized}", which is outside the workspace "{base}""#
+    )]
+    EscapesWorkspace {
+        /// Workspace root path relative to the `cwd`.
+        base: Box<Path>,
+        /// Input path without normalization.
+        input: Box<Path>,
+        /// Absolute input path with `.` and `..` resolved lexically.
+        normalized: Box<Path>,
+    },
+    /// The path is on a different drive than the workspace.
+    #[error(r#"Path "{input}" is on a different drive than the workspace "{base}""#)]
+    DriveMismatch {
+        /// Workspace root path relative to the `cwd`.
+        base: Box<Path>,
+        /// Input path without normalization.
+        input: Box<Path>,
+    },
+    /// The path isn't valid UTF-8.
+    #[error(r#"Path "{}" is not valid UTF-8"#, input.to_string_lossy())]
+    NonUtf8 {
+        /// Input path without normalization.
+        input: Box<Path>,
+    },
+    /// Symlinks couldn't be resolved because they form a loop.
+    #[error(r#"Too many levels of symbolic links in path "{input}""#)]
+    SymlinkLoop {
+        /// Input path without normalization.
+        input: Box<Path>,
+    },
+}
+
+/// Returns true if the path prefixes refer to the same drive. Drive letters
+/// are compared case-insensitively.
+fn is_same_drive(a: PrefixComponent<'_>, b: PrefixComponent<'_>) -> bool {
+    match (a.kind(), b.kind()) {
+        (Prefix::Disk(a) | Prefix::VerbatimDisk(a), Prefix::Disk(b) | Prefix::VerbatimDisk(b)) => {
+            a.eq_ignore_ascii_case(&b)
+        }
+        _ => a.as_os_str().eq_ignore_ascii_case(b.as_os_str()),
+    }
 }
 
 /// Converts `RepoPath`s to and from plain strings as displayed to the user
@@ -685,7 +804,7 @@ impl RepoPathUiConverter {
     pub fn parse_file_path(&self, input: &str) -> Result<RepoPathBuf, UiPathParseError> {
         match self {
             RepoPathUiConverter::Fs { cwd, base } => {
-                RepoPathBuf::parse_fs_path(cwd, base, input).map_err(UiPathParseError::Fs)
+                RepoPathBuf::parse_fs_path_strict(cwd, base, input)
             }
         }
     }
@@ -1150,6 +1269,158 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_fs_path_strict() {
+        let temp_dir = new_temp_dir();
+        let wc_path = temp_dir.path().join("repo");
+        let cwd_path = wc_path.join("dir");
+        let parse = |input: &str| RepoPathBuf::parse_fs_path_strict(&cwd_path, &wc_path, input);
+
+        // (input, expected repo path, or None if the path escapes the workspace)
+        let cases = [
+            ("", Some("dir")),
+            (".", Some("dir")),
+            ("file", Some("dir/file")),
+            ("sub/../file", Some("dir/file")),
+            ("..", Some("")),
+            ("../other-dir/file", Some("other-dir/file")),
+            ("../../repo/file", Some("file")),
+            ("../..", None),
+            ("../../other-repo/file", None),
+            ("../../repo-suffix", None),
+            ("../../../..", None),
+        ];
+        for (input, expected) in cases {
+            match expected {
+                Some(expected) => {
+                    assert_eq!(
+                        parse(input).as_deref().ok(),
+                        Some(repo_path(expected)),
+                        "{input:?}"
+                    );
+                }
+                None => {
+                    assert_matches!(
+                        parse(input),
+                        Err(UiPathParseError::EscapesWorkspace { .. }),
+                        "{input:?}"
+                    );
+                }
+            }
+        }
+
+        // Input may be absolute path
+        assert_eq!(
+            parse(wc_path.join("file").to_str().unwrap())
+                .as_deref()
+                .ok(),
+            Some(repo_path("file"))
+        );
+        assert_matches!(
+            parse(temp_dir.path().join("repo/../file").to_str().unwrap()),
+            Err(UiPathParseError::EscapesWorkspace { normalized, .. })
+                if *normalized == *temp_dir.path().join("file")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parse_fs_path_strict_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = new_temp_dir();
+        let wc_path = temp_dir.path().join("repo");
+        std::fs::create_dir_all(wc_path.join("dir")).unwrap();
+        let parse = |cwd: &Path, wc_path: &Path, input: &str| {
+            RepoPathBuf::parse_fs_path_strict(cwd, wc_path, input)
+        };
+
+        // cwd reached through a symlink to a directory in the workspace
+        let dir_link_path = temp_dir.path().join("dir-link");
+        symlink(wc_path.join("dir"), &dir_link_path).unwrap();
+        assert_eq!(
+            parse(&dir_link_path, &wc_path, "file").as_deref().ok(),
+            Some(repo_path("dir/file"))
+        );
+        // ".." is resolved lexically, so it refers to the parent of the link
+        assert_matches!(
+            parse(&dir_link_path, &wc_path, "../file"),
+            Err(UiPathParseError::EscapesWorkspace { .. })
+        );
+
+        // Workspace root reached through a symlink
+        let wc_link_path = temp_dir.path().join("repo-link");
+        symlink(&wc_path, &wc_link_path).unwrap();
+        assert_eq!(
+            parse(&wc_path.join("dir"), &wc_link_path, "file")
+                .as_deref()
+                .ok(),
+            Some(repo_path("dir/file"))
+        );
+        assert_matches!(
+            parse(&wc_path, &wc_link_path, "../file"),
+            Err(UiPathParseError::EscapesWorkspace { .. })
+        );
+
+        // Symlink pointing to itself
+        let loop_path = temp_dir.path().join("loop");
+        symlink(&loop_path, &loop_path).unwrap();
+        assert_matches!(
+            parse(&loop_path, &wc_path, "file"),
+            Err(UiPathParseError::SymlinkLoop { .. })
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parse_fs_path_strict_non_utf8() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt as _;
+
+        let temp_dir = new_temp_dir();
+        let wc_path = temp_dir.path().join("repo");
+        let input = Path::new(OsStr::from_bytes(b"file\xff"));
+        assert_matches!(
+            RepoPathBuf::parse_fs_path_strict(&wc_path, &wc_path, input),
+            Err(UiPathParseError::NonUtf8 { .. })
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn parse_fs_path_strict_drives() {
+        let wc_path = Path::new(r"C:\repo");
+        let cwd_path = Path::new(r"C:\repo\dir");
+        let parse = |input: &str| RepoPathBuf::parse_fs_path_strict(cwd_path, wc_path, input);
+
+        assert_eq!(
+            parse(r"C:\repo\file").as_deref().ok(),
+            Some(repo_path("file"))
+        );
+        // Drive letters are case-insensitive
+        assert_eq!(
+            parse(r"c:\repo\file").as_deref().ok(),
+            Some(repo_path("file"))
+        );
+        // Drive-relative path is relative to the cwd
+        assert_eq!(
+            parse(r"C:file").as_deref().ok(),
+            Some(repo_path("dir/file"))
+        );
+        assert_matches!(
+            parse(r"C:\other\file"),
+            Err(UiPathParseError::EscapesWorkspace { .. })
+        );
+        assert_matches!(
+            parse(r"D:\repo\file"),
+            Err(UiPathParseError::DriveMismatch { .. })
+        );
+        assert_matches!(
+            parse(r"D:file"),
+            Err(UiPathParseError::DriveMismatch { .. })
+        );
+    }
+
     #[test]
     fn test_format_copied_path() {
         let ui = RepoPathUiConverter::Fs {
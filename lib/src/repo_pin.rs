@@ -0,0 +1,229 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pins that keep a repo snapshot alive for long-lived readers.
+//!
+//! A process that holds on to a `ReadonlyRepo` for a long time (e.g. a server
+//! or an IDE integration) can't rely on the operation and index files it was
+//! loaded from to exist forever, since `jj util gc` and `jj debug reindex` may
+//! delete them. A pin records the operation id and index segments the reader
+//! depends on in `.jj/repo/pins/`, and the garbage collection paths skip
+//! anything that is pinned.
+//!
+//! Pins are removed when dropped. Pins left behind by crashed processes are
+//! ignored and deleted once they are older than [`PIN_EXPIRY`], so
+//! long-running readers should call [`RepoPin::renew()`] periodically.
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use thiserror::Error;
+
+use crate::backend::MillisSinceEpoch;
+use crate::backend::Timestamp;
+use crate::file_util::persist_atomically_with;
+use crate::file_util::Durability;
+use crate::file_util::IoResultExt as _;
+use crate::file_util::PathError;
+use crate::object_id::ObjectId as _;
+use crate::op_store::OperationId;
+
+/// Name of the directory in the repo directory that pins are stored in.
+pub const PINS_DIR_NAME: &str = "pins";
+
+/// Pins that haven't been created or renewed within this duration are
+/// considered stale and are deleted.
+pub const PIN_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Error that may occur while creating or reading pins.
+#[derive(Debug, Error)]
+pub enum RepoPinError {
+    /// The repo wasn't loaded from the file system.
+    #[error("Repo was not loaded from a directory that can hold pins")]
+    Unsupported,
+    /// The pin file couldn't be parsed.
+    #[error("Invalid pin file {path}")]
+    InvalidPin {
+        /// Path to the pin file.
+        path: PathBuf,
+        /// Parse error.
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The pin file doesn't contain a valid operation id.
+    #[error("Invalid operation id in pin file {0}")]
+    InvalidOperationId(PathBuf),
+    /// I/O error.
+    #[error(transparent)]
+    Path(#[from] PathError),
+}
+
+/// Snapshot recorded in a pin file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepoPinInfo {
+    /// Operation the reader is looking at.
+    pub operation_id: OperationId,
+    /// Names of the index segment files the reader has loaded.
+    pub index_segments: Vec<String>,
+    /// Process that created the pin.
+    pub pid: u32,
+    /// Time the pin was created.
+    pub created: MillisSinceEpoch,
+}
+
+#[derive(serde::Deserialize, serde::Serialize)]
+struct RepoPinData {
+    operation_id: String,
+    index_segments: Vec<String>,
+    pid: u32,
+    created_millis: i64,
+}
+
+/// Manages the pin files in `.jj/repo/pins/`.
+#[derive(Clone, Debug)]
+pub struct RepoPinStore {
+    dir: PathBuf,
+}
+
+impl RepoPinStore {
+    /// Returns the pin store of the repo at `repo_path`. The directory is
+    /// created lazily when the first pin is written.
+    pub fn for_repo(repo_path: &Path) -> Self {
+        RepoPinStore {
+            dir: repo_path.join(PINS_DIR_NAME),
+        }
+    }
+
+    /// Directory the pin files are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes a new pin file for the given snapshot.
+    pub fn create(
+        &self,
+        operation_id: &OperationId,
+        index_segments: Vec<String>,
+    ) -> Result<RepoPin, RepoPinError> {
+        fs::create_dir_all(&self.dir).context(&self.dir)?;
+        let pid = process::id();
+        let created = Timestamp::now().timestamp;
+        let data = RepoPinData {
+            operation_id: operation_id.hex(),
+            index_segments,
+            pid,
+            created_millis: created.0,
+        };
+        let name = format!("{pid}-{:016x}", rand::random::<u64>());
+        let path = persist_atomically_with(&self.dir, &name, Durability::Buffered, |writer| {
+            serde_json::to_writer(writer, &data).map_err(io::Error::other)
+        })?;
+        Ok(RepoPin { path })
+    }
+
+    /// Returns the pins that haven't expired yet. Expired pins are deleted.
+    pub fn active_pins(&self) -> Result<Vec<RepoPinInfo>, RepoPinError> {
+        let entries = match self.dir.read_dir() {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => Err(err).context(&self.dir)?,
+        };
+        let keep_newer = SystemTime::now()
+            .checked_sub(PIN_EXPIRY)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        let mut pins = vec![];
+        for entry in entries {
+            let entry = entry.context(&self.dir)?;
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                // Skip temporary files
+                continue;
+            }
+            let mtime = match entry.metadata() {
+                Ok(metadata) => metadata.modified().expect("unsupported platform?"),
+                // The pin was dropped concurrently
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => Err(err).context(&path)?,
+            };
+            if mtime < keep_newer {
+                tracing::info!(?path, "removing stale pin");
+                match fs::remove_file(&path) {
+                    Ok(()) => {}
+                    Err(err) if err.kind() == ErrorKind::NotFound => {}
+                    Err(err) => Err(err).context(&path)?,
+                }
+                continue;
+            }
+            let content = match fs::read(&path) {
+                Ok(content) => content,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => Err(err).context(&path)?,
+            };
+            let data: RepoPinData =
+                serde_json::from_slice(&content).map_err(|source| RepoPinError::InvalidPin {
+                    path: path.clone(),
+                    source,
+                })?;
+            let operation_id = OperationId::try_from_hex(&data.operation_id)
+                .map_err(|_| RepoPinError::InvalidOperationId(path))?;
+            pins.push(RepoPinInfo {
+                operation_id,
+                index_segments: data.index_segments,
+                pid: data.pid,
+                created: MillisSinceEpoch(data.created_millis),
+            });
+        }
+        Ok(pins)
+    }
+}
+
+/// Keeps the operation and index segments of a repo snapshot from being
+/// garbage-collected. The pin file is removed when this is dropped.
+#[derive(Debug)]
+pub struct RepoPin {
+    path: PathBuf,
+}
+
+impl RepoPin {
+    /// Path to the pin file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Updates the pin's timestamp so it doesn't expire.
+    pub fn renew(&self) -> Result<(), PathError> {
+        let file = File::options()
+            .write(true)
+            .open(&self.path)
+            .context(&self.path)?;
+        file.set_modified(SystemTime::now()).context(&self.path)
+    }
+}
+
+impl Drop for RepoPin {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            if err.kind() != ErrorKind::NotFound {
+                tracing::warn!(?err, path = ?self.path, "failed to remove pin");
+            }
+        }
+    }
+}
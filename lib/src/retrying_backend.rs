@@ -0,0 +1,392 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Provides a backend wrapper that retries failed requests.
+
+use std::any::Any;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use futures::stream::BoxStream;
+use serde::Deserialize;
+use tokio::io::AsyncRead;
+use tokio::io::AsyncReadExt as _;
+
+use crate::backend::Backend;
+use crate::backend::BackendError;
+use crate::backend::BackendResult;
+use crate::backend::ChangeId;
+use crate::backend::Commit;
+use crate::backend::CommitId;
+use crate::backend::Conflict;
+use crate::backend::ConflictId;
+use crate::backend::CopyHistory;
+use crate::backend::CopyId;
+use crate::backend::CopyRecord;
+use crate::backend::FileId;
+use crate::backend::SigningFn;
+use crate::backend::StorageStats;
+use crate::backend::SymlinkId;
+use crate::backend::Tree;
+use crate::backend::TreeId;
+use crate::config::ConfigGetError;
+use crate::index::Index;
+use crate::repo_path::RepoPath;
+use crate::repo_path::RepoPathBuf;
+use crate::settings::UserSettings;
+
+/// Kind of backend error that [`RetryingBackend`] may retry.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetryableErrorKind {
+    /// Errors the backend reported as transient. See
+    /// [`BackendError::is_transient()`].
+    Transient,
+    /// Errors from reading an object.
+    Read,
+    /// Errors from writing an object.
+    Write,
+}
+
+impl RetryableErrorKind {
+    fn matches(self, err: &BackendError) -> bool {
+        match self {
+            RetryableErrorKind::Transient => err.is_transient(),
+            RetryableErrorKind::Read => matches!(
+                err,
+                BackendError::ReadObject { .. } | BackendError::ReadFile { .. }
+            ),
+            RetryableErrorKind::Write => matches!(err, BackendError::WriteObject { .. }),
+        }
+    }
+}
+
+/// Determines which failed backend requests are retried, and how.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts per request, including the first one.
+    pub max_attempts: usize,
+    /// Delay before the first retry. The delay is doubled for each subsequent
+    /// retry.
+    pub initial_backoff: Duration,
+    /// Kinds of errors that are retried. Other errors are returned
+    /// immediately.
+    pub retryable_errors: Vec<RetryableErrorKind>,
+}
+
+impl RetryPolicy {
+    /// Loads the policy from the `backend.retry` settings.
+    pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
+        let max_attempts = settings.backend_retry_max_attempts()?;
+        let initial_backoff_ms = settings.backend_retry_initial_backoff_ms()?;
+        Ok(RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            initial_backoff: Duration::from_millis(initial_backoff_ms),
+            retryable_errors: settings.backend_retry_errors()?,
+        })
+    }
+
+    /// Returns true if requests may be attempted more than once.
+    pub fn is_enabled(&self) -> bool {
+        self.max_attempts > 1 && !self.retryable_errors.is_empty()
+    }
+
+    /// Returns true if a request that failed with `err` may be retried.
+    pub fn is_retryable(&self, err: &BackendError) -> bool {
+        self.retryable_errors.iter().any(|kind| kind.matches(err))
+    }
+
+    /// Returns the delay before retrying a request that failed `attempt`
+    /// times.
+    fn backoff(&self, attempt: usize) -> Duration {
+        let exponent = u32::try_from(attempt - 1).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(exponent))
+    }
+}
+
+/// Evaluates `$call` until it succeeds, fails with an error that isn't
+/// retryable, or runs out of attempts.
+macro_rules! retry {
+    ($backend:expr, $call:expr) => {{
+        let mut attempt = 1;
+        loop {
+            match $call {
+                Err(err) if $backend.should_retry(&err, attempt) => attempt += 1,
+                result => break result,
+            }
+        }
+    }};
+}
+
+/// A commit backend that forwards requests to another backend and retries
+/// them if they fail according to its [`RetryPolicy`].
+///
+/// Since objects are content-addressed, retrying a write that may have
+/// partially succeeded is expected to be harmless.
+#[derive(Debug)]
+pub struct RetryingBackend {
+    inner: Box<dyn Backend>,
+    policy: RetryPolicy,
+    retry_count: AtomicUsize,
+}
+
+impl RetryingBackend {
+    /// Wraps `inner` so its failed requests are retried according to
+    /// `policy`.
+    pub fn new(inner: Box<dyn Backend>, policy: RetryPolicy) -> Self {
+        RetryingBackend {
+            inner,
+            policy,
+            retry_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// The wrapped backend.
+    pub fn inner(&self) -> &dyn Backend {
+        self.inner.as_ref()
+    }
+
+    /// The policy failed requests are retried by.
+    pub fn policy(&self) -> &RetryPolicy {
+        &self.policy
+    }
+
+    /// Number of times a failed request has been retried so far.
+    pub fn retry_count(&self) -> usize {
+        self.retry_count.load(Ordering::Relaxed)
+    }
+
+    fn should_retry(&self, err: &BackendError, attempt: usize) -> bool {
+        if attempt >= self.policy.max_attempts || !self.policy.is_retryable(err) {
+            return false;
+        }
+        let backoff = self.policy.backoff(attempt);
+        tracing::debug!(?err, attempt, ?backoff, "retrying failed backend request");
+        self.retry_count.fetch_add(1, Ordering::Relaxed);
+        thread::sleep(backoff);
+        true
+    }
+}
+
+#[async_trait]
+impl Backend for RetryingBackend {
+    // Forward to the wrapped backend so callers can still downcast to it.
+    fn as_any(&self) -> &dyn Any {
+        self.inner.as_any()
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn commit_id_length(&self) -> usize {
+        self.inner.commit_id_length()
+    }
+
+    fn change_id_length(&self) -> usize {
+        self.inner.change_id_length()
+    }
+
+    fn root_commit_id(&self) -> &CommitId {
+        self.inner.root_commit_id()
+    }
+
+    fn root_change_id(&self) -> &ChangeId {
+        self.inner.root_change_id()
+    }
+
+    fn empty_tree_id(&self) -> &TreeId {
+        self.inner.empty_tree_id()
+    }
+
+    fn concurrency(&self) -> usize {
+        self.inner.concurrency()
+    }
+
+    async fn read_file(
+        &self,
+        path: &RepoPath,
+        id: &FileId,
+    ) -> BackendResult<Pin<Box<dyn AsyncRead>>> {
+        retry!(self, self.inner.read_file(path, id).await)
+    }
+
+    async fn prefetch_files(&self, files: &[(RepoPathBuf, FileId)]) -> BackendResult<()> {
+        retry!(self, self.inner.prefetch_files(files).await)
+    }
+
+    async fn write_file(
+        &self,
+        path: &RepoPath,
+        contents: &mut (dyn AsyncRead + Send + Unpin),
+    ) -> BackendResult<FileId> {
+        // The contents stream can only be consumed once, so buffer it in case
+        // the write needs to be retried.
+        let mut bytes = Vec::new();
+        contents
+            .read_to_end(&mut bytes)
+            .await
+            .map_err(|err| BackendError::WriteObject {
+                object_type: "file",
+                source: err.into(),
+            })?;
+        retry!(
+            self,
+            self.inner.write_file(path, &mut bytes.as_slice()).await
+        )
+    }
+
+    async fn read_symlink(&self, path: &RepoPath, id: &SymlinkId) -> BackendResult<String> {
+        retry!(self, self.inner.read_symlink(path, id).await)
+    }
+
+    async fn write_symlink(&self, path: &RepoPath, target: &str) -> BackendResult<SymlinkId> {
+        retry!(self, self.inner.write_symlink(path, target).await)
+    }
+
+    async fn read_copy(&self, id: &CopyId) -> BackendResult<CopyHistory> {
+        retry!(self, self.inner.read_copy(id).await)
+    }
+
+    async fn write_copy(&self, copy: &CopyHistory) -> BackendResult<CopyId> {
+        retry!(self, self.inner.write_copy(copy).await)
+    }
+
+    async fn get_related_copies(&self, copy_id: &CopyId) -> BackendResult<Vec<CopyHistory>> {
+        retry!(self, self.inner.get_related_copies(copy_id).await)
+    }
+
+    async fn read_tree(&self, path: &RepoPath, id: &TreeId) -> BackendResult<Tree> {
+        retry!(self, self.inner.read_tree(path, id).await)
+    }
+
+    async fn write_tree(&self, path: &RepoPath, contents: &Tree) -> BackendResult<TreeId> {
+        retry!(self, self.inner.write_tree(path, contents).await)
+    }
+
+    fn read_conflict(&self, path: &RepoPath, id: &ConflictId) -> BackendResult<Conflict> {
+        retry!(self, self.inner.read_conflict(path, id))
+    }
+
+    fn write_conflict(&self, path: &RepoPath, contents: &Conflict) -> BackendResult<ConflictId> {
+        retry!(self, self.inner.write_conflict(path, contents))
+    }
+
+    async fn read_commit(&self, id: &CommitId) -> BackendResult<Commit> {
+        retry!(self, self.inner.read_commit(id).await)
+    }
+
+    async fn write_commit(
+        &self,
+        contents: Commit,
+        mut sign_with: Option<&mut SigningFn>,
+    ) -> BackendResult<(CommitId, Commit)> {
+        retry!(
+            self,
+            self.inner
+                .write_commit(contents.clone(), sign_with.as_deref_mut())
+                .await
+        )
+    }
+
+    fn compute_commit_id(&self, contents: &mut Commit) -> Option<CommitId> {
+        self.inner.compute_commit_id(contents)
+    }
+
+    async fn write_commits_batch(&self, commits: Vec<(CommitId, Commit)>) -> BackendResult<()> {
+        retry!(self, self.inner.write_commits_batch(commits.clone()).await)
+    }
+
+    // Only the request for the stream is retried. Errors reported by the
+    // stream itself are passed through.
+    fn get_copy_records(
+        &self,
+        paths: Option<&[RepoPathBuf]>,
+        root: &CommitId,
+        head: &CommitId,
+    ) -> BackendResult<BoxStream<BackendResult<CopyRecord>>> {
+        retry!(self, self.inner.get_copy_records(paths, root, head))
+    }
+
+    fn write_copy_records(&self, records: &[CopyRecord]) -> BackendResult<()> {
+        retry!(self, self.inner.write_copy_records(records))
+    }
+
+    fn storage_stats(&self) -> BackendResult<Option<StorageStats>> {
+        retry!(self, self.inner.storage_stats())
+    }
+
+    fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
+        retry!(self, self.inner.gc(index, keep_newer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(max_attempts: usize) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(10),
+            retryable_errors: vec![RetryableErrorKind::Transient],
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_backoff() {
+        let policy = policy(5);
+        assert_eq!(policy.backoff(1), Duration::from_millis(10));
+        assert_eq!(policy.backoff(2), Duration::from_millis(20));
+        assert_eq!(policy.backoff(4), Duration::from_millis(80));
+    }
+
+    #[test]
+    fn test_retry_policy_is_retryable() {
+        let policy = policy(5);
+        assert!(policy.is_retryable(&BackendError::Transient("timeout".into())));
+        assert!(!policy.is_retryable(&BackendError::Other("boom".into())));
+        assert!(!policy.is_retryable(&BackendError::WriteObject {
+            object_type: "file",
+            source: "boom".into(),
+        }));
+
+        let policy = RetryPolicy {
+            retryable_errors: vec![RetryableErrorKind::Write],
+            ..policy
+        };
+        assert!(!policy.is_retryable(&BackendError::Transient("timeout".into())));
+        assert!(policy.is_retryable(&BackendError::WriteObject {
+            object_type: "file",
+            source: "boom".into(),
+        }));
+    }
+
+    #[test]
+    fn test_retry_policy_is_enabled() {
+        assert!(!policy(1).is_enabled());
+        assert!(policy(2).is_enabled());
+        let policy = RetryPolicy {
+            retryable_errors: vec![],
+            ..policy(2)
+        };
+        assert!(!policy.is_enabled());
+    }
+}
@@ -21,7 +21,10 @@ use std::convert::Infallible;
 use std::fmt;
 use std::ops::Range;
 use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Instant;
 
 use itertools::Itertools as _;
 use once_cell::sync::Lazy;
@@ -34,11 +37,13 @@ use crate::commit::Commit;
 use crate::dsl_util;
 use crate::dsl_util::collect_similar;
 use crate::dsl_util::AliasExpandError as _;
+use crate::dsl_util::AliasId;
 use crate::fileset;
 use crate::fileset::FilesetDiagnostics;
 use crate::fileset::FilesetExpression;
 use crate::graph::GraphNode;
 use crate::hex_util::to_forward_hex;
+use crate::hex_util::trim_user_id_input;
 use crate::id_prefix::IdPrefixContext;
 use crate::id_prefix::IdPrefixIndex;
 use crate::object_id::HexPrefix;
@@ -61,6 +66,7 @@ pub use crate::revset_parser::BinaryOp;
 pub use crate::revset_parser::ExpressionKind;
 pub use crate::revset_parser::ExpressionNode;
 pub use crate::revset_parser::FunctionCallNode;
+pub use crate::revset_parser::RevsetAliasParser;
 pub use crate::revset_parser::RevsetAliasesMap;
 pub use crate::revset_parser::RevsetDiagnostics;
 pub use crate::revset_parser::RevsetParseError;
@@ -98,6 +104,8 @@ pub enum RevsetResolutionError {
 pub enum RevsetEvaluationError {
     #[error("Unexpected error from commit backend")]
     Backend(#[from] BackendError),
+    #[error("Revset evaluation was interrupted")]
+    Interrupted,
     #[error(transparent)]
     Other(Box<dyn std::error::Error + Send + Sync>),
 }
@@ -108,11 +116,43 @@ impl RevsetEvaluationError {
     pub fn into_backend_error(self) -> BackendError {
         match self {
             Self::Backend(err) => err,
+            err @ Self::Interrupted => BackendError::Other(err.into()),
             Self::Other(err) => BackendError::Other(err),
         }
     }
 }
 
+/// Allows a long-running revset evaluation to be stopped.
+///
+/// The index checks the context at regular intervals while it iterates over
+/// commits and tests filter predicates, and fails with
+/// [`RevsetEvaluationError::Interrupted`] once the `interrupt` flag is set or
+/// the `deadline` has passed. The context is also checked when the evaluated
+/// revset is iterated later.
+#[derive(Clone, Debug, Default)]
+pub struct RevsetEvaluationContext {
+    /// Flag that can be set from another thread (or a signal handler) to
+    /// interrupt the evaluation.
+    pub interrupt: Arc<AtomicBool>,
+    /// Time after which the evaluation is interrupted.
+    pub deadline: Option<Instant>,
+}
+
+impl RevsetEvaluationContext {
+    /// Returns [`RevsetEvaluationError::Interrupted`] if the evaluation should
+    /// stop.
+    pub fn check(&self) -> Result<(), RevsetEvaluationError> {
+        let expired = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        if self.interrupt.load(Ordering::Relaxed) || expired {
+            Err(RevsetEvaluationError::Interrupted)
+        } else {
+            Ok(())
+        }
+    }
+}
+
 // assumes index has less than u64::MAX entries.
 pub const GENERATION_RANGE_FULL: Range<u64> = 0..u64::MAX;
 pub const GENERATION_RANGE_EMPTY: Range<u64> = 0..0;
@@ -267,6 +307,12 @@ pub enum RevsetExpression<St: ExpressionState> {
         count: usize,
     },
     Filter(RevsetFilterPredicate),
+    /// Commits whose tree differs from the tree of the single `base` commit in
+    /// the paths specified by `files`. Should be intersected as filter.
+    ChangesAgainst {
+        base: Rc<Self>,
+        files: FilesetExpression,
+    },
     /// Marker for subtree that should be intersected as filter.
     AsFilter(Rc<Self>),
     /// Resolves symbols and visibility at the specified operation.
@@ -445,6 +491,15 @@ impl<St: ExpressionState> RevsetExpression<St> {
         Rc::new(Self::ForkPoint(self.clone()))
     }
 
+    /// Commits whose tree differs from the tree of `self` in the paths
+    /// specified by `files`. `self` must evaluate to a single commit.
+    pub fn changes_against(self: &Rc<Self>, files: FilesetExpression) -> Rc<Self> {
+        Rc::new(Self::AsFilter(Rc::new(Self::ChangesAgainst {
+            base: self.clone(),
+            files,
+        })))
+    }
+
     /// Filter all commits by `predicate` in `self`.
     pub fn filtered(self: &Rc<Self>, predicate: RevsetFilterPredicate) -> Rc<Self> {
         self.intersection(&Self::filter(predicate))
@@ -567,6 +622,17 @@ impl ResolvedRevsetExpression {
         optimize(self).evaluate_unoptimized(repo)
     }
 
+    /// Optimizes and evaluates this expression. The evaluation, and the
+    /// iteration over the returned revset, can be stopped by the `context`.
+    pub fn evaluate_with_context<'index>(
+        self: Rc<Self>,
+        repo: &'index dyn Repo,
+        context: &RevsetEvaluationContext,
+    ) -> Result<Box<dyn Revset + 'index>, RevsetEvaluationError> {
+        let expr = optimize(self).to_backend_expression(repo);
+        repo.index().evaluate_revset(&expr, repo.store(), context)
+    }
+
     /// Evaluates this expression without optimizing it.
     ///
     /// Use this function if `self` is already optimized, or to debug
@@ -576,7 +642,8 @@ impl ResolvedRevsetExpression {
         repo: &'index dyn Repo,
     ) -> Result<Box<dyn Revset + 'index>, RevsetEvaluationError> {
         let expr = self.to_backend_expression(repo);
-        repo.index().evaluate_revset(&expr, repo.store())
+        let context = RevsetEvaluationContext::default();
+        repo.index().evaluate_revset(&expr, repo.store(), &context)
     }
 
     /// Transforms this expression to the form which the `Index` backend will
@@ -590,6 +657,12 @@ impl ResolvedRevsetExpression {
 pub enum ResolvedPredicateExpression {
     /// Pure filter predicate.
     Filter(RevsetFilterPredicate),
+    /// Commits whose tree differs from the tree of the single `base` commit in
+    /// the paths specified by `files`.
+    ChangesAgainst {
+        base: Box<ResolvedExpression>,
+        files: FilesetExpression,
+    },
     /// Set expression to be evaluated as filter. This is typically a subtree
     /// node of `Union` with a pure filter predicate.
     Set(Box<ResolvedExpression>),
@@ -656,6 +729,22 @@ pub type RevsetFunction = fn(
     &LoweringContext,
 ) -> Result<Rc<UserRevsetExpression>, RevsetParseError>;
 
+/// Resolver of a revset function registered at runtime by
+/// [`RevsetExtensions::add_function()`].
+///
+/// It's called with the positional arguments of the function call, which have
+/// already been checked against the registered arity.
+pub type RevsetFunctionResolver = dyn Fn(
+    &mut RevsetDiagnostics,
+    &[ExpressionNode],
+    &LoweringContext,
+) -> Result<Rc<UserRevsetExpression>, RevsetParseError>;
+
+struct CustomRevsetFunction {
+    arity: usize,
+    resolver: Box<RevsetFunctionResolver>,
+}
+
 static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy::new(|| {
     // Not using maplit::hashmap!{} or custom declarative macro here because
     // code completion inside macro is quite restricted.
@@ -891,6 +980,18 @@ static BUILTIN_FUNCTION_MAP: Lazy<HashMap<&'static str, RevsetFunction>> = Lazy:
         let expr = expect_fileset_expression(diagnostics, arg, ctx.path_converter)?;
         Ok(RevsetExpression::filter(RevsetFilterPredicate::File(expr)))
     });
+    map.insert("changes_against", |diagnostics, function, context| {
+        let [base_arg, files_arg] = function.expect_exact_arguments()?;
+        let base = lower_expression(diagnostics, base_arg, context)?;
+        let ctx = context.workspace.as_ref().ok_or_else(|| {
+            RevsetParseError::with_span(
+                RevsetParseErrorKind::FsPathWithoutWorkspace,
+                files_arg.span,
+            )
+        })?;
+        let files = expect_fileset_expression(diagnostics, files_arg, ctx.path_converter)?;
+        Ok(base.changes_against(files))
+    });
     map.insert("diff_contains", |diagnostics, function, context| {
         let ([text_arg], [files_opt_arg]) = function.expect_arguments()?;
         let text = expect_string_pattern(diagnostics, text_arg)?;
@@ -1030,13 +1131,21 @@ fn lower_function_call(
     context: &LoweringContext,
 ) -> Result<Rc<UserRevsetExpression>, RevsetParseError> {
     let function_map = &context.extensions.function_map;
+    let custom_functions = &context.extensions.custom_functions;
     if let Some(func) = function_map.get(function.name) {
         func(diagnostics, function, context)
+    } else if let Some(custom) = custom_functions.get(function.name) {
+        let args = function.expect_arity(custom.arity)?;
+        (custom.resolver)(diagnostics, args, context)
     } else {
+        let names = itertools::chain(
+            function_map.keys().copied(),
+            custom_functions.keys().map(|name| name.as_str()),
+        );
         Err(RevsetParseError::with_span(
             RevsetParseErrorKind::NoSuchFunction {
                 name: function.name.to_owned(),
-                candidates: collect_similar(function.name, function_map.keys()),
+                candidates: collect_similar(function.name, names),
             },
             function.name_span,
         ))
@@ -1164,6 +1273,35 @@ pub fn parse_with_modifier(
     .map_err(|err| err.extend_function_candidates(context.aliases_map.function_names()))
 }
 
+/// Expands all aliases in `context.aliases_map`, and returns the errors found
+/// in their definitions.
+///
+/// Symbol aliases and function aliases without parameters are also lowered to
+/// detect unknown functions and invalid arguments. Function aliases with
+/// parameters are only expanded since their arguments are unknown.
+pub fn check_aliases<'a>(context: &'a RevsetParseContext) -> Vec<(AliasId<'a>, RevsetParseError)> {
+    let lowering_context = context.to_lowering_context();
+    dsl_util::expand_alias_definitions(context.aliases_map)
+        .into_iter()
+        .filter_map(|(id, result)| {
+            let node = match result {
+                Ok(node) => node,
+                Err(err) => return Some((id, err)),
+            };
+            let (AliasId::Symbol(_) | AliasId::Function(_, [])) = id else {
+                return None;
+            };
+            let mut diagnostics = RevsetDiagnostics::new();
+            lower_expression(&mut diagnostics, &node, &lowering_context)
+                .err()
+                .map(|err| {
+                    let err = err.extend_function_candidates(context.aliases_map.function_names());
+                    (id, err)
+                })
+        })
+        .collect()
+}
+
 /// `Some` for rewritten expression, or `None` to reuse the original expression.
 type TransformedExpression<St> = Option<Rc<RevsetExpression<St>>>;
 
@@ -1253,6 +1391,11 @@ fn try_transform_expression<St: ExpressionState, E>(
                     count: *count,
                 }),
             RevsetExpression::Filter(_) => None,
+            RevsetExpression::ChangesAgainst { base, files } => transform_rec(base, pre, post)?
+                .map(|base| RevsetExpression::ChangesAgainst {
+                    base,
+                    files: files.clone(),
+                }),
             RevsetExpression::AsFilter(candidates) => {
                 transform_rec(candidates, pre, post)?.map(RevsetExpression::AsFilter)
             }
@@ -1447,6 +1590,11 @@ where
             RevsetExpression::Latest { candidates, count }.into()
         }
         RevsetExpression::Filter(predicate) => RevsetExpression::Filter(predicate.clone()).into(),
+        RevsetExpression::ChangesAgainst { base, files } => {
+            let base = folder.fold_expression(base)?;
+            let files = files.clone();
+            RevsetExpression::ChangesAgainst { base, files }.into()
+        }
         RevsetExpression::AsFilter(candidates) => {
             let candidates = folder.fold_expression(candidates)?;
             RevsetExpression::AsFilter(candidates).into()
@@ -1961,7 +2109,7 @@ impl PartialSymbolResolver for CommitPrefixResolver<'_> {
         repo: &dyn Repo,
         symbol: &str,
     ) -> Result<Option<Vec<CommitId>>, RevsetResolutionError> {
-        if let Some(prefix) = HexPrefix::new(symbol) {
+        if let Some(prefix) = HexPrefix::new_lenient(symbol) {
             let index = self
                 .context
                 .map(|ctx| ctx.populate(self.context_repo))
@@ -1992,7 +2140,8 @@ impl PartialSymbolResolver for ChangePrefixResolver<'_> {
         repo: &dyn Repo,
         symbol: &str,
     ) -> Result<Option<Vec<CommitId>>, RevsetResolutionError> {
-        if let Some(prefix) = to_forward_hex(symbol).as_deref().and_then(HexPrefix::new) {
+        let forward_hex = trim_user_id_input(symbol).and_then(to_forward_hex);
+        if let Some(prefix) = forward_hex.as_deref().and_then(HexPrefix::new) {
             let index = self
                 .context
                 .map(|ctx| ctx.populate(self.context_repo))
@@ -2231,7 +2380,7 @@ impl ExpressionStateFolder<UserExpressionState, ResolvedExpressionState>
         let repo = reload_repo_at_operation(self.repo(), operation)?;
         self.repo_stack.push(repo);
         let candidates = self.fold_expression(candidates)?;
-        let visible_heads = self.repo().view().heads().iter().cloned().collect();
+        let visible_heads = self.repo().view().visible_head_ids().cloned().collect();
         self.repo_stack.pop();
         Ok(Rc::new(RevsetExpression::WithinVisibility {
             candidates,
@@ -2263,7 +2412,7 @@ fn resolve_visibility(
     expression: &ResolvedRevsetExpression,
 ) -> ResolvedExpression {
     let context = VisibilityResolutionContext {
-        visible_heads: &repo.view().heads().iter().cloned().collect_vec(),
+        visible_heads: &repo.view().visible_head_ids().cloned().collect_vec(),
         root: repo.store().root_commit_id(),
     };
     context.resolve(expression)
@@ -2327,7 +2476,9 @@ impl VisibilityResolutionContext<'_> {
                 candidates: self.resolve(candidates).into(),
                 count: *count,
             },
-            RevsetExpression::Filter(_) | RevsetExpression::AsFilter(_) => {
+            RevsetExpression::Filter(_)
+            | RevsetExpression::ChangesAgainst { .. }
+            | RevsetExpression::AsFilter(_) => {
                 // Top-level filter without intersection: e.g. "~author(_)" is represented as
                 // `AsFilter(NotIn(Filter(Author(_))))`.
                 ResolvedExpression::FilterWithin {
@@ -2434,6 +2585,12 @@ impl VisibilityResolutionContext<'_> {
             RevsetExpression::Filter(predicate) => {
                 ResolvedPredicateExpression::Filter(predicate.clone())
             }
+            RevsetExpression::ChangesAgainst { base, files } => {
+                ResolvedPredicateExpression::ChangesAgainst {
+                    base: self.resolve(base).into(),
+                    files: files.clone(),
+                }
+            }
             RevsetExpression::AsFilter(candidates) => self.resolve_predicate(candidates),
             RevsetExpression::AtOperation { operation, .. } => match *operation {},
             // Filters should be intersected with all() within the at-op repo.
@@ -2540,6 +2697,7 @@ impl<I: Iterator<Item = Result<CommitId, RevsetEvaluationError>>> Iterator
 pub struct RevsetExtensions {
     symbol_resolvers: Vec<Box<dyn SymbolResolverExtension>>,
     function_map: HashMap<&'static str, RevsetFunction>,
+    custom_functions: HashMap<String, CustomRevsetFunction>,
 }
 
 impl Default for RevsetExtensions {
@@ -2553,6 +2711,7 @@ impl RevsetExtensions {
         Self {
             symbol_resolvers: vec![],
             function_map: BUILTIN_FUNCTION_MAP.clone(),
+            custom_functions: HashMap::new(),
         }
     }
 
@@ -2565,6 +2724,9 @@ impl RevsetExtensions {
     }
 
     pub fn add_custom_function(&mut self, name: &'static str, func: RevsetFunction) {
+        if self.custom_functions.contains_key(name) {
+            panic!("Conflict registering revset function '{name}'");
+        }
         match self.function_map.entry(name) {
             hash_map::Entry::Occupied(_) => {
                 panic!("Conflict registering revset function '{name}'")
@@ -2572,6 +2734,44 @@ impl RevsetExtensions {
             hash_map::Entry::Vacant(v) => v.insert(func),
         };
     }
+
+    /// Registers a function taking exactly `arity` positional arguments.
+    ///
+    /// Unlike [`add_custom_function()`](Self::add_custom_function), the name
+    /// doesn't have to be known at compile time, and the `resolver` may
+    /// capture state. The `resolver` can return any expression, including a
+    /// symbol to be resolved by a [`SymbolResolverExtension`].
+    ///
+    /// User-defined function aliases take precedence over registered
+    /// functions of the same name, regardless of arity, just like they do
+    /// for builtin functions.
+    ///
+    /// Panics if a function of the same name is already registered.
+    pub fn add_function(
+        &mut self,
+        name: impl Into<String>,
+        arity: usize,
+        resolver: impl Fn(
+                &mut RevsetDiagnostics,
+                &[ExpressionNode],
+                &LoweringContext,
+            ) -> Result<Rc<UserRevsetExpression>, RevsetParseError>
+            + 'static,
+    ) {
+        let name = name.into();
+        if self.function_map.contains_key(name.as_str()) {
+            panic!("Conflict registering revset function '{name}'");
+        }
+        match self.custom_functions.entry(name) {
+            hash_map::Entry::Occupied(entry) => {
+                panic!("Conflict registering revset function '{}'", entry.key())
+            }
+            hash_map::Entry::Vacant(entry) => entry.insert(CustomRevsetFunction {
+                arity,
+                resolver: Box::new(resolver),
+            }),
+        };
+    }
 }
 
 /// Information needed to parse revset expression.
@@ -3256,6 +3456,15 @@ mod tests {
         )
         "#);
         insta::assert_debug_snapshot!(parse("signed()").unwrap(), @"Filter(Signed)");
+        assert_matches!(
+            parse_with_workspace("changes_against(foo, bar)", WorkspaceName::DEFAULT)
+                .unwrap()
+                .as_ref(),
+            RevsetExpression::AsFilter(expression)
+                if matches!(expression.as_ref(), RevsetExpression::ChangesAgainst { .. })
+        );
+        assert!(parse_with_workspace("changes_against(foo)", WorkspaceName::DEFAULT).is_err());
+        assert!(parse("changes_against(foo, bar)").is_err());
     }
 
     #[test]
@@ -14,34 +14,52 @@
 
 #![allow(missing_docs)]
 
+use std::collections::hash_map::Entry;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::sync::Arc;
 
 use futures::StreamExt as _;
+use futures::TryStreamExt as _;
 use indexmap::IndexMap;
 use indexmap::IndexSet;
 use itertools::Itertools as _;
 use pollster::FutureExt as _;
+use thiserror::Error;
 use tracing::instrument;
 
 use crate::backend::BackendError;
 use crate::backend::BackendResult;
 use crate::backend::CommitId;
+use crate::backend::CopyRecord;
+use crate::backend::FileId;
 use crate::backend::MergedTreeId;
+use crate::backend::TreeId;
+use crate::backend::TreeValue;
 use crate::commit::Commit;
 use crate::commit::CommitIteratorExt as _;
 use crate::commit_builder::CommitBuilder;
+use crate::copies::CopiesTreeDiffEntry;
+use crate::copies::CopyOperation;
+use crate::copies::CopyRecords;
 use crate::index::Index;
 use crate::index::IndexError;
+use crate::matchers::FilesMatcher;
 use crate::matchers::Matcher;
+use crate::matchers::UnionMatcher;
 use crate::matchers::Visit;
 use crate::merged_tree::MergedTree;
 use crate::merged_tree::MergedTreeBuilder;
 use crate::merged_tree::TreeDiffEntry;
+use crate::metrics;
+use crate::object_id::ObjectId as _;
 use crate::repo::MutableRepo;
 use crate::repo::Repo;
 use crate::repo_path::RepoPath;
+use crate::repo_path::RepoPathBuf;
+use crate::revset::RevsetContainingFn;
+use crate::revset::RevsetEvaluationError;
 use crate::revset::RevsetExpression;
 use crate::revset::RevsetIteratorExt as _;
 use crate::store::Store;
@@ -86,6 +104,36 @@ pub fn merge_commit_trees_no_resolve_without_repo(
     }
 }
 
+/// Returns the merge commits in `commits` whose tree differs from the merge of
+/// their parents.
+///
+/// This is meant to be called on reparented commits. If only some parents of
+/// a merge commit were rewritten, its preserved content may no longer be a
+/// merge of the new parents, so the merge would appear to introduce changes
+/// of its own. Note that merge commits which already had changes of their own
+/// are reported as well. The merged parent trees are computed once per set of
+/// parents.
+pub fn find_diverged_merges<'a>(
+    repo: &dyn Repo,
+    commits: impl IntoIterator<Item = &'a Commit>,
+) -> BackendResult<Vec<Commit>> {
+    let mut merged_tree_ids: HashMap<Vec<CommitId>, MergedTreeId> = HashMap::new();
+    let mut diverged = vec![];
+    for commit in commits {
+        if commit.parent_ids().len() < 2 {
+            continue;
+        }
+        let merged_tree_id = match merged_tree_ids.entry(commit.parent_ids().to_vec()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(commit.parent_tree(repo)?.id()),
+        };
+        if commit.tree_id() != merged_tree_id {
+            diverged.push(commit.clone());
+        }
+    }
+    Ok(diverged)
+}
+
 /// Restore matching paths from the source into the destination.
 pub fn restore_tree(
     source: &MergedTree,
@@ -100,7 +148,6 @@ pub fn restore_tree(
         // matches an entire subtree.
         let mut tree_builder = MergedTreeBuilder::new(destination.id().clone());
         async {
-            // TODO: handle copy tracking
             let mut diff_stream = source.diff_stream(destination, matcher);
             while let Some(TreeDiffEntry {
                 path: repo_path,
@@ -117,6 +164,184 @@ pub fn restore_tree(
     }
 }
 
+/// Restore matching paths from the source into the destination, pairing up
+/// renamed files.
+///
+/// The `copy_records` should map paths in the `source` tree to paths in the
+/// `destination` tree. A renamed file is restored if the `matcher` matches
+/// either of its paths, and the destination path gets the content of the
+/// source path. Other paths are restored as by [`restore_tree()`].
+pub fn restore_tree_with_copies(
+    source: &MergedTree,
+    destination: &MergedTree,
+    matcher: &dyn Matcher,
+    copy_records: &CopyRecords,
+) -> BackendResult<MergedTreeId> {
+    if copy_records.iter().next().is_none() {
+        return restore_tree(source, destination, matcher);
+    }
+    // The matcher is applied after pairing up the renamed paths, so the diff
+    // has to include both sides of the renames.
+    let copied_paths = FilesMatcher::new(
+        copy_records
+            .iter()
+            .flat_map(|record| [&record.source, &record.target]),
+    );
+    let diff_matcher = UnionMatcher::new(matcher, &copied_paths);
+    let mut tree_builder = MergedTreeBuilder::new(destination.id().clone());
+    async {
+        let mut diff_stream =
+            source.diff_stream_with_copies(destination, &diff_matcher, copy_records);
+        while let Some(CopiesTreeDiffEntry { path, values }) = diff_stream.next().await {
+            let (source_value, _destination_value) = values?;
+            match path.copy_operation() {
+                Some(CopyOperation::Rename) => {
+                    if matcher.matches(path.source()) || matcher.matches(path.target()) {
+                        tree_builder.set_or_remove(path.target, source_value);
+                    }
+                }
+                Some(CopyOperation::Copy) => {
+                    // The copy doesn't exist in the source tree, so restoring
+                    // it is the same as without copy tracking.
+                    if matcher.matches(path.target()) {
+                        let source_value = source.path_value(path.target())?;
+                        tree_builder.set_or_remove(path.target, source_value);
+                    }
+                }
+                None => {
+                    if matcher.matches(path.target()) {
+                        tree_builder.set_or_remove(path.target, source_value);
+                    }
+                }
+            }
+        }
+        Ok::<(), BackendError>(())
+    }
+    .block_on()?;
+    tree_builder.write_tree(destination.store())
+}
+
+/// Restores the given paths from the source into the destination.
+///
+/// A directory path is restored as a whole, which is the same as restoring
+/// a path prefix by [`restore_tree()`]. Unlike [`restore_tree()`], the trees
+/// aren't diffed, so only the trees on the way to the paths are read.
+pub fn restore_tree_paths(
+    source: &MergedTree,
+    destination: &MergedTree,
+    paths: &[RepoPathBuf],
+) -> BackendResult<MergedTreeId> {
+    if paths.iter().any(|path| path.is_root()) {
+        return Ok(source.id());
+    }
+    let mut tree_builder = MergedTreeBuilder::new(destination.id().clone());
+    // Paths below a restored directory are restored along with it. They are
+    // sorted right after the directory.
+    let mut last_restored: Option<&RepoPath> = None;
+    for path in paths.iter().sorted() {
+        if last_restored.is_some_and(|dir| path.starts_with(dir)) {
+            continue;
+        }
+        tree_builder.set_or_remove(path.clone(), source.path_value(path)?);
+        last_restored = Some(path);
+    }
+    tree_builder.write_tree(destination.store())
+}
+
+/// Rewrites `to_commit` with the given paths restored from `from_tree`. See
+/// [`restore_tree_paths()`].
+pub fn restore_paths(
+    mut_repo: &mut MutableRepo,
+    from_tree: &MergedTree,
+    to_commit: &Commit,
+    paths: &[RepoPathBuf],
+) -> BackendResult<Commit> {
+    let new_tree_id = restore_tree_paths(from_tree, &to_commit.tree()?, paths)?;
+    mut_repo
+        .rewrite_commit(to_commit)
+        .set_tree_id(new_tree_id)
+        .write()
+}
+
+/// Carries over the copy records of `original` to the `first` and `second`
+/// commits it was split into.
+///
+/// A record goes to a new commit if the commit changes its target path, the
+/// target is in the commit's parents as it was in the parents of `original`,
+/// and the source path is a file in one of the commit's parents. The source of
+/// the record is updated to refer to that parent. Other records, such as the
+/// renames already applied by the `first` commit, aren't carried over to the
+/// `second` commit. Records whose sources don't exist in the new parents, for
+/// example because the `first` commit was moved elsewhere, are dropped.
+pub fn split_copy_records(
+    repo: &dyn Repo,
+    original: &Commit,
+    first: &Commit,
+    second: &Commit,
+) -> BackendResult<()> {
+    let store = original.store();
+    let original_parent_tree = original.parent_tree(repo)?;
+    let mut records = vec![];
+    for parent_id in original.parent_ids() {
+        let stream = store.get_copy_records(None, parent_id, original.id())?;
+        let parent_records: Vec<_> = stream.try_collect().block_on()?;
+        records.extend(parent_records);
+    }
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut new_records = vec![];
+    for commit in [first, second] {
+        let tree = commit.tree()?;
+        let parent_tree = commit.parent_tree(repo)?;
+        let parents: Vec<_> = commit.parents().try_collect()?;
+        for record in &records {
+            let parent_value = parent_tree.path_value(&record.target)?;
+            if parent_value == tree.path_value(&record.target)?
+                || parent_value != original_parent_tree.path_value(&record.target)?
+            {
+                continue;
+            }
+            for parent in &parents {
+                let source_value = parent.tree()?.path_value(&record.source)?;
+                if let Some(Some(TreeValue::File { id, .. })) = source_value.as_resolved() {
+                    new_records.push(CopyRecord {
+                        target_commit: commit.id().clone(),
+                        source_file: id.clone(),
+                        source_commit: parent.id().clone(),
+                        ..record.clone()
+                    });
+                    break;
+                }
+            }
+        }
+    }
+    if new_records.is_empty() {
+        return Ok(());
+    }
+    store.write_copy_records(&new_records)
+}
+
+/// Error raised when rebasing a commit would need the history beyond a shallow
+/// boundary.
+#[derive(Debug, Error)]
+#[error(
+    "Cannot rebase commit {} across the shallow history boundary at {}",
+    .commit_id.hex(),
+    .boundary_id.hex()
+)]
+pub struct ShallowBoundaryError {
+    pub commit_id: CommitId,
+    pub boundary_id: CommitId,
+}
+
+impl From<ShallowBoundaryError> for BackendError {
+    fn from(err: ShallowBoundaryError) -> Self {
+        BackendError::Other(err.into())
+    }
+}
+
 pub fn rebase_commit(
     mut_repo: &mut MutableRepo,
     old_commit: Commit,
@@ -200,8 +425,7 @@ impl<'repo> CommitRewriter<'repo> {
     pub fn simplify_ancestor_merge(&mut self) -> Result<(), IndexError> {
         let head_set: HashSet<_> = self
             .mut_repo
-            .index()
-            .heads(&mut self.new_parents.iter())?
+            .heads_of_set(&mut self.new_parents.iter())?
             .into_iter()
             .collect();
         self.new_parents.retain(|parent| head_set.contains(parent));
@@ -224,6 +448,13 @@ impl<'repo> CommitRewriter<'repo> {
         self,
         empty: EmptyBehaviour,
     ) -> BackendResult<Option<CommitBuilder<'repo>>> {
+        if self.old_commit.is_shallow_boundary() {
+            return Err(ShallowBoundaryError {
+                commit_id: self.old_commit.id().clone(),
+                boundary_id: self.old_commit.id().clone(),
+            }
+            .into());
+        }
         let old_parents: Vec<_> = self.old_commit.parents().try_collect()?;
         let old_parent_trees = old_parents
             .iter()
@@ -238,6 +469,21 @@ impl<'repo> CommitRewriter<'repo> {
             .iter()
             .map(|parent| parent.tree_id().clone())
             .collect_vec();
+        // The tree of a boundary stub isn't the real tree, so the changes
+        // relative to it can't be computed.
+        if self.parents_changed() {
+            if let Some(boundary) = old_parents
+                .iter()
+                .chain(&new_parents)
+                .find(|parent| parent.is_shallow_boundary())
+            {
+                return Err(ShallowBoundaryError {
+                    commit_id: self.old_commit.id().clone(),
+                    boundary_id: boundary.id().clone(),
+                }
+                .into());
+            }
+        }
 
         let (was_empty, new_tree_id) = if new_parent_trees == old_parent_trees {
             (
@@ -248,13 +494,17 @@ impl<'repo> CommitRewriter<'repo> {
                 self.old_commit.tree_id().clone(),
             )
         } else {
-            let old_base_tree = merge_commit_trees(self.mut_repo, &old_parents)?;
-            let new_base_tree = merge_commit_trees(self.mut_repo, &new_parents)?;
-            let old_tree = self.old_commit.tree()?;
-            (
-                old_base_tree.id() == *self.old_commit.tree_id(),
-                new_base_tree.merge(&old_base_tree, &old_tree)?.id(),
-            )
+            let (result, elapsed) = metrics::timed(|| -> BackendResult<_> {
+                let old_base_tree = merge_commit_trees(self.mut_repo, &old_parents)?;
+                let new_base_tree = merge_commit_trees(self.mut_repo, &new_parents)?;
+                let old_tree = self.old_commit.tree()?;
+                Ok((
+                    old_base_tree.id() == *self.old_commit.tree_id(),
+                    new_base_tree.merge(&old_base_tree, &old_tree)?.id(),
+                ))
+            });
+            self.mut_repo.metrics_mut().record_tree_merge(elapsed);
+            result?
         };
         // Ensure we don't abandon commits with multiple parents (merge commits), even
         // if they're empty.
@@ -317,7 +567,15 @@ pub fn rebase_commit_with_options(
         _ => None,
     };
     let new_parents_len = rewriter.new_parents.len();
-    if let Some(builder) = rewriter.rebase_with_empty_behavior(options.empty)? {
+    let empty = if options
+        .keep_empty_commits
+        .contains(rewriter.old_commit().id())
+    {
+        EmptyBehaviour::Keep
+    } else {
+        options.empty
+    };
+    if let Some(builder) = rewriter.rebase_with_empty_behavior(empty)? {
         let new_commit = builder.write()?;
         Ok(RebasedCommit::Rewritten(new_commit))
     } else {
@@ -377,6 +635,10 @@ pub struct RebaseOptions {
     /// If a merge commit would end up with one parent being an ancestor of the
     /// other, then filter out the ancestor.
     pub simplify_ancestor_merge: bool,
+    /// Commits (identified by their ids before the rebase) that are never
+    /// abandoned for becoming empty, regardless of `empty`. Used to protect
+    /// commits whose contents were explicitly restored by the caller.
+    pub keep_empty_commits: HashSet<CommitId>,
 }
 
 /// Configuration for [`MutableRepo::update_rewritten_references()`].
@@ -387,6 +649,22 @@ pub struct RewriteRefsOptions {
     /// If false, bookmarks will be moved to the parents of the abandoned
     /// commit.
     pub delete_abandoned_bookmarks: bool,
+    /// How to update bookmarks pointing to commits which were rewritten into
+    /// multiple commits.
+    pub bookmark_move_policy: BookmarkMovePolicy,
+}
+
+/// How bookmarks should be updated when the commit they point to is
+/// divergently rewritten.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BookmarkMovePolicy {
+    /// Make the bookmark conflicted between all the new commits.
+    #[default]
+    Conflict,
+    /// Move the bookmark to the first of the new commits.
+    FollowFirst,
+    /// Leave the bookmark pointing to the old commit.
+    LeaveBehind,
 }
 
 pub struct MoveCommitsStats {
@@ -420,6 +698,90 @@ pub enum MoveCommitsTarget {
     Roots(Vec<CommitId>),
 }
 
+/// Destination for [`move_commits_to_destination()`], which is resolved against
+/// the repo state of the transaction performing the move.
+#[derive(Clone, Debug)]
+pub enum MoveCommitsDestination {
+    /// Insert the commits between the root-most mutable ancestors of the given
+    /// commit (inclusive) and their parents.
+    BeforeFirstMutableAncestorOf(CommitId),
+}
+
+impl MoveCommitsDestination {
+    /// Resolves the destination to new parent and child commit ids.
+    ///
+    /// Commits for which `is_immutable` returns true, as well as the root
+    /// commit, are considered immutable.
+    pub fn resolve(
+        &self,
+        repo: &dyn Repo,
+        is_immutable: &RevsetContainingFn,
+    ) -> Result<(Vec<CommitId>, Vec<CommitId>), MoveCommitsError> {
+        match self {
+            Self::BeforeFirstMutableAncestorOf(commit_id) => {
+                let roots = find_mutable_roots(repo, commit_id, is_immutable)?;
+                // Not using `RevsetExpression::parents` here to persist the order of
+                // parents.
+                let new_parent_ids = roots
+                    .iter()
+                    .flat_map(|commit| commit.parent_ids())
+                    .unique()
+                    .cloned()
+                    .collect_vec();
+                let new_child_ids = roots.iter().ids().cloned().collect_vec();
+                Ok((new_parent_ids, new_child_ids))
+            }
+        }
+    }
+}
+
+/// Error from [`move_commits_to_destination()`].
+#[derive(Debug, Error)]
+pub enum MoveCommitsError {
+    #[error("Commit {} has no mutable ancestors", .0.hex())]
+    NoMutableAncestors(CommitId),
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error(transparent)]
+    RevsetEvaluation(#[from] RevsetEvaluationError),
+}
+
+/// Finds the root-most commits among the mutable ancestors of `commit_id`
+/// which can be reached from it without passing through immutable commits.
+fn find_mutable_roots(
+    repo: &dyn Repo,
+    commit_id: &CommitId,
+    is_immutable: &RevsetContainingFn,
+) -> Result<Vec<Commit>, MoveCommitsError> {
+    let root_commit_id = repo.store().root_commit_id();
+    let is_mutable = |id: &CommitId| -> Result<bool, RevsetEvaluationError> {
+        Ok(id != root_commit_id && !is_immutable(id)?)
+    };
+    if !is_mutable(commit_id)? {
+        return Err(MoveCommitsError::NoMutableAncestors(commit_id.clone()));
+    }
+
+    let mut roots = vec![];
+    let mut visited = HashSet::from([commit_id.clone()]);
+    let mut to_visit = vec![commit_id.clone()];
+    while let Some(id) = to_visit.pop() {
+        let commit = repo.store().get_commit(&id)?;
+        let mut has_mutable_parent = false;
+        for parent_id in commit.parent_ids() {
+            if is_mutable(parent_id)? {
+                has_mutable_parent = true;
+                if visited.insert(parent_id.clone()) {
+                    to_visit.push(parent_id.clone());
+                }
+            }
+        }
+        if !has_mutable_parent {
+            roots.push(commit);
+        }
+    }
+    Ok(roots)
+}
+
 #[derive(Clone, Debug)]
 pub struct ComputedMoveCommits {
     target_commit_ids: IndexSet<CommitId>,
@@ -473,6 +835,28 @@ pub fn move_commits(
     compute_move_commits(mut_repo, loc)?.apply(mut_repo, options)
 }
 
+/// Moves `target` commits to `destination`, which is resolved in the same
+/// transaction as the move.
+///
+/// This is the same as [`move_commits()`], except that the new parents and
+/// children are computed from the current state of `mut_repo`, so they can't
+/// go stale between resolution and rewriting.
+pub fn move_commits_to_destination(
+    mut_repo: &mut MutableRepo,
+    target: MoveCommitsTarget,
+    destination: &MoveCommitsDestination,
+    is_immutable: &RevsetContainingFn,
+    options: &RebaseOptions,
+) -> Result<MoveCommitsStats, MoveCommitsError> {
+    let (new_parent_ids, new_child_ids) = destination.resolve(mut_repo, is_immutable)?;
+    let loc = MoveCommitsLocation {
+        new_parent_ids,
+        new_child_ids,
+        target,
+    };
+    Ok(move_commits(mut_repo, &loc, options)?)
+}
+
 pub fn compute_move_commits(
     repo: &MutableRepo,
     loc: &MoveCommitsLocation,
@@ -501,12 +885,10 @@ pub fn compute_move_commits(
             connected_target_commits_internal_parents =
                 compute_internal_parents_within(&target_commit_ids, &connected_target_commits);
 
-            target_roots = connected_target_commits_internal_parents
-                .iter()
-                .filter(|&(commit_id, parents)| {
-                    target_commit_ids.contains(commit_id) && parents.is_empty()
-                })
-                .map(|(commit_id, _)| commit_id.clone())
+            target_roots = repo
+                .roots_of_set(&mut target_commit_ids.iter())
+                .map_err(|err| BackendError::Other(err.into()))?
+                .into_iter()
                 .collect();
         }
         MoveCommitsTarget::Roots(root_ids) => {
@@ -776,6 +1158,7 @@ fn apply_move_commits(
         empty: EmptyBehaviour::Keep,
         rewrite_refs: options.rewrite_refs.clone(),
         simplify_ancestor_merge: options.simplify_ancestor_merge,
+        keep_empty_commits: HashSet::new(),
     };
 
     let mut rebased_commits: HashMap<CommitId, RebasedCommit> = HashMap::new();
@@ -1281,3 +1664,228 @@ pub fn find_duplicate_divergent_commits(
     }
     Ok(duplicate_divergent)
 }
+
+/// How [`rewrite_file_storage()`] should store a matched file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileRewrite {
+    /// Leave the file as is.
+    Keep,
+    /// Replace the file with the file with the given id, e.g. a pointer file
+    /// written to the store by the callback. The executable bit and copy id
+    /// are preserved.
+    Replace(FileId),
+}
+
+/// A file replaced by [`rewrite_file_storage()`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplacedFile {
+    /// The rewritten commit containing the new file.
+    pub commit_id: CommitId,
+    pub path: RepoPathBuf,
+    /// The id of the file before it was replaced.
+    pub old_id: FileId,
+    pub new_id: FileId,
+}
+
+#[derive(Default)]
+pub struct RewriteFileStorageStats {
+    /// Map of original commit ID to the rewritten commit. Commits in which no
+    /// file was replaced and whose parents weren't rewritten are not included.
+    pub rewritten_commits: IndexMap<CommitId, Commit>,
+    /// Every replaced file in every rewritten commit.
+    pub replaced_files: Vec<ReplacedFile>,
+}
+
+/// Replaces the files matched by `matcher` in `root_commit_ids` and all their
+/// descendants with the files returned by `transform`.
+///
+/// This can be used to move files to a different storage representation after
+/// the fact, e.g. to replace large files with pointer files. Change ids are
+/// preserved, so the rewritten commits are new versions of the original
+/// commits.
+///
+/// Only the subtrees containing matched paths are rebuilt. Since most trees
+/// are shared between commits, rewritten subtrees are memoized by their
+/// original id, and `transform` is called at most once per path and file id.
+pub fn rewrite_file_storage(
+    mut_repo: &mut MutableRepo,
+    root_commit_ids: &[CommitId],
+    matcher: &dyn Matcher,
+    transform: impl Fn(&RepoPath, FileId) -> BackendResult<FileRewrite>,
+) -> BackendResult<RewriteFileStorageStats> {
+    let mut rewriter = FileStorageRewriter {
+        store: mut_repo.store().clone(),
+        matcher,
+        transform,
+        rewritten_trees: HashMap::new(),
+        rewritten_files: HashMap::new(),
+    };
+    let mut stats = RewriteFileStorageStats::default();
+    mut_repo.transform_descendants(root_commit_ids.to_vec(), |commit_rewriter| {
+        let old_commit = commit_rewriter.old_commit().clone();
+        let (new_tree_id, replaced) = rewriter.rewrite_merged_tree(old_commit.tree_id())?;
+        if replaced.is_empty() && !commit_rewriter.parents_changed() {
+            return Ok(());
+        }
+        // The tree is rewritten directly instead of being rebased onto the new
+        // parents, which would give the same result at a higher cost.
+        let new_commit = commit_rewriter
+            .reparent()
+            .set_tree_id(new_tree_id)
+            .write()?;
+        stats.replaced_files.extend(replaced.into_iter().map(
+            |RewrittenFile {
+                 path,
+                 old_id,
+                 new_id,
+             }| ReplacedFile {
+                commit_id: new_commit.id().clone(),
+                path,
+                old_id,
+                new_id,
+            },
+        ));
+        stats
+            .rewritten_commits
+            .insert(old_commit.id().clone(), new_commit);
+        Ok(())
+    })?;
+    Ok(stats)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct RewrittenFile {
+    path: RepoPathBuf,
+    old_id: FileId,
+    new_id: FileId,
+}
+
+#[derive(Clone)]
+struct RewrittenTree {
+    id: TreeId,
+    replaced: Arc<[RewrittenFile]>,
+}
+
+struct FileStorageRewriter<'a, F> {
+    store: Arc<Store>,
+    matcher: &'a dyn Matcher,
+    transform: F,
+    /// Rewritten trees by their original directory and id. The directory is
+    /// part of the key because the matcher depends on it.
+    rewritten_trees: HashMap<(RepoPathBuf, TreeId), RewrittenTree>,
+    /// Replacement file ids by their original path and id.
+    rewritten_files: HashMap<(RepoPathBuf, FileId), Option<FileId>>,
+}
+
+impl<F> FileStorageRewriter<'_, F>
+where
+    F: Fn(&RepoPath, FileId) -> BackendResult<FileRewrite>,
+{
+    /// Rewrites each term of the tree and returns the new tree id and the
+    /// files replaced in any of the terms.
+    fn rewrite_merged_tree(
+        &mut self,
+        tree_id: &MergedTreeId,
+    ) -> BackendResult<(MergedTreeId, Vec<RewrittenFile>)> {
+        // A conflicted tree may contain the same replaced file in multiple
+        // terms
+        let mut replaced = BTreeSet::new();
+        let new_tree_id = match tree_id {
+            MergedTreeId::Legacy(id) => {
+                let rewritten = self.rewrite_tree(RepoPath::root(), id)?;
+                replaced.extend(rewritten.replaced.iter().cloned());
+                MergedTreeId::Legacy(rewritten.id)
+            }
+            MergedTreeId::Merge(ids) => MergedTreeId::Merge(ids.try_map(|id| {
+                let rewritten = self.rewrite_tree(RepoPath::root(), id)?;
+                replaced.extend(rewritten.replaced.iter().cloned());
+                Ok::<_, BackendError>(rewritten.id)
+            })?),
+        };
+        Ok((new_tree_id, replaced.into_iter().collect()))
+    }
+
+    fn rewrite_tree(&mut self, dir: &RepoPath, tree_id: &TreeId) -> BackendResult<RewrittenTree> {
+        let key = (dir.to_owned(), tree_id.clone());
+        if let Some(rewritten) = self.rewritten_trees.get(&key) {
+            return Ok(rewritten.clone());
+        }
+        let tree = self.store.get_tree(dir.to_owned(), tree_id)?;
+        let mut new_data = tree.data().clone();
+        let mut replaced = vec![];
+        for entry in tree.data().entries() {
+            let name = entry.name();
+            match entry.value() {
+                TreeValue::File {
+                    id,
+                    executable,
+                    copy_id,
+                } => {
+                    let path = dir.join(name);
+                    if !self.matcher.matches(&path) {
+                        continue;
+                    }
+                    let Some(new_id) = self.rewrite_file(&path, id)? else {
+                        continue;
+                    };
+                    new_data.set(
+                        name.to_owned(),
+                        TreeValue::File {
+                            id: new_id.clone(),
+                            executable: *executable,
+                            copy_id: copy_id.clone(),
+                        },
+                    );
+                    replaced.push(RewrittenFile {
+                        path,
+                        old_id: id.clone(),
+                        new_id,
+                    });
+                }
+                TreeValue::Tree(sub_tree_id) => {
+                    let sub_dir = dir.join(name);
+                    if self.matcher.visit(&sub_dir).is_nothing() {
+                        continue;
+                    }
+                    let rewritten = self.rewrite_tree(&sub_dir, sub_tree_id)?;
+                    if rewritten.id != *sub_tree_id {
+                        new_data.set(name.to_owned(), TreeValue::Tree(rewritten.id));
+                    }
+                    replaced.extend(rewritten.replaced.iter().cloned());
+                }
+                // Files in legacy conflict objects are left as is
+                TreeValue::Symlink(_) | TreeValue::GitSubmodule(_) | TreeValue::Conflict(_) => {}
+            }
+        }
+        let new_id = if new_data == *tree.data() {
+            tree_id.clone()
+        } else {
+            self.store
+                .write_tree(dir, new_data)
+                .block_on()?
+                .id()
+                .clone()
+        };
+        let rewritten = RewrittenTree {
+            id: new_id,
+            replaced: replaced.into(),
+        };
+        self.rewritten_trees.insert(key, rewritten.clone());
+        Ok(rewritten)
+    }
+
+    /// Returns the id of the file replacing `id`, or `None` if it's kept.
+    fn rewrite_file(&mut self, path: &RepoPath, id: &FileId) -> BackendResult<Option<FileId>> {
+        let key = (path.to_owned(), id.clone());
+        if let Some(new_id) = self.rewritten_files.get(&key) {
+            return Ok(new_id.clone());
+        }
+        let new_id = match (self.transform)(path, id.clone())? {
+            FileRewrite::Keep => None,
+            FileRewrite::Replace(new_id) if new_id == *id => None,
+            FileRewrite::Replace(new_id) => Some(new_id),
+        };
+        self.rewritten_files.insert(key, new_id.clone());
+        Ok(new_id)
+    }
+}
@@ -0,0 +1,164 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Policies deciding which commits may be rewritten.
+//!
+//! Policies are installed on a [`MutableRepo`](crate::repo::MutableRepo),
+//! which consults them whenever a commit is recorded as rewritten or
+//! abandoned. See [`MutableRepo::check_rewrite()`].
+//!
+//! [`MutableRepo::check_rewrite()`]: crate::repo::MutableRepo::check_rewrite
+
+use std::fmt::Debug;
+
+use thiserror::Error;
+
+use crate::backend::CommitId;
+use crate::object_id::ObjectId as _;
+use crate::repo::Repo;
+
+/// Whether a commit may be rewritten.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RewritePermission {
+    /// The commit may be rewritten.
+    Allowed,
+    /// The commit may only be rewritten if a [`RewriteOverride`] is set. The
+    /// string explains why the commit is protected.
+    RequiresOverride(String),
+    /// The commit must not be rewritten, even with a [`RewriteOverride`]. The
+    /// string explains why the commit is protected.
+    Forbidden(String),
+}
+
+impl RewritePermission {
+    /// Returns the more restrictive of the two permissions. If both are
+    /// equally restrictive, `self` is returned.
+    pub fn restrict(self, other: Self) -> Self {
+        let rank = |permission: &Self| match permission {
+            Self::Allowed => 0,
+            Self::RequiresOverride(_) => 1,
+            Self::Forbidden(_) => 2,
+        };
+        if rank(&other) > rank(&self) {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+/// Decides whether commits may be rewritten.
+pub trait RewritePolicy: Debug + Send + Sync {
+    /// Returns the permission for rewriting `commit_id` in `repo`.
+    fn check(
+        &self,
+        repo: &dyn Repo,
+        commit_id: &CommitId,
+    ) -> Result<RewritePermission, Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Token which allows rewriting commits with
+/// [`RewritePermission::RequiresOverride`].
+///
+/// The token never allows rewriting commits with
+/// [`RewritePermission::Forbidden`].
+#[derive(Clone, Debug, Default)]
+pub struct RewriteOverride {
+    _private: (),
+}
+
+impl RewriteOverride {
+    /// Creates a new override token.
+    pub fn new() -> Self {
+        RewriteOverride { _private: () }
+    }
+}
+
+/// Error returned when a commit is not allowed to be rewritten.
+#[derive(Debug, Error)]
+pub enum RewriteDeniedError {
+    /// The commit requires an override, which was not given.
+    #[error("Commit {} cannot be rewritten without an override: {reason}", commit_id.hex())]
+    RequiresOverride {
+        /// The commit that was about to be rewritten.
+        commit_id: CommitId,
+        /// Why the commit is protected.
+        reason: String,
+    },
+    /// The commit must not be rewritten.
+    #[error("Commit {} must not be rewritten: {reason}", commit_id.hex())]
+    Forbidden {
+        /// The commit that was about to be rewritten.
+        commit_id: CommitId,
+        /// Why the commit is protected.
+        reason: String,
+    },
+    /// A policy failed to decide.
+    #[error("Failed to check whether commit {} can be rewritten", commit_id.hex())]
+    Policy {
+        /// The commit that was about to be rewritten.
+        commit_id: CommitId,
+        /// The underlying error.
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+}
+
+/// Commits that an operation would rewrite, partitioned by whether they may be
+/// rewritten.
+///
+/// Commands that do expensive work per target commit (such as running tools or
+/// materializing file contents) can compute this upfront, and fail or skip the
+/// immutable targets before doing the work.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct AffectedCommits {
+    /// Commits that may be rewritten, and the number of changes targeting
+    /// each, in input order.
+    pub rewritable: Vec<(CommitId, usize)>,
+    /// Commits that must not be rewritten, and the number of changes targeting
+    /// each, in input order.
+    pub immutable: Vec<(CommitId, usize)>,
+}
+
+impl AffectedCommits {
+    /// Partitions the `targets` by the caller-provided `is_immutable`
+    /// predicate. Each target is a commit and the number of changes (e.g.
+    /// hunks or files) that would be made to it.
+    pub fn partition<E>(
+        targets: impl IntoIterator<Item = (CommitId, usize)>,
+        mut is_immutable: impl FnMut(&CommitId) -> Result<bool, E>,
+    ) -> Result<Self, E> {
+        let mut affected = Self::default();
+        for (commit_id, num_changes) in targets {
+            if is_immutable(&commit_id)? {
+                affected.immutable.push((commit_id, num_changes));
+            } else {
+                affected.rewritable.push((commit_id, num_changes));
+            }
+        }
+        Ok(affected)
+    }
+
+    /// Returns true if any of the targets must not be rewritten.
+    pub fn has_immutable(&self) -> bool {
+        !self.immutable.is_empty()
+    }
+
+    /// Returns the total number of changes targeting immutable commits.
+    pub fn num_immutable_changes(&self) -> usize {
+        self.immutable
+            .iter()
+            .map(|(_, num_changes)| num_changes)
+            .sum()
+    }
+}
@@ -37,6 +37,7 @@ use crate::backend::CopyId;
 use crate::backend::CopyRecord;
 use crate::backend::FileId;
 use crate::backend::SigningFn;
+use crate::backend::StorageStats;
 use crate::backend::SymlinkId;
 use crate::backend::Tree;
 use crate::backend::TreeId;
@@ -218,6 +219,10 @@ impl Backend for SecretBackend {
         self.inner.get_copy_records(paths, root, head)
     }
 
+    fn storage_stats(&self) -> BackendResult<Option<StorageStats>> {
+        self.inner.storage_stats()
+    }
+
     fn gc(&self, index: &dyn Index, keep_newer: SystemTime) -> BackendResult<()> {
         self.inner.gc(index, keep_newer)
     }
@@ -14,11 +14,16 @@
 
 #![allow(missing_docs)]
 
+use std::fmt::Debug;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::Mutex;
 
+use blake2::Blake2b512;
+use blake2::Digest as _;
 use chrono::DateTime;
 use rand::prelude::*;
 use rand_chacha::ChaCha20Rng;
@@ -36,6 +41,7 @@ use crate::config::StackedConfig;
 use crate::config::ToConfigNamePath;
 use crate::fmt_util::binary_prefix;
 use crate::fsmonitor::FsmonitorSettings;
+use crate::settings_schema::get_or_default;
 use crate::signing::SignBehavior;
 
 #[derive(Debug, Clone)]
@@ -43,6 +49,7 @@ pub struct UserSettings {
     config: Arc<StackedConfig>,
     data: Arc<UserSettingsData>,
     rng: Arc<JJRng>,
+    change_id_generator: Arc<dyn ChangeIdGenerator>,
 }
 
 #[derive(Debug)]
@@ -68,10 +75,10 @@ pub struct GitSettings {
 impl GitSettings {
     pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
         Ok(GitSettings {
-            auto_local_bookmark: settings.get_bool("git.auto-local-bookmark")?,
-            abandon_unreachable_commits: settings.get_bool("git.abandon-unreachable-commits")?,
-            executable_path: settings.get("git.executable-path")?,
-            write_change_id_header: settings.get("git.write-change-id-header")?,
+            auto_local_bookmark: settings.git_auto_local_bookmark()?,
+            abandon_unreachable_commits: settings.git_abandon_unreachable_commits()?,
+            executable_path: settings.git_executable_path()?,
+            write_change_id_header: settings.git_write_change_id_header()?,
         })
     }
 }
@@ -132,21 +139,31 @@ fn to_timestamp(value: ConfigValue) -> Result<Timestamp, Box<dyn std::error::Err
 impl UserSettings {
     pub fn from_config(config: StackedConfig) -> Result<Self, ConfigGetError> {
         let rng_seed = config.get::<u64>("debug.randomness-seed").optional()?;
-        Self::from_config_and_rng(config, Arc::new(JJRng::new(rng_seed)))
+        let rng = Arc::new(JJRng::new(rng_seed));
+        let change_id_seed = config.get::<u64>("debug.change-id-seed").optional()?;
+        let change_id_generator: Arc<dyn ChangeIdGenerator> = match change_id_seed {
+            Some(seed) => Arc::new(SeededChangeIdGenerator::new(seed)),
+            None => rng.clone(),
+        };
+        Self::from_config_and_rng(config, rng, change_id_generator)
     }
 
-    fn from_config_and_rng(config: StackedConfig, rng: Arc<JJRng>) -> Result<Self, ConfigGetError> {
-        let user_name = config.get("user.name")?;
-        let user_email = config.get("user.email")?;
+    fn from_config_and_rng(
+        config: StackedConfig,
+        rng: Arc<JJRng>,
+        change_id_generator: Arc<dyn ChangeIdGenerator>,
+    ) -> Result<Self, ConfigGetError> {
+        let user_name = get_or_default(&config, "user.name")?;
+        let user_email = get_or_default(&config, "user.email")?;
         let commit_timestamp = config
             .get_value_with("debug.commit-timestamp", to_timestamp)
             .optional()?;
         let operation_timestamp = config
             .get_value_with("debug.operation-timestamp", to_timestamp)
             .optional()?;
-        let operation_hostname = config.get("operation.hostname")?;
-        let operation_username = config.get("operation.username")?;
-        let signing_behavior = config.get("signing.behavior")?;
+        let operation_hostname = get_or_default(&config, "operation.hostname")?;
+        let operation_username = get_or_default(&config, "operation.username")?;
+        let signing_behavior = get_or_default(&config, "signing.behavior")?;
         let signing_key = config.get("signing.key").optional()?;
         let data = UserSettingsData {
             user_name,
@@ -162,21 +179,37 @@ impl UserSettings {
             config: Arc::new(config),
             data: Arc::new(data),
             rng,
+            change_id_generator,
         })
     }
 
     /// Like [`UserSettings::from_config()`], but retains the internal state.
     ///
     /// This ensures that no duplicated change IDs are generated within the
-    /// current process. New `debug.randomness-seed` value is ignored.
+    /// current process. New `debug.randomness-seed` and `debug.change-id-seed`
+    /// values are ignored.
     pub fn with_new_config(&self, config: StackedConfig) -> Result<Self, ConfigGetError> {
-        Self::from_config_and_rng(config, self.rng.clone())
+        Self::from_config_and_rng(config, self.rng.clone(), self.change_id_generator.clone())
+    }
+
+    /// Returns settings which generate change ids for new commits by the given
+    /// `generator`.
+    pub fn with_change_id_generator(&self, generator: Arc<dyn ChangeIdGenerator>) -> Self {
+        UserSettings {
+            change_id_generator: generator,
+            ..self.clone()
+        }
     }
 
     pub fn get_rng(&self) -> Arc<JJRng> {
         self.rng.clone()
     }
 
+    /// Generator of change ids for new commits.
+    pub fn change_id_generator(&self) -> &Arc<dyn ChangeIdGenerator> {
+        &self.change_id_generator
+    }
+
     pub fn user_name(&self) -> &str {
         &self.data.user_name
     }
@@ -223,7 +256,9 @@ impl UserSettings {
 
     /// Returns low-level config object.
     ///
-    /// You should typically use `settings.get_<type>()` methods instead.
+    /// You should typically use the typed accessors generated from the
+    /// [settings schema](crate::settings_schema), or `settings.get_<type>()`
+    /// methods instead.
     pub fn config(&self) -> &StackedConfig {
         &self.config
     }
@@ -235,7 +270,7 @@ impl UserSettings {
     // separate from sign_settings as those two are needed in pretty different
     // places
     pub fn signing_backend(&self) -> Result<Option<String>, ConfigGetError> {
-        let backend = self.get_string("signing.backend")?;
+        let backend: String = get_or_default(self.config(), "signing.backend")?;
         Ok((backend != "none").then_some(backend))
     }
 
@@ -301,6 +336,53 @@ impl UserSettings {
     }
 }
 
+/// Generates change ids for new commits.
+pub trait ChangeIdGenerator: Debug + Send + Sync {
+    /// Returns a new change id of `length` bytes. The generator is responsible
+    /// for not returning the same id twice.
+    fn new_change_id(&self, length: usize) -> ChangeId;
+}
+
+/// Generates deterministic change ids from a seed and a counter.
+///
+/// The same sequence of change ids is generated for the same seed, which is
+/// useful for reproducible tests and imports. Ids are unique only within the
+/// generator instance, so different repos shouldn't be populated by generators
+/// with the same seed.
+#[derive(Debug)]
+pub struct SeededChangeIdGenerator {
+    seed: u64,
+    counter: AtomicU64,
+}
+
+impl SeededChangeIdGenerator {
+    pub fn new(seed: u64) -> Self {
+        SeededChangeIdGenerator {
+            seed,
+            counter: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ChangeIdGenerator for SeededChangeIdGenerator {
+    fn new_change_id(&self, length: usize) -> ChangeId {
+        let count = self.counter.fetch_add(1, Ordering::Relaxed);
+        let mut bytes = Vec::with_capacity(length);
+        for block in 0_u64.. {
+            if bytes.len() >= length {
+                break;
+            }
+            let mut hasher = Blake2b512::new();
+            hasher.update(self.seed.to_le_bytes());
+            hasher.update(count.to_le_bytes());
+            hasher.update(block.to_le_bytes());
+            bytes.extend_from_slice(&hasher.finalize());
+        }
+        bytes.truncate(length);
+        ChangeId::new(bytes)
+    }
+}
+
 /// This Rng uses interior mutability to allow generating random values using an
 /// immutable reference. It also fixes a specific seedable RNG for
 /// reproducibility.
@@ -327,6 +409,12 @@ impl JJRng {
     }
 }
 
+impl ChangeIdGenerator for JJRng {
+    fn new_change_id(&self, length: usize) -> ChangeId {
+        JJRng::new_change_id(self, length)
+    }
+}
+
 /// A size in bytes optionally formatted/serialized with binary prefixes
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
 pub struct HumanByteSize(pub u64);
@@ -0,0 +1,330 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Declarative schema of settings.
+//!
+//! Each setting is declared once with its type and default value. Typed
+//! accessors on [`UserSettings`] are generated from the declarations, and
+//! values missing from the config fall back to the declared defaults. Config
+//! keys which aren't declared can be reported by [`validate_unknown_keys()`].
+
+#![warn(missing_docs)]
+
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::de::IntoDeserializer as _;
+
+use crate::config::ConfigGetError;
+use crate::config::ConfigGetResultExt as _;
+use crate::config::ConfigNamePathBuf;
+use crate::config::ConfigSource;
+use crate::config::ConfigValue;
+use crate::config::StackedConfig;
+use crate::dsl_util::collect_similar;
+use crate::leased_op_heads_store::OpHeadsLocking;
+use crate::retrying_backend::RetryableErrorKind;
+use crate::settings::UserSettings;
+
+/// Setting declared in the schema.
+#[derive(Clone, Copy, Debug)]
+pub struct SettingDef {
+    /// Dotted name of the setting.
+    pub name: &'static str,
+    /// Name of the Rust type the value is converted to.
+    pub type_name: &'static str,
+    /// Default value in TOML syntax, or `None` if unset by default.
+    pub default: Option<&'static str>,
+}
+
+impl SettingDef {
+    /// Parses the default value.
+    pub fn default_value(&self) -> Option<ConfigValue> {
+        let text = self.default?;
+        Some(
+            text.parse()
+                .expect("schema default should be a valid TOML value"),
+        )
+    }
+}
+
+/// Setting which was renamed or replaced.
+#[derive(Clone, Copy, Debug)]
+pub struct DeprecatedSettingDef {
+    /// Dotted name of the deprecated setting.
+    pub name: &'static str,
+    /// Dotted name of the setting to use instead.
+    pub replacement: &'static str,
+}
+
+macro_rules! settings_schema {
+    (
+        accessors {
+            $(
+                $(#[$attr:meta])*
+                $method:ident: $ty:ty = $name:literal, default $default:literal;
+            )*
+        }
+        others {
+            $(
+                $other_name:literal: $other_ty:ty $(, default $other_default:literal)?;
+            )*
+        }
+    ) => {
+        /// Settings declared in the schema.
+        pub static SETTINGS: &[SettingDef] = &[
+            $(
+                SettingDef {
+                    name: $name,
+                    type_name: stringify!($ty),
+                    default: Some($default),
+                },
+            )*
+            $(
+                SettingDef {
+                    name: $other_name,
+                    type_name: stringify!($other_ty),
+                    default: settings_schema!(@default $($other_default)?),
+                },
+            )*
+        ];
+
+        /// Typed accessors generated from the settings schema.
+        impl UserSettings {
+            $(
+                $(#[$attr])*
+                pub fn $method(&self) -> Result<$ty, ConfigGetError> {
+                    get_or_default(self.config(), $name)
+                }
+            )*
+        }
+    };
+    (@default) => { None };
+    (@default $default:literal) => { Some($default) };
+}
+
+settings_schema! {
+    accessors {
+        /// Maximum number of attempts of a backend request.
+        backend_retry_max_attempts: usize = "backend.retry.max-attempts", default "1";
+        /// Initial delay in milliseconds between retries of a backend request.
+        backend_retry_initial_backoff_ms: u64 = "backend.retry.initial-backoff-ms", default "100";
+        /// Kinds of backend errors which are retried.
+        backend_retry_errors: Vec<RetryableErrorKind> =
+            "backend.retry.errors", default r#"["transient"]"#;
+        /// How updates of the operation heads are serialized in new repos.
+        core_op_heads_locking: OpHeadsLocking = "core.op-heads-locking", default r#""lock-file""#;
+        /// Time in milliseconds after which an operation heads lease expires.
+        core_op_heads_lease_duration_ms: u64 = "core.op-heads-lease-duration-ms", default "10000";
+        /// Whether to register a snapshot trigger with Watchman.
+        core_watchman_register_snapshot_trigger: bool =
+            "core.watchman.register-snapshot-trigger", default "false";
+        /// Whether to create local bookmarks for fetched remote bookmarks.
+        git_auto_local_bookmark: bool = "git.auto-local-bookmark", default "false";
+        /// Whether to abandon commits which became unreachable in Git.
+        git_abandon_unreachable_commits: bool =
+            "git.abandon-unreachable-commits", default "true";
+        /// Path to the `git` executable.
+        git_executable_path: PathBuf = "git.executable-path", default r#""git""#;
+        /// Whether to write the change id to Git commit headers.
+        git_write_change_id_header: bool = "git.write-change-id-header", default "true";
+        /// Path to the GPG program.
+        signing_gpg_program: String = "signing.backends.gpg.program", default r#""gpg""#;
+        /// Whether expired GPG keys are considered valid.
+        signing_gpg_allow_expired_keys: bool =
+            "signing.backends.gpg.allow-expired-keys", default "false";
+        /// Path to the GPGSM program.
+        signing_gpgsm_program: String = "signing.backends.gpgsm.program", default r#""gpgsm""#;
+        /// Whether expired GPGSM keys are considered valid.
+        signing_gpgsm_allow_expired_keys: bool =
+            "signing.backends.gpgsm.allow-expired-keys", default "false";
+        /// Path to the SSH signing program.
+        signing_ssh_program: String =
+            "signing.backends.ssh.program", default r#""ssh-keygen""#;
+        /// Whether `jj split` keeps bookmarks on the first commit.
+        split_legacy_bookmark_behavior: bool = "split.legacy-bookmark-behavior", default "true";
+        /// Number of selected files above which `jj split` asks for confirmation.
+        split_confirm_threshold: usize = "split.confirm-threshold", default "50";
+    }
+    // Settings which are read without generated accessors.
+    others {
+        "backend.prefetch-concurrency": usize, default "16";
+        "core.fsmonitor": String, default r#""none""#;
+        "debug.commit-timestamp": String;
+        "debug.operation-timestamp": String;
+        "debug.randomness-seed": u64;
+        "debug.change-id-seed": u64;
+        "operation.hostname": String, default r#""""#;
+        "operation.username": String, default r#""""#;
+        "signing.backend": String, default r#""none""#;
+        "signing.behavior": SignBehavior, default r#""keep""#;
+        "signing.key": String;
+        "signing.backends.ssh.allowed-signers": String;
+        "user.email": String, default r#""""#;
+        "user.name": String, default r#""""#;
+    }
+}
+
+/// Settings which were renamed or replaced.
+pub static DEPRECATED_SETTINGS: &[DeprecatedSettingDef] = &[
+    // TODO: Delete in jj 0.32+
+    DeprecatedSettingDef {
+        name: "git.auto-local-branch",
+        replacement: "git.auto-local-bookmark",
+    },
+    // TODO: Delete in jj 0.33+
+    DeprecatedSettingDef {
+        name: "signing.sign-all",
+        replacement: "signing.behavior",
+    },
+    // TODO: Delete in jj 0.34+
+    DeprecatedSettingDef {
+        name: "core.watchman.register_snapshot_trigger",
+        replacement: "core.watchman.register-snapshot-trigger",
+    },
+];
+
+/// Tables in which all settings are declared in the schema.
+///
+/// Unknown keys are only reported in these tables. Other tables may contain
+/// settings read by the CLI or by extensions.
+pub static CLOSED_TABLES: &[&str] = &[
+    "backend.retry",
+    "core.watchman",
+    "operation",
+    "signing",
+    "signing.backends.gpg",
+    "signing.backends.gpgsm",
+    "signing.backends.ssh",
+    "split",
+    "user",
+];
+
+/// Looks up the setting declared by `name`.
+pub fn find_setting(name: &str) -> Option<&'static SettingDef> {
+    SETTINGS.iter().find(|def| def.name == name)
+}
+
+/// Looks up value of the setting `name`, or its default value declared in the
+/// schema if the value isn't set.
+pub fn get_or_default<T: DeserializeOwned>(
+    config: &StackedConfig,
+    name: &'static str,
+) -> Result<T, ConfigGetError> {
+    if let Some(value) = config.get(name).optional()? {
+        return Ok(value);
+    }
+    let default = find_setting(name)
+        .and_then(|def| def.default_value())
+        .ok_or_else(|| ConfigGetError::NotFound {
+            name: name.to_owned(),
+        })?;
+    T::deserialize(default.into_deserializer()).map_err(|err| ConfigGetError::Type {
+        name: name.to_owned(),
+        error: err.into(),
+        source_path: None,
+    })
+}
+
+/// Config key which isn't declared in the schema.
+#[derive(Clone, Debug)]
+pub struct UnknownKeyWarning {
+    /// Name of the config variable.
+    pub key: ConfigNamePathBuf,
+    /// Source of the config layer defining the key.
+    pub source: ConfigSource,
+    /// Path to the config file defining the key if any.
+    pub path: Option<PathBuf>,
+    /// Why the key is reported.
+    pub kind: UnknownKeyKind,
+}
+
+/// Kind of [`UnknownKeyWarning`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UnknownKeyKind {
+    /// The key isn't a known setting.
+    Unknown {
+        /// Declared settings with similar names.
+        similar: Vec<String>,
+    },
+    /// The key is a deprecated setting.
+    Deprecated {
+        /// Setting to use instead.
+        replacement: &'static str,
+    },
+}
+
+/// Returns the deprecated config keys, and the undeclared keys in
+/// [`CLOSED_TABLES`].
+///
+/// Each layer is checked separately, so a key is reported for every layer
+/// defining it.
+pub fn validate_unknown_keys(config: &StackedConfig) -> Vec<UnknownKeyWarning> {
+    let mut warnings = vec![];
+    for layer in config.layers() {
+        for deprecated in DEPRECATED_SETTINGS {
+            if let Ok(Some(_)) = layer.look_up_item(deprecated.name) {
+                warnings.push(UnknownKeyWarning {
+                    key: deprecated.name.parse().unwrap(),
+                    source: layer.source,
+                    path: layer.path.clone(),
+                    kind: UnknownKeyKind::Deprecated {
+                        replacement: deprecated.replacement,
+                    },
+                });
+            }
+        }
+        for table_name in CLOSED_TABLES {
+            let Ok(Some(table)) = layer.look_up_table(*table_name) else {
+                continue;
+            };
+            for (key, item) in table.iter() {
+                let name = format!("{table_name}.{key}");
+                let is_known = find_setting(&name).is_some()
+                    || DEPRECATED_SETTINGS.iter().any(|def| def.name == name)
+                    || (item.is_table_like() && is_parent_table(&name));
+                if is_known {
+                    continue;
+                }
+                // Compare with the other keys in the table, not the whole
+                // names, which share the table prefix.
+                let siblings = SETTINGS.iter().filter_map(|def| {
+                    let (parent, leaf) = def.name.rsplit_once('.')?;
+                    (parent == *table_name).then_some(leaf)
+                });
+                let similar = collect_similar(key, siblings)
+                    .into_iter()
+                    .map(|leaf| format!("{table_name}.{leaf}"))
+                    .collect();
+                warnings.push(UnknownKeyWarning {
+                    key: ConfigNamePathBuf::from_iter(table_name.split('.').chain([key])),
+                    source: layer.source,
+                    path: layer.path.clone(),
+                    kind: UnknownKeyKind::Unknown { similar },
+                });
+            }
+        }
+    }
+    warnings
+}
+
+/// Returns true if `name` is a table containing declared settings.
+fn is_parent_table(name: &str) -> bool {
+    SETTINGS
+        .iter()
+        .map(|def| def.name)
+        .chain(CLOSED_TABLES.iter().copied())
+        .filter_map(|other| other.strip_prefix(name))
+        .any(|rest| rest.starts_with('.'))
+}
@@ -56,6 +56,7 @@ use crate::backend::MillisSinceEpoch;
 use crate::backend::SecureSig;
 use crate::backend::Signature;
 use crate::backend::SigningFn;
+use crate::backend::StorageStats;
 use crate::backend::SymlinkId;
 use crate::backend::Timestamp;
 use crate::backend::Tree;
@@ -382,6 +383,22 @@ impl Backend for SimpleBackend {
         Ok(Box::pin(stream::empty()))
     }
 
+    fn storage_stats(&self) -> BackendResult<Option<StorageStats>> {
+        let mut num_objects = 0;
+        let mut size_bytes = 0;
+        for dir_name in ["commits", "trees", "files", "symlinks", "conflicts"] {
+            for entry in fs::read_dir(self.path.join(dir_name)).map_err(to_other_err)? {
+                let metadata = entry.and_then(|entry| entry.metadata());
+                num_objects += 1;
+                size_bytes += metadata.map_err(to_other_err)?.len();
+            }
+        }
+        Ok(Some(StorageStats {
+            num_objects: Some(num_objects),
+            size_bytes: Some(size_bytes),
+        }))
+    }
+
     fn gc(&self, _index: &dyn Index, _keep_newer: SystemTime) -> BackendResult<()> {
         Ok(())
     }
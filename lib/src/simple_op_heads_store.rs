@@ -25,6 +25,8 @@ use std::path::PathBuf;
 use thiserror::Error;
 
 use crate::backend::BackendInitError;
+use crate::file_util::persist_content_atomically;
+use crate::file_util::Durability;
 use crate::file_util::IoResultExt as _;
 use crate::file_util::PathError;
 use crate::lock::FileLock;
@@ -73,11 +75,12 @@ impl SimpleOpHeadsStore {
         Self { dir: op_heads_dir }
     }
 
-    fn add_op_head(&self, id: &OperationId) -> io::Result<()> {
-        std::fs::write(self.dir.join(id.hex()), "")
+    pub(crate) fn add_op_head(&self, id: &OperationId) -> Result<(), PathError> {
+        persist_content_atomically(&self.dir, &id.hex(), b"", Durability::Buffered)?;
+        Ok(())
     }
 
-    fn remove_op_head(&self, id: &OperationId) -> io::Result<()> {
+    pub(crate) fn remove_op_head(&self, id: &OperationId) -> io::Result<()> {
         std::fs::remove_file(self.dir.join(id.hex())).or_else(|err| {
             if err.kind() == io::ErrorKind::NotFound {
                 // It's fine if the old head was not found. It probably means
@@ -22,23 +22,27 @@ use std::fmt::Debug;
 use std::fs;
 use std::io;
 use std::io::ErrorKind;
-use std::io::Write as _;
+use std::mem;
 use std::path::Path;
 use std::path::PathBuf;
 use std::time::SystemTime;
 
+use blake2::Blake2b512;
+use digest::Digest as _;
 use itertools::Itertools as _;
 use prost::Message as _;
-use tempfile::NamedTempFile;
 use thiserror::Error;
 
 use crate::backend::BackendInitError;
+use crate::backend::ChangeId;
 use crate::backend::CommitId;
 use crate::backend::MillisSinceEpoch;
 use crate::backend::Timestamp;
 use crate::content_hash::blake2b_hash;
 use crate::dag_walk;
-use crate::file_util::persist_content_addressed_temp_file;
+use crate::file_util;
+use crate::file_util::persist_content_atomically;
+use crate::file_util::Durability;
 use crate::file_util::IoResultExt as _;
 use crate::file_util::PathError;
 use crate::merge::Merge;
@@ -46,6 +50,7 @@ use crate::object_id::HexPrefix;
 use crate::object_id::ObjectId;
 use crate::object_id::PrefixResolution;
 use crate::op_store;
+use crate::op_store::CommitRewrites;
 use crate::op_store::OpStore;
 use crate::op_store::OpStoreError;
 use crate::op_store::OpStoreResult;
@@ -59,11 +64,14 @@ use crate::op_store::RemoteView;
 use crate::op_store::RootOperationData;
 use crate::op_store::View;
 use crate::op_store::ViewId;
+use crate::op_store::WorkspaceMetadata;
 use crate::ref_name::GitRefNameBuf;
 use crate::ref_name::RefNameBuf;
 use crate::ref_name::RemoteNameBuf;
 use crate::ref_name::WorkspaceName;
 use crate::ref_name::WorkspaceNameBuf;
+use crate::repo_pin::RepoPinError;
+use crate::repo_pin::RepoPinStore;
 
 // BLAKE2b-512 hash length in bytes
 const OPERATION_ID_LENGTH: usize = 64;
@@ -131,6 +139,63 @@ impl SimpleOpStore {
     fn operations_dir(&self) -> PathBuf {
         self.path.join("operations")
     }
+
+    fn view_chunks_dir(&self) -> PathBuf {
+        self.path.join("view_chunks")
+    }
+
+    /// Reads the view file without loading the ref chunks it refers to.
+    fn read_view_proto(&self, id: &ViewId) -> OpStoreResult<crate::protos::op_store::View> {
+        let path = self.views_dir().join(id.hex());
+        let buf = fs::read(&path)
+            .context(&path)
+            .map_err(|err| io_to_read_error(err, id))?;
+        let proto = if let Some(compressed) = buf.strip_prefix(VIEW_FORMAT_HEADER) {
+            let buf = zstd::decode_all(compressed).map_err(|err| to_read_error(err.into(), id))?;
+            crate::protos::op_store::View::decode(&*buf)
+        } else {
+            // Views written by jj <= 0.29 are uncompressed
+            crate::protos::op_store::View::decode(&*buf)
+        };
+        proto.map_err(|err| to_read_error(err.into(), id))
+    }
+
+    fn read_ref_chunk(
+        &self,
+        chunk_id: &[u8],
+    ) -> Result<crate::protos::op_store::View, Box<dyn std::error::Error + Send + Sync>> {
+        let path = self.view_chunks_dir().join(hex::encode(chunk_id));
+        let compressed = fs::read(&path).context(&path)?;
+        let buf = zstd::decode_all(&*compressed).context(&path)?;
+        Ok(crate::protos::op_store::View::decode(&*buf)?)
+    }
+
+    /// Writes the chunk unless a chunk with the same content already exists,
+    /// and returns its id.
+    fn write_ref_chunk(&self, chunk: &crate::protos::op_store::View) -> Result<Vec<u8>, PathError> {
+        let dir = self.view_chunks_dir();
+        file_util::create_or_reuse_dir(&dir).context(&dir)?;
+        let buf = chunk.encode_to_vec();
+        let chunk_id = Blake2b512::digest(&buf).to_vec();
+        let compressed = zstd::encode_all(&*buf, 0).context(&dir)?;
+        persist_content_atomically(
+            &dir,
+            &hex::encode(&chunk_id),
+            &compressed,
+            Durability::Synced,
+        )?;
+        Ok(chunk_id)
+    }
+
+    /// Returns the operations recorded by the active pins of the repo this
+    /// store belongs to.
+    fn pinned_operation_ids(&self) -> Result<Vec<OperationId>, RepoPinError> {
+        let Some(repo_dir) = self.path.parent() else {
+            return Ok(vec![]);
+        };
+        let pins = RepoPinStore::for_repo(repo_dir).active_pins()?;
+        Ok(pins.into_iter().map(|pin| pin.operation_id).collect())
+    }
 }
 
 impl OpStore for SimpleOpStore {
@@ -151,34 +216,59 @@ impl OpStore for SimpleOpStore {
             return Ok(View::make_root(self.root_data.root_commit_id.clone()));
         }
 
-        let path = self.views_dir().join(id.hex());
-        let buf = fs::read(&path)
-            .context(&path)
-            .map_err(|err| io_to_read_error(err, id))?;
-
-        let proto = crate::protos::op_store::View::decode(&*buf)
-            .map_err(|err| to_read_error(err.into(), id))?;
+        let mut proto = self.read_view_proto(id)?;
+        for chunk_id in mem::take(&mut proto.ref_chunk_ids) {
+            let chunk = self
+                .read_ref_chunk(&chunk_id)
+                .map_err(|err| to_read_error(err, id))?;
+            proto.bookmarks.extend(chunk.bookmarks);
+            proto.tags.extend(chunk.tags);
+            proto.git_refs.extend(chunk.git_refs);
+        }
         Ok(view_from_proto(proto))
     }
 
     fn write_view(&self, view: &View) -> OpStoreResult<ViewId> {
         let dir = self.views_dir();
-        let temp_file = NamedTempFile::new_in(&dir)
-            .context(&dir)
-            .map_err(|err| io_to_write_error(err, "view"))?;
-
-        let proto = view_to_proto(view);
-        temp_file
-            .as_file()
-            .write_all(&proto.encode_to_vec())
-            .context(temp_file.path())
-            .map_err(|err| io_to_write_error(err, "view"))?;
-
+        let mut proto = view_to_proto(view);
         let id = ViewId::new(blake2b_hash(view).to_vec());
-
-        let new_path = dir.join(id.hex());
-        persist_content_addressed_temp_file(temp_file, &new_path)
-            .context(&new_path)
+        let num_refs = proto.bookmarks.len() + proto.tags.len() + proto.git_refs.len();
+        if num_refs >= REF_CHUNKING_THRESHOLD {
+            let chunks = itertools::chain!(
+                split_ref_chunks(mem::take(&mut proto.bookmarks), |r| &r.name).map(|bookmarks| {
+                    crate::protos::op_store::View {
+                        bookmarks,
+                        ..Default::default()
+                    }
+                }),
+                split_ref_chunks(mem::take(&mut proto.tags), |r| &r.name).map(|tags| {
+                    crate::protos::op_store::View {
+                        tags,
+                        ..Default::default()
+                    }
+                }),
+                split_ref_chunks(mem::take(&mut proto.git_refs), |r| &r.name).map(|git_refs| {
+                    crate::protos::op_store::View {
+                        git_refs,
+                        ..Default::default()
+                    }
+                }),
+            );
+            for chunk in chunks {
+                let chunk_id = self
+                    .write_ref_chunk(&chunk)
+                    .map_err(|err| io_to_write_error(err, "view"))?;
+                proto.ref_chunk_ids.push(chunk_id);
+            }
+        }
+        let mut content = VIEW_FORMAT_HEADER.to_vec();
+        zstd::stream::copy_encode(&*proto.encode_to_vec(), &mut content, 0).map_err(|err| {
+            OpStoreError::WriteObject {
+                object_type: "view",
+                source: err.into(),
+            }
+        })?;
+        persist_content_atomically(&dir, &id.hex(), &content, Durability::Synced)
             .map_err(|err| io_to_write_error(err, "view"))?;
         Ok(id)
     }
@@ -208,22 +298,9 @@ impl OpStore for SimpleOpStore {
     fn write_operation(&self, operation: &Operation) -> OpStoreResult<OperationId> {
         assert!(!operation.parents.is_empty());
         let dir = self.operations_dir();
-        let temp_file = NamedTempFile::new_in(&dir)
-            .context(&dir)
-            .map_err(|err| io_to_write_error(err, "operation"))?;
-
         let proto = operation_to_proto(operation);
-        temp_file
-            .as_file()
-            .write_all(&proto.encode_to_vec())
-            .context(temp_file.path())
-            .map_err(|err| io_to_write_error(err, "operation"))?;
-
         let id = OperationId::new(blake2b_hash(operation).to_vec());
-
-        let new_path = dir.join(id.hex());
-        persist_content_addressed_temp_file(temp_file, &new_path)
-            .context(&new_path)
+        persist_content_atomically(&dir, &id.hex(), &proto.encode_to_vec(), Durability::Synced)
             .map_err(|err| io_to_write_error(err, "operation"))?;
         Ok(id)
     }
@@ -298,12 +375,23 @@ impl OpStore for SimpleOpStore {
             }
         };
 
+        // Operations pinned by long-lived readers are kept as if they were
+        // heads. Pins referring to operations that no longer exist are ignored.
+        let pinned_ids = self
+            .pinned_operation_ids()
+            .map_err(|err| OpStoreError::Other(err.into()))?;
+        let pinned_ids = pinned_ids
+            .into_iter()
+            .filter(|id| !head_ids.contains(id))
+            .filter(|id| self.operations_dir().join(id.hex()).exists())
+            .collect_vec();
+
         // Reachable objects are resolved without considering the keep_newer
         // parameter. We could collect ancestors of the "new" operations here,
         // but more files can be added anyway after that.
         let read_op = |id: &OperationId| self.read_operation(id).map(|data| (id.clone(), data));
         let reachable_ops: HashMap<OperationId, Operation> = dag_walk::dfs_ok(
-            head_ids.iter().map(read_op),
+            head_ids.iter().chain(&pinned_ids).map(read_op),
             |(id, _)| id.clone(),
             |(_, data)| data.parents.iter().map(read_op).collect_vec(),
         )
@@ -353,10 +441,78 @@ impl OpStore for SimpleOpStore {
         };
         prune_views().map_err(|err| OpStoreError::Other(err.into()))?;
 
+        // Chunks are shared between views, so a chunk is kept if any of the
+        // reachable views refers to it.
+        let mut reachable_chunks: HashSet<String> = HashSet::new();
+        for id in reachable_views {
+            if *id == self.root_view_id {
+                continue;
+            }
+            let proto = self.read_view_proto(id)?;
+            reachable_chunks.extend(proto.ref_chunk_ids.iter().map(hex::encode));
+        }
+        let prune_chunks = || -> Result<(), PathError> {
+            let chunk_dir = self.view_chunks_dir();
+            let entries = match chunk_dir.read_dir() {
+                Ok(entries) => entries,
+                // Repos without any chunked views
+                Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+                Err(err) => return Err(err).context(&chunk_dir),
+            };
+            for entry in entries {
+                let entry = entry.context(&chunk_dir)?;
+                let Ok(name) = entry.file_name().into_string() else {
+                    tracing::trace!(?entry, "skipping invalid file name");
+                    continue;
+                };
+                if reachable_chunks.contains(&name) {
+                    continue;
+                }
+                remove_file_if_not_new(&entry)?;
+            }
+            Ok(())
+        };
+        prune_chunks().map_err(|err| OpStoreError::Other(err.into()))?;
+
         Ok(())
     }
 }
 
+/// Header of view files written in the compressed format. The rest of the file
+/// is a zstd-compressed `View` proto. Serialized protos can't start with a
+/// zero byte, so views written in the old format are told apart by the lack of
+/// this header.
+const VIEW_FORMAT_HEADER: &[u8] = b"\0jj-view\x02";
+
+/// Views with at least this many bookmarks, tags, and git refs store them in
+/// chunks that can be shared between views.
+const REF_CHUNKING_THRESHOLD: usize = 64;
+
+/// On average, one in this many refs ends a chunk.
+const REF_CHUNK_BOUNDARY_MODULUS: u8 = 32;
+
+/// Splits `refs` sorted by name into chunks.
+///
+/// Chunk boundaries only depend on the names of the refs, so adding, removing,
+/// or updating a ref changes only the chunk that contains it, and the other
+/// chunks can be shared with the previous view.
+fn split_ref_chunks<T>(refs: Vec<T>, name: impl Fn(&T) -> &String) -> impl Iterator<Item = Vec<T>> {
+    let mut chunks = vec![];
+    let mut current = vec![];
+    for r in refs {
+        let is_boundary =
+            Blake2b512::digest(name(&r).as_bytes())[0] % REF_CHUNK_BOUNDARY_MODULUS == 0;
+        current.push(r);
+        if is_boundary {
+            chunks.push(mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks.into_iter()
+}
+
 fn io_to_read_error(err: PathError, id: &impl ObjectId) -> OpStoreError {
     if err.error.kind() == ErrorKind::NotFound {
         OpStoreError::ObjectNotFound {
@@ -489,6 +645,37 @@ fn commit_predecessors_map_from_proto(
         .collect()
 }
 
+fn commit_rewrites_to_proto(
+    rewrites: &CommitRewrites,
+) -> Vec<crate::protos::op_store::CommitRewrite> {
+    rewrites
+        .pairs
+        .iter()
+        .map(
+            |(old_commit_id, new_commit_id)| crate::protos::op_store::CommitRewrite {
+                old_commit_id: old_commit_id.to_bytes(),
+                new_commit_id: new_commit_id.to_bytes(),
+            },
+        )
+        .collect()
+}
+
+fn commit_rewrites_from_proto(
+    proto: Vec<crate::protos::op_store::CommitRewrite>,
+    truncated: bool,
+) -> CommitRewrites {
+    let pairs = proto
+        .into_iter()
+        .map(|entry| {
+            (
+                CommitId::new(entry.old_commit_id),
+                CommitId::new(entry.new_commit_id),
+            )
+        })
+        .collect();
+    CommitRewrites { pairs, truncated }
+}
+
 fn operation_to_proto(operation: &Operation) -> crate::protos::op_store::Operation {
     let (commit_predecessors, stores_commit_predecessors) = match &operation.commit_predecessors {
         Some(map) => (commit_predecessors_map_to_proto(map), true),
@@ -500,6 +687,8 @@ fn operation_to_proto(operation: &Operation) -> crate::protos::op_store::Operati
         metadata: Some(operation_metadata_to_proto(&operation.metadata)),
         commit_predecessors,
         stores_commit_predecessors,
+        commit_rewrites: commit_rewrites_to_proto(&operation.commit_rewrites),
+        commit_rewrites_truncated: operation.commit_rewrites.truncated,
     };
     for parent in &operation.parents {
         proto.parents.push(parent.to_bytes());
@@ -520,11 +709,14 @@ fn operation_from_proto(
     let commit_predecessors = proto
         .stores_commit_predecessors
         .then(|| commit_predecessors_map_from_proto(proto.commit_predecessors));
+    let commit_rewrites =
+        commit_rewrites_from_proto(proto.commit_rewrites, proto.commit_rewrites_truncated);
     Ok(Operation {
         view_id,
         parents,
         metadata,
         commit_predecessors,
+        commit_rewrites,
     })
 }
 
@@ -537,9 +729,19 @@ fn view_to_proto(view: &View) -> crate::protos::op_store::View {
             .wc_commit_ids
             .insert(name.into(), commit_id.to_bytes());
     }
-    for head_id in &view.head_ids {
+    // Sort the heads so that the encoded view doesn't depend on the hash order
+    for head_id in view.head_ids.iter().sorted() {
         proto.head_ids.push(head_id.to_bytes());
     }
+    for pinned_id in &view.pinned_commits {
+        proto.pinned_commit_ids.push(pinned_id.to_bytes());
+    }
+    for (change_id, text) in &view.notes {
+        proto.notes.push(crate::protos::op_store::Note {
+            change_id: change_id.to_bytes(),
+            text: text.clone(),
+        });
+    }
 
     proto.bookmarks = bookmark_views_to_proto_legacy(&view.local_bookmarks, &view.remote_views);
 
@@ -560,6 +762,12 @@ fn view_to_proto(view: &View) -> crate::protos::op_store::View {
 
     proto.git_head = ref_target_to_proto(&view.git_head);
 
+    for (name, metadata) in &view.workspace_metadata {
+        proto
+            .workspace_metadata
+            .insert(name.into(), workspace_metadata_to_proto(metadata));
+    }
+
     proto
 }
 
@@ -582,6 +790,12 @@ fn view_from_proto(proto: crate::protos::op_store::View) -> View {
     for head_id_bytes in proto.head_ids {
         view.head_ids.insert(CommitId::new(head_id_bytes));
     }
+    for pinned_id_bytes in proto.pinned_commit_ids {
+        view.pinned_commits.insert(CommitId::new(pinned_id_bytes));
+    }
+    for note in proto.notes {
+        view.notes.insert(ChangeId::new(note.change_id), note.text);
+    }
 
     let (local_bookmarks, remote_views) = bookmark_views_from_proto_legacy(proto.bookmarks);
     view.local_bookmarks = local_bookmarks;
@@ -611,9 +825,39 @@ fn view_from_proto(proto: crate::protos::op_store::View) -> View {
         view.git_head = RefTarget::normal(CommitId::new(proto.git_head_legacy));
     }
 
+    for (name, metadata) in proto.workspace_metadata {
+        view.workspace_metadata.insert(
+            WorkspaceNameBuf::from(name),
+            workspace_metadata_from_proto(metadata),
+        );
+    }
+
     view
 }
 
+fn workspace_metadata_to_proto(
+    metadata: &WorkspaceMetadata,
+) -> crate::protos::op_store::WorkspaceMetadata {
+    crate::protos::op_store::WorkspaceMetadata {
+        description: metadata.description.clone(),
+        created_op_id: metadata
+            .created_op_id
+            .as_ref()
+            .map(|id| id.to_bytes())
+            .unwrap_or_default(),
+    }
+}
+
+fn workspace_metadata_from_proto(
+    proto: crate::protos::op_store::WorkspaceMetadata,
+) -> WorkspaceMetadata {
+    WorkspaceMetadata {
+        description: proto.description,
+        created_op_id: (!proto.created_op_id.is_empty())
+            .then(|| OperationId::new(proto.created_op_id)),
+    }
+}
+
 fn bookmark_views_to_proto_legacy(
     local_bookmarks: &BTreeMap<RefNameBuf, RefTarget>,
     remote_views: &BTreeMap<RemoteNameBuf, RemoteView>,
@@ -783,9 +1027,12 @@ fn remote_ref_state_from_proto(proto_value: Option<i32>) -> Option<RemoteRefStat
 
 #[cfg(test)]
 mod tests {
+    use std::slice;
+
     use insta::assert_snapshot;
     use itertools::Itertools as _;
     use maplit::btreemap;
+    use maplit::btreeset;
     use maplit::hashmap;
     use maplit::hashset;
 
@@ -839,6 +1086,15 @@ mod tests {
                 WorkspaceName::DEFAULT.to_owned() => default_wc_commit_id,
                 "test".into() => test_wc_commit_id,
             },
+            workspace_metadata: btreemap! {
+                WorkspaceName::DEFAULT.to_owned() => WorkspaceMetadata::default(),
+                "test".into() => WorkspaceMetadata {
+                    description: Some("for testing".to_owned()),
+                    created_op_id: Some(OperationId::from_hex("aaa111")),
+                },
+            },
+            pinned_commits: btreeset! {CommitId::from_hex("bbb111")},
+            notes: btreemap! {},
         }
     }
 
@@ -879,6 +1135,7 @@ mod tests {
                     CommitId::from_hex("444444"),
                 ],
             }),
+            commit_rewrites: CommitRewrites::default(),
         }
     }
 
@@ -887,7 +1144,9 @@ mod tests {
         // Test exact output so we detect regressions in compatibility
         assert_snapshot!(
             ViewId::new(blake2b_hash(&create_view()).to_vec()).hex(),
-            @"f426676b3a2f7c6b9ec8677cb05ed249d0d244ab7e86a7c51117e2d8a4829db65e55970c761231e2107d303bf3d33a1f2afdd4ed2181f223e99753674b20a35e"
+            @r"
+        8f61b3ce88cf44795eba3e927b19e4826f83a208b3250b4e8f19e975c654952b9de276e6190076b57bfbf834d8d83e2e25bad51903fa89291fcb89313eaee922
+        "
         );
     }
 
@@ -913,6 +1172,63 @@ mod tests {
         assert_eq!(read_view, view);
     }
 
+    #[test]
+    fn test_read_write_view_with_notes() {
+        let temp_dir = new_temp_dir();
+        let root_data = RootOperationData {
+            root_commit_id: CommitId::from_hex("000000"),
+        };
+        let store = SimpleOpStore::init(temp_dir.path(), root_data).unwrap();
+        let mut view = create_view();
+        view.notes = btreemap! {
+            ChangeId::from_hex("eee111") => "ci: passed".to_owned(),
+            ChangeId::from_hex("eee222") => "review: approved\n".to_owned(),
+        };
+        let view_id = store.write_view(&view).unwrap();
+        let read_view = store.read_view(&view_id).unwrap();
+        assert_eq!(read_view, view);
+        // Notes are part of the view id
+        assert_ne!(view_id, store.write_view(&create_view()).unwrap());
+    }
+
+    #[test]
+    fn test_view_to_proto_is_deterministic() {
+        // Build two equal views with many heads and workspaces inserted in
+        // opposite orders. They should be encoded to identical bytes.
+        let ids = (0..100)
+            .map(|i| CommitId::new(format!("{i:08}").into_bytes()))
+            .collect_vec();
+        let mut view1 = create_view();
+        let mut view2 = create_view();
+        for (i, id) in ids.iter().enumerate() {
+            view1.head_ids.insert(id.clone());
+            view1
+                .wc_commit_ids
+                .insert(format!("ws{i}").into(), id.clone());
+        }
+        for (i, id) in ids.iter().enumerate().rev() {
+            view2.head_ids.insert(id.clone());
+            view2
+                .wc_commit_ids
+                .insert(format!("ws{i}").into(), id.clone());
+        }
+        assert_eq!(view1, view2);
+        assert_eq!(
+            view_to_proto(&view1).encode_to_vec(),
+            view_to_proto(&view2).encode_to_vec()
+        );
+    }
+
+    #[test]
+    fn test_view_without_workspace_metadata() {
+        // Views written before the metadata was introduced have no entries.
+        let mut view = create_view();
+        let mut proto = view_to_proto(&view);
+        proto.workspace_metadata.clear();
+        view.workspace_metadata.clear();
+        assert_eq!(view_from_proto(proto), view);
+    }
+
     #[test]
     fn test_read_write_operation() {
         let temp_dir = new_temp_dir();
@@ -926,6 +1242,157 @@ mod tests {
         assert_eq!(read_operation, operation);
     }
 
+    #[test]
+    fn test_writes_are_synced() {
+        let temp_dir = new_temp_dir();
+        let root_data = RootOperationData {
+            root_commit_id: CommitId::from_hex("000000"),
+        };
+        let store = SimpleOpStore::init(temp_dir.path(), root_data).unwrap();
+        file_util::take_synced_dirs();
+        store.write_view(&create_view()).unwrap();
+        store.write_operation(&create_operation()).unwrap();
+        let synced_dirs = file_util::take_synced_dirs();
+        assert!(synced_dirs.contains(&store.views_dir()));
+        assert!(synced_dirs.contains(&store.operations_dir()));
+    }
+
+    fn create_view_with_many_refs(num_refs: usize) -> View {
+        let mut view = create_view();
+        for i in 0..num_refs {
+            let target = RefTarget::normal(CommitId::new(format!("{i:08}").into_bytes()));
+            view.local_bookmarks
+                .insert(format!("bookmark{i}").into(), target.clone());
+            view.tags.insert(format!("v{i}").into(), target.clone());
+            view.git_refs
+                .insert(format!("refs/heads/bookmark{i}").into(), target);
+        }
+        view
+    }
+
+    fn list_dir(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .sorted()
+            .collect()
+    }
+
+    #[test]
+    fn test_read_write_view_with_many_refs() {
+        let temp_dir = new_temp_dir();
+        let root_data = RootOperationData {
+            root_commit_id: CommitId::from_hex("000000"),
+        };
+        let store = SimpleOpStore::init(temp_dir.path(), root_data).unwrap();
+        let view = create_view_with_many_refs(1000);
+        let view_id = store.write_view(&view).unwrap();
+        assert!(store.view_chunks_dir().exists());
+        let read_view = store.read_view(&view_id).unwrap();
+        assert_eq!(read_view, view);
+
+        // Small views don't need chunks
+        let temp_dir = new_temp_dir();
+        let root_data = RootOperationData {
+            root_commit_id: CommitId::from_hex("000000"),
+        };
+        let store = SimpleOpStore::init(temp_dir.path(), root_data).unwrap();
+        store.write_view(&create_view()).unwrap();
+        assert!(!store.view_chunks_dir().exists());
+    }
+
+    #[test]
+    fn test_view_ref_chunks_are_shared() {
+        let temp_dir = new_temp_dir();
+        let root_data = RootOperationData {
+            root_commit_id: CommitId::from_hex("000000"),
+        };
+        let store = SimpleOpStore::init(temp_dir.path(), root_data).unwrap();
+        let view1 = create_view_with_many_refs(1000);
+        let view_id1 = store.write_view(&view1).unwrap();
+        let chunks1 = list_dir(&store.view_chunks_dir());
+        assert!(chunks1.len() > 3);
+
+        // Moving a bookmark only rewrites the chunk containing it
+        let mut view2 = view1.clone();
+        view2.local_bookmarks.insert(
+            "bookmark500".into(),
+            RefTarget::normal(CommitId::from_hex("eee111")),
+        );
+        let view_id2 = store.write_view(&view2).unwrap();
+        let chunks2 = list_dir(&store.view_chunks_dir());
+        assert_eq!(chunks2.len(), chunks1.len() + 1);
+        assert_eq!(store.read_view(&view_id1).unwrap(), view1);
+        assert_eq!(store.read_view(&view_id2).unwrap(), view2);
+
+        // The view file only refers to the chunks
+        let view_size = fs::metadata(store.views_dir().join(view_id2.hex()))
+            .unwrap()
+            .len();
+        let legacy_size = view_to_proto(&view2).encoded_len() as u64;
+        assert!(view_size * 10 < legacy_size);
+    }
+
+    #[test]
+    fn test_read_legacy_uncompressed_view() {
+        let temp_dir = new_temp_dir();
+        let root_data = RootOperationData {
+            root_commit_id: CommitId::from_hex("000000"),
+        };
+        let store = SimpleOpStore::init(temp_dir.path(), root_data).unwrap();
+        let view = create_view_with_many_refs(100);
+        let view_id = ViewId::new(blake2b_hash(&view).to_vec());
+        let path = store.views_dir().join(view_id.hex());
+        fs::write(path, view_to_proto(&view).encode_to_vec()).unwrap();
+        assert_eq!(store.read_view(&view_id).unwrap(), view);
+    }
+
+    #[test]
+    fn test_gc_view_ref_chunks() {
+        let temp_dir = new_temp_dir();
+        let root_data = RootOperationData {
+            root_commit_id: CommitId::from_hex("000000"),
+        };
+        let store = SimpleOpStore::init(temp_dir.path(), root_data).unwrap();
+        let write_op = |view: &View| {
+            let view_id = store.write_view(view).unwrap();
+            let operation = Operation {
+                view_id,
+                parents: vec![store.root_operation_id().clone()],
+                ..create_operation()
+            };
+            store.write_operation(&operation).unwrap()
+        };
+
+        let view1 = create_view_with_many_refs(1000);
+        let mut view2 = view1.clone();
+        view2.local_bookmarks.insert(
+            "bookmark500".into(),
+            RefTarget::normal(CommitId::from_hex("eee111")),
+        );
+        let _op_id1 = write_op(&view1);
+        let chunks1 = list_dir(&store.view_chunks_dir());
+        let op_id2 = write_op(&view2);
+        let chunks_all = list_dir(&store.view_chunks_dir());
+        assert_eq!(chunks_all.len(), chunks1.len() + 1);
+
+        // Only the chunk unique to the first view is removed
+        store
+            .gc(slice::from_ref(&op_id2), SystemTime::now())
+            .unwrap();
+        let chunks2 = list_dir(&store.view_chunks_dir());
+        assert_eq!(chunks2.len(), chunks1.len());
+        assert_eq!(
+            chunks1
+                .iter()
+                .filter(|name| !chunks2.contains(name))
+                .count(),
+            1
+        );
+        let view_id2 = store.read_operation(&op_id2).unwrap().view_id;
+        assert_eq!(store.read_view(&view_id2).unwrap(), view2);
+    }
+
     #[test]
     fn test_bookmark_views_legacy_roundtrip() {
         let new_remote_ref = |target: &RefTarget| RemoteRef {
@@ -120,7 +120,7 @@ impl SshBackend {
     }
 
     pub fn from_settings(settings: &UserSettings) -> Result<Self, ConfigGetError> {
-        let program = settings.get_string("signing.backends.ssh.program")?;
+        let program = settings.signing_ssh_program()?;
         let allowed_signers = settings
             .get_string("signing.backends.ssh.allowed-signers")
             .optional()?
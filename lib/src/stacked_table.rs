@@ -19,12 +19,18 @@
 //! concatenated after the keys. A file may have a parent file, and
 //! the parent may have its own parent, and so on. The child file then
 //! represents the union of the entries.
+//!
+//! The stack of files can be merged into a single file with
+//! [`TableStore::compact()`]. If the newest files are corrupt, they can be
+//! moved aside by loading the table with
+//! [`TableStore::get_head_with_recovery()`].
 
 #![allow(missing_docs)]
 
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io;
 use std::io::Read;
@@ -32,6 +38,8 @@ use std::io::Write as _;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::RwLock;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use blake2::Blake2b512;
 use blake2::Digest as _;
@@ -84,36 +92,70 @@ impl ReadonlyTable {
             name: name.clone(),
             err,
         };
-        let read_u32 = |file: &mut dyn Read| -> TableStoreResult<u32> {
-            let mut buf = [0; 4];
-            file.read_exact(&mut buf).map_err(to_load_err)?;
-            Ok(u32::from_le_bytes(buf))
-        };
-        let parent_filename_len = read_u32(file)?;
-        let maybe_parent_file = if parent_filename_len > 0 {
-            let mut parent_filename_bytes = vec![0; parent_filename_len as usize];
-            file.read_exact(&mut parent_filename_bytes)
-                .map_err(to_load_err)?;
-            let parent_filename = String::from_utf8(parent_filename_bytes).unwrap();
-            let parent_file = store.load_table(parent_filename)?;
-            Some(parent_file)
-        } else {
-            None
-        };
-        let num_local_entries = read_u32(file)? as usize;
+        let maybe_parent_file = read_parent_filename(file)
+            .map_err(to_load_err)?
+            .map(|parent_filename| store.load_table(parent_filename))
+            .transpose()?;
+        let num_local_entries = read_u32(file).map_err(to_load_err)? as usize;
         let index_size = num_local_entries * ReadonlyTableIndexEntry::size(key_size);
         let mut data = vec![];
         file.read_to_end(&mut data).map_err(to_load_err)?;
+        if data.len() < index_size {
+            return Err(to_load_err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "table index is truncated",
+            )));
+        }
         let values = data.split_off(index_size);
         let index = data;
-        Ok(Arc::new(ReadonlyTable {
+        let table = ReadonlyTable {
             key_size,
             parent_file: maybe_parent_file,
             name,
             num_local_entries,
             index,
             values,
-        }))
+        };
+        table
+            .validate_value_offsets()
+            .map_err(|err| TableStoreError::LoadSegment {
+                name: table.name.clone(),
+                err,
+            })?;
+        Ok(Arc::new(table))
+    }
+
+    /// Checks that the value offsets are in order and within the values, so
+    /// a truncated or corrupt file doesn't cause out-of-bounds lookups.
+    fn validate_value_offsets(&self) -> io::Result<()> {
+        let mut prev_offset = 0;
+        for pos in 0..=self.num_local_entries {
+            let offset = self.segment_value_offset_by_pos(pos);
+            if offset < prev_offset || offset > self.values.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "table value offsets are out of range",
+                ));
+            }
+            prev_offset = offset;
+        }
+        Ok(())
+    }
+
+    /// Returns this segment and its ancestors, from the newest to the oldest.
+    fn ancestors(self: &Arc<Self>) -> Vec<Arc<ReadonlyTable>> {
+        let mut segments = vec![self.clone()];
+        while let Some(parent_file) = &segments.last().unwrap().parent_file {
+            segments.push(parent_file.clone());
+        }
+        segments
+    }
+
+    /// Returns the size of the segment file in bytes, not counting the parent
+    /// file.
+    fn segment_file_size(&self) -> u64 {
+        let parent_filename_size = self.parent_file.as_ref().map_or(0, |file| file.name.len());
+        (4 + parent_filename_size + 4 + self.index.len() + self.values.len()) as u64
     }
 
     pub fn start_mutation(self: &Arc<Self>) -> MutableTable {
@@ -177,6 +219,25 @@ impl TableSegment for ReadonlyTable {
     }
 }
 
+fn read_u32(file: &mut dyn Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    file.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Reads the name of the parent file from the header of a segment file.
+fn read_parent_filename(file: &mut dyn Read) -> io::Result<Option<String>> {
+    let parent_filename_len = read_u32(file)?;
+    if parent_filename_len == 0 {
+        return Ok(None);
+    }
+    let mut parent_filename_bytes = vec![0; parent_filename_len as usize];
+    file.read_exact(&mut parent_filename_bytes)?;
+    let parent_filename = String::from_utf8(parent_filename_bytes)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok(Some(parent_filename))
+}
+
 struct ReadonlyTableIndexEntry<'table> {
     data: &'table [u8],
 }
@@ -398,6 +459,39 @@ pub enum TableStoreError {
 
 pub type TableStoreResult<T> = Result<T, TableStoreError>;
 
+/// A corrupt segment that was moved aside while loading the table.
+#[derive(Debug)]
+pub struct QuarantinedSegment {
+    /// Name of the segment file.
+    pub name: String,
+    /// Path the segment file was moved to, or `None` if the file was missing.
+    pub quarantine_path: Option<PathBuf>,
+    /// Why the segment couldn't be loaded.
+    pub error: TableStoreError,
+}
+
+/// Result of loading a table with recovery from corrupt segments.
+///
+/// If any segments were quarantined, their entries are lost, and `value`
+/// represents the newest intact ancestor of the corrupt segments.
+#[derive(Debug)]
+pub struct Recovered<T> {
+    pub value: T,
+    pub quarantined: Vec<QuarantinedSegment>,
+}
+
+/// Statistics about the stack of segments of a table.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TableStoreStats {
+    /// Number of segment files in the stack.
+    pub num_segments: usize,
+    /// Total size of the segment files in bytes.
+    pub total_size: u64,
+    /// Number of entries shadowed by an entry with the same key in a newer
+    /// segment. These are dropped by [`TableStore::compact()`].
+    pub num_dead_entries: usize,
+}
+
 pub struct TableStore {
     dir: PathBuf,
     key_size: usize,
@@ -485,20 +579,114 @@ impl TableStore {
         Ok(table)
     }
 
-    fn get_head_tables(&self) -> TableStoreResult<Vec<Arc<ReadonlyTable>>> {
+    /// Loads the head tables. If `quarantined` is set, corrupt head segments
+    /// are moved aside and replaced by their parents.
+    fn get_head_tables(
+        &self,
+        mut quarantined: Option<&mut Vec<QuarantinedSegment>>,
+    ) -> TableStoreResult<Vec<Arc<ReadonlyTable>>> {
         let mut tables = vec![];
         for head_entry in
             std::fs::read_dir(self.dir.join("heads")).map_err(TableStoreError::LoadHeads)?
         {
             let head_file_name = head_entry.map_err(TableStoreError::LoadHeads)?.file_name();
-            let table = self.load_table(head_file_name.to_str().unwrap().to_string())?;
-            tables.push(table);
+            let head_name = head_file_name.to_str().unwrap().to_string();
+            let table = match (self.load_table(head_name.clone()), &mut quarantined) {
+                (Ok(table), _) => Some(table),
+                (Err(err), Some(quarantined)) => self.recover_head(head_name, err, quarantined)?,
+                (Err(err), None) => return Err(err),
+            };
+            tables.extend(table);
         }
         Ok(tables)
     }
 
+    /// Moves the corrupt head segment `name` aside, and makes its newest
+    /// intact ancestor (if any) a head instead.
+    fn recover_head(
+        &self,
+        mut name: String,
+        mut err: TableStoreError,
+        quarantined: &mut Vec<QuarantinedSegment>,
+    ) -> TableStoreResult<Option<Arc<ReadonlyTable>>> {
+        loop {
+            // If an older ancestor is corrupt, it's unclear which of the
+            // segments on top of it should be dropped, so give up.
+            if !matches!(&err, TableStoreError::LoadSegment { name: failed, .. } if *failed == name)
+            {
+                return Err(err);
+            }
+            let (quarantine_path, parent_name) = self.quarantine_segment(&name)?;
+            quarantined.push(QuarantinedSegment {
+                name,
+                quarantine_path,
+                error: err,
+            });
+            let Some(parent_name) = parent_name else {
+                return Ok(None);
+            };
+            match self.load_table(parent_name.clone()) {
+                Ok(table) => {
+                    self.add_head(&table)?;
+                    return Ok(Some(table));
+                }
+                Err(parent_err) => {
+                    name = parent_name;
+                    err = parent_err;
+                }
+            }
+        }
+    }
+
+    /// Renames the segment file `name` to `<name>.corrupt-<timestamp>` and
+    /// removes it from the heads. Returns the new path of the file and the
+    /// name of its parent, if they could be determined.
+    fn quarantine_segment(
+        &self,
+        name: &str,
+    ) -> TableStoreResult<(Option<PathBuf>, Option<String>)> {
+        let path = self.dir.join(name);
+        let parent_name = File::open(&path)
+            .and_then(|mut file| read_parent_filename(&mut file))
+            .ok()
+            .flatten();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let quarantine_path = self.dir.join(format!("{name}.corrupt-{timestamp}"));
+        let quarantine_path = match std::fs::rename(&path, &quarantine_path) {
+            Ok(()) => Some(quarantine_path),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(TableStoreError::SaveSegment {
+                    name: name.to_owned(),
+                    err,
+                })
+            }
+        };
+        // The head file may not exist if this is an ancestor of a head.
+        std::fs::remove_file(self.dir.join("heads").join(name)).ok();
+        Ok((quarantine_path, parent_name))
+    }
+
     pub fn get_head(&self) -> TableStoreResult<Arc<ReadonlyTable>> {
-        let mut tables = self.get_head_tables()?;
+        self.get_head_impl(None)
+    }
+
+    /// Like [`Self::get_head()`], but moves corrupt head segments aside
+    /// instead of failing. The table falls back to the newest intact
+    /// ancestors of the corrupt segments, and the lost segments are reported.
+    pub fn get_head_with_recovery(&self) -> TableStoreResult<Recovered<Arc<ReadonlyTable>>> {
+        let mut quarantined = vec![];
+        let value = self.get_head_impl(Some(&mut quarantined))?;
+        Ok(Recovered { value, quarantined })
+    }
+
+    fn get_head_impl(
+        &self,
+        mut quarantined: Option<&mut Vec<QuarantinedSegment>>,
+    ) -> TableStoreResult<Arc<ReadonlyTable>> {
+        let mut tables = self.get_head_tables(quarantined.as_deref_mut())?;
 
         if tables.is_empty() {
             let empty_table = MutableTable::full(self.key_size);
@@ -513,14 +701,31 @@ impl TableStore {
             // head. Note that the locking isn't necessary for correctness; we
             // take the lock only to avoid other concurrent processes from doing
             // the same work (and producing another set of divergent heads).
-            let (table, _) = self.get_head_locked()?;
+            let (table, _) = self.get_head_locked_impl(quarantined)?;
             Ok(table)
         }
     }
 
     pub fn get_head_locked(&self) -> TableStoreResult<(Arc<ReadonlyTable>, FileLock)> {
+        self.get_head_locked_impl(None)
+    }
+
+    /// Like [`Self::get_head_locked()`], but recovers from corrupt head
+    /// segments as [`Self::get_head_with_recovery()`] does.
+    pub fn get_head_locked_with_recovery(
+        &self,
+    ) -> TableStoreResult<Recovered<(Arc<ReadonlyTable>, FileLock)>> {
+        let mut quarantined = vec![];
+        let value = self.get_head_locked_impl(Some(&mut quarantined))?;
+        Ok(Recovered { value, quarantined })
+    }
+
+    fn get_head_locked_impl(
+        &self,
+        quarantined: Option<&mut Vec<QuarantinedSegment>>,
+    ) -> TableStoreResult<(Arc<ReadonlyTable>, FileLock)> {
         let lock = self.lock()?;
-        let mut tables = self.get_head_tables()?;
+        let mut tables = self.get_head_tables(quarantined)?;
 
         if tables.is_empty() {
             let empty_table = MutableTable::full(self.key_size);
@@ -543,10 +748,51 @@ impl TableStore {
         }
         Ok((merged_table, lock))
     }
+
+    /// Returns statistics about the segments of the head table.
+    pub fn stats(&self) -> TableStoreResult<TableStoreStats> {
+        let head = self.get_head()?;
+        let segments = head.ancestors();
+        let mut keys = HashSet::new();
+        let mut num_entries = 0;
+        for segment in &segments {
+            for pos in 0..segment.num_local_entries {
+                keys.insert(ReadonlyTableIndexEntry::new(segment, pos).key());
+            }
+            num_entries += segment.num_local_entries;
+        }
+        Ok(TableStoreStats {
+            num_segments: segments.len(),
+            total_size: segments.iter().map(|s| s.segment_file_size()).sum(),
+            num_dead_entries: num_entries - keys.len(),
+        })
+    }
+
+    /// Merges all segments of the head table into a single segment, which
+    /// becomes the new head. Entries shadowed by newer segments are dropped.
+    ///
+    /// The new segment is written before the old head is removed, so
+    /// concurrent readers see either the old or the new head.
+    pub fn compact(&self) -> TableStoreResult<Arc<ReadonlyTable>> {
+        let (head, _lock) = self.get_head_locked()?;
+        if head.parent_file.is_none() {
+            return Ok(head);
+        }
+        let mut compacted = MutableTable::full(self.key_size);
+        for segment in head.ancestors().iter().rev() {
+            compacted.add_entries_from(segment.as_ref());
+        }
+        let table = self.save_table(compacted)?;
+        if table.name != head.name {
+            self.remove_head(&head);
+        }
+        Ok(table)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use assert_matches::assert_matches;
     use test_case::test_case;
 
     use super::*;
@@ -736,4 +982,107 @@ mod tests {
         let table = store.get_head().unwrap();
         assert_eq!(table.get_value(b"abc"), Some(b"value".as_slice()));
     }
+
+    /// Saves three segments with keys `0xx`, `1xx`, and `2xx`, without
+    /// squashing them. The newest segment also overwrites the key `000`.
+    fn save_three_segments(store: &TableStore) -> Vec<Arc<ReadonlyTable>> {
+        let mut segments = vec![];
+        for (prefix, num_entries) in [(0, 100), (1, 20), (2, 5)] {
+            let mut mut_table = store.get_head().unwrap().start_mutation();
+            for i in 0..num_entries {
+                mut_table.add_entry(
+                    format!("{prefix}{i:02}").into_bytes(),
+                    format!("value {prefix}{i:02}").into_bytes(),
+                );
+            }
+            if prefix == 2 {
+                mut_table.add_entry(b"000".to_vec(), b"new value".to_vec());
+            }
+            segments.push(store.save_table(mut_table).unwrap());
+        }
+        segments
+    }
+
+    #[test]
+    fn stacked_table_compact() {
+        let temp_dir = new_temp_dir();
+        let store = TableStore::init(temp_dir.path().to_path_buf(), 3);
+        let segments = save_three_segments(&store);
+
+        let stats = store.stats().unwrap();
+        assert_eq!(stats.num_segments, 3);
+        let file_size = |table: &Arc<ReadonlyTable>| {
+            std::fs::metadata(temp_dir.path().join(&table.name))
+                .unwrap()
+                .len()
+        };
+        assert_eq!(
+            stats.total_size,
+            segments.iter().map(file_size).sum::<u64>()
+        );
+        assert_eq!(stats.num_dead_entries, 1);
+
+        let table = store.compact().unwrap();
+        assert!(table.parent_file.is_none());
+        assert_eq!(table.num_entries(), 125);
+        let stats = store.stats().unwrap();
+        assert_eq!(stats.num_segments, 1);
+        assert_eq!(stats.num_dead_entries, 0);
+
+        // All live entries are preserved in a freshly loaded store
+        let store = TableStore::load(temp_dir.path().to_path_buf(), 3);
+        let table = store.get_head().unwrap();
+        assert_eq!(table.get_value(b"000"), Some(b"new value".as_slice()));
+        assert_eq!(table.get_value(b"099"), Some(b"value 099".as_slice()));
+        assert_eq!(table.get_value(b"119"), Some(b"value 119".as_slice()));
+        assert_eq!(table.get_value(b"204"), Some(b"value 204".as_slice()));
+    }
+
+    #[test]
+    fn stacked_table_recover_corrupt_head() {
+        let temp_dir = new_temp_dir();
+        let store = TableStore::init(temp_dir.path().to_path_buf(), 3);
+        let segments = save_three_segments(&store);
+
+        // Truncate the newest segment
+        let head_path = temp_dir.path().join(&segments[2].name);
+        let head_len = std::fs::metadata(&head_path).unwrap().len();
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .open(&head_path)
+            .unwrap();
+        file.set_len(head_len - 10).unwrap();
+        drop(file);
+
+        // Loading without recovery fails
+        let store = TableStore::load(temp_dir.path().to_path_buf(), 3);
+        assert_matches!(
+            store.get_head().err(),
+            Some(TableStoreError::LoadSegment { name, .. }) if name == segments[2].name
+        );
+
+        // Recovery falls back to the parent segment
+        let recovered = store.get_head_with_recovery().unwrap();
+        let table = recovered.value;
+        assert_eq!(table.name, segments[1].name);
+        assert_eq!(table.get_value(b"000"), Some(b"value 000".as_slice()));
+        assert_eq!(table.get_value(b"119"), Some(b"value 119".as_slice()));
+        assert_eq!(table.get_value(b"204"), None);
+        assert_eq!(recovered.quarantined.len(), 1);
+        let quarantined = &recovered.quarantined[0];
+        assert_eq!(quarantined.name, segments[2].name);
+        let quarantine_path = quarantined.quarantine_path.as_ref().unwrap();
+        assert!(quarantine_path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with(&format!("{}.corrupt-", segments[2].name)));
+        assert!(quarantine_path.is_file());
+        assert!(!head_path.exists());
+
+        // The parent is the new head
+        let store = TableStore::load(temp_dir.path().to_path_buf(), 3);
+        assert_eq!(store.get_head().unwrap().name, segments[1].name);
+    }
 }
@@ -15,20 +15,31 @@
 #![allow(missing_docs)]
 
 use std::any::Any;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
 use std::pin::Pin;
+use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::SystemTime;
 
 use clru::CLruCache;
+use futures::future::try_join_all;
 use futures::stream::BoxStream;
+use itertools::Itertools as _;
 use pollster::FutureExt as _;
 use tokio::io::AsyncRead;
 
 use crate::backend;
 use crate::backend::Backend;
+use crate::backend::BackendError;
 use crate::backend::BackendResult;
 use crate::backend::ChangeId;
 use crate::backend::CommitId;
@@ -39,13 +50,24 @@ use crate::backend::MergedTreeId;
 use crate::backend::SigningFn;
 use crate::backend::SymlinkId;
 use crate::backend::TreeId;
+use crate::backend::TreeValue;
 use crate::commit::Commit;
+use crate::content_hash::blake2b_hash;
+use crate::file_util::persist_atomically_with;
+use crate::file_util::Durability;
+use crate::file_util::IoResultExt as _;
+use crate::file_util::PathError;
 use crate::index::Index;
 use crate::merge::Merge;
 use crate::merge::MergedTreeValue;
 use crate::merged_tree::MergedTree;
+use crate::metrics::StoreMetrics;
+use crate::object_id::ObjectId as _;
+use crate::repo::Repo;
 use crate::repo_path::RepoPath;
 use crate::repo_path::RepoPathBuf;
+use crate::revset::ResolvedRevsetExpression;
+use crate::revset::RevsetEvaluationError;
 use crate::signing::Signer;
 use crate::tree::Tree;
 use crate::tree_builder::TreeBuilder;
@@ -55,6 +77,9 @@ use crate::tree_builder::TreeBuilder;
 pub(crate) const COMMIT_CACHE_CAPACITY: usize = 100;
 const TREE_CACHE_CAPACITY: usize = 1000;
 
+/// File next to the backend data that lists the shallow boundary commits.
+const SHALLOW_BOUNDARIES_FILE_NAME: &str = "shallow_boundaries";
+
 /// Wraps the low-level backend and makes it return more convenient types. Also
 /// adds caching.
 pub struct Store {
@@ -62,6 +87,27 @@ pub struct Store {
     signer: Signer,
     commit_cache: Mutex<CLruCache<CommitId, Arc<backend::Commit>>>,
     tree_cache: Mutex<CLruCache<(RepoPathBuf, TreeId), Arc<backend::Tree>>>,
+    queued_commits: Mutex<QueuedCommits>,
+    shallow_boundaries: Mutex<ShallowBoundaries>,
+    metrics: StoreMetrics,
+}
+
+/// Commits queued by [`WriteBatch`]es but not yet written to the backend.
+#[derive(Default)]
+struct QueuedCommits {
+    /// Lookup table of the commits queued by all batches. Since commit IDs are
+    /// content hashes, batches queuing the same commit can share the entry.
+    commit_map: HashMap<CommitId, Arc<backend::Commit>>,
+    /// Number of `write_commits_batch()` calls made so far.
+    flush_count: usize,
+}
+
+/// Commits whose history isn't available in the backend.
+#[derive(Default)]
+struct ShallowBoundaries {
+    /// Directory in which the boundaries are persisted, if any.
+    store_path: Option<PathBuf>,
+    ids: HashSet<CommitId>,
 }
 
 impl Debug for Store {
@@ -79,6 +125,9 @@ impl Store {
             signer,
             commit_cache: Mutex::new(CLruCache::new(COMMIT_CACHE_CAPACITY.try_into().unwrap())),
             tree_cache: Mutex::new(CLruCache::new(TREE_CACHE_CAPACITY.try_into().unwrap())),
+            queued_commits: Mutex::new(QueuedCommits::default()),
+            shallow_boundaries: Mutex::new(ShallowBoundaries::default()),
+            metrics: StoreMetrics::default(),
         })
     }
 
@@ -94,6 +143,11 @@ impl Store {
         &self.signer
     }
 
+    /// Counts of backend accesses made through this store.
+    pub fn metrics(&self) -> &StoreMetrics {
+        &self.metrics
+    }
+
     pub fn get_copy_records(
         &self,
         paths: Option<&[RepoPathBuf]>,
@@ -103,6 +157,10 @@ impl Store {
         self.backend.get_copy_records(paths, root, head)
     }
 
+    pub fn write_copy_records(&self, records: &[CopyRecord]) -> BackendResult<()> {
+        self.backend.write_copy_records(records)
+    }
+
     pub fn commit_id_length(&self) -> usize {
         self.backend.commit_id_length()
     }
@@ -151,6 +209,16 @@ impl Store {
                 return Ok(data);
             }
         }
+        if let Some(data) = self.queued_commits.lock().unwrap().commit_map.get(id) {
+            return Ok(data.clone());
+        }
+        if self.is_shallow_boundary(id) {
+            let data = Arc::new(self.make_shallow_boundary_commit(id));
+            let mut locked_cache = self.commit_cache.lock().unwrap();
+            locked_cache.put(id.clone(), data.clone());
+            return Ok(data);
+        }
+        self.metrics.record_commit_read();
         let commit = self.backend.read_commit(id).await?;
         let data = Arc::new(commit);
         let mut locked_cache = self.commit_cache.lock().unwrap();
@@ -158,12 +226,98 @@ impl Store {
         Ok(data)
     }
 
+    /// Returns the commits that stand in for the missing history of a shallow
+    /// repo, sorted by id.
+    pub fn shallow_boundaries(&self) -> Vec<CommitId> {
+        let boundaries = self.shallow_boundaries.lock().unwrap();
+        boundaries.ids.iter().cloned().sorted().collect()
+    }
+
+    /// Whether `id` is a shallow boundary commit. See
+    /// [`Store::set_shallow_boundaries()`].
+    pub fn is_shallow_boundary(&self, id: &CommitId) -> bool {
+        self.shallow_boundaries.lock().unwrap().ids.contains(id)
+    }
+
+    /// Marks the given commits as the boundary of a shallow history.
+    ///
+    /// These are typically parents of the oldest imported commits which don't
+    /// exist in the backend. Reading a boundary commit returns a stub instead
+    /// of reading it from the backend. The stub has an empty tree and the root
+    /// commit as its only parent, so it appears as a root of the imported
+    /// history in the index. The boundaries replace any previously set ones
+    /// and are persisted next to the backend data if the store was loaded
+    /// from disk.
+    pub fn set_shallow_boundaries(
+        &self,
+        ids: impl IntoIterator<Item = CommitId>,
+    ) -> BackendResult<()> {
+        let new_ids: HashSet<CommitId> = ids.into_iter().collect();
+        let mut boundaries = self.shallow_boundaries.lock().unwrap();
+        if let Some(store_path) = &boundaries.store_path {
+            persist_atomically_with(
+                store_path,
+                SHALLOW_BOUNDARIES_FILE_NAME,
+                Durability::Buffered,
+                |writer| {
+                    for id in new_ids.iter().sorted() {
+                        writeln!(writer, "{}", id.hex())?;
+                    }
+                    Ok(())
+                },
+            )
+            .map_err(|err| BackendError::Other(err.into()))?;
+        }
+        // Commits which became or stopped being boundaries read differently
+        let mut locked_cache = self.commit_cache.lock().unwrap();
+        for id in boundaries.ids.symmetric_difference(&new_ids) {
+            locked_cache.pop(id);
+        }
+        boundaries.ids = new_ids;
+        Ok(())
+    }
+
+    /// Loads the shallow boundaries persisted in `store_path`, and makes
+    /// [`Store::set_shallow_boundaries()`] persist them there.
+    pub(crate) fn load_shallow_boundaries(&self, store_path: &Path) -> Result<(), PathError> {
+        let path = store_path.join(SHALLOW_BOUNDARIES_FILE_NAME);
+        let ids = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .lines()
+                .map(|line| {
+                    CommitId::try_from_hex(line).map_err(|err| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Invalid shallow boundary commit id {line:?}: {err}"),
+                        )
+                    })
+                })
+                .try_collect()
+                .context(&path)?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => HashSet::new(),
+            Err(err) => return Err(err).context(&path),
+        };
+        let mut boundaries = self.shallow_boundaries.lock().unwrap();
+        boundaries.store_path = Some(store_path.to_owned());
+        boundaries.ids = ids;
+        Ok(())
+    }
+
+    fn make_shallow_boundary_commit(&self, id: &CommitId) -> backend::Commit {
+        // The change id only has to be stable, so derive it from the commit id
+        let change_id = ChangeId::new(blake2b_hash(id)[..self.change_id_length()].to_vec());
+        let mut commit = backend::make_root_commit(change_id, self.empty_tree_id().clone());
+        commit.parents = vec![self.root_commit_id().clone()];
+        commit
+    }
+
     pub async fn write_commit(
         self: &Arc<Self>,
         commit: backend::Commit,
         sign_with: Option<&mut SigningFn<'_>>,
     ) -> BackendResult<Commit> {
         assert!(!commit.parents.is_empty());
+        self.metrics.record_commit_write();
 
         let (commit_id, commit) = self.backend.write_commit(commit, sign_with).await?;
         let data = Arc::new(commit);
@@ -175,6 +329,54 @@ impl Store {
         Ok(Commit::new(self.clone(), commit_id, data))
     }
 
+    /// Like [`Store::write_commit()`], but queues the commit in `batch`
+    /// instead of writing it if the backend can compute the commit ID upfront
+    /// (see [`Backend::compute_commit_id()`]).
+    ///
+    /// Queued commits can be read back from the store before they're flushed.
+    pub async fn write_commit_batched(
+        self: &Arc<Self>,
+        batch: &mut WriteBatch,
+        mut commit: backend::Commit,
+        sign_with: Option<&mut SigningFn<'_>>,
+    ) -> BackendResult<Commit> {
+        assert!(Arc::ptr_eq(&batch.store, self));
+        if sign_with.is_none() {
+            if let Some(commit_id) = self.backend.compute_commit_id(&mut commit) {
+                assert!(!commit.parents.is_empty());
+                self.metrics.record_commit_write();
+                let data = Arc::new(commit);
+                batch.commits.push((commit_id.clone(), data.clone()));
+                {
+                    let mut queued = self.queued_commits.lock().unwrap();
+                    queued.commit_map.insert(commit_id.clone(), data.clone());
+                }
+                {
+                    let mut locked_cache = self.commit_cache.lock().unwrap();
+                    locked_cache.put(commit_id.clone(), data.clone());
+                }
+                return Ok(Commit::new(self.clone(), commit_id, data));
+            }
+        }
+        // Queued commits may be referenced by this commit.
+        batch.flush_async().await?;
+        self.write_commit(commit, sign_with).await
+    }
+
+    /// Starts a batch in which commit writes can be queued. See
+    /// [`Store::write_commit_batched()`].
+    pub fn begin_write_batch(self: &Arc<Self>) -> WriteBatch {
+        WriteBatch {
+            store: self.clone(),
+            commits: vec![],
+        }
+    }
+
+    /// Number of batched writes sent to the backend so far.
+    pub fn write_batch_flush_count(&self) -> usize {
+        self.queued_commits.lock().unwrap().flush_count
+    }
+
     pub fn get_tree(self: &Arc<Self>, dir: RepoPathBuf, id: &TreeId) -> BackendResult<Tree> {
         self.get_tree_async(dir, id).block_on()
     }
@@ -200,6 +402,7 @@ impl Store {
                 return Ok(data);
             }
         }
+        self.metrics.record_tree_read();
         let data = self.backend.read_tree(dir, id).await?;
         let data = Arc::new(data);
         let mut locked_cache = self.tree_cache.lock().unwrap();
@@ -225,6 +428,7 @@ impl Store {
         path: &RepoPath,
         tree: backend::Tree,
     ) -> BackendResult<Tree> {
+        self.metrics.record_tree_write();
         let tree_id = self.backend.write_tree(path, &tree).await?;
         let data = Arc::new(tree);
         {
@@ -235,6 +439,81 @@ impl Store {
         Ok(Tree::new(self.clone(), path.to_owned(), tree_id, data))
     }
 
+    /// Finds the paths at which the file with `file_id` appears in the trees
+    /// of `candidate_commits`.
+    ///
+    /// Each distinct tree is scanned at most once, so subtrees shared between
+    /// the commits (or between different paths in the same commit) don't need
+    /// to be visited again. Files which are only part of a legacy conflict
+    /// object are not found.
+    pub fn find_file_occurrences(
+        self: &Arc<Self>,
+        file_id: &FileId,
+        candidate_commits: impl Iterator<Item = CommitId>,
+    ) -> BackendResult<Vec<(CommitId, RepoPathBuf)>> {
+        let mut scanned_trees = HashMap::new();
+        let mut occurrences = vec![];
+        for commit_id in candidate_commits {
+            let commit = self.get_commit(&commit_id)?;
+            // A conflicted tree may contain the file at the same path in
+            // multiple terms
+            let mut paths = BTreeSet::new();
+            for tree_id in commit.tree_id().to_merge() {
+                let tree_paths = self.find_file_in_tree(
+                    RepoPath::root(),
+                    &tree_id,
+                    file_id,
+                    &mut scanned_trees,
+                )?;
+                paths.extend(tree_paths.iter().cloned());
+            }
+            occurrences.extend(paths.into_iter().map(|path| (commit_id.clone(), path)));
+        }
+        Ok(occurrences)
+    }
+
+    /// Returns the paths relative to the tree at which `file_id` appears.
+    fn find_file_in_tree(
+        &self,
+        dir: &RepoPath,
+        tree_id: &TreeId,
+        file_id: &FileId,
+        scanned_trees: &mut HashMap<TreeId, Arc<[RepoPathBuf]>>,
+    ) -> BackendResult<Arc<[RepoPathBuf]>> {
+        if let Some(paths) = scanned_trees.get(tree_id) {
+            return Ok(paths.clone());
+        }
+        let tree = self.get_backend_tree(dir, tree_id).block_on()?;
+        let mut paths = vec![];
+        for entry in tree.entries() {
+            let name = entry.name();
+            match entry.value() {
+                TreeValue::File { id, .. } if id == file_id => {
+                    paths.push(RepoPath::root().join(name));
+                }
+                TreeValue::Tree(sub_tree_id) => {
+                    let sub_paths = self.find_file_in_tree(
+                        &dir.join(name),
+                        sub_tree_id,
+                        file_id,
+                        scanned_trees,
+                    )?;
+                    paths.extend(sub_paths.iter().map(|sub_path| {
+                        sub_path
+                            .components()
+                            .fold(RepoPath::root().join(name), |path, component| {
+                                path.join(component)
+                            })
+                    }));
+                }
+                _ => {}
+            }
+        }
+        let paths: Arc<[RepoPathBuf]> = paths.into();
+        scanned_trees.insert(tree_id.clone(), paths.clone());
+        Ok(paths)
+    }
+
     pub async fn read_file(
         &self,
         path: &RepoPath,
@@ -243,6 +522,27 @@ impl Store {
         self.backend.read_file(path, id).await
     }
 
+    /// Tells the backend that the given files are about to be read. The files
+    /// are split into at most `max_concurrency` batches which are prefetched
+    /// concurrently.
+    pub async fn prefetch_files(
+        &self,
+        files: &[(RepoPathBuf, FileId)],
+        max_concurrency: usize,
+    ) -> BackendResult<()> {
+        if files.is_empty() {
+            return Ok(());
+        }
+        let batch_size = files.len().div_ceil(max_concurrency.max(1));
+        try_join_all(
+            files
+                .chunks(batch_size)
+                .map(|batch| self.backend.prefetch_files(batch)),
+        )
+        .await?;
+        Ok(())
+    }
+
     pub async fn write_file(
         &self,
         path: &RepoPath,
@@ -285,3 +585,79 @@ impl Store {
         self.backend.gc(index, keep_newer)
     }
 }
+
+/// Commit writes queued by a single writer, typically a `MutableRepo`.
+///
+/// The queued commits are written when the batch is flushed, finished, or
+/// dropped. Trees aren't queued since their IDs are needed to build the
+/// commits, and backends can usually write them without expensive bookkeeping.
+/// See [`Store::begin_write_batch()`].
+#[must_use]
+pub struct WriteBatch {
+    store: Arc<Store>,
+    /// Queued commits in order of creation.
+    commits: Vec<(CommitId, Arc<backend::Commit>)>,
+}
+
+impl WriteBatch {
+    /// Writes the queued commits to the backend.
+    pub fn flush(&mut self) -> BackendResult<()> {
+        self.flush_async().block_on()
+    }
+
+    async fn flush_async(&mut self) -> BackendResult<()> {
+        if self.commits.is_empty() {
+            return Ok(());
+        }
+        let commits = std::mem::take(&mut self.commits);
+        let ids = commits.iter().map(|(id, _)| id.clone()).collect_vec();
+        let result = self
+            .store
+            .backend
+            .write_commits_batch(
+                commits
+                    .into_iter()
+                    .map(|(id, data)| (id, Arc::unwrap_or_clone(data)))
+                    .collect(),
+            )
+            .await;
+        // Keep the commits readable until they've been written.
+        let mut queued = self.store.queued_commits.lock().unwrap();
+        queued.flush_count += 1;
+        for id in &ids {
+            queued.commit_map.remove(id);
+        }
+        result
+    }
+
+    /// Writes the queued commits and ends this batch.
+    pub fn finish(mut self) -> BackendResult<()> {
+        self.flush()
+    }
+}
+
+impl Drop for WriteBatch {
+    fn drop(&mut self) {
+        // The queued commits may already be referenced from elsewhere, so try
+        // to write them even if the batch wasn't finished properly.
+        if let Err(err) = self.flush() {
+            tracing::warn!(?err, "failed to flush write batch");
+        }
+    }
+}
+
+/// Finds the paths at which the file with `file_id` appears in the commits
+/// matched by `expression`.
+///
+/// See [`Store::find_file_occurrences()`] for details.
+pub fn find_file_occurrences_in_revset(
+    repo: &dyn Repo,
+    file_id: &FileId,
+    expression: &Rc<ResolvedRevsetExpression>,
+) -> Result<Vec<(CommitId, RepoPathBuf)>, RevsetEvaluationError> {
+    let commit_ids: Vec<CommitId> = expression.clone().evaluate(repo)?.iter().try_collect()?;
+    let occurrences = repo
+        .store()
+        .find_file_occurrences(file_id, commit_ids.into_iter())?;
+    Ok(occurrences)
+}
@@ -12,7 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Parsing trailers from commit messages.
+//! Parsing and formatting trailers in commit messages.
+//!
+//! A trailer line has the form `Key: Value`, where the key consists of ASCII
+//! alphanumeric characters and `-`. Spaces around the colon are allowed and
+//! are not part of the key or the value. A value can be continued on the
+//! following lines by starting them with a space; the continuation lines are
+//! kept as is, joined by `\n`. Trailing whitespace at the end of a value is
+//! dropped.
+//!
+//! Trailers formatted by [`format_with_trailers()`] are parsed back by
+//! [`parse_description_trailers()`] unchanged, provided that their keys are
+//! valid and that every line of their values but the first starts with a
+//! space.
+
+use std::fmt::Write as _;
 
 use itertools::Itertools as _;
 use thiserror::Error;
@@ -87,6 +101,30 @@ pub fn parse_trailers(body: &str) -> Result<Vec<Trailer>, TrailerParseError> {
     Ok(trailers)
 }
 
+/// Appends `trailers` to the trailer paragraph of `description`, or to a new
+/// paragraph if the description doesn't end with one.
+///
+/// Trailing whitespace is trimmed from the description before the trailers
+/// are added, and an empty description gets an empty first line so that the
+/// summary can be filled in later. The description is returned unchanged if
+/// there are no trailers to add.
+pub fn format_with_trailers(description: &str, trailers: &[Trailer]) -> String {
+    if trailers.is_empty() {
+        return description.to_owned();
+    }
+    let body = description.trim_ascii_end();
+    let mut output = body.to_owned();
+    output.push('\n');
+    if body.is_empty() || parse_description_trailers(body).is_empty() {
+        // create a new paragraph for the trailers
+        output.push('\n');
+    }
+    for trailer in trailers {
+        writeln!(output, "{}: {}", trailer.key, trailer.value).unwrap();
+    }
+    output
+}
+
 fn parse_trailers_impl(body: &str) -> (Vec<Trailer>, bool, bool, Option<String>) {
     // a trailer always comes at the end of a message; we can split the message
     // by newline, but we need to immediately reverse the order of the lines
@@ -138,6 +176,8 @@ fn parse_trailers_impl(body: &str) -> (Vec<Trailer>, bool, bool, Option<String>)
 
 #[cfg(test)]
 mod tests {
+    use std::slice;
+
     use indoc::indoc;
     use pretty_assertions::assert_eq;
 
@@ -452,4 +492,140 @@ mod tests {
         assert_eq!(trailers[0].key, "foo");
         assert_eq!(trailers[0].value, "bar");
     }
+
+    #[test]
+    fn test_format_with_trailers() {
+        let trailers = vec![
+            Trailer {
+                key: "Signed-off-by".to_owned(),
+                value: "Alice <alice@example.com>".to_owned(),
+            },
+            Trailer {
+                key: "Change-Id".to_owned(),
+                value: "I1234567890abcdef1234567890abcdef12345678".to_owned(),
+            },
+        ];
+
+        // no trailers to add
+        assert_eq!(format_with_trailers("subject", &[]), "subject");
+        // empty description
+        assert_eq!(
+            format_with_trailers("", &trailers),
+            indoc! {r#"
+
+
+                Signed-off-by: Alice <alice@example.com>
+                Change-Id: I1234567890abcdef1234567890abcdef12345678
+            "#}
+        );
+        // missing final newline
+        assert_eq!(
+            format_with_trailers("subject", &trailers[..1]),
+            indoc! {r#"
+                subject
+
+                Signed-off-by: Alice <alice@example.com>
+            "#}
+        );
+        // trailing blank lines
+        assert_eq!(
+            format_with_trailers("subject\n\nbody\n\n \n", &trailers[..1]),
+            indoc! {r#"
+                subject
+
+                body
+
+                Signed-off-by: Alice <alice@example.com>
+            "#}
+        );
+        // single paragraph looking like a trailer
+        assert_eq!(
+            format_with_trailers("fix: typo\n", &trailers[..1]),
+            indoc! {r#"
+                fix: typo
+
+                Signed-off-by: Alice <alice@example.com>
+            "#}
+        );
+        // existing trailer paragraph
+        assert_eq!(
+            format_with_trailers(
+                "subject\n\nSigned-off-by: Alice <alice@example.com>\n\n",
+                &trailers[1..]
+            ),
+            indoc! {r#"
+                subject
+
+                Signed-off-by: Alice <alice@example.com>
+                Change-Id: I1234567890abcdef1234567890abcdef12345678
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_trailers_round_trip() {
+        // canonical trailer blocks are reproduced exactly
+        let cases = [
+            ("subject\n\n", "Signed-off-by: Alice <alice@example.com>\n"),
+            (
+                "\n\n",
+                "Change-Id: I1234567890abcdef1234567890abcdef12345678\n",
+            ),
+            (
+                "subject\n\nbody\n\n",
+                indoc! {r#"
+                    Co-authored-by: Alice <alice@example.com>
+                    Co-authored-by: Alice <alice@example.com>
+                    Link: https://example.com/a:b?c=d
+                    X-2: multi-line
+                      value with
+                       continuation lines
+                    Reviewed-by: Bob
+                "#},
+            ),
+            ("subject\n\n", "Empty: \nReviewed-by: Bob\n"),
+        ];
+        for (body, block) in cases {
+            let description = format!("{body}{block}");
+            let trailers = parse_description_trailers(&description);
+            assert!(!trailers.is_empty());
+            assert_eq!(format_with_trailers(body, &trailers), description);
+        }
+
+        // other valid trailer blocks are normalized, but the parsed trailers
+        // are preserved
+        let descriptions = [
+            "subject\n\nKey :value\nother-key  :   spaced value   \n",
+            indoc! {r#"
+                subject
+
+                Key :value
+                other-key  :   spaced value
+            "#},
+            indoc! {r#"
+                subject
+
+                some non-trailer text
+                Signed-off-by: Alice <alice@example.com>
+                  continued
+                (cherry picked from commit 72bb9f9cf4bbb6bbb11da9cda4499c55c44e87b9)
+            "#},
+        ];
+        for description in descriptions {
+            let trailers = parse_description_trailers(description);
+            assert!(!trailers.is_empty());
+            let formatted = format_with_trailers("subject\n", &trailers);
+            assert_eq!(parse_description_trailers(&formatted), trailers);
+            // appending to the original paragraph keeps the existing trailers
+            let extra = Trailer {
+                key: "Change-Id".to_owned(),
+                value: "I1234567890abcdef1234567890abcdef12345678".to_owned(),
+            };
+            let formatted = format_with_trailers(description, slice::from_ref(&extra));
+            assert_eq!(
+                parse_description_trailers(&formatted),
+                [trailers, vec![extra]].concat()
+            );
+        }
+    }
 }
@@ -14,29 +14,36 @@
 
 #![allow(missing_docs)]
 
+use std::slice;
 use std::sync::Arc;
 
 use itertools::Itertools as _;
 use thiserror::Error;
 
 use crate::backend::Timestamp;
-use crate::dag_walk;
 use crate::index::IndexWriteError;
 use crate::index::ReadonlyIndex;
+use crate::metrics::TransactionMetrics;
 use crate::op_heads_store::OpHeadsStore;
 use crate::op_heads_store::OpHeadsStoreError;
 use crate::op_store;
 use crate::op_store::OpStoreError;
 use crate::op_store::OperationMetadata;
 use crate::operation::Operation;
+use crate::refs::RefMergeConflict;
 use crate::repo::MutableRepo;
 use crate::repo::ReadonlyRepo;
 use crate::repo::Repo as _;
 use crate::repo::RepoLoader;
 use crate::repo::RepoLoaderError;
+use crate::rewrite_policy::RewriteDeniedError;
 use crate::settings::UserSettings;
 use crate::view::View;
 
+/// Operation tag summarizing the bookmarks and tags which became conflicted
+/// by merging concurrent operations, e.g. "2 bookmark conflicts".
+pub const REF_MERGE_CONFLICTS_TAG: &str = "ref-conflicts";
+
 /// Error from attempts to write and publish transaction.
 #[derive(Debug, Error)]
 #[error("Failed to commit new operation")]
@@ -44,6 +51,43 @@ pub enum TransactionCommitError {
     IndexWrite(#[from] IndexWriteError),
     OpHeadsStore(#[from] OpHeadsStoreError),
     OpStore(#[from] OpStoreError),
+    RewriteDenied(#[from] RewriteDeniedError),
+    HookVeto(#[from] HookVeto),
+}
+
+/// Error returned by [`TransactionHooks::pre_commit()`] to prevent a
+/// transaction from being committed.
+#[derive(Debug, Error)]
+#[error("Transaction was vetoed: {message}")]
+pub struct HookVeto {
+    pub message: String,
+}
+
+impl HookVeto {
+    pub fn new(message: impl Into<String>) -> Self {
+        HookVeto {
+            message: message.into(),
+        }
+    }
+}
+
+/// Callbacks invoked whenever a transaction is written to the operation store.
+///
+/// Hooks are registered by the embedding application with
+/// [`RepoLoader::set_transaction_hooks()`], and are shared by all repos loaded
+/// from the loader. They also run for the merge operations created when
+/// divergent operations are reconciled.
+pub trait TransactionHooks: Send + Sync {
+    /// Called before anything is written. Returning an error aborts the
+    /// transaction.
+    fn pre_commit(&self, _repo: &MutableRepo, _description: &str) -> Result<(), HookVeto> {
+        Ok(())
+    }
+
+    /// Called after the operation has been published, i.e. once it's visible
+    /// as an op head. Operations that are left unpublished don't trigger this
+    /// hook.
+    fn post_commit(&self, _op: &Operation) {}
 }
 
 /// An in-memory representation of a repo and any changes being made to it.
@@ -94,14 +138,16 @@ impl Transaction {
     }
 
     pub fn merge_operation(&mut self, other_op: Operation) -> Result<(), RepoLoaderError> {
-        let ancestor_op = dag_walk::closest_common_node_ok(
-            self.parent_ops.iter().cloned().map(Ok),
-            [Ok(other_op.clone())],
-            |op: &Operation| op.id().clone(),
-            |op: &Operation| op.parents().collect_vec(),
-        )?
-        .unwrap();
         let repo_loader = self.base_repo().loader();
+        let parent_op_ids = self
+            .parent_ops
+            .iter()
+            .map(|op| op.id().clone())
+            .collect_vec();
+        let ancestor_op = repo_loader
+            .op_graph()
+            .common_ancestor_of(&parent_op_ids, slice::from_ref(other_op.id()))?
+            .unwrap();
         let base_repo = repo_loader.load_at(&ancestor_op)?;
         let other_repo = repo_loader.load_at(&other_op)?;
         self.parent_ops.push(other_op);
@@ -129,18 +175,32 @@ impl Transaction {
         mut self,
         description: impl Into<String>,
     ) -> Result<UnpublishedOperation, TransactionCommitError> {
-        let mut_repo = self.mut_repo;
+        let mut mut_repo = self.mut_repo;
         // TODO: Should we instead just do the rebasing here if necessary?
         assert!(
             !mut_repo.has_rewrites(),
             "BUG: Descendants have not been rebased after the last rewrites."
         );
+        if let Some(err) = mut_repo.take_rewrite_denied() {
+            return Err(err.into());
+        }
+        let description = description.into();
+        let hooks = mut_repo.base_repo().loader().transaction_hooks().cloned();
+        if let Some(hooks) = &hooks {
+            hooks.pre_commit(&mut_repo, &description)?;
+        }
+        if let Some(summary) = RefMergeConflict::summarize(mut_repo.ref_merge_conflicts()) {
+            self.op_metadata
+                .tags
+                .insert(REF_MERGE_CONFLICTS_TAG.to_owned(), summary);
+        }
         let base_repo = mut_repo.base_repo().clone();
-        let (mut_index, view, predecessors) = mut_repo.consume();
+        let metrics = mut_repo.metrics();
+        let (mut_index, view, predecessors, rewrites) = mut_repo.consume();
 
         let operation = {
             let view_id = base_repo.op_store().write_view(view.store_view())?;
-            self.op_metadata.description = description.into();
+            self.op_metadata.description = description;
             self.op_metadata.end_time = self.end_time.unwrap_or_else(Timestamp::now);
             let parents = self.parent_ops.iter().map(|op| op.id().clone()).collect();
             let store_operation = op_store::Operation {
@@ -148,13 +208,15 @@ impl Transaction {
                 parents,
                 metadata: self.op_metadata,
                 commit_predecessors: Some(predecessors),
+                commit_rewrites: rewrites,
             };
             let new_op_id = base_repo.op_store().write_operation(&store_operation)?;
             Operation::new(base_repo.op_store().clone(), new_op_id, store_operation)
         };
 
         let index = base_repo.index_store().write_index(mut_index, &operation)?;
-        let unpublished = UnpublishedOperation::new(base_repo.loader(), operation, view, index);
+        let unpublished =
+            UnpublishedOperation::new(base_repo.loader(), operation, view, index, metrics);
         Ok(unpublished)
     }
 }
@@ -193,6 +255,7 @@ pub fn create_op_metadata(
 pub struct UnpublishedOperation {
     op_heads_store: Arc<dyn OpHeadsStore>,
     repo: Arc<ReadonlyRepo>,
+    metrics: TransactionMetrics,
 }
 
 impl UnpublishedOperation {
@@ -201,10 +264,12 @@ impl UnpublishedOperation {
         operation: Operation,
         view: View,
         index: Box<dyn ReadonlyIndex>,
+        metrics: TransactionMetrics,
     ) -> Self {
         UnpublishedOperation {
             op_heads_store: repo_loader.op_heads_store().clone(),
             repo: repo_loader.create_from(operation, view, index),
+            metrics,
         }
     }
 
@@ -212,10 +277,20 @@ impl UnpublishedOperation {
         self.repo.operation()
     }
 
+    /// Metrics collected while the transaction was open.
+    pub fn metrics(&self) -> &TransactionMetrics {
+        &self.metrics
+    }
+
     pub fn publish(self) -> Result<Arc<ReadonlyRepo>, TransactionCommitError> {
-        let _lock = self.op_heads_store.lock()?;
-        self.op_heads_store
-            .update_op_heads(self.operation().parent_ids(), self.operation().id())?;
+        {
+            let _lock = self.op_heads_store.lock()?;
+            self.op_heads_store
+                .update_op_heads(self.operation().parent_ids(), self.operation().id())?;
+        }
+        if let Some(hooks) = self.repo.loader().transaction_hooks() {
+            hooks.post_commit(self.operation());
+        }
         Ok(self.repo)
     }
 
@@ -15,17 +15,20 @@
 #![allow(missing_docs)]
 
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashSet;
 
 use itertools::Itertools as _;
 use thiserror::Error;
 
+use crate::backend::ChangeId;
 use crate::backend::CommitId;
 use crate::op_store;
 use crate::op_store::BookmarkTarget;
 use crate::op_store::RefTarget;
 use crate::op_store::RefTargetOptionExt as _;
 use crate::op_store::RemoteRef;
+use crate::op_store::WorkspaceMetadata;
 use crate::ref_name::GitRefName;
 use crate::ref_name::GitRefNameBuf;
 use crate::ref_name::RefName;
@@ -51,6 +54,10 @@ impl View {
         }
     }
 
+    /// Working-copy commits by workspace name.
+    ///
+    /// The map is ordered by name, so iteration order doesn't depend on the
+    /// order in which the workspaces were added.
     pub fn wc_commit_ids(&self) -> &BTreeMap<WorkspaceNameBuf, CommitId> {
         &self.data.wc_commit_ids
     }
@@ -59,6 +66,8 @@ impl View {
         self.data.wc_commit_ids.get(name)
     }
 
+    /// Returns the names of the workspaces editing `commit_id` in
+    /// lexicographical order.
     pub fn workspaces_for_wc_commit_id(&self, commit_id: &CommitId) -> Vec<WorkspaceNameBuf> {
         let mut workspace_names = vec![];
         for (name, wc_commit_id) in &self.data.wc_commit_ids {
@@ -73,19 +82,69 @@ impl View {
         self.data.wc_commit_ids.values().contains(commit_id)
     }
 
+    /// Iterates workspace names and their metadata in lexicographical order.
+    ///
+    /// The metadata is `None` for workspaces created before it was recorded.
+    pub fn workspaces(&self) -> impl Iterator<Item = (&WorkspaceName, Option<&WorkspaceMetadata>)> {
+        self.data
+            .wc_commit_ids
+            .keys()
+            .map(|name| (name.as_ref(), self.data.workspace_metadata.get(name)))
+    }
+
+    pub fn all_workspace_metadata(&self) -> &BTreeMap<WorkspaceNameBuf, WorkspaceMetadata> {
+        &self.data.workspace_metadata
+    }
+
+    pub fn workspace_metadata(&self, name: &WorkspaceName) -> Option<&WorkspaceMetadata> {
+        self.data.workspace_metadata.get(name)
+    }
+
+    /// Head commits. The iteration order is unspecified, so sort the ids if
+    /// they affect output or the order in which commits are processed.
     pub fn heads(&self) -> &HashSet<CommitId> {
         &self.data.head_ids
     }
 
+    /// Commits that are kept visible even if they're not reachable from the
+    /// heads.
+    pub fn pinned_commits(&self) -> &BTreeSet<CommitId> {
+        &self.data.pinned_commits
+    }
+
+    pub fn is_pinned(&self, commit_id: &CommitId) -> bool {
+        self.data.pinned_commits.contains(commit_id)
+    }
+
+    /// Notes attached to changes.
+    pub fn notes(&self) -> &BTreeMap<ChangeId, String> {
+        &self.data.notes
+    }
+
+    pub fn get_note(&self, change_id: &ChangeId) -> Option<&str> {
+        self.data.notes.get(change_id).map(String::as_str)
+    }
+
+    /// Iterates heads and pinned commits, which together determine the visible
+    /// commits.
+    ///
+    /// The iteration order is unspecified, and pinned commits may be
+    /// ancestors of the heads.
+    pub fn visible_head_ids(&self) -> impl Iterator<Item = &CommitId> {
+        self.data.head_ids.iter().chain(&self.data.pinned_commits)
+    }
+
     /// Iterates pair of local and remote bookmarks by bookmark name.
     pub fn bookmarks(&self) -> impl Iterator<Item = (&RefName, BookmarkTarget<'_>)> {
         op_store::merge_join_bookmark_views(&self.data.local_bookmarks, &self.data.remote_views)
     }
 
+    /// Tags ordered by name.
     pub fn tags(&self) -> &BTreeMap<RefNameBuf, RefTarget> {
         &self.data.tags
     }
 
+    /// Git refs ordered by full ref name.
     pub fn git_refs(&self) -> &BTreeMap<GitRefNameBuf, RefTarget> {
         &self.data.git_refs
     }
@@ -98,8 +157,18 @@ impl View {
         self.data.wc_commit_ids.insert(name, commit_id);
     }
 
+    /// Removes the working-copy commit and metadata of the workspace.
     pub fn remove_wc_commit(&mut self, name: &WorkspaceName) {
         self.data.wc_commit_ids.remove(name);
+        self.data.workspace_metadata.remove(name);
+    }
+
+    pub fn set_workspace_metadata(&mut self, name: WorkspaceNameBuf, metadata: WorkspaceMetadata) {
+        self.data.workspace_metadata.insert(name, metadata);
+    }
+
+    pub fn remove_workspace_metadata(&mut self, name: &WorkspaceName) {
+        self.data.workspace_metadata.remove(name);
     }
 
     pub fn rename_workspace(
@@ -117,6 +186,11 @@ impl View {
                 name: old_name.to_owned(),
             }
         })?;
+        if let Some(metadata) = self.data.workspace_metadata.remove(old_name) {
+            self.data
+                .workspace_metadata
+                .insert(new_name.clone(), metadata);
+        }
         self.data.wc_commit_ids.insert(new_name, wc_commit_id);
         Ok(())
     }
@@ -129,6 +203,22 @@ impl View {
         self.data.head_ids.remove(head_id);
     }
 
+    pub fn pin_commit(&mut self, commit_id: CommitId) {
+        self.data.pinned_commits.insert(commit_id);
+    }
+
+    pub fn unpin_commit(&mut self, commit_id: &CommitId) {
+        self.data.pinned_commits.remove(commit_id);
+    }
+
+    pub fn set_note(&mut self, change_id: ChangeId, text: String) {
+        self.data.notes.insert(change_id, text);
+    }
+
+    pub fn remove_note(&mut self, change_id: &ChangeId) {
+        self.data.notes.remove(change_id);
+    }
+
     /// Iterates local bookmark `(name, target)`s in lexicographical order.
     pub fn local_bookmarks(&self) -> impl Iterator<Item = (&RefName, &RefTarget)> {
         self.data
@@ -174,7 +264,7 @@ impl View {
     }
 
     /// Iterates over `(symbol, remote_ref)` for all remote bookmarks in
-    /// lexicographical order.
+    /// lexicographical order of `(name, remote)`.
     pub fn all_remote_bookmarks(&self) -> impl Iterator<Item = (RemoteRefSymbol<'_>, &RemoteRef)> {
         op_store::flatten_remote_bookmarks(&self.data.remote_views)
     }
@@ -381,6 +471,9 @@ impl View {
             git_refs,
             git_head,
             wc_commit_ids,
+            workspace_metadata: _,
+            pinned_commits,
+            notes: _,
         } = &self.data;
         itertools::chain!(
             head_ids,
@@ -394,7 +487,8 @@ impl View {
             }),
             git_refs.values().flat_map(ref_target_ids),
             ref_target_ids(git_head),
-            wc_commit_ids.values()
+            wc_commit_ids.values(),
+            pinned_commits
         )
     }
 
@@ -18,23 +18,24 @@
 use std::any::Any;
 use std::collections::BTreeMap;
 use std::ffi::OsString;
+use std::ops::ControlFlow;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use itertools::Itertools as _;
 use thiserror::Error;
 use tracing::instrument;
 
 use crate::backend::BackendError;
 use crate::backend::MergedTreeId;
 use crate::commit::Commit;
+use crate::conflicts::ConflictLabels;
 use crate::conflicts::ConflictMarkerStyle;
-use crate::dag_walk;
 use crate::fsmonitor::FsmonitorSettings;
 use crate::gitignore::GitIgnoreError;
 use crate::gitignore::GitIgnoreFile;
 use crate::matchers::EverythingMatcher;
 use crate::matchers::Matcher;
+use crate::merged_tree::MergedTree;
 use crate::op_store::OpStoreError;
 use crate::op_store::OperationId;
 use crate::operation::Operation;
@@ -73,6 +74,20 @@ pub trait WorkingCopy: Send {
     /// that all files should be checked out.
     fn sparse_patterns(&self) -> Result<&[RepoPathBuf], WorkingCopyStateError>;
 
+    /// Returns paths matching the `matcher` that might have changed in the
+    /// working copy compared to `base_tree`, without snapshotting.
+    ///
+    /// This is a heuristic: the result is a superset of the paths a snapshot
+    /// would report as changed, and includes new files that aren't ignored.
+    /// Nothing is written to the store and the working-copy state isn't
+    /// updated.
+    fn changed_paths_since(
+        &self,
+        base_tree: &MergedTree,
+        matcher: &dyn Matcher,
+        options: &ChangedPathsOptions,
+    ) -> Result<Vec<RepoPathBuf>, SnapshotError>;
+
     /// Locks the working copy and returns an instance with methods for updating
     /// the working copy files and state.
     fn start_mutation(&self) -> Result<Box<dyn LockedWorkingCopy>, WorkingCopyStateError>;
@@ -120,12 +135,30 @@ pub trait LockedWorkingCopy {
     ) -> Result<(MergedTreeId, SnapshotStats), SnapshotError>;
 
     /// Check out the specified commit in the working copy.
+    ///
+    /// If a previous checkout was interrupted, it is finished first, even if
+    /// the commit's tree is the one that was being checked out.
     fn check_out(
         &mut self,
         commit: &Commit,
         options: &CheckoutOptions,
     ) -> Result<CheckoutStats, CheckoutError>;
 
+    /// Writes the conflict markers of the conflicted files matching `matcher`
+    /// which were left as placeholders by a checkout with
+    /// `CheckoutOptions::lazy_conflicts` set. Placeholders which have been
+    /// modified since the checkout are skipped.
+    ///
+    /// The default implementation does nothing, which is correct for working
+    /// copies that always materialize conflicts on checkout.
+    fn materialize_conflicts(
+        &mut self,
+        _matcher: &dyn Matcher,
+        _options: &CheckoutOptions,
+    ) -> Result<CheckoutStats, CheckoutError> {
+        Ok(CheckoutStats::default())
+    }
+
     /// Update the workspace name.
     fn rename_workspace(&mut self, new_workspace_name: WorkspaceNameBuf);
 
@@ -184,6 +217,11 @@ pub enum SnapshotError {
     /// Checking path with ignore patterns failed.
     #[error(transparent)]
     GitIgnoreError(#[from] GitIgnoreError),
+    /// A previous checkout was interrupted, so the files in the working copy
+    /// may not match the recorded state. Checking out the working-copy commit
+    /// again finishes the interrupted checkout.
+    #[error("The working copy has an interrupted checkout")]
+    InterruptedCheckout,
     /// Some other error happened while snapshotting the working copy.
     #[error("{message}")]
     Other {
@@ -238,6 +276,30 @@ impl SnapshotOptions<'_> {
     }
 }
 
+/// Options used when querying changed paths in the working copy. Some of them
+/// may be ignored by some `WorkingCopy` implementations.
+#[derive(Clone)]
+pub struct ChangedPathsOptions {
+    /// The `.gitignore`s to use while looking for new files.
+    pub base_ignores: Arc<GitIgnoreFile>,
+    /// The fsmonitor (e.g. Watchman) to use, if any.
+    pub fsmonitor_settings: FsmonitorSettings,
+    /// Whether to compare the contents of files whose stat information is
+    /// inconclusive. If false, such files are reported as changed.
+    pub compare_contents: bool,
+}
+
+impl ChangedPathsOptions {
+    /// Create an instance for use in tests.
+    pub fn empty_for_test() -> Self {
+        ChangedPathsOptions {
+            base_ignores: GitIgnoreFile::empty(),
+            fsmonitor_settings: FsmonitorSettings::None,
+            compare_contents: false,
+        }
+    }
+}
+
 /// A callback for getting progress updates.
 pub type SnapshotProgress<'a> = dyn Fn(&RepoPath) + 'a + Sync;
 
@@ -264,20 +326,46 @@ pub enum UntrackedReason {
 
 /// Options used when checking out a tree in the working copy.
 #[derive(Clone)]
-pub struct CheckoutOptions {
+pub struct CheckoutOptions<'a> {
     /// Conflict marker style to use when materializing files
     pub conflict_marker_style: ConflictMarkerStyle,
+    /// Labels to annotate the sides of materialized conflicts with. They're
+    /// ignored for conflicts with a different number of sides.
+    pub conflict_labels: ConflictLabels,
+    /// If true, conflicted files are written as placeholders, and the conflict
+    /// markers are only written by `LockedWorkingCopy::materialize_conflicts()`.
+    pub lazy_conflicts: bool,
+    /// A callback for the UI to display progress, which can also cancel the
+    /// checkout.
+    pub progress: Option<&'a CheckoutProgressCallback<'a>>,
 }
 
-impl CheckoutOptions {
+impl CheckoutOptions<'_> {
     /// Create an instance for use in tests.
     pub fn empty_for_test() -> Self {
         CheckoutOptions {
             conflict_marker_style: ConflictMarkerStyle::default(),
+            conflict_labels: ConflictLabels::unlabeled(),
+            lazy_conflicts: false,
+            progress: None,
         }
     }
 }
 
+/// Progress of a checkout.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CheckoutProgress {
+    /// The number of paths which have been updated so far.
+    pub processed_files: usize,
+    /// The total number of paths to update.
+    pub total_files: usize,
+}
+
+/// A callback for getting checkout progress updates. If it returns
+/// `ControlFlow::Break`, the checkout is cancelled with
+/// [`CheckoutError::Cancelled`].
+pub type CheckoutProgressCallback<'a> = dyn Fn(CheckoutProgress) -> ControlFlow<()> + 'a + Sync;
+
 /// Stats about a checkout operation on a working copy. All "files" mentioned
 /// below may also be symlinks or materialized conflicts.
 #[derive(Debug, PartialEq, Eq, Clone, Default)]
@@ -323,6 +411,10 @@ pub enum CheckoutError {
     /// Reading or writing from the commit backend failed.
     #[error("Internal backend error")]
     InternalBackendError(#[from] BackendError),
+    /// The checkout was cancelled by the progress callback. The working copy
+    /// is left partially updated, and the next checkout will finish the work.
+    #[error("Checkout was cancelled")]
+    Cancelled,
     /// Some other error happened while checking out the working copy.
     #[error("{message}")]
     Other {
@@ -389,13 +481,11 @@ impl WorkingCopyFreshness {
         } else {
             let wc_operation = repo.loader().load_operation(locked_wc.old_operation_id())?;
             let repo_operation = repo.operation();
-            let ancestor_op = dag_walk::closest_common_node_ok(
-                [Ok(wc_operation.clone())],
-                [Ok(repo_operation.clone())],
-                |op: &Operation| op.id().clone(),
-                |op: &Operation| op.parents().collect_vec(),
-            )?
-            .expect("unrelated operations");
+            let ancestor_op = repo
+                .loader()
+                .op_graph()
+                .common_ancestor(wc_operation.id(), repo_operation.id())?
+                .expect("unrelated operations");
             if ancestor_op.id() == repo_operation.id() {
                 // The working copy was updated since we loaded the repo. The repo must be
                 // reloaded at the working copy's operation.
@@ -23,8 +23,10 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
 
+use itertools::Itertools as _;
 use thiserror::Error;
 
+use crate::backend::BackendError;
 use crate::backend::BackendInitError;
 use crate::backend::MergedTreeId;
 use crate::commit::Commit;
@@ -32,8 +34,11 @@ use crate::file_util::IoResultExt as _;
 use crate::file_util::PathError;
 use crate::local_working_copy::LocalWorkingCopy;
 use crate::local_working_copy::LocalWorkingCopyFactory;
+use crate::object_id::ObjectId as _;
 use crate::op_heads_store::OpHeadsStoreError;
+use crate::op_store::OpStoreError;
 use crate::op_store::OperationId;
+use crate::op_store::WorkspaceMetadata;
 use crate::ref_name::WorkspaceName;
 use crate::ref_name::WorkspaceNameBuf;
 use crate::repo::read_store_type;
@@ -46,6 +51,7 @@ use crate::repo::ReadonlyRepo;
 use crate::repo::Repo as _;
 use crate::repo::RepoInitError;
 use crate::repo::RepoLoader;
+use crate::repo::RewriteRootCommit;
 use crate::repo::StoreFactories;
 use crate::repo::StoreLoadError;
 use crate::repo::SubmoduleStoreInitializer;
@@ -58,8 +64,12 @@ use crate::working_copy::CheckoutError;
 use crate::working_copy::CheckoutOptions;
 use crate::working_copy::CheckoutStats;
 use crate::working_copy::LockedWorkingCopy;
+use crate::working_copy::SnapshotError;
+use crate::working_copy::SnapshotOptions;
+use crate::working_copy::SnapshotStats;
 use crate::working_copy::WorkingCopy;
 use crate::working_copy::WorkingCopyFactory;
+use crate::working_copy::WorkingCopyFreshness;
 use crate::working_copy::WorkingCopyStateError;
 
 #[derive(Error, Debug)]
@@ -139,6 +149,13 @@ fn init_working_copy(
     let mut tx = repo.start_transaction();
     tx.repo_mut()
         .check_out(workspace_name.clone(), &repo.store().root_commit())?;
+    tx.repo_mut().set_workspace_metadata(
+        workspace_name.clone(),
+        WorkspaceMetadata {
+            description: None,
+            created_op_id: Some(repo.op_id().clone()),
+        },
+    );
     let repo = tx.commit(format!("add workspace '{}'", workspace_name.as_symbol()))?;
 
     let working_copy = working_copy_factory.init_working_copy(
@@ -478,6 +495,25 @@ impl LockedWorkspace<'_> {
         self.base.working_copy = new_wc;
         Ok(())
     }
+
+    /// Checks out `new_commit` in the stale working copy which was left at
+    /// `stale_commit`, and records the working copy at `operation_id`.
+    pub fn update_stale(
+        mut self,
+        operation_id: OperationId,
+        stale_commit: &Commit,
+        new_commit: &Commit,
+        options: &CheckoutOptions,
+    ) -> Result<CheckoutStats, UpdateStaleError> {
+        // If the working copy isn't at the stale commit, it was updated by
+        // another process in the meantime.
+        if stale_commit.tree_id() != self.locked_wc().old_tree_id() {
+            return Err(UpdateStaleError::ConcurrentWorkingCopyOperation);
+        }
+        let stats = self.locked_wc().check_out(new_commit, options)?;
+        self.finish(operation_id)?;
+        Ok(stats)
+    }
 }
 
 // Factory trait to build WorkspaceLoaders given the workspace root.
@@ -498,6 +534,7 @@ pub fn get_working_copy_factory<'a>(
         Err(StoreLoadError::UnsupportedType {
             store: "working copy",
             store_type: working_copy_type.to_string(),
+            known_types: working_copy_factories.keys().cloned().sorted().collect(),
         })
     }
 }
@@ -623,3 +660,192 @@ pub fn default_working_copy_factories() -> WorkingCopyFactories {
 pub fn default_working_copy_factory() -> Box<dyn WorkingCopyFactory> {
     Box::new(LocalWorkingCopyFactory {})
 }
+
+#[derive(Error, Debug)]
+pub enum UpdateStaleError {
+    #[error("Concurrent working copy operation. Try again.")]
+    ConcurrentWorkingCopyOperation,
+    #[error(r#""{}" doesn't have a working-copy commit"#, .0.as_symbol())]
+    WorkspaceMissingWorkingCopy(WorkspaceNameBuf),
+    #[error(
+        "The repo was loaded at operation {}, which seems to be a sibling of the working copy's \
+         operation {}",
+        repo_op_id.hex(),
+        wc_op_id.hex()
+    )]
+    SiblingOperation {
+        repo_op_id: OperationId,
+        wc_op_id: OperationId,
+    },
+    #[error(transparent)]
+    Backend(#[from] BackendError),
+    #[error(transparent)]
+    OpStore(#[from] OpStoreError),
+    #[error(transparent)]
+    WorkingCopyState(#[from] WorkingCopyStateError),
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error(transparent)]
+    Checkout(#[from] CheckoutError),
+    #[error(transparent)]
+    RewriteRootCommit(#[from] RewriteRootCommit),
+    #[error(transparent)]
+    TransactionCommit(#[from] TransactionCommitError),
+}
+
+/// Result of [`update_stale_working_copy()`].
+#[derive(Debug)]
+pub enum StaleRecoveryOutcome {
+    /// The working copy wasn't stale, so it was left alone.
+    NotStale,
+    /// The working copy was updated to the working-copy commit of the repo.
+    Updated {
+        /// The repo at the operation the working copy was updated to. It's a
+        /// new operation if the local changes were carried over or recorded
+        /// in a recovery commit.
+        repo: Arc<ReadonlyRepo>,
+        /// The commit checked out in the working copy.
+        wc_commit: Commit,
+        /// What happened to the changes made in the stale working copy.
+        local_changes: StaleLocalChanges,
+        snapshot_stats: SnapshotStats,
+        checkout_stats: CheckoutStats,
+    },
+}
+
+/// What happened to the changes made in a stale working copy.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StaleLocalChanges {
+    /// There were no changes.
+    None,
+    /// The changes were merged into the working-copy commit.
+    Carried,
+    /// The working-copy commit already contained the changes, so there was
+    /// nothing to keep.
+    Discarded,
+    /// The changes conflicted with the working-copy commit, so the files in
+    /// the working copy were recorded as they were in a new commit on top of
+    /// it.
+    RecoveryCommit,
+}
+
+const STALE_RECOVERY_COMMIT_DESCRIPTION: &str = "RECOVERY COMMIT FROM STALE WORKING COPY
+
+This commit contains the files of a stale working copy whose changes conflicted
+with the working-copy commit it was updated to. The diff compared to the parent
+may include reverts of the changes made to the working-copy commit since the
+working copy was last updated.
+";
+
+/// Updates the stale working copy of the `workspace` to the working-copy
+/// commit of the `repo`, keeping the changes made in the working copy.
+///
+/// The working copy is snapshotted first, and the changes compared to the
+/// commit it was last updated to are merged into the new working-copy commit.
+/// If the changes conflict, the snapshot is recorded in a new commit on top of
+/// the working-copy commit instead. The operation recording the changes is
+/// committed before any file is touched, so if the process is interrupted, the
+/// working copy stays stale and the update can be run again.
+///
+/// Fails with [`UpdateStaleError::SiblingOperation`] if the working copy was
+/// updated at an operation concurrent to the operation of the `repo`. The repo
+/// should be reloaded at the merged operation heads in that case.
+///
+/// Fails with [`OpStoreError`] if the operation the working copy was last
+/// updated at is missing. [`create_and_check_out_recovery_commit()`] can be
+/// used to recover from that.
+///
+/// [`create_and_check_out_recovery_commit()`]: crate::working_copy::create_and_check_out_recovery_commit
+pub fn update_stale_working_copy(
+    workspace: &mut Workspace,
+    repo: &Arc<ReadonlyRepo>,
+    snapshot_options: &SnapshotOptions,
+    checkout_options: &CheckoutOptions,
+) -> Result<StaleRecoveryOutcome, UpdateStaleError> {
+    let workspace_name = workspace.workspace_name().to_owned();
+    let missing_wc_commit =
+        || UpdateStaleError::WorkspaceMissingWorkingCopy(workspace_name.clone());
+    let wc_commit_id = repo
+        .view()
+        .get_wc_commit_id(&workspace_name)
+        .ok_or_else(missing_wc_commit)?;
+    let wc_commit = repo.store().get_commit(wc_commit_id)?;
+
+    let mut locked_ws = workspace.start_working_copy_mutation()?;
+    match WorkingCopyFreshness::check_stale(locked_ws.locked_wc(), &wc_commit, repo)? {
+        WorkingCopyFreshness::Fresh | WorkingCopyFreshness::Updated(_) => {
+            return Ok(StaleRecoveryOutcome::NotStale);
+        }
+        WorkingCopyFreshness::WorkingCopyStale => {}
+        WorkingCopyFreshness::SiblingOperation => {
+            return Err(UpdateStaleError::SiblingOperation {
+                repo_op_id: repo.op_id().clone(),
+                wc_op_id: locked_ws.locked_wc().old_operation_id().clone(),
+            });
+        }
+    }
+    let stale_op = repo
+        .loader()
+        .load_operation(locked_ws.locked_wc().old_operation_id())?;
+    let stale_commit_id = stale_op
+        .view()?
+        .get_wc_commit_id(&workspace_name)
+        .cloned()
+        .ok_or_else(missing_wc_commit)?;
+    let stale_commit = repo.store().get_commit(&stale_commit_id)?;
+    if stale_commit.tree_id() != locked_ws.locked_wc().old_tree_id() {
+        return Err(UpdateStaleError::ConcurrentWorkingCopyOperation);
+    }
+
+    let (snapshot_tree_id, snapshot_stats) = locked_ws.locked_wc().snapshot(snapshot_options)?;
+    let (repo, new_wc_commit, local_changes) = if snapshot_tree_id == *stale_commit.tree_id() {
+        (repo.clone(), wc_commit, StaleLocalChanges::None)
+    } else {
+        let snapshot_tree = repo.store().get_root_tree(&snapshot_tree_id)?;
+        let merged_tree = wc_commit
+            .tree()?
+            .merge(&stale_commit.tree()?, &snapshot_tree)?;
+        if merged_tree.id() == *wc_commit.tree_id() {
+            (repo.clone(), wc_commit, StaleLocalChanges::Discarded)
+        } else {
+            let mut tx = repo.start_transaction();
+            let (new_commit, local_changes) = if merged_tree.has_conflict() {
+                let new_commit = tx
+                    .repo_mut()
+                    .new_commit(vec![wc_commit.id().clone()], snapshot_tree_id)
+                    .set_description(STALE_RECOVERY_COMMIT_DESCRIPTION)
+                    .write()?;
+                tx.repo_mut()
+                    .set_wc_commit(workspace_name.clone(), new_commit.id().clone())?;
+                (new_commit, StaleLocalChanges::RecoveryCommit)
+            } else {
+                let new_commit = tx
+                    .repo_mut()
+                    .rewrite_commit(&wc_commit)
+                    .set_tree_id(merged_tree.id())
+                    .write()?;
+                tx.repo_mut().rebase_descendants()?;
+                (new_commit, StaleLocalChanges::Carried)
+            };
+            let repo = tx.commit(format!(
+                "update stale working copy of workspace {}",
+                workspace_name.as_symbol()
+            ))?;
+            (repo, new_commit, local_changes)
+        }
+    };
+
+    let checkout_stats = locked_ws.update_stale(
+        repo.op_id().clone(),
+        &stale_commit,
+        &new_wc_commit,
+        checkout_options,
+    )?;
+    Ok(StaleRecoveryOutcome::Updated {
+        repo,
+        wc_commit: new_wc_commit,
+        local_changes,
+        snapshot_stats,
+        checkout_stats,
+    })
+}
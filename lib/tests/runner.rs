@@ -6,20 +6,26 @@ fn test_no_forgotten_test_files() {
     testutils::assert_no_forgotten_test_files(&test_dir);
 }
 
+mod test_absorb;
 mod test_annotate;
+mod test_backend_test_suite;
 mod test_bad_locking;
 mod test_commit_builder;
 mod test_commit_concurrent;
+mod test_config_lint;
 mod test_conflicts;
 mod test_default_revset_graph_iterator;
 mod test_evolution_predecessors;
+mod test_files;
 mod test_fix;
 mod test_git;
 mod test_git_backend;
+mod test_gitattributes;
 mod test_gpg;
 mod test_id_prefix;
 mod test_index;
 mod test_init;
+mod test_leased_op_heads_store;
 mod test_load_repo;
 mod test_local_working_copy;
 mod test_local_working_copy_concurrent;
@@ -28,12 +34,20 @@ mod test_merge_trees;
 mod test_merged_tree;
 mod test_mut_repo;
 mod test_operations;
+mod test_ref_name;
 mod test_refs;
+mod test_repo_pin;
+mod test_repo_stats;
+mod test_retrying_backend;
 mod test_revset;
 mod test_rewrite;
 mod test_rewrite_duplicate;
+mod test_rewrite_policy;
 mod test_rewrite_transform;
+mod test_settings_schema;
 mod test_signing;
 mod test_ssh_signing;
+mod test_store;
+mod test_transaction_hooks;
 mod test_view;
 mod test_workspace;
@@ -0,0 +1,307 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::convert::Infallible;
+
+use assert_matches::assert_matches;
+use itertools::Itertools as _;
+use jj_lib::absorb;
+use jj_lib::absorb::AbsorbDestination;
+use jj_lib::absorb::AbsorbError;
+use jj_lib::absorb::AbsorbFile;
+use jj_lib::absorb::AbsorbHunk;
+use jj_lib::absorb::AbsorbSource;
+use jj_lib::absorb::Fixup;
+use jj_lib::backend::CommitId;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::repo::Repo as _;
+use jj_lib::revset::ResolvedRevsetExpression;
+use jj_lib::rewrite;
+use jj_lib::rewrite_policy::AffectedCommits;
+use pollster::FutureExt as _;
+use testutils::create_tree;
+use testutils::repo_path;
+use testutils::TestRepo;
+
+#[test]
+fn test_absorb_plan_and_apply() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let file_path = repo_path("file");
+
+    let mut tx = repo.start_transaction();
+    let tree1 = create_tree(repo, &[(file_path, "1a\n1b\n")]);
+    let tree2 = create_tree(repo, &[(file_path, "1a\n1b\n2a\n2b\n")]);
+    let tree3 = create_tree(repo, &[(file_path, "1A\n1b\n2a\n2B\n")]);
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let commit1 = tx
+        .repo_mut()
+        .new_commit(vec![root_commit_id], tree1.id())
+        .write()
+        .unwrap();
+    let commit2 = tx
+        .repo_mut()
+        .new_commit(vec![commit1.id().clone()], tree2.id())
+        .write()
+        .unwrap();
+    let commit3 = tx
+        .repo_mut()
+        .new_commit(vec![commit2.id().clone()], tree3.id())
+        .write()
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    let source = AbsorbSource::from_commit(repo.as_ref(), commit3.clone()).unwrap();
+    let destinations =
+        ResolvedRevsetExpression::commits(vec![commit1.id().clone(), commit2.id().clone()]);
+    let plan = absorb::plan(repo.as_ref(), &source, &destinations, &EverythingMatcher)
+        .block_on()
+        .unwrap();
+    assert_eq!(plan.operation_id, *repo.op_id());
+    assert_eq!(plan.source_commit_id, *commit3.id());
+    assert_eq!(
+        plan.destinations,
+        [
+            AbsorbDestination {
+                commit_id: commit2.id().clone(),
+                files: vec![AbsorbFile {
+                    path: file_path.to_owned(),
+                    hunks: vec![AbsorbHunk {
+                        left_lines: 3..4,
+                        right_lines: 3..4,
+                        left_range: 9..12,
+                        right_range: 9..12,
+                        preview: "2B".into(),
+                    }],
+                }],
+            },
+            AbsorbDestination {
+                commit_id: commit1.id().clone(),
+                files: vec![AbsorbFile {
+                    path: file_path.to_owned(),
+                    hunks: vec![AbsorbHunk {
+                        left_lines: 0..1,
+                        right_lines: 0..1,
+                        left_range: 0..3,
+                        right_range: 0..3,
+                        preview: "1A".into(),
+                    }],
+                }],
+            },
+        ]
+    );
+    assert_eq!(plan.skipped_paths, []);
+
+    // The plan is stable if the repo didn't change
+    let plan2 = absorb::plan(repo.as_ref(), &source, &destinations, &EverythingMatcher)
+        .block_on()
+        .unwrap();
+    assert_eq!(plan2, plan);
+
+    // Applying the plan rewrites the planned destinations
+    let mut tx = repo.start_transaction();
+    let stats = absorb::apply(tx.repo_mut(), &plan).block_on().unwrap();
+    assert_eq!(
+        stats
+            .rewritten_destinations
+            .iter()
+            .rev()
+            .map(|commit| commit.store_commit().predecessors.clone())
+            .collect_vec(),
+        plan.destinations
+            .iter()
+            .map(|destination| vec![destination.commit_id.clone(), commit3.id().clone()])
+            .collect_vec()
+    );
+    let [new_commit1, new_commit2] = &stats.rewritten_destinations[..] else {
+        panic!("unexpected rewritten commits");
+    };
+    assert_eq!(
+        *new_commit1.tree_id(),
+        create_tree(&repo, &[(file_path, "1A\n1b\n")]).id()
+    );
+    assert_eq!(*new_commit2.tree_id(), tree3.id());
+    // The source commit became empty and was abandoned
+    assert!(stats.rewritten_source.is_none());
+    assert_eq!(stats.num_rebased, 0);
+    tx.repo_mut().rebase_descendants().unwrap();
+    let repo = tx.commit("absorb").unwrap();
+
+    // The plan can't be applied once the repo has changed
+    let mut tx = repo.start_transaction();
+    assert_matches!(
+        absorb::apply(tx.repo_mut(), &plan).block_on(),
+        Err(AbsorbError::StalePlan { plan_operation_id, repo_operation_id })
+            if plan_operation_id == plan.operation_id && repo_operation_id == *repo.op_id()
+    );
+}
+
+#[test]
+fn test_absorb_plan_affected_commits() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let file_path = repo_path("file");
+
+    let mut tx = repo.start_transaction();
+    let tree1 = create_tree(repo, &[(file_path, "1a\n1b\n")]);
+    let tree2 = create_tree(repo, &[(file_path, "1a\n1b\n2a\n2b\n")]);
+    let tree3 = create_tree(repo, &[(file_path, "1A\n1b\n2A\n2B\n")]);
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let commit1 = tx
+        .repo_mut()
+        .new_commit(vec![root_commit_id], tree1.id())
+        .write()
+        .unwrap();
+    let commit2 = tx
+        .repo_mut()
+        .new_commit(vec![commit1.id().clone()], tree2.id())
+        .write()
+        .unwrap();
+    let commit3 = tx
+        .repo_mut()
+        .new_commit(vec![commit2.id().clone()], tree3.id())
+        .write()
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    let source = AbsorbSource::from_commit(repo.as_ref(), commit3.clone()).unwrap();
+    let destinations =
+        ResolvedRevsetExpression::commits(vec![commit1.id().clone(), commit2.id().clone()]);
+    let mut plan = absorb::plan(repo.as_ref(), &source, &destinations, &EverythingMatcher)
+        .block_on()
+        .unwrap();
+
+    // Partition the destinations, treating commit1 as immutable
+    let is_immutable = |commit_id: &CommitId| Ok::<_, Infallible>(commit_id == commit1.id());
+    let affected = plan.affected_commits(is_immutable).unwrap();
+    assert_eq!(
+        affected,
+        AffectedCommits {
+            rewritable: vec![(commit2.id().clone(), 1)],
+            immutable: vec![(commit1.id().clone(), 1)],
+        }
+    );
+    assert!(affected.has_immutable());
+    assert_eq!(affected.num_immutable_changes(), 1);
+
+    // Errors from the predicate are propagated
+    assert_eq!(plan.affected_commits(|_| Err("error")), Err("error"));
+
+    // Skipping the immutable destination leaves its hunk in the source
+    plan.retain_destinations(|commit_id| commit_id != commit1.id());
+    let mut tx = repo.start_transaction();
+    let stats = absorb::apply(tx.repo_mut(), &plan).block_on().unwrap();
+    let [new_commit2] = &stats.rewritten_destinations[..] else {
+        panic!("unexpected rewritten commits");
+    };
+    assert_eq!(new_commit2.store_commit().predecessors[0], *commit2.id());
+    assert_eq!(
+        *new_commit2.tree_id(),
+        create_tree(&repo, &[(file_path, "1a\n1b\n2A\n2B\n")]).id()
+    );
+    let new_commit3 = stats.rewritten_source.unwrap();
+    assert_eq!(*new_commit3.tree_id(), tree3.id());
+    assert_eq!(new_commit3.parent_ids(), [new_commit2.id().clone()]);
+}
+
+#[test]
+fn test_find_fixups() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let file_path = repo_path("file");
+    let other_path = repo_path("other");
+
+    let mut tx = repo.start_transaction();
+    let tree1 = create_tree(repo, &[(file_path, "1\n"), (other_path, "a\n")]);
+    let tree2 = create_tree(repo, &[(file_path, "1\n2\n"), (other_path, "a\n")]);
+    let tree3 = create_tree(repo, &[(file_path, "1\n2\n"), (other_path, "a\nb\n")]);
+    let tree4 = create_tree(repo, &[(file_path, "1\n2\n3\n"), (other_path, "a\nb\n")]);
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let commit1 = tx
+        .repo_mut()
+        .new_commit(vec![root_commit_id], tree1.id())
+        .set_description("commit 1\n")
+        .write()
+        .unwrap();
+    let commit2 = tx
+        .repo_mut()
+        .new_commit(vec![commit1.id().clone()], tree2.id())
+        .set_description("commit 2\n")
+        .write()
+        .unwrap();
+    // Marked as a fixup of commit 1
+    let description = absorb::add_fixup_trailer("fixup\n", commit1.change_id());
+    assert_eq!(
+        description,
+        format!("fixup\n\nFixup-Of: {}\n", commit1.change_id().reverse_hex())
+    );
+    assert_eq!(
+        absorb::add_fixup_trailer(&description, commit1.change_id()),
+        description
+    );
+    let commit3 = tx
+        .repo_mut()
+        .new_commit(vec![commit2.id().clone()], tree3.id())
+        .set_description(description)
+        .write()
+        .unwrap();
+    // Marked as a fixup of an unknown change
+    let commit4 = tx
+        .repo_mut()
+        .new_commit(vec![commit3.id().clone()], tree4.id())
+        .set_description("fixup\n\nFixup-Of: kkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkk\n")
+        .write()
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    let candidates = ResolvedRevsetExpression::commits(vec![
+        commit1.id().clone(),
+        commit2.id().clone(),
+        commit3.id().clone(),
+        commit4.id().clone(),
+    ]);
+    let plan = absorb::find_fixups(repo.as_ref(), &candidates).unwrap();
+    assert_eq!(plan.operation_id, *repo.op_id());
+    assert_eq!(
+        plan.fixups,
+        [Fixup {
+            commit_id: commit3.id().clone(),
+            target_commit_id: commit1.id().clone(),
+        }]
+    );
+    assert_eq!(
+        plan.skipped_commits,
+        [(
+            commit4.id().clone(),
+            "Change kkkkkkkkkkkkkkkkkkkkkkkkkkkkkkkk is not visible".to_owned()
+        )]
+    );
+
+    // The whole diff of the fixup commit can be squashed into the target
+    let fixup = &plan.fixups[0];
+    let mut tx = repo.start_transaction();
+    let source = fixup.squash_source(tx.repo()).unwrap();
+    assert!(source.is_full_selection());
+    let target = repo.store().get_commit(&fixup.target_commit_id).unwrap();
+    let squashed = rewrite::squash_commits(tx.repo_mut(), &[source], &target, false)
+        .unwrap()
+        .unwrap();
+    assert_eq!(squashed.abandoned_commits, [commit3.clone()]);
+    let new_commit1 = squashed.commit_builder.write().unwrap();
+    tx.repo_mut().rebase_descendants().unwrap();
+    assert_eq!(
+        *new_commit1.tree_id(),
+        create_tree(&repo, &[(file_path, "1\n"), (other_path, "a\nb\n")]).id()
+    );
+}
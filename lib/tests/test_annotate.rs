@@ -13,9 +13,11 @@
 // limitations under the License.
 
 use std::fmt::Write as _;
+use std::ops::Range;
 use std::rc::Rc;
 
 use itertools::Itertools as _;
+use jj_lib::annotate::AnnotationSession;
 use jj_lib::annotate::FileAnnotation;
 use jj_lib::annotate::FileAnnotator;
 use jj_lib::backend::CommitId;
@@ -75,6 +77,22 @@ fn annotate_within(
     format_annotation(repo, &annotator.to_annotation())
 }
 
+fn annotate_line_range(
+    repo: &dyn Repo,
+    commit: &Commit,
+    file_path: &RepoPath,
+    line_range: Range<usize>,
+    session: &mut AnnotationSession,
+) -> String {
+    let mut annotator = FileAnnotator::from_commit(commit, file_path)
+        .unwrap()
+        .with_line_range(line_range);
+    annotator
+        .compute_in_session(repo, &RevsetExpression::all(), session)
+        .unwrap();
+    format_annotation(repo, &annotator.to_annotation())
+}
+
 fn annotate_parent_tree(repo: &dyn Repo, commit: &Commit, file_path: &RepoPath) -> String {
     let tree = commit.parent_tree(repo).unwrap();
     let text = match tree.path_value(file_path).unwrap().into_resolved().unwrap() {
@@ -409,3 +427,102 @@ fn test_annotate_file_directory_transition() {
 
     insta::assert_snapshot!(annotate(tx.repo(), &commit2, file_path2), @"commit2 : 2");
 }
+
+#[test]
+fn test_annotate_line_range() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let root_commit_id = repo.store().root_commit_id();
+    let file_path = repo_path("file");
+
+    let mut tx = repo.start_transaction();
+    let mut create_commit = create_commit_fn(tx.repo_mut());
+    let tree1 = create_tree(repo, &[(file_path, "1a\n1b\n1c\n1d\n")]);
+    let tree2 = create_tree(repo, &[(file_path, "1a\n2b\n1c\n1d\n")]);
+    let tree3 = create_tree(repo, &[(file_path, "1a\n2b\n1c\n3d\n")]);
+    let tree4 = create_tree(repo, &[(file_path, "1a\n4\n2b\n1c\n3d\n")]);
+    let commit1 = create_commit("commit1", &[root_commit_id], tree1.id());
+    let commit2 = create_commit("commit2", &[commit1.id()], tree2.id());
+    let commit3 = create_commit("commit3", &[commit2.id()], tree3.id());
+    let commit4 = create_commit("commit4", &[commit3.id()], tree4.id());
+    drop(create_commit);
+
+    // Lines outside the range are unresolved at the starting commit
+    let mut session = AnnotationSession::new();
+    insta::assert_snapshot!(
+        annotate_line_range(tx.repo(), &commit4, file_path, 1..3, &mut session), @r"
+    commit4*: 1a
+    commit4 : 4
+    commit2 : 2b
+    commit4*: 1c
+    commit4*: 3d
+    ");
+
+    // The lines in any range are annotated as in the whole file
+    let full_annotation = annotate(tx.repo(), &commit4, file_path);
+    let full_lines = full_annotation.lines().collect_vec();
+    for start in 0..=5 {
+        for end in start..=6 {
+            let annotation = annotate_line_range(
+                tx.repo(),
+                &commit4,
+                file_path,
+                start..end,
+                &mut AnnotationSession::new(),
+            );
+            for (i, line) in annotation.lines().enumerate() {
+                if (start..end).contains(&i) {
+                    assert_eq!(line, full_lines[i], "line {i} in {start}..{end}");
+                } else {
+                    assert!(line.starts_with("commit4*"), "line {i} in {start}..{end}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_annotate_session_reuse() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let root_commit_id = repo.store().root_commit_id();
+    let file_path = repo_path("file");
+
+    let mut tx = repo.start_transaction();
+    let mut create_commit = create_commit_fn(tx.repo_mut());
+    let tree1 = create_tree(repo, &[(file_path, "1a\n1b\n1c\n")]);
+    let tree2 = create_tree(repo, &[(file_path, "1a\n2\n1b\n1c\n")]);
+    let tree3 = create_tree(repo, &[(file_path, "3\n1a\n2\n1b\n1c\n")]);
+    let commit1 = create_commit("commit1", &[root_commit_id], tree1.id());
+    let commit2 = create_commit("commit2", &[commit1.id()], tree2.id());
+    let commit3 = create_commit("commit3", &[commit2.id()], tree3.id());
+    drop(create_commit);
+
+    // The last lines originate in the first commit, so the whole history is
+    // visited
+    let mut session = AnnotationSession::new();
+    insta::assert_snapshot!(
+        annotate_line_range(tx.repo(), &commit3, file_path, 3..5, &mut session), @r"
+    commit3*: 3
+    commit3*: 1a
+    commit3*: 2
+    commit1 : 1b
+    commit1 : 1c
+    ");
+    let num_loaded_files = session.num_loaded_files();
+    assert_eq!(num_loaded_files, 3);
+
+    // Another range of the same file is annotated without loading the file
+    // contents again
+    insta::assert_snapshot!(
+        annotate_line_range(tx.repo(), &commit3, file_path, 0..3, &mut session), @r"
+    commit3 : 3
+    commit1 : 1a
+    commit2 : 2
+    commit3*: 1b
+    commit3*: 1c
+    ");
+    assert_eq!(session.num_loaded_files(), num_loaded_files);
+}
@@ -0,0 +1,96 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use jj_lib::backend::CommitId;
+use jj_lib::backend_test_suite::conformance_tests;
+use jj_lib::backend_test_suite::op_heads_store_conformance_tests;
+use jj_lib::backend_test_suite::op_store_conformance_tests;
+use jj_lib::git_backend::GitBackend;
+use jj_lib::leased_op_heads_store::LeaseOptions;
+use jj_lib::leased_op_heads_store::LeasedOpHeadsStore;
+use jj_lib::op_store::RootOperationData;
+use jj_lib::simple_backend::SimpleBackend;
+use jj_lib::simple_op_heads_store::SimpleOpHeadsStore;
+use jj_lib::simple_op_store::SimpleOpStore;
+use testutils::new_temp_dir;
+use testutils::user_settings;
+
+/// Returns a function which creates a new empty directory every time it's
+/// called.
+fn new_dir_factory(root: &Path) -> impl Fn() -> PathBuf + '_ {
+    let counter = Cell::new(0);
+    move || {
+        counter.set(counter.get() + 1);
+        let path = root.join(counter.get().to_string());
+        fs::create_dir(&path).unwrap();
+        path
+    }
+}
+
+#[test]
+fn test_simple_backend_conformance() {
+    let temp_dir = new_temp_dir();
+    let new_dir = new_dir_factory(temp_dir.path());
+    let report = conformance_tests(|| Box::new(SimpleBackend::init(&new_dir())));
+    report.assert_success();
+    assert!(!report.checks().is_empty());
+}
+
+#[test]
+fn test_git_backend_conformance() {
+    let settings = user_settings();
+    let temp_dir = new_temp_dir();
+    let new_dir = new_dir_factory(temp_dir.path());
+    let report =
+        conformance_tests(|| Box::new(GitBackend::init_internal(&settings, &new_dir()).unwrap()));
+    report.assert_success();
+}
+
+#[test]
+fn test_simple_op_store_conformance() {
+    let temp_dir = new_temp_dir();
+    let new_dir = new_dir_factory(temp_dir.path());
+    let report = op_store_conformance_tests(|| {
+        let root_data = RootOperationData {
+            root_commit_id: CommitId::from_bytes(&[0; 64]),
+        };
+        Box::new(SimpleOpStore::init(&new_dir(), root_data).unwrap())
+    });
+    report.assert_success();
+}
+
+#[test]
+fn test_simple_op_heads_store_conformance() {
+    let temp_dir = new_temp_dir();
+    let new_dir = new_dir_factory(temp_dir.path());
+    let report = op_heads_store_conformance_tests(|| {
+        Box::new(SimpleOpHeadsStore::init(&new_dir()).unwrap())
+    });
+    report.assert_success();
+}
+
+#[test]
+fn test_leased_op_heads_store_conformance() {
+    let temp_dir = new_temp_dir();
+    let new_dir = new_dir_factory(temp_dir.path());
+    let report = op_heads_store_conformance_tests(|| {
+        Box::new(LeasedOpHeadsStore::init(&new_dir(), LeaseOptions::default()).unwrap())
+    });
+    report.assert_success();
+}
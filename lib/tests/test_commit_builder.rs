@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
 use assert_matches::assert_matches;
 use futures::StreamExt as _;
 use indoc::indoc;
@@ -29,6 +31,8 @@ use jj_lib::repo::Repo as _;
 use jj_lib::repo_path::RepoPath;
 use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::rewrite::RebaseOptions;
+use jj_lib::settings::ChangeIdGenerator as _;
+use jj_lib::settings::SeededChangeIdGenerator;
 use jj_lib::settings::UserSettings;
 use pollster::FutureExt as _;
 use test_case::test_case;
@@ -413,3 +417,82 @@ fn test_commit_builder_descendants(backend: TestRepoBackend) {
         rebase_descendants_with_options_return_map(tx.repo_mut(), &RebaseOptions::default());
     assert!(rebase_map.is_empty());
 }
+
+#[test]
+fn test_seeded_change_id_generator() {
+    let new_change_ids = |settings: &UserSettings| {
+        let test_repo = TestRepo::init_with_settings(settings);
+        let repo = &test_repo.repo;
+        let mut tx = repo.start_transaction();
+        let root_commit_id = repo.store().root_commit_id().clone();
+        let tree_id = repo.store().empty_merged_tree_id();
+        (0..3)
+            .map(|_| {
+                tx.repo_mut()
+                    .new_commit(vec![root_commit_id.clone()], tree_id.clone())
+                    .write()
+                    .unwrap()
+                    .change_id()
+                    .clone()
+            })
+            .collect_vec()
+    };
+
+    // The same seed produces the same sequence of change ids
+    let change_ids1 = new_change_ids(&testutils::user_settings_with_change_id_seed(7));
+    let change_ids2 = new_change_ids(&testutils::user_settings_with_change_id_seed(7));
+    assert_eq!(change_ids1, change_ids2);
+    assert!(change_ids1.iter().all_unique());
+    let change_ids3 = new_change_ids(&testutils::user_settings_with_change_id_seed(8));
+    assert_ne!(change_ids1, change_ids3);
+
+    // The seed can also be configured
+    let mut config = testutils::base_user_config();
+    config.add_layer(ConfigLayer::parse(ConfigSource::User, "debug.change-id-seed = 7").unwrap());
+    let settings = UserSettings::from_config(config).unwrap();
+    assert_eq!(new_change_ids(&settings), change_ids1);
+
+    // By default, change ids are generated by the RNG
+    let settings = testutils::user_settings();
+    let rng = testutils::user_settings().get_rng();
+    let expected_change_ids = (0..3).map(|_| rng.new_change_id(16)).collect_vec();
+    assert_eq!(new_change_ids(&settings), expected_change_ids);
+}
+
+#[test]
+fn test_commit_builder_set_change_id_generator() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let root_commit_id = repo.store().root_commit_id().clone();
+    let tree_id = repo.store().empty_merged_tree_id();
+    let expected_change_id = SeededChangeIdGenerator::new(1).new_change_id(16);
+
+    let mut tx = repo.start_transaction();
+    let commit = tx
+        .repo_mut()
+        .new_commit(vec![root_commit_id], tree_id)
+        .set_change_id_generator(Arc::new(SeededChangeIdGenerator::new(1)))
+        .write()
+        .unwrap();
+    assert_eq!(commit.change_id(), &expected_change_id);
+
+    // The change id of rewritten commit is kept unless explicitly regenerated
+    let rewritten_commit = tx
+        .repo_mut()
+        .rewrite_commit(&commit)
+        .set_change_id_generator(Arc::new(SeededChangeIdGenerator::new(2)))
+        .write()
+        .unwrap();
+    assert_eq!(rewritten_commit.change_id(), commit.change_id());
+    let rewritten_commit = tx
+        .repo_mut()
+        .rewrite_commit(&rewritten_commit)
+        .set_change_id_generator(Arc::new(SeededChangeIdGenerator::new(2)))
+        .generate_new_change_id()
+        .write()
+        .unwrap();
+    assert_eq!(
+        rewritten_commit.change_id(),
+        &SeededChangeIdGenerator::new(2).new_change_id(16)
+    );
+}
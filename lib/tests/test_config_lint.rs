@@ -0,0 +1,118 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches::assert_matches;
+use jj_lib::config::ConfigLayer;
+use jj_lib::config::ConfigSource;
+use jj_lib::config_lint::validate_settings;
+use jj_lib::config_lint::LintError;
+use jj_lib::revset::RevsetParseErrorKind;
+use jj_lib::settings::UserSettings;
+
+fn settings_with_layers(layers: &[(ConfigSource, &str)]) -> UserSettings {
+    let mut config = testutils::base_user_config();
+    for &(source, text) in layers {
+        config.add_layer(ConfigLayer::parse(source, text).unwrap());
+    }
+    UserSettings::from_config(config).unwrap()
+}
+
+#[test]
+fn test_validate_settings_no_issues() {
+    let settings = settings_with_layers(&[(
+        ConfigSource::User,
+        r#"
+        revset-aliases.'mine()' = 'author(exact:"test.user@example.com")'
+        revset-aliases.'stack(x)' = 'x:: | ::x'
+        revsets.log = 'stack(mine())'
+        snapshot.auto-track = 'glob:"*.rs"'
+        fix.tools.rustfmt = { command = ["rustfmt"], patterns = ['glob:"**/*.rs"'] }
+        "#,
+    )]);
+    let issues = validate_settings(&settings, None);
+    assert!(issues.is_empty(), "{issues:?}");
+}
+
+#[test]
+fn test_validate_settings_broken_values() {
+    let settings = settings_with_layers(&[
+        (
+            ConfigSource::User,
+            r#"
+            revset-aliases.'mine()' = 'author(exact:"test.user@example.com"'
+            revset-aliases.'a' = 'b'
+            revset-aliases.'b' = 'a'
+            revset-aliases.'stack(x)' = 'x:: | ::x'
+            revsets.log = 'stack(mine())'
+            "#,
+        ),
+        (
+            ConfigSource::Repo,
+            r#"
+            snapshot.auto-track = 'glob:"*.rs" |'
+            "#,
+        ),
+    ]);
+    let issues = validate_settings(&settings, None);
+    let keys = issues
+        .iter()
+        .map(|issue| (issue.key.to_string(), issue.source))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        keys,
+        [
+            ("revset-aliases.a".to_owned(), ConfigSource::User),
+            ("revset-aliases.b".to_owned(), ConfigSource::User),
+            (r#"revset-aliases."mine()""#.to_owned(), ConfigSource::User),
+            ("revsets.log".to_owned(), ConfigSource::User),
+            ("snapshot.auto-track".to_owned(), ConfigSource::Repo),
+        ]
+    );
+
+    // Recursive aliases are reported within the alias expansion
+    let [a_issue, _, mine_issue, log_issue, fileset_issue] = &issues[..] else {
+        panic!("unexpected issues {issues:?}");
+    };
+    assert_matches!(
+        &a_issue.error,
+        LintError::Revset(err) if matches!(err.kind(), RevsetParseErrorKind::InAliasExpansion(_))
+    );
+    assert_matches!(
+        &mine_issue.error,
+        LintError::Revset(err) if matches!(err.kind(), RevsetParseErrorKind::InAliasExpansion(_))
+    );
+    // The broken alias is also reported where it's used
+    assert_matches!(&log_issue.error, LintError::Revset(_));
+    assert_matches!(&fileset_issue.error, LintError::Fileset(_));
+}
+
+#[test]
+fn test_validate_settings_overridden_alias() {
+    // The broken alias is overridden by the repo config
+    let settings = settings_with_layers(&[
+        (ConfigSource::User, "revset-aliases.'f(x)' = 'x |'"),
+        (ConfigSource::Repo, "revset-aliases.'f(y)' = 'y | root()'"),
+    ]);
+    let issues = validate_settings(&settings, None);
+    assert!(issues.is_empty(), "{issues:?}");
+
+    let settings = settings_with_layers(&[
+        (ConfigSource::User, "revset-aliases.'f(x)' = 'x'"),
+        (ConfigSource::Repo, "revset-aliases.'f(y)' = 'y |'"),
+    ]);
+    let issues = validate_settings(&settings, None);
+    assert_eq!(issues.len(), 1, "{issues:?}");
+    assert_eq!(issues[0].key.to_string(), r#"revset-aliases."f(y)""#);
+    assert_eq!(issues[0].source, ConfigSource::Repo);
+}
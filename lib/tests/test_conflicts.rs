@@ -17,18 +17,29 @@ use itertools::Itertools as _;
 use jj_lib::backend::FileId;
 use jj_lib::conflicts::choose_materialized_conflict_marker_len;
 use jj_lib::conflicts::extract_as_single_hunk;
+use jj_lib::conflicts::extract_sides;
 use jj_lib::conflicts::materialize_merge_result_to_bytes;
+use jj_lib::conflicts::materialize_merge_result_to_bytes_with_labels;
 use jj_lib::conflicts::parse_conflict;
 use jj_lib::conflicts::update_from_content;
+use jj_lib::conflicts::ConflictLabels;
 use jj_lib::conflicts::ConflictMarkerStyle;
+use jj_lib::conflicts::ConflictSide;
+use jj_lib::conflicts::ConflictSideContent;
 use jj_lib::conflicts::MIN_CONFLICT_MARKER_LEN;
 use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTree;
 use jj_lib::repo::Repo as _;
 use jj_lib::repo_path::RepoPath;
 use jj_lib::store::Store;
+use jj_lib::tree::Tree;
 use pollster::FutureExt as _;
+use testutils::create_random_commit;
+use testutils::create_single_tree;
+use testutils::create_single_tree_with;
 use testutils::read_file;
 use testutils::repo_path;
+use testutils::write_file;
 use testutils::TestRepo;
 
 #[test]
@@ -612,6 +623,106 @@ fn test_materialize_parse_roundtrip_different_markers() {
     }
 }
 
+#[test]
+fn test_materialize_parse_roundtrip_with_labels() {
+    let test_repo = TestRepo::init();
+    let store = test_repo.repo.store();
+
+    let path = repo_path("file");
+    let base_id = testutils::write_file(store, path, "line 1\nline 2 base\nline 3\n");
+    let a_id = testutils::write_file(store, path, "line 1\nline 2 a\nline 3\n");
+    let b_id = testutils::write_file(store, path, "line 1\nline 2 b.1\nline 2 b.2\nline 3\n");
+    let conflict = Merge::from_removes_adds(
+        vec![Some(base_id.clone())],
+        vec![Some(a_id.clone()), Some(b_id.clone())],
+    );
+    let labels = ConflictLabels::from_sides(["main", "Add feature\n\nMore details"]);
+    let materialize = |conflict_marker_style| {
+        let contents = extract_as_single_hunk(&conflict, store, path)
+            .block_on()
+            .unwrap();
+        let materialized = materialize_merge_result_to_bytes_with_labels(
+            &contents,
+            conflict_marker_style,
+            MIN_CONFLICT_MARKER_LEN,
+            &labels,
+        );
+        String::from_utf8(materialized.into()).unwrap()
+    };
+
+    insta::assert_snapshot!(materialize(ConflictMarkerStyle::Diff), @r#"
+    line 1
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1 "main"
+    -line 2 base
+    +line 2 a
+    +++++++ Contents of side #2 "Add feature"
+    line 2 b.1
+    line 2 b.2
+    >>>>>>> Conflict 1 of 1 ends
+    line 3
+    "#);
+    insta::assert_snapshot!(materialize(ConflictMarkerStyle::Snapshot), @r#"
+    line 1
+    <<<<<<< Conflict 1 of 1
+    +++++++ Contents of side #1 "main"
+    line 2 a
+    ------- Contents of base
+    line 2 base
+    +++++++ Contents of side #2 "Add feature"
+    line 2 b.1
+    line 2 b.2
+    >>>>>>> Conflict 1 of 1 ends
+    line 3
+    "#);
+    insta::assert_snapshot!(materialize(ConflictMarkerStyle::Git), @r#"
+    line 1
+    <<<<<<< Side #1 "main" (Conflict 1 of 1)
+    line 2 a
+    ||||||| Base
+    line 2 base
+    =======
+    line 2 b.1
+    line 2 b.2
+    >>>>>>> Side #2 "Add feature" (Conflict 1 of 1 ends)
+    line 3
+    "#);
+
+    // The labels are only informational, so they shouldn't affect parsing
+    for style in [
+        ConflictMarkerStyle::Diff,
+        ConflictMarkerStyle::Snapshot,
+        ConflictMarkerStyle::Git,
+    ] {
+        let parsed = update_from_content(
+            &conflict,
+            store,
+            path,
+            materialize(style).as_bytes(),
+            style,
+            MIN_CONFLICT_MARKER_LEN,
+        )
+        .block_on()
+        .unwrap();
+        assert_eq!(parsed, conflict, "parse {style:?} conflict markers");
+    }
+
+    // Labels which don't match the number of sides are ignored
+    let contents = extract_as_single_hunk(&conflict, store, path)
+        .block_on()
+        .unwrap();
+    let materialized = materialize_merge_result_to_bytes_with_labels(
+        &contents,
+        ConflictMarkerStyle::Diff,
+        MIN_CONFLICT_MARKER_LEN,
+        &ConflictLabels::from_sides(["main"]),
+    );
+    assert_eq!(
+        materialized,
+        materialize_merge_result_to_bytes(&contents, ConflictMarkerStyle::Diff)
+    );
+}
+
 #[test]
 fn test_materialize_conflict_no_newlines_at_eof() {
     let test_repo = TestRepo::init();
@@ -2435,6 +2546,137 @@ fn test_update_from_content_malformed_conflict() {
     assert_eq!(second_snapshot, new_conflict);
 }
 
+#[test]
+fn test_extract_sides_with_deletion() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+    let path = repo_path("file");
+
+    let base = create_single_tree(repo, &[(path, "base\n")]);
+    let side1 = create_single_tree(repo, &[(path, "side1\n")]);
+    let side2 = create_single_tree(repo, &[]);
+    let tree = MergedTree::new(Merge::from_removes_adds(
+        vec![base.clone()],
+        vec![side1.clone(), side2.clone()],
+    ));
+    let mut tx = repo.start_transaction();
+    let mut write_commit = |tree: Tree| {
+        create_random_commit(tx.repo_mut())
+            .set_tree_id(MergedTree::resolved(tree).id())
+            .write()
+            .unwrap()
+    };
+    let base_commit = write_commit(base);
+    let side1_commit = write_commit(side1);
+    let side2_commit = write_commit(side2);
+
+    let sides = extract_sides(
+        store,
+        &tree,
+        path,
+        &[
+            side1_commit.clone(),
+            side2_commit.clone(),
+            base_commit.clone(),
+        ],
+    )
+    .block_on()
+    .unwrap();
+    assert_eq!(
+        sides.bases().collect_vec(),
+        [&ConflictSide {
+            content: file_side_content(store, path, "base\n", false),
+            commit_ids: vec![base_commit.id().clone()],
+        }]
+    );
+    assert_eq!(
+        sides.sides().collect_vec(),
+        [
+            &ConflictSide {
+                content: file_side_content(store, path, "side1\n", false),
+                commit_ids: vec![side1_commit.id().clone()],
+            },
+            &ConflictSide {
+                content: ConflictSideContent::Absent,
+                commit_ids: vec![side2_commit.id().clone()],
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_extract_sides_three_sided() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+    let path = repo_path("file");
+    let other_path = repo_path("other");
+
+    let base1 = create_single_tree(repo, &[(path, "base1\n"), (other_path, "other\n")]);
+    let base2 = create_single_tree(repo, &[(path, "base2\n"), (other_path, "other\n")]);
+    let side1 = create_single_tree(repo, &[(path, "side1\n"), (other_path, "other\n")]);
+    let side2 = create_single_tree_with(repo, |builder| {
+        builder.symlink(path, "target");
+        builder.file(other_path, "other\n");
+    });
+    let side3 = create_single_tree_with(repo, |builder| {
+        builder.file(path, "base2\n").executable(true);
+        builder.file(other_path, "other\n");
+    });
+    let tree = MergedTree::new(Merge::from_removes_adds(
+        vec![base1, base2],
+        vec![side1, side2, side3],
+    ));
+
+    let sides = extract_sides(store, &tree, path, &[]).block_on().unwrap();
+    let contents = sides.terms.map(|side| {
+        assert!(side.commit_ids.is_empty());
+        side.content.clone()
+    });
+    let file_content = |text, executable| file_side_content(store, path, text, executable);
+    // The executable bit change isn't simplified away.
+    assert_eq!(
+        contents,
+        Merge::from_removes_adds(
+            vec![
+                file_content("base1\n", false),
+                file_content("base2\n", false)
+            ],
+            vec![
+                file_content("side1\n", false),
+                ConflictSideContent::Symlink {
+                    id: store.write_symlink(path, "target").block_on().unwrap(),
+                    target: "target".to_owned(),
+                },
+                file_content("base2\n", true),
+            ],
+        )
+    );
+
+    // A path without conflicts has a single term.
+    let sides = extract_sides(store, &tree, other_path, &[])
+        .block_on()
+        .unwrap();
+    assert_eq!(
+        sides.terms.map(|side| side.content.clone()),
+        Merge::resolved(file_side_content(store, other_path, "other\n", false))
+    );
+}
+
+fn file_side_content(
+    store: &Store,
+    path: &RepoPath,
+    text: &str,
+    executable: bool,
+) -> ConflictSideContent {
+    ConflictSideContent::File {
+        id: write_file(store, path, text),
+        executable,
+        content: text.into(),
+    }
+}
+
 fn materialize_conflict_string(
     store: &Store,
     path: &RepoPath,
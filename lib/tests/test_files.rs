@@ -0,0 +1,155 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
+use jj_lib::files::last_modified;
+use jj_lib::merged_tree::MergedTree;
+use jj_lib::repo::MutableRepo;
+use jj_lib::repo::Repo as _;
+use testutils::create_tree;
+use testutils::repo_path;
+use testutils::repo_path_buf;
+use testutils::TestRepo;
+
+fn write_commit(mut_repo: &mut MutableRepo, parents: &[&Commit], tree: &MergedTree) -> Commit {
+    let parent_ids = parents.iter().map(|commit| commit.id().clone()).collect();
+    mut_repo.new_commit(parent_ids, tree.id()).write().unwrap()
+}
+
+#[test]
+fn test_last_modified() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let root_commit = repo.store().root_commit();
+
+    let top_path = repo_path("top");
+    let shallow_path = repo_path("dir/shallow");
+    let deep_path = repo_path("dir/sub/deep");
+    let conflict_path = repo_path("dir/sub/conflict");
+    let untouched_path = repo_path("untouched");
+    let deleted_path = repo_path("dir/deleted");
+
+    // A---B---C---M---E
+    //  \         /
+    //   `-------D
+    let mut tx = repo.start_transaction();
+    let tree_a = create_tree(
+        repo,
+        &[
+            (top_path, "a"),
+            (shallow_path, "a"),
+            (deep_path, "a"),
+            (conflict_path, "a"),
+            (untouched_path, "a"),
+            (deleted_path, "a"),
+        ],
+    );
+    let commit_a = write_commit(tx.repo_mut(), &[&root_commit], &tree_a);
+    let tree_b = create_tree(
+        repo,
+        &[
+            (top_path, "a"),
+            (shallow_path, "a"),
+            (deep_path, "b"),
+            (conflict_path, "a"),
+            (untouched_path, "a"),
+            (deleted_path, "a"),
+        ],
+    );
+    let commit_b = write_commit(tx.repo_mut(), &[&commit_a], &tree_b);
+    let tree_c = create_tree(
+        repo,
+        &[
+            (top_path, "c"),
+            (shallow_path, "a"),
+            (deep_path, "b"),
+            (conflict_path, "c"),
+            (untouched_path, "a"),
+        ],
+    );
+    let commit_c = write_commit(tx.repo_mut(), &[&commit_b], &tree_c);
+    let tree_d = create_tree(
+        repo,
+        &[
+            (top_path, "a"),
+            (shallow_path, "d"),
+            (deep_path, "a"),
+            (conflict_path, "d"),
+            (untouched_path, "a"),
+            (deleted_path, "a"),
+        ],
+    );
+    let commit_d = write_commit(tx.repo_mut(), &[&commit_a], &tree_d);
+    // The merge resolves the conflicting changes to `conflict_path`
+    let tree_m = create_tree(
+        repo,
+        &[
+            (top_path, "c"),
+            (shallow_path, "d"),
+            (deep_path, "b"),
+            (conflict_path, "m"),
+            (untouched_path, "a"),
+        ],
+    );
+    let commit_m = write_commit(tx.repo_mut(), &[&commit_c, &commit_d], &tree_m);
+    let commit_e = write_commit(tx.repo_mut(), &[&commit_m], &tree_m);
+    let repo = tx.commit("test").unwrap();
+
+    let paths = [
+        top_path,
+        shallow_path,
+        deep_path,
+        conflict_path,
+        untouched_path,
+        deleted_path,
+        repo_path("nonexistent"),
+        repo_path("dir/sub/nonexistent"),
+    ]
+    .map(|path| path.to_owned());
+    let result = last_modified(repo.as_ref(), &commit_e, &paths).unwrap();
+    let expected: HashMap<_, CommitId> = [
+        (top_path, commit_c.id()),
+        (shallow_path, commit_d.id()),
+        (deep_path, commit_b.id()),
+        (conflict_path, commit_m.id()),
+        (untouched_path, commit_a.id()),
+        (deleted_path, commit_c.id()),
+    ]
+    .into_iter()
+    .map(|(path, id)| (path.to_owned(), id.clone()))
+    .collect();
+    assert_eq!(result, expected);
+
+    // Querying at an earlier commit ignores later modifications
+    let result = last_modified(
+        repo.as_ref(),
+        &commit_d,
+        &[repo_path_buf("top"), repo_path_buf("dir/shallow")],
+    )
+    .unwrap();
+    assert_eq!(
+        result,
+        HashMap::from([
+            (repo_path_buf("top"), commit_a.id().clone()),
+            (repo_path_buf("dir/shallow"), commit_d.id().clone()),
+        ])
+    );
+
+    // No paths
+    let result = last_modified(repo.as_ref(), &commit_e, &[]).unwrap();
+    assert_eq!(result, HashMap::new());
+}
@@ -121,6 +121,7 @@ fn test_fix_one_file() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut file_fixer,
     )
@@ -161,6 +162,7 @@ fn test_fixer_does_not_change_content() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut file_fixer,
     )
@@ -192,6 +194,7 @@ fn test_empty_commit() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut file_fixer,
     )
@@ -224,6 +227,7 @@ fn test_fixer_fails() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut file_fixer,
     );
@@ -257,6 +261,7 @@ fn test_unchanged_file_is_not_fixed() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut file_fixer,
     )
@@ -291,6 +296,7 @@ fn test_unchanged_file_is_fixed() {
         root_commits,
         &EverythingMatcher,
         true,
+        &[],
         tx.repo_mut(),
         &mut file_fixer,
     )
@@ -335,6 +341,7 @@ fn test_already_fixed_descendant() {
         root_commits,
         &EverythingMatcher,
         true,
+        &[],
         tx.repo_mut(),
         &mut file_fixer,
     )
@@ -380,6 +387,7 @@ fn test_parallel_fixer_basic() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut parallel_fixer,
     )
@@ -423,6 +431,7 @@ fn test_parallel_fixer_fixes_files() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut parallel_fixer,
     )
@@ -471,6 +480,7 @@ fn test_parallel_fixer_does_not_change_content() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut parallel_fixer,
     )
@@ -514,6 +524,7 @@ fn test_parallel_fixer_no_changes_upon_partial_failure() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut parallel_fixer,
     );
@@ -560,6 +571,7 @@ fn test_fix_multiple_revisions() {
         root_commits,
         &EverythingMatcher,
         include_unchanged_files,
+        &[],
         tx.repo_mut(),
         &mut file_fixer,
     )
@@ -573,3 +585,49 @@ fn test_fix_multiple_revisions() {
         .unwrap();
     assert_eq!(*new_commit_a.tree_id(), expected_tree_a.id());
 }
+
+#[test]
+fn test_fix_changed_since() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commit A is the base. Commit B changes file1 but not file2.
+    let mut tx = repo.start_transaction();
+    let path1 = repo_path("file1");
+    let path2 = repo_path("file2");
+    let tree1 = create_tree(repo, &[(path1, "fixme:foo"), (path2, "fixme:bar")]);
+    let commit_a = create_commit(
+        &mut tx,
+        vec![repo.store().root_commit_id().clone()],
+        tree1.id(),
+    );
+    let tree2 = create_tree(repo, &[(path1, "fixme:baz"), (path2, "fixme:bar")]);
+    let commit_b = create_commit(&mut tx, vec![commit_a.clone()], tree2.id());
+
+    // The fixer skips files that are unchanged since the first base, like a
+    // tool scoped with `changed-since` would.
+    let mut file_fixer = ParallelFileFixer::new(|store: &Store, file_to_fix: &FileToFix| {
+        if file_to_fix.unchanged_since.contains(&0) {
+            return Ok(None);
+        }
+        fix_file(store, file_to_fix)
+    });
+    let include_unchanged_files = true;
+    let summary = fix_files(
+        vec![commit_b.clone()],
+        &EverythingMatcher,
+        include_unchanged_files,
+        &[vec![commit_a.clone()]],
+        tx.repo_mut(),
+        &mut file_fixer,
+    )
+    .unwrap();
+
+    let expected_tree_b = create_tree(repo, &[(path1, "BAZ"), (path2, "fixme:bar")]);
+    assert_eq!(summary.rewrites.len(), 1);
+    let new_commit_b = repo
+        .store()
+        .get_commit(summary.rewrites.get(&commit_b).unwrap())
+        .unwrap();
+    assert_eq!(*new_commit_b.tree_id(), expected_tree_b.id());
+}
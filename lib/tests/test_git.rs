@@ -58,6 +58,7 @@ use jj_lib::ref_name::RefName;
 use jj_lib::ref_name::RefNameBuf;
 use jj_lib::ref_name::RemoteName;
 use jj_lib::ref_name::RemoteRefSymbol;
+use jj_lib::ref_name::WorkspaceName;
 use jj_lib::refs::BookmarkPushUpdate;
 use jj_lib::repo::MutableRepo;
 use jj_lib::repo::ReadonlyRepo;
@@ -351,7 +352,8 @@ fn test_import_refs_reimport() {
         .write()
         .unwrap();
     tx.repo_mut()
-        .set_local_bookmark_target("feature2".as_ref(), RefTarget::normal(commit6.id().clone()));
+        .set_local_bookmark_target("feature2".as_ref(), RefTarget::normal(commit6.id().clone()))
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1632,7 +1634,9 @@ fn test_export_refs_bookmark_changed() {
         .set_parents(vec![jj_id(commit)])
         .write()
         .unwrap();
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(new_commit.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(new_commit.id().clone()))
+        .unwrap();
     let stats = git::export_refs(mut_repo).unwrap();
     assert!(stats.failed_bookmarks.is_empty());
     assert_eq!(
@@ -1676,7 +1680,9 @@ fn test_export_refs_current_bookmark_changed() {
         .set_parents(vec![jj_id(commit1)])
         .write()
         .unwrap();
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(new_commit.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(new_commit.id().clone()))
+        .unwrap();
     let stats = git::export_refs(mut_repo).unwrap();
     assert!(stats.failed_bookmarks.is_empty());
     assert_eq!(
@@ -1714,7 +1720,9 @@ fn test_export_refs_unborn_git_bookmark(move_placeholder_ref: bool) {
     assert!(git_repo.head().unwrap().is_unborn(), "HEAD is unborn");
 
     let new_commit = write_random_commit(mut_repo);
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(new_commit.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(new_commit.id().clone()))
+        .unwrap();
     if move_placeholder_ref {
         git_repo
             .reference(
@@ -1776,7 +1784,9 @@ fn test_export_import_sequence() {
     );
 
     // Modify the bookmark in jj to point to B
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
 
     // Export the bookmark to git
     let stats = git::export_refs(mut_repo).unwrap();
@@ -1927,22 +1937,29 @@ fn test_export_conflicts() {
     let commit_a = write_random_commit(mut_repo);
     let commit_b = write_random_commit(mut_repo);
     let commit_c = write_random_commit(mut_repo);
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_a.id().clone()));
     mut_repo
-        .set_local_bookmark_target("feature".as_ref(), RefTarget::normal(commit_a.id().clone()));
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_a.id().clone()))
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target("feature".as_ref(), RefTarget::normal(commit_a.id().clone()))
+        .unwrap();
     let stats = git::export_refs(mut_repo).unwrap();
     assert!(stats.failed_bookmarks.is_empty());
 
     // Create a conflict and export. It should not be exported, but other changes
     // should be.
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()));
-    mut_repo.set_local_bookmark_target(
-        "feature".as_ref(),
-        RefTarget::from_legacy_form(
-            [commit_a.id().clone()],
-            [commit_b.id().clone(), commit_c.id().clone()],
-        ),
-    );
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target(
+            "feature".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit_a.id().clone()],
+                [commit_b.id().clone(), commit_c.id().clone()],
+            ),
+        )
+        .unwrap();
     let stats = git::export_refs(mut_repo).unwrap();
     assert!(stats.failed_bookmarks.is_empty());
     assert_eq!(
@@ -1985,10 +2002,12 @@ fn test_export_bookmark_on_root_commit() {
     let test_data = GitRepoData::create();
     let mut tx = test_data.repo.start_transaction();
     let mut_repo = tx.repo_mut();
-    mut_repo.set_local_bookmark_target(
-        "on_root".as_ref(),
-        RefTarget::normal(mut_repo.store().root_commit_id().clone()),
-    );
+    mut_repo
+        .set_local_bookmark_target(
+            "on_root".as_ref(),
+            RefTarget::normal(mut_repo.store().root_commit_id().clone()),
+        )
+        .unwrap();
     let stats = git::export_refs(mut_repo).unwrap();
     assert_eq!(stats.failed_bookmarks.len(), 1);
     assert_eq!(
@@ -2010,14 +2029,23 @@ fn test_export_partial_failure() {
     let mut_repo = tx.repo_mut();
     let commit_a = write_random_commit(mut_repo);
     let target = RefTarget::normal(commit_a.id().clone());
-    // Empty string is disallowed by Git
-    mut_repo.set_local_bookmark_target("".as_ref(), target.clone());
+    // Empty string is disallowed by Git. Such names can't be created by
+    // MutableRepo, but may exist in old views.
+    let mut view = mut_repo.view().store_view().clone();
+    view.local_bookmarks.insert("".into(), target.clone());
+    mut_repo.set_view(view);
     // Branch named HEAD is disallowed by Git CLI
-    mut_repo.set_local_bookmark_target("HEAD".as_ref(), target.clone());
-    mut_repo.set_local_bookmark_target("main".as_ref(), target.clone());
+    mut_repo
+        .set_local_bookmark_target("HEAD".as_ref(), target.clone())
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), target.clone())
+        .unwrap();
     // `main/sub` will conflict with `main` in Git, at least when using loose ref
     // storage
-    mut_repo.set_local_bookmark_target("main/sub".as_ref(), target.clone());
+    mut_repo
+        .set_local_bookmark_target("main/sub".as_ref(), target.clone())
+        .unwrap();
     let stats = git::export_refs(mut_repo).unwrap();
     assert_eq!(stats.failed_bookmarks.len(), 3);
     assert_eq!(
@@ -2078,7 +2106,9 @@ fn test_export_partial_failure() {
 
     // Now remove the `main` bookmark and make sure that the `main/sub` gets
     // exported even though it didn't change
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::absent());
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::absent())
+        .unwrap();
     let stats = git::export_refs(mut_repo).unwrap();
     assert_eq!(stats.failed_bookmarks.len(), 2);
     assert_eq!(
@@ -2163,22 +2193,27 @@ fn test_export_reexport_transitions() {
         "AAB", "AAX", "ABA", "ABB", "ABC", "ABX", "AXA", "AXB", "AXX",
     ] {
         mut_repo
-            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::normal(commit_a.id().clone()));
+            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::normal(commit_a.id().clone()))
+            .unwrap();
     }
     let stats = git::export_refs(mut_repo).unwrap();
     assert!(stats.failed_bookmarks.is_empty());
 
     // Make changes on the jj side
     for bookmark in ["AXA", "AXB", "AXX"] {
-        mut_repo.set_local_bookmark_target(bookmark.as_ref(), RefTarget::absent());
+        mut_repo
+            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::absent())
+            .unwrap();
     }
     for bookmark in ["XAA", "XAB", "XAX"] {
         mut_repo
-            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::normal(commit_a.id().clone()));
+            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::normal(commit_a.id().clone()))
+            .unwrap();
     }
     for bookmark in ["ABA", "ABB", "ABC", "ABX"] {
         mut_repo
-            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::normal(commit_b.id().clone()));
+            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::normal(commit_b.id().clone()))
+            .unwrap();
     }
 
     // Make changes on the git side
@@ -2300,7 +2335,9 @@ fn test_export_undo_reexport() {
     // Initial export
     let commit_a = write_random_commit(mut_repo);
     let target_a = RefTarget::normal(commit_a.id().clone());
-    mut_repo.set_local_bookmark_target("main".as_ref(), target_a.clone());
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), target_a.clone())
+        .unwrap();
     let stats = git::export_refs(mut_repo).unwrap();
     assert!(stats.failed_bookmarks.is_empty());
     assert_eq!(
@@ -2344,6 +2381,103 @@ fn test_export_undo_reexport() {
     );
 }
 
+#[test]
+fn test_export_import_abandoned_commit_at_git_ref_and_head() {
+    // Create colocated workspace
+    let settings = testutils::user_settings();
+    let git_settings = GitSettings::default();
+    let temp_dir = testutils::new_temp_dir();
+    let workspace_root = temp_dir.path().join("repo");
+    let git_repo = testutils::git::init(&workspace_root);
+    let (_workspace, repo) =
+        Workspace::init_external_git(&settings, &workspace_root, &workspace_root.join(".git"))
+            .unwrap();
+
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    let tree_id = repo.store().empty_merged_tree_id();
+    let commit1 = write_random_commit(mut_repo);
+    let commit2 = mut_repo
+        .new_commit(vec![commit1.id().clone()], tree_id.clone())
+        .write()
+        .unwrap();
+    let wc_commit = mut_repo
+        .new_commit(vec![commit2.id().clone()], tree_id.clone())
+        .write()
+        .unwrap();
+    mut_repo
+        .edit(WorkspaceName::DEFAULT.to_owned(), &wc_commit)
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
+    git::export_refs(mut_repo).unwrap();
+    git::reset_head(mut_repo, &wc_commit).unwrap();
+    let repo = tx.commit("test").unwrap();
+    assert_eq!(
+        repo.view().get_git_ref("refs/heads/main".as_ref()),
+        &RefTarget::normal(commit2.id().clone())
+    );
+    assert_eq!(
+        repo.view().git_head(),
+        &RefTarget::normal(commit2.id().clone())
+    );
+
+    // Abandon the commit pointed to by the Git ref and HEAD. The bookmark
+    // moves to the parent, but the Git ref and HEAD keep recording the state
+    // of the Git repo until they're exported. Otherwise, the export would
+    // think the Git repo was already up to date.
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    mut_repo.record_abandoned_commit(&commit2);
+    mut_repo.rebase_descendants().unwrap();
+    assert_eq!(
+        mut_repo.get_local_bookmark("main".as_ref()),
+        RefTarget::normal(commit1.id().clone())
+    );
+    assert_eq!(
+        mut_repo.get_git_ref("refs/heads/main".as_ref()),
+        RefTarget::normal(commit2.id().clone())
+    );
+    assert_eq!(mut_repo.git_head(), RefTarget::normal(commit2.id().clone()));
+
+    // Exporting updates both the Git repo and the recorded refs.
+    let new_wc_commit_id = mut_repo
+        .view()
+        .get_wc_commit_id(WorkspaceName::DEFAULT)
+        .unwrap()
+        .clone();
+    let new_wc_commit = mut_repo.store().get_commit(&new_wc_commit_id).unwrap();
+    let stats = git::export_refs(mut_repo).unwrap();
+    assert!(stats.failed_bookmarks.is_empty());
+    git::reset_head(mut_repo, &new_wc_commit).unwrap();
+    assert_eq!(
+        git_repo
+            .find_reference("refs/heads/main")
+            .unwrap()
+            .target()
+            .id(),
+        git_id(&commit1)
+    );
+    assert_eq!(git_repo.head_id().unwrap().detach(), git_id(&commit1));
+    assert_eq!(
+        mut_repo.get_git_ref("refs/heads/main".as_ref()),
+        RefTarget::normal(commit1.id().clone())
+    );
+    assert_eq!(mut_repo.git_head(), RefTarget::normal(commit1.id().clone()));
+
+    // Importing again doesn't resurrect the abandoned commit.
+    git::import_head(mut_repo).unwrap();
+    git::import_refs(mut_repo, &git_settings).unwrap();
+    mut_repo.rebase_descendants().unwrap();
+    let repo = tx.commit("test").unwrap();
+    assert!(!repo.view().heads().contains(commit2.id()));
+    assert_eq!(
+        repo.view().get_local_bookmark("main".as_ref()),
+        &RefTarget::normal(commit1.id().clone())
+    );
+}
+
 #[test]
 fn test_reset_head_to_root() {
     // Create colocated workspace
@@ -20,6 +20,7 @@ use std::time::Duration;
 use std::time::SystemTime;
 
 use futures::executor::block_on_stream;
+use itertools::Itertools as _;
 use jj_lib::backend::CommitId;
 use jj_lib::backend::CopyRecord;
 use jj_lib::commit::Commit;
@@ -241,6 +242,51 @@ fn test_gc() {
     assert_eq!(collect_no_gc_refs(git_repo_path), hashset! {});
 }
 
+#[test]
+fn test_rebase_batched_writes() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
+    let repo = &test_repo.repo;
+    let git_repo_path = get_git_backend(repo).git_repo_path();
+
+    let mut tx = repo.start_transaction();
+    let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    graph_builder.commit_with_parents(&[&commit_b]);
+    let repo = tx.commit("test").unwrap();
+
+    // The descendants are queued while rebasing, and written when the rebase
+    // is done
+    let mut tx = repo.start_transaction();
+    let new_commit_a = tx
+        .repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    assert_eq!(tx.repo_mut().rebase_descendants().unwrap(), 2);
+    let repo = tx.commit("test").unwrap();
+    let [head_id] = repo.view().heads().iter().collect_array().unwrap();
+    let new_commit_c = repo.store().get_commit(head_id).unwrap();
+    let new_commit_b = new_commit_c.parents().next().unwrap().unwrap();
+    assert_eq!(new_commit_b.parent_ids(), [new_commit_a.id().clone()]);
+    assert!(collect_no_gc_refs(git_repo_path).is_superset(&hashset! {
+        new_commit_b.id().clone(),
+        new_commit_c.id().clone(),
+    }));
+
+    // The queued commits match the written ones, including the metadata which
+    // isn't stored in the Git commits
+    let repo = test_repo
+        .env
+        .load_repo_at_head(&settings, test_repo.repo_path());
+    for commit in [&new_commit_b, &new_commit_c] {
+        let written_commit = repo.store().get_commit(commit.id()).unwrap();
+        assert_eq!(written_commit.store_commit(), commit.store_commit());
+    }
+}
+
 #[test]
 fn test_copy_detection() {
     let test_repo = TestRepo::init_with_backend(TestRepoBackend::Git);
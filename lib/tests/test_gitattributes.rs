@@ -0,0 +1,65 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jj_lib::gitattributes::AttributeState;
+use jj_lib::gitattributes::TreeGitAttributes;
+use jj_lib::merge::Merge;
+use jj_lib::merged_tree::MergedTree;
+use testutils::create_single_tree;
+use testutils::create_tree;
+use testutils::repo_path;
+use testutils::TestRepo;
+
+#[test]
+fn test_tree_gitattributes() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let tree = create_tree(
+        repo,
+        &[
+            (repo_path(".gitattributes"), "*.ipynb diff=jupyter\n"),
+            (repo_path("dir/.gitattributes"), "*.ipynb -diff\n"),
+            (repo_path("a.ipynb"), ""),
+            (repo_path("dir/a.ipynb"), ""),
+            (repo_path("dir/sub/a.ipynb"), ""),
+            (repo_path("other/a.ipynb"), ""),
+        ],
+    );
+    let mut attributes = TreeGitAttributes::new(tree);
+    let mut get = |path| attributes.get(repo_path(path), "diff").unwrap();
+    assert_eq!(get("a.ipynb").as_value(), Some("jupyter"));
+    assert_eq!(get("other/a.ipynb").as_value(), Some("jupyter"));
+    assert_eq!(get("dir/a.ipynb"), AttributeState::Unset);
+    assert_eq!(get("dir/sub/a.ipynb"), AttributeState::Unset);
+    assert_eq!(get("a.txt"), AttributeState::Unspecified);
+}
+
+#[test]
+fn test_tree_gitattributes_conflicted_file() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let base = create_single_tree(repo, &[(repo_path(".gitattributes"), "")]);
+    let side1 = create_single_tree(repo, &[(repo_path(".gitattributes"), "* merge=a\n")]);
+    let side2 = create_single_tree(repo, &[(repo_path(".gitattributes"), "* merge=b\n")]);
+    let tree = MergedTree::new(Merge::from_removes_adds(vec![base], vec![side1, side2]));
+
+    // The conflicted file is ignored
+    let mut attributes = TreeGitAttributes::new(tree);
+    assert_eq!(
+        attributes.get(repo_path("file"), "merge").unwrap(),
+        AttributeState::Unspecified
+    );
+}
@@ -12,6 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::fs;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
 use itertools::Itertools as _;
 use jj_lib::backend::ChangeId;
 use jj_lib::backend::CommitId;
@@ -19,13 +24,18 @@ use jj_lib::backend::MillisSinceEpoch;
 use jj_lib::backend::Signature;
 use jj_lib::backend::Timestamp;
 use jj_lib::id_prefix::IdPrefixContext;
+use jj_lib::id_prefix::PrefixCache;
+use jj_lib::id_prefix::PrefixCacheNotice;
 use jj_lib::object_id::HexPrefix;
 use jj_lib::object_id::ObjectId as _;
 use jj_lib::object_id::PrefixResolution::AmbiguousMatch;
 use jj_lib::object_id::PrefixResolution::NoMatch;
 use jj_lib::object_id::PrefixResolution::SingleMatch;
+use jj_lib::repo::ReadonlyRepo;
 use jj_lib::repo::Repo as _;
 use jj_lib::revset::RevsetExpression;
+use testutils::new_temp_dir;
+use testutils::write_random_commit;
 use testutils::TestRepo;
 use testutils::TestRepoBackend;
 
@@ -532,3 +542,162 @@ fn test_id_prefix_hidden() {
         NoMatch
     );
 }
+
+/// Adds random commits until `prefix` becomes ambiguous in the repo.
+fn make_commit_prefix_ambiguous(
+    mut repo: Arc<ReadonlyRepo>,
+    prefix: &HexPrefix,
+) -> Arc<ReadonlyRepo> {
+    while repo.index().resolve_commit_id_prefix(prefix) != AmbiguousMatch {
+        let mut tx = repo.start_transaction();
+        write_random_commit(tx.repo_mut());
+        repo = tx.commit("test").unwrap();
+    }
+    repo
+}
+
+#[test]
+fn test_id_prefix_cache_disambiguation() {
+    let test_repo = TestRepo::init();
+    let cache_dir = new_temp_dir();
+    let max_age = Duration::from_secs(3600);
+
+    let mut tx = test_repo.repo.start_transaction();
+    let commit = write_random_commit(tx.repo_mut());
+    let repo = tx.commit("test").unwrap();
+
+    // Record the prefixes as if they were printed by a command.
+    let cache = Rc::new(PrefixCache::load(cache_dir.path(), 10, max_age).unwrap());
+    let context = IdPrefixContext::default().with_prefix_cache(cache.clone());
+    let index = context.populate(repo.as_ref()).unwrap();
+    let commit_prefix_len = index.shortest_commit_prefix_len(repo.as_ref(), commit.id());
+    let change_prefix_len = index.shortest_change_prefix_len(repo.as_ref(), commit.change_id());
+    index.record_commit_prefix(commit.id(), commit_prefix_len);
+    index.record_change_prefix(commit.change_id(), change_prefix_len);
+    cache.save().unwrap();
+
+    // Add commits until both prefixes become ambiguous.
+    let commit_prefix = HexPrefix::new(&commit.id().hex()[..commit_prefix_len]).unwrap();
+    let change_prefix = HexPrefix::new(&commit.change_id().hex()[..change_prefix_len]).unwrap();
+    let mut repo = make_commit_prefix_ambiguous(repo, &commit_prefix);
+    while repo.resolve_change_id_prefix(&change_prefix) != AmbiguousMatch {
+        let mut tx = repo.start_transaction();
+        write_random_commit(tx.repo_mut());
+        repo = tx.commit("test").unwrap();
+    }
+
+    // Without the cache, the prefixes are ambiguous
+    let context = IdPrefixContext::default();
+    let index = context.populate(repo.as_ref()).unwrap();
+    assert_eq!(
+        index.resolve_commit_prefix(repo.as_ref(), &commit_prefix),
+        AmbiguousMatch
+    );
+    assert_eq!(
+        index.resolve_change_prefix(repo.as_ref(), &change_prefix),
+        AmbiguousMatch
+    );
+
+    // The reloaded cache resolves them to the recorded IDs
+    let cache = Rc::new(PrefixCache::load(cache_dir.path(), 10, max_age).unwrap());
+    let context = IdPrefixContext::default().with_prefix_cache(cache.clone());
+    let index = context.populate(repo.as_ref()).unwrap();
+    assert_eq!(
+        index.resolve_commit_prefix(repo.as_ref(), &commit_prefix),
+        SingleMatch(commit.id().clone())
+    );
+    assert_eq!(
+        index.resolve_change_prefix(repo.as_ref(), &change_prefix),
+        SingleMatch(vec![commit.id().clone()])
+    );
+    assert_eq!(
+        cache.take_notices(),
+        [
+            PrefixCacheNotice::Commit {
+                prefix_len: commit_prefix_len,
+                id: commit.id().clone(),
+            },
+            PrefixCacheNotice::Change {
+                prefix_len: change_prefix_len,
+                id: commit.change_id().clone(),
+            },
+        ]
+    );
+    assert_eq!(cache.take_notices(), []);
+
+    // A shorter prefix than the recorded one isn't resolved by the cache
+    if commit_prefix_len > 1 {
+        let shorter_prefix = HexPrefix::new(&commit.id().hex()[..commit_prefix_len - 1]).unwrap();
+        assert_eq!(
+            index.resolve_commit_prefix(repo.as_ref(), &shorter_prefix),
+            AmbiguousMatch
+        );
+    }
+
+    // Hidden commits aren't resolved
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().record_abandoned_commit(&commit);
+    tx.repo_mut().rebase_descendants().unwrap();
+    let repo = tx.commit("test").unwrap();
+    let index = context.populate(repo.as_ref()).unwrap();
+    assert_eq!(
+        index.resolve_change_prefix(repo.as_ref(), &change_prefix),
+        AmbiguousMatch
+    );
+    assert_eq!(cache.take_notices(), []);
+}
+
+#[test]
+fn test_id_prefix_cache_expiry() {
+    let test_repo = TestRepo::init();
+    let cache_dir = new_temp_dir();
+    let max_age = Duration::from_secs(3600);
+
+    let mut tx = test_repo.repo.start_transaction();
+    let commit1 = write_random_commit(tx.repo_mut());
+    let commit2 = write_random_commit(tx.repo_mut());
+    let commit3 = write_random_commit(tx.repo_mut());
+    let repo = tx.commit("test").unwrap();
+    let prefix1 = HexPrefix::new(&commit1.id().hex()[..1]).unwrap();
+    let repo = make_commit_prefix_ambiguous(repo, &prefix1);
+    let resolve_prefix1 = |context: &IdPrefixContext| {
+        let index = context.populate(repo.as_ref()).unwrap();
+        index.resolve_commit_prefix(repo.as_ref(), &prefix1)
+    };
+
+    // Expired by count, least recently recorded first
+    let cache = PrefixCache::load(cache_dir.path(), 2, max_age).unwrap();
+    let context = IdPrefixContext::default().with_prefix_cache(Rc::new(cache));
+    let index = context.populate(repo.as_ref()).unwrap();
+    index.record_commit_prefix(commit1.id(), 1);
+    index.record_commit_prefix(commit2.id(), commit2.id().hex().len());
+    assert_eq!(resolve_prefix1(&context), SingleMatch(commit1.id().clone()));
+    index.record_commit_prefix(commit1.id(), 1);
+    index.record_commit_prefix(commit3.id(), commit3.id().hex().len());
+    assert_eq!(resolve_prefix1(&context), SingleMatch(commit1.id().clone()));
+    index.record_commit_prefix(commit2.id(), commit2.id().hex().len());
+    assert_eq!(resolve_prefix1(&context), AmbiguousMatch);
+
+    // Expired by age
+    let cache_path = cache_dir.path().join("prefix_cache");
+    let now = Timestamp::now().timestamp.0;
+    let commit1_hex = commit1.id().hex();
+    let prefix1_hex = &commit1_hex[..1];
+    fs::write(
+        &cache_path,
+        format!("commit {prefix1_hex} {commit1_hex} {now}\n"),
+    )
+    .unwrap();
+    let cache = PrefixCache::load(cache_dir.path(), 2, max_age).unwrap();
+    let context = IdPrefixContext::default().with_prefix_cache(Rc::new(cache));
+    assert_eq!(resolve_prefix1(&context), SingleMatch(commit1.id().clone()));
+    let old = now - 2 * 3600 * 1000;
+    fs::write(
+        &cache_path,
+        format!("commit {prefix1_hex} {commit1_hex} {old}\n"),
+    )
+    .unwrap();
+    let cache = PrefixCache::load(cache_dir.path(), 2, max_age).unwrap();
+    let context = IdPrefixContext::default().with_prefix_cache(Rc::new(cache));
+    assert_eq!(resolve_prefix1(&context), AmbiguousMatch);
+}
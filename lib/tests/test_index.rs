@@ -14,6 +14,7 @@
 
 use std::collections::HashSet;
 use std::fs;
+use std::ops::ControlFlow;
 use std::sync::Arc;
 
 use assert_matches::assert_matches;
@@ -28,6 +29,7 @@ use jj_lib::default_index::DefaultIndexStoreError;
 use jj_lib::default_index::DefaultMutableIndex;
 use jj_lib::default_index::DefaultReadonlyIndex;
 use jj_lib::index::Index as _;
+use jj_lib::index::IndexRebuildProgress;
 use jj_lib::object_id::HexPrefix;
 use jj_lib::object_id::ObjectId as _;
 use jj_lib::object_id::PrefixResolution;
@@ -40,6 +42,7 @@ use jj_lib::repo::MutableRepo;
 use jj_lib::repo::ReadonlyRepo;
 use jj_lib::repo::Repo as _;
 use jj_lib::revset::ResolvedExpression;
+use jj_lib::revset::RevsetEvaluationContext;
 use jj_lib::revset::GENERATION_RANGE_FULL;
 use maplit::hashset;
 use testutils::commit_transactions;
@@ -220,7 +223,10 @@ fn test_index_commits_criss_cross() {
             heads: ResolvedExpression::Commits(wanted.to_vec()).into(),
             generation,
         };
-        let revset = index.evaluate_revset(&expression, repo.store()).unwrap();
+        let context = RevsetEvaluationContext::default();
+        let revset = index
+            .evaluate_revset(&expression, repo.store(), &context)
+            .unwrap();
         // Don't switch to more efficient .count() implementation. Here we're
         // testing the iterator behavior.
         revset.iter().count()
@@ -601,6 +607,107 @@ fn test_index_commits_incremental_squashed() {
     assert_eq!(commits_by_level(&repo), vec![71, 20]);
 }
 
+/// Rewrites the index segment files in place in the format version 6, which
+/// didn't record the commit and change id lengths.
+fn downgrade_index_segment_files(test_repo: &TestRepo) {
+    let segments_dir = test_repo.repo_path().join("index").join("segments");
+    for entry in segments_dir.read_dir().unwrap() {
+        let path = entry.unwrap().path();
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data[..4], 7_u32.to_le_bytes());
+        fs::write(&path, [&6_u32.to_le_bytes(), &data[12..]].concat()).unwrap();
+    }
+}
+
+#[test]
+fn test_index_migrate_old_format_incrementally() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let test_env = &test_repo.env;
+    let repo = &test_repo.repo;
+
+    let repo = create_n_commits(repo, 30);
+    let repo = create_n_commits(&repo, 15);
+    let repo = create_n_commits(&repo, 7);
+    assert_eq!(commits_by_level(&repo), vec![31, 15, 7]);
+    assert!(!repo
+        .index_store()
+        .needs_rebuild(repo.operation(), repo.store())
+        .unwrap());
+
+    // The old format is still readable
+    downgrade_index_segment_files(&test_repo);
+    let repo = test_env.load_repo_at_head(&settings, test_repo.repo_path());
+    assert_eq!(commits_by_level(&repo), vec![31, 15, 7]);
+    assert!(repo
+        .index_store()
+        .needs_rebuild(repo.operation(), repo.store())
+        .unwrap());
+
+    // New segments are written in the new format
+    let repo = create_n_commits(&repo, 1);
+    assert_eq!(commits_by_level(&repo), vec![31, 15, 7, 1]);
+    assert!(repo
+        .index_store()
+        .needs_rebuild(repo.operation(), repo.store())
+        .unwrap());
+
+    // Migrate one segment at a time
+    let mut progress_calls = vec![];
+    let progress = repo
+        .index_store()
+        .rebuild_incrementally(repo.operation(), repo.store(), &mut |progress| {
+            progress_calls.push(progress);
+            ControlFlow::Break(())
+        })
+        .unwrap();
+    let expected_progress = IndexRebuildProgress {
+        migrated: 1,
+        remaining: 2,
+    };
+    assert_eq!(progress, expected_progress);
+    assert_eq!(progress_calls, vec![expected_progress]);
+    let repo = test_env.load_repo_at_head(&settings, test_repo.repo_path());
+    assert_eq!(commits_by_level(&repo), vec![31, 15, 7, 1]);
+    assert!(repo
+        .index_store()
+        .needs_rebuild(repo.operation(), repo.store())
+        .unwrap());
+
+    // The migration continues at a later operation
+    let repo = create_n_commits(&repo, 1);
+    assert_eq!(commits_by_level(&repo), vec![31, 15, 7, 2]);
+    let mut progress_calls = vec![];
+    let progress = repo
+        .index_store()
+        .rebuild_incrementally(repo.operation(), repo.store(), &mut |progress| {
+            progress_calls.push(progress);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    assert_eq!(
+        progress_calls,
+        vec![
+            IndexRebuildProgress {
+                migrated: 1,
+                remaining: 1,
+            },
+            IndexRebuildProgress {
+                migrated: 2,
+                remaining: 0,
+            },
+        ]
+    );
+    assert_eq!(progress, progress_calls[1]);
+    let repo = test_env.load_repo_at_head(&settings, test_repo.repo_path());
+    assert_eq!(commits_by_level(&repo), vec![31, 15, 7, 2]);
+    assert_eq!(as_readonly_composite(&repo).num_commits(), 1 + 54);
+    assert!(!repo
+        .index_store()
+        .needs_rebuild(repo.operation(), repo.store())
+        .unwrap());
+}
+
 #[test]
 fn test_reindex_no_segments_dir() {
     let settings = testutils::user_settings();
@@ -639,12 +746,14 @@ fn test_reindex_corrupt_segment_files() {
     for entry in segments_dir.read_dir().unwrap() {
         let entry = entry.unwrap();
         // u32: file format version
+        // u32: commit id length
+        // u32: change id length
         // u32: parent segment file name length (0 means root)
         // u32: number of local commit entries
         // u32: number of local change ids
         // u32: number of overflow parent entries
         // u32: number of overflow change id positions
-        fs::write(entry.path(), b"\0".repeat(24)).unwrap();
+        fs::write(entry.path(), b"\0".repeat(32)).unwrap();
     }
 
     let repo = test_env.load_repo_at_head(&settings, test_repo.repo_path());
@@ -0,0 +1,145 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+use jj_lib::leased_op_heads_store::LeaseOptions;
+use jj_lib::leased_op_heads_store::LeasedOpHeadsStore;
+use jj_lib::op_heads_store::OpHeadsStore;
+use jj_lib::op_heads_store::OpHeadsStoreError;
+use jj_lib::op_store::OperationId;
+use testutils::new_temp_dir;
+
+fn op_id(thread: u8, index: u8) -> OperationId {
+    OperationId::new(vec![thread, index])
+}
+
+fn short_options() -> LeaseOptions {
+    LeaseOptions {
+        duration: Duration::from_secs(10),
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+    }
+}
+
+#[test]
+fn test_concurrent_updates() {
+    let temp_dir = new_temp_dir();
+    let store = LeasedOpHeadsStore::init(temp_dir.path(), LeaseOptions::default()).unwrap();
+    let root_id = op_id(0, 0);
+    store.update_op_heads(&[], &root_id).unwrap();
+
+    // Each thread chains its updates from the root operation. No head must be
+    // lost or left behind by the interleaved updates.
+    thread::scope(|s| {
+        for thread in 1..=2 {
+            let store = &store;
+            let mut old_id = root_id.clone();
+            s.spawn(move || {
+                for index in 0..50 {
+                    let new_id = op_id(thread, index);
+                    store.update_op_heads(&[old_id], &new_id).unwrap();
+                    old_id = new_id;
+                }
+            });
+        }
+    });
+    let heads: HashSet<_> = store.get_op_heads().unwrap().into_iter().collect();
+    assert_eq!(heads, HashSet::from([op_id(1, 49), op_id(2, 49)]));
+    assert!(!store.lease_path().exists());
+}
+
+#[test]
+fn test_expired_lease_is_broken() {
+    let temp_dir = new_temp_dir();
+    let store = LeasedOpHeadsStore::init(temp_dir.path(), short_options()).unwrap();
+    fs::write(
+        store.lease_path(),
+        r#"{"owner":"crashed","expires_millis":0}"#,
+    )
+    .unwrap();
+
+    store.update_op_heads(&[], &op_id(0, 0)).unwrap();
+    assert_eq!(store.get_op_heads().unwrap(), vec![op_id(0, 0)]);
+    assert!(!store.lease_path().exists());
+}
+
+#[test]
+fn test_held_lease_times_out() {
+    let temp_dir = new_temp_dir();
+    let store = LeasedOpHeadsStore::init(temp_dir.path(), short_options()).unwrap();
+    let lease = format!(r#"{{"owner":"other","expires_millis":{}}}"#, i64::MAX);
+    fs::write(store.lease_path(), &lease).unwrap();
+
+    let result = store.update_op_heads(&[], &op_id(0, 0));
+    assert!(matches!(result, Err(OpHeadsStoreError::Write { .. })));
+    assert_eq!(store.get_op_heads().unwrap(), vec![]);
+    // The lease held by someone else is left alone
+    assert_eq!(fs::read_to_string(store.lease_path()).unwrap(), lease);
+}
+
+#[test]
+fn test_lease_is_renewed() {
+    let temp_dir = new_temp_dir();
+    // The lease is renewed before each old head is removed
+    let options = LeaseOptions {
+        duration: Duration::ZERO,
+        ..short_options()
+    };
+    let store = LeasedOpHeadsStore::init(temp_dir.path(), options).unwrap();
+    let old_ids = [op_id(0, 0), op_id(0, 1), op_id(0, 2)];
+    for old_id in &old_ids {
+        store.update_op_heads(&[], old_id).unwrap();
+    }
+
+    store.update_op_heads(&old_ids, &op_id(1, 0)).unwrap();
+    assert_eq!(store.get_op_heads().unwrap(), vec![op_id(1, 0)]);
+    assert!(!store.lease_path().exists());
+}
+
+#[test]
+fn test_lease_being_broken_is_left_alone() {
+    let temp_dir = new_temp_dir();
+    let store = LeasedOpHeadsStore::init(temp_dir.path(), short_options()).unwrap();
+    let lease = r#"{"owner":"crashed","expires_millis":0}"#;
+    fs::write(store.lease_path(), lease).unwrap();
+    // Somebody else is breaking the expired lease
+    let breaker_path = store.lease_path().with_extension("breaker");
+    fs::write(&breaker_path, "").unwrap();
+
+    let result = store.update_op_heads(&[], &op_id(0, 0));
+    assert!(matches!(result, Err(OpHeadsStoreError::Write { .. })));
+    assert_eq!(store.get_op_heads().unwrap(), vec![]);
+    assert_eq!(fs::read_to_string(store.lease_path()).unwrap(), lease);
+    assert!(breaker_path.exists());
+}
+
+#[test]
+fn test_lock_is_a_separate_lease() {
+    let temp_dir = new_temp_dir();
+    let store = LeasedOpHeadsStore::init(temp_dir.path(), short_options()).unwrap();
+
+    let lock = store.lock().unwrap();
+    assert!(store.lock_path().exists());
+    assert!(matches!(store.lock(), Err(OpHeadsStoreError::Lock(_))));
+    // The heads can be updated while the lock is held
+    store.update_op_heads(&[], &op_id(0, 0)).unwrap();
+    assert_eq!(store.get_op_heads().unwrap(), vec![op_id(0, 0)]);
+    drop(lock);
+    assert!(!store.lock_path().exists());
+    drop(store.lock().unwrap());
+}
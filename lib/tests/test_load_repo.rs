@@ -12,7 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::any::Any;
+use std::fs;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use jj_lib::index::IndexReadError;
+use jj_lib::index::IndexStore;
+use jj_lib::index::IndexWriteError;
+use jj_lib::index::MutableIndex;
+use jj_lib::index::ReadonlyIndex;
+use jj_lib::operation::Operation;
 use jj_lib::repo::RepoLoader;
+use jj_lib::repo::StoreLoadError;
+use jj_lib::simple_op_heads_store::SimpleOpHeadsStore;
+use jj_lib::store::Store;
 use testutils::write_random_commit;
 use testutils::TestRepo;
 
@@ -52,3 +67,122 @@ fn test_load_at_operation() {
     let old_repo = loader.load_at(repo.operation()).unwrap();
     assert!(old_repo.view().heads().contains(commit.id()));
 }
+
+/// Index store that counts how many times an index was loaded.
+#[derive(Debug)]
+struct CountingIndexStore {
+    inner: Arc<dyn IndexStore>,
+    load_count: AtomicUsize,
+}
+
+impl IndexStore for CountingIndexStore {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn get_index_at_op(
+        &self,
+        op: &Operation,
+        store: &Arc<Store>,
+    ) -> Result<Box<dyn ReadonlyIndex>, IndexReadError> {
+        self.load_count.fetch_add(1, Ordering::Relaxed);
+        self.inner.get_index_at_op(op, store)
+    }
+
+    fn write_index(
+        &self,
+        index: Box<dyn MutableIndex>,
+        op: &Operation,
+    ) -> Result<Box<dyn ReadonlyIndex>, IndexWriteError> {
+        self.inner.write_index(index, op)
+    }
+}
+
+#[test]
+fn test_load_view_at_operation_without_index() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit = write_random_commit(tx.repo_mut());
+    let old_repo = tx.commit("add commit").unwrap();
+
+    let mut tx = old_repo.start_transaction();
+    tx.repo_mut().remove_head(commit.id());
+    tx.commit("remove commit").unwrap();
+
+    let base_loader = old_repo.loader();
+    let index_store = Arc::new(CountingIndexStore {
+        inner: base_loader.index_store().clone(),
+        load_count: AtomicUsize::new(0),
+    });
+    let loader = RepoLoader::new(
+        base_loader.settings().clone(),
+        base_loader.store().clone(),
+        base_loader.op_store().clone(),
+        base_loader.op_heads_store().clone(),
+        index_store.clone(),
+        base_loader.submodule_store().clone(),
+    );
+
+    let view = loader.load_view_at(old_repo.operation()).unwrap();
+    assert!(view.heads().contains(commit.id()));
+    let view_repo = loader.load_view_only_at(old_repo.operation()).unwrap();
+    assert_eq!(view_repo.op_id(), old_repo.op_id());
+    assert_eq!(view_repo.view(), old_repo.view());
+    assert_eq!(index_store.load_count.load(Ordering::Relaxed), 0);
+
+    // Loading a full repo still goes through the index store
+    loader.load_at(old_repo.operation()).unwrap();
+    assert_eq!(index_store.load_count.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_load_store_type_alias() {
+    let settings = testutils::user_settings();
+    let test_repo = TestRepo::init();
+    let type_path = test_repo.repo_path().join("op_heads").join("type");
+    fs::write(&type_path, "legacy_op_heads_store").unwrap();
+
+    // Without an alias, the error lists the supported types
+    let factories = test_repo.env.default_store_factories();
+    let result = RepoLoader::init_from_file_system(&settings, test_repo.repo_path(), &factories);
+    let Err(err @ StoreLoadError::UnsupportedType { .. }) = result else {
+        panic!("unexpected result");
+    };
+    assert_eq!(
+        err.to_string(),
+        "Unsupported operation heads backend type 'legacy_op_heads_store' (known types: \
+         leased_op_heads_store, simple_op_heads_store)"
+    );
+
+    // The alias is used, but the type file is left alone by default
+    let mut factories = test_repo.env.default_store_factories();
+    factories.add_op_heads_store_alias("legacy_op_heads_store", SimpleOpHeadsStore::name());
+    let loader =
+        RepoLoader::init_from_file_system(&settings, test_repo.repo_path(), &factories).unwrap();
+    assert_eq!(
+        loader.load_at_head().unwrap().op_id(),
+        test_repo.repo.op_id()
+    );
+    assert_eq!(
+        fs::read_to_string(&type_path).unwrap(),
+        "legacy_op_heads_store"
+    );
+
+    // With migration enabled, the type file is rewritten to the current name
+    factories.set_migrate_type_files(true);
+    RepoLoader::init_from_file_system(&settings, test_repo.repo_path(), &factories).unwrap();
+    assert_eq!(
+        fs::read_to_string(&type_path).unwrap(),
+        SimpleOpHeadsStore::name()
+    );
+
+    // Once migrated, the repo can be loaded without the alias
+    let factories = test_repo.env.default_store_factories();
+    RepoLoader::init_from_file_system(&settings, test_repo.repo_path(), &factories).unwrap();
+}
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::ops::ControlFlow;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt as _;
 #[cfg(unix)]
@@ -19,6 +20,9 @@ use std::os::unix::net::UnixListener;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
 
 use assert_matches::assert_matches;
 use indoc::indoc;
@@ -27,11 +31,17 @@ use jj_lib::backend::CopyId;
 use jj_lib::backend::MergedTreeId;
 use jj_lib::backend::TreeId;
 use jj_lib::backend::TreeValue;
+use jj_lib::file_util::check_case_insensitive_fs;
 use jj_lib::file_util::check_symlink_support;
 use jj_lib::file_util::try_symlink;
 use jj_lib::fsmonitor::FsmonitorSettings;
 use jj_lib::gitignore::GitIgnoreFile;
 use jj_lib::local_working_copy::LocalWorkingCopy;
+use jj_lib::local_working_copy::CONFLICT_PLACEHOLDER;
+use jj_lib::matchers::EverythingMatcher;
+use jj_lib::matchers::FilesMatcher;
+use jj_lib::matchers::Matcher;
+use jj_lib::matchers::PrefixMatcher;
 use jj_lib::merge::Merge;
 use jj_lib::merge::MergedTreeValue;
 use jj_lib::merged_tree::MergedTree;
@@ -43,9 +53,12 @@ use jj_lib::repo::Repo as _;
 use jj_lib::repo_path::RepoPath;
 use jj_lib::repo_path::RepoPathBuf;
 use jj_lib::secret_backend::SecretBackend;
+use jj_lib::working_copy::ChangedPathsOptions;
 use jj_lib::working_copy::CheckoutError;
 use jj_lib::working_copy::CheckoutOptions;
+use jj_lib::working_copy::CheckoutProgress;
 use jj_lib::working_copy::CheckoutStats;
+use jj_lib::working_copy::SnapshotError;
 use jj_lib::working_copy::SnapshotOptions;
 use jj_lib::working_copy::UntrackedReason;
 use jj_lib::working_copy::WorkingCopy as _;
@@ -65,14 +78,7 @@ use testutils::TestRepoBackend;
 use testutils::TestWorkspace;
 
 fn check_icase_fs(dir: &Path) -> bool {
-    let test_file = tempfile::Builder::new()
-        .prefix("icase-")
-        .tempfile_in(dir)
-        .unwrap();
-    let orig_name = test_file.path().file_name().unwrap().to_str().unwrap();
-    let upper_name = orig_name.to_ascii_uppercase();
-    assert_ne!(orig_name, upper_name);
-    dir.join(upper_name).try_exists().unwrap()
+    check_case_insensitive_fs(dir).unwrap()
 }
 
 /// Returns true if the directory appears to ignore some unicode zero-width
@@ -824,6 +830,163 @@ fn test_checkout_discard() {
     assert!(!reloaded_wc.file_states().unwrap().contains_path(file2_path));
 }
 
+#[test]
+fn test_checkout_progress_and_cancel() {
+    let mut test_workspace = TestWorkspace::init();
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+
+    let paths = (0..100)
+        .map(|i| repo_path_buf(format!("file{i}")))
+        .collect_vec();
+    let entries1 = paths
+        .iter()
+        .map(|path| (path.as_ref(), "contents1"))
+        .collect_vec();
+    let entries2 = paths
+        .iter()
+        .skip(10)
+        .map(|path| (path.as_ref(), "contents2"))
+        .collect_vec();
+    let tree1 = create_tree(&repo, &entries1);
+    let tree2 = create_tree(&repo, &entries2);
+    let commit1 = commit_with_tree(repo.store(), tree1.id());
+    let commit2 = commit_with_tree(repo.store(), tree2.id());
+
+    // Progress is reported for every file
+    let ws = &mut test_workspace.workspace;
+    let reported = Mutex::new(vec![]);
+    let progress = |progress: CheckoutProgress| {
+        reported.lock().unwrap().push(progress);
+        ControlFlow::Continue(())
+    };
+    let options = CheckoutOptions {
+        progress: Some(&progress),
+        ..CheckoutOptions::empty_for_test()
+    };
+    let stats = ws
+        .check_out(repo.op_id().clone(), None, &commit1, &options)
+        .unwrap();
+    assert_eq!(stats.added_files, 100);
+    let reported = reported.into_inner().unwrap();
+    assert_eq!(reported.len(), 100);
+    assert_eq!(
+        reported[0],
+        CheckoutProgress {
+            processed_files: 0,
+            total_files: 100
+        }
+    );
+    assert_eq!(
+        reported[99],
+        CheckoutProgress {
+            processed_files: 99,
+            total_files: 100
+        }
+    );
+
+    // Cancel the checkout after some files have been updated
+    let cancel = |progress: CheckoutProgress| {
+        if progress.processed_files < 50 {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    };
+    let options = CheckoutOptions {
+        progress: Some(&cancel),
+        ..CheckoutOptions::empty_for_test()
+    };
+    let result = ws.check_out(repo.op_id().clone(), None, &commit2, &options);
+    assert_matches!(result, Err(CheckoutError::Cancelled));
+
+    // The working copy can't be snapshotted until the checkout is finished
+    assert_matches!(
+        test_workspace.snapshot(),
+        Err(SnapshotError::InterruptedCheckout)
+    );
+
+    // Checking out the same commit again finishes the checkout
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(
+        repo.op_id().clone(),
+        None,
+        &commit2,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    for path in &paths[..10] {
+        assert!(!path.to_fs_path_unchecked(&workspace_root).exists());
+    }
+    for path in &paths[10..] {
+        assert_eq!(
+            std::fs::read_to_string(path.to_fs_path_unchecked(&workspace_root)).unwrap(),
+            "contents2"
+        );
+    }
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree2.id());
+}
+
+#[test]
+fn test_checkout_other_commit_after_cancel() {
+    let mut test_workspace = TestWorkspace::init();
+    let repo = test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+
+    let paths = (0..20)
+        .map(|i| repo_path_buf(format!("file{i}")))
+        .collect_vec();
+    let entries1 = paths
+        .iter()
+        .map(|path| (path.as_ref(), "contents1"))
+        .collect_vec();
+    let entries3 = paths
+        .iter()
+        .step_by(2)
+        .map(|path| (path.as_ref(), "contents3"))
+        .collect_vec();
+    let tree1 = create_tree(&repo, &entries1);
+    let tree3 = create_tree(&repo, &entries3);
+    let commit1 = commit_with_tree(repo.store(), tree1.id());
+    let commit3 = commit_with_tree(repo.store(), tree3.id());
+
+    // Cancel the initial checkout midway
+    let ws = &mut test_workspace.workspace;
+    let cancel = |progress: CheckoutProgress| {
+        if progress.processed_files < 5 {
+            ControlFlow::Continue(())
+        } else {
+            ControlFlow::Break(())
+        }
+    };
+    let options = CheckoutOptions {
+        progress: Some(&cancel),
+        ..CheckoutOptions::empty_for_test()
+    };
+    let result = ws.check_out(repo.op_id().clone(), None, &commit1, &options);
+    assert_matches!(result, Err(CheckoutError::Cancelled));
+
+    // Checking out another commit finishes the interrupted checkout first
+    ws.check_out(
+        repo.op_id().clone(),
+        None,
+        &commit3,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    for (i, path) in paths.iter().enumerate() {
+        let disk_path = path.to_fs_path_unchecked(&workspace_root);
+        if i % 2 == 0 {
+            assert_eq!(std::fs::read_to_string(disk_path).unwrap(), "contents3");
+        } else {
+            assert!(!disk_path.exists());
+        }
+    }
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree3.id());
+}
+
 #[test]
 fn test_snapshot_file_directory_transition() {
     let mut test_workspace = TestWorkspace::init();
@@ -994,6 +1157,106 @@ fn test_materialize_snapshot_conflicted_files() {
     );
 }
 
+#[test]
+fn test_materialize_conflicts_lazily() {
+    let mut test_workspace = TestWorkspace::init();
+    let repo = &test_workspace.repo.clone();
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+
+    let file1_path = repo_path("file1");
+    let file2_path = repo_path("file2");
+    let normal_path = repo_path("normal");
+    let base_tree = create_tree(
+        repo,
+        &[
+            (file1_path, "base\n"),
+            (file2_path, "base\n"),
+            (normal_path, "normal\n"),
+        ],
+    );
+    let side1_tree = create_tree(
+        repo,
+        &[
+            (file1_path, "side 1\n"),
+            (file2_path, "side 1\n"),
+            (normal_path, "normal\n"),
+        ],
+    );
+    let side2_tree = create_tree(
+        repo,
+        &[
+            (file1_path, "side 2\n"),
+            (file2_path, "side 2\n"),
+            (normal_path, "normal\n"),
+        ],
+    );
+    let merged_tree = side1_tree.merge(&base_tree, &side2_tree).unwrap();
+    let commit = commit_with_tree(repo.store(), merged_tree.id());
+    let read_file = |path: &RepoPath| std::fs::read(path.to_fs_path_unchecked(&workspace_root));
+
+    // Conflicted files are checked out as placeholders
+    let options = CheckoutOptions {
+        lazy_conflicts: true,
+        ..CheckoutOptions::empty_for_test()
+    };
+    let stats = test_workspace
+        .workspace
+        .check_out(repo.op_id().clone(), None, &commit, &options)
+        .unwrap();
+    assert_eq!(stats.added_files, 3);
+    assert_eq!(read_file(file1_path).unwrap(), CONFLICT_PLACEHOLDER);
+    assert_eq!(read_file(file2_path).unwrap(), CONFLICT_PLACEHOLDER);
+    assert_eq!(read_file(normal_path).unwrap(), b"normal\n");
+
+    // The placeholders aren't snapshotted
+    let tree = test_workspace.snapshot().unwrap();
+    assert_eq!(tree.id(), merged_tree.id());
+
+    // Only the conflicts matching the matcher are materialized
+    let mut locked_ws = test_workspace
+        .workspace
+        .start_working_copy_mutation()
+        .unwrap();
+    let stats = locked_ws
+        .locked_wc()
+        .materialize_conflicts(&FilesMatcher::new([file1_path]), &options)
+        .unwrap();
+    locked_ws.finish(repo.op_id().clone()).unwrap();
+    assert_eq!(stats.updated_files, 1);
+    insta::assert_snapshot!(
+        String::from_utf8(read_file(file1_path).unwrap()).unwrap(),
+        @r"
+    <<<<<<< Conflict 1 of 1
+    %%%%%%% Changes from base to side #1
+    -base
+    +side 1
+    +++++++ Contents of side #2
+    side 2
+    >>>>>>> Conflict 1 of 1 ends
+    ");
+    assert_eq!(read_file(file2_path).unwrap(), CONFLICT_PLACEHOLDER);
+    let tree = test_workspace.snapshot().unwrap();
+    assert_eq!(tree.id(), merged_tree.id());
+
+    // Resolving the materialized conflict only changes that path
+    testutils::write_working_copy_file(&workspace_root, file1_path, "resolved\n");
+    let tree = test_workspace.snapshot().unwrap();
+    let resolved_tree = create_tree(repo, &[(file1_path, "resolved\n")]);
+    assert_eq!(
+        tree.path_value(file1_path).unwrap(),
+        resolved_tree.path_value(file1_path).unwrap()
+    );
+    assert_eq!(
+        tree.path_value(file2_path).unwrap(),
+        merged_tree.path_value(file2_path).unwrap()
+    );
+    assert_eq!(
+        tree.path_value(normal_path).unwrap(),
+        merged_tree.path_value(normal_path).unwrap()
+    );
+    assert_eq!(read_file(file2_path).unwrap(), CONFLICT_PLACEHOLDER);
+}
+
 #[test]
 fn test_snapshot_racy_timestamps() {
     // Tests that file modifications are detected even if they happen the same
@@ -1670,6 +1933,117 @@ fn test_check_out_existing_file_symlink_icase_fs(victim_exists: bool) {
     }
 }
 
+/// Returns the names of the directory entries as they're stored on disk.
+fn read_dir_names(dir: &Path) -> Vec<String> {
+    let mut names = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect_vec();
+    names.sort();
+    names
+}
+
+#[test]
+fn test_check_out_case_rename_icase_fs() {
+    let mut test_workspace = TestWorkspace::init();
+    let repo = &test_workspace.repo;
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+    if !check_icase_fs(&workspace_root) {
+        eprintln!("Skipping test because the file system is case-sensitive");
+        return;
+    }
+
+    let tree1 = create_tree(
+        repo,
+        &[
+            (repo_path("Foo.rs"), "foo"),
+            (repo_path("Dir/file"), "file"),
+        ],
+    );
+    let tree2 = create_tree(
+        repo,
+        &[
+            (repo_path("foo.rs"), "foo"),
+            (repo_path("dir/file"), "modified"),
+        ],
+    );
+    let commit1 = commit_with_tree(repo.store(), tree1.id());
+    let commit2 = commit_with_tree(repo.store(), tree2.id());
+
+    let ws = &mut test_workspace.workspace;
+    ws.check_out(
+        repo.op_id().clone(),
+        None,
+        &commit1,
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    assert_eq!(read_dir_names(&workspace_root), [".jj", "Dir", "Foo.rs"]);
+
+    // The files on disk are renamed, not left with the old names or deleted.
+    let stats = ws
+        .check_out(
+            repo.op_id().clone(),
+            None,
+            &commit2,
+            &CheckoutOptions::empty_for_test(),
+        )
+        .unwrap();
+    assert_eq!(stats.skipped_files, 0);
+    assert_eq!(read_dir_names(&workspace_root), [".jj", "dir", "foo.rs"]);
+    assert_eq!(read_dir_names(&workspace_root.join("dir")), ["file"]);
+    assert_eq!(
+        std::fs::read_to_string(workspace_root.join("dir").join("file")).unwrap(),
+        "modified"
+    );
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree2.id());
+}
+
+#[test]
+fn test_check_out_case_swap_icase_fs() {
+    let mut test_workspace = TestWorkspace::init();
+    let repo = &test_workspace.repo;
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+    if !check_icase_fs(&workspace_root) {
+        eprintln!("Skipping test because the file system is case-sensitive");
+        return;
+    }
+
+    let tree1 = create_tree(repo, &[(repo_path("a.rs"), "contents")]);
+    let tree2 = create_tree(repo, &[(repo_path("A.rs"), "contents")]);
+    let commit1 = commit_with_tree(repo.store(), tree1.id());
+    let commit2 = commit_with_tree(repo.store(), tree2.id());
+
+    let ws = &mut test_workspace.workspace;
+    for (commit, tree, name) in [
+        (&commit1, &tree1, "a.rs"),
+        (&commit2, &tree2, "A.rs"),
+        (&commit1, &tree1, "a.rs"),
+    ] {
+        ws.check_out(
+            repo.op_id().clone(),
+            None,
+            commit,
+            &CheckoutOptions::empty_for_test(),
+        )
+        .unwrap();
+        assert_eq!(read_dir_names(ws.workspace_root()), [".jj", name]);
+        let wc: &LocalWorkingCopy = ws.working_copy().as_any().downcast_ref().unwrap();
+        let file_states = wc.file_states().unwrap();
+        assert_eq!(
+            file_states
+                .paths()
+                .map(|path| path.to_owned())
+                .collect_vec(),
+            [repo_path_buf(name)]
+        );
+        assert_eq!(*wc.tree_id().unwrap(), tree.id());
+    }
+    let new_tree = test_workspace.snapshot().unwrap();
+    assert_eq!(new_tree.id(), tree1.id());
+}
+
 #[test]
 fn test_check_out_file_removal_over_existing_directory_symlink() {
     if !check_symlink_support().unwrap() {
@@ -2157,6 +2531,107 @@ fn test_fsmonitor() {
     }
 }
 
+#[test]
+fn test_changed_paths_since() {
+    let mut test_workspace = TestWorkspace::init();
+    let repo = &test_workspace.repo;
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+
+    let modified_path = repo_path("dir/modified");
+    let touched_path = repo_path("dir/touched");
+    let unchanged_path = repo_path("dir/unchanged");
+    let committed_path = repo_path("committed");
+    let base_tree = create_tree(
+        repo,
+        &[
+            (modified_path, "modified\n"),
+            (touched_path, "touched\n"),
+            (unchanged_path, "unchanged\n"),
+            (committed_path, "old\n"),
+        ],
+    );
+    testutils::write_working_copy_file(&workspace_root, committed_path, "new\n");
+    testutils::write_working_copy_file(&workspace_root, modified_path, "modified\n");
+    testutils::write_working_copy_file(&workspace_root, touched_path, "touched\n");
+    testutils::write_working_copy_file(&workspace_root, unchanged_path, "unchanged\n");
+    // Make sure the unchanged file isn't racily clean
+    std::fs::File::options()
+        .write(true)
+        .open(unchanged_path.to_fs_path_unchecked(&workspace_root))
+        .unwrap()
+        .set_modified(SystemTime::now() - Duration::from_secs(60))
+        .unwrap();
+    test_workspace.snapshot().unwrap();
+
+    // Modify a file without changing its size, and rewrite another file with
+    // the same contents
+    testutils::write_working_copy_file(&workspace_root, modified_path, "MODIFIED\n");
+    testutils::write_working_copy_file(&workspace_root, touched_path, "touched\n");
+    // New files, including an ignored one
+    let untracked_path = repo_path("dir/untracked");
+    let other_untracked_path = repo_path("other/untracked");
+    let ignored_path = repo_path("dir/ignored");
+    testutils::write_working_copy_file(&workspace_root, untracked_path, "untracked\n");
+    testutils::write_working_copy_file(&workspace_root, other_untracked_path, "untracked\n");
+    testutils::write_working_copy_file(&workspace_root, ignored_path, "ignored\n");
+    let base_ignores = GitIgnoreFile::empty()
+        .chain("", Path::new(""), b"/dir/ignored\n")
+        .unwrap();
+
+    let wc = test_workspace.workspace.working_copy();
+    let changed_paths = |matcher: &dyn Matcher, compare_contents| {
+        let options = ChangedPathsOptions {
+            base_ignores: base_ignores.clone(),
+            compare_contents,
+            ..ChangedPathsOptions::empty_for_test()
+        };
+        wc.changed_paths_since(&base_tree, matcher, &options)
+            .unwrap()
+    };
+
+    // Without comparing contents, the touched file can't be told apart from
+    // a modified file
+    assert_eq!(
+        changed_paths(&EverythingMatcher, false),
+        to_owned_path_vec(&[
+            committed_path,
+            modified_path,
+            touched_path,
+            untracked_path,
+            other_untracked_path,
+        ])
+    );
+    assert_eq!(
+        changed_paths(&EverythingMatcher, true),
+        to_owned_path_vec(&[
+            committed_path,
+            modified_path,
+            untracked_path,
+            other_untracked_path,
+        ])
+    );
+
+    // Untracked files outside the matcher aren't reported
+    let dir_matcher = PrefixMatcher::new([repo_path("dir")]);
+    assert_eq!(
+        changed_paths(&dir_matcher, true),
+        to_owned_path_vec(&[modified_path, untracked_path])
+    );
+
+    // Only the paths reported by the fsmonitor are checked on disk
+    let options = ChangedPathsOptions {
+        fsmonitor_settings: FsmonitorSettings::Test {
+            changed_files: vec![modified_path.to_fs_path_unchecked(Path::new(""))],
+        },
+        ..ChangedPathsOptions::empty_for_test()
+    };
+    assert_eq!(
+        wc.changed_paths_since(&base_tree, &EverythingMatcher, &options)
+            .unwrap(),
+        to_owned_path_vec(&[committed_path, modified_path])
+    );
+}
+
 #[test]
 fn test_snapshot_max_new_file_size() {
     let mut test_workspace = TestWorkspace::init();
@@ -31,6 +31,7 @@ use jj_lib::matchers::PrefixMatcher;
 use jj_lib::merge::Merge;
 use jj_lib::merge::MergeBuilder;
 use jj_lib::merge::MergedTreeValue;
+use jj_lib::merged_tree::ConflictKind;
 use jj_lib::merged_tree::MergedTree;
 use jj_lib::merged_tree::MergedTreeBuilder;
 use jj_lib::merged_tree::TreeDiffEntry;
@@ -42,6 +43,7 @@ use jj_lib::repo_path::RepoPathBuf;
 use pollster::FutureExt as _;
 use pretty_assertions::assert_eq;
 use testutils::create_single_tree;
+use testutils::create_single_tree_with;
 use testutils::create_tree;
 use testutils::repo_path;
 use testutils::repo_path_buf;
@@ -759,6 +761,86 @@ fn test_conflict_iterator_higher_arity() {
     );
 }
 
+#[test]
+fn test_conflicts_ex_classification() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let contents_path = repo_path("contents");
+    let file_dir_path = repo_path("file-dir");
+    let file_dir_child_path = repo_path("file-dir/file");
+    let exec_bit_path = repo_path("exec-bit");
+    let delete_modify_path = repo_path("dir/delete-modify");
+    let base = create_single_tree_with(repo, |builder| {
+        builder.file(contents_path, "base");
+        builder.file(exec_bit_path, "base");
+        builder.file(delete_modify_path, "base");
+    });
+    let side1 = create_single_tree_with(repo, |builder| {
+        builder.file(contents_path, "side1");
+        builder.file(file_dir_path, "side1");
+        builder.file(exec_bit_path, "new").executable(true);
+        builder.file(delete_modify_path, "side1");
+    });
+    let side2 = create_single_tree_with(repo, |builder| {
+        builder.file(contents_path, "side2");
+        builder.file(file_dir_child_path, "side2");
+        builder.file(exec_bit_path, "new");
+    });
+    let tree = MergedTree::new(Merge::from_removes_adds(
+        vec![base.clone()],
+        vec![side1.clone(), side2.clone()],
+    ));
+
+    let conflicts: Vec<_> = tree.conflicts_ex(&EverythingMatcher).try_collect().unwrap();
+    let summary = conflicts
+        .iter()
+        .map(|info| {
+            (
+                info.path.as_ref(),
+                info.kind,
+                info.num_sides(),
+                info.absent_sides.clone(),
+            )
+        })
+        .collect_vec();
+    assert_eq!(
+        summary,
+        vec![
+            (contents_path, ConflictKind::ContentsConflict, 2, vec![]),
+            (delete_modify_path, ConflictKind::DeleteModify, 2, vec![1]),
+            (exec_bit_path, ConflictKind::ExecBitConflict, 2, vec![]),
+            (
+                file_dir_path,
+                ConflictKind::FileDirectoryConflict,
+                2,
+                vec![]
+            ),
+        ]
+    );
+    assert_eq!(
+        conflicts[0].value,
+        Merge::from_removes_adds(
+            vec![base.path_value(contents_path).unwrap()],
+            vec![
+                side1.path_value(contents_path).unwrap(),
+                side2.path_value(contents_path).unwrap(),
+            ],
+        )
+    );
+
+    // Conflicts outside the matcher are skipped
+    let matcher = PrefixMatcher::new([repo_path("dir")]);
+    let conflicts: Vec<_> = tree.conflicts_ex(&matcher).try_collect().unwrap();
+    assert_eq!(
+        conflicts
+            .iter()
+            .map(|info| info.path.as_ref())
+            .collect_vec(),
+        vec![delete_modify_path]
+    );
+}
+
 /// Diff two resolved trees
 #[test]
 fn test_diff_resolved() {
@@ -12,22 +12,28 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use assert_matches::assert_matches;
 use jj_lib::backend::CommitId;
+use jj_lib::metrics::TransactionMetrics;
 use jj_lib::op_store::RefTarget;
 use jj_lib::op_store::RemoteRef;
 use jj_lib::op_store::RemoteRefState;
+use jj_lib::ref_name::InvalidRefNameErrorKind;
 use jj_lib::ref_name::RefName;
 use jj_lib::ref_name::RemoteName;
 use jj_lib::ref_name::RemoteRefSymbol;
 use jj_lib::ref_name::WorkspaceName;
 use jj_lib::ref_name::WorkspaceNameBuf;
+use jj_lib::repo::RefUpdateError;
 use jj_lib::repo::Repo as _;
 use jj_lib::rewrite::RebaseOptions;
 use maplit::hashset;
 use testutils::assert_rebased_onto;
 use testutils::create_random_commit;
 use testutils::create_random_tree;
+use testutils::create_tree;
 use testutils::rebase_descendants_with_options_return_map;
+use testutils::repo_path;
 use testutils::write_random_commit;
 use testutils::CommitGraphBuilder;
 use testutils::TestRepo;
@@ -215,7 +221,9 @@ fn test_edit_previous_empty_with_local_bookmark() {
         )
         .write()
         .unwrap();
-    mut_repo.set_local_bookmark_target("b".as_ref(), RefTarget::normal(old_wc_commit.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("b".as_ref(), RefTarget::normal(old_wc_commit.id().clone()))
+        .unwrap();
     let ws_name = WorkspaceName::DEFAULT.to_owned();
     mut_repo.edit(ws_name.clone(), &old_wc_commit).unwrap();
     let repo = tx.commit("test").unwrap();
@@ -478,7 +486,9 @@ fn test_has_changed() {
     mut_repo
         .set_wc_commit(ws_name.clone(), commit1.id().clone())
         .unwrap();
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit1.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit1.id().clone()))
+        .unwrap();
     mut_repo.set_remote_bookmark(
         remote_symbol("main", "origin"),
         normal_remote_ref(commit1.id()),
@@ -494,7 +504,9 @@ fn test_has_changed() {
     mut_repo
         .set_wc_commit(ws_name.clone(), commit1.id().clone())
         .unwrap();
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit1.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit1.id().clone()))
+        .unwrap();
     mut_repo.set_remote_bookmark(
         remote_symbol("main", "origin"),
         normal_remote_ref(commit1.id()),
@@ -502,7 +514,9 @@ fn test_has_changed() {
     assert!(!mut_repo.has_changes());
 
     mut_repo.remove_head(commit2.id());
-    mut_repo.set_local_bookmark_target("stable".as_ref(), RefTarget::absent());
+    mut_repo
+        .set_local_bookmark_target("stable".as_ref(), RefTarget::absent())
+        .unwrap();
     mut_repo.set_remote_bookmark(remote_symbol("stable", "origin"), RemoteRef::absent());
     assert!(!mut_repo.has_changes());
 
@@ -520,9 +534,13 @@ fn test_has_changed() {
         .unwrap();
     assert!(!mut_repo.has_changes());
 
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit2.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
     assert!(mut_repo.has_changes());
-    mut_repo.set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit1.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit1.id().clone()))
+        .unwrap();
     mut_repo.remove_head(commit2.id());
     assert!(!mut_repo.has_changes());
 
@@ -699,7 +717,8 @@ fn test_reparent_descendants() {
         ("child_b", &commit_child_b),
     ] {
         mut_repo
-            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::normal(commit.id().clone()));
+            .set_local_bookmark_target(bookmark.as_ref(), RefTarget::normal(commit.id().clone()))
+            .unwrap();
     }
     let repo = tx.commit("test").unwrap();
 
@@ -747,6 +766,81 @@ fn test_reparent_descendants() {
     }
 }
 
+#[test]
+fn test_reparent_descendants_diverged_merges() {
+    // Test that reparented merge commits are reported if their content is no
+    // longer the merge of their new parents.
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let path_a = repo_path("a");
+    let path_b = repo_path("b");
+    let tree_a = create_tree(repo, &[(path_a, "a")]);
+    let tree_b = create_tree(repo, &[(path_b, "b")]);
+    let tree_a_b = create_tree(repo, &[(path_a, "a"), (path_b, "b")]);
+
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    let root_id = repo.store().root_commit_id().clone();
+    let commit_a = create_random_commit(mut_repo)
+        .set_parents(vec![root_id.clone()])
+        .set_tree_id(tree_a.id())
+        .write()
+        .unwrap();
+    let commit_b = create_random_commit(mut_repo)
+        .set_parents(vec![root_id])
+        .set_tree_id(tree_b.id())
+        .write()
+        .unwrap();
+    let commit_merge = create_random_commit(mut_repo)
+        .set_parents(vec![commit_a.id().clone(), commit_b.id().clone()])
+        .set_tree_id(tree_a_b.id())
+        .write()
+        .unwrap();
+    let commit_child_a = create_random_commit(mut_repo)
+        .set_parents(vec![commit_a.id().clone()])
+        .set_tree_id(tree_a.id())
+        .write()
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    // Only change the description of "a". The merge is still clean.
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    mut_repo
+        .rewrite_commit(&commit_a)
+        .set_description("a")
+        .write()
+        .unwrap();
+    let stats = mut_repo.reparent_descendants_with_stats().unwrap();
+    assert_eq!(stats.num_reparented, 2);
+    assert!(stats.diverged_merges.is_empty());
+
+    // Change the content of "a". The merge keeps the old content of "a".
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    let tree_a2 = create_tree(&repo, &[(path_a, "a2")]);
+    mut_repo
+        .rewrite_commit(&commit_a)
+        .set_tree_id(tree_a2.id())
+        .write()
+        .unwrap();
+    let stats = mut_repo.reparent_descendants_with_stats().unwrap();
+    assert_eq!(stats.num_reparented, 2);
+    assert_eq!(stats.diverged_merges.len(), 1);
+    let new_merge = &stats.diverged_merges[0];
+    assert_eq!(new_merge.change_id(), commit_merge.change_id());
+    assert_eq!(new_merge.tree_id(), commit_merge.tree_id());
+    assert_ne!(
+        new_merge.parent_tree(tx.repo()).unwrap().id(),
+        *new_merge.tree_id()
+    );
+    // A non-merge child isn't reported even though its diff changed.
+    assert!(!stats
+        .diverged_merges
+        .iter()
+        .any(|commit| commit.change_id() == commit_child_a.change_id()));
+}
+
 #[test]
 fn test_bookmark_hidden_commit() {
     // Test that MutableRepo::set_local_bookmark_target() on a hidden commit makes
@@ -764,7 +858,133 @@ fn test_bookmark_hidden_commit() {
 
     let mut tx = repo.start_transaction();
     tx.repo_mut()
-        .set_local_bookmark_target("b".as_ref(), RefTarget::normal(commit.id().clone()));
+        .set_local_bookmark_target("b".as_ref(), RefTarget::normal(commit.id().clone()))
+        .unwrap();
     let repo = tx.commit("test").unwrap();
     assert_eq!(*repo.view().heads(), hashset! {commit.id().clone()});
 }
+
+#[test]
+fn test_set_bookmark_and_tag_invalid_name() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit = write_random_commit(tx.repo_mut());
+    let target = RefTarget::normal(commit.id().clone());
+    assert_matches!(
+        tx.repo_mut()
+            .set_local_bookmark_target("foo..bar".as_ref(), target.clone()),
+        Err(RefUpdateError::InvalidName(err))
+            if err.kind == InvalidRefNameErrorKind::ConsecutiveDots
+    );
+    assert_matches!(
+        tx.repo_mut().set_tag_target("-v1".as_ref(), target.clone()),
+        Err(RefUpdateError::InvalidName(err)) if err.kind == InvalidRefNameErrorKind::LeadingDash
+    );
+    assert!(tx.repo().view().local_bookmarks().next().is_none());
+    assert!(tx.repo().view().tags().is_empty());
+}
+
+#[test]
+fn test_set_bookmark_and_tag_invalid_legacy_name() {
+    // Names written before the validation was introduced can still be updated
+    // and deleted.
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit1 = write_random_commit(tx.repo_mut());
+    let commit2 = write_random_commit(tx.repo_mut());
+    let mut view = tx.repo().view().store_view().clone();
+    view.local_bookmarks
+        .insert("foo bar".into(), RefTarget::normal(commit1.id().clone()));
+    view.tags
+        .insert("v1..".into(), RefTarget::normal(commit1.id().clone()));
+    tx.repo_mut().set_view(view);
+    let repo = tx.commit("test").unwrap();
+
+    let mut tx = repo.start_transaction();
+    tx.repo_mut()
+        .set_local_bookmark_target("foo bar".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
+    tx.repo_mut()
+        .set_tag_target("v1..".as_ref(), RefTarget::absent())
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+    assert_eq!(
+        repo.view().get_local_bookmark("foo bar".as_ref()),
+        &RefTarget::normal(commit2.id().clone())
+    );
+    assert!(repo.view().tags().is_empty());
+
+    let mut tx = repo.start_transaction();
+    tx.repo_mut()
+        .set_local_bookmark_target("foo bar".as_ref(), RefTarget::absent())
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+    assert!(repo
+        .view()
+        .get_local_bookmark("foo bar".as_ref())
+        .is_absent());
+
+    // Once deleted, the name can't be reused
+    let mut tx = repo.start_transaction();
+    assert_matches!(
+        tx.repo_mut()
+            .set_local_bookmark_target("foo bar".as_ref(), RefTarget::normal(commit1.id().clone())),
+        Err(RefUpdateError::InvalidName(_))
+    );
+}
+
+#[test]
+fn test_metrics_rebase_descendants() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let _commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+    let repo = tx.commit("test").unwrap();
+
+    // Rewrite A with a different tree and rebase its 3 descendants
+    let mut tx = repo.start_transaction();
+    assert_eq!(tx.repo().metrics(), TransactionMetrics::default());
+    let new_tree_id = create_random_tree(&repo);
+    tx.repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_tree_id(new_tree_id)
+        .write()
+        .unwrap();
+    let metrics_before = tx.repo().metrics();
+    assert_eq!(metrics_before.commits_written, 1);
+    assert_eq!(tx.repo_mut().rebase_descendants().unwrap(), 3);
+    let metrics_after = tx.repo().metrics();
+    assert_eq!(
+        metrics_after.commits_written - metrics_before.commits_written,
+        3
+    );
+    assert_eq!(metrics_after.commits_visited, 3);
+    assert_eq!(metrics_after.tree_merges, 3);
+    assert_eq!(
+        metrics_after.store.commit_writes - metrics_before.store.commit_writes,
+        3
+    );
+
+    let unpublished_op = tx.write("test").unwrap();
+    assert_eq!(unpublished_op.metrics().commits_written, 4);
+    assert_eq!(unpublished_op.metrics().commits_visited, 3);
+    let repo = unpublished_op.publish().unwrap();
+
+    // Metrics are collected per transaction
+    let mut tx = repo.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let unpublished_op = tx.write("test").unwrap();
+    assert_eq!(unpublished_op.metrics().commits_written, 1);
+    assert_eq!(unpublished_op.metrics().commits_visited, 0);
+    assert_eq!(unpublished_op.metrics().tree_merges, 0);
+    unpublished_op.leave_unpublished();
+}
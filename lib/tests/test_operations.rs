@@ -20,19 +20,35 @@ use std::time::SystemTime;
 use assert_matches::assert_matches;
 use itertools::Itertools as _;
 use jj_lib::backend::CommitId;
+use jj_lib::commit::Commit;
 use jj_lib::config::ConfigLayer;
 use jj_lib::config::ConfigSource;
 use jj_lib::object_id::ObjectId as _;
 use jj_lib::op_store::OperationId;
+use jj_lib::op_store::RefTarget;
+use jj_lib::op_store::RemoteRef;
+use jj_lib::op_store::RemoteRefState;
 use jj_lib::op_walk;
+use jj_lib::op_walk::OpGraph;
 use jj_lib::op_walk::OpsetEvaluationError;
 use jj_lib::op_walk::OpsetResolutionError;
+use jj_lib::op_walk::RestoreScope;
+use jj_lib::op_walk::UndoChangedRefBehavior;
+use jj_lib::op_walk::UndoOperationError;
+use jj_lib::op_walk::UndoOptions;
+use jj_lib::op_walk::UndoRefName;
+use jj_lib::op_walk::UndoStats;
 use jj_lib::operation::Operation;
+use jj_lib::ref_name::RemoteRefSymbol;
 use jj_lib::repo::ReadonlyRepo;
 use jj_lib::repo::Repo;
+use jj_lib::rewrite::RebaseOptions;
+use jj_lib::rewrite::RebasedCommit;
 use jj_lib::settings::UserSettings;
+use jj_lib::str_util::StringPattern;
 use testutils::create_random_commit;
 use testutils::write_random_commit;
+use testutils::CommitGraphBuilder;
 use testutils::TestRepo;
 
 fn list_dir(dir: &Path) -> Vec<String> {
@@ -244,6 +260,55 @@ fn test_stored_commit_predecessors() {
     assert!(!op.stores_commit_predecessors());
 }
 
+#[test]
+fn test_stored_commit_rewrites() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let loader = repo.loader();
+
+    let mut tx = repo.start_transaction();
+    let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+    let repo = tx.commit("test").unwrap();
+    assert!(repo.operation().commit_rewrites().is_empty());
+
+    // Abandon A, which rebases B, C, and D
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().record_abandoned_commit(&commit_a);
+    let mut rebased = vec![];
+    tx.repo_mut()
+        .rebase_descendants_with_options(&RebaseOptions::default(), |old_commit, new_commit| {
+            let RebasedCommit::Rewritten(new_commit) = new_commit else {
+                panic!("unexpected abandoned commit {old_commit:?}");
+            };
+            rebased.push((old_commit.id().clone(), new_commit.id().clone()));
+        })
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+    assert_eq!(
+        rebased.iter().map(|(old_id, _)| old_id).collect_vec(),
+        [commit_b.id(), commit_c.id(), commit_d.id()]
+    );
+
+    // Reload operation from disk.
+    let op = loader.load_operation(repo.op_id()).unwrap();
+    let rewrites = op.commit_rewrites();
+    assert_eq!(rewrites.pairs, rebased);
+    assert!(!rewrites.truncated);
+
+    // The truncation flag is also stored.
+    let mut data = op.store_operation().clone();
+    data.commit_rewrites.pairs.truncate(1);
+    data.commit_rewrites.truncated = true;
+    let op_id = loader.op_store().write_operation(&data).unwrap();
+    let op = loader.load_operation(&op_id).unwrap();
+    assert_eq!(op.commit_rewrites().pairs, rebased[..1]);
+    assert!(op.commit_rewrites().truncated);
+}
+
 #[test]
 fn test_reparent_range_linear() {
     let test_repo = TestRepo::init();
@@ -449,6 +514,309 @@ fn test_reparent_range_branchy() {
     assert_eq!(new_op_f.parent_ids(), slice::from_ref(repo_d.op_id()));
 }
 
+#[test]
+fn test_op_graph_ancestry() {
+    let test_repo = TestRepo::init();
+    let repo_0 = test_repo.repo;
+    let op_graph = repo_0.loader().op_graph();
+
+    // Set up branchy operation graph:
+    // G
+    // |\
+    // | F
+    // E |
+    // D |
+    // |/
+    // C
+    // B
+    // 0 (initial)
+    let random_tx = |repo: &Arc<ReadonlyRepo>| {
+        let mut tx = repo.start_transaction();
+        write_random_commit(tx.repo_mut());
+        tx
+    };
+    let repo_b = random_tx(&repo_0).commit("op B").unwrap();
+    let repo_c = random_tx(&repo_b).commit("op C").unwrap();
+    let repo_d = random_tx(&repo_c).commit("op D").unwrap();
+    let tx_e = random_tx(&repo_d);
+    let tx_f = random_tx(&repo_c);
+    let repo_g = testutils::commit_transactions(vec![tx_e, tx_f]);
+    let [op_e_id, op_f_id] = repo_g.operation().parent_ids().to_vec().try_into().unwrap();
+    let op_b_id = repo_b.op_id();
+    let op_c_id = repo_c.op_id();
+    let op_d_id = repo_d.op_id();
+    let op_g_id = repo_g.op_id();
+
+    let ancestor_ids: Vec<_> = op_graph
+        .ancestors(op_g_id)
+        .map_ok(|op| op.id().clone())
+        .try_collect()
+        .unwrap();
+    assert_eq!(ancestor_ids.len(), 7);
+    assert_eq!(&ancestor_ids[0], op_g_id);
+    assert_eq!(
+        ancestor_ids[4..],
+        [op_c_id, op_b_id, repo_0.op_id()].map(Clone::clone)
+    );
+    let ancestor_ids: Vec<_> = op_graph
+        .ancestors(&op_f_id)
+        .map_ok(|op| op.id().clone())
+        .try_collect()
+        .unwrap();
+    assert_eq!(
+        ancestor_ids,
+        [&op_f_id, op_c_id, op_b_id, repo_0.op_id()].map(Clone::clone)
+    );
+
+    assert!(op_graph.is_ancestor(op_c_id, op_g_id).unwrap());
+    assert!(op_graph.is_ancestor(op_d_id, op_g_id).unwrap());
+    assert!(op_graph.is_ancestor(&op_f_id, op_g_id).unwrap());
+    assert!(op_graph.is_ancestor(op_g_id, op_g_id).unwrap());
+    assert!(!op_graph.is_ancestor(op_g_id, op_c_id).unwrap());
+    assert!(!op_graph.is_ancestor(&op_e_id, &op_f_id).unwrap());
+    assert!(!op_graph.is_ancestor(&op_f_id, op_d_id).unwrap());
+
+    let common_ancestor_id = |id1, id2| {
+        op_graph
+            .common_ancestor(id1, id2)
+            .unwrap()
+            .map(|op| op.id().clone())
+    };
+    assert_eq!(
+        common_ancestor_id(&op_e_id, &op_f_id).as_ref(),
+        Some(op_c_id)
+    );
+    assert_eq!(
+        common_ancestor_id(op_d_id, &op_f_id).as_ref(),
+        Some(op_c_id)
+    );
+    assert_eq!(
+        common_ancestor_id(op_d_id, &op_e_id).as_ref(),
+        Some(op_d_id)
+    );
+    assert_eq!(common_ancestor_id(op_g_id, op_b_id).as_ref(), Some(op_b_id));
+    assert_eq!(common_ancestor_id(op_g_id, op_g_id).as_ref(), Some(op_g_id));
+}
+
+#[test]
+fn test_op_graph_cache_bounded() {
+    let test_repo = TestRepo::init();
+    let mut repo = test_repo.repo;
+    let root_op_id = repo.op_id().clone();
+    let mut op_ids = vec![root_op_id.clone()];
+    for i in 0..10 {
+        let mut tx = repo.start_transaction();
+        write_random_commit(tx.repo_mut());
+        repo = tx.commit(format!("op {i}")).unwrap();
+        op_ids.push(repo.op_id().clone());
+    }
+
+    let op_graph = OpGraph::with_capacity(repo.op_store().clone(), 3.try_into().unwrap());
+    assert_eq!(op_graph.cached_len(), 0);
+    let ancestor_ids: Vec<_> = op_graph
+        .ancestors(repo.op_id())
+        .map_ok(|op| op.id().clone())
+        .try_collect()
+        .unwrap();
+    assert_eq!(ancestor_ids, op_ids.iter().rev().cloned().collect_vec());
+    assert_eq!(op_graph.cached_len(), 3);
+
+    assert!(op_graph.is_ancestor(&op_ids[1], &op_ids[10]).unwrap());
+    assert!(!op_graph.is_ancestor(&op_ids[10], &op_ids[1]).unwrap());
+    assert_eq!(op_graph.cached_len(), 3);
+    let common_ancestor = op_graph.common_ancestor(&op_ids[3], &op_ids[7]).unwrap();
+    assert_eq!(common_ancestor.unwrap().id(), &op_ids[3]);
+    assert_eq!(op_graph.cached_len(), 3);
+}
+
+#[test]
+fn test_undo_operation_with_later_changes() {
+    let test_repo = TestRepo::init();
+    let repo_0 = test_repo.repo;
+
+    // Op A creates commits and bookmarks, op B moves both bookmarks, op C moves
+    // "main" again.
+    let mut tx = repo_0.start_transaction();
+    let commit1 = write_random_commit(tx.repo_mut());
+    let commit2 = write_random_commit(tx.repo_mut());
+    let commit3 = write_random_commit(tx.repo_mut());
+    for name in ["main", "other"] {
+        tx.repo_mut()
+            .set_local_bookmark_target(name.as_ref(), RefTarget::normal(commit1.id().clone()))
+            .unwrap();
+    }
+    let repo_a = tx.commit("op A").unwrap();
+    let mut tx = repo_a.start_transaction();
+    for name in ["main", "other"] {
+        tx.repo_mut()
+            .set_local_bookmark_target(name.as_ref(), RefTarget::normal(commit2.id().clone()))
+            .unwrap();
+    }
+    let repo_b = tx.commit("op B").unwrap();
+    let mut tx = repo_b.start_transaction();
+    tx.repo_mut()
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit3.id().clone()))
+        .unwrap();
+    let repo_c = tx.commit("op C").unwrap();
+
+    let expected_stats = UndoStats {
+        reverted_refs: vec![UndoRefName::LocalBookmark("other".into())],
+        changed_refs: vec![UndoRefName::LocalBookmark("main".into())],
+    };
+
+    // By default, the bookmark moved again since becomes conflicted
+    let mut tx = repo_c.start_transaction();
+    let stats =
+        op_walk::undo_operation(&mut tx, repo_b.operation(), &UndoOptions::default()).unwrap();
+    assert_eq!(stats, expected_stats);
+    assert_eq!(
+        tx.repo().get_local_bookmark("other".as_ref()),
+        RefTarget::normal(commit1.id().clone())
+    );
+    assert_eq!(
+        tx.repo().get_local_bookmark("main".as_ref()),
+        RefTarget::from_legacy_form(
+            [commit2.id().clone()],
+            [commit3.id().clone(), commit1.id().clone()]
+        )
+    );
+
+    // The bookmark moved again since can be left untouched
+    let mut tx = repo_c.start_transaction();
+    let options = UndoOptions {
+        changed_ref_behavior: UndoChangedRefBehavior::Skip,
+    };
+    let stats = op_walk::undo_operation(&mut tx, repo_b.operation(), &options).unwrap();
+    assert_eq!(stats, expected_stats);
+    assert_eq!(
+        tx.repo().get_local_bookmark("other".as_ref()),
+        RefTarget::normal(commit1.id().clone())
+    );
+    assert_eq!(
+        tx.repo().get_local_bookmark("main".as_ref()),
+        RefTarget::normal(commit3.id().clone())
+    );
+
+    // The root operation can't be undone
+    let mut tx = repo_c.start_transaction();
+    let root_op = repo_0.loader().root_operation();
+    assert_matches!(
+        op_walk::undo_operation(&mut tx, &root_op, &UndoOptions::default()),
+        Err(UndoOperationError::RootOperation)
+    );
+}
+
+#[test]
+fn test_restore_view_partial_local_bookmarks() {
+    let test_repo = TestRepo::init();
+    let repo_0 = test_repo.repo;
+    let origin_main = RemoteRefSymbol {
+        name: "main".as_ref(),
+        remote: "origin".as_ref(),
+    };
+    let tracked_remote_ref = |commit: &Commit| RemoteRef {
+        target: RefTarget::normal(commit.id().clone()),
+        state: RemoteRefState::Tracked,
+    };
+
+    // Op A creates bookmarks, op B moves and deletes them, op C fetches a new
+    // commit to the remote bookmark.
+    let mut tx = repo_0.start_transaction();
+    let commit1 = write_random_commit(tx.repo_mut());
+    let commit2 = write_random_commit(tx.repo_mut());
+    for name in ["main", "other"] {
+        tx.repo_mut()
+            .set_local_bookmark_target(name.as_ref(), RefTarget::normal(commit1.id().clone()))
+            .unwrap();
+    }
+    tx.repo_mut()
+        .set_remote_bookmark(origin_main, tracked_remote_ref(&commit1));
+    let repo_a = tx.commit("op A").unwrap();
+    let mut tx = repo_a.start_transaction();
+    tx.repo_mut()
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
+    tx.repo_mut()
+        .set_local_bookmark_target("other".as_ref(), RefTarget::absent())
+        .unwrap();
+    tx.repo_mut()
+        .set_local_bookmark_target("new".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
+    let repo_b = tx.commit("op B").unwrap();
+    let mut tx = repo_b.start_transaction();
+    let commit3 = write_random_commit(tx.repo_mut());
+    tx.repo_mut()
+        .set_remote_bookmark(origin_main, tracked_remote_ref(&commit3));
+    let repo_c = tx.commit("op C").unwrap();
+
+    // Only the local bookmarks are restored
+    let mut tx = repo_c.start_transaction();
+    op_walk::restore_view_partial(
+        &mut tx,
+        repo_a.operation(),
+        &RestoreScope::LocalBookmarks(StringPattern::everything()),
+    )
+    .unwrap();
+    let repo = tx.commit("restore").unwrap();
+    assert_eq!(
+        repo.view().get_local_bookmark("main".as_ref()),
+        &RefTarget::normal(commit1.id().clone())
+    );
+    assert_eq!(
+        repo.view().get_local_bookmark("other".as_ref()),
+        &RefTarget::normal(commit1.id().clone())
+    );
+    assert_eq!(
+        repo.view().get_local_bookmark("new".as_ref()),
+        RefTarget::absent_ref()
+    );
+    assert_eq!(repo.view().heads(), repo_c.view().heads());
+    assert_eq!(
+        repo.view().get_remote_bookmark(origin_main),
+        &tracked_remote_ref(&commit3)
+    );
+
+    // Bookmarks not matching the pattern are left untouched
+    let mut tx = repo_c.start_transaction();
+    op_walk::restore_view_partial(
+        &mut tx,
+        repo_a.operation(),
+        &RestoreScope::LocalBookmarks(StringPattern::exact("other")),
+    )
+    .unwrap();
+    let repo = tx.commit("restore").unwrap();
+    assert_eq!(
+        repo.view().get_local_bookmark("main".as_ref()),
+        &RefTarget::normal(commit2.id().clone())
+    );
+    assert_eq!(
+        repo.view().get_local_bookmark("new".as_ref()),
+        &RefTarget::normal(commit2.id().clone())
+    );
+    assert_eq!(
+        repo.view().get_local_bookmark("other".as_ref()),
+        &RefTarget::normal(commit1.id().clone())
+    );
+
+    // The full restore also restores the heads and remote bookmarks
+    let mut tx = repo_c.start_transaction();
+    op_walk::restore_view_partial(&mut tx, repo_a.operation(), &RestoreScope::All).unwrap();
+    let repo = tx.commit("restore").unwrap();
+    assert_eq!(
+        repo.view().get_local_bookmark("main".as_ref()),
+        &RefTarget::normal(commit1.id().clone())
+    );
+    assert_eq!(
+        repo.view().get_local_bookmark("new".as_ref()),
+        RefTarget::absent_ref()
+    );
+    assert_eq!(repo.view().heads(), repo_a.view().heads());
+    assert_eq!(
+        repo.view().get_remote_bookmark(origin_main),
+        &tracked_remote_ref(&commit1)
+    );
+}
+
 fn stable_op_id_settings() -> UserSettings {
     let mut config = testutils::base_user_config();
     config.add_layer(
@@ -471,7 +839,7 @@ fn test_resolve_op_id() {
     let mut operations = Vec::new();
     // The actual value of `i` doesn't matter, we just need to make sure we end
     // up with hashes with ambiguous prefixes.
-    for i in (1..5).chain([9, 27]) {
+    for i in (1..4).chain([38, 58, 145]) {
         let tx = repo.start_transaction();
         let repo = tx.commit(format!("transaction {i}")).unwrap();
         operations.push(repo.operation().clone());
@@ -479,12 +847,12 @@ fn test_resolve_op_id() {
     // "6" and "0" are ambiguous
     insta::assert_debug_snapshot!(operations.iter().map(|op| op.id().hex()).collect_vec(), @r#"
     [
-        "ff7608ec55acf1ccb44bee52a0972f8b36864540ee6547d5e124a30bafd3bfbe00418446d5581cf71debac912a091dd2f93a2dd3bcb34bc53c61604aa9c129d6",
-        "b83ded05e46bef569737b8c1293c59af3fe89f72bc2cecd017b5eb96f5c69e50205069eedf144ca4fa9e55ac0c27842dce874b371a752223c5f85c4b6faadf96",
-        "6412c4e33f791b71f440817d3d16c0ee1b7640845db8f5e4146c58e8c3f4329df4662b0edeab5860c86b4679b150f38318a6e3d4ada5803176f9c5273d97f4dd",
-        "6838be3a934e1b8fc966dcf43796a3cc639a3d26edb1e9af94a285f4ce7edaecfe9e101dd7f0338af22e1632f36d634916015b72b026e1529a7b600566c29067",
-        "0ecccbdd90dd12a592dd0de010feda8bf23e4a5650f1946a82df854fc28791ad046b2d42b38060103db7fb99c00787689df98a7d2166d8180666b745cc32b172",
-        "065be6feb0ab573f0638e952ead8482899307d211f80af5dc90952a6171cc7122d5af9f13fde2ce3e37fc5e7776d5e3bc4236d82ce7d2ecbf1f63373c20772e4",
+        "7bbabe2cadb615f28db5e476747700987cc1c8f5c0a9b9a23f8f4ddc8174adb92ca15715501c76323a930c146abf71cba918a3fddcbaea3d777db643b60d42e1",
+        "9b7587e61decdc08cfd64fae77afbb8a3e1b868b143f12e27a531ab1c9fa21bbc4d610c57d64c23be75256d4514934284d148050e0acbd905671b5c06b231aae",
+        "d113a622d3630d3979ff8515525a0fd654f9818bd5b2a5a5d1d942c525486be68cd3be49be1d0e92dc2f6d3e1416aa24a97009a6cbe845e4bee6cfa7f790af30",
+        "67ac5b2dbc4903bad0ee791c4aaf68843793fdb7adc5503cb5b08bbaa637ef31087addbbea4e6d6d3e35c55f2263f67df194661d9362bd5e7b1d695dcf2eb9d0",
+        "0ed862ac9c46632180fc260fb823c1904ffa25f3b3a02f006e1b5c9fdb6914880069b859f3a5ef0a4ba7e70b27e3e14bbd70185cc85c572dbff053b044974cb7",
+        "652fe77ee738d592f7ac0f545fb2d433677ac0db1b83a37215b8c91963e3223147f03bf503abf6c8f9499bfc43a3b7e2e28e9e0ffc332f248d92145dfd843539",
     ]
     "#);
 
@@ -517,6 +885,21 @@ fn test_resolve_op_id() {
             OpsetResolutionError::InvalidIdPrefix(_)
         ))
     );
+    // Id pasted with uppercase digits, whitespace, or punctuation
+    assert_eq!(resolve(" 7BBAB.\n").unwrap(), operations[0]);
+    assert_eq!(resolve("9b75, ").unwrap(), operations[1]);
+    assert_matches!(
+        resolve("6."),
+        Err(OpsetEvaluationError::OpsetResolution(
+            OpsetResolutionError::AmbiguousIdPrefix(_)
+        ))
+    );
+    assert_matches!(
+        resolve("7bb abe"),
+        Err(OpsetEvaluationError::OpsetResolution(
+            OpsetResolutionError::InvalidIdPrefix(_)
+        ))
+    );
     // Unknown id
     assert_matches!(
         resolve("deadbee"),
@@ -732,6 +1115,65 @@ fn test_gc() {
     assert_eq!(expected_view_entries.len(), 1);
 }
 
+#[test]
+fn test_reachable_commits_across_ops() {
+    let test_repo = TestRepo::init();
+    let repo_0 = test_repo.repo;
+    let loader = repo_0.loader();
+    let op_store = repo_0.op_store();
+
+    // Set up linear operation graph:
+    // C (create commit2)
+    // B (abandon commit1)
+    // A (create commit1)
+    // 0 (initial)
+    let mut tx = repo_0.start_transaction();
+    let commit1 = write_random_commit(tx.repo_mut());
+    let repo_a = tx.commit("op A").unwrap();
+    let mut tx = repo_a.start_transaction();
+    tx.repo_mut().record_abandoned_commit(&commit1);
+    tx.repo_mut().rebase_descendants().unwrap();
+    let repo_b = tx.commit("op B").unwrap();
+    let mut tx = repo_b.start_transaction();
+    let commit2 = write_random_commit(tx.repo_mut());
+    let repo_c = tx.commit("op C").unwrap();
+
+    // commit1 is only referenced by the view of op A
+    let summary =
+        op_walk::reachable_commits_across_ops(loader, slice::from_ref(repo_c.operation())).unwrap();
+    assert!(summary.contains(commit1.id()));
+    assert!(summary.contains(commit2.id()));
+    assert!(summary.contains(repo_c.store().root_commit_id()));
+    assert_eq!(
+        summary.op_count,
+        op_walk::walk_ancestors(slice::from_ref(repo_c.operation())).count()
+    );
+    assert!(summary.view_count <= summary.op_count);
+    assert_eq!(
+        summary.hidden_commit_ids(repo_c.as_ref()).unwrap(),
+        [commit1.id().clone()]
+    );
+
+    // Abandon ops A|B, and garbage-collect them
+    let stats = op_walk::reparent_range(
+        op_store.as_ref(),
+        slice::from_ref(repo_b.operation()),
+        slice::from_ref(repo_c.operation()),
+        repo_0.operation(),
+    )
+    .unwrap();
+    assert_eq!(stats.unreachable_count, 2);
+    op_store.gc(&stats.new_head_ids, SystemTime::now()).unwrap();
+    let new_op_c = loader.load_operation(&stats.new_head_ids[0]).unwrap();
+
+    // commit1 is no longer reachable
+    let summary =
+        op_walk::reachable_commits_across_ops(loader, slice::from_ref(&new_op_c)).unwrap();
+    assert!(!summary.contains(commit1.id()));
+    assert!(summary.contains(commit2.id()));
+    assert_eq!(summary.hidden_commit_ids(repo_c.as_ref()).unwrap(), []);
+}
+
 #[track_caller]
 fn extract_multiple_operations_error(
     error: &OpsetEvaluationError,
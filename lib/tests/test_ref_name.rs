@@ -0,0 +1,112 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jj_lib::ref_name::InvalidRefNameErrorKind;
+use jj_lib::ref_name::RefName;
+use jj_lib::ref_name::RefNameBuf;
+use jj_lib::ref_name::RemoteName;
+
+#[test]
+fn test_validate_ref_name() {
+    use InvalidRefNameErrorKind as Kind;
+    let cases = [
+        ("main", None),
+        ("feature/foo-bar", None),
+        ("v1.0", None),
+        ("foo@bar", None),
+        ("foo.lock.bar", None),
+        ("über", None),
+        ("", Some(Kind::Empty)),
+        ("foo bar", Some(Kind::InvalidChar(' '))),
+        ("foo\tbar", Some(Kind::InvalidChar('\t'))),
+        ("foo\u{a0}bar", Some(Kind::InvalidChar('\u{a0}'))),
+        ("foo\x7f", Some(Kind::InvalidChar('\x7f'))),
+        ("foo~1", Some(Kind::InvalidChar('~'))),
+        ("foo^", Some(Kind::InvalidChar('^'))),
+        ("foo:bar", Some(Kind::InvalidChar(':'))),
+        ("foo?", Some(Kind::InvalidChar('?'))),
+        ("foo*", Some(Kind::InvalidChar('*'))),
+        ("foo[bar", Some(Kind::InvalidChar('['))),
+        ("foo\\bar", Some(Kind::InvalidChar('\\'))),
+        ("-foo", Some(Kind::LeadingDash)),
+        ("foo.", Some(Kind::TrailingDot)),
+        ("foo..bar", Some(Kind::ConsecutiveDots)),
+        ("foo@{1}", Some(Kind::AtBrace)),
+        ("@", Some(Kind::SingleAt)),
+        ("/foo", Some(Kind::EmptyComponent)),
+        ("foo/", Some(Kind::EmptyComponent)),
+        ("foo//bar", Some(Kind::EmptyComponent)),
+        (".foo", Some(Kind::LeadingDotInComponent)),
+        ("foo/.bar", Some(Kind::LeadingDotInComponent)),
+        ("foo.lock", Some(Kind::LockSuffix)),
+        ("foo.lock/bar", Some(Kind::LockSuffix)),
+    ];
+    for (name, expected) in cases {
+        let result = RefName::new(name).validate();
+        assert_eq!(
+            result.as_ref().err().map(|err| &err.kind),
+            expected.as_ref(),
+            "{name:?}"
+        );
+        assert_eq!(RemoteName::new(name).validate(), result, "{name:?}");
+        match RefNameBuf::try_from_str(name) {
+            Ok(valid) => assert_eq!(valid, name),
+            Err(err) => assert_eq!(err.name, name),
+        }
+    }
+}
+
+#[test]
+fn test_ref_name_try_from_bytes() {
+    assert_eq!(RefNameBuf::try_from_bytes(b"main").unwrap(), "main");
+    let err = RefNameBuf::try_from_bytes(b"foo\xffbar").unwrap_err();
+    assert_eq!(err.kind, InvalidRefNameErrorKind::InvalidUtf8);
+    assert_eq!(err.name, "foo\u{fffd}bar");
+    assert_eq!(
+        RefNameBuf::try_from_bytes(b"foo bar").unwrap_err().kind,
+        InvalidRefNameErrorKind::InvalidChar(' ')
+    );
+}
+
+#[test]
+fn test_ref_name_normalize() {
+    assert_eq!(RefNameBuf::normalize("main").unwrap(), "main");
+    assert_eq!(RefNameBuf::normalize(" main\n").unwrap(), "main");
+    assert_eq!(RefNameBuf::normalize("/foo//bar/").unwrap(), "foo/bar");
+    assert_eq!(
+        RefNameBuf::normalize("foo bar").unwrap_err().kind,
+        InvalidRefNameErrorKind::InvalidChar(' ')
+    );
+    assert_eq!(
+        RefNameBuf::normalize("//").unwrap_err().kind,
+        InvalidRefNameErrorKind::Empty
+    );
+}
+
+#[test]
+fn test_remote_ref_symbol_validate() {
+    let name = RefName::new("main");
+    assert!(name.to_remote_symbol("origin".as_ref()).validate().is_ok());
+    let err = name
+        .to_remote_symbol("my remote".as_ref())
+        .validate()
+        .unwrap_err();
+    assert_eq!(err.name, "my remote");
+    let err = RefName::new("foo..bar")
+        .to_remote_symbol("origin".as_ref())
+        .validate()
+        .unwrap_err();
+    assert_eq!(err.name, "foo..bar");
+    assert_eq!(err.to_string(), r#"Invalid name "foo..bar""#);
+}
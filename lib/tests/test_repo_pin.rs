@@ -0,0 +1,155 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::fs::File;
+use std::path::Path;
+use std::slice;
+use std::time::SystemTime;
+
+use itertools::Itertools as _;
+use jj_lib::default_index::DefaultIndexStore;
+use jj_lib::default_index::DefaultReadonlyIndex;
+use jj_lib::object_id::ObjectId as _;
+use jj_lib::repo::Repo as _;
+use jj_lib::repo_pin::RepoPinStore;
+use jj_lib::repo_pin::PIN_EXPIRY;
+use testutils::write_random_commit;
+use testutils::TestRepo;
+
+fn list_dir(dir: &Path) -> Vec<String> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_owned())
+        .sorted()
+        .collect()
+}
+
+fn expire(path: &Path) {
+    let file = File::options().write(true).open(path).unwrap();
+    let mtime = SystemTime::now() - PIN_EXPIRY * 2;
+    file.set_modified(mtime).unwrap();
+}
+
+#[test]
+fn test_pin_records_snapshot() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let pin_store = RepoPinStore::for_repo(test_repo.repo_path());
+    assert_eq!(pin_store.active_pins().unwrap(), vec![]);
+
+    let pin = repo.pin().unwrap();
+    assert!(pin.path().exists());
+    let [info] = pin_store.active_pins().unwrap().try_into().unwrap();
+    assert_eq!(&info.operation_id, repo.op_id());
+    assert_eq!(info.pid, std::process::id());
+    let index: &DefaultReadonlyIndex = repo.readonly_index().as_any().downcast_ref().unwrap();
+    assert_eq!(info.index_segments, index.segment_names());
+    assert!(!info.index_segments.is_empty());
+
+    // Dropping the pin removes the file
+    let path = pin.path().to_owned();
+    drop(pin);
+    assert!(!path.exists());
+    assert_eq!(pin_store.active_pins().unwrap(), vec![]);
+
+    // Stale pins are ignored and removed
+    let pin = repo.pin().unwrap();
+    expire(pin.path());
+    assert_eq!(pin_store.active_pins().unwrap(), vec![]);
+    assert!(!pin.path().exists());
+
+    // Renewing the pin keeps it alive
+    let pin = repo.pin().unwrap();
+    expire(pin.path());
+    pin.renew().unwrap();
+    assert_eq!(pin_store.active_pins().unwrap().len(), 1);
+}
+
+#[test]
+fn test_pin_keeps_operations_from_gc() {
+    let test_repo = TestRepo::init();
+    let op_dir = test_repo.repo_path().join("op_store").join("operations");
+    let repo_0 = test_repo.repo;
+    let op_store = repo_0.op_store();
+
+    // B and C are siblings, and C is only kept alive by the pin
+    let mut tx = repo_0.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let repo_a = tx.commit("op A").unwrap();
+    let mut tx = repo_a.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let repo_b = tx.commit("op B").unwrap();
+    let mut tx = repo_a.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let repo_c = tx.commit("op C").unwrap();
+    let head_ids = slice::from_ref(repo_b.op_id());
+
+    let pin = repo_c.pin().unwrap();
+    op_store.gc(head_ids, SystemTime::now()).unwrap();
+    assert!(list_dir(&op_dir).contains(&repo_c.op_id().hex()));
+    assert!(op_store.read_operation(repo_c.op_id()).is_ok());
+
+    // Collected once the pin is dropped
+    drop(pin);
+    op_store.gc(head_ids, SystemTime::now()).unwrap();
+    assert!(!list_dir(&op_dir).contains(&repo_c.op_id().hex()));
+    assert!(list_dir(&op_dir).contains(&repo_b.op_id().hex()));
+
+    // Expired pins don't keep operations alive
+    let pin = repo_b.pin().unwrap();
+    expire(pin.path());
+    op_store
+        .gc(slice::from_ref(repo_a.op_id()), SystemTime::now())
+        .unwrap();
+    assert!(!list_dir(&op_dir).contains(&repo_b.op_id().hex()));
+    assert!(!pin.path().exists());
+}
+
+#[test]
+fn test_pin_keeps_index_segments_on_reinit() {
+    let test_repo = TestRepo::init();
+    let segments_dir = test_repo.repo_path().join("index").join("segments");
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let repo = tx.commit("test").unwrap();
+    let index: &DefaultReadonlyIndex = repo.readonly_index().as_any().downcast_ref().unwrap();
+    let pinned_segments = index.segment_names();
+    let default_index_store: &DefaultIndexStore =
+        repo.index_store().as_any().downcast_ref().unwrap();
+
+    let pin = repo.pin().unwrap();
+    default_index_store.reinit().unwrap();
+    assert_eq!(
+        list_dir(&segments_dir),
+        pinned_segments.iter().cloned().sorted().collect_vec()
+    );
+
+    // Collected once the pin is dropped
+    drop(pin);
+    default_index_store.reinit().unwrap();
+    assert_eq!(list_dir(&segments_dir), Vec::<String>::new());
+
+    // Expired pins don't keep segments alive
+    let repo = test_repo
+        .env
+        .load_repo_at_head(&testutils::user_settings(), test_repo.repo_path());
+    let pin = repo.pin().unwrap();
+    assert!(!list_dir(&segments_dir).is_empty());
+    expire(pin.path());
+    default_index_store.reinit().unwrap();
+    assert_eq!(list_dir(&segments_dir), Vec::<String>::new());
+}
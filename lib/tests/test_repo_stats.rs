@@ -0,0 +1,107 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jj_lib::op_store::RefTarget;
+use jj_lib::op_store::RemoteRef;
+use jj_lib::op_store::RemoteRefState;
+use jj_lib::ref_name::RemoteRefSymbol;
+use jj_lib::ref_name::WorkspaceNameBuf;
+use jj_lib::repo::RepoStatsOptions;
+use jj_lib::repo::ViewStats;
+use testutils::write_random_commit;
+use testutils::TestRepo;
+
+#[test]
+fn test_gather_stats() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    let commit1 = write_random_commit(mut_repo);
+    let commit2 = write_random_commit(mut_repo);
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit1.id().clone()))
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target("feature".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
+    mut_repo.set_remote_bookmark(
+        RemoteRefSymbol {
+            name: "main".as_ref(),
+            remote: "origin".as_ref(),
+        },
+        RemoteRef {
+            target: RefTarget::normal(commit1.id().clone()),
+            state: RemoteRefState::Tracked,
+        },
+    );
+    mut_repo
+        .set_tag_target("v1".as_ref(), RefTarget::normal(commit1.id().clone()))
+        .unwrap();
+    mut_repo
+        .set_wc_commit(WorkspaceNameBuf::from("default"), commit2.id().clone())
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    // Only the cheap statistics are gathered by default
+    let stats = repo.gather_stats(&RepoStatsOptions::default()).unwrap();
+    assert_eq!(
+        stats.view,
+        Some(ViewStats {
+            num_heads: 2,
+            num_local_bookmarks: 2,
+            num_remote_bookmarks: 1,
+            num_tags: 1,
+            num_workspaces: 1,
+        })
+    );
+    let index_stats = stats.index.unwrap();
+    // The root commit is indexed too
+    assert_eq!(index_stats.num_commits, 3);
+    assert_eq!(
+        index_stats
+            .segments
+            .iter()
+            .map(|segment| segment.num_commits)
+            .sum::<u32>(),
+        3
+    );
+    assert!(index_stats
+        .segments
+        .iter()
+        .all(|segment| segment.file_size.is_some_and(|size| size > 0)));
+    assert_eq!(stats.op_log_length, None);
+    assert_eq!(stats.storage, None);
+
+    // The root operation and the transaction above
+    let stats = repo.gather_stats(&RepoStatsOptions::all()).unwrap();
+    assert_eq!(stats.op_log_length, Some(2));
+    let storage = stats.storage.unwrap();
+    assert!(storage.num_objects.unwrap() >= 2);
+    assert_eq!(storage.size_bytes, None);
+
+    // Each statistic can be skipped
+    let options = RepoStatsOptions {
+        view: false,
+        index: false,
+        op_log: true,
+        storage: false,
+    };
+    let stats = repo.gather_stats(&options).unwrap();
+    assert_eq!(stats.view, None);
+    assert_eq!(stats.index, None);
+    assert_eq!(stats.op_log_length, Some(2));
+    assert_eq!(stats.storage, None);
+}
@@ -0,0 +1,160 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use assert_matches::assert_matches;
+use itertools::Itertools as _;
+use jj_lib::backend::Backend as _;
+use jj_lib::backend::BackendError;
+use jj_lib::backend::CommitId;
+use jj_lib::config::ConfigLayer;
+use jj_lib::config::ConfigSource;
+use jj_lib::repo::Repo as _;
+use jj_lib::retrying_backend::RetryPolicy;
+use jj_lib::retrying_backend::RetryableErrorKind;
+use jj_lib::retrying_backend::RetryingBackend;
+use jj_lib::settings::UserSettings;
+use pollster::FutureExt as _;
+use testutils::base_user_config;
+use testutils::test_backend::TestBackend;
+use testutils::CommitGraphBuilder;
+use testutils::TestRepo;
+
+fn retry_settings(max_attempts: usize) -> UserSettings {
+    let mut config = base_user_config();
+    config.add_layer(
+        ConfigLayer::parse(
+            ConfigSource::User,
+            &format!(
+                "backend.retry.max-attempts = {max_attempts}\n\
+                 backend.retry.initial-backoff-ms = 0"
+            ),
+        )
+        .unwrap(),
+    );
+    UserSettings::from_config(config).unwrap()
+}
+
+#[test]
+fn test_rebase_with_transient_failures() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    tx.commit("test").unwrap();
+
+    // Load the repo again so the backend gets wrapped according to the
+    // settings
+    let repo = test_repo
+        .env
+        .load_repo_at_head(&retry_settings(4), test_repo.repo_path());
+    let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
+    let commit_a = repo.store().get_commit(commit_a.id()).unwrap();
+
+    // The first request fails 3 times and succeeds on the 4th attempt
+    test_backend.inject_transient_failures(3);
+    let mut tx = repo.start_transaction();
+    let new_commit_a = tx
+        .repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    assert_eq!(test_backend.pending_transient_failures(), 0);
+
+    // Rebasing the descendants also survives failures
+    test_backend.inject_transient_failures(3);
+    let num_rebased = tx.repo_mut().rebase_descendants().unwrap();
+    assert_eq!(num_rebased, 2);
+    let repo = tx.commit("test").unwrap();
+    assert_eq!(test_backend.pending_transient_failures(), 0);
+
+    let [head_id] = repo.view().heads().iter().collect_array().unwrap();
+    let new_commit_c = repo.store().get_commit(head_id).unwrap();
+    assert_eq!(new_commit_c.change_id(), commit_c.change_id());
+    let new_commit_b = repo
+        .store()
+        .get_commit(&new_commit_c.parent_ids()[0])
+        .unwrap();
+    assert_eq!(new_commit_b.change_id(), commit_b.change_id());
+    assert_eq!(new_commit_b.parent_ids(), vec![new_commit_a.id().clone()]);
+}
+
+#[test]
+fn test_rewrite_with_too_many_transient_failures() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit_a = CommitGraphBuilder::new(tx.repo_mut()).initial_commit();
+    tx.commit("test").unwrap();
+
+    let repo = test_repo
+        .env
+        .load_repo_at_head(&retry_settings(3), test_repo.repo_path());
+    let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
+    let commit_a = repo.store().get_commit(commit_a.id()).unwrap();
+
+    // The error is returned once all 3 attempts have failed
+    test_backend.inject_transient_failures(4);
+    let mut tx = repo.start_transaction();
+    let result = tx
+        .repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_description("rewritten")
+        .write();
+    assert_matches!(result, Err(BackendError::Transient(_)));
+    assert_eq!(test_backend.pending_transient_failures(), 1);
+}
+
+#[test]
+fn test_retry_count() {
+    let backend = RetryingBackend::new(
+        Box::new(TestBackend::with_data(Default::default())),
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::ZERO,
+            retryable_errors: vec![RetryableErrorKind::Transient],
+        },
+    );
+    let test_backend: &TestBackend = backend.as_any().downcast_ref().unwrap();
+    let root_commit_id = backend.root_commit_id().clone();
+
+    test_backend.inject_transient_failures(2);
+    assert!(backend.read_commit(&root_commit_id).block_on().is_ok());
+    assert_eq!(backend.retry_count(), 2);
+    assert_eq!(test_backend.pending_transient_failures(), 0);
+
+    // Errors that aren't transient are returned immediately
+    let missing_id = CommitId::from_hex("0123456789abcdef0123");
+    assert_matches!(
+        backend.read_commit(&missing_id).block_on(),
+        Err(BackendError::ObjectNotFound { .. })
+    );
+    assert_eq!(backend.retry_count(), 2);
+
+    // Requests are attempted at most 3 times
+    test_backend.inject_transient_failures(5);
+    assert_matches!(
+        backend.read_commit(&root_commit_id).block_on(),
+        Err(BackendError::Transient(_))
+    );
+    assert_eq!(backend.retry_count(), 4);
+    assert_eq!(test_backend.pending_transient_failures(), 2);
+}
@@ -12,9 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::any::Any;
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::iter;
 use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
 use assert_matches::assert_matches;
 use chrono::DateTime;
@@ -42,22 +51,29 @@ use jj_lib::ref_name::WorkspaceNameBuf;
 use jj_lib::repo::Repo;
 use jj_lib::repo_path::RepoPath;
 use jj_lib::repo_path::RepoPathUiConverter;
+use jj_lib::revset::expect_literal;
 use jj_lib::revset::parse;
 use jj_lib::revset::DefaultSymbolResolver;
 use jj_lib::revset::FailingSymbolResolver;
 use jj_lib::revset::Revset;
 use jj_lib::revset::RevsetAliasesMap;
 use jj_lib::revset::RevsetDiagnostics;
+use jj_lib::revset::RevsetEvaluationContext;
+use jj_lib::revset::RevsetEvaluationError;
 use jj_lib::revset::RevsetExpression;
 use jj_lib::revset::RevsetExtensions;
+use jj_lib::revset::RevsetFilterExtension;
 use jj_lib::revset::RevsetFilterPredicate;
 use jj_lib::revset::RevsetParseContext;
+use jj_lib::revset::RevsetParseError;
+use jj_lib::revset::RevsetParseErrorKind;
 use jj_lib::revset::RevsetResolutionError;
 use jj_lib::revset::RevsetWorkspaceContext;
 use jj_lib::revset::SymbolResolver as _;
 use jj_lib::revset::SymbolResolverExtension;
 use jj_lib::signing::SignBehavior;
 use jj_lib::signing::Signer;
+use jj_lib::str_util::StringPattern;
 use jj_lib::test_signing_backend::TestSigningBackend;
 use jj_lib::workspace::Workspace;
 use test_case::test_case;
@@ -205,6 +221,24 @@ fn test_resolve_symbol_commit_id() {
         Err(RevsetResolutionError::NoSuchRevision{name, candidates}) if name == "010" && candidates.is_empty()
     );
 
+    // Test commit id pasted with uppercase digits, whitespace, or punctuation
+    assert_eq!(
+        resolve_symbol(repo.as_ref(), "019F179B").unwrap(),
+        vec![commits[0].id().clone()]
+    );
+    assert_eq!(
+        resolve_symbol(repo.as_ref(), r#"" 017.""#).unwrap(),
+        vec![commits[2].id().clone()]
+    );
+    assert_matches!(
+        resolve_symbol(repo.as_ref(), r#""01.""#),
+        Err(RevsetResolutionError::AmbiguousCommitIdPrefix(s)) if s == "01."
+    );
+    assert_matches!(
+        resolve_symbol(repo.as_ref(), r#""01 7""#),
+        Err(RevsetResolutionError::NoSuchRevision{name, candidates}) if name == "01 7" && candidates.is_empty()
+    );
+
     // Test non-hex string
     assert_matches!(
         resolve_symbol(repo.as_ref(), "foo"),
@@ -338,6 +372,24 @@ fn test_resolve_symbol_change_id(readonly: bool) {
         Err(RevsetResolutionError::NoSuchRevision{name, candidates}) if name == "zvlyw" && candidates.is_empty()
     );
 
+    // Test change id pasted with uppercase letters, whitespace, or punctuation
+    assert_eq!(
+        resolve_symbol(repo, "ZVLYX").unwrap(),
+        vec![commits[0].id().clone()]
+    );
+    assert_eq!(
+        resolve_symbol(repo, r#""zvlyn. ""#).unwrap(),
+        vec![commits[2].id().clone()]
+    );
+    assert_matches!(
+        resolve_symbol(repo, r#""zvly,""#),
+        Err(RevsetResolutionError::AmbiguousChangeIdPrefix(s)) if s == "zvly,"
+    );
+    assert_matches!(
+        resolve_symbol(repo, r#""zv lyx""#),
+        Err(RevsetResolutionError::NoSuchRevision{name, candidates}) if name == "zv lyx" && candidates.is_empty()
+    );
+
     // Test that commit and changed id don't conflict ("040" and "zvz" are the
     // same).
     assert_eq!(
@@ -348,6 +400,11 @@ fn test_resolve_symbol_change_id(readonly: bool) {
         resolve_symbol(repo, "zvz").unwrap(),
         vec![commits[1].id().clone()]
     );
+    // Uppercase letters aren't mixed up between the two alphabets
+    assert_eq!(
+        resolve_symbol(repo, "ZVZ").unwrap(),
+        vec![commits[1].id().clone()]
+    );
 
     // Test non-hex string
     assert_matches!(
@@ -535,23 +592,29 @@ fn test_resolve_symbol_bookmarks() {
     let commit4 = write_random_commit(mut_repo);
     let commit5 = write_random_commit(mut_repo);
 
-    mut_repo.set_local_bookmark_target("local".as_ref(), RefTarget::normal(commit1.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("local".as_ref(), RefTarget::normal(commit1.id().clone()))
+        .unwrap();
     mut_repo.set_remote_bookmark(
         remote_symbol("remote", "origin"),
         normal_tracked_remote_ref(commit2.id()),
     );
-    mut_repo.set_local_bookmark_target(
-        "local-remote".as_ref(),
-        RefTarget::normal(commit3.id().clone()),
-    );
+    mut_repo
+        .set_local_bookmark_target(
+            "local-remote".as_ref(),
+            RefTarget::normal(commit3.id().clone()),
+        )
+        .unwrap();
     mut_repo.set_remote_bookmark(
         remote_symbol("local-remote", "origin"),
         normal_tracked_remote_ref(commit4.id()),
     );
-    mut_repo.set_local_bookmark_target(
-        "local-remote@origin".as_ref(), // not a remote bookmark
-        RefTarget::normal(commit5.id().clone()),
-    );
+    mut_repo
+        .set_local_bookmark_target(
+            "local-remote@origin".as_ref(), // not a remote bookmark
+            RefTarget::normal(commit5.id().clone()),
+        )
+        .unwrap();
     mut_repo.set_remote_bookmark(
         remote_symbol("local-remote", "mirror"),
         tracked_remote_ref(mut_repo.get_local_bookmark("local-remote".as_ref())),
@@ -565,13 +628,15 @@ fn test_resolve_symbol_bookmarks() {
         tracked_remote_ref(mut_repo.get_local_bookmark("local-remote".as_ref())),
     );
 
-    mut_repo.set_local_bookmark_target(
-        "local-conflicted".as_ref(),
-        RefTarget::from_legacy_form(
-            [commit1.id().clone()],
-            [commit3.id().clone(), commit2.id().clone()],
-        ),
-    );
+    mut_repo
+        .set_local_bookmark_target(
+            "local-conflicted".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit1.id().clone()],
+                [commit3.id().clone(), commit2.id().clone()],
+            ),
+        )
+        .unwrap();
     mut_repo.set_remote_bookmark(
         remote_symbol("remote-conflicted", "origin"),
         tracked_remote_ref(RefTarget::from_legacy_form(
@@ -762,14 +827,18 @@ fn test_resolve_symbol_tags() {
     let commit2 = write_random_commit(mut_repo);
     let commit3 = write_random_commit(mut_repo);
 
-    mut_repo.set_tag_target(
-        "tag-bookmark".as_ref(),
-        RefTarget::normal(commit1.id().clone()),
-    );
-    mut_repo.set_local_bookmark_target(
-        "tag-bookmark".as_ref(),
-        RefTarget::normal(commit2.id().clone()),
-    );
+    mut_repo
+        .set_tag_target(
+            "tag-bookmark".as_ref(),
+            RefTarget::normal(commit1.id().clone()),
+        )
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target(
+            "tag-bookmark".as_ref(),
+            RefTarget::normal(commit2.id().clone()),
+        )
+        .unwrap();
     mut_repo.set_git_ref_target(
         "refs/tags/unimported".as_ref(),
         RefTarget::normal(commit3.id().clone()),
@@ -791,8 +860,14 @@ fn test_resolve_symbol_tags() {
     mut_repo
         .set_wc_commit(ws_name.clone(), commit1.id().clone())
         .unwrap();
-    mut_repo.set_tag_target("@".as_ref(), RefTarget::normal(commit2.id().clone()));
-    mut_repo.set_tag_target("root".as_ref(), RefTarget::normal(commit3.id().clone()));
+    // Tag named "@" can't be created by MutableRepo, but may exist in old views.
+    let mut view = mut_repo.view().store_view().clone();
+    view.tags
+        .insert("@".into(), RefTarget::normal(commit2.id().clone()));
+    mut_repo.set_view(view);
+    mut_repo
+        .set_tag_target("root".as_ref(), RefTarget::normal(commit3.id().clone()))
+        .unwrap();
     assert_eq!(
         resolve_symbol(mut_repo, r#""@""#).unwrap(),
         vec![commit2.id().clone()]
@@ -2034,14 +2109,18 @@ fn test_evaluate_expression_bookmarks() {
     // Can get bookmarks when there are none
     assert_eq!(resolve_commit_ids(mut_repo, "bookmarks()"), vec![]);
     // Can get a few bookmarks
-    mut_repo.set_local_bookmark_target(
-        "bookmark1".as_ref(),
-        RefTarget::normal(commit1.id().clone()),
-    );
-    mut_repo.set_local_bookmark_target(
-        "bookmark2".as_ref(),
-        RefTarget::normal(commit2.id().clone()),
-    );
+    mut_repo
+        .set_local_bookmark_target(
+            "bookmark1".as_ref(),
+            RefTarget::normal(commit1.id().clone()),
+        )
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target(
+            "bookmark2".as_ref(),
+            RefTarget::normal(commit2.id().clone()),
+        )
+        .unwrap();
     assert_eq!(
         resolve_commit_ids(mut_repo, "bookmarks()"),
         vec![commit2.id().clone(), commit1.id().clone()]
@@ -2087,30 +2166,38 @@ fn test_evaluate_expression_bookmarks() {
     );
     // Two bookmarks pointing to the same commit does not result in a duplicate in
     // the revset
-    mut_repo.set_local_bookmark_target(
-        "bookmark3".as_ref(),
-        RefTarget::normal(commit2.id().clone()),
-    );
+    mut_repo
+        .set_local_bookmark_target(
+            "bookmark3".as_ref(),
+            RefTarget::normal(commit2.id().clone()),
+        )
+        .unwrap();
     assert_eq!(
         resolve_commit_ids(mut_repo, "bookmarks()"),
         vec![commit2.id().clone(), commit1.id().clone()]
     );
     // Can get bookmarks when there are conflicted refs
-    mut_repo.set_local_bookmark_target(
-        "bookmark1".as_ref(),
-        RefTarget::from_legacy_form(
-            [commit1.id().clone()],
-            [commit2.id().clone(), commit3.id().clone()],
-        ),
-    );
-    mut_repo.set_local_bookmark_target(
-        "bookmark2".as_ref(),
-        RefTarget::from_legacy_form(
-            [commit2.id().clone()],
-            [commit3.id().clone(), commit4.id().clone()],
-        ),
-    );
-    mut_repo.set_local_bookmark_target("bookmark3".as_ref(), RefTarget::absent());
+    mut_repo
+        .set_local_bookmark_target(
+            "bookmark1".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit1.id().clone()],
+                [commit2.id().clone(), commit3.id().clone()],
+            ),
+        )
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target(
+            "bookmark2".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit2.id().clone()],
+                [commit3.id().clone(), commit4.id().clone()],
+            ),
+        )
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target("bookmark3".as_ref(), RefTarget::absent())
+        .unwrap();
     assert_eq!(
         resolve_commit_ids(mut_repo, "bookmarks()"),
         vec![
@@ -2312,8 +2399,12 @@ fn test_evaluate_expression_tags() {
     // Can get tags when there are none
     assert_eq!(resolve_commit_ids(mut_repo, "tags()"), vec![]);
     // Can get a few tags
-    mut_repo.set_tag_target("tag1".as_ref(), RefTarget::normal(commit1.id().clone()));
-    mut_repo.set_tag_target("tag2".as_ref(), RefTarget::normal(commit2.id().clone()));
+    mut_repo
+        .set_tag_target("tag1".as_ref(), RefTarget::normal(commit1.id().clone()))
+        .unwrap();
+    mut_repo
+        .set_tag_target("tag2".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
     assert_eq!(
         resolve_commit_ids(mut_repo, "tags()"),
         vec![commit2.id().clone(), commit1.id().clone()]
@@ -2349,27 +2440,35 @@ fn test_evaluate_expression_tags() {
     assert_eq!(resolve_commit_ids(mut_repo, "tags(exact:ag1)"), vec![]);
     // Two tags pointing to the same commit does not result in a duplicate in
     // the revset
-    mut_repo.set_tag_target("tag3".as_ref(), RefTarget::normal(commit2.id().clone()));
+    mut_repo
+        .set_tag_target("tag3".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
     assert_eq!(
         resolve_commit_ids(mut_repo, "tags()"),
         vec![commit2.id().clone(), commit1.id().clone()]
     );
     // Can get tags when there are conflicted refs
-    mut_repo.set_tag_target(
-        "tag1".as_ref(),
-        RefTarget::from_legacy_form(
-            [commit1.id().clone()],
-            [commit2.id().clone(), commit3.id().clone()],
-        ),
-    );
-    mut_repo.set_tag_target(
-        "tag2".as_ref(),
-        RefTarget::from_legacy_form(
-            [commit2.id().clone()],
-            [commit3.id().clone(), commit4.id().clone()],
-        ),
-    );
-    mut_repo.set_tag_target("tag3".as_ref(), RefTarget::absent());
+    mut_repo
+        .set_tag_target(
+            "tag1".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit1.id().clone()],
+                [commit2.id().clone(), commit3.id().clone()],
+            ),
+        )
+        .unwrap();
+    mut_repo
+        .set_tag_target(
+            "tag2".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit2.id().clone()],
+                [commit3.id().clone(), commit4.id().clone()],
+            ),
+        )
+        .unwrap();
+    mut_repo
+        .set_tag_target("tag3".as_ref(), RefTarget::absent())
+        .unwrap();
     assert_eq!(
         resolve_commit_ids(mut_repo, "tags()"),
         vec![
@@ -2744,6 +2843,86 @@ fn test_evaluate_expression_description() {
     );
 }
 
+#[test]
+fn test_evaluate_expression_custom_function() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+
+    let commit1 = create_random_commit(mut_repo)
+        .set_description("fix crash\n\nTicket: ABC-123\n")
+        .write()
+        .unwrap();
+    let commit2 = create_random_commit(mut_repo)
+        .set_parents(vec![commit1.id().clone()])
+        .set_description("add feature\n\nTicket: ABC-456\n")
+        .write()
+        .unwrap();
+
+    // The resolver may capture state
+    let prefix = "Ticket: ".to_owned();
+    let mut extensions = RevsetExtensions::new();
+    extensions.add_function("ticket", 1, move |diagnostics, args, _context| {
+        let id: String = expect_literal(diagnostics, "string", &args[0])?;
+        Ok(RevsetExpression::filter(
+            RevsetFilterPredicate::Description(StringPattern::Substring(format!("{prefix}{id}"))),
+        ))
+    });
+    let evaluate = |revset_str: &str, aliases_map: &RevsetAliasesMap| {
+        let context = RevsetParseContext {
+            aliases_map,
+            local_variables: HashMap::new(),
+            user_email: "",
+            date_pattern_context: chrono::Utc::now().fixed_offset().into(),
+            extensions: &extensions,
+            workspace: None,
+        };
+        let expression = parse(&mut RevsetDiagnostics::new(), revset_str, &context)?;
+        let symbol_resolver = DefaultSymbolResolver::new(mut_repo, extensions.symbol_resolvers());
+        let commit_ids: Vec<_> = expression
+            .resolve_user_expression(mut_repo, &symbol_resolver)
+            .unwrap()
+            .evaluate(mut_repo)
+            .unwrap()
+            .iter()
+            .try_collect()
+            .unwrap();
+        Ok::<_, RevsetParseError>(commit_ids)
+    };
+    let no_aliases = RevsetAliasesMap::new();
+
+    assert_eq!(
+        evaluate("ticket('ABC-123')", &no_aliases).unwrap(),
+        vec![commit1.id().clone()]
+    );
+    assert_eq!(
+        evaluate("ticket('ABC-456') | ticket('ABC-123')", &no_aliases).unwrap(),
+        vec![commit2.id().clone(), commit1.id().clone()]
+    );
+    assert_eq!(evaluate("ticket('ABC-789')", &no_aliases).unwrap(), vec![]);
+
+    // The number of arguments is checked
+    assert_matches!(
+        evaluate("ticket()", &no_aliases).unwrap_err().kind(),
+        RevsetParseErrorKind::InvalidFunctionArguments { name, .. } if name == "ticket"
+    );
+    // Registered functions are suggested for typos
+    assert_matches!(
+        evaluate("tickets('ABC-123')", &no_aliases).unwrap_err().kind(),
+        RevsetParseErrorKind::NoSuchFunction { candidates, .. } if candidates == &["ticket"]
+    );
+
+    // User aliases shadow the registered function
+    let mut aliases_map = RevsetAliasesMap::new();
+    aliases_map.insert("ticket(x)", "root()").unwrap();
+    assert_eq!(
+        evaluate("ticket('ABC-123')", &aliases_map).unwrap(),
+        vec![repo.store().root_commit_id().clone()]
+    );
+}
+
 #[test]
 fn test_evaluate_expression_author() {
     let test_repo = TestRepo::init();
@@ -3202,10 +3381,12 @@ fn test_evaluate_expression_at_operation() {
         .set_description("commit2@op1")
         .write()
         .unwrap();
-    tx.repo_mut().set_local_bookmark_target(
-        "commit1_ref".as_ref(),
-        RefTarget::normal(commit1_op1.id().clone()),
-    );
+    tx.repo_mut()
+        .set_local_bookmark_target(
+            "commit1_ref".as_ref(),
+            RefTarget::normal(commit1_op1.id().clone()),
+        )
+        .unwrap();
     let repo1 = tx.commit("test").unwrap();
 
     let mut tx = repo1.start_transaction();
@@ -3333,8 +3514,12 @@ fn test_evaluate_expression_coalesce() {
     let mut graph_builder = CommitGraphBuilder::new(mut_repo);
     let commit1 = graph_builder.initial_commit();
     let commit2 = graph_builder.commit_with_parents(&[&commit1]);
-    mut_repo.set_local_bookmark_target("commit1".as_ref(), RefTarget::normal(commit1.id().clone()));
-    mut_repo.set_local_bookmark_target("commit2".as_ref(), RefTarget::normal(commit2.id().clone()));
+    mut_repo
+        .set_local_bookmark_target("commit1".as_ref(), RefTarget::normal(commit1.id().clone()))
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target("commit2".as_ref(), RefTarget::normal(commit2.id().clone()))
+        .unwrap();
 
     assert_eq!(resolve_commit_ids(mut_repo, "coalesce()"), vec![]);
     assert_eq!(resolve_commit_ids(mut_repo, "coalesce(none())"), vec![]);
@@ -3776,6 +3961,102 @@ fn test_evaluate_expression_file() {
     );
 }
 
+#[test]
+fn test_evaluate_expression_changes_against() {
+    let test_workspace = TestWorkspace::init();
+    let repo = &test_workspace.repo;
+
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+
+    let modified_reverted = repo_path("modified_reverted");
+    let clean_modified = repo_path("clean_modified");
+    let tree1 = create_tree(repo, &[(modified_reverted, "1"), (clean_modified, "1")]);
+    let tree2 = create_tree(repo, &[(modified_reverted, "2"), (clean_modified, "1")]);
+    let tree4 = create_tree(repo, &[(modified_reverted, "1"), (clean_modified, "2")]);
+    let commit1 = mut_repo
+        .new_commit(vec![repo.store().root_commit_id().clone()], tree1.id())
+        .write()
+        .unwrap();
+    let commit2 = mut_repo
+        .new_commit(vec![commit1.id().clone()], tree2.id())
+        .write()
+        .unwrap();
+    let commit3 = mut_repo
+        .new_commit(vec![commit2.id().clone()], tree1.id())
+        .write()
+        .unwrap();
+    let commit4 = mut_repo
+        .new_commit(vec![commit3.id().clone()], tree4.id())
+        .write()
+        .unwrap();
+
+    let query = |revset_str: &str| {
+        resolve_commit_ids_in_workspace(
+            mut_repo,
+            revset_str,
+            &test_workspace.workspace,
+            Some(test_workspace.workspace.workspace_root()),
+        )
+    };
+
+    // The revert is a change relative to its parent
+    assert_eq!(
+        query(&format!(
+            "{}:: & files({modified_reverted:?})",
+            commit1.id()
+        )),
+        vec![
+            commit3.id().clone(),
+            commit2.id().clone(),
+            commit1.id().clone()
+        ]
+    );
+    // but not relative to the base, and neither are its descendants
+    assert_eq!(
+        query(&format!(
+            "{id}:: & changes_against({id}, {modified_reverted:?})",
+            id = commit1.id()
+        )),
+        vec![commit2.id().clone()]
+    );
+    assert_eq!(
+        query(&format!(
+            "{id}:: & changes_against({id}, {clean_modified:?})",
+            id = commit1.id()
+        )),
+        vec![commit4.id().clone()]
+    );
+    assert_eq!(
+        query(&format!(
+            "{id}:: & changes_against({id}, all())",
+            id = commit1.id()
+        )),
+        vec![commit4.id().clone(), commit2.id().clone()]
+    );
+    // Without candidates, all commits are filtered
+    assert_eq!(
+        query(&format!(
+            "changes_against({}, {clean_modified:?})",
+            commit3.id()
+        )),
+        vec![commit4.id().clone(), repo.store().root_commit_id().clone()]
+    );
+
+    // The base must be a single commit
+    let expression = RevsetExpression::commits(vec![commit1.id().clone(), commit2.id().clone()])
+        .changes_against(FilesetExpression::all());
+    assert_matches!(
+        expression.evaluate(mut_repo),
+        Err(RevsetEvaluationError::Other(_))
+    );
+    let expression = RevsetExpression::none().changes_against(FilesetExpression::all());
+    assert_matches!(
+        expression.evaluate(mut_repo),
+        Err(RevsetEvaluationError::Other(_))
+    );
+}
+
 #[test]
 fn test_evaluate_expression_diff_contains() {
     let test_workspace = TestWorkspace::init();
@@ -4114,10 +4395,12 @@ fn test_no_such_revision_suggestion() {
     let commit = write_random_commit(mut_repo);
 
     for bookmark_name in ["foo", "bar", "baz"] {
-        mut_repo.set_local_bookmark_target(
-            bookmark_name.as_ref(),
-            RefTarget::normal(commit.id().clone()),
-        );
+        mut_repo
+            .set_local_bookmark_target(
+                bookmark_name.as_ref(),
+                RefTarget::normal(commit.id().clone()),
+            )
+            .unwrap();
     }
 
     assert_matches!(resolve_symbol(mut_repo, "bar"), Ok(_));
@@ -4149,3 +4432,79 @@ fn test_revset_containing_fn() {
     assert!(!revset_has_commit(commit_c.id()).unwrap());
     assert!(revset_has_commit(commit_d.id()).unwrap());
 }
+
+#[derive(Debug)]
+struct InterruptingFilter {
+    calls: Cell<usize>,
+    interrupt_after: usize,
+    interrupt: Arc<AtomicBool>,
+}
+
+impl RevsetFilterExtension for InterruptingFilter {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn matches_commit(&self, _commit: &Commit) -> bool {
+        // Pretend that testing the commit is expensive
+        thread::sleep(Duration::from_millis(1));
+        self.calls.set(self.calls.get() + 1);
+        if self.calls.get() == self.interrupt_after {
+            self.interrupt.store(true, Ordering::Relaxed);
+        }
+        false
+    }
+}
+
+#[test]
+fn test_evaluate_interrupted() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    for _ in 0..100 {
+        write_random_commit(tx.repo_mut());
+    }
+    let repo = tx.commit("test").unwrap();
+
+    // Interrupted while testing the filter predicate
+    let context = RevsetEvaluationContext::default();
+    let filter = Rc::new(InterruptingFilter {
+        calls: Cell::new(0),
+        interrupt_after: 5,
+        interrupt: context.interrupt.clone(),
+    });
+    let mut tx = repo.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let expression = RevsetExpression::filter(RevsetFilterPredicate::Extension(filter.clone()));
+    let revset = expression
+        .evaluate_with_context(tx.repo(), &context)
+        .unwrap();
+    let result: Result<Vec<_>, _> = revset.iter().collect();
+    assert_matches!(result, Err(RevsetEvaluationError::Interrupted));
+    // The context is checked every 16 items
+    assert!(filter.calls.get() <= 16, "calls: {}", filter.calls.get());
+    drop(revset);
+    drop(tx);
+    // The interrupted transaction wasn't committed
+    let head_repo = repo.loader().load_at_head().unwrap();
+    assert_eq!(head_repo.op_id(), repo.op_id());
+
+    // Interrupted before evaluation starts
+    let context = RevsetEvaluationContext {
+        deadline: Some(Instant::now()),
+        ..Default::default()
+    };
+    let result = RevsetExpression::all().evaluate_with_context(repo.as_ref(), &context);
+    assert_matches!(result.err(), Some(RevsetEvaluationError::Interrupted));
+
+    // Not interrupted
+    let context = RevsetEvaluationContext {
+        deadline: Some(Instant::now() + Duration::from_secs(3600)),
+        ..Default::default()
+    };
+    let revset = RevsetExpression::all()
+        .evaluate_with_context(repo.as_ref(), &context)
+        .unwrap();
+    assert_eq!(revset.iter().count(), 101);
+}
@@ -12,31 +12,63 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::cell::Cell;
+use std::iter;
+use std::slice;
+use std::sync::Arc;
+
+use assert_matches::assert_matches;
+use futures::executor::block_on_stream;
 use itertools::Itertools as _;
 use jj_lib::backend::ChangeId;
+use jj_lib::backend::CommitId;
+use jj_lib::backend::CopyRecord;
+use jj_lib::backend::FileId;
+use jj_lib::backend::MergedTreeId;
+use jj_lib::backend::TreeValue;
 use jj_lib::commit::Commit;
+use jj_lib::copies::CopyRecords;
 use jj_lib::matchers::EverythingMatcher;
+use jj_lib::matchers::FileGlobsMatcher;
 use jj_lib::matchers::FilesMatcher;
+use jj_lib::matchers::PrefixMatcher;
 use jj_lib::merge::Merge;
 use jj_lib::merged_tree::MergedTree;
+use jj_lib::object_id::ObjectId as _;
 use jj_lib::op_store::RefTarget;
 use jj_lib::op_store::RemoteRef;
 use jj_lib::op_store::RemoteRefState;
 use jj_lib::ref_name::RefName;
+use jj_lib::ref_name::RefNameBuf;
 use jj_lib::ref_name::RemoteName;
 use jj_lib::ref_name::RemoteRefSymbol;
 use jj_lib::ref_name::WorkspaceName;
 use jj_lib::ref_name::WorkspaceNameBuf;
 use jj_lib::repo::Repo as _;
+use jj_lib::repo_path::RepoPath;
 use jj_lib::rewrite::find_duplicate_divergent_commits;
+use jj_lib::rewrite::move_commits_to_destination;
 use jj_lib::rewrite::rebase_commit_with_options;
+use jj_lib::rewrite::restore_paths;
 use jj_lib::rewrite::restore_tree;
+use jj_lib::rewrite::restore_tree_paths;
+use jj_lib::rewrite::restore_tree_with_copies;
+use jj_lib::rewrite::rewrite_file_storage;
+use jj_lib::rewrite::split_copy_records;
+use jj_lib::rewrite::BookmarkMovePolicy;
 use jj_lib::rewrite::CommitRewriter;
 use jj_lib::rewrite::CommitWithSelection;
 use jj_lib::rewrite::EmptyBehaviour;
+use jj_lib::rewrite::FileRewrite;
+use jj_lib::rewrite::MoveCommitsDestination;
+use jj_lib::rewrite::MoveCommitsError;
 use jj_lib::rewrite::MoveCommitsTarget;
 use jj_lib::rewrite::RebaseOptions;
+use jj_lib::rewrite::RebasedCommit;
+use jj_lib::rewrite::ReplacedFile;
 use jj_lib::rewrite::RewriteRefsOptions;
+use jj_lib::settings::ChangeIdGenerator as _;
+use jj_lib::settings::SeededChangeIdGenerator;
 use maplit::hashmap;
 use maplit::hashset;
 use test_case::test_case;
@@ -45,8 +77,13 @@ use testutils::assert_rebased_onto;
 use testutils::create_random_commit;
 use testutils::create_tree;
 use testutils::create_tree_with;
+use testutils::read_file;
 use testutils::rebase_descendants_with_options_return_map;
 use testutils::repo_path;
+use testutils::repo_path_buf;
+use testutils::test_backend::TestBackend;
+use testutils::user_settings;
+use testutils::write_file;
 use testutils::write_random_commit;
 use testutils::CommitGraphBuilder;
 use testutils::TestRepo;
@@ -96,6 +133,282 @@ fn test_restore_tree() {
     assert_eq!(restored, expected.id());
 }
 
+#[test]
+fn test_restore_tree_with_copies() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let old_path = repo_path("old");
+    let new_path = repo_path("dir/new");
+    let other_path = repo_path("other");
+    let source = create_tree(repo, &[(old_path, "source"), (other_path, "source")]);
+    let destination = create_tree(
+        repo,
+        &[(new_path, "destination"), (other_path, "destination")],
+    );
+    let mut copy_records = CopyRecords::default();
+    copy_records
+        .add_records([Ok(CopyRecord {
+            target: new_path.to_owned(),
+            target_commit: repo.store().root_commit_id().clone(),
+            source: old_path.to_owned(),
+            source_file: FileId::new(vec![]),
+            source_commit: repo.store().root_commit_id().clone(),
+        })])
+        .unwrap();
+
+    // Without copy records, the new path is removed
+    let restored = restore_tree(&source, &destination, &FilesMatcher::new([new_path])).unwrap();
+    let expected = create_tree(repo, &[(other_path, "destination")]);
+    assert_eq!(restored, expected.id());
+
+    // The rename is matched by either of its paths, and the new path gets the
+    // content of the old path
+    let expected = create_tree(repo, &[(new_path, "source"), (other_path, "destination")]);
+    for path in [new_path, old_path] {
+        let restored = restore_tree_with_copies(
+            &source,
+            &destination,
+            &FilesMatcher::new([path]),
+            &copy_records,
+        )
+        .unwrap();
+        assert_eq!(restored, expected.id());
+    }
+
+    // Unrelated paths are restored as usual
+    let restored = restore_tree_with_copies(
+        &source,
+        &destination,
+        &FilesMatcher::new([other_path]),
+        &copy_records,
+    )
+    .unwrap();
+    let expected = create_tree(repo, &[(new_path, "destination"), (other_path, "source")]);
+    assert_eq!(restored, expected.id());
+}
+
+#[test]
+fn test_restore_tree_paths() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let file1 = repo_path("file1");
+    let file2 = repo_path("dir1/file2");
+    let file3 = repo_path("dir1/sub/file3");
+    let file4 = repo_path("dir2/file4");
+    let source = create_tree(
+        repo,
+        &[
+            (file1, "source"),
+            (file2, "source"),
+            (file3, "source"),
+            (repo_path("dir2"), "source"),
+            (repo_path("only_source/file"), "source"),
+        ],
+    );
+    let destination = create_tree(
+        repo,
+        &[
+            (file1, "destination"),
+            (file2, "destination"),
+            (file3, "source"),
+            (file4, "destination"),
+            (repo_path("only_destination"), "destination"),
+        ],
+    );
+
+    // The result is the same as restoring the paths as prefixes
+    let path_sets: &[&[&str]] = &[
+        &[],
+        &[""],
+        &["file1"],
+        &["dir1"],
+        &["dir1", "dir1/sub/file3"],
+        &["dir1/sub"],
+        &["dir2"],
+        &["dir2/file4"],
+        &["only_source", "only_destination"],
+        &["missing", "dir1/missing"],
+    ];
+    for &paths in path_sets {
+        let paths = paths.iter().map(|&path| repo_path_buf(path)).collect_vec();
+        let restored = restore_tree_paths(&source, &destination, &paths).unwrap();
+        let expected = restore_tree(&source, &destination, &PrefixMatcher::new(&paths)).unwrap();
+        assert_eq!(restored, expected, "{paths:?}");
+    }
+
+    // The commit is rewritten with the restored tree
+    let mut tx = repo.start_transaction();
+    let commit = tx
+        .repo_mut()
+        .new_commit(
+            vec![repo.store().root_commit_id().clone()],
+            destination.id(),
+        )
+        .write()
+        .unwrap();
+    let new_commit = restore_paths(tx.repo_mut(), &source, &commit, &[file1.to_owned()]).unwrap();
+    assert_eq!(new_commit.change_id(), commit.change_id());
+    let expected = restore_tree(&source, &destination, &FilesMatcher::new([file1])).unwrap();
+    assert_eq!(*new_commit.tree_id(), expected);
+}
+
+#[test]
+fn test_restore_tree_paths_tree_reads() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // A directory in which many subdirectories were changed
+    let paths = (0..50)
+        .map(|i| repo_path_buf(format!("dir/sub{i}/file")))
+        .collect_vec();
+    let other_path = repo_path("other");
+    let source_entries = paths
+        .iter()
+        .map(|path| (path.as_ref(), "source"))
+        .chain([(other_path, "source")])
+        .collect_vec();
+    let destination_entries = paths
+        .iter()
+        .map(|path| (path.as_ref(), "destination"))
+        .chain([(other_path, "destination")])
+        .collect_vec();
+    let source_id = create_tree(repo, &source_entries).id();
+    let destination_id = create_tree(repo, &destination_entries).id();
+    let restored_paths = [repo_path_buf("dir")];
+
+    // Load the repo again for each measurement so the store doesn't have any
+    // cached trees
+    let count_tree_reads = |restore: &dyn Fn(&MergedTree, &MergedTree) -> MergedTreeId| {
+        let repo = test_repo
+            .env
+            .load_repo_at_head(&user_settings(), test_repo.repo_path());
+        let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
+        let source = repo.store().get_root_tree(&source_id).unwrap();
+        let destination = repo.store().get_root_tree(&destination_id).unwrap();
+        let old_read_count = test_backend.tree_read_count();
+        let restored = restore(&source, &destination);
+        (restored, test_backend.tree_read_count() - old_read_count)
+    };
+    let (expected, diff_reads) = count_tree_reads(&|source, destination| {
+        restore_tree(source, destination, &PrefixMatcher::new(&restored_paths)).unwrap()
+    });
+    let (restored, path_reads) = count_tree_reads(&|source, destination| {
+        restore_tree_paths(source, destination, &restored_paths).unwrap()
+    });
+    assert_eq!(restored, expected);
+    // Diffing reads both sides of every changed subdirectory, whereas the
+    // restored directory is replaced without reading it
+    assert!(diff_reads >= 2 * paths.len(), "{diff_reads}");
+    assert!(path_reads <= 2, "{path_reads}");
+}
+
+#[test]
+fn test_split_copy_records() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let old1 = repo_path("old1");
+    let old2 = repo_path("old2");
+    let old3 = repo_path("old3");
+    let new1 = repo_path("new1");
+    let new2 = repo_path("new2");
+    let new3 = repo_path("new3");
+    let parent_tree = create_tree(repo, &[(old1, "1"), (old2, "2"), (old3, "3")]);
+    let tree = create_tree(repo, &[(new1, "1"), (new2, "2"), (new3, "3\nmore\n")]);
+    // The rename of old1 and a part of the change to new3 are selected
+    let selected_tree = create_tree(repo, &[(new1, "1"), (old2, "2"), (new3, "3")]);
+
+    let mut tx = repo.start_transaction();
+    let root_id = store.root_commit_id().clone();
+    let parent = tx
+        .repo_mut()
+        .new_commit(vec![root_id.clone()], parent_tree.id())
+        .write()
+        .unwrap();
+    let commit = tx
+        .repo_mut()
+        .new_commit(vec![parent.id().clone()], tree.id())
+        .write()
+        .unwrap();
+    let rename_record = |source: &RepoPath, target: &RepoPath| CopyRecord {
+        target: target.to_owned(),
+        target_commit: commit.id().clone(),
+        source: source.to_owned(),
+        source_file: FileId::new(vec![]),
+        source_commit: parent.id().clone(),
+    };
+    store
+        .write_copy_records(&[
+            rename_record(old1, new1),
+            rename_record(old2, new2),
+            rename_record(old3, new3),
+        ])
+        .unwrap();
+
+    let get_renames = |root: &Commit, head: &Commit| {
+        let stream = store.get_copy_records(None, root.id(), head.id()).unwrap();
+        block_on_stream(stream)
+            .map(|record| {
+                let record = record.unwrap();
+                assert_eq!(&record.target_commit, head.id());
+                assert_eq!(&record.source_commit, root.id());
+                (record.source, record.target)
+            })
+            .sorted()
+            .collect_vec()
+    };
+
+    let first = tx
+        .repo_mut()
+        .new_commit(vec![parent.id().clone()], selected_tree.id())
+        .write()
+        .unwrap();
+    let second = tx
+        .repo_mut()
+        .new_commit(vec![first.id().clone()], tree.id())
+        .write()
+        .unwrap();
+    split_copy_records(tx.repo(), &commit, &first, &second).unwrap();
+    assert_eq!(
+        get_renames(&parent, &first),
+        vec![
+            (old1.to_owned(), new1.to_owned()),
+            (old3.to_owned(), new3.to_owned()),
+        ]
+    );
+    // The rename of old3 was already applied by the first commit
+    assert_eq!(
+        get_renames(&first, &second),
+        vec![(old2.to_owned(), new2.to_owned())]
+    );
+
+    // The sources don't exist in the new parent of a moved first commit
+    let root = store.root_commit();
+    let moved_first = tx
+        .repo_mut()
+        .new_commit(vec![root_id], selected_tree.id())
+        .write()
+        .unwrap();
+    let moved_second = tx
+        .repo_mut()
+        .new_commit(vec![parent.id().clone()], tree.id())
+        .write()
+        .unwrap();
+    split_copy_records(tx.repo(), &commit, &moved_first, &moved_second).unwrap();
+    assert_eq!(get_renames(&root, &moved_first), vec![]);
+    assert_eq!(
+        get_renames(&parent, &moved_second),
+        vec![
+            (old1.to_owned(), new1.to_owned()),
+            (old2.to_owned(), new2.to_owned()),
+            (old3.to_owned(), new3.to_owned()),
+        ]
+    );
+}
+
 #[test]
 fn test_rebase_descendants_sideways() {
     let test_repo = TestRepo::init();
@@ -945,7 +1258,8 @@ fn test_rebase_descendants_basic_bookmark_update() {
     let commit_a = graph_builder.initial_commit();
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
     tx.repo_mut()
-        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()));
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -980,7 +1294,8 @@ fn test_rebase_descendants_bookmark_move_two_steps() {
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
     let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
     tx.repo_mut()
-        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_c.id().clone()));
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_c.id().clone()))
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1031,11 +1346,13 @@ fn test_rebase_descendants_basic_bookmark_update_with_non_local_bookmark() {
         state: RemoteRefState::Tracked,
     };
     tx.repo_mut()
-        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()));
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
     tx.repo_mut()
         .set_remote_bookmark(remote_symbol("main", "origin"), commit_b_remote_ref.clone());
     tx.repo_mut()
-        .set_tag_target("v1".as_ref(), RefTarget::normal(commit_b.id().clone()));
+        .set_tag_target("v1".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1085,11 +1402,13 @@ fn test_rebase_descendants_update_bookmark_after_abandon(delete_abandoned_bookma
         state: RemoteRefState::Tracked,
     };
     tx.repo_mut()
-        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()));
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
     tx.repo_mut()
         .set_remote_bookmark(remote_symbol("main", "origin"), commit_b_remote_ref.clone());
     tx.repo_mut()
-        .set_local_bookmark_target("other".as_ref(), RefTarget::normal(commit_c.id().clone()));
+        .set_local_bookmark_target("other".as_ref(), RefTarget::normal(commit_c.id().clone()))
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1097,6 +1416,7 @@ fn test_rebase_descendants_update_bookmark_after_abandon(delete_abandoned_bookma
     let options = RebaseOptions {
         rewrite_refs: RewriteRefsOptions {
             delete_abandoned_bookmarks,
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -1148,9 +1468,11 @@ fn test_rebase_descendants_update_bookmarks_after_divergent_rewrite() {
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
     let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
     tx.repo_mut()
-        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()));
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
     tx.repo_mut()
-        .set_local_bookmark_target("other".as_ref(), RefTarget::normal(commit_c.id().clone()));
+        .set_local_bookmark_target("other".as_ref(), RefTarget::normal(commit_c.id().clone()))
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1237,13 +1559,15 @@ fn test_rebase_descendants_rewrite_updates_bookmark_conflict() {
     let commit_a = graph_builder.initial_commit();
     let commit_b = graph_builder.initial_commit();
     let commit_c = graph_builder.initial_commit();
-    tx.repo_mut().set_local_bookmark_target(
-        "main".as_ref(),
-        RefTarget::from_legacy_form(
-            [commit_a.id().clone()],
-            [commit_b.id().clone(), commit_c.id().clone()],
-        ),
-    );
+    tx.repo_mut()
+        .set_local_bookmark_target(
+            "main".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit_a.id().clone()],
+                [commit_b.id().clone(), commit_c.id().clone()],
+            ),
+        )
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1300,6 +1624,79 @@ fn test_rebase_descendants_rewrite_updates_bookmark_conflict() {
     );
 }
 
+#[test_case(BookmarkMovePolicy::Conflict; "conflict")]
+#[test_case(BookmarkMovePolicy::FollowFirst; "follow first")]
+#[test_case(BookmarkMovePolicy::LeaveBehind; "leave behind")]
+fn test_rebase_descendants_divergent_rewrite_bookmark_move_policy(
+    bookmark_move_policy: BookmarkMovePolicy,
+) {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Bookmark "main" points to commit B. B gets rewritten as {B2, B3}. Bookmark
+    // "other" points to commit A, which isn't rewritten.
+    let mut tx = repo.start_transaction();
+    let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    tx.repo_mut()
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
+    tx.repo_mut()
+        .set_local_bookmark_target("other".as_ref(), RefTarget::normal(commit_a.id().clone()))
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    let mut tx = repo.start_transaction();
+    let commit_b2 = tx.repo_mut().rewrite_commit(&commit_b).write().unwrap();
+    // Different description so they're not the same commit
+    let commit_b3 = tx
+        .repo_mut()
+        .rewrite_commit(&commit_b)
+        .set_description("different")
+        .write()
+        .unwrap();
+    tx.repo_mut().set_divergent_rewrite(
+        commit_b.id().clone(),
+        vec![commit_b2.id().clone(), commit_b3.id().clone()],
+    );
+    let options = RewriteRefsOptions {
+        bookmark_move_policy,
+        ..Default::default()
+    };
+    let left_behind_bookmarks = tx.repo_mut().update_rewritten_references(&options).unwrap();
+
+    let main_target = tx.repo().get_local_bookmark("main".as_ref());
+    match bookmark_move_policy {
+        BookmarkMovePolicy::Conflict => {
+            assert!(left_behind_bookmarks.is_empty());
+            assert_eq!(
+                main_target,
+                RefTarget::from_legacy_form(
+                    [commit_b.id().clone()],
+                    [commit_b2.id().clone(), commit_b3.id().clone()],
+                )
+            );
+        }
+        BookmarkMovePolicy::FollowFirst => {
+            assert!(left_behind_bookmarks.is_empty());
+            assert_eq!(main_target, RefTarget::normal(commit_b2.id().clone()));
+        }
+        BookmarkMovePolicy::LeaveBehind => {
+            assert_eq!(left_behind_bookmarks, vec![RefNameBuf::from("main")]);
+            assert_eq!(main_target, RefTarget::normal(commit_b.id().clone()));
+        }
+    }
+    assert_eq!(
+        tx.repo().get_local_bookmark("other".as_ref()),
+        RefTarget::normal(commit_a.id().clone())
+    );
+    assert_eq!(
+        *tx.repo().view().heads(),
+        hashset! {commit_b2.id().clone(), commit_b3.id().clone()}
+    );
+}
+
 #[test]
 fn test_rebase_descendants_rewrite_resolves_bookmark_conflict() {
     let test_repo = TestRepo::init();
@@ -1317,13 +1714,15 @@ fn test_rebase_descendants_rewrite_resolves_bookmark_conflict() {
     let commit_a = graph_builder.initial_commit();
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
     let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
-    tx.repo_mut().set_local_bookmark_target(
-        "main".as_ref(),
-        RefTarget::from_legacy_form(
-            [commit_a.id().clone()],
-            [commit_b.id().clone(), commit_c.id().clone()],
-        ),
-    );
+    tx.repo_mut()
+        .set_local_bookmark_target(
+            "main".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit_a.id().clone()],
+                [commit_b.id().clone(), commit_c.id().clone()],
+            ),
+        )
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1365,10 +1764,12 @@ fn test_rebase_descendants_bookmark_delete_modify_abandon(delete_abandoned_bookm
     let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
     let commit_a = graph_builder.initial_commit();
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
-    tx.repo_mut().set_local_bookmark_target(
-        "main".as_ref(),
-        RefTarget::from_legacy_form([commit_a.id().clone()], [commit_b.id().clone()]),
-    );
+    tx.repo_mut()
+        .set_local_bookmark_target(
+            "main".as_ref(),
+            RefTarget::from_legacy_form([commit_a.id().clone()], [commit_b.id().clone()]),
+        )
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1376,6 +1777,7 @@ fn test_rebase_descendants_bookmark_delete_modify_abandon(delete_abandoned_bookm
     let options = RebaseOptions {
         rewrite_refs: RewriteRefsOptions {
             delete_abandoned_bookmarks,
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -1405,14 +1807,16 @@ fn test_rebase_descendants_bookmark_move_forward_abandon(delete_abandoned_bookma
     let commit_a = graph_builder.initial_commit();
     let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
     let commit_c = graph_builder.commit_with_parents(&[&commit_a]);
-    tx.repo_mut().set_local_bookmark_target(
-        "main".as_ref(),
-        RefTarget::from_merge(Merge::from_vec(vec![
-            Some(commit_b.id().clone()),
-            Some(commit_a.id().clone()),
-            Some(commit_c.id().clone()),
-        ])),
-    );
+    tx.repo_mut()
+        .set_local_bookmark_target(
+            "main".as_ref(),
+            RefTarget::from_merge(Merge::from_vec(vec![
+                Some(commit_b.id().clone()),
+                Some(commit_a.id().clone()),
+                Some(commit_c.id().clone()),
+            ])),
+        )
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1420,6 +1824,7 @@ fn test_rebase_descendants_bookmark_move_forward_abandon(delete_abandoned_bookma
     let options = RebaseOptions {
         rewrite_refs: RewriteRefsOptions {
             delete_abandoned_bookmarks,
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -1457,14 +1862,16 @@ fn test_rebase_descendants_bookmark_move_sideways_abandon(delete_abandoned_bookm
     let commit_a = graph_builder.initial_commit();
     let commit_b = graph_builder.initial_commit();
     let commit_c = graph_builder.initial_commit();
-    tx.repo_mut().set_local_bookmark_target(
-        "main".as_ref(),
-        RefTarget::from_merge(Merge::from_vec(vec![
-            Some(commit_b.id().clone()),
-            Some(commit_a.id().clone()),
-            Some(commit_c.id().clone()),
-        ])),
-    );
+    tx.repo_mut()
+        .set_local_bookmark_target(
+            "main".as_ref(),
+            RefTarget::from_merge(Merge::from_vec(vec![
+                Some(commit_b.id().clone()),
+                Some(commit_a.id().clone()),
+                Some(commit_c.id().clone()),
+            ])),
+        )
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx = repo.start_transaction();
@@ -1472,6 +1879,7 @@ fn test_rebase_descendants_bookmark_move_sideways_abandon(delete_abandoned_bookm
     let options = RebaseOptions {
         rewrite_refs: RewriteRefsOptions {
             delete_abandoned_bookmarks,
+            ..Default::default()
         },
         ..Default::default()
     };
@@ -1494,6 +1902,83 @@ fn test_rebase_descendants_bookmark_move_sideways_abandon(delete_abandoned_bookm
     );
 }
 
+#[test]
+fn test_rebase_descendants_bookmark_update_large_mapping() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let root_id = repo.store().root_commit_id().clone();
+    let empty_tree_id = repo.store().empty_merged_tree_id();
+
+    // Commits are rewritten in groups of 10, where each commit but the last
+    // is rewritten to the next commit, or abandoned in favor of the next
+    // commit and the last commit of this or the previous group. Every 7th
+    // commit has a bookmark.
+    const NUM_COMMITS: usize = 50_000;
+    let mut tx = repo.start_transaction();
+    let commit_ids = (0..NUM_COMMITS)
+        .map(|i| {
+            let commit = tx
+                .repo_mut()
+                .new_commit(vec![root_id.clone()], empty_tree_id.clone())
+                .set_description(format!("commit {i}"))
+                .write()
+                .unwrap();
+            commit.id().clone()
+        })
+        .collect_vec();
+    let bookmark_names = (0..NUM_COMMITS)
+        .step_by(7)
+        .map(|i| RefNameBuf::from(format!("b{i}")))
+        .collect_vec();
+    for (name, i) in bookmark_names.iter().zip((0..NUM_COMMITS).step_by(7)) {
+        tx.repo_mut()
+            .set_local_bookmark_target(name, RefTarget::normal(commit_ids[i].clone()))
+            .unwrap();
+    }
+    let repo = tx.commit("test").unwrap();
+
+    let mut tx = repo.start_transaction();
+    for (i, old_id) in commit_ids.iter().enumerate() {
+        let next_id = commit_ids.get(i + 1).cloned();
+        let last_id = commit_ids[i - i % 10 + 9].clone();
+        let prev_last_id = (i - i % 10)
+            .checked_sub(1)
+            .map_or(root_id.clone(), |j| commit_ids[j].clone());
+        let mut_repo = tx.repo_mut();
+        match i % 10 {
+            0 => mut_repo
+                .record_abandoned_commit_with_parents(old_id.clone(), [next_id.unwrap(), last_id]),
+            3 => mut_repo.record_abandoned_commit_with_parents(
+                old_id.clone(),
+                [prev_last_id, next_id.unwrap()],
+            ),
+            6 => mut_repo.record_abandoned_commit_with_parents(
+                old_id.clone(),
+                [next_id.unwrap(), prev_last_id],
+            ),
+            9 => {}
+            _ => mut_repo.set_rewritten_commit(old_id.clone(), next_id.unwrap()),
+        }
+    }
+    // The expected bookmark targets are calculated by following the rewrites
+    // of each commit individually.
+    let expected_targets = (0..NUM_COMMITS)
+        .step_by(7)
+        .map(|i| {
+            let old_id = &commit_ids[i];
+            let new_ids = tx.repo().new_parents(slice::from_ref(old_id));
+            let old_ids = iter::repeat_n(old_id.clone(), new_ids.len() - 1);
+            RefTarget::from_legacy_form(old_ids, new_ids)
+        })
+        .collect_vec();
+    tx.repo_mut()
+        .update_rewritten_references(&RewriteRefsOptions::default())
+        .unwrap();
+    for (name, expected_target) in bookmark_names.iter().zip(expected_targets) {
+        assert_eq!(tx.repo().get_local_bookmark(name), expected_target);
+    }
+}
+
 #[test]
 fn test_rebase_descendants_update_checkout() {
     let test_repo = TestRepo::init();
@@ -1592,6 +2077,70 @@ fn test_rebase_descendants_update_checkout_abandoned() {
     assert_eq!(repo.view().get_wc_commit_id(&ws3_name), Some(commit_a.id()));
 }
 
+#[test]
+fn test_rebase_descendants_update_checkouts_in_workspace_name_order() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Checked-out commits B and C were abandoned. New working-copy commits
+    // should be created for the workspaces in name order, regardless of the
+    // order in which they were added.
+    //
+    // B C
+    // |/
+    // A
+    let mut tx = repo.start_transaction();
+    let commit_a = write_random_commit(tx.repo_mut());
+    let commit_b = create_random_commit(tx.repo_mut())
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let commit_c = create_random_commit(tx.repo_mut())
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let ws_z_name = WorkspaceNameBuf::from("zz");
+    let ws_a_name = WorkspaceNameBuf::from("aa");
+    tx.repo_mut()
+        .set_wc_commit(ws_z_name.clone(), commit_b.id().clone())
+        .unwrap();
+    tx.repo_mut()
+        .set_wc_commit(ws_a_name.clone(), commit_c.id().clone())
+        .unwrap();
+    tx.commit("test").unwrap();
+
+    // Reload the repo so that change ids of new commits are predictable
+    let settings = testutils::user_settings()
+        .with_change_id_generator(Arc::new(SeededChangeIdGenerator::new(1)));
+    let repo = test_repo
+        .env
+        .load_repo_at_head(&settings, test_repo.repo_path());
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().record_abandoned_commit(&commit_b);
+    tx.repo_mut().record_abandoned_commit(&commit_c);
+    tx.repo_mut().rebase_descendants().unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    let generator = SeededChangeIdGenerator::new(1);
+    let change_id_length = repo.store().change_id_length();
+    let get_wc_commit = |name: &WorkspaceName| {
+        let id = repo.view().get_wc_commit_id(name).unwrap();
+        repo.store().get_commit(id).unwrap()
+    };
+    let wc_commit_a = get_wc_commit(&ws_a_name);
+    let wc_commit_z = get_wc_commit(&ws_z_name);
+    assert_eq!(wc_commit_a.parent_ids(), [commit_a.id().clone()]);
+    assert_eq!(wc_commit_z.parent_ids(), [commit_a.id().clone()]);
+    assert_eq!(
+        wc_commit_a.change_id(),
+        &generator.new_change_id(change_id_length)
+    );
+    assert_eq!(
+        wc_commit_z.change_id(),
+        &generator.new_change_id(change_id_length)
+    );
+}
+
 #[test]
 fn test_rebase_descendants_update_checkout_abandoned_merge() {
     let test_repo = TestRepo::init();
@@ -1708,8 +2257,10 @@ fn test_empty_commit_option(empty_behavior: EmptyBehaviour) {
             empty: empty_behavior,
             rewrite_refs: RewriteRefsOptions {
                 delete_abandoned_bookmarks: false,
+                ..Default::default()
             },
             simplify_ancestor_merge: true,
+            ..Default::default()
         },
     );
 
@@ -1843,8 +2394,10 @@ fn test_rebase_abandoning_empty() {
         empty: EmptyBehaviour::AbandonAllEmpty,
         rewrite_refs: RewriteRefsOptions {
             delete_abandoned_bookmarks: false,
+            ..Default::default()
         },
         simplify_ancestor_merge: true,
+        ..Default::default()
     };
     let rewriter = CommitRewriter::new(tx.repo_mut(), commit_b, vec![commit_b2.id().clone()]);
     rebase_commit_with_options(rewriter, &rebase_options).unwrap();
@@ -1871,6 +2424,63 @@ fn test_rebase_abandoning_empty() {
     );
 }
 
+#[test]
+fn test_rebase_keep_empty_commits() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Rebase C and D from B onto B2, where B2 already contains the changes from
+    // C and D, so both would become empty. C is protected and is kept, while D
+    // is abandoned.
+    //
+    // C D                    C' (empty)
+    // |/                     |
+    // B B2          =>       B2
+    // |/                     |
+    // A                      A
+
+    let mut tx = repo.start_transaction();
+    let commit_a = write_random_commit(tx.repo_mut());
+    let commit_b = create_random_commit(tx.repo_mut())
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let commit_c = create_random_commit(tx.repo_mut())
+        .set_parents(vec![commit_b.id().clone()])
+        .write()
+        .unwrap();
+    let commit_d = create_random_commit(tx.repo_mut())
+        .set_parents(vec![commit_b.id().clone()])
+        .set_tree_id(commit_c.tree_id().clone())
+        .write()
+        .unwrap();
+    let commit_b2 = create_random_commit(tx.repo_mut())
+        .set_parents(vec![commit_a.id().clone()])
+        .set_tree_id(commit_c.tree_id().clone())
+        .write()
+        .unwrap();
+
+    tx.repo_mut()
+        .set_rewritten_commit(commit_b.id().clone(), commit_b2.id().clone());
+    let rebase_map = rebase_descendants_with_options_return_map(
+        tx.repo_mut(),
+        &RebaseOptions {
+            empty: EmptyBehaviour::AbandonNewlyEmpty,
+            keep_empty_commits: hashset! {commit_c.id().clone()},
+            ..Default::default()
+        },
+    );
+    assert_eq!(rebase_map.len(), 2);
+    let new_commit_c = assert_rebased_onto(tx.repo(), &rebase_map, &commit_c, &[commit_b2.id()]);
+    assert_eq!(new_commit_c.tree_id(), commit_b2.tree_id());
+    assert_abandoned_with_parent(tx.repo(), &rebase_map, &commit_d, commit_b2.id());
+
+    assert_eq!(
+        *tx.repo().view().heads(),
+        hashset! {new_commit_c.id().clone()}
+    );
+}
+
 #[test]
 fn test_commit_with_selection() {
     let test_repo = TestRepo::init();
@@ -2002,3 +2612,194 @@ fn test_find_duplicate_divergent_commits() {
     // Commit c2 is a duplicate
     assert_eq!(duplicate_commits, &[commit_c2.clone()]);
 }
+
+#[test]
+fn test_move_commits_before_first_mutable_ancestor() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Commits A and B are immutable. Moving D before the first mutable ancestor
+    // of D should insert it between B and C.
+    //
+    // D
+    // C
+    // B (immutable)
+    // A (immutable)
+    let mut tx = repo.start_transaction();
+    let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+    let commit_d = graph_builder.commit_with_parents(&[&commit_c]);
+
+    let immutable_ids = hashset! {commit_a.id().clone(), commit_b.id().clone()};
+    let is_immutable = |id: &CommitId| Ok(immutable_ids.contains(id));
+
+    let stats = move_commits_to_destination(
+        tx.repo_mut(),
+        MoveCommitsTarget::Commits(vec![commit_d.id().clone()]),
+        &MoveCommitsDestination::BeforeFirstMutableAncestorOf(commit_d.id().clone()),
+        &is_immutable,
+        &RebaseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 1);
+    assert_eq!(stats.num_rebased_descendants, 1);
+    let new_commit_d = match &stats.rebased_commits[commit_d.id()] {
+        RebasedCommit::Rewritten(commit) => commit.clone(),
+        RebasedCommit::Abandoned { .. } => panic!("commit D shouldn't be abandoned"),
+    };
+    let new_commit_c = match &stats.rebased_commits[commit_c.id()] {
+        RebasedCommit::Rewritten(commit) => commit.clone(),
+        RebasedCommit::Abandoned { .. } => panic!("commit C shouldn't be abandoned"),
+    };
+    assert_eq!(new_commit_d.parent_ids(), &[commit_b.id().clone()]);
+    assert_eq!(new_commit_c.parent_ids(), &[new_commit_d.id().clone()]);
+    assert_eq!(
+        *tx.repo().view().heads(),
+        hashset! {new_commit_c.id().clone()}
+    );
+
+    // Moving the first mutable commit before itself is a no-op
+    let stats = move_commits_to_destination(
+        tx.repo_mut(),
+        MoveCommitsTarget::Commits(vec![new_commit_d.id().clone()]),
+        &MoveCommitsDestination::BeforeFirstMutableAncestorOf(new_commit_c.id().clone()),
+        &is_immutable,
+        &RebaseOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(stats.num_rebased_targets, 0);
+    assert_eq!(stats.num_rebased_descendants, 0);
+}
+
+#[test]
+fn test_move_commits_before_first_mutable_ancestor_all_immutable() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
+    let commit_a = graph_builder.initial_commit();
+    let commit_b = graph_builder.commit_with_parents(&[&commit_a]);
+    let commit_c = graph_builder.commit_with_parents(&[&commit_b]);
+
+    let is_immutable = |id: &CommitId| Ok(id != commit_c.id());
+    let result = move_commits_to_destination(
+        tx.repo_mut(),
+        MoveCommitsTarget::Commits(vec![commit_c.id().clone()]),
+        &MoveCommitsDestination::BeforeFirstMutableAncestorOf(commit_b.id().clone()),
+        &is_immutable,
+        &RebaseOptions::default(),
+    );
+    assert_matches!(
+        result.err(),
+        Some(MoveCommitsError::NoMutableAncestors(id)) if id == *commit_b.id()
+    );
+    // Nothing was rewritten
+    assert_eq!(*tx.repo().view().heads(), hashset! {commit_c.id().clone()});
+}
+
+#[test]
+fn test_rewrite_file_storage() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let bin_path1 = repo_path("a.bin");
+    let bin_path2 = repo_path("assets/b.bin");
+    let src_path = repo_path("src/lib.rs");
+    let docs_path = repo_path("docs/readme.txt");
+    let mut tx = repo.start_transaction();
+    let mut parent_id = store.root_commit_id().clone();
+    let mut commits = vec![];
+    for [bin1, bin2, src, docs] in [
+        ["a1", "b1", "lib1", "readme1"],
+        ["a1", "b1", "lib2", "readme1"],
+        ["a1", "b2", "lib2", "readme1"],
+        ["a1", "b2", "lib2", "readme2"],
+        ["a2", "b2", "lib2", "readme2"],
+    ] {
+        let tree = create_tree(
+            repo,
+            &[
+                (bin_path1, bin1),
+                (bin_path2, bin2),
+                (src_path, src),
+                (docs_path, docs),
+            ],
+        );
+        let commit = tx
+            .repo_mut()
+            .new_commit(vec![parent_id], tree.id())
+            .write()
+            .unwrap();
+        parent_id = commit.id().clone();
+        commits.push(commit);
+    }
+
+    let matcher = FileGlobsMatcher::new([
+        (RepoPath::root(), glob::Pattern::new("*.bin").unwrap()),
+        (repo_path("assets"), glob::Pattern::new("*.bin").unwrap()),
+    ]);
+    let num_transformed = Cell::new(0);
+    let stats = rewrite_file_storage(
+        tx.repo_mut(),
+        slice::from_ref(commits[0].id()),
+        &matcher,
+        |path, id| {
+            num_transformed.set(num_transformed.get() + 1);
+            let pointer = format!("pointer {}\n", id.hex());
+            Ok(FileRewrite::Replace(write_file(store, path, &pointer)))
+        },
+    )
+    .unwrap();
+    // Each distinct file is only transformed once
+    assert_eq!(num_transformed.get(), 4);
+    assert_eq!(stats.rewritten_commits.len(), commits.len());
+
+    let file_id =
+        |tree: &MergedTree, path: &RepoPath| match tree.path_value(path).unwrap().into_resolved() {
+            Ok(Some(TreeValue::File { id, .. })) => id,
+            value => panic!("unexpected value: {value:?}"),
+        };
+    let mut expected_replaced_files = vec![];
+    for old_commit in &commits {
+        let new_commit = &stats.rewritten_commits[old_commit.id()];
+        assert_eq!(new_commit.change_id(), old_commit.change_id());
+        let old_tree = old_commit.tree().unwrap();
+        let new_tree = new_commit.tree().unwrap();
+
+        // Subtrees without matched files keep their ids
+        for dir in ["src", "docs"] {
+            assert_eq!(
+                new_tree.path_value(repo_path(dir)).unwrap(),
+                old_tree.path_value(repo_path(dir)).unwrap()
+            );
+        }
+        assert_ne!(
+            new_tree.path_value(repo_path("assets")).unwrap(),
+            old_tree.path_value(repo_path("assets")).unwrap()
+        );
+
+        for path in [bin_path1, bin_path2] {
+            let old_id = file_id(&old_tree, path);
+            let new_id = file_id(&new_tree, path);
+            assert_eq!(
+                read_file(store, path, &new_id),
+                format!("pointer {}\n", old_id.hex()).as_bytes()
+            );
+            expected_replaced_files.push(ReplacedFile {
+                commit_id: new_commit.id().clone(),
+                path: path.to_owned(),
+                old_id,
+                new_id,
+            });
+        }
+    }
+    assert_eq!(stats.replaced_files, expected_replaced_files);
+
+    // Commits are rewritten in place of the originals
+    let new_head = &stats.rewritten_commits[commits[4].id()];
+    assert_eq!(*tx.repo().view().heads(), hashset! {new_head.id().clone()});
+}
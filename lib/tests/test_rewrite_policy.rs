@@ -0,0 +1,182 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use assert_matches::assert_matches;
+use jj_lib::backend::CommitId;
+use jj_lib::repo::Repo;
+use jj_lib::rewrite_policy::RewriteDeniedError;
+use jj_lib::rewrite_policy::RewriteOverride;
+use jj_lib::rewrite_policy::RewritePermission;
+use jj_lib::rewrite_policy::RewritePolicy;
+use jj_lib::transaction::TransactionCommitError;
+use testutils::write_random_commit;
+use testutils::TestRepo;
+
+/// Policy returning a fixed permission per commit.
+#[derive(Debug, Default)]
+struct FixedPolicy {
+    permissions: HashMap<CommitId, RewritePermission>,
+}
+
+impl RewritePolicy for FixedPolicy {
+    fn check(
+        &self,
+        _repo: &dyn Repo,
+        commit_id: &CommitId,
+    ) -> Result<RewritePermission, Box<dyn std::error::Error + Send + Sync>> {
+        Ok(self
+            .permissions
+            .get(commit_id)
+            .cloned()
+            .unwrap_or(RewritePermission::Allowed))
+    }
+}
+
+fn requires_override() -> RewritePermission {
+    RewritePermission::RequiresOverride("protected".to_owned())
+}
+
+fn forbidden() -> RewritePermission {
+    RewritePermission::Forbidden("released".to_owned())
+}
+
+#[test]
+fn test_check_rewrite_permission_levels() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit_a = write_random_commit(tx.repo_mut());
+    let commit_b = write_random_commit(tx.repo_mut());
+    let commit_c = write_random_commit(tx.repo_mut());
+    let repo = tx.commit("test").unwrap();
+
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    let root_id = mut_repo.store().root_commit_id().clone();
+    assert_eq!(
+        mut_repo.check_rewrite(commit_a.id()).unwrap(),
+        RewritePermission::Allowed
+    );
+    assert_matches!(
+        mut_repo.check_rewrite(&root_id).unwrap(),
+        RewritePermission::Forbidden(_)
+    );
+
+    mut_repo.add_rewrite_policy(Arc::new(FixedPolicy {
+        permissions: HashMap::from([
+            (commit_b.id().clone(), requires_override()),
+            (commit_c.id().clone(), requires_override()),
+        ]),
+    }));
+    mut_repo.add_rewrite_policy(Arc::new(FixedPolicy {
+        permissions: HashMap::from([(commit_c.id().clone(), forbidden())]),
+    }));
+    assert_eq!(
+        mut_repo.check_rewrite(commit_a.id()).unwrap(),
+        RewritePermission::Allowed
+    );
+    assert_eq!(
+        mut_repo.check_rewrite(commit_b.id()).unwrap(),
+        requires_override()
+    );
+    // The most restrictive permission wins
+    assert_eq!(mut_repo.check_rewrite(commit_c.id()).unwrap(), forbidden());
+
+    assert_matches!(mut_repo.ensure_rewritable(commit_a.id()), Ok(()));
+    assert_matches!(
+        mut_repo.ensure_rewritable(commit_b.id()),
+        Err(RewriteDeniedError::RequiresOverride { .. })
+    );
+    assert_matches!(
+        mut_repo.ensure_rewritable(commit_c.id()),
+        Err(RewriteDeniedError::Forbidden { .. })
+    );
+
+    // The override token only upgrades commits requiring an override
+    mut_repo.set_rewrite_override(Some(RewriteOverride::new()));
+    assert_matches!(mut_repo.ensure_rewritable(commit_a.id()), Ok(()));
+    assert_matches!(mut_repo.ensure_rewritable(commit_b.id()), Ok(()));
+    assert_matches!(
+        mut_repo.ensure_rewritable(commit_c.id()),
+        Err(RewriteDeniedError::Forbidden { .. })
+    );
+    assert_matches!(
+        mut_repo.ensure_rewritable(&root_id),
+        Err(RewriteDeniedError::Forbidden { .. })
+    );
+}
+
+#[test]
+fn test_rewrite_policy_denies_transaction() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit_a = write_random_commit(tx.repo_mut());
+    let commit_b = write_random_commit(tx.repo_mut());
+    let repo = tx.commit("test").unwrap();
+    let policy = Arc::new(FixedPolicy {
+        permissions: HashMap::from([
+            (commit_a.id().clone(), requires_override()),
+            (commit_b.id().clone(), forbidden()),
+        ]),
+    });
+
+    // Rewriting a commit which requires an override
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().add_rewrite_policy(policy.clone());
+    tx.repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.repo_mut().rebase_descendants().unwrap();
+    assert_matches!(
+        tx.commit("test"),
+        Err(TransactionCommitError::RewriteDenied(
+            RewriteDeniedError::RequiresOverride { commit_id, .. }
+        )) if commit_id == *commit_a.id()
+    );
+
+    // ... succeeds with an override
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().add_rewrite_policy(policy.clone());
+    tx.repo_mut()
+        .set_rewrite_override(Some(RewriteOverride::new()));
+    tx.repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.repo_mut().rebase_descendants().unwrap();
+    assert_matches!(tx.commit("test"), Ok(_));
+
+    // Abandoning a forbidden commit fails even with an override
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().add_rewrite_policy(policy.clone());
+    tx.repo_mut()
+        .set_rewrite_override(Some(RewriteOverride::new()));
+    tx.repo_mut().record_abandoned_commit(&commit_b);
+    tx.repo_mut().rebase_descendants().unwrap();
+    assert_matches!(
+        tx.commit("test"),
+        Err(TransactionCommitError::RewriteDenied(
+            RewriteDeniedError::Forbidden { commit_id, .. }
+        )) if commit_id == *commit_b.id()
+    );
+}
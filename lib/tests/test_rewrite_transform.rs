@@ -14,11 +14,14 @@
 
 use std::collections::HashMap;
 
+use itertools::Itertools as _;
 use jj_lib::commit::Commit;
 use jj_lib::repo::Repo as _;
 use jj_lib::rewrite::RewriteRefsOptions;
 use maplit::hashmap;
 use maplit::hashset;
+use pollster::FutureExt as _;
+use testutils::test_backend::TestBackend;
 use testutils::CommitGraphBuilder;
 use testutils::TestRepo;
 
@@ -197,3 +200,52 @@ fn test_transform_descendants_new_parents_map() {
     assert_eq!(new_commit_e.parent_ids(), vec![new_commit_d.id().clone()]);
     assert_eq!(new_commit_f.parent_ids(), vec![new_commit_b.id().clone()]);
 }
+
+// Rebase a long linear chain of commits. The rebased commits should be written
+// to the backend in a single batch.
+#[test]
+fn test_transform_descendants_batched_writes() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
+
+    let mut tx = repo.start_transaction();
+    let mut graph_builder = CommitGraphBuilder::new(tx.repo_mut());
+    let commit_a = graph_builder.initial_commit();
+    let mut commits = vec![commit_a.clone()];
+    for _ in 0..100 {
+        let commit = graph_builder.commit_with_parents(&[commits.last().unwrap()]);
+        commits.push(commit);
+    }
+
+    let (old_write_count, old_batch_write_count) = test_backend.commit_write_counts();
+    let old_flush_count = repo.store().write_batch_flush_count();
+    tx.repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    let num_rebased = tx.repo_mut().rebase_descendants().unwrap();
+    assert_eq!(num_rebased, 100);
+
+    // Only the rewritten root commit is written individually
+    let (new_write_count, new_batch_write_count) = test_backend.commit_write_counts();
+    assert_eq!(new_write_count - old_write_count, 1);
+    assert_eq!(new_batch_write_count - old_batch_write_count, 1);
+    assert_eq!(repo.store().write_batch_flush_count() - old_flush_count, 1);
+
+    // All rebased commits have been written to the backend
+    let [head_id] = tx.repo().view().heads().iter().collect_array().unwrap();
+    let mut commit_id = head_id.clone();
+    for _ in 0..100 {
+        let commit = repo
+            .store()
+            .backend()
+            .read_commit(&commit_id)
+            .block_on()
+            .unwrap();
+        commit_id = commit.parents[0].clone();
+    }
+    let new_commit_a = repo.store().get_commit(&commit_id).unwrap();
+    assert_eq!(new_commit_a.description(), "rewritten");
+}
@@ -0,0 +1,128 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use jj_lib::config::ConfigLayer;
+use jj_lib::config::ConfigSource;
+use jj_lib::config::ConfigValue;
+use jj_lib::config::StackedConfig;
+use jj_lib::retrying_backend::RetryableErrorKind;
+use jj_lib::settings::UserSettings;
+use jj_lib::settings_schema::validate_unknown_keys;
+use jj_lib::settings_schema::UnknownKeyKind;
+use jj_lib::settings_schema::SETTINGS;
+
+fn config_with_user_layer(text: &str) -> StackedConfig {
+    let mut config = testutils::base_user_config();
+    config.add_layer(ConfigLayer::parse(ConfigSource::User, text).unwrap());
+    config
+}
+
+fn undecorated(mut value: ConfigValue) -> String {
+    value.decor_mut().clear();
+    value.to_string()
+}
+
+#[test]
+fn test_settings_schema_defaults() {
+    // Nothing is set, so all values come from the schema.
+    let settings = UserSettings::from_config(StackedConfig::empty()).unwrap();
+    assert_eq!(settings.user_name(), "");
+    assert!(settings.split_legacy_bookmark_behavior().unwrap());
+    assert_eq!(settings.split_confirm_threshold().unwrap(), 50);
+    assert_eq!(
+        settings.backend_retry_errors().unwrap(),
+        [RetryableErrorKind::Transient]
+    );
+    assert_eq!(settings.signing_ssh_program().unwrap(), "ssh-keygen");
+
+    // Values set in config take precedence.
+    let config = config_with_user_layer("split.legacy-bookmark-behavior = false");
+    let settings = UserSettings::from_config(config).unwrap();
+    assert!(!settings.split_legacy_bookmark_behavior().unwrap());
+
+    // Values of the wrong type aren't replaced by the default.
+    let config = config_with_user_layer("split.confirm-threshold = 'many'");
+    let settings = UserSettings::from_config(config).unwrap();
+    assert!(settings.split_confirm_threshold().is_err());
+}
+
+#[test]
+fn test_settings_schema_defaults_match_default_config() {
+    let config = StackedConfig::with_defaults();
+    for def in SETTINGS {
+        let Ok(value) = config.get_value(def.name) else {
+            continue;
+        };
+        let default = def
+            .default_value()
+            .unwrap_or_else(|| panic!("{} should have a default", def.name));
+        assert_eq!(undecorated(value), undecorated(default), "{}", def.name);
+    }
+}
+
+#[test]
+fn test_validate_unknown_keys_no_issues() {
+    let config = config_with_user_layer(
+        r#"
+        ui.unknown-cli-setting = true
+        signing.backend = "ssh"
+        signing.backends.custom.program = "custom"
+        split.confirm-threshold = 10
+        "#,
+    );
+    let warnings = validate_unknown_keys(&config);
+    assert!(warnings.is_empty(), "{warnings:?}");
+}
+
+#[test]
+fn test_validate_unknown_keys_typo() {
+    let config = config_with_user_layer(
+        r#"
+        split.legacy-bookmark-behaviour = false
+        backend.retry.max-attempt = 3
+        "#,
+    );
+    let warnings = validate_unknown_keys(&config);
+    assert_eq!(warnings.len(), 2, "{warnings:?}");
+    assert_eq!(warnings[0].key.to_string(), "backend.retry.max-attempt");
+    assert_eq!(warnings[0].source, ConfigSource::User);
+    assert_eq!(
+        warnings[0].kind,
+        UnknownKeyKind::Unknown {
+            similar: vec!["backend.retry.max-attempts".to_owned()]
+        }
+    );
+    assert_eq!(
+        warnings[1].key.to_string(),
+        "split.legacy-bookmark-behaviour"
+    );
+    let UnknownKeyKind::Unknown { similar } = &warnings[1].kind else {
+        panic!("unexpected warning {:?}", warnings[1]);
+    };
+    assert!(similar.contains(&"split.legacy-bookmark-behavior".to_owned()));
+}
+
+#[test]
+fn test_validate_unknown_keys_deprecated() {
+    let config = config_with_user_layer("git.auto-local-branch = true");
+    let warnings = validate_unknown_keys(&config);
+    assert_eq!(warnings.len(), 1, "{warnings:?}");
+    assert_eq!(warnings[0].key.to_string(), "git.auto-local-branch");
+    assert_eq!(
+        warnings[0].kind,
+        UnknownKeyKind::Deprecated {
+            replacement: "git.auto-local-bookmark"
+        }
+    );
+}
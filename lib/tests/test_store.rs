@@ -0,0 +1,244 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use assert_matches::assert_matches;
+use itertools::Itertools as _;
+use jj_lib::backend::BackendError;
+use jj_lib::backend::CommitId;
+use jj_lib::repo::Repo as _;
+use jj_lib::revset::RevsetExpression;
+use jj_lib::rewrite::rebase_commit;
+use jj_lib::rewrite::ShallowBoundaryError;
+use jj_lib::store::find_file_occurrences_in_revset;
+use pollster::FutureExt as _;
+use testutils::create_random_commit;
+use testutils::create_tree;
+use testutils::repo_path;
+use testutils::repo_path_buf;
+use testutils::test_backend::TestBackend;
+use testutils::user_settings;
+use testutils::write_file;
+use testutils::write_random_commit;
+use testutils::TestRepo;
+use testutils::TestRepoBackend;
+
+#[test]
+fn test_find_file_occurrences() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let file_path = repo_path("file");
+    let secret_path = repo_path("dir/secret");
+    let copy_path = repo_path("copy");
+
+    let mut tx = repo.start_transaction();
+    let base_tree = create_tree(repo, &[(file_path, "base")]);
+    let base = tx
+        .repo_mut()
+        .new_commit(vec![repo.store().root_commit_id().clone()], base_tree.id())
+        .write()
+        .unwrap();
+    // The secret is introduced and then carried unchanged through 10
+    // descendants, which only modify another file
+    let mut commits = vec![];
+    let mut parent = base.clone();
+    for i in 0..=10 {
+        let tree = create_tree(
+            repo,
+            &[(secret_path, "secret"), (file_path, &i.to_string())],
+        );
+        parent = tx
+            .repo_mut()
+            .new_commit(vec![parent.id().clone()], tree.id())
+            .write()
+            .unwrap();
+        commits.push(parent.clone());
+    }
+    // The secret is copied to another path
+    let tree = create_tree(
+        repo,
+        &[
+            (secret_path, "secret"),
+            (copy_path, "secret"),
+            (file_path, "copy"),
+        ],
+    );
+    let copy_commit = tx
+        .repo_mut()
+        .new_commit(vec![parent.id().clone()], tree.id())
+        .write()
+        .unwrap();
+    tx.commit("test").unwrap();
+
+    // Load the repo again so the store doesn't have any cached trees
+    let repo = test_repo
+        .env
+        .load_repo_at_head(&user_settings(), test_repo.repo_path());
+    let test_backend: &TestBackend = repo.store().backend_impl().downcast_ref().unwrap();
+    let secret_id = write_file(repo.store(), secret_path, "secret");
+
+    let old_read_count = test_backend.tree_read_count();
+    let expression = RevsetExpression::commit(base.id().clone()).descendants();
+    let occurrences =
+        find_file_occurrences_in_revset(repo.as_ref(), &secret_id, &expression).unwrap();
+    let mut expected = vec![
+        (copy_commit.id().clone(), repo_path_buf("copy")),
+        (copy_commit.id().clone(), repo_path_buf("dir/secret")),
+    ];
+    expected.extend(
+        commits
+            .iter()
+            .rev()
+            .map(|commit| (commit.id().clone(), repo_path_buf("dir/secret"))),
+    );
+    assert_eq!(occurrences, expected);
+    // Each of the 13 root trees is read, but the shared "dir" tree is only
+    // read once
+    assert_eq!(test_backend.tree_read_count() - old_read_count, 14);
+
+    // A file which doesn't appear anywhere
+    let other_id = write_file(repo.store(), file_path, "other");
+    let occurrences = repo
+        .store()
+        .find_file_occurrences(
+            &other_id,
+            [base.id().clone(), copy_commit.id().clone()].into_iter(),
+        )
+        .unwrap();
+    assert_eq!(occurrences, vec![]);
+}
+
+#[test]
+fn test_shallow_boundaries() {
+    let settings = user_settings();
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Simple);
+    let repo = &test_repo.repo;
+    let store = repo.store();
+    let root_commit_id = store.root_commit_id().clone();
+
+    // The parent of the oldest imported commit doesn't exist in the backend
+    let missing_id = CommitId::new(vec![0xab; store.commit_id_length()]);
+    assert_matches!(
+        store.get_commit(&missing_id),
+        Err(BackendError::ObjectNotFound { .. })
+    );
+    store.set_shallow_boundaries([missing_id.clone()]).unwrap();
+    assert_eq!(store.shallow_boundaries(), [missing_id.clone()]);
+
+    let mut tx = repo.start_transaction();
+    let commit = create_random_commit(tx.repo_mut())
+        .set_parents(vec![missing_id.clone()])
+        .write()
+        .unwrap();
+    let other_commit = write_random_commit(tx.repo_mut());
+    let repo = tx.commit("test").unwrap();
+
+    // The boundary is a stub on top of the root commit
+    let boundary = store.get_commit(&missing_id).unwrap();
+    assert!(boundary.is_shallow_boundary());
+    assert!(!commit.is_shallow_boundary());
+    assert_eq!(boundary.parent_ids(), [root_commit_id.clone()]);
+    assert_eq!(boundary.tree_id(), &store.empty_merged_tree_id());
+    let ancestors: Vec<_> = RevsetExpression::commit(commit.id().clone())
+        .ancestors()
+        .evaluate(repo.as_ref())
+        .unwrap()
+        .iter()
+        .try_collect()
+        .unwrap();
+    assert_eq!(
+        ancestors,
+        [commit.id().clone(), missing_id.clone(), root_commit_id]
+    );
+
+    // Rebasing across the boundary fails
+    let mut tx = repo.start_transaction();
+    let result = rebase_commit(
+        tx.repo_mut(),
+        commit.clone(),
+        vec![other_commit.id().clone()],
+    );
+    assert_matches!(
+        result,
+        Err(BackendError::Other(err)) if err.downcast_ref::<ShallowBoundaryError>().is_some()
+    );
+    let result = rebase_commit(
+        tx.repo_mut(),
+        other_commit.clone(),
+        vec![missing_id.clone()],
+    );
+    assert_matches!(
+        result,
+        Err(BackendError::Other(err)) if err.downcast_ref::<ShallowBoundaryError>().is_some()
+    );
+    drop(tx);
+
+    // The boundaries are persisted
+    let repo = test_repo
+        .env
+        .load_repo_at_head(&settings, test_repo.repo_path());
+    assert_eq!(repo.store().shallow_boundaries(), [missing_id.clone()]);
+    assert!(repo
+        .store()
+        .get_commit(&missing_id)
+        .unwrap()
+        .is_shallow_boundary());
+
+    // Clearing the boundaries makes the commit missing again
+    repo.store().set_shallow_boundaries([]).unwrap();
+    assert_matches!(
+        repo.store().get_commit(&missing_id),
+        Err(BackendError::ObjectNotFound { .. })
+    );
+}
+
+#[test]
+fn test_write_batches_are_independent() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+    let store = repo.store();
+
+    let mut tx = repo.start_transaction();
+    let base = write_random_commit(tx.repo_mut());
+    let make_commit = |description: &str| {
+        let mut commit = (**base.store_commit()).clone();
+        commit.description = description.to_owned();
+        commit
+    };
+
+    // Each writer flushes only the commits queued in its own batch
+    let old_flush_count = store.write_batch_flush_count();
+    let mut batch1 = store.begin_write_batch();
+    let mut batch2 = store.begin_write_batch();
+    let commit1 = store
+        .write_commit_batched(&mut batch1, make_commit("1"), None)
+        .block_on()
+        .unwrap();
+    let commit2 = store
+        .write_commit_batched(&mut batch2, make_commit("2"), None)
+        .block_on()
+        .unwrap();
+    batch2.finish().unwrap();
+    assert_matches!(store.backend().read_commit(commit2.id()).block_on(), Ok(_));
+    assert_matches!(
+        store.backend().read_commit(commit1.id()).block_on(),
+        Err(BackendError::ObjectNotFound { .. })
+    );
+    // The queued commit can still be read through the store
+    let commit1_from_store = store.get_commit(commit1.id()).unwrap();
+    assert_eq!(commit1_from_store.description(), "1");
+
+    batch1.finish().unwrap();
+    assert_matches!(store.backend().read_commit(commit1.id()).block_on(), Ok(_));
+    assert_eq!(store.write_batch_flush_count() - old_flush_count, 2);
+}
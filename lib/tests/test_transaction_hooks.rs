@@ -0,0 +1,165 @@
+// Copyright 2025 The Jujutsu Authors
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use assert_matches::assert_matches;
+use jj_lib::op_store::OperationId;
+use jj_lib::operation::Operation;
+use jj_lib::repo::MutableRepo;
+use jj_lib::repo::RepoLoaderError;
+use jj_lib::transaction::HookVeto;
+use jj_lib::transaction::TransactionCommitError;
+use jj_lib::transaction::TransactionHooks;
+use testutils::write_random_commit;
+use testutils::TestRepo;
+
+/// Hooks recording the transactions they see, optionally vetoing all of them.
+#[derive(Default)]
+struct RecordingHooks {
+    veto: bool,
+    descriptions: Mutex<Vec<String>>,
+    op_ids: Mutex<Vec<OperationId>>,
+}
+
+impl TransactionHooks for RecordingHooks {
+    fn pre_commit(&self, _repo: &MutableRepo, description: &str) -> Result<(), HookVeto> {
+        self.descriptions
+            .lock()
+            .unwrap()
+            .push(description.to_owned());
+        if self.veto {
+            Err(HookVeto::new("not allowed"))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn post_commit(&self, op: &Operation) {
+        self.op_ids.lock().unwrap().push(op.id().clone());
+    }
+}
+
+#[test]
+fn test_transaction_hooks_veto() {
+    let test_repo = TestRepo::init();
+    let hooks = Arc::new(RecordingHooks {
+        veto: true,
+        ..Default::default()
+    });
+    let mut loader = test_repo.repo.loader().clone();
+    loader.set_transaction_hooks(hooks.clone());
+    let repo = loader.load_at_head().unwrap();
+    let operations_dir = test_repo.repo_path().join("op_store").join("operations");
+    let num_ops_before = std::fs::read_dir(&operations_dir).unwrap().count();
+
+    let mut tx = repo.start_transaction();
+    write_random_commit(tx.repo_mut());
+    assert_matches!(
+        tx.commit("test"),
+        Err(TransactionCommitError::HookVeto(veto)) if veto.message == "not allowed"
+    );
+    assert_eq!(*hooks.descriptions.lock().unwrap(), ["test"]);
+    assert!(hooks.op_ids.lock().unwrap().is_empty());
+
+    // Nothing was written or published
+    let head_repo = loader.load_at_head().unwrap();
+    assert_eq!(head_repo.op_id(), repo.op_id());
+    assert_eq!(
+        std::fs::read_dir(&operations_dir).unwrap().count(),
+        num_ops_before
+    );
+}
+
+#[test]
+fn test_transaction_hooks_post_commit() {
+    let test_repo = TestRepo::init();
+    let hooks = Arc::new(RecordingHooks::default());
+    let mut loader = test_repo.repo.loader().clone();
+    loader.set_transaction_hooks(hooks.clone());
+    let repo = loader.load_at_head().unwrap();
+
+    let mut tx = repo.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let repo = tx.commit("first").unwrap();
+    // Hooks are inherited by repos created from the committed transaction
+    let mut tx = repo.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let repo2 = tx.commit("second").unwrap();
+
+    assert_eq!(*hooks.descriptions.lock().unwrap(), ["first", "second"]);
+    assert_eq!(
+        *hooks.op_ids.lock().unwrap(),
+        [repo.op_id().clone(), repo2.op_id().clone()]
+    );
+}
+
+#[test]
+fn test_transaction_hooks_post_commit_after_publish() {
+    let test_repo = TestRepo::init();
+    let hooks = Arc::new(RecordingHooks::default());
+    let mut loader = test_repo.repo.loader().clone();
+    loader.set_transaction_hooks(hooks.clone());
+    let repo = loader.load_at_head().unwrap();
+
+    let mut tx = repo.start_transaction();
+    write_random_commit(tx.repo_mut());
+    let unpublished_op = tx.write("test").unwrap();
+    assert_eq!(*hooks.descriptions.lock().unwrap(), ["test"]);
+    assert!(hooks.op_ids.lock().unwrap().is_empty());
+
+    let repo = unpublished_op.publish().unwrap();
+    assert_eq!(*hooks.op_ids.lock().unwrap(), [repo.op_id().clone()]);
+    assert_eq!(loader.load_at_head().unwrap().op_id(), repo.op_id());
+}
+
+#[test]
+fn test_transaction_hooks_reconcile_divergent_operations() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Create divergent operations without hooks
+    let mut tx1 = repo.start_transaction();
+    write_random_commit(tx1.repo_mut());
+    tx1.commit("op 1").unwrap();
+    let mut tx2 = repo.start_transaction();
+    write_random_commit(tx2.repo_mut());
+    tx2.commit("op 2").unwrap();
+
+    // The merge operation can be vetoed
+    let vetoing_hooks = Arc::new(RecordingHooks {
+        veto: true,
+        ..Default::default()
+    });
+    let mut loader = repo.loader().clone();
+    loader.set_transaction_hooks(vetoing_hooks.clone());
+    assert_matches!(
+        loader.load_at_head(),
+        Err(RepoLoaderError::TransactionCommit(
+            TransactionCommitError::HookVeto(_)
+        ))
+    );
+    assert_eq!(
+        *vetoing_hooks.descriptions.lock().unwrap(),
+        ["reconcile divergent operations"]
+    );
+
+    // The merge operation is observed by the post-commit hook
+    let hooks = Arc::new(RecordingHooks::default());
+    loader.set_transaction_hooks(hooks.clone());
+    let merged_repo = loader.load_at_head().unwrap();
+    assert_eq!(merged_repo.operation().parent_ids().len(), 2);
+    assert_eq!(*hooks.op_ids.lock().unwrap(), [merged_repo.op_id().clone()]);
+}
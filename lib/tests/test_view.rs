@@ -14,23 +14,41 @@
 
 use std::collections::BTreeMap;
 
+use assert_matches::assert_matches;
+use itertools::Itertools as _;
+use jj_lib::backend::ChangeId;
+use jj_lib::backend::MillisSinceEpoch;
+use jj_lib::git;
+use jj_lib::git::GitRefKind;
 use jj_lib::op_store::BookmarkTarget;
 use jj_lib::op_store::RefTarget;
 use jj_lib::op_store::RemoteRef;
 use jj_lib::op_store::RemoteRefState;
+use jj_lib::op_store::WorkspaceMetadata;
 use jj_lib::ref_name::RefName;
 use jj_lib::ref_name::RemoteName;
 use jj_lib::ref_name::RemoteRefSymbol;
 use jj_lib::ref_name::WorkspaceNameBuf;
+use jj_lib::refs::RefMergeConflict;
+use jj_lib::refs::RefMergeConflictKind;
+use jj_lib::repo::NoteTooLargeError;
+use jj_lib::repo::ReadonlyRepo;
 use jj_lib::repo::Repo as _;
+use jj_lib::repo::MAX_NOTE_SIZE;
+use jj_lib::revset::ResolvedRevsetExpression;
+use jj_lib::transaction::Transaction;
+use jj_lib::transaction::REF_MERGE_CONFLICTS_TAG;
 use maplit::btreemap;
 use maplit::hashset;
 use test_case::test_case;
 use testutils::commit_transactions;
 use testutils::create_random_commit;
+use testutils::create_random_tree;
+use testutils::user_settings;
 use testutils::write_random_commit;
 use testutils::CommitGraphBuilder;
 use testutils::TestRepo;
+use testutils::TestRepoBackend;
 
 fn remote_symbol<'a, N, M>(name: &'a N, remote: &'a M) -> RemoteRefSymbol<'a>
 where
@@ -91,6 +109,201 @@ fn test_heads_merge() {
     assert_eq!(*repo.view().heads(), hashset! {merge.id().clone()});
 }
 
+#[test]
+fn test_pinned_commits() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit_a = write_random_commit(tx.repo_mut());
+    let repo = tx.commit("test").unwrap();
+
+    // Rewrite A, which hides the predecessor
+    let mut tx = repo.start_transaction();
+    let commit_a2 = tx
+        .repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.repo_mut().rebase_descendants().unwrap();
+    let repo = tx.commit("test").unwrap();
+    let visible_ids = |repo: &ReadonlyRepo| {
+        ResolvedRevsetExpression::all()
+            .evaluate(repo)
+            .unwrap()
+            .iter()
+            .map(Result::unwrap)
+            .collect_vec()
+    };
+    assert!(commit_a.is_hidden(repo.as_ref()));
+    assert!(!visible_ids(&repo).contains(commit_a.id()));
+
+    // The pinned predecessor is visible, but isn't a head
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().pin_commit(commit_a.id().clone());
+    let repo = tx.commit("test").unwrap();
+    assert!(repo.view().is_pinned(commit_a.id()));
+    assert_eq!(*repo.view().heads(), hashset! {commit_a2.id().clone()});
+    assert!(!commit_a.is_hidden(repo.as_ref()));
+    assert!(visible_ids(&repo).contains(commit_a.id()));
+
+    // Unpinning hides the predecessor again
+    let mut tx = repo.start_transaction();
+    tx.repo_mut().unpin_commit(commit_a.id());
+    let repo = tx.commit("test").unwrap();
+    assert!(repo.view().pinned_commits().is_empty());
+    assert!(commit_a.is_hidden(repo.as_ref()));
+    assert!(!visible_ids(&repo).contains(commit_a.id()));
+}
+
+#[test]
+fn test_merge_views_pinned_commits() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit_unchanged = write_random_commit(tx.repo_mut());
+    let commit_unpin_tx1 = write_random_commit(tx.repo_mut());
+    let commit_pin_tx1 = write_random_commit(tx.repo_mut());
+    let commit_pin_tx2 = write_random_commit(tx.repo_mut());
+    tx.repo_mut().pin_commit(commit_unchanged.id().clone());
+    tx.repo_mut().pin_commit(commit_unpin_tx1.id().clone());
+    let repo = tx.commit("test").unwrap();
+
+    let mut tx1 = repo.start_transaction();
+    tx1.repo_mut().unpin_commit(commit_unpin_tx1.id());
+    tx1.repo_mut().pin_commit(commit_pin_tx1.id().clone());
+
+    let mut tx2 = repo.start_transaction();
+    tx2.repo_mut().pin_commit(commit_pin_tx2.id().clone());
+
+    let repo = commit_transactions(vec![tx1, tx2]);
+    assert_eq!(
+        repo.view().pinned_commits().iter().collect_vec(),
+        [
+            commit_unchanged.id(),
+            commit_pin_tx1.id(),
+            commit_pin_tx2.id()
+        ]
+        .into_iter()
+        .sorted()
+        .collect_vec()
+    );
+}
+
+#[test]
+fn test_notes() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let commit_a = write_random_commit(tx.repo_mut());
+    tx.repo_mut()
+        .set_note(commit_a.change_id().clone(), "ci: passed".to_owned())
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+    assert_eq!(
+        repo.view().get_note(commit_a.change_id()),
+        Some("ci: passed")
+    );
+
+    // The note is kept when the commit is rewritten
+    let mut tx = repo.start_transaction();
+    let commit_a2 = tx
+        .repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_description("rewritten")
+        .write()
+        .unwrap();
+    tx.repo_mut().rebase_descendants().unwrap();
+    let repo = tx.commit("test").unwrap();
+    assert_eq!(
+        repo.view().get_note(commit_a2.change_id()),
+        Some("ci: passed")
+    );
+
+    // Notes are size-capped
+    let mut tx = repo.start_transaction();
+    assert_matches!(
+        tx.repo_mut()
+            .set_note(commit_a2.change_id().clone(), "x".repeat(MAX_NOTE_SIZE + 1)),
+        Err(NoteTooLargeError { .. })
+    );
+    tx.repo_mut().remove_note(commit_a2.change_id());
+    let repo = tx.commit("test").unwrap();
+    assert!(repo.view().notes().is_empty());
+}
+
+#[test]
+fn test_merge_views_notes() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let change_id = |hex: &str| ChangeId::from_hex(hex);
+    let mut tx = repo.start_transaction();
+    for (hex, text) in [
+        ("aaaa", "unchanged"),
+        ("bbbb", "base"),
+        ("cccc", "base"),
+        ("dddd", "base"),
+        ("eeee", "base"),
+    ] {
+        tx.repo_mut()
+            .set_note(change_id(hex), text.to_owned())
+            .unwrap();
+    }
+    let repo = tx.commit("test").unwrap();
+
+    let mut tx1 = repo.start_transaction();
+    let mut tx2 = repo.start_transaction();
+    // Changed on one side
+    tx1.repo_mut()
+        .set_note(change_id("bbbb"), "changed by tx1".to_owned())
+        .unwrap();
+    // Changed differently on both sides
+    tx1.repo_mut()
+        .set_note(change_id("cccc"), "changed by tx1".to_owned())
+        .unwrap();
+    tx2.repo_mut()
+        .set_note(change_id("cccc"), "changed by tx2".to_owned())
+        .unwrap();
+    // Changed the same way on both sides
+    tx1.repo_mut()
+        .set_note(change_id("dddd"), "changed by both".to_owned())
+        .unwrap();
+    tx2.repo_mut()
+        .set_note(change_id("dddd"), "changed by both".to_owned())
+        .unwrap();
+    // Removed on one side, changed on the other
+    tx1.repo_mut().remove_note(&change_id("eeee"));
+    tx2.repo_mut()
+        .set_note(change_id("eeee"), "changed by tx2".to_owned())
+        .unwrap();
+    // Added on one side
+    tx2.repo_mut()
+        .set_note(change_id("ffff"), "added by tx2".to_owned())
+        .unwrap();
+
+    let repo = commit_transactions(vec![tx1, tx2]);
+    assert_eq!(
+        *repo.view().notes(),
+        btreemap! {
+            change_id("aaaa") => "unchanged".to_owned(),
+            change_id("bbbb") => "changed by tx1".to_owned(),
+            change_id("cccc") => "<<<<<<< Conflicting note\n\
+                                  changed by tx1\n\
+                                  =======\n\
+                                  changed by tx2\n\
+                                  >>>>>>> Conflicting note ends\n"
+                .to_owned(),
+            change_id("dddd") => "changed by both".to_owned(),
+            change_id("eeee") => "changed by tx2".to_owned(),
+            change_id("ffff") => "added by tx2".to_owned(),
+        }
+    );
+}
+
 #[test]
 fn test_merge_views_heads() {
     // Tests merging of the view's heads (by performing divergent operations).
@@ -211,6 +424,101 @@ fn test_merge_views_checkout() {
     assert_eq!(repo.view().get_wc_commit_id(&ws7_name), Some(commit3.id()));
 }
 
+#[test_case(false ; "tx1 first")]
+#[test_case(true ; "tx2 first")]
+fn test_merge_views_workspace_metadata(tx2_first: bool) {
+    // Tests merging of the workspace metadata (by performing divergent
+    // operations).
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    // Workspace 1 gets a different description in both transactions.
+    // Workspace 2 gets a description only in tx2.
+    // Workspace 3 gets deleted in tx1 and described in tx2.
+    let metadata = |description: &str| WorkspaceMetadata {
+        description: Some(description.to_owned()),
+        created_op_id: Some(repo.op_id().clone()),
+    };
+    let mut initial_tx = repo.start_transaction();
+    let commit1 = write_random_commit(initial_tx.repo_mut());
+    let ws1_name = WorkspaceNameBuf::from("ws1");
+    let ws2_name = WorkspaceNameBuf::from("ws2");
+    let ws3_name = WorkspaceNameBuf::from("ws3");
+    for name in [&ws1_name, &ws2_name, &ws3_name] {
+        initial_tx
+            .repo_mut()
+            .set_wc_commit(name.clone(), commit1.id().clone())
+            .unwrap();
+    }
+    initial_tx
+        .repo_mut()
+        .set_workspace_metadata(ws1_name.clone(), metadata("initial"));
+    let repo = initial_tx.commit("test").unwrap();
+
+    let mut tx1 = repo.start_transaction();
+    tx1.repo_mut()
+        .set_workspace_metadata(ws1_name.clone(), metadata("perf experiments"));
+    tx1.repo_mut().remove_wc_commit(&ws3_name).unwrap();
+
+    let mut tx2 = repo.start_transaction();
+    tx2.repo_mut()
+        .set_workspace_metadata(ws1_name.clone(), metadata("CI bot checkout"));
+    tx2.repo_mut()
+        .set_workspace_metadata(ws2_name.clone(), metadata("scratch"));
+    tx2.repo_mut()
+        .set_workspace_metadata(ws3_name.clone(), metadata("gone"));
+
+    let txs = if tx2_first {
+        vec![tx2, tx1]
+    } else {
+        vec![tx1, tx2]
+    };
+    let repo = commit_transactions(txs);
+
+    // The greater metadata wins regardless of the merge order.
+    assert_eq!(
+        repo.view().workspace_metadata(&ws1_name),
+        Some(&metadata("perf experiments"))
+    );
+    assert_eq!(
+        repo.view().workspace_metadata(&ws2_name),
+        Some(&metadata("scratch"))
+    );
+    assert_eq!(repo.view().workspace_metadata(&ws3_name), None);
+    assert_eq!(repo.view().get_wc_commit_id(&ws3_name), None);
+}
+
+#[test]
+fn test_rename_workspace_preserves_metadata() {
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let metadata = WorkspaceMetadata {
+        description: Some("perf experiments".to_owned()),
+        created_op_id: None,
+    };
+    let mut tx = repo.start_transaction();
+    let commit1 = write_random_commit(tx.repo_mut());
+    let old_name = WorkspaceNameBuf::from("old");
+    let new_name = WorkspaceNameBuf::from("new");
+    tx.repo_mut()
+        .set_wc_commit(old_name.clone(), commit1.id().clone())
+        .unwrap();
+    tx.repo_mut()
+        .set_workspace_metadata(old_name.clone(), metadata.clone());
+    tx.repo_mut()
+        .rename_workspace(&old_name, new_name.clone())
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    assert_eq!(repo.view().workspace_metadata(&old_name), None);
+    assert_eq!(repo.view().workspace_metadata(&new_name), Some(&metadata));
+    assert_eq!(
+        repo.view().workspaces().collect_vec(),
+        [(new_name.as_ref(), Some(&metadata))]
+    );
+}
+
 #[test]
 fn test_merge_views_bookmarks() {
     // Tests merging of bookmarks (by performing concurrent operations). See
@@ -231,10 +539,12 @@ fn test_merge_views_bookmarks() {
         target: RefTarget::normal(main_bookmark_alternate_tx0.id().clone()),
         state: RemoteRefState::Tracked,
     };
-    mut_repo.set_local_bookmark_target(
-        "main".as_ref(),
-        RefTarget::normal(main_bookmark_local_tx0.id().clone()),
-    );
+    mut_repo
+        .set_local_bookmark_target(
+            "main".as_ref(),
+            RefTarget::normal(main_bookmark_local_tx0.id().clone()),
+        )
+        .unwrap();
     mut_repo.set_remote_bookmark(
         remote_symbol("main", "origin"),
         main_bookmark_origin_tx0_remote_ref,
@@ -244,23 +554,29 @@ fn test_merge_views_bookmarks() {
         main_bookmark_alternate_tx0_remote_ref.clone(),
     );
     let feature_bookmark_local_tx0 = write_random_commit(mut_repo);
-    mut_repo.set_local_bookmark_target(
-        "feature".as_ref(),
-        RefTarget::normal(feature_bookmark_local_tx0.id().clone()),
-    );
+    mut_repo
+        .set_local_bookmark_target(
+            "feature".as_ref(),
+            RefTarget::normal(feature_bookmark_local_tx0.id().clone()),
+        )
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction();
     let main_bookmark_local_tx1 = write_random_commit(tx1.repo_mut());
-    tx1.repo_mut().set_local_bookmark_target(
-        "main".as_ref(),
-        RefTarget::normal(main_bookmark_local_tx1.id().clone()),
-    );
+    tx1.repo_mut()
+        .set_local_bookmark_target(
+            "main".as_ref(),
+            RefTarget::normal(main_bookmark_local_tx1.id().clone()),
+        )
+        .unwrap();
     let feature_bookmark_tx1 = write_random_commit(tx1.repo_mut());
-    tx1.repo_mut().set_local_bookmark_target(
-        "feature".as_ref(),
-        RefTarget::normal(feature_bookmark_tx1.id().clone()),
-    );
+    tx1.repo_mut()
+        .set_local_bookmark_target(
+            "feature".as_ref(),
+            RefTarget::normal(feature_bookmark_tx1.id().clone()),
+        )
+        .unwrap();
 
     let mut tx2 = repo.start_transaction();
     let main_bookmark_local_tx2 = write_random_commit(tx2.repo_mut());
@@ -269,10 +585,12 @@ fn test_merge_views_bookmarks() {
         target: RefTarget::normal(main_bookmark_origin_tx2.id().clone()),
         state: RemoteRefState::Tracked,
     };
-    tx2.repo_mut().set_local_bookmark_target(
-        "main".as_ref(),
-        RefTarget::normal(main_bookmark_local_tx2.id().clone()),
-    );
+    tx2.repo_mut()
+        .set_local_bookmark_target(
+            "main".as_ref(),
+            RefTarget::normal(main_bookmark_local_tx2.id().clone()),
+        )
+        .unwrap();
     tx2.repo_mut().set_remote_bookmark(
         remote_symbol("main", "origin"),
         main_bookmark_origin_tx2_remote_ref.clone(),
@@ -319,23 +637,30 @@ fn test_merge_views_tags() {
     let mut tx = repo.start_transaction();
     let mut_repo = tx.repo_mut();
     let v1_tx0 = write_random_commit(mut_repo);
-    mut_repo.set_tag_target("v1.0".as_ref(), RefTarget::normal(v1_tx0.id().clone()));
+    mut_repo
+        .set_tag_target("v1.0".as_ref(), RefTarget::normal(v1_tx0.id().clone()))
+        .unwrap();
     let v2_tx0 = write_random_commit(mut_repo);
-    mut_repo.set_tag_target("v2.0".as_ref(), RefTarget::normal(v2_tx0.id().clone()));
+    mut_repo
+        .set_tag_target("v2.0".as_ref(), RefTarget::normal(v2_tx0.id().clone()))
+        .unwrap();
     let repo = tx.commit("test").unwrap();
 
     let mut tx1 = repo.start_transaction();
     let v1_tx1 = write_random_commit(tx1.repo_mut());
     tx1.repo_mut()
-        .set_tag_target("v1.0".as_ref(), RefTarget::normal(v1_tx1.id().clone()));
+        .set_tag_target("v1.0".as_ref(), RefTarget::normal(v1_tx1.id().clone()))
+        .unwrap();
     let v2_tx1 = write_random_commit(tx1.repo_mut());
     tx1.repo_mut()
-        .set_tag_target("v2.0".as_ref(), RefTarget::normal(v2_tx1.id().clone()));
+        .set_tag_target("v2.0".as_ref(), RefTarget::normal(v2_tx1.id().clone()))
+        .unwrap();
 
     let mut tx2 = repo.start_transaction();
     let v1_tx2 = write_random_commit(tx2.repo_mut());
     tx2.repo_mut()
-        .set_tag_target("v1.0".as_ref(), RefTarget::normal(v1_tx2.id().clone()));
+        .set_tag_target("v1.0".as_ref(), RefTarget::normal(v1_tx2.id().clone()))
+        .unwrap();
 
     let repo = commit_transactions(vec![tx1, tx2]);
     let expected_v1 = RefTarget::from_legacy_form(
@@ -352,6 +677,114 @@ fn test_merge_views_tags() {
     );
 }
 
+#[test]
+fn test_merge_views_ref_conflicts() {
+    // Tests that bookmarks conflicted by concurrent operations are reported
+    // once, by the operation which reconciles them.
+    let test_repo = TestRepo::init();
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    let commit_a = write_random_commit(mut_repo);
+    let commit_b = write_random_commit(mut_repo);
+    let commit_c = write_random_commit(mut_repo);
+    mut_repo
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_a.id().clone()))
+        .unwrap();
+    mut_repo
+        .set_local_bookmark_target(
+            "stale".as_ref(),
+            RefTarget::from_legacy_form([], [commit_a.id().clone(), commit_b.id().clone()]),
+        )
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    // Both operations move "main". The pre-existing conflict of "stale" is
+    // updated on both sides, but isn't newly created by the merge.
+    let mut tx1 = repo.start_transaction();
+    tx1.repo_mut()
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_b.id().clone()))
+        .unwrap();
+    tx1.repo_mut()
+        .set_local_bookmark_target("stale".as_ref(), RefTarget::normal(commit_c.id().clone()))
+        .unwrap();
+    let op1 = tx1.commit("tx1").unwrap().operation().clone();
+    let mut tx2 = repo.start_transaction();
+    tx2.repo_mut()
+        .set_local_bookmark_target("main".as_ref(), RefTarget::normal(commit_c.id().clone()))
+        .unwrap();
+    tx2.repo_mut()
+        .set_local_bookmark_target(
+            "stale".as_ref(),
+            RefTarget::from_legacy_form(
+                [commit_a.id().clone()],
+                [commit_b.id().clone(), commit_c.id().clone()],
+            ),
+        )
+        .unwrap();
+    let op2 = tx2.commit("tx2").unwrap().operation().clone();
+
+    let (merged_op, conflicts) = repo
+        .loader()
+        .merge_operations(vec![op1, op2], None)
+        .unwrap();
+    assert_eq!(
+        conflicts,
+        vec![RefMergeConflict {
+            kind: RefMergeConflictKind::LocalBookmark,
+            name: "main".into(),
+            base: RefTarget::normal(commit_a.id().clone()),
+            sides: vec![
+                RefTarget::normal(commit_b.id().clone()),
+                RefTarget::normal(commit_c.id().clone()),
+            ],
+        }]
+    );
+    assert_eq!(
+        merged_op.metadata().tags.get(REF_MERGE_CONFLICTS_TAG),
+        Some(&"1 bookmark conflict".to_owned())
+    );
+
+    // Reconciling the op heads records the summary in the merge operation.
+    let repo = repo.reload_at_head().unwrap();
+    assert!(repo
+        .view()
+        .get_local_bookmark("main".as_ref())
+        .has_conflict());
+    assert_eq!(
+        repo.operation()
+            .metadata()
+            .tags
+            .get(REF_MERGE_CONFLICTS_TAG),
+        Some(&"1 bookmark conflict".to_owned())
+    );
+
+    // The conflict isn't reported again by later operations.
+    let (_, conflicts) = repo
+        .loader()
+        .merge_operations(vec![repo.operation().clone()], None)
+        .unwrap();
+    assert_eq!(conflicts, vec![]);
+    let mut tx = repo.start_transaction();
+    write_random_commit(tx.repo_mut());
+    assert_eq!(tx.repo().ref_merge_conflicts(), &[]);
+    let repo = tx.commit("test").unwrap();
+    assert!(!repo
+        .operation()
+        .metadata()
+        .tags
+        .contains_key(REF_MERGE_CONFLICTS_TAG));
+    let repo = repo.reload_at_head().unwrap();
+    assert_eq!(
+        repo.view()
+            .get_local_bookmark("main".as_ref())
+            .added_ids()
+            .count(),
+        2
+    );
+}
+
 #[test]
 fn test_merge_views_git_refs() {
     // Tests merging of git refs (by performing divergent operations). See
@@ -441,6 +874,60 @@ fn test_merge_views_git_heads() {
     assert_eq!(repo.view().git_head(), &expected_git_head);
 }
 
+#[test]
+fn test_git_refs_without_git_backend() {
+    // The view of a repo which isn't backed by Git can still contain Git refs,
+    // e.g. if they were imported by a build with the "git" feature. Reading
+    // them doesn't need the Git backend. (Integration tests are always built
+    // with the "git" feature, so the stub module is only covered by the
+    // no-git CI build.)
+    let test_repo = TestRepo::init_with_backend(TestRepoBackend::Simple);
+    let repo = &test_repo.repo;
+
+    let mut tx = repo.start_transaction();
+    let mut_repo = tx.repo_mut();
+    let commit = write_random_commit(mut_repo);
+    for name in [
+        "refs/heads/main",
+        "refs/remotes/origin/main",
+        "refs/tags/v1",
+    ] {
+        mut_repo.set_git_ref_target(name.as_ref(), RefTarget::normal(commit.id().clone()));
+    }
+    mut_repo.set_git_head_target(RefTarget::normal(commit.id().clone()));
+    tx.commit("test").unwrap();
+
+    let repo = test_repo
+        .env
+        .load_repo_at_head(&user_settings(), test_repo.repo_path());
+    assert!(git::get_git_backend(repo.store()).is_err());
+    assert_eq!(
+        repo.view().git_head(),
+        &RefTarget::normal(commit.id().clone())
+    );
+    let parsed_refs = repo
+        .view()
+        .git_refs()
+        .keys()
+        .map(|name| {
+            let (kind, symbol) = git::parse_git_ref(name).unwrap();
+            (
+                kind,
+                symbol.to_string(),
+                git::is_special_git_remote(symbol.remote),
+            )
+        })
+        .collect_vec();
+    assert_eq!(
+        parsed_refs,
+        [
+            (GitRefKind::Bookmark, "main@git".to_owned(), true),
+            (GitRefKind::Bookmark, "main@origin".to_owned(), false),
+            (GitRefKind::Tag, "v1@git".to_owned(), true),
+        ]
+    );
+}
+
 #[test]
 fn test_merge_views_divergent() {
     // We start with just commit A. Operation 1 rewrites it as A2. Operation 2
@@ -478,6 +965,94 @@ fn test_merge_views_divergent() {
     );
 }
 
+#[test_case(false ; "tx1 first")]
+#[test_case(true ; "tx2 first")]
+fn test_merge_views_identical_rewrites(tx2_first: bool) {
+    // We start with commit A and its child B. Operations 1 and 2 both make the
+    // same change to A, which only differs in the committer timestamp, and
+    // rebase B onto it.
+    let test_repo = TestRepo::init();
+
+    let mut tx = test_repo.repo.start_transaction();
+    let commit_a = write_random_commit(tx.repo_mut());
+    let commit_b = create_random_commit(tx.repo_mut())
+        .set_parents(vec![commit_a.id().clone()])
+        .write()
+        .unwrap();
+    let repo = tx.commit("test").unwrap();
+
+    let rewrite_a = |tx: &mut Transaction, timestamp: i64| {
+        let mut committer = commit_a.committer().clone();
+        committer.timestamp.timestamp = MillisSinceEpoch(timestamp);
+        let commit = tx
+            .repo_mut()
+            .rewrite_commit(&commit_a)
+            .set_description("A2")
+            .set_committer(committer)
+            .write()
+            .unwrap();
+        tx.repo_mut().rebase_descendants().unwrap();
+        commit
+    };
+    let mut tx1 = repo.start_transaction();
+    let commit_a2 = rewrite_a(&mut tx1, 1000);
+    let mut tx2 = repo.start_transaction();
+    let commit_a3 = rewrite_a(&mut tx2, 2000);
+    assert_ne!(commit_a2.id(), commit_a3.id());
+
+    let repo = if tx2_first {
+        commit_transactions(vec![tx2, tx1])
+    } else {
+        commit_transactions(vec![tx1, tx2])
+    };
+
+    // Only one of the rewrites should survive, with B on top of it.
+    let survivor_id = commit_a2.id().min(commit_a3.id());
+    let heads = repo.view().heads();
+    assert_eq!(heads.len(), 1);
+    let b2_id = heads.iter().next().unwrap();
+    let commit_b2 = repo.store().get_commit(b2_id).unwrap();
+    assert_eq!(commit_b2.change_id(), commit_b.change_id());
+    assert_eq!(commit_b2.parent_ids(), vec![survivor_id.clone()]);
+}
+
+#[test]
+fn test_merge_views_divergent_trees() {
+    // We start with just commit A. Operations 1 and 2 both rewrite it with the
+    // same description, but different contents.
+    let test_repo = TestRepo::init();
+
+    let mut tx = test_repo.repo.start_transaction();
+    let commit_a = write_random_commit(tx.repo_mut());
+    let repo = tx.commit("test").unwrap();
+
+    let mut tx1 = repo.start_transaction();
+    let commit_a2 = tx1
+        .repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_tree_id(create_random_tree(&repo))
+        .write()
+        .unwrap();
+    tx1.repo_mut().rebase_descendants().unwrap();
+
+    let mut tx2 = repo.start_transaction();
+    let commit_a3 = tx2
+        .repo_mut()
+        .rewrite_commit(&commit_a)
+        .set_tree_id(create_random_tree(&repo))
+        .write()
+        .unwrap();
+    tx2.repo_mut().rebase_descendants().unwrap();
+
+    let repo = commit_transactions(vec![tx1, tx2]);
+
+    // A2 and A3 should be divergent heads.
+    assert_eq!(
+        *repo.view().heads(),
+        hashset! {commit_a2.id().clone(), commit_a3.id().clone()}
+    );
+}
+
 #[test_case(false ; "rewrite first")]
 #[test_case(true ; "add child first")]
 fn test_merge_views_child_on_rewritten(child_first: bool) {
@@ -12,15 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::slice;
+use std::sync::Arc;
 use std::thread;
 
 use assert_matches::assert_matches;
+use jj_lib::commit::Commit;
+use jj_lib::op_store::WorkspaceMetadata;
 use jj_lib::ref_name::WorkspaceNameBuf;
+use jj_lib::repo::ReadonlyRepo;
 use jj_lib::repo::Repo as _;
+use jj_lib::repo_path::RepoPath;
+use jj_lib::working_copy::CheckoutOptions;
+use jj_lib::working_copy::SnapshotOptions;
 use jj_lib::workspace::default_working_copy_factories;
 use jj_lib::workspace::default_working_copy_factory;
+use jj_lib::workspace::update_stale_working_copy;
+use jj_lib::workspace::StaleLocalChanges;
+use jj_lib::workspace::StaleRecoveryOutcome;
+use jj_lib::workspace::UpdateStaleError;
 use jj_lib::workspace::Workspace;
 use jj_lib::workspace::WorkspaceLoadError;
+use testutils::create_tree;
+use testutils::repo_path;
+use testutils::write_working_copy_file;
 use testutils::TestEnvironment;
 use testutils::TestWorkspace;
 
@@ -67,6 +82,13 @@ fn test_init_additional_workspace() {
         wc_commit.parent_ids(),
         vec![repo.store().root_commit_id().clone()]
     );
+    assert_eq!(
+        repo.view().workspace_metadata(&ws2_name),
+        Some(&WorkspaceMetadata {
+            description: None,
+            created_op_id: Some(test_workspace.repo.op_id().clone()),
+        })
+    );
     assert_eq!(ws2.workspace_name(), &ws2_name);
     assert_eq!(
         *ws2.repo_path(),
@@ -105,3 +127,225 @@ fn test_sendable() {
     .join()
     .unwrap();
 }
+
+/// Checks out a new commit with the given files in the workspace, and returns
+/// the commit and the repo at the operation it was checked out at.
+fn check_out_new_commit(
+    test_workspace: &mut TestWorkspace,
+    files: &[(&RepoPath, &str)],
+) -> (Arc<ReadonlyRepo>, Commit) {
+    let repo = &test_workspace.repo;
+    let workspace_name = test_workspace.workspace.workspace_name().to_owned();
+    let tree = create_tree(repo, files);
+    let mut tx = repo.start_transaction();
+    let commit = tx
+        .repo_mut()
+        .new_commit(vec![repo.store().root_commit_id().clone()], tree.id())
+        .write()
+        .unwrap();
+    tx.repo_mut().edit(workspace_name, &commit).unwrap();
+    tx.repo_mut().rebase_descendants().unwrap();
+    let repo = tx.commit("test").unwrap();
+    test_workspace
+        .workspace
+        .check_out(
+            repo.op_id().clone(),
+            None,
+            &commit,
+            &CheckoutOptions::empty_for_test(),
+        )
+        .unwrap();
+    (repo, commit)
+}
+
+/// Rewrites the working-copy commit with the given files without updating the
+/// working copy, as another process would.
+fn rewrite_wc_commit_concurrently(
+    repo: &Arc<ReadonlyRepo>,
+    commit: &Commit,
+    files: &[(&RepoPath, &str)],
+) -> Arc<ReadonlyRepo> {
+    let tree = create_tree(repo, files);
+    let mut tx = repo.start_transaction();
+    tx.repo_mut()
+        .rewrite_commit(commit)
+        .set_tree_id(tree.id())
+        .write()
+        .unwrap();
+    tx.repo_mut().rebase_descendants().unwrap();
+    tx.commit("test").unwrap()
+}
+
+fn update_stale(
+    test_workspace: &mut TestWorkspace,
+    repo: &Arc<ReadonlyRepo>,
+) -> (Arc<ReadonlyRepo>, Commit, StaleLocalChanges) {
+    let outcome = update_stale_working_copy(
+        &mut test_workspace.workspace,
+        repo,
+        &SnapshotOptions::empty_for_test(),
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    let StaleRecoveryOutcome::Updated {
+        repo,
+        wc_commit,
+        local_changes,
+        ..
+    } = outcome
+    else {
+        panic!("unexpected outcome: {outcome:?}");
+    };
+    let workspace_name = test_workspace.workspace.workspace_name();
+    assert_eq!(
+        repo.view().get_wc_commit_id(workspace_name),
+        Some(wc_commit.id())
+    );
+    assert_eq!(
+        test_workspace.workspace.working_copy().operation_id(),
+        repo.op_id()
+    );
+    (repo, wc_commit, local_changes)
+}
+
+#[test]
+fn test_update_stale_working_copy_carries_local_changes() {
+    let mut test_workspace = TestWorkspace::init();
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+    let file = repo_path("file");
+    let other = repo_path("other");
+    let local = repo_path("local");
+    let (repo, commit) = check_out_new_commit(&mut test_workspace, &[(file, "base\n")]);
+
+    let new_repo =
+        rewrite_wc_commit_concurrently(&repo, &commit, &[(file, "base\n"), (other, "other\n")]);
+    write_working_copy_file(&workspace_root, local, "local\n");
+    let (repo, wc_commit, local_changes) = update_stale(&mut test_workspace, &new_repo);
+    assert_eq!(local_changes, StaleLocalChanges::Carried);
+    assert_ne!(repo.op_id(), new_repo.op_id());
+    assert_eq!(wc_commit.change_id(), commit.change_id());
+    let expected_tree = create_tree(
+        &repo,
+        &[(file, "base\n"), (other, "other\n"), (local, "local\n")],
+    );
+    assert_eq!(*wc_commit.tree_id(), expected_tree.id());
+    assert_eq!(
+        std::fs::read_to_string(other.to_fs_path_unchecked(&workspace_root)).unwrap(),
+        "other\n"
+    );
+
+    // The working copy is fresh now
+    let outcome = update_stale_working_copy(
+        &mut test_workspace.workspace,
+        &repo,
+        &SnapshotOptions::empty_for_test(),
+        &CheckoutOptions::empty_for_test(),
+    )
+    .unwrap();
+    assert_matches!(outcome, StaleRecoveryOutcome::NotStale);
+}
+
+#[test]
+fn test_update_stale_working_copy_without_local_changes() {
+    let mut test_workspace = TestWorkspace::init();
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+    let file = repo_path("file");
+    let (repo, commit) = check_out_new_commit(&mut test_workspace, &[(file, "base\n")]);
+
+    let new_repo = rewrite_wc_commit_concurrently(&repo, &commit, &[(file, "other\n")]);
+    let (repo, wc_commit, local_changes) = update_stale(&mut test_workspace, &new_repo);
+    assert_eq!(local_changes, StaleLocalChanges::None);
+    assert_eq!(repo.op_id(), new_repo.op_id());
+    assert_eq!(
+        wc_commit.id(),
+        new_repo
+            .view()
+            .get_wc_commit_id(test_workspace.workspace.workspace_name())
+            .unwrap()
+    );
+    assert_eq!(
+        std::fs::read_to_string(file.to_fs_path_unchecked(&workspace_root)).unwrap(),
+        "other\n"
+    );
+}
+
+#[test]
+fn test_update_stale_working_copy_conflicting_local_changes() {
+    let mut test_workspace = TestWorkspace::init();
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+    let file = repo_path("file");
+    let (repo, commit) = check_out_new_commit(&mut test_workspace, &[(file, "base\n")]);
+
+    let new_repo = rewrite_wc_commit_concurrently(&repo, &commit, &[(file, "other\n")]);
+    write_working_copy_file(&workspace_root, file, "local\n");
+    let (repo, wc_commit, local_changes) = update_stale(&mut test_workspace, &new_repo);
+    assert_eq!(local_changes, StaleLocalChanges::RecoveryCommit);
+
+    // The local edit survives in a recovery commit on top of the rewritten
+    // working-copy commit
+    let rewritten_commit_id = new_repo
+        .view()
+        .get_wc_commit_id(test_workspace.workspace.workspace_name())
+        .unwrap();
+    assert_eq!(wc_commit.parent_ids(), slice::from_ref(rewritten_commit_id));
+    let expected_tree = create_tree(&repo, &[(file, "local\n")]);
+    assert_eq!(*wc_commit.tree_id(), expected_tree.id());
+    assert_eq!(
+        std::fs::read_to_string(file.to_fs_path_unchecked(&workspace_root)).unwrap(),
+        "local\n"
+    );
+}
+
+#[test]
+fn test_update_stale_working_copy_discards_contained_changes() {
+    let mut test_workspace = TestWorkspace::init();
+    let workspace_root = test_workspace.workspace.workspace_root().to_owned();
+    let file = repo_path("file");
+    let (repo, commit) = check_out_new_commit(&mut test_workspace, &[(file, "base\n")]);
+
+    // The other process made the same change
+    let new_repo = rewrite_wc_commit_concurrently(&repo, &commit, &[(file, "local\n")]);
+    write_working_copy_file(&workspace_root, file, "local\n");
+    let (repo, _wc_commit, local_changes) = update_stale(&mut test_workspace, &new_repo);
+    assert_eq!(local_changes, StaleLocalChanges::Discarded);
+    assert_eq!(repo.op_id(), new_repo.op_id());
+}
+
+#[test]
+fn test_update_stale_working_copy_sibling_operation() {
+    let mut test_workspace = TestWorkspace::init();
+    let workspace_name = test_workspace.workspace.workspace_name().to_owned();
+    let file = repo_path("file");
+    let (repo, commit) = check_out_new_commit(&mut test_workspace, &[(file, "base\n")]);
+
+    // The working copy is updated to one operation, and the repo is loaded at
+    // a concurrent one
+    let wc_repo = rewrite_wc_commit_concurrently(&repo, &commit, &[(file, "wc\n")]);
+    let wc_commit_id = wc_repo.view().get_wc_commit_id(&workspace_name).unwrap();
+    let wc_commit = wc_repo.store().get_commit(wc_commit_id).unwrap();
+    test_workspace
+        .workspace
+        .check_out(
+            wc_repo.op_id().clone(),
+            Some(commit.tree_id()),
+            &wc_commit,
+            &CheckoutOptions::empty_for_test(),
+        )
+        .unwrap();
+    let sibling_repo = rewrite_wc_commit_concurrently(&repo, &commit, &[(file, "sibling\n")]);
+    let result = update_stale_working_copy(
+        &mut test_workspace.workspace,
+        &sibling_repo,
+        &SnapshotOptions::empty_for_test(),
+        &CheckoutOptions::empty_for_test(),
+    );
+    assert_matches!(
+        result,
+        Err(UpdateStaleError::SiblingOperation { repo_op_id, wc_op_id })
+            if repo_op_id == *sibling_repo.op_id() && wc_op_id == *wc_repo.op_id()
+    );
+    assert_eq!(
+        test_workspace.workspace.working_copy().operation_id(),
+        wc_repo.op_id()
+    );
+}
@@ -57,6 +57,7 @@ use jj_lib::repo_path::RepoPathComponent;
 use jj_lib::rewrite::RebaseOptions;
 use jj_lib::rewrite::RebasedCommit;
 use jj_lib::secret_backend::SecretBackend;
+use jj_lib::settings::SeededChangeIdGenerator;
 use jj_lib::settings::UserSettings;
 use jj_lib::signing::Signer;
 use jj_lib::simple_backend::SimpleBackend;
@@ -122,6 +123,12 @@ pub fn user_settings() -> UserSettings {
     UserSettings::from_config(base_user_config()).unwrap()
 }
 
+/// Returns settings which generate the same sequence of change ids for the
+/// same `seed`.
+pub fn user_settings_with_change_id_seed(seed: u64) -> UserSettings {
+    user_settings().with_change_id_generator(Arc::new(SeededChangeIdGenerator::new(seed)))
+}
+
 /// Panic if `CI` environment variable is set to a non-empty value
 ///
 /// Most CI environments set this variable automatically. See e.g.
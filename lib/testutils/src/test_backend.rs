@@ -29,6 +29,7 @@ use std::time::SystemTime;
 use async_trait::async_trait;
 use futures::stream;
 use futures::stream::BoxStream;
+use itertools::Itertools as _;
 use jj_lib::backend::make_root_commit;
 use jj_lib::backend::Backend;
 use jj_lib::backend::BackendError;
@@ -44,6 +45,7 @@ use jj_lib::backend::CopyRecord;
 use jj_lib::backend::FileId;
 use jj_lib::backend::SecureSig;
 use jj_lib::backend::SigningFn;
+use jj_lib::backend::StorageStats;
 use jj_lib::backend::SymlinkId;
 use jj_lib::backend::Tree;
 use jj_lib::backend::TreeId;
@@ -72,6 +74,28 @@ pub struct TestBackendData {
     symlinks: HashMap<RepoPathBuf, HashMap<SymlinkId, String>>,
     conflicts: HashMap<RepoPathBuf, HashMap<ConflictId, Conflict>>,
     copies: HashMap<CopyId, CopyHistory>,
+    /// Copy records written by `write_copy_records()`, keyed by target commit.
+    copy_records: HashMap<CommitId, Vec<CopyRecord>>,
+    /// Number of `write_commit()` calls.
+    commit_write_count: usize,
+    /// Number of `write_commits_batch()` calls.
+    commit_batch_write_count: usize,
+    /// Number of `read_tree()` calls.
+    tree_read_count: usize,
+    /// `prefetch_files()` and `read_file()` calls in the order they were made.
+    file_accesses: Vec<FileAccess>,
+    /// Number of upcoming object reads and writes that should fail with a
+    /// transient error.
+    pending_transient_failures: usize,
+}
+
+/// A file access recorded by `TestBackend`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FileAccess {
+    /// A batch of files passed to `prefetch_files()`.
+    Prefetch(Vec<(RepoPathBuf, FileId)>),
+    /// A single `read_file()` call.
+    Read(RepoPathBuf, FileId),
 }
 
 #[derive(Clone, Default)]
@@ -146,6 +170,44 @@ impl TestBackend {
     pub fn remove_commit_unchecked(&self, id: &CommitId) {
         self.locked_data().commits.remove(id);
     }
+
+    /// Returns the number of individual and batched commit writes made to
+    /// this backend.
+    pub fn commit_write_counts(&self) -> (usize, usize) {
+        let data = self.locked_data();
+        (data.commit_write_count, data.commit_batch_write_count)
+    }
+
+    /// Returns the number of tree reads made from this backend.
+    pub fn tree_read_count(&self) -> usize {
+        self.locked_data().tree_read_count
+    }
+
+    /// Returns the file prefetches and reads made from this backend, in the
+    /// order they were made.
+    pub fn file_accesses(&self) -> Vec<FileAccess> {
+        self.locked_data().file_accesses.clone()
+    }
+
+    /// Makes the next `count` file, tree, and commit reads and writes fail
+    /// with `BackendError::Transient`.
+    pub fn inject_transient_failures(&self, count: usize) {
+        self.locked_data().pending_transient_failures += count;
+    }
+
+    /// Returns the number of injected failures that haven't been returned yet.
+    pub fn pending_transient_failures(&self) -> usize {
+        self.locked_data().pending_transient_failures
+    }
+
+    fn take_transient_failure(&self) -> BackendResult<()> {
+        let mut data = self.locked_data();
+        if data.pending_transient_failures > 0 {
+            data.pending_transient_failures -= 1;
+            return Err(BackendError::Transient("Injected failure".into()));
+        }
+        Ok(())
+    }
 }
 
 impl Debug for TestBackend {
@@ -194,8 +256,11 @@ impl Backend for TestBackend {
         path: &RepoPath,
         id: &FileId,
     ) -> BackendResult<Pin<Box<dyn AsyncRead>>> {
-        match self
-            .locked_data()
+        self.take_transient_failure()?;
+        let mut data = self.locked_data();
+        data.file_accesses
+            .push(FileAccess::Read(path.to_owned(), id.clone()));
+        match data
             .files
             .get(path)
             .and_then(|items| items.get(id))
@@ -210,11 +275,19 @@ impl Backend for TestBackend {
         }
     }
 
+    async fn prefetch_files(&self, files: &[(RepoPathBuf, FileId)]) -> BackendResult<()> {
+        self.locked_data()
+            .file_accesses
+            .push(FileAccess::Prefetch(files.to_vec()));
+        Ok(())
+    }
+
     async fn write_file(
         &self,
         path: &RepoPath,
         contents: &mut (dyn AsyncRead + Send + Unpin),
     ) -> BackendResult<FileId> {
+        self.take_transient_failure()?;
         let mut bytes = Vec::new();
         contents.read_to_end(&mut bytes).await.unwrap();
         let id = FileId::new(get_hash(&bytes));
@@ -295,6 +368,8 @@ impl Backend for TestBackend {
     }
 
     async fn read_tree(&self, path: &RepoPath, id: &TreeId) -> BackendResult<Tree> {
+        self.take_transient_failure()?;
+        self.locked_data().tree_read_count += 1;
         if id == &self.empty_tree_id {
             return Ok(Tree::default());
         }
@@ -315,6 +390,7 @@ impl Backend for TestBackend {
     }
 
     async fn write_tree(&self, path: &RepoPath, contents: &Tree) -> BackendResult<TreeId> {
+        self.take_transient_failure()?;
         let id = TreeId::new(get_hash(contents));
         self.locked_data()
             .trees
@@ -352,6 +428,7 @@ impl Backend for TestBackend {
     }
 
     async fn read_commit(&self, id: &CommitId) -> BackendResult<Commit> {
+        self.take_transient_failure()?;
         if id == &self.root_commit_id {
             return Ok(make_root_commit(
                 self.root_change_id.clone(),
@@ -374,6 +451,7 @@ impl Backend for TestBackend {
         mut sign_with: Option<&mut SigningFn>,
     ) -> BackendResult<(CommitId, Commit)> {
         assert!(contents.secure_sig.is_none(), "commit.secure_sig was set");
+        self.take_transient_failure()?;
 
         if let Some(sign) = &mut sign_with {
             let data = format!("{contents:?}").into_bytes();
@@ -382,19 +460,85 @@ impl Backend for TestBackend {
         }
 
         let id = CommitId::new(get_hash(&contents));
-        self.locked_data()
-            .commits
-            .insert(id.clone(), contents.clone());
+        let mut data = self.locked_data();
+        data.commit_write_count += 1;
+        data.commits.insert(id.clone(), contents.clone());
         Ok((id, contents))
     }
 
+    fn compute_commit_id(&self, contents: &mut Commit) -> Option<CommitId> {
+        assert!(contents.secure_sig.is_none(), "commit.secure_sig was set");
+        Some(CommitId::new(get_hash(contents)))
+    }
+
+    async fn write_commits_batch(&self, commits: Vec<(CommitId, Commit)>) -> BackendResult<()> {
+        self.take_transient_failure()?;
+        let mut data = self.locked_data();
+        data.commit_batch_write_count += 1;
+        for (id, contents) in commits {
+            assert_eq!(id, CommitId::new(get_hash(&contents)));
+            data.commits.insert(id, contents);
+        }
+        Ok(())
+    }
+
     fn get_copy_records(
         &self,
-        _paths: Option<&[RepoPathBuf]>,
-        _root: &CommitId,
-        _head: &CommitId,
+        paths: Option<&[RepoPathBuf]>,
+        root: &CommitId,
+        head: &CommitId,
     ) -> BackendResult<BoxStream<BackendResult<CopyRecord>>> {
-        Ok(Box::pin(stream::empty()))
+        let data = self.locked_data();
+        // Walk back from the head without following the parents of the root,
+        // which is enough for the linear histories used in tests.
+        let commit_ids = topo_order_reverse(
+            [head],
+            |id| *id,
+            |id| {
+                if *id == root {
+                    &[][..]
+                } else {
+                    data.commits
+                        .get(*id)
+                        .map(|commit| commit.parents.as_slice())
+                        .unwrap_or_default()
+                }
+            },
+        );
+        let records = commit_ids
+            .into_iter()
+            .filter(|id| *id != root)
+            .filter_map(|id| data.copy_records.get(id))
+            .flatten()
+            .filter(|record| paths.is_none_or(|paths| paths.contains(&record.target)))
+            .map(|record| Ok(record.clone()))
+            .collect_vec();
+        Ok(Box::pin(stream::iter(records)))
+    }
+
+    fn write_copy_records(&self, records: &[CopyRecord]) -> BackendResult<()> {
+        let mut data = self.locked_data();
+        for record in records {
+            data.copy_records
+                .entry(record.target_commit.clone())
+                .or_default()
+                .push(record.clone());
+        }
+        Ok(())
+    }
+
+    fn storage_stats(&self) -> BackendResult<Option<StorageStats>> {
+        let data = self.locked_data();
+        let num_objects = data.commits.len()
+            + data.trees.values().map(HashMap::len).sum::<usize>()
+            + data.files.values().map(HashMap::len).sum::<usize>()
+            + data.symlinks.values().map(HashMap::len).sum::<usize>()
+            + data.conflicts.values().map(HashMap::len).sum::<usize>()
+            + data.copies.len();
+        Ok(Some(StorageStats {
+            num_objects: Some(num_objects.try_into().unwrap()),
+            size_bytes: None,
+        }))
     }
 
     fn gc(&self, _index: &dyn Index, _keep_newer: SystemTime) -> BackendResult<()> {